@@ -0,0 +1,11 @@
+use std::process::Command;
+
+fn main() {
+    let git_hash = Command::new("git").args(["rev-parse", "--short", "HEAD"]).output().ok()
+        .filter(|o| o.status.success())
+        .and_then(|o| String::from_utf8(o.stdout).ok())
+        .map(|h| h.trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=MITHRIL_GIT_HASH={}", git_hash);
+    println!("cargo:rerun-if-changed=.git/HEAD");
+}
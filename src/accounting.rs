@@ -0,0 +1,195 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const SECS_PER_DAY : u64 = 86_400;
+/// Header of the daily export, the columns pool frontends show per worker and day
+pub const EXPORT_HEADER : &str = "date,pool,valid_shares,invalid_shares,stale_shares,total_hashes";
+/// Difficulties of this many recent jobs are kept to account late share results
+const MAX_JOBS : usize = 32;
+
+/// `[accounting]` section, the share log is only written if a file is set
+#[derive(Clone, Debug)]
+pub struct AccountingConfig {
+    /// every share result is appended to this file (CSV)
+    pub share_log: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShareResult {
+    Accepted,
+    Rejected,
+    /// rejected because the job was no longer valid
+    Stale,
+}
+
+impl ShareResult {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ShareResult::Accepted => "accepted",
+            ShareResult::Rejected => "rejected",
+            ShareResult::Stale => "stale",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ShareResult> {
+        match name {
+            "accepted" => Some(ShareResult::Accepted),
+            "rejected" => Some(ShareResult::Rejected),
+            "stale" => Some(ShareResult::Stale),
+            _ => None
+        }
+    }
+}
+
+/// One line of the share log: `<unix time>,<pool>,<difficulty>,<result>`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShareEntry {
+    pub time: u64,
+    pub pool_address: String,
+    /// difficulty of the job the share was found for, 0 if the job was unknown
+    pub difficulty: u64,
+    pub result: ShareResult,
+}
+
+impl ShareEntry {
+    pub fn to_line(&self) -> String {
+        format!("{},{},{},{}", self.time, self.pool_address, self.difficulty, self.result.name())
+    }
+
+    pub fn parse(line: &str) -> Option<ShareEntry> {
+        let parts : Vec<&str> = line.trim().split(',').collect();
+        if parts.len() != 4 {
+            return None;
+        }
+        Some(ShareEntry{
+            time: parts[0].parse().ok()?,
+            pool_address: parts[1].to_string(),
+            difficulty: parts[2].parse().ok()?,
+            result: ShareResult::from_name(parts[3])?
+        })
+    }
+}
+
+/// Appends the result of every share answered by the pool to the share log
+pub struct ShareLog {
+    file: File,
+    pool_address: String,
+    /// job id and difficulty of the recent jobs, oldest first
+    jobs: VecDeque<(String, u64)>,
+}
+
+impl ShareLog {
+    pub fn open(path: &Path) -> io::Result<ShareLog> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(ShareLog{file, pool_address: String::new(), jobs: VecDeque::new()})
+    }
+
+    /// The following shares are accounted to `pool_address`, jobs of the previous connection are forgotten
+    pub fn connected(&mut self, pool_address: &str) {
+        self.pool_address = pool_address.to_string();
+        self.jobs.clear();
+    }
+
+    pub fn record_job(&mut self, job_id: &str, difficulty: u64) {
+        if self.jobs.len() == MAX_JOBS {
+            self.jobs.pop_front();
+        }
+        self.jobs.push_back((job_id.to_string(), difficulty));
+    }
+
+    pub fn record_share(&mut self, job_id: &str, result: ShareResult) -> io::Result<()> {
+        let difficulty = self.jobs.iter().rev().find(|j| j.0 == job_id).map(|j| j.1).unwrap_or(0);
+        let entry = ShareEntry{time: unix_time(), pool_address: self.pool_address.clone(), difficulty, result};
+        writeln!(self.file, "{}", entry.to_line())
+    }
+}
+
+/// Share results of one UTC day and pool
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DaySummary {
+    /// days since 1970-01-01
+    pub day: u64,
+    pub pool_address: String,
+    pub valid_shares: u64,
+    pub invalid_shares: u64,
+    pub stale_shares: u64,
+    /// sum of the difficulties of the valid shares, the hashes the pool credits
+    pub total_hashes: u64,
+}
+
+impl DaySummary {
+    pub fn to_line(&self) -> String {
+        format!("{},{},{},{},{},{}", format_date(self.day), self.pool_address, self.valid_shares,
+            self.invalid_shares, self.stale_shares, self.total_hashes)
+    }
+}
+
+/// Reads the share log, returns the entries and the number of lines that could not be parsed
+pub fn read_log(path: &Path) -> io::Result<(Vec<ShareEntry>, usize)> {
+    let mut entries = Vec::new();
+    let mut invalid = 0;
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match ShareEntry::parse(&line) {
+            Some(entry) => entries.push(entry),
+            None => invalid += 1
+        }
+    }
+    Ok((entries, invalid))
+}
+
+/// Sums the entries per UTC day and pool, ordered by day and pool
+pub fn summarize(entries: &[ShareEntry]) -> Vec<DaySummary> {
+    let mut days : BTreeMap<(u64, String), DaySummary> = BTreeMap::new();
+    for entry in entries {
+        let day = entry.time / SECS_PER_DAY;
+        let summary = days.entry((day, entry.pool_address.clone()))
+            .or_insert_with(|| DaySummary{day, pool_address: entry.pool_address.clone(), ..DaySummary::default()});
+        match entry.result {
+            ShareResult::Accepted => {
+                summary.valid_shares += 1;
+                summary.total_hashes += entry.difficulty;
+            },
+            ShareResult::Rejected => summary.invalid_shares += 1,
+            ShareResult::Stale => summary.stale_shares += 1
+        }
+    }
+    days.into_iter().map(|(_, summary)| summary).collect()
+}
+
+/// The summaries as CSV with `EXPORT_HEADER`
+pub fn render_csv(summaries: &[DaySummary]) -> String {
+    let mut csv = String::from(EXPORT_HEADER);
+    csv.push('\n');
+    for summary in summaries {
+        csv.push_str(&summary.to_line());
+        csv.push('\n');
+    }
+    csv
+}
+
+/// `YYYY-MM-DD` of the day since 1970-01-01
+pub fn format_date(day: u64) -> String {
+    //civil date from days, see http://howardhinnant.github.io/date_algorithms.html
+    let z = day + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", y, m, d)
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
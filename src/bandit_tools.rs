@@ -22,6 +22,11 @@ impl Identifiable for ThreadArm {
     }
 }
 
+/// Largest thread count auto tuning can choose
+pub fn max_threads() -> u64 {
+    (num_cpus::get() * MAX_THREADS_PER_CPU - 1) as u64
+}
+
 pub fn setup_bandit(log_file: String) -> AnnealingSoftmax<ThreadArm> {
     let num_arms = num_cpus::get() * MAX_THREADS_PER_CPU;
     let mut arms = Vec::with_capacity(num_arms);
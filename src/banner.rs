@@ -0,0 +1,59 @@
+use mithril_config::{MithrilConfig};
+
+/// git hash of the build, set by build.rs
+pub const GIT_HASH : &str = env!("MITHRIL_GIT_HASH");
+
+/// Algorithms the hash core implements
+pub const ALGORITHMS : &[&str] = &["randomx", "cryptonight-r", "cryptonight v8", "cryptonight-heavy"];
+
+/// cargo features and build settings of this binary
+pub fn enabled_features() -> Vec<&'static str> {
+    let mut features = Vec::new();
+    if cfg!(feature = "jemalloc") {
+        features.push("jemalloc");
+    }
+    if cfg!(feature = "mimalloc") {
+        features.push("mimalloc");
+    }
+    features.push(if cfg!(debug_assertions) { "debug" } else { "release" });
+    features
+}
+
+/// Summary of the build and the effective configuration, printed on startup
+/// (and the first thing to include in a bug report). The wallet address and
+/// the pool password are not included.
+pub fn render(config: &MithrilConfig) -> String {
+    let pool = &config.pool_conf;
+    let worker = &config.worker_conf;
+
+    let mut lines = Vec::new();
+    lines.push(format!("mithril {} (git {})", env!("CARGO_PKG_VERSION"), GIT_HASH));
+    lines.push(format!("features: {}", enabled_features().join(", ")));
+    lines.push(format!("algorithms: {}", ALGORITHMS.join(", ")));
+    match pool.solo_daemon_address {
+        Some(ref daemon) => lines.push(format!("solo mining on daemon: {}", daemon)),
+        None => lines.push(format!("pool: {}{}", pool.pool_address, if pool.tls { " (tls)" } else { "" }))
+    }
+    for (i, address) in pool.failover_pool_addresses.iter().enumerate() {
+        lines.push(format!("failover pool {}: {}", i + 1, address));
+    }
+    if worker.auto_tune {
+        lines.push(format!("threads: chosen by auto-tuning, new arm every {} minutes", worker.auto_tune_interval_minutes));
+    } else {
+        lines.push(format!("threads: {}", worker.num_threads));
+    }
+    let affinity = if config.hw_conf.skip_efficiency_cores {
+        "performance cores only"
+    } else if config.hw_conf.thread_affinity {
+        "by core type and l3 cache"
+    } else {
+        "none"
+    };
+    lines.push(format!("thread affinity: {}", affinity));
+    lines.push(format!("nonce strategy: {:?}", worker.nonce_strategy));
+    lines.push(format!("background level: {:?}", worker.background_level));
+    lines.push(format!("randomx: {}", if worker.randomx_full_dataset { "fast mode (2080 MiB dataset)" } else { "light mode (256 MiB cache)" }));
+    lines.push(format!("aes: {:?}", config.hw_conf.aes_support));
+    lines.push(format!("donation: {}%", config.donation_conf.percentage));
+    lines.join("\n")
+}
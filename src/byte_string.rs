@@ -1,19 +1,25 @@
 use u64x2::u64x2;
+use hex;
+use hex::{HexError};
 
 //TODO Rename to hex2_u8_array
+/// Decodes the hex string, an odd trailing char is ignored. On an invalid
+/// char the bytes decoded so far are returned.
 pub fn string_to_u8_array(hex: &str) -> Vec<u8> {
-    let mut bytes = Vec::new();
-    for i in 0..(hex.len() / 2) {
-        let res = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16);
-        match res {
-            Ok(v) => bytes.push(v),
-            Err(e) => {
-                error!("Problem with hex: {}", e);
-                return bytes;
-            }
-        };
+    //sliced as bytes, a non ascii char may end at an odd index
+    let chars = hex.as_bytes();
+    let even = &chars[..chars.len() - chars.len() % 2];
+    match hex::decode_bytes(even) {
+        Ok(bytes) => bytes,
+        Err(HexError::InvalidChar{index}) => {
+            error!("Problem with hex: invalid char at {}", index);
+            hex::decode_bytes(&even[..index - index % 2]).unwrap_or_default()
+        },
+        Err(e) => {
+            error!("Problem with hex: {:?}", e);
+            Vec::new()
+        }
     }
-    bytes
 }
 
 /// Converts the first 8 hex chars of the slice to a u32
@@ -51,11 +57,7 @@ pub fn hex2_u64_be(hex: &str) -> u64 {
 }
 
 pub fn u8_array_to_string(a: &[u8]) -> String {
-    let mut str = String::new();
-    for a_i in a {
-        str.push_str(&format!("{:02x}", a_i));
-    }
-    str
+    hex::encode(a)
 }
 
 pub fn u128_to_string(u: u128) -> String {
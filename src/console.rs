@@ -0,0 +1,68 @@
+use std::io;
+use std::io::{BufRead};
+use std::thread;
+use std::sync::mpsc::{channel, Receiver};
+use log::{LevelFilter};
+
+/// Commands typed on stdin while mining
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConsoleCmd {
+    /// history of the pool connection (connects, logins, disconnects, failovers)
+    ConnectionHistory,
+    /// recent jobs and shares
+    Events,
+    /// switches the share submission dry run on or off
+    ToggleDryRun,
+    /// changes the maximum log level
+    LogLevel(LevelFilter),
+    /// switches logging the raw lines exchanged with the pool on or off
+    ToggleTrace,
+    Help
+}
+
+pub const HELP : &str = "commands: h (connection history), e (recent jobs and shares), d (dry run on/off), \
+                         l <off|error|warn|info|debug|trace> (log level), t (raw pool traffic on/off), ? (this help)";
+
+impl ConsoleCmd {
+    pub fn parse(line: &str) -> Option<ConsoleCmd> {
+        let mut words = line.split_whitespace();
+        if let (Some(cmd), Some(level)) = (words.next(), words.next()) {
+            if cmd == "l" || cmd == "log" {
+                return Some(level.parse().map(ConsoleCmd::LogLevel).unwrap_or(ConsoleCmd::Help));
+            }
+        }
+        match line.trim() {
+            "h" | "history" => Some(ConsoleCmd::ConnectionHistory),
+            "e" | "events" => Some(ConsoleCmd::Events),
+            "d" | "dryrun" => Some(ConsoleCmd::ToggleDryRun),
+            "t" | "trace" => Some(ConsoleCmd::ToggleTrace),
+            "?" | "help" => Some(ConsoleCmd::Help),
+            "" => None,
+            _ => Some(ConsoleCmd::Help)
+        }
+    }
+}
+
+/// Reads commands from stdin until it is closed (e.g. when running as a service).
+pub fn start() -> io::Result<Receiver<ConsoleCmd>> {
+    let (tx, rx) = channel();
+    thread::Builder::new().name("console thread".to_string()).spawn(move || {
+        let stdin = io::stdin();
+        for line in stdin.lock().lines() {
+            let cmd = match line {
+                Ok(l) => ConsoleCmd::parse(&l),
+                Err(e) => {
+                    info!("reading console input failed: {}", e);
+                    break;
+                }
+            };
+            if let Some(cmd) = cmd {
+                if tx.send(cmd).is_err() {
+                    break;
+                }
+            }
+        }
+        info!("console thread ended");
+    })?;
+    Ok(rx)
+}
@@ -0,0 +1,317 @@
+use std::cmp;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Sender};
+use std::thread;
+use std::time::{Duration};
+
+/// Process names of the miners that are detected on the machine
+pub const KNOWN_MINERS : &[&str] = &["mithril", "xmrig", "xmr-stak", "xmr-stak-rx", "xmr-stak-cpu", "cpuminer", "minerd"];
+/// How long `CpuShare::start` waits for the first share of the cores
+const FIRST_SHARE_WAIT_MILLIS : u64 = 1000;
+/// Pause before an instance tries to join again after the coordinator went away
+const REJOIN_DELAY_MILLIS : u64 = 500;
+
+/// `[cpu_share]` section, negotiating the cores with other mithril instances is opt-in
+#[derive(Clone, Debug)]
+pub struct CpuShareConfig {
+    pub enabled: bool,
+    /// None: `cpu_share.sock` in the mithril folder of the user
+    pub socket_path: Option<String>,
+}
+
+/// A miner process running beside this one
+#[derive(Debug, Clone, PartialEq)]
+pub struct MinerProcess {
+    pub pid: u32,
+    pub name: String,
+}
+
+/// The known miner a process name belongs to (e.g. `xmrig-notls` is `xmrig`)
+pub fn known_miner(process_name: &str) -> Option<&'static str> {
+    let name = process_name.trim();
+    KNOWN_MINERS.iter().cloned()
+        .filter(|m| name == *m || name.starts_with(&format!("{}-", m)))
+        .max_by_key(|m| m.len())
+}
+
+/// Other miner processes on this machine (Linux only, empty elsewhere)
+#[cfg(target_os = "linux")]
+pub fn other_miners() -> Vec<MinerProcess> {
+    use std::fs;
+
+    let own_pid = ::std::process::id();
+    let entries = match fs::read_dir("/proc") {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new()
+    };
+    let mut miners : Vec<MinerProcess> = entries.filter_map(|e| e.ok())
+        .filter_map(|e| e.file_name().to_str().and_then(|name| name.parse::<u32>().ok()))
+        .filter(|pid| *pid != own_pid)
+        .filter_map(|pid| {
+            let comm = fs::read_to_string(format!("/proc/{}/comm", pid)).ok()?;
+            known_miner(&comm).map(|name| MinerProcess{pid, name: name.to_string()})
+        })
+        .collect();
+    miners.sort_by_key(|m| m.pid);
+    miners
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn other_miners() -> Vec<MinerProcess> {
+    Vec::new()
+}
+
+/// Cores of `total` for member `index` of `members`, the first members get the remainder.
+/// Every member gets at least one core.
+pub fn split_cores(total: usize, members: usize, index: usize) -> usize {
+    if members == 0 {
+        return total;
+    }
+    let extra = if index < total % members { 1 } else { 0 };
+    cmp::max(1, total / members + extra)
+}
+
+/// The thread count reduced to the share of `cores` of `total` cores (rounded, at least one thread)
+pub fn limit_threads(num_threads: u64, cores: usize, total: usize) -> u64 {
+    if cores >= total || total == 0 {
+        return num_threads;
+    }
+    cmp::max(1, (num_threads * cores as u64 + total as u64 / 2) / total as u64)
+}
+
+/// `cores <n> <total>`, the message the coordinator sends to every member
+pub fn parse_cores(line: &str) -> Option<(usize, usize)> {
+    let mut parts = line.split_whitespace();
+    if parts.next() != Some("cores") {
+        return None;
+    }
+    let cores = parts.next()?.parse::<usize>().ok()?;
+    let total = parts.next()?.parse::<usize>().ok()?;
+    Some((cores, total))
+}
+
+pub fn format_cores(cores: usize, total: usize) -> String {
+    format!("cores {} {}", cores, total)
+}
+
+/// The share of the cores this instance mines with. The first instance listens on the
+/// socket and divides the cores among all instances connected to it, the share is
+/// updated in the background when instances come and go.
+pub struct CpuShare {
+    cores: Arc<AtomicUsize>,
+    total: usize,
+}
+
+impl CpuShare {
+    /// Joins (or starts coordinating) the instances on `socket_path`, `total` is the number of cores of the machine
+    pub fn start(socket_path: &Path, total: usize) -> io::Result<CpuShare> {
+        let cores = Arc::new(AtomicUsize::new(total));
+        let (first_share_tx, first_share_rx) = channel();
+        let thread_cores = cores.clone();
+        let thread_path = socket_path.to_path_buf();
+        thread::Builder::new().name("cpu share thread".to_string()).spawn(move || {
+            negotiate(&thread_path, &thread_cores, total, first_share_tx)
+        })?;
+        if first_share_rx.recv_timeout(Duration::from_millis(FIRST_SHARE_WAIT_MILLIS)).is_err() {
+            warn!("no share of the cores negotiated yet, starting with all {} cores", total);
+        }
+        Ok(CpuShare{cores, total})
+    }
+
+    pub fn cores(&self) -> usize {
+        self.cores.load(Ordering::SeqCst)
+    }
+
+    pub fn total(&self) -> usize {
+        self.total
+    }
+}
+
+/// Socket path of the config, `cpu_share.sock` in `folder` if not set
+pub fn socket_path(conf: &CpuShareConfig, folder: &Path) -> PathBuf {
+    match conf.socket_path {
+        Some(ref path) => PathBuf::from(path),
+        None => folder.join("cpu_share.sock")
+    }
+}
+
+#[cfg(unix)]
+fn negotiate(socket_path: &Path, cores: &Arc<AtomicUsize>, total: usize, first_share_tx: Sender<()>) {
+    use std::os::unix::net::{UnixStream};
+
+    let mut first_share_tx = Some(first_share_tx);
+    loop {
+        match UnixStream::connect(socket_path) {
+            Ok(stream) => {
+                match coordination::follow(stream, cores, &mut first_share_tx) {
+                    Ok(()) => info!("cpu share coordinator ended, negotiating again"),
+                    Err(e) => info!("cpu share connection failed ({}), negotiating again", e)
+                }
+            },
+            Err(_) => {
+                match coordination::bind(socket_path) {
+                    Ok(listener) => {
+                        info!("coordinating the cpu share of mithril instances on {}", socket_path.display());
+                        coordination::coordinate(listener, cores, total, &mut first_share_tx);
+                    },
+                    Err(e) => debug!("cpu share socket {} not bound: {}", socket_path.display(), e)
+                }
+            }
+        }
+        //spread the retries of instances that lost the coordinator at the same time
+        thread::sleep(Duration::from_millis(REJOIN_DELAY_MILLIS + u64::from(::std::process::id() % 500)));
+    }
+}
+
+#[cfg(not(unix))]
+fn negotiate(_socket_path: &Path, _cores: &Arc<AtomicUsize>, _total: usize, _first_share_tx: Sender<()>) {
+    warn!("cpu sharing needs unix domain sockets, not supported on this platform");
+}
+
+#[cfg(unix)]
+mod coordination {
+    use std::fs;
+    use std::io;
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::{Path};
+    use std::sync::{Arc, Mutex};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::mpsc::{Sender};
+    use std::thread;
+    use super::{split_cores, parse_cores, format_cores};
+
+    struct Member {
+        id: u64,
+        stream: UnixStream,
+    }
+
+    /// Follows the shares sent by the coordinator until it goes away
+    pub fn follow(stream: UnixStream, cores: &AtomicUsize, first_share_tx: &mut Option<Sender<()>>) -> io::Result<()> {
+        let mut writer = stream.try_clone()?;
+        writeln!(writer, "join {}", ::std::process::id())?;
+        for line in BufReader::new(stream).lines() {
+            let line = line?;
+            match parse_cores(&line) {
+                Some((share, total)) => {
+                    if cores.swap(share, Ordering::SeqCst) != share {
+                        info!("cpu share: mining with {} of {} cores", share, total);
+                    }
+                    if let Some(tx) = first_share_tx.take() {
+                        let _ = tx.send(());
+                    }
+                },
+                None => warn!("unknown cpu share message {}", line)
+            }
+        }
+        Ok(())
+    }
+
+    /// Binds the socket. A file left behind by an ended coordinator (connects are refused) is
+    /// replaced, the socket of a running one is not. Instances check and replace the socket
+    /// under the lock file, so they never remove each other's fresh socket.
+    pub fn bind(socket_path: &Path) -> io::Result<UnixListener> {
+        let _lock = lock(socket_path)?;
+        match UnixListener::bind(socket_path) {
+            Err(ref e) if e.kind() == io::ErrorKind::AddrInUse => {
+                match UnixStream::connect(socket_path) {
+                    Err(ref e) if e.kind() == io::ErrorKind::ConnectionRefused => {
+                        //nobody listens anymore, the socket is stale
+                        fs::remove_file(socket_path)?;
+                        UnixListener::bind(socket_path)
+                    },
+                    Ok(_) => Err(io::Error::new(io::ErrorKind::AddrInUse, "another instance coordinates on the socket")),
+                    Err(e) => Err(e)
+                }
+            },
+            result => result
+        }
+    }
+
+    /// Exclusive lock on `<socket path>.lock`, released when the returned file is closed
+    fn lock(socket_path: &Path) -> io::Result<fs::File> {
+        extern crate libc;
+        use std::os::unix::io::AsRawFd;
+
+        let mut lock_path = socket_path.as_os_str().to_owned();
+        lock_path.push(".lock");
+        let file = fs::OpenOptions::new().create(true).write(true).open(&lock_path)?;
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(file)
+    }
+
+    /// Accepts the other instances and divides the cores, this instance is the first member
+    pub fn coordinate(listener: UnixListener, cores: &Arc<AtomicUsize>, total: usize, first_share_tx: &mut Option<Sender<()>>) {
+        let members : Arc<Mutex<Vec<Member>>> = Arc::new(Mutex::new(Vec::new()));
+        redistribute(&members.lock().expect("cpu share members lock"), cores, total);
+        if let Some(tx) = first_share_tx.take() {
+            let _ = tx.send(());
+        }
+        let mut next_id = 0;
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    warn!("accepting a cpu share member failed: {}", e);
+                    continue;
+                }
+            };
+            next_id += 1;
+            let id = next_id;
+            let thread_members = members.clone();
+            let thread_cores = cores.clone();
+            let spawned = thread::Builder::new().name("cpu share member thread".to_string()).spawn(move || {
+                serve_member(id, stream, &thread_members, &thread_cores, total)
+            });
+            if let Err(e) = spawned {
+                warn!("cpu share member thread not started: {}", e);
+            }
+        }
+    }
+
+    /// Adds the member after its join message and removes it when its connection ends
+    fn serve_member(id: u64, stream: UnixStream, members: &Mutex<Vec<Member>>, cores: &AtomicUsize, total: usize) {
+        let writer = match stream.try_clone() {
+            Ok(writer) => writer,
+            Err(e) => {
+                warn!("cpu share member {} not added: {}", id, e);
+                return;
+            }
+        };
+        let mut lines = BufReader::new(stream).lines();
+        match lines.next() {
+            Some(Ok(ref line)) if line.starts_with("join") => {
+                info!("mithril instance joined the cpu share ({})", line);
+                let mut members = members.lock().expect("cpu share members lock");
+                members.push(Member{id, stream: writer});
+                redistribute(&members, cores, total);
+            },
+            _ => return
+        }
+        //nothing else is expected from members, the loop ends when the instance ends
+        for _ in lines {}
+        info!("mithril instance left the cpu share");
+        let mut members = members.lock().expect("cpu share members lock");
+        members.retain(|m| m.id != id);
+        redistribute(&members, cores, total);
+    }
+
+    fn redistribute(members: &[Member], cores: &AtomicUsize, total: usize) {
+        let count = members.len() + 1;
+        let own = split_cores(total, count, 0);
+        if cores.swap(own, Ordering::SeqCst) != own {
+            info!("cpu share: mining with {} of {} cores ({} instances)", own, total, count);
+        }
+        for (ix, member) in members.iter().enumerate() {
+            let mut stream = &member.stream;
+            if let Err(e) = writeln!(stream, "{}", format_cores(split_cores(total, count, ix + 1), total)) {
+                debug!("sending the cpu share to member {} failed: {}", member.id, e);
+            }
+        }
+    }
+}
@@ -1,12 +1,29 @@
-#![allow(unknown_lints)]
+extern crate byteorder;
 
+use self::byteorder::{ByteOrder, LittleEndian};
 
+/// Keccak state size in bytes
+pub const STATE_BYTES: usize = 200;
+/// Keccak state size in 64 bit lanes
+pub const STATE_WORDS: usize = 25;
+
+/// Reads the little-endian u64 lane `word_ix` from a byte buffer.
 #[inline(always)]
-#[allow(cast_ptr_alignment)]
-pub fn as_u64_array(t: &mut [u8; 200]) -> &mut [u64; 25] {
-    unsafe { &mut *(t as *mut [u8; 200] as *mut [u64; 25]) }
+pub fn read_u64_le(bytes: &[u8], word_ix: usize) -> u64 {
+    LittleEndian::read_u64(&bytes[word_ix*8..(word_ix+1)*8])
+}
+
+/// Decodes the byte view of the keccak state into its lanes. The byte view is
+/// defined as little-endian, independent of the target endianness.
+pub fn state_to_u64(bytes: &[u8; STATE_BYTES]) -> [u64; STATE_WORDS] {
+    let mut words = [0u64; STATE_WORDS];
+    LittleEndian::read_u64_into(bytes, &mut words);
+    words
 }
 
-pub fn as_u8_array(t: &mut [u64; 25]) -> &mut [u8; 200] {
-    unsafe { &mut *(t as *mut [u64; 25] as *mut [u8; 200]) }
+/// Encodes the keccak lanes into the little-endian byte view of the state.
+pub fn state_to_u8(words: &[u64; STATE_WORDS]) -> [u8; STATE_BYTES] {
+    let mut bytes = [0u8; STATE_BYTES];
+    LittleEndian::write_u64_into(words, &mut bytes);
+    bytes
 }
@@ -7,9 +7,13 @@ extern crate byteorder;
 use super::keccak;
 use super::aes::{AES};
 use super::sse;
-use super::common::{as_u64_array, as_u8_array};
+use super::common::{state_to_u64, state_to_u8};
+use super::random_math;
+use super::HashVersion;
 use u64x2::u64x2;
 use std::boxed::Box;
+use std::slice;
+use std::alloc::{alloc_zeroed, Layout};
 use self::groestl::{Digest, Groestl256};
 use super::super::byte_string;
 
@@ -19,31 +23,127 @@ const ITERATIONS : u32 = 524_288;
 const ADDR_MASK : u64 = 0x1F_FFF0;
 const SQRT_CONST : u64 = 1023 << 52;
 
+/// Scratchpad size (in 16 byte chunks) of cryptonight-heavy, 4 MB
+pub const HEAVY_MEM_SIZE : usize = 4_194_304 / 16;
+const HEAVY_ITERATIONS : u32 = 262_144;
+const HEAVY_ADDR_MASK : u64 = 0x3F_FFF0;
+
+/// Scratchpad size, iteration count and address mask of a cryptonight main loop,
+/// `heavy` adds the extra mixing rounds of cryptonight-heavy to the scratchpad init and finalisation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Params {
+    pub mem_size: usize,
+    pub iterations: u32,
+    pub addr_mask: u64,
+    pub heavy: bool,
+}
+
+/// v8 and cryptonight-r
+pub const PARAMS : Params = Params{mem_size: MEM_SIZE, iterations: ITERATIONS, addr_mask: ADDR_MASK, heavy: false};
+pub const HEAVY_PARAMS : Params = Params{mem_size: HEAVY_MEM_SIZE, iterations: HEAVY_ITERATIONS, addr_mask: HEAVY_ADDR_MASK, heavy: true};
+
 /// This is mainly for testing, allocates a new scratchpad on every hash
 pub fn hash_alloc_scratchpad(input: &[u8], aes: &AES) -> String {
     let mut scratchpad : Box<[u64x2; MEM_SIZE]> = box [u64x2(0,0); MEM_SIZE];
     hash(&mut scratchpad, input, aes)
 }
 
-pub fn hash(mut scratchpad : &mut [u64x2; MEM_SIZE], input: &[u8], aes: &AES) -> String {
+/// Allocates a zeroed scratchpad, `None` if the memory is not available
+/// (instead of aborting the process like a failed `box` allocation).
+pub fn try_alloc_scratchpad() -> Option<Box<[u64x2; MEM_SIZE]>> {
+    let layout = Layout::new::<[u64x2; MEM_SIZE]>();
+    unsafe {
+        let ptr = alloc_zeroed(layout) as *mut [u64x2; MEM_SIZE];
+        if ptr.is_null() {
+            None
+        } else {
+            Some(Box::from_raw(ptr))
+        }
+    }
+}
+
+/// Allocates a zeroed cryptonight-heavy scratchpad (`HEAVY_MEM_SIZE` chunks), `None` if the
+/// memory is not available, like `try_alloc_scratchpad`
+pub fn try_alloc_heavy_scratchpad() -> Option<Box<[u64x2]>> {
+    let layout = Layout::new::<[u64x2; HEAVY_MEM_SIZE]>();
+    unsafe {
+        let ptr = alloc_zeroed(layout) as *mut u64x2;
+        if ptr.is_null() {
+            None
+        } else {
+            Some(Box::from_raw(slice::from_raw_parts_mut(ptr, HEAVY_MEM_SIZE)))
+        }
+    }
+}
+
+/// The regular scratchpad of a slice, panics if it does not have `MEM_SIZE` chunks
+fn as_scratchpad(scratchpad: &mut [u64x2]) -> &mut [u64x2; MEM_SIZE] {
+    assert_eq!(scratchpad.len(), MEM_SIZE, "cryptonight scratchpad size");
+    unsafe { &mut *(scratchpad.as_mut_ptr() as *mut [u64x2; MEM_SIZE]) }
+}
+
+pub fn hash(scratchpad : &mut [u64x2; MEM_SIZE], input: &[u8], aes: &AES) -> String {
+    final_hash(&hash_state(scratchpad, input, aes))
+}
+
+/// cryptonight-heavy, `scratchpad` must have `HEAVY_MEM_SIZE` chunks
+pub fn hash_heavy(scratchpad : &mut [u64x2], input: &[u8], aes: &AES) -> String {
+    final_hash(&hash_state_heavy(scratchpad, input, aes))
+}
+
+/// The main loop of a hash version, for cryptonight-r with the random math program of the block height
+#[derive(Debug, Clone, PartialEq)]
+pub enum Variant {
+    V8,
+    R(Vec<random_math::Instruction>),
+    Heavy,
+}
+
+impl Variant {
+    /// Generates the random math program of `height` for cryptonight-r, the height is not used otherwise.
+    /// None for RandomX, which has no cryptonight main loop (see the randomx module).
+    pub fn new(version: HashVersion, height: u64) -> Option<Variant> {
+        match version {
+            HashVersion::V8 => Some(Variant::V8),
+            HashVersion::VersionR => Some(Variant::R(random_math::generate(height))),
+            HashVersion::Heavy => Some(Variant::Heavy),
+            HashVersion::RandomX => None
+        }
+    }
+}
+
+/// Like `hash`, with the main loop of `variant`. `scratchpad` has `HEAVY_MEM_SIZE` chunks for
+/// cryptonight-heavy (see `try_alloc_heavy_scratchpad`) and `MEM_SIZE` chunks otherwise.
+pub fn hash_variant(scratchpad : &mut [u64x2], input: &[u8], aes: &AES, variant: &Variant) -> String {
+    match *variant {
+        Variant::V8 => hash(as_scratchpad(scratchpad), input, aes),
+        Variant::R(ref code) => final_hash(&hash_state_r(as_scratchpad(scratchpad), input, aes, code)),
+        Variant::Heavy => hash_heavy(scratchpad, input, aes)
+    }
+}
+
+/// Computes the keccak state right before the final hash function is applied,
+/// `final_hash` on this state gives the same result as `hash`.
+pub fn hash_state(mut scratchpad : &mut [u64x2; MEM_SIZE], input: &[u8], aes: &AES) -> [u8; 200] {
     //scratchpad init
     let mut state = keccak::keccak(input);
     init_scratchpad(&mut scratchpad, &mut state, aes);
+    let words = state_to_u64(&state);
 
-    let al = u64x2::read(&state[0..16]);
-    let ar = u64x2::read(&state[32..48]);
+    let al = u64x2(words[0], words[1]);
+    let ar = u64x2(words[4], words[5]);
     let mut a = al ^ ar;
 
-    let bl = u64x2::read(&state[16..32]);
-    let br = u64x2::read(&state[48..64]);
+    let bl = u64x2(words[2], words[3]);
+    let br = u64x2(words[6], words[7]);
     let mut b = bl ^ br;
 
-    let cl = u64x2::read(&state[64..80]);
-    let cr = u64x2::read(&state[80..96]);
-    let dl = u64x2::read(&state[96..112]);
-    let mut ax0 = u64x2(a.0, al.1 ^ ar.1);
-    let mut bx0 = u64x2(bl.0 ^ br.0, bl.1 ^ br.1);
-    let mut bx1 = u64x2(cl.0 ^ cr.0, cl.1 ^ cr.1);
+    let cl = u64x2(words[8], words[9]);
+    let cr = u64x2(words[10], words[11]);
+    let dl = u64x2(words[12], words[13]);
+    let mut ax0 = a;
+    let mut bx0 = b;
+    let mut bx1 = cl ^ cr;
     let mut division_res = dl.0;
     let mut sqrt_res = dl.1;
 
@@ -62,40 +162,126 @@ pub fn hash(mut scratchpad : &mut [u64x2; MEM_SIZE], input: &[u8], aes: &AES) ->
         division_res = division_res_n;
         let mem = scratchpad[ix];
 
-        let r = (aes_result.0 as u128) * (mem.0 as u128);
-        let lo = r as u64;
-        let hi = (r >> 64) as u64;
-        let (lo_p, hi_p) = shuffle_1(aes_result.0, &mut scratchpad, ax0, bx0, bx1, lo, hi);
-        let (a0_p, _) = a.1.overflowing_add(lo_p);
-        let (a1_p, _) = a.0.overflowing_add(hi_p);
-        a = u64x2(a0_p, a1_p);
+        let product = aes_result.widening_mul_lo(mem);
+        let (lo_p, hi_p) = shuffle_1(aes_result.0, &mut scratchpad, ax0, bx0, bx1, product.1, product.0);
+        a = a.swap().wrapping_add(u64x2(lo_p, hi_p));
         bx1 = bx0;
         bx0 = aes_result;
 
-        scratchpad[ix].0 = a.1;
-        scratchpad[ix].1 = a.0;
+        scratchpad[ix] = a.swap();
 
-        a = u64x2(a.1 ^ mem.0, a.0 ^ mem.1);
+        a = a.swap() ^ mem;
         ax0 = a;
         b = aes_result;
 
         i += 1;
     }
 
-    let final_result = finalise_scratchpad(scratchpad, &mut state, aes);
+    finish_state(scratchpad, &mut state, aes, &PARAMS)
+}
+
+/// Like `hash_state`, with the cryptonight-r main loop: the integer math of v8 is replaced by
+/// the random math `code`, the shuffles also mix the memory chunks into the AES result.
+pub fn hash_state_r(scratchpad : &mut [u64x2; MEM_SIZE], input: &[u8], aes: &AES, code: &[random_math::Instruction]) -> [u8; 200] {
+    let mut state = keccak::keccak(input);
+    init_scratchpad(scratchpad, &mut state, aes);
+    let words = state_to_u64(&state);
+
+    let mut a = u64x2(words[0], words[1]) ^ u64x2(words[4], words[5]);
+    let mut bx0 = u64x2(words[2], words[3]) ^ u64x2(words[6], words[7]);
+    let mut bx1 = u64x2(words[8], words[9]) ^ u64x2(words[10], words[11]);
+    //r0-r3 are variable, r4-r8 are loaded from the loop variables in every iteration
+    let mut r = [words[12] as u32, (words[12] >> 32) as u32, words[13] as u32, (words[13] >> 32) as u32, 0, 0, 0, 0, 0];
+
+    let mut i = 0;
+    while i < ITERATIONS {
+        let ax0 = a;
+        let mut ix = scratchpad_addr(a.0);
+        let mut c = aes.aes_round(scratchpad[ix], a);
+        c = c ^ shuffle_r(a.0, scratchpad, ax0, bx0, bx1);
+        scratchpad[ix] = bx0 ^ c;
+
+        ix = scratchpad_addr(c.0);
+        let mem = scratchpad[ix];
+        let cl = mem.0 ^ (u64::from(r[0].wrapping_add(r[1])) | (u64::from(r[2].wrapping_add(r[3])) << 32));
+        r[4] = a.0 as u32;
+        r[5] = a.1 as u32;
+        r[6] = bx0.0 as u32;
+        r[7] = bx1.0 as u32;
+        r[8] = bx1.1 as u32;
+        random_math::execute(code, &mut r);
+        a = a ^ u64x2(u64::from(r[2]) | (u64::from(r[3]) << 32), u64::from(r[0]) | (u64::from(r[1]) << 32));
+
+        let product = c.widening_mul_lo(u64x2(cl, 0));
+        c = c ^ shuffle_r(c.0, scratchpad, ax0, bx0, bx1);
+        a = a.wrapping_add(product);
+        scratchpad[ix] = a;
+        a = a ^ u64x2(cl, mem.1);
+
+        bx1 = bx0;
+        bx0 = c;
+        i += 1;
+    }
+
+    finish_state(scratchpad, &mut state, aes, &PARAMS)
+}
+
+/// Like `hash_state`, with the cryptonight-heavy main loop (based on the original cryptonight loop):
+/// after each iteration a signed division on the next chunk gives the next address.
+pub fn hash_state_heavy(scratchpad : &mut [u64x2], input: &[u8], aes: &AES) -> [u8; 200] {
+    assert_eq!(scratchpad.len(), HEAVY_MEM_SIZE, "cryptonight-heavy scratchpad size");
+    let params = HEAVY_PARAMS;
+    let mut state = keccak::keccak(input);
+    explode_scratchpad(scratchpad, &state, aes, &params);
+    let words = state_to_u64(&state);
+
+    let mut a = u64x2(words[0], words[1]) ^ u64x2(words[4], words[5]);
+    let mut b = u64x2(words[2], words[3]) ^ u64x2(words[6], words[7]);
+    let mut idx = a.0;
+
+    let mut i = 0;
+    while i < params.iterations {
+        let mut ix = ((idx & params.addr_mask) >> 4) as usize;
+        let c = aes.aes_round(scratchpad[ix], a);
+        scratchpad[ix] = b ^ c;
+        b = c;
+
+        ix = ((c.0 & params.addr_mask) >> 4) as usize;
+        let mem = scratchpad[ix];
+        let product = u128::from(c.0) * u128::from(mem.0);
+        a = u64x2(a.0.wrapping_add((product >> 64) as u64), a.1.wrapping_add(product as u64));
+        scratchpad[ix] = a;
+        a = a ^ mem;
+
+        ix = ((a.0 & params.addr_mask) >> 4) as usize;
+        let n = scratchpad[ix].0 as i64;
+        let d = scratchpad[ix].1 as u32 as i32;
+        let q = n.wrapping_div(i64::from(d | 5));
+        scratchpad[ix].0 = (n ^ q) as u64;
+        idx = (i64::from(d) ^ q) as u64;
+
+        i += 1;
+    }
+
+    finish_state(scratchpad, &mut state, aes, &params)
+}
+
+/// Writes the scratchpad into the state and applies keccak-f, the state for the final hash function
+fn finish_state(scratchpad: &[u64x2], state: &mut [u8; 200], aes: &AES, params: &Params) -> [u8; 200] {
+    let final_result = implode_scratchpad(scratchpad, state, aes, params);
 
     let mut k = 0;
     while k < 8 {
         let block = final_result[k];
         let offset = 64+(k<<4);
-        block.write(&mut state[offset..offset+16]);
+        state[offset..offset+16].copy_from_slice(&block.to_le_bytes());
         k += 1;
     }
 
-    let state_64 = as_u64_array(&mut state);
-    keccak::keccakf(state_64);
+    let mut state_64 = state_to_u64(state);
+    keccak::keccakf(&mut state_64);
 
-    final_hash(as_u8_array(state_64))
+    state_to_u8(&state_64)
 }
 
 pub fn shuffle_0(ix: u64, scratchpad : &mut [u64x2; MEM_SIZE], ax0: u64x2, bx0: u64x2, bx1: u64x2) {
@@ -112,6 +298,23 @@ pub fn shuffle_0(ix: u64, scratchpad : &mut [u64x2; MEM_SIZE], ax0: u64x2, bx0:
     scratchpad[a3] = v2 + ax0;
 }
 
+/// The shuffle of cryptonight-r, returns the xor of the three chunks read (before they were replaced)
+pub fn shuffle_r(ix: u64, scratchpad : &mut [u64x2; MEM_SIZE], ax0: u64x2, bx0: u64x2, bx1: u64x2) -> u64x2 {
+    let addr = (ix & ADDR_MASK) as usize;
+    let a1 = (addr ^ 0x10) >> 4;
+    let a2 = (addr ^ 0x20) >> 4;
+    let a3 = (addr ^ 0x30) >> 4;
+    let v1 = scratchpad[a1];
+    let v2 = scratchpad[a2];
+    let v3 = scratchpad[a3];
+
+    scratchpad[a1] = v3 + bx1;
+    scratchpad[a2] = v1 + bx0;
+    scratchpad[a3] = v2 + ax0;
+
+    v1 ^ v2 ^ v3
+}
+
 pub fn shuffle_1(ix: u64, scratchpad : &mut [u64x2; MEM_SIZE], ax0: u64x2, bx0: u64x2, bx1: u64x2, lo: u64, hi: u64) -> (u64, u64){
     let addr = (ix & ADDR_MASK) as usize;
     let a1 = (addr ^ 0x10) >> 4;
@@ -158,8 +361,13 @@ pub fn sqrt(v: u64) -> u64 {
     }
 }
 
-fn final_hash(keccak_state: &[u8; 200]) -> String {
-    match keccak_state[0] & 3 {
+/// Index of the final hash function (0: blake, 1: groestl, 2: jh, 3: skein)
+pub fn final_hash_selector(keccak_state: &[u8; 200]) -> usize {
+    (keccak_state[0] & 3) as usize
+}
+
+pub fn final_hash(keccak_state: &[u8; 200]) -> String {
+    match final_hash_selector(keccak_state) {
         0 => {
               let mut result = [0; 32];
               blake::hash(256, keccak_state, &mut result).unwrap();
@@ -186,14 +394,11 @@ fn final_hash(keccak_state: &[u8; 200]) -> String {
 }
 
 pub fn ebyte_mul(a: &u64x2, b: &u64x2) -> u64x2 {
-    let r0 = u128::from(a.0);
-    let r1 = u128::from(b.0);
-    let r = r0 * r1;
-    u64x2((r >> 64) as u64, r as u64)
+    a.widening_mul_lo(*b)
 }
 
 pub fn ebyte_add(a: &u64x2, b: &u64x2) -> u64x2 {
-    u64x2(a.0.wrapping_add(b.0), a.1.wrapping_add(b.1))
+    a.wrapping_add(*b)
 }
 
 pub fn scratchpad_addr(u: u64) -> usize {
@@ -201,7 +406,38 @@ pub fn scratchpad_addr(u: u64) -> usize {
 }
 
 pub fn finalise_scratchpad(scratchpad: &mut [u64x2; MEM_SIZE], keccak_state: &mut [u8; 200], aes: &AES) -> [u64x2; 8] {
-    let t_state = as_u64_array(keccak_state);
+    implode_scratchpad(&scratchpad[..], keccak_state, aes, &PARAMS)
+}
+
+pub fn init_scratchpad(scratchpad : &mut [u64x2; MEM_SIZE], state: &mut [u8; 200], aes: &AES) {
+    explode_scratchpad(&mut scratchpad[..], state, aes, &PARAMS)
+}
+
+/// Ten AES rounds with the expanded `keys`
+fn aes_rounds(aes: &AES, mut block: u64x2, keys: &[u64x2; 10]) -> u64x2 {
+    let mut k = 0;
+    while k < 10 {
+        block = aes.aes_round(block, keys[k]);
+        k += 1;
+    }
+    block
+}
+
+/// The extra mixing step of cryptonight-heavy: every block is xored with its successor,
+/// the last one with the first
+fn mix_and_propagate(blocks: &mut [u64x2; 8]) {
+    let first = blocks[0];
+    let mut i = 0;
+    while i < 7 {
+        blocks[i] = blocks[i] ^ blocks[i+1];
+        i += 1;
+    }
+    blocks[7] = blocks[7] ^ first;
+}
+
+/// Encrypts the scratchpad into 8 blocks, the blocks of the keccak state are the initial value
+pub fn implode_scratchpad(scratchpad: &[u64x2], keccak_state: &[u8; 200], aes: &AES, params: &Params) -> [u64x2; 8] {
+    let t_state = state_to_u64(keccak_state);
     let input0 = u64x2(t_state[4], t_state[5]);
     let input1 = u64x2(t_state[6], t_state[7]);
 
@@ -211,66 +447,85 @@ pub fn finalise_scratchpad(scratchpad: &mut [u64x2; MEM_SIZE], keccak_state: &mu
     let mut i = 0;
     while i < 8 {
         let offset = i*2;
-        let mut block = u64x2(t_state[8+offset], t_state[8+offset+1]);
-        block = scratchpad[i] ^ block;
+        state[i] = u64x2(t_state[8+offset], t_state[8+offset+1]);
+        i += 1;
+    }
+
+    //cryptonight-heavy encrypts the scratchpad twice
+    let passes = if params.heavy { 2 } else { 1 };
+    let mut pass = 0;
+    while pass < passes {
         let mut k = 0;
-        while k < 10 {
-            block = aes.aes_round(block, keys[k]);
-            k += 1;
+        while k < params.mem_size {
+            let mut i = 0;
+            while i < 8 {
+                state[i] = aes_rounds(aes, state[i] ^ scratchpad[k+i], &keys);
+                i += 1;
+            }
+            if params.heavy {
+                mix_and_propagate(&mut state);
+            }
+            k += 8;
         }
-        state[i] = block;
-        i += 1;
+        pass += 1;
     }
 
-    let mut k = 8;
-    while k < MEM_SIZE {
-        let mut i = 0;
-        while i < 8 {
-            let mut block = scratchpad[k+i];
-            block = state[i] ^ block;
-            let mut j = 0;
-            while j < 10 {
-                block = aes.aes_round(block, keys[j]);
-                j += 1;
+    if params.heavy {
+        let mut round = 0;
+        while round < 16 {
+            let mut i = 0;
+            while i < 8 {
+                state[i] = aes_rounds(aes, state[i], &keys);
+                i += 1;
             }
-            state[i] = block;
-            i += 1;
+            mix_and_propagate(&mut state);
+            round += 1;
         }
-        k += 8;
     }
     state
 }
 
-pub fn init_scratchpad(scratchpad : &mut [u64x2; MEM_SIZE], state: &mut [u8; 200], aes: &AES) {
-    let t_state = as_u64_array(state);
+/// Fills the scratchpad from the keccak state: the first 8 chunks are the encrypted blocks of
+/// the state, every following chunk is the encrypted chunk 8 positions before it
+pub fn explode_scratchpad(scratchpad : &mut [u64x2], state: &[u8; 200], aes: &AES, params: &Params) {
+    let t_state = state_to_u64(state);
     let input0 = u64x2(t_state[0], t_state[1]);
     let input1 = u64x2(t_state[2], t_state[3]);
     let keys = aes.gen_round_keys(input0, input1);
 
+    let mut blocks : [u64x2; 8] = [u64x2(0,0); 8];
     let mut i = 0;
     while i < 8 {
         let offset = i*2;
-        let mut block = u64x2(t_state[8+offset], t_state[8+offset+1]);
-        let mut k = 0;
-        while k < 10 {
-            block = aes.aes_round(block, keys[k]);
-            k += 1;
+        blocks[i] = u64x2(t_state[8+offset], t_state[8+offset+1]);
+        i += 1;
+    }
+
+    //cryptonight-heavy mixes the blocks before the scratchpad is filled
+    if params.heavy {
+        let mut round = 0;
+        while round < 16 {
+            let mut i = 0;
+            while i < 8 {
+                blocks[i] = aes_rounds(aes, blocks[i], &keys);
+                i += 1;
+            }
+            mix_and_propagate(&mut blocks);
+            round += 1;
         }
-        scratchpad[i] = block;
+    }
+
+    let mut i = 0;
+    while i < 8 {
+        scratchpad[i] = aes_rounds(aes, blocks[i], &keys);
         i += 1;
     }
 
     let mut k = 0;
-    while k < (MEM_SIZE-8) {
+    while k < (params.mem_size-8) {
         let mut i = k;
         while i < (k+8) {
-            let mut block = scratchpad[i];
-            let mut j = 0;
-            while j < 10 {
-                block = aes.aes_round(block, keys[j]);
-                j += 1;
-            }
-            scratchpad[i+8] = block;
+            scratchpad[i+8] = aes_rounds(aes, scratchpad[i], &keys);
             i += 1;
         }
         k += 8;
@@ -2,22 +2,17 @@
 #![allow(unreadable_literal)]
 extern crate tiny_keccak;
 
-use super::common::{as_u8_array};
+use super::common::{read_u64_le, state_to_u8};
 
 const PLEN: usize = 25;
 const TLEN: usize = 144;
 
-fn xorin(dst: &mut [u8], src: &[u8]) {
-    for (d, i) in dst.iter_mut().zip(src) {
-        *d ^= *i;
+fn xorin(a: &mut [u64; PLEN], src: &[u8], rate: usize) {
+    for i in 0..(rate/8) {
+        a[i] ^= read_u64_le(src, i);
     }
 }
 
-#[allow(cast_ptr_alignment)]
-fn as_u64_array(t: &mut [u8; TLEN]) -> &mut [u64; TLEN / 8] {
-    unsafe { &mut *(t as *mut [u8; TLEN] as *mut [u64; TLEN / 8]) }
-}
-
 #[inline(always)]
 pub fn keccakf(a: &mut [u64; PLEN]) {
     tiny_keccak::keccakf(a)
@@ -36,7 +31,7 @@ pub fn keccak(input: &[u8]) -> [u8; 200] {
     let mut ip = 0;
     let mut l = inlen;
     while l >= rate {
-        xorin(&mut as_u8_array(&mut a)[0..][..rate], &input[ip..]);
+        xorin(&mut a, &input[ip..], rate);
         tiny_keccak::keccakf(&mut a);
         ip += rate;
         l -= rate;
@@ -47,13 +42,9 @@ pub fn keccak(input: &[u8]) -> [u8; 200] {
     tmp[inlen] = 1;
     tmp[rate - 1] |= 0x80;
 
-    let t64 = as_u64_array(&mut tmp);
-    for i in 0..(rate/8) {
-        a[i] ^= t64[i];
-    }
+    xorin(&mut a, &tmp, rate);
 
     tiny_keccak::keccakf(&mut a);
 
-    let t8 = as_u8_array(&mut a);
-    *t8
+    state_to_u8(&a)
 }
@@ -6,3 +6,44 @@ pub mod hw_aes;
 pub mod sw_aes;
 pub mod sse;
 pub mod common;
+pub mod random_math;
+
+use super::randomx;
+
+/// Hash versions implemented by the hash core
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HashVersion {
+    /// cryptonight v8 (monero hard fork october 2018)
+    V8,
+    /// cryptonight-r, v8 with random math generated from the block height (monero hard fork march 2019)
+    VersionR,
+    /// RandomX (monero hard fork november 2019), not a cryptonight variant, hashed by `randomx`
+    RandomX,
+    /// cryptonight-heavy (haven, loki), 4 MB scratchpad and extra mixing in the scratchpad init and finalisation
+    Heavy,
+}
+
+/// Versions advertised to the pool on login, the first is preferred
+pub const SUPPORTED_VERSIONS : &[HashVersion] = &[HashVersion::RandomX, HashVersion::VersionR, HashVersion::V8, HashVersion::Heavy];
+
+impl HashVersion {
+    /// Stratum algorithm names of this version
+    pub fn algo_names(self) -> &'static [&'static str] {
+        match self {
+            HashVersion::V8 => &["cn/2", "cryptonight/2"],
+            HashVersion::VersionR => &["cn/r", "cryptonight/r"],
+            HashVersion::RandomX => randomx::ALGO_NAMES,
+            HashVersion::Heavy => &["cn-heavy/0", "cryptonight-heavy"]
+        }
+    }
+
+    /// The version for a stratum algorithm name, None if it is not supported
+    pub fn from_algo(algo: &str) -> Option<HashVersion> {
+        SUPPORTED_VERSIONS.iter().cloned().find(|v| v.algo_names().contains(&algo))
+    }
+}
+
+/// All algorithm names to advertise on login
+pub fn supported_algo_names() -> Vec<String> {
+    SUPPORTED_VERSIONS.iter().map(|v| v.algo_names()[0].to_string()).collect()
+}
@@ -0,0 +1,248 @@
+extern crate blake;
+
+/// Minimal latency of the generated code in cycles, the same as 15 multiplications
+const TOTAL_LATENCY : usize = 15 * 3;
+pub const NUM_INSTRUCTIONS_MIN : usize = 60;
+pub const NUM_INSTRUCTIONS_MAX : usize = 70;
+/// Modern CPUs have a single ALU for multiplications
+const ALU_COUNT_MUL : usize = 1;
+/// Three of the (usually four) ALUs, the main loop needs the others
+const ALU_COUNT : usize = 3;
+
+/// Latency on Intel CPUs since Sandy Bridge by opcode (MUL, ADD, SUB, ROR, ROL, XOR)
+const OP_LATENCY : [usize; 6] = [3, 2, 1, 2, 2, 1];
+/// Latency of a theoretical ASIC by opcode
+const ASIC_OP_LATENCY : [usize; 6] = [3, 1, 1, 1, 1, 1];
+const OP_ALUS : [usize; 6] = [ALU_COUNT_MUL, ALU_COUNT, ALU_COUNT, ALU_COUNT, ALU_COUNT, ALU_COUNT];
+
+/// Operations of the cryptonight-r random math on 32 bit registers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Opcode {
+    /// dst * src
+    Mul = 0,
+    /// dst + src + C
+    Add = 1,
+    /// dst - src
+    Sub = 2,
+    /// rotate dst right by src & 31 bits
+    Ror = 3,
+    /// rotate dst left by src & 31 bits
+    Rol = 4,
+    /// dst ^ src
+    Xor = 5,
+}
+
+impl Opcode {
+    fn is_rotation(self) -> bool {
+        self == Opcode::Ror || self == Opcode::Rol
+    }
+}
+
+/// One instruction of the program, `dst` is one of the variable registers r0-r3,
+/// `src` any of the registers r0-r8
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Instruction {
+    pub opcode: Opcode,
+    pub dst: usize,
+    pub src: usize,
+    /// the constant of `Add`, 0 for all other opcodes
+    pub c: u32,
+}
+
+/// Random bytes for the generator, refilled with blake-256 of the last bytes when used up
+struct RandomData {
+    data: [u8; 32],
+    index: usize,
+}
+
+impl RandomData {
+    fn new(height: u64) -> RandomData {
+        let mut data = [0u8; 32];
+        data[0..8].copy_from_slice(&height.to_le_bytes());
+        data[20] = 0xda; //seed change of the reference implementation (-38)
+        //starting past the end hashes the data before the first byte is used
+        RandomData{data, index: 32}
+    }
+
+    fn check(&mut self, bytes_needed: usize) {
+        if self.index + bytes_needed > self.data.len() {
+            let mut next = [0u8; 32];
+            blake::hash(256, &self.data, &mut next).expect("blake-256 of random math data");
+            self.data = next;
+            self.index = 0;
+        }
+    }
+
+    fn byte(&mut self) -> u8 {
+        self.check(1);
+        let b = self.data[self.index];
+        self.index += 1;
+        b
+    }
+
+    fn u32(&mut self) -> u32 {
+        self.check(4);
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(&self.data[self.index..self.index + 4]);
+        self.index += 4;
+        u32::from_le_bytes(bytes)
+    }
+}
+
+/// Generates the random math program of block `height`. It is the same for all nonces
+/// and jobs of a height and has `NUM_INSTRUCTIONS_MIN` to `NUM_INSTRUCTIONS_MAX` instructions.
+pub fn generate(height: u64) -> Vec<Instruction> {
+    let mut data = RandomData::new(height);
+    loop {
+        let (code, r8_used) = generate_attempt(&mut data);
+        //about 2% of the programs do not use r8 and are generated again from the following data
+        if r8_used && code.len() >= NUM_INSTRUCTIONS_MIN && code.len() <= NUM_INSTRUCTIONS_MAX {
+            return code;
+        }
+    }
+}
+
+fn generate_attempt(data: &mut RandomData) -> (Vec<Instruction>, bool) {
+    let mut code = Vec::with_capacity(NUM_INSTRUCTIONS_MAX);
+    let mut latency = [0usize; 9];
+    let mut asic_latency = [0usize; 9];
+    //value of the destination (byte 0), opcode (byte 1) and value of the source (byte 2) of the
+    //last instruction per register, r4-r8 are constants and treated as the same value
+    let mut inst_data : [u32; 9] = [0, 1, 2, 3, 0xFF_FFFF, 0xFF_FFFF, 0xFF_FFFF, 0xFF_FFFF, 0xFF_FFFF];
+    let mut alu_busy = [[false; ALU_COUNT]; TOTAL_LATENCY + 1];
+    let mut rotated = [false; 4];
+    let mut rotate_count = 0;
+    let mut num_retries = 0;
+    let mut total_iterations = 0;
+    let mut r8_used = false;
+
+    //the latency of the abstract CPU is reached for all four variable registers
+    while latency[0..4].iter().any(|l| *l < TOTAL_LATENCY) && num_retries < 64 {
+        total_iterations += 1;
+        if total_iterations > 256 {
+            break;
+        }
+
+        let c = data.byte();
+        let opcode = match c & 7 {
+            0 ..= 2 => Opcode::Mul,
+            3 => Opcode::Add,
+            4 => Opcode::Sub,
+            5 => if data.byte() < 128 { Opcode::Ror } else { Opcode::Rol },
+            _ => Opcode::Xor
+        };
+        let dst = ((c >> 3) & 3) as usize;
+        let mut src = ((c >> 5) & 7) as usize;
+
+        //ADD, SUB and XOR of a register with itself use r8 instead
+        if (opcode == Opcode::Add || opcode == Opcode::Sub || opcode == Opcode::Xor) && dst == src {
+            src = 8;
+        }
+        //two rotations of the same register equal a single one
+        if opcode.is_rotation() && rotated[dst] {
+            continue;
+        }
+        //the same instruction (except MUL) with the same source value twice can be simplified
+        let op = opcode as usize;
+        if opcode != Opcode::Mul && (inst_data[dst] & 0xFF_FF00) == ((op as u32) << 8) + ((inst_data[src] & 255) << 16) {
+            continue;
+        }
+
+        //find the first cycle an ALU is available for the instruction
+        let mut next_latency = if latency[dst] > latency[src] { latency[dst] } else { latency[src] };
+        let mut alu_index = None;
+        while next_latency < TOTAL_LATENCY {
+            for i in (0..OP_ALUS[op]).rev() {
+                if alu_busy[next_latency][i] {
+                    continue;
+                }
+                //ADD takes two 1-cycle instructions on a real CPU
+                if opcode == Opcode::Add && alu_busy[next_latency + 1][i] {
+                    continue;
+                }
+                //a rotation starts after the previous rotation finished
+                if opcode.is_rotation() && next_latency < rotate_count * OP_LATENCY[op] {
+                    continue;
+                }
+                alu_index = Some(i);
+                break;
+            }
+            if alu_index.is_some() {
+                break;
+            }
+            next_latency += 1;
+        }
+
+        //no register is left unchanged for more than 7 cycles
+        if next_latency > latency[dst] + 7 {
+            continue;
+        }
+
+        next_latency += OP_LATENCY[op];
+        if next_latency > TOTAL_LATENCY {
+            num_retries += 1;
+            continue;
+        }
+        let alu_index = alu_index.expect("alu found before the total latency");
+        if opcode.is_rotation() {
+            rotate_count += 1;
+        }
+        //ALUs are pipelined, they are busy in the first cycle of the instruction only
+        alu_busy[next_latency - OP_LATENCY[op]][alu_index] = true;
+        latency[dst] = next_latency;
+        asic_latency[dst] = (if asic_latency[dst] > asic_latency[src] { asic_latency[dst] } else { asic_latency[src] }) + ASIC_OP_LATENCY[op];
+        rotated[dst] = opcode.is_rotation();
+        inst_data[dst] = code.len() as u32 + ((op as u32) << 8) + ((inst_data[src] & 255) << 16);
+        if src == 8 {
+            r8_used = true;
+        }
+
+        let mut constant = 0;
+        if opcode == Opcode::Add {
+            alu_busy[next_latency - OP_LATENCY[op] + 1][alu_index] = true;
+            constant = data.u32();
+        }
+        code.push(Instruction{opcode, dst, src, c: constant});
+        if code.len() >= NUM_INSTRUCTIONS_MIN {
+            break;
+        }
+    }
+
+    //an ASIC runs more instructions in parallel, MUL and ROR are added until one register
+    //reaches the minimal latency there too
+    let prev_code_size = code.len();
+    while code.len() < NUM_INSTRUCTIONS_MAX && asic_latency[0..4].iter().all(|l| *l < TOTAL_LATENCY) {
+        let mut min_idx = 0;
+        let mut max_idx = 0;
+        for i in 1..4 {
+            if asic_latency[i] < asic_latency[min_idx] {
+                min_idx = i;
+            }
+            if asic_latency[i] > asic_latency[max_idx] {
+                max_idx = i;
+            }
+        }
+        let opcode = [Opcode::Ror, Opcode::Mul, Opcode::Mul][(code.len() - prev_code_size) % 3];
+        latency[min_idx] = latency[max_idx] + OP_LATENCY[opcode as usize];
+        asic_latency[min_idx] = asic_latency[max_idx] + ASIC_OP_LATENCY[opcode as usize];
+        code.push(Instruction{opcode, dst: min_idx, src: max_idx, c: 0});
+    }
+    (code, r8_used)
+}
+
+/// Runs the program on the registers r0-r8
+#[inline(always)]
+pub fn execute(code: &[Instruction], r: &mut [u32; 9]) {
+    for op in code {
+        let src = r[op.src];
+        let dst = &mut r[op.dst];
+        *dst = match op.opcode {
+            Opcode::Mul => dst.wrapping_mul(src),
+            Opcode::Add => dst.wrapping_add(src).wrapping_add(op.c),
+            Opcode::Sub => dst.wrapping_sub(src),
+            Opcode::Ror => dst.rotate_right(src % 32),
+            Opcode::Rol => dst.rotate_left(src % 32),
+            Opcode::Xor => *dst ^ src
+        };
+    }
+}
@@ -0,0 +1,45 @@
+extern crate rand;
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use self::rand::{FromEntropy, SeedableRng};
+use self::rand::rngs::StdRng;
+
+/// Random number stream of the keep alive jitter, nonce partitions use their index as stream
+pub const STREAM_KEEPALIVE_JITTER : u64 = u64::max_value();
+
+/// Odd constant spreading stream numbers over the seed space
+const STREAM_MULTIPLIER : u64 = 0x9e37_79b9_7f4a_7c15;
+
+static SEEDED: AtomicBool = AtomicBool::new(false);
+static SEED: AtomicU64 = AtomicU64::new(0);
+
+/// Enables the deterministic mode: every random decision of the miner is derived
+/// from `seed`, so runs with the same seed and pool transcript behave identically.
+/// Has to be called before the worker pool and the stratum client are started.
+pub fn set_seed(seed: u64) {
+    SEED.store(seed, Ordering::SeqCst);
+    SEEDED.store(true, Ordering::SeqCst);
+}
+
+/// The seed of the deterministic mode, `None` if it is not enabled
+pub fn seed() -> Option<u64> {
+    if SEEDED.load(Ordering::SeqCst) {
+        Some(SEED.load(Ordering::SeqCst))
+    } else {
+        None
+    }
+}
+
+/// Random number generator for `stream`, seeded from the entropy of the system
+/// unless the deterministic mode is enabled.
+pub fn rng(stream: u64) -> StdRng {
+    match seed() {
+        Some(seed) => seeded_rng(seed, stream),
+        None => StdRng::from_entropy()
+    }
+}
+
+/// The generator of `stream` in the deterministic mode with `seed`
+pub fn seeded_rng(seed: u64, stream: u64) -> StdRng {
+    StdRng::seed_from_u64(seed ^ stream.wrapping_mul(STREAM_MULTIPLIER))
+}
@@ -0,0 +1,100 @@
+/// Jobs with an easier target are logged, a pool sending them is probably misconfigured
+/// and would be flooded with shares. With `clamp_easy_targets` they are clamped to it.
+pub const MIN_JOB_DIFFICULTY : u64 = 100;
+/// Jobs with a harder target are rejected, no share would ever be found
+pub const MAX_JOB_DIFFICULTY : u64 = 1 << 48;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum TargetError {
+    #[error("target {0} is not 8 or 16 hex chars")]
+    Malformed(String),
+    #[error("target is zero")]
+    Zero,
+    #[error("target difficulty {0} is too high")]
+    TooHard(u64),
+}
+
+/// Difficulty of a hash, `hash_val` are the 8 most significant bytes of
+/// the hash (as compared against the target).
+pub fn hash_difficulty(hash_val: u64) -> u64 {
+    if hash_val == 0 {
+        return u64::max_value();
+    }
+    u64::max_value() / hash_val
+}
+
+/// Expands a 32 bit compact target to 64 bit
+pub fn target_u64(t: u32) -> u64 {
+    u64::max_value() / (u64::from(u32::max_value()) / u64::from(t))
+}
+
+/// Difficulty of a job with the given 64 bit target
+pub fn target_difficulty(target: u64) -> u64 {
+    hash_difficulty(target)
+}
+
+/// Formats a difficulty in human units, e.g. 142500 => "142.5k"
+pub fn format_difficulty(difficulty: u64) -> String {
+    const UNITS : [&str; 6] = ["k", "M", "G", "T", "P", "E"];
+
+    if difficulty < 1000 {
+        return difficulty.to_string();
+    }
+    let mut value = difficulty as f64 / 1000.0;
+    let mut unit = 0;
+    while value >= 1000.0 && unit < UNITS.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    let formatted = format!("{:.1}", value);
+    let trimmed = if formatted.ends_with(".0") { &formatted[..formatted.len()-2] } else { &formatted[..] };
+    format!("{}{}", trimmed, UNITS[unit])
+}
+
+/// Decodes a job target to 64 bit, jobs have either a 32 bit (8 hex chars, usual
+/// for pools) or a 64 bit (16 hex chars) little endian target.
+pub fn parse_target(target: &str) -> Result<u64, TargetError> {
+    if !target.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(TargetError::Malformed(target.to_string()));
+    }
+    let value = match target.len() {
+        8 => {
+            let compact = u32::from_str_radix(target, 16).map_err(|_| TargetError::Malformed(target.to_string()))?.swap_bytes();
+            if compact == 0 {
+                return Err(TargetError::Zero);
+            }
+            target_u64(compact)
+        },
+        16 => u64::from_str_radix(target, 16).map_err(|_| TargetError::Malformed(target.to_string()))?.swap_bytes(),
+        _ => return Err(TargetError::Malformed(target.to_string()))
+    };
+    if value == 0 {
+        return Err(TargetError::Zero);
+    }
+    Ok(value)
+}
+
+/// Sanity check of a decoded pool target, targets harder than `MAX_JOB_DIFFICULTY` are rejected
+pub fn check_target(value: u64) -> Result<u64, TargetError> {
+    let difficulty = target_difficulty(value);
+    if difficulty > MAX_JOB_DIFFICULTY {
+        return Err(TargetError::TooHard(difficulty));
+    }
+    Ok(value)
+}
+
+/// True if the target is easier than `MIN_JOB_DIFFICULTY`
+pub fn is_too_easy(value: u64) -> bool {
+    target_difficulty(value) < MIN_JOB_DIFFICULTY
+}
+
+/// The target raised to `MIN_JOB_DIFFICULTY` if it is easier
+pub fn clamp_target(value: u64) -> u64 {
+    if is_too_easy(value) { u64::max_value() / MIN_JOB_DIFFICULTY } else { value }
+}
+
+/// The 64 bit target as 16 hex chars, little endian
+pub fn target_hex(target: u64) -> String {
+    let bytes : Vec<String> = (0..8).map(|i| format!("{:02x}", (target >> (i * 8)) as u8)).collect();
+    bytes.concat()
+}
@@ -0,0 +1,27 @@
+#[cfg(feature = "miner-bin")]
+extern crate config;
+
+use std::io;
+use std::result;
+#[cfg(feature = "stratum")]
+use stratum::{StratumError};
+
+/// Errors that can occur in mithril, grouped by the part of the miner
+/// they originate from.
+#[derive(Debug, Error)]
+pub enum MithrilError {
+    #[cfg(feature = "stratum")]
+    #[error("stratum error: {0}")]
+    Stratum(#[from] StratumError),
+    #[error("hash error: {0}")]
+    Hash(String),
+    #[cfg(feature = "miner-bin")]
+    #[error("config error: {0}")]
+    Config(#[from] config::ConfigError),
+    #[error("worker error: {0}")]
+    Worker(String),
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+}
+
+pub type Result<T> = result::Result<T, MithrilError>;
@@ -0,0 +1,74 @@
+use std::fs;
+use std::io;
+use std::path::{Path};
+
+/// Placeholder in the template replaced by the host name
+pub const HOST_PLACEHOLDER : &str = "{{host}}";
+/// Placeholder in the template replaced by the host thread count
+pub const THREADS_PLACEHOLDER : &str = "{{num_threads}}";
+
+/// A machine from the host inventory file
+#[derive(Debug, PartialEq)]
+pub struct Host {
+    pub name: String,
+    pub num_threads: u64
+}
+
+/// Parses the host inventory. Each line contains a host name and its
+/// thread count separated by whitespace, `#` starts a comment.
+pub fn parse_hosts(inventory: &str) -> io::Result<Vec<Host>> {
+    let mut hosts = Vec::new();
+    for (ix, raw_line) in inventory.lines().enumerate() {
+        let line = match raw_line.find('#') {
+            Some(pos) => &raw_line[..pos],
+            None => raw_line
+        };
+        let fields : Vec<&str> = line.split_whitespace().collect();
+        if fields.is_empty() {
+            continue;
+        }
+        if fields.len() != 2 {
+            return Err(invalid_data(format!("line {}: expected '<host> <num_threads>'", ix + 1)));
+        }
+        let num_threads = match fields[1].parse::<u64>() {
+            Ok(n) if n > 0 => n,
+            _ => return Err(invalid_data(format!("line {}: num_threads has to be > 0", ix + 1)))
+        };
+        if !valid_host_name(fields[0]) {
+            return Err(invalid_data(format!("line {}: host name '{}' must not contain path separators or '..'", ix + 1, fields[0])));
+        }
+        hosts.push(Host{name: fields[0].to_string(), num_threads});
+    }
+    Ok(hosts)
+}
+
+/// The host name is used as file name of its config, it must stay inside the output directory
+fn valid_host_name(name: &str) -> bool {
+    !name.contains('/') && !name.contains('\\') && !name.contains("..") && name != "."
+}
+
+/// Renders the config template for a single host
+pub fn render(template: &str, host: &Host) -> String {
+    template.replace(HOST_PLACEHOLDER, &host.name)
+            .replace(THREADS_PLACEHOLDER, &host.num_threads.to_string())
+}
+
+/// Writes one `<host>.toml` per inventory entry into `out_dir` and
+/// returns the number of written files.
+pub fn generate(inventory_file: &Path, template_file: &Path, out_dir: &Path) -> io::Result<usize> {
+    let hosts = parse_hosts(&fs::read_to_string(inventory_file)?)?;
+    let template = fs::read_to_string(template_file)?;
+
+    fs::DirBuilder::new().recursive(true).create(out_dir)?;
+    for host in &hosts {
+        let mut file = out_dir.to_path_buf();
+        file.push(format!("{}.toml", host.name));
+        fs::write(&file, render(&template, host))?;
+        info!("wrote config for {} to {:?}", host.name, file);
+    }
+    Ok(hosts.len())
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
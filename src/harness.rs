@@ -0,0 +1,197 @@
+extern crate num_cpus;
+extern crate libc;
+
+use cryptonight::hash;
+use cryptonight::hash::{MEM_SIZE};
+use cryptonight::aes;
+use cryptonight::aes::{AESSupport};
+use worker::worker_pool;
+use byte_string;
+use u64x2::u64x2;
+
+use std::env;
+use std::fs;
+use std::thread;
+use std::time::{Duration, Instant};
+use std::sync::{Arc, Barrier};
+
+/// The fixed job hashed by the harness
+pub const HARNESS_BLOB : &str = "09099aebd3e1057aad462f2d998d8b9adcf16e03a5bf1820728240eefe433735904fcf663eeb1d00000000b0203ca955ed446e47ab9e884941bc67c75ecb06e444036aafc7ff442c60d2f907";
+
+/// Chi-square critical value for 3 degrees of freedom at p = 0.001
+const CHI_SQUARE_3DF : f64 = 16.27;
+/// Chi-square critical value for 1 degree of freedom at p = 0.001
+const CHI_SQUARE_1DF : f64 = 10.83;
+/// Below this number of hashes the distribution checks are not meaningful
+pub const MIN_SAMPLES_FOR_STATS : u64 = 1000;
+
+pub struct HarnessResult {
+    pub num_hashes: u64,
+    pub elapsed: Duration,
+    /// result of the last hash, identical on every run with the same number of hashes
+    pub last_hash: String,
+    pub pinned: bool,
+    pub stats: HashStats,
+}
+
+/// Distribution of the final hash selector and of the hash output bits. For a
+/// correct implementation both are uniform, a bias indicates a broken hash.
+#[derive(Debug, Default, Clone)]
+pub struct HashStats {
+    pub selector_counts: [u64; 4],
+    /// number of set bits per bit position of the first hash byte
+    pub first_byte_bits: [u64; 8],
+    pub samples: u64,
+}
+
+impl HashStats {
+    pub fn record(&mut self, keccak_state: &[u8; 200], hash: &[u8]) {
+        self.selector_counts[hash::final_hash_selector(keccak_state)] += 1;
+        if let Some(first) = hash.first() {
+            for (bit, count) in self.first_byte_bits.iter_mut().enumerate() {
+                *count += u64::from((first >> bit) & 1);
+            }
+        }
+        self.samples += 1;
+    }
+
+    /// true if the selector distribution deviates significantly from uniform
+    pub fn selector_biased(&self) -> bool {
+        if self.samples < MIN_SAMPLES_FOR_STATS {
+            return false;
+        }
+        let expected = self.samples as f64 / 4.0;
+        let chi_square : f64 = self.selector_counts.iter()
+            .map(|c| (*c as f64 - expected).powi(2) / expected)
+            .sum();
+        chi_square > CHI_SQUARE_3DF
+    }
+
+    /// bit positions of the first hash byte that are significantly unbalanced
+    pub fn biased_bits(&self) -> Vec<usize> {
+        if self.samples < MIN_SAMPLES_FOR_STATS {
+            return Vec::new();
+        }
+        let expected = self.samples as f64 / 2.0;
+        self.first_byte_bits.iter().enumerate().filter(|&(_, ones)| {
+            let zeros = (self.samples - ones) as f64;
+            let chi_square = ((*ones as f64 - expected).powi(2) + (zeros - expected).powi(2)) / expected;
+            chi_square > CHI_SQUARE_1DF
+        }).map(|(bit, _)| bit).collect()
+    }
+}
+
+impl HarnessResult {
+    pub fn hashes_per_second(&self) -> f64 {
+        let secs = self.elapsed.as_secs() as f64 + f64::from(self.elapsed.subsec_nanos()) / 1_000_000_000.0;
+        self.num_hashes as f64 / secs
+    }
+}
+
+/// Hashes `num_hashes` nonces (0, 1, 2, ...) of the fixed harness job on a
+/// single thread that is pinned to the first CPU if the platform supports it.
+pub fn run(num_hashes: u64, aes_support: AESSupport) -> HarnessResult {
+    let hnd = thread::Builder::new().name("harness thread".to_string()).spawn(move || {
+        let pinned = pin_to_first_cpu();
+        let aes = aes::new(aes_support);
+        let mut scratchpad : Box<[u64x2; MEM_SIZE]> = box [u64x2(0,0); MEM_SIZE];
+
+        let mut last_hash = String::new();
+        let mut stats = HashStats::default();
+        let start = Instant::now();
+        for nonce in 0..num_hashes {
+            let input = worker_pool::with_nonce(HARNESS_BLOB, &format!("{:08x}", nonce as u32));
+            let state = hash::hash_state(&mut scratchpad, &byte_string::string_to_u8_array(&input), &aes);
+            last_hash = hash::final_hash(&state);
+            stats.record(&state, &byte_string::string_to_u8_array(&last_hash));
+        }
+        HarnessResult{num_hashes, elapsed: start.elapsed(), last_hash, pinned, stats}
+    }).expect("harness thread handle");
+    hnd.join().expect("harness thread join")
+}
+
+/// Throughput of scratchpad initialisation with a given number of threads
+#[derive(Debug, Clone, PartialEq)]
+pub struct BandwidthSample {
+    pub num_threads: usize,
+    /// scratchpad bytes initialised per second over all threads
+    pub bytes_per_second: f64,
+}
+
+/// A thread count is considered saturated if it adds less than this relative throughput
+const SATURATION_GAIN : f64 = 0.1;
+
+/// Measures scratchpad initialisation throughput for 1 to `max_threads` threads,
+/// every thread initialises its own scratchpad `rounds` times. Since the
+/// initialisation mostly writes memory, this shows how many hashing threads the
+/// memory subsystem can feed.
+pub fn scratchpad_bandwidth(max_threads: usize, rounds: u64, aes_support: AESSupport) -> Vec<BandwidthSample> {
+    (1..=max_threads).map(|num_threads| {
+        let barrier = Arc::new(Barrier::new(num_threads + 1));
+        let hnds : Vec<_> = (0..num_threads).map(|i| {
+            let barrier = barrier.clone();
+            thread::Builder::new().name(format!("bandwidth thread {}", i)).spawn(move || {
+                let aes = aes::new(aes_support);
+                let mut scratchpad : Box<[u64x2; MEM_SIZE]> = box [u64x2(0,0); MEM_SIZE];
+                let mut state = [i as u8; 200];
+                barrier.wait();
+                for _ in 0..rounds {
+                    hash::init_scratchpad(&mut scratchpad, &mut state, &aes);
+                }
+            }).expect("bandwidth thread handle")
+        }).collect();
+
+        barrier.wait();
+        let start = Instant::now();
+        for hnd in hnds {
+            hnd.join().expect("bandwidth thread join");
+        }
+        let elapsed = start.elapsed();
+        let secs = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1_000_000_000.0;
+        let bytes = (num_threads as u64 * rounds * (MEM_SIZE * 16) as u64) as f64;
+        BandwidthSample{num_threads, bytes_per_second: bytes / secs}
+    }).collect()
+}
+
+/// The smallest thread count after which adding a thread increases throughput
+/// by less than 10%, None if throughput still scales at the largest count.
+pub fn saturation_point(samples: &[BandwidthSample]) -> Option<usize> {
+    samples.windows(2)
+           .find(|w| w[1].bytes_per_second < w[0].bytes_per_second * (1.0 + SATURATION_GAIN))
+           .map(|w| w[0].num_threads)
+}
+
+/// Describes the environment the harness ran in, results are only comparable
+/// between runs with the same fingerprint.
+pub fn environment_fingerprint(aes_support: AESSupport) -> String {
+    format!("mithril {}, {}/{}, {} cpus, cpu model: {}, aes: {:?}, build: {}",
+        env!("CARGO_PKG_VERSION"),
+        env::consts::OS,
+        env::consts::ARCH,
+        num_cpus::get(),
+        cpu_model().unwrap_or_else(|| "unknown".to_string()),
+        aes_support,
+        if cfg!(debug_assertions) { "debug" } else { "release" })
+}
+
+fn cpu_model() -> Option<String> {
+    let cpuinfo = fs::read_to_string("/proc/cpuinfo").ok()?;
+    cpuinfo.lines()
+           .find(|l| l.starts_with("model name"))
+           .and_then(|l| l.splitn(2, ':').nth(1))
+           .map(|m| m.trim().to_string())
+}
+
+#[cfg(target_os = "linux")]
+fn pin_to_first_cpu() -> bool {
+    unsafe {
+        let mut set : libc::cpu_set_t = ::std::mem::zeroed();
+        libc::CPU_SET(0, &mut set);
+        libc::sched_setaffinity(0, ::std::mem::size_of::<libc::cpu_set_t>(), &set) == 0
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pin_to_first_cpu() -> bool {
+    false
+}
@@ -0,0 +1,79 @@
+/// Lookup table for encoding a nibble
+const ENCODE_LUT : &[u8; 16] = b"0123456789abcdef";
+
+/// Marks a non hex character in `DECODE_LUT`
+const __ : u8 = 0xFF;
+
+/// Lookup table for decoding a hex character (upper and lower case) into its nibble
+static DECODE_LUT : [u8; 256] = [
+    __, __, __, __, __, __, __, __, __, __, __, __, __, __, __, __,
+    __, __, __, __, __, __, __, __, __, __, __, __, __, __, __, __,
+    __, __, __, __, __, __, __, __, __, __, __, __, __, __, __, __,
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, __, __, __, __, __, __,
+    __, 0x0A, 0x0B, 0x0C, 0x0D, 0x0E, 0x0F, __, __, __, __, __, __, __, __, __,
+    __, __, __, __, __, __, __, __, __, __, __, __, __, __, __, __,
+    __, 0x0A, 0x0B, 0x0C, 0x0D, 0x0E, 0x0F, __, __, __, __, __, __, __, __, __,
+    __, __, __, __, __, __, __, __, __, __, __, __, __, __, __, __,
+    __, __, __, __, __, __, __, __, __, __, __, __, __, __, __, __,
+    __, __, __, __, __, __, __, __, __, __, __, __, __, __, __, __,
+    __, __, __, __, __, __, __, __, __, __, __, __, __, __, __, __,
+    __, __, __, __, __, __, __, __, __, __, __, __, __, __, __, __,
+    __, __, __, __, __, __, __, __, __, __, __, __, __, __, __, __,
+    __, __, __, __, __, __, __, __, __, __, __, __, __, __, __, __,
+    __, __, __, __, __, __, __, __, __, __, __, __, __, __, __, __,
+    __, __, __, __, __, __, __, __, __, __, __, __, __, __, __, __,
+];
+
+#[derive(Debug, PartialEq)]
+pub enum HexError {
+    OddLength,
+    InvalidChar {
+        index: usize
+    }
+}
+
+/// Encodes the bytes as lower case hex string
+pub fn encode(bytes: &[u8]) -> String {
+    let mut out = Vec::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        out.push(ENCODE_LUT[(b >> 4) as usize]);
+        out.push(ENCODE_LUT[(b & 0x0F) as usize]);
+    }
+    //only ascii hex chars were pushed
+    String::from_utf8(out).expect("hex string")
+}
+
+/// Decodes a hex string (upper or lower case) into bytes
+pub fn decode(hex: &str) -> Result<Vec<u8>, HexError> {
+    decode_bytes(hex.as_bytes())
+}
+
+/// Decodes the bytes of a hex string, `index` of an invalid char is a byte index
+pub fn decode_bytes(chars: &[u8]) -> Result<Vec<u8>, HexError> {
+    let mut out = vec![0; chars.len() / 2];
+    decode_bytes_into(chars, &mut out)?;
+    Ok(out)
+}
+
+/// Decodes a hex string into `out`, which must have exactly half the length of `hex`
+pub fn decode_into(hex: &str, out: &mut [u8]) -> Result<(), HexError> {
+    decode_bytes_into(hex.as_bytes(), out)
+}
+
+fn decode_bytes_into(chars: &[u8], out: &mut [u8]) -> Result<(), HexError> {
+    if chars.len() % 2 != 0 || chars.len() / 2 != out.len() {
+        return Err(HexError::OddLength);
+    }
+    for (i, o) in out.iter_mut().enumerate() {
+        let hi = DECODE_LUT[chars[2 * i] as usize];
+        if hi == __ {
+            return Err(HexError::InvalidChar{index: 2 * i});
+        }
+        let lo = DECODE_LUT[chars[2 * i + 1] as usize];
+        if lo == __ {
+            return Err(HexError::InvalidChar{index: 2 * i + 1});
+        }
+        *o = (hi << 4) | lo;
+    }
+    Ok(())
+}
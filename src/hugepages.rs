@@ -0,0 +1,86 @@
+use std::fs;
+use std::io;
+use std::io::{Error, ErrorKind};
+use std::process::{Command};
+use worker::scratchpad::{SCRATCHPAD_BYTES};
+
+/// Size of the huge pages scratchpads are mapped on
+pub const HUGE_PAGE_KB : u64 = 2048;
+pub const NR_HUGEPAGES_PATH : &str = "/proc/sys/vm/nr_hugepages";
+/// sysctl drop-in that keeps the reservation after a reboot
+pub const SYSCTL_CONF_PATH : &str = "/etc/sysctl.d/60-mithril-hugepages.conf";
+const MEMINFO_PATH : &str = "/proc/meminfo";
+
+/// Huge page counters of `/proc/meminfo`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HugePageStatus {
+    pub total: u64,
+    pub free: u64,
+    /// default huge page size of the kernel, the one `vm.nr_hugepages` reserves
+    pub page_size_kb: u64,
+}
+
+/// Huge pages needed for the scratchpads of `num_threads` worker threads
+pub fn required_pages(num_threads: u64) -> u64 {
+    let page_bytes = HUGE_PAGE_KB * 1024;
+    num_threads * ((SCRATCHPAD_BYTES as u64 + page_bytes - 1) / page_bytes)
+}
+
+/// Value for `vm.nr_hugepages` that leaves `required` pages free on top of the
+/// pages in use now. An existing larger reservation is kept.
+pub fn target_pages(required: u64, status: &HugePageStatus) -> u64 {
+    let in_use = status.total.saturating_sub(status.free);
+    (in_use + required).max(status.total)
+}
+
+pub fn parse_meminfo(meminfo: &str) -> Option<HugePageStatus> {
+    let value = |name: &str| meminfo.lines()
+        .find(|l| l.starts_with(name))
+        .and_then(|l| l[name.len()..].split_whitespace().next())
+        .and_then(|v| v.parse::<u64>().ok());
+    Some(HugePageStatus{
+        total: value("HugePages_Total:")?,
+        free: value("HugePages_Free:")?,
+        page_size_kb: value("Hugepagesize:")?,
+    })
+}
+
+pub fn read_status() -> io::Result<HugePageStatus> {
+    let meminfo = fs::read_to_string(MEMINFO_PATH)?;
+    parse_meminfo(&meminfo).ok_or_else(|| Error::new(ErrorKind::InvalidData, "no huge page counters in /proc/meminfo, kernel without huge page support?"))
+}
+
+/// Shell commands that reserve `pages` huge pages now and after a reboot
+pub fn commands(pages: u64) -> Vec<String> {
+    vec![format!("sudo sysctl -w vm.nr_hugepages={}", pages),
+         format!("echo 'vm.nr_hugepages={}' | sudo tee {}", pages, SYSCTL_CONF_PATH)]
+}
+
+/// Sets `vm.nr_hugepages` for the running system, directly if mithril runs as root,
+/// with `sudo sysctl` otherwise (sudo may ask for a password).
+pub fn apply(pages: u64) -> io::Result<()> {
+    match fs::write(NR_HUGEPAGES_PATH, pages.to_string()) {
+        Err(ref e) if e.kind() == ErrorKind::PermissionDenied => {
+            let status = Command::new("sudo").arg("sysctl").arg("-w").arg(format!("vm.nr_hugepages={}", pages)).status()?;
+            if status.success() {
+                Ok(())
+            } else {
+                Err(Error::new(ErrorKind::Other, format!("sudo sysctl failed: {}", status)))
+            }
+        },
+        result => result
+    }
+}
+
+/// Problems that keep the scratchpads of `required` pages from being mapped on huge pages
+pub fn verify(required: u64, status: &HugePageStatus) -> Vec<String> {
+    let mut problems = Vec::new();
+    if status.page_size_kb != HUGE_PAGE_KB {
+        problems.push(format!("the default huge page size is {} kB, set /sys/kernel/mm/hugepages/hugepages-{}kB/nr_hugepages instead",
+            status.page_size_kb, HUGE_PAGE_KB));
+    } else if status.free < required {
+        problems.push(format!("only {} of {} needed huge pages are free, the kernel found no more contiguous memory; \
+            reserve them right after boot or with the kernel parameter hugepages={}", status.free, required, status.total + required - status.free));
+    }
+    problems
+}
@@ -7,17 +7,121 @@
 #![feature(integer_atomics)]
 #![feature(mpsc_select)]
 
+#[cfg(feature = "stratum")]
 #[macro_use]
 extern crate serde_derive;
 #[macro_use]
 extern crate log;
+#[macro_use]
+extern crate thiserror;
+
+// The modules are internal, the miner binary, the benches and the integration tests use
+// them directly. Embedders use the types re-exported below (or the prelude) instead.
 
+// hashing core, always built
+#[doc(hidden)]
+pub mod error;
+#[doc(hidden)]
+pub mod hex;
+#[doc(hidden)]
 pub mod byte_string;
+#[doc(hidden)]
 pub mod cryptonight;
+#[doc(hidden)]
+pub mod randomx;
+#[doc(hidden)]
+pub mod u64x2;
+#[doc(hidden)]
+pub mod difficulty;
+#[doc(hidden)]
+pub mod variance;
+
+// stratum client and workers, feature `stratum`
+#[cfg(feature = "stratum")]
+#[doc(hidden)]
 pub mod stratum;
+#[cfg(feature = "stratum")]
+#[doc(hidden)]
 pub mod worker;
-pub mod u64x2;
+#[cfg(feature = "stratum")]
+#[doc(hidden)]
 pub mod metric;
+#[cfg(feature = "stratum")]
+#[doc(hidden)]
+pub mod solo;
+#[cfg(feature = "stratum")]
+#[doc(hidden)]
+pub mod determinism;
+
+// config file, tuning and subcommands of the miner binary, feature `miner-bin`
+#[cfg(feature = "miner-bin")]
+#[doc(hidden)]
 pub mod bandit_tools;
+#[cfg(feature = "miner-bin")]
+#[doc(hidden)]
 pub mod mithril_config;
+#[cfg(feature = "miner-bin")]
+#[doc(hidden)]
 pub mod timer;
+#[cfg(feature = "miner-bin")]
+#[doc(hidden)]
+pub mod fleet;
+#[cfg(feature = "miner-bin")]
+#[doc(hidden)]
+pub mod harness;
+#[cfg(feature = "miner-bin")]
+#[doc(hidden)]
+pub mod banner;
+#[cfg(feature = "miner-bin")]
+#[doc(hidden)]
+pub mod soak;
+#[cfg(feature = "miner-bin")]
+#[doc(hidden)]
+pub mod xmrig_import;
+#[cfg(feature = "miner-bin")]
+#[doc(hidden)]
+pub mod console;
+#[cfg(feature = "miner-bin")]
+#[doc(hidden)]
+pub mod update;
+#[cfg(feature = "miner-bin")]
+#[doc(hidden)]
+pub mod hugepages;
+#[cfg(feature = "miner-bin")]
+#[doc(hidden)]
+pub mod cpu_share;
+#[cfg(feature = "miner-bin")]
+#[doc(hidden)]
+pub mod accounting;
+#[cfg(feature = "miner-bin")]
+#[doc(hidden)]
+pub mod supervisor;
+
+pub use error::{MithrilError, Result};
+pub use cryptonight::aes::{AESSupport};
+/// The hash algorithm of a job (cn/2, cn/r, rx/0 or cn-heavy)
+pub use cryptonight::{HashVersion as Algorithm};
+#[cfg(feature = "miner-bin")]
+pub use mithril_config::{MithrilConfig as Config, read_config};
+#[cfg(feature = "stratum")]
+pub use stratum::{StratumClient, StratumCmd, StratumAction, StratumError, StratumEvent};
+#[cfg(feature = "stratum")]
+pub use stratum::builder::{StratumClientBuilder, BuildError};
+#[cfg(feature = "stratum")]
+pub use stratum::stratum_data::{PoolConfig, Share};
+/// The worker threads hashing the jobs of the stratum client
+#[cfg(feature = "stratum")]
+pub use worker::worker_pool::{WorkerPool as Miner, WorkerPool, WorkerConfig, WorkerEvent};
+
+/// The types needed for embedding mithril, `use mithril::prelude::*;`
+pub mod prelude {
+    pub use super::{MithrilError, AESSupport, Algorithm};
+    #[cfg(feature = "miner-bin")]
+    pub use super::{Config, read_config};
+    #[cfg(feature = "stratum")]
+    pub use super::{StratumClient, StratumCmd, StratumAction, StratumError, StratumEvent, PoolConfig, Share};
+    #[cfg(feature = "stratum")]
+    pub use super::{StratumClientBuilder, BuildError};
+    #[cfg(feature = "stratum")]
+    pub use super::{Miner, WorkerPool, WorkerConfig, WorkerEvent};
+}
@@ -6,47 +6,292 @@ extern crate log;
 extern crate mithril;
 extern crate env_logger;
 extern crate bandit;
+extern crate num_cpus;
+#[cfg(feature = "jemalloc")]
+extern crate jemallocator;
+#[cfg(feature = "mimalloc")]
+extern crate mimalloc;
 
-use mithril::stratum::{StratumClient, StratumAction};
+use mithril::stratum;
+use mithril::stratum::{StratumClient, StratumAction, StratumError, StratumEvent, RejectKind};
+use mithril::stratum::failover::{Failover};
+use mithril::stratum::multi_pool::{MultiPoolClient};
+use mithril::stratum::schedule;
+use mithril::stratum::schedule::{PoolSchedule};
+use mithril::stratum::stratum_data::{PoolConfig};
+use mithril::stratum::share_queue::{ShareQueue};
+use mithril::stratum::offline::{OfflineShares};
+#[cfg(feature = "async-stratum")]
+use mithril::stratum::async_client::{AsyncStratumClient};
+use mithril::stratum::event_log::{EventLog, ConnectionEvent};
+use mithril::stratum::trace;
+use mithril::solo::{SoloClient};
 use mithril::worker::worker_pool;
-use mithril::worker::worker_pool::{WorkerPool};
+use mithril::worker::worker_pool::{WorkerEvent, WorkerPool, WorkerGroup};
+use mithril::worker::topology;
+use mithril::worker::topology::{Topology};
 use mithril::metric;
+use mithril::metric::counters::{HashCounters, Sampler};
+use mithril::metric::coverage::{NonceCoverage};
+use mithril::variance::{ShareEstimate};
 use mithril::cryptonight::hash;
 use mithril::cryptonight::aes;
 use mithril::cryptonight::aes::{AESSupport};
+use mithril::cryptonight::{HashVersion};
 use mithril::byte_string;
 use mithril::bandit_tools;
 use mithril::mithril_config;
+use mithril::mithril_config::{MithrilConfig};
 use mithril::timer;
-use std::sync::mpsc::{channel, Select, Receiver};
+use mithril::fleet;
+use mithril::harness;
+use mithril::banner;
+use mithril::difficulty;
+use mithril::soak;
+use mithril::determinism;
+use mithril::xmrig_import;
+use mithril::console;
+use mithril::console::{ConsoleCmd};
+use mithril::update;
+use mithril::hugepages;
+use mithril::cpu_share;
+use mithril::cpu_share::{CpuShare};
+use mithril::accounting;
+use mithril::accounting::{ShareLog, ShareResult};
+use mithril::supervisor;
+use mithril::supervisor::{Supervisor, Decision};
+use mithril::error::{MithrilError};
+use log::{LevelFilter};
+
+use std::sync::mpsc::{channel, Select, Receiver, Sender};
+use std::sync::Arc;
+use std::collections::{HashMap};
 use std::path::Path;
+use std::env;
+use std::fs;
+use std::process;
 use std::io;
-use std::io::{Error};
 use std::thread;
-use std::time::{Duration};
+use std::time::{Duration, Instant};
 
 use bandit::MultiArmedBandit;
 
+#[cfg(all(feature = "jemalloc", feature = "mimalloc"))]
+compile_error!("features jemalloc and mimalloc are mutually exclusive");
+
+#[cfg(feature = "jemalloc")]
+#[global_allocator]
+static GLOBAL: jemallocator::Jemalloc = jemallocator::Jemalloc;
+
+#[cfg(feature = "mimalloc")]
+#[global_allocator]
+static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
+
+/// Recent jobs and shares are logged when this many shares were rejected in a row
+const REJECT_STREAK_LOG : usize = 5;
+/// Number of jobs and shares shown when recent pool events are logged
+const RECENT_EVENTS : usize = 20;
+/// Number of connection events printed by the console command
+const RECENT_CONNECTION_EVENTS : usize = 50;
+
+/// Seconds between checks of the job watchdog
+const JOB_WATCHDOG_CHECK_SECS : u64 = 10;
+/// Seconds between logs of the expected and the found number of shares
+const SHARE_ESTIMATE_SECS : u64 = 600;
+
+/// Ends the main loop with an error if the pool sends no job for `timeout`
+struct JobWatchdog<'a> {
+    tick_rx: &'a Receiver<stratum::Tick>,
+    timeout: Option<Duration>,
+}
+
+/// Ends the main loop when another window of the pool schedule begins
+struct ScheduleCheck<'a> {
+    schedule: &'a PoolSchedule,
+    /// the window the client is connected for, None for the `[pool]` settings
+    active: Option<usize>,
+}
+
+/// Ends the main loop when the share of the cores negotiated with other instances changes
+struct CpuShareCheck<'a> {
+    share: &'a CpuShare,
+    /// the cores the worker threads were started for
+    cores: usize,
+}
+
+/// Connection jobs are received from and shares are sent to
+enum PoolClient {
+    Stratum(StratumClient),
+    #[cfg(feature = "async-stratum")]
+    AsyncStratum(AsyncStratumClient),
+    MultiPool(MultiPoolClient),
+    Solo(SoloClient)
+}
+
+impl PoolClient {
+    fn new_cmd_channel(&self) -> Sender<stratum::StratumCmd> {
+        match *self {
+            PoolClient::Stratum(ref client) => client.new_cmd_channel(),
+            #[cfg(feature = "async-stratum")]
+            PoolClient::AsyncStratum(ref client) => client.new_cmd_channel(),
+            PoolClient::MultiPool(ref client) => client.new_cmd_channel(),
+            PoolClient::Solo(ref client) => client.new_cmd_channel()
+        }
+    }
+
+    fn share_queue(&self) -> Arc<ShareQueue> {
+        match *self {
+            PoolClient::Stratum(ref client) => client.share_queue(),
+            #[cfg(feature = "async-stratum")]
+            PoolClient::AsyncStratum(ref client) => client.share_queue(),
+            PoolClient::MultiPool(ref client) => client.share_queue(),
+            PoolClient::Solo(ref client) => client.share_queue()
+        }
+    }
+
+    fn stop(self) {
+        match self {
+            PoolClient::Stratum(client) => client.stop(),
+            #[cfg(feature = "async-stratum")]
+            PoolClient::AsyncStratum(client) => client.stop(),
+            PoolClient::MultiPool(client) => client.stop(),
+            PoolClient::Solo(client) => client.stop()
+        }
+    }
+}
+
+/// Records a failure of the active pool, a switch to another pool is added to the event log
+fn record_pool_failure(failover: &mut Failover, event_log: &mut EventLog) {
+    let from = failover.active_address().to_string();
+    failover.record_failure();
+    if failover.active_address() != from {
+        event_log.record_connection(ConnectionEvent::Failover{from, to: failover.active_address().to_string()});
+    }
+}
+
+/// Logs in with the async client if it is compiled in and supports the pool connection,
+/// with a secondary pool both pools are connected
+#[cfg(feature = "async-stratum")]
+fn login_stratum(conf: PoolConfig, err_tx: Sender<StratumError>, action_tx: Sender<StratumEvent>) -> Result<PoolClient, MithrilError> {
+    if conf.secondary_pool_address.is_some() {
+        MultiPoolClient::login(conf, err_tx, action_tx).map(PoolClient::MultiPool)
+    } else if AsyncStratumClient::supports(&conf) {
+        AsyncStratumClient::login(conf, err_tx, action_tx).map(PoolClient::AsyncStratum)
+    } else {
+        StratumClient::login(conf, err_tx, action_tx).map(PoolClient::Stratum)
+    }
+}
+
+#[cfg(not(feature = "async-stratum"))]
+fn login_stratum(conf: PoolConfig, err_tx: Sender<StratumError>, action_tx: Sender<StratumEvent>) -> Result<PoolClient, MithrilError> {
+    if conf.secondary_pool_address.is_some() {
+        MultiPoolClient::login(conf, err_tx, action_tx).map(PoolClient::MultiPool)
+    } else {
+        StratumClient::login(conf, err_tx, action_tx).map(PoolClient::Stratum)
+    }
+}
+
 #[derive(Debug, PartialEq)]
 enum MainLoopExit {
     DrawNewBanditArm,
-    DonationHashing
+    DonationHashing,
+    DonationEnd,
+    ProbePrimaryPool,
+    ScheduleChange,
+    CpuShareChange,
+    /// the flood guard gave up on the pool, the next failover pool is used
+    PoolUnhealthy
 }
 
 fn main() {
 
-    env_logger::init();
+    //env_logger lets everything through, the level of RUST_LOG is the maximum level of the
+    //log crate instead, so the `l` console command can change it while mining
+    let configured_level = env_logger::Builder::from_default_env().build().filter();
+    env_logger::Builder::from_default_env().filter_level(LevelFilter::Trace).init();
+    log::set_max_level(configured_level);
+
+    let args : Vec<String> = env::args().skip(1).collect();
+    let capture_session = if args.first().map(|a| a.as_str()) == Some("--capture-session") {
+        Some(arg_value(&args, "--capture-session").unwrap_or_else(|| exit_with_usage()))
+    } else {
+        if !args.is_empty() {
+            run_subcommand(&args);
+            return;
+        }
+        None
+    };
 
     //Read config
     let cwd_path = &format!("{}{}", "./", mithril_config::CONFIG_FILE_NAME);
-    let config = mithril_config::read_config(Path::new(cwd_path), mithril_config::CONFIG_FILE_NAME).unwrap();
+    let mut config = mithril_config::read_config(Path::new(cwd_path), mithril_config::CONFIG_FILE_NAME).unwrap();
+    if capture_session.is_some() {
+        info!("recording the pool session to {:?}", capture_session);
+        config.pool_conf.capture_session = capture_session;
+    }
+
+    if let Some(seed) = config.worker_conf.deterministic_seed {
+        info!("deterministic mode, seed {}", seed);
+        determinism::set_seed(seed);
+        if config.worker_conf.auto_tune {
+            //the bandit draws arms from its own random number generator
+            warn!("auto tuning is disabled in deterministic mode, using num_threads");
+            config.worker_conf.auto_tune = false;
+        }
+    }
+
+    println!("{}", banner::render(&config));
 
-    sanity_check(config.hw_conf.aes_support);
+    let cpu_topology = topology::detect();
+    match cpu_topology {
+        Some(ref detected) => {
+            let placement = if config.hw_conf.thread_affinity {
+                Some(topology::plan(detected, config.worker_conf.num_threads, config.hw_conf.skip_efficiency_cores))
+            } else {
+                None
+            };
+            println!("{}", topology::report(detected, placement.as_ref()));
+        },
+        None => if config.hw_conf.thread_affinity {
+            warn!("cpu topology not available, worker threads are not pinned");
+        }
+    }
+
+    if let Err(err) = sanity_check(config.hw_conf.aes_support) {
+        error!("{}", err);
+        process::exit(1);
+    }
 
     if config.donation_conf.percentage > 0.0 {
         print_donation_hint(config.donation_conf.percentage);
     }
 
+    //a downloaded update is placed next to the running binary
+    let download_dir = env::current_exe().ok().and_then(|exe| exe.parent().map(|p| p.to_path_buf()))
+        .unwrap_or_else(|| Path::new(".").to_path_buf());
+    update::spawn_check(config.update_conf.clone(), download_dir);
+
+    for miner in cpu_share::other_miners() {
+        if miner.name != "mithril" {
+            warn!("{} (pid {}) is mining on this machine too, lower num_threads to avoid oversubscribing the cores", miner.name, miner.pid);
+        } else if !config.cpu_share_conf.enabled {
+            warn!("another mithril instance (pid {}) is running, enable [cpu_share] to split the cores", miner.pid);
+        }
+    }
+    let cpu_share = if config.cpu_share_conf.enabled {
+        let _ = bandit_tools::ensure_mithril_folder_exists();
+        let socket_path = cpu_share::socket_path(&config.cpu_share_conf, &bandit_tools::mithril_folder());
+        match CpuShare::start(&socket_path, num_cpus::get()) {
+            Ok(share) => Some(share),
+            Err(e) => {
+                warn!("cpu sharing not started: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
     let mut bandit = if config.worker_conf.auto_tune {
         Some(bandit_tools::setup_bandit(config.worker_conf.auto_tune_log.clone()))
     } else {
@@ -54,28 +299,152 @@ fn main() {
     };
 
     let timer_rx = timer::setup(&config.worker_conf, &config.donation_conf);
+    //warmup only once on startup, not on every restart of the worker pool
+    let mut warmup = config.worker_conf.warmup_seconds.map(Duration::from_secs);
+
+    let mut event_log = EventLog::new();
+    let console_rx = match console::start() {
+        Ok(rx) => {
+            trace::install_switch();
+            rx
+        },
+        Err(e) => {
+            warn!("console input not available: {}", e);
+            channel().1
+        }
+    };
+
+    if !config.supervisor_conf.enabled {
+        mine(&config, &cpu_topology, &cpu_share, &mut bandit, &timer_rx, &console_rx, &mut warmup, &mut event_log);
+        return;
+    }
+    let mut supervisor = Supervisor::new(config.supervisor_conf.clone());
+    loop {
+        let reason = match supervisor::catch_panic(|| mine(&config, &cpu_topology, &cpu_share, &mut bandit, &timer_rx, &console_rx, &mut warmup, &mut event_log)) {
+            Ok(()) => return,
+            Err(reason) => reason
+        };
+        event_log.record_connection(ConnectionEvent::Disconnected{reason: format!("pipeline failed: {}", reason)});
+        info!("recent pool events\n{}", event_log.render(RECENT_EVENTS));
+        match supervisor.record_failure(Instant::now()) {
+            Decision::Restart(backoff) => {
+                error!("mining pipeline failed: {}, restart {} of {} within {} minutes in {} seconds", reason, supervisor.restarts(),
+                    config.supervisor_conf.max_restarts, config.supervisor_conf.window_minutes, backoff.as_secs());
+                thread::sleep(backoff);
+            },
+            Decision::GiveUp => {
+                let alert = format!("mining pipeline failed more than {} times within {} minutes, last error: {}",
+                    config.supervisor_conf.max_restarts, config.supervisor_conf.window_minutes, reason);
+                error!("{}, stopping", alert);
+                supervisor.alert(&alert);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+/// The mining pipeline: pool connections, worker threads and the main event loop. It only
+/// returns by panicking, the supervisor (if enabled) starts it again.
+fn mine(config: &MithrilConfig,
+    cpu_topology: &Option<Topology>,
+    cpu_share: &Option<CpuShare>,
+    bandit: &mut Option<bandit::softmax::AnnealingSoftmax<bandit_tools::ThreadArm>>,
+    timer_rx: &Receiver<timer::TickAction>,
+    console_rx: &Receiver<ConsoleCmd>,
+    warmup: &mut Option<Duration>,
+    event_log: &mut EventLog) {
+
     let mut donation_hashing = false;
 
+    let mut failover = Failover::new(&config.pool_conf.pool_address,
+        &config.pool_conf.failover_pool_addresses, config.pool_conf.failover_max_failures);
+    //runs as long as the pipeline, ticks are ignored while the primary pool is used
+    let (_probe_stop_tx, probe_stop_rx) = channel();
+    let (probe_rx, _) = stratum::start_tick_thread(Duration::from_secs(config.pool_conf.failover_probe_minutes * 60), Duration::from_secs(0), probe_stop_rx);
+    //same for the job watchdog, its ticks also trigger the share estimate log and the schedule check
+    let (_watchdog_stop_tx, watchdog_stop_rx) = channel();
+    let (watchdog_rx, _) = stratum::start_tick_thread(Duration::from_secs(JOB_WATCHDOG_CHECK_SECS), Duration::from_secs(0), watchdog_stop_rx);
+
+    let mut share_log = match config.accounting_conf.share_log {
+        Some(ref path) => match ShareLog::open(Path::new(path)) {
+            Ok(log) => Some(log),
+            Err(e) => {
+                warn!("share log {} not opened, shares are not accounted: {}", path, e);
+                None
+            }
+        },
+        None => None
+    };
+
+    //shares found while the connection was down, for the next connection to the same pool
+    let offline_shares = Arc::new(OfflineShares::new(config.pool_conf.offline_share_capacity as usize,
+        Duration::from_secs(config.pool_conf.offline_share_max_age_secs)));
+    //switched with the `d` console command, kept for the next connections
+    let mut dry_run = config.pool_conf.dry_run;
+
     loop {
         //Stratum start
         let (stratum_tx, stratum_rx) = channel();
         let (client_err_tx, client_err_rx) = channel();
 
-        let conf = if donation_hashing {
-            mithril_config::donation_conf()
+        //the schedule is not followed while donating
+        let scheduled_window = if donation_hashing { None } else { config.schedule_conf.active(schedule::local_minute_of_day()) };
+        let mut conf = if donation_hashing {
+            info!("donation hashing active: mining to the donation pool {} for the next {}% of the time",
+                config.donation_conf.pool_address, config.donation_conf.percentage);
+            config.donation_conf.pool_config()
+        } else if let Some(window) = scheduled_window {
+            let pool_conf = config.schedule_conf.pool_config(&config.pool_conf, Some(window));
+            info!("schedule window {} active: mining to {}", config.schedule_conf.windows[window].describe(), pool_conf.pool_address);
+            pool_conf
         } else {
-            config.pool_conf.clone()
+            let mut pool_conf = config.pool_conf.clone();
+            pool_conf.pool_address = failover.active_address().to_string();
+            pool_conf
         };
 
-        let login_result = StratumClient::login(conf, client_err_tx, stratum_tx);
-        if login_result.is_err() {
-            error!("stratum login failed {:?}", login_result.err());
-            await_timeout();
-            continue;
+        if !donation_hashing {
+            conf.dry_run = dry_run;
         }
-        let client = login_result.expect("stratum client");
+        let watchdog = JobWatchdog{tick_rx: &watchdog_rx, timeout: conf.job_timeout_secs.map(Duration::from_secs)};
+        let clamp_easy_targets = conf.clamp_easy_targets;
+        let address = match conf.solo_daemon_address {
+            Some(ref daemon_address) if !donation_hashing => daemon_address.clone(),
+            _ => conf.pool_address.clone()
+        };
+        //only single pool connections keep their shares, not while donating
+        let offline_address = if donation_hashing || conf.offline_share_capacity == 0
+            || conf.solo_daemon_address.is_some() || conf.secondary_pool_address.is_some() {
+            None
+        } else {
+            Some(conf.pool_address.clone())
+        };
+        let login_result = match conf.solo_daemon_address.clone() {
+            Some(daemon_address) if !donation_hashing => SoloClient::start(conf, daemon_address, client_err_tx, stratum_tx).map(PoolClient::Solo),
+            _ => login_stratum(conf, client_err_tx, stratum_tx)
+        };
+        let client = match login_result {
+            Ok(client) => client,
+            Err(err) => {
+                error!("stratum login failed {:?}", err);
+                event_log.record_connection(ConnectionEvent::ConnectFailed{address, reason: err.to_string()});
+                if !donation_hashing {
+                    record_pool_failure(&mut failover, event_log);
+                }
+                await_timeout();
+                continue;
+            }
+        };
+        if let Some(ref mut log) = share_log {
+            log.connected(&address);
+        }
+        event_log.record_connection(ConnectionEvent::Connected{address});
 
         let share_tx = client.new_cmd_channel();
+        let share_queue = client.share_queue();
+        if let Some(ref pool_address) = offline_address {
+            share_queue.keep_offline(offline_shares.clone(), pool_address);
+        }
 
         let (arm, num_threads) = if bandit.is_some() {
             let selected_arm = bandit.as_ref().unwrap().select_arm();
@@ -84,25 +453,86 @@ fn main() {
         } else {
             (None, config.worker_conf.num_threads)
         };
+        let shared_cores = cpu_share.as_ref().map(|share| share.cores());
+        let limited_threads = match (cpu_share, shared_cores) {
+            (&Some(ref share), Some(cores)) => cpu_share::limit_threads(num_threads, cores, share.total()),
+            _ => num_threads
+        };
+        if limited_threads < num_threads {
+            info!("using {} of {} threads for the share of {} cores", limited_threads, num_threads, shared_cores.unwrap_or(0));
+        }
+        let threads_limited = limited_threads < num_threads;
+        let groups = if config.worker_groups.is_empty() {
+            vec![WorkerGroup::all(limited_threads)]
+        } else {
+            worker_pool::limit_groups(&config.worker_groups, limited_threads)
+        };
+        let num_threads : u64 = groups.iter().map(|g| g.num_threads).sum();
+        let placement = match *cpu_topology {
+            Some(ref detected) if config.hw_conf.thread_affinity => Some(topology::plan(detected, num_threads, config.hw_conf.skip_efficiency_cores)),
+            _ => None
+        };
+        let groups = match placement {
+            Some(ref placement) if placement.skipped > 0 => {
+                info!("{} threads not started, only efficiency cores were left for them", placement.skipped);
+                worker_pool::limit_groups(&groups, num_threads - placement.skipped)
+            },
+            _ => groups
+        };
+        let num_threads : u64 = groups.iter().map(|g| g.num_threads).sum();
+        let affinity : &[usize] = placement.as_ref().map(|p| &p.cpus[..]).unwrap_or(&[]);
 
-        let (metric_tx, metric_rx) = channel();
-        let metric = metric::start(config.metric_conf.clone(), metric_rx);
-
-        //worker pool start
-        let pool = worker_pool::start(num_threads, config.hw_conf.clone().aes_support,
-            &share_tx, config.metric_conf.resolution, &metric_tx.clone());
+        let counters = Arc::new(HashCounters::new(num_threads as usize));
+        let coverage = Arc::new(NonceCoverage::new());
+        let metric = metric::start(config.metric_conf.clone(), counters.clone());
 
-        let term_result = start_main_event_loop(&pool, &client_err_rx, &stratum_rx, &timer_rx);
+        //worker pool settings, the pool is started below together with the main loop
+        let worker_options = worker_pool::WorkerOptions{
+            max_job_age: config.worker_conf.max_job_age_seconds.map(Duration::from_secs),
+            warmup: warmup.take(),
+            nonce_strategy: config.worker_conf.nonce_strategy,
+            background_level: config.worker_conf.background_level,
+            randomx_full_dataset: config.worker_conf.randomx_full_dataset
+        };
+        //failover pools stand in for the [pool] address only
+        let pool_failover = if donation_hashing || scheduled_window.is_some() { None } else { Some(&mut failover) };
+        let schedule_check = if donation_hashing || config.schedule_conf.is_empty() {
+            None
+        } else {
+            Some(ScheduleCheck{schedule: &config.schedule_conf, active: scheduled_window})
+        };
+        let cpu_share_check = match (cpu_share, shared_cores) {
+            (&Some(ref share), Some(cores)) => Some(CpuShareCheck{share, cores}),
+            _ => None
+        };
+        //a panic while starting, running or stopping the workers ends this connection only after
+        //the client was stopped, the dropped pool stops its threads while unwinding
+        let term_result = supervisor::catch_panic(|| -> Result<(MainLoopExit, WorkerPool), MithrilError> {
+            let mut pool = worker_pool::start_groups(&groups, affinity, config.hw_conf.clone().aes_support,
+                &share_tx, &share_queue, config.metric_conf.resolution, &counters, &coverage, worker_options)?;
+            pool.set_algorithm_profiles(config.worker_conf.algorithm_profiles.clone());
+            let loop_result = start_main_event_loop(&pool, &share_tx, &share_queue, &client_err_rx, &stratum_rx, timer_rx, &probe_rx, console_rx, &watchdog, schedule_check.as_ref(), cpu_share_check.as_ref(), &counters, &coverage, &mut *event_log, share_log.as_mut(), pool_failover, &mut dry_run, clamp_easy_targets);
+            pool.stop();
+            loop_result.map(|ex| (ex, pool))
+        });
 
-        pool.stop();
         client.stop();
+        let term_result = term_result.unwrap_or_else(|reason| {
+            metric.stop();
+            panic!("worker pipeline: {}", reason)
+        });
 
         match term_result {
             Err(err) => {
+                metric.stop();
+                metric.join();
+                event_log.record_connection(ConnectionEvent::Disconnected{reason: err.to_string()});
                 error!("error received, restarting connection after 60 seconds. err was {}", err);
+                info!("recent pool events\n{}", event_log.render(RECENT_EVENTS));
                 await_timeout();
             },
-            Ok(ex) => {
+            Ok((ex, pool)) => {
+                event_log.record_connection(ConnectionEvent::Disconnected{reason: format!("restart ({:?})", ex)});
                 info!("main loop exit, next loop {:?}", ex);
                 pool.join();
 
@@ -110,8 +540,12 @@ fn main() {
                 let hashes = metric.hash_count();
                 metric.join();
 
-                if arm.is_some() && bandit.is_some() && !donation_hashing {
+                if arm.is_some() && bandit.is_some() && !donation_hashing && !threads_limited &&
+                    ex != MainLoopExit::ProbePrimaryPool && ex != MainLoopExit::ScheduleChange && ex != MainLoopExit::CpuShareChange &&
+                    ex != MainLoopExit::PoolUnhealthy {
                     //do not save reward for donation hashing, it probably only runs for a short period
+                    //(same for an interval cut short by probing the primary pool, a schedule change, a new cpu share or a failover,
+                    //and for an arm that did not get all of its threads)
                     let bandit_ref = bandit.as_mut().unwrap();
                     let reward = (hashes as f64 / (config.worker_conf.auto_tune_interval_minutes as f64 * 60.0)) / 1000.0; /*kH/s*/
                     info!("adding reward {:?} for arm {:?}", reward, arm);
@@ -119,12 +553,227 @@ fn main() {
                     save_bandit_state(bandit_ref);
                 }
 
+                if ex == MainLoopExit::ProbePrimaryPool {
+                    let from = failover.active_address().to_string();
+                    if failover.probe_primary() {
+                        event_log.record_connection(ConnectionEvent::Failover{from, to: failover.active_address().to_string()});
+                    }
+                }
+                if ex == MainLoopExit::DonationEnd {
+                    info!("donation hashing ended, thank you! Mining continues on {}", failover.active_address());
+                }
                 donation_hashing = ex == MainLoopExit::DonationHashing;
             }
         }
     }
 }
 
+fn run_subcommand(args: &[String]) {
+    match args[0].as_ref() {
+        "genconfig" => {
+            let fleet = arg_value(args, "--fleet");
+            let template = arg_value(args, "--template");
+            let out = arg_value(args, "--out").unwrap_or_else(|| ".".to_string());
+            if fleet.is_none() || template.is_none() {
+                exit_with_usage();
+            }
+            match fleet::generate(Path::new(&fleet.unwrap()), Path::new(&template.unwrap()), Path::new(&out)) {
+                Ok(n) => println!("{} config files written to {}", n, out),
+                Err(e) => {
+                    eprintln!("generating configs failed: {}", e);
+                    process::exit(1);
+                }
+            }
+        },
+        "harness" => {
+            let num_hashes = arg_value(args, "--hashes").map(|h| h.parse::<u64>().unwrap_or_else(|_| exit_with_usage())).unwrap_or(1000);
+            let aes_support = if args.iter().any(|a| a == "--sw-aes") { AESSupport::SW } else { AESSupport::HW };
+
+            println!("{}", harness::environment_fingerprint(aes_support));
+            let result = harness::run(num_hashes, aes_support);
+            println!("pinned: {}, hashes: {}, elapsed: {:?}, last hash: {}",
+                result.pinned, result.num_hashes, result.elapsed, result.last_hash);
+            println!("{:.2} H/s", result.hashes_per_second());
+            println!("final hash selector distribution (blake, groestl, jh, skein): {:?}", result.stats.selector_counts);
+            if result.stats.selector_biased() {
+                println!("WARNING: final hash selector distribution is biased, the hash implementation is likely broken");
+            }
+            let biased_bits = result.stats.biased_bits();
+            if !biased_bits.is_empty() {
+                println!("WARNING: hash output bits {:?} are biased, the hash implementation is likely broken", biased_bits);
+            }
+        },
+        "membench" => {
+            let max_threads = arg_value(args, "--threads").map(|t| t.parse::<usize>().unwrap_or_else(|_| exit_with_usage())).unwrap_or_else(num_cpus::get);
+            let rounds = arg_value(args, "--rounds").map(|r| r.parse::<u64>().unwrap_or_else(|_| exit_with_usage())).unwrap_or(100);
+            let aes_support = if args.iter().any(|a| a == "--sw-aes") { AESSupport::SW } else { AESSupport::HW };
+
+            println!("{}", harness::environment_fingerprint(aes_support));
+            let samples = harness::scratchpad_bandwidth(max_threads, rounds, aes_support);
+            for sample in &samples {
+                println!("{:>3} threads: {:>10.1} MB/s", sample.num_threads, sample.bytes_per_second / 1_000_000.0);
+            }
+            match harness::saturation_point(&samples) {
+                Some(n) => println!("memory saturated at {} threads", n),
+                None => println!("no saturation up to {} threads", max_threads)
+            }
+        },
+        "import-config" => {
+            let xmrig_file = match args.get(1) {
+                Some(file) if !file.starts_with("--") => file.clone(),
+                _ => exit_with_usage()
+            };
+            let config = match xmrig_import::import(Path::new(&xmrig_file)) {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("importing {} failed: {}", xmrig_file, e);
+                    process::exit(1);
+                }
+            };
+            match arg_value(args, "--out") {
+                Some(out) => {
+                    if let Err(e) = fs::write(&out, config) {
+                        eprintln!("writing {} failed: {}", out, e);
+                        process::exit(1);
+                    }
+                    println!("mithril config written to {}", out);
+                },
+                None => print!("{}", config)
+            }
+        },
+        "setup-hugepages" => {
+            let num_threads = match arg_value(args, "--threads") {
+                Some(t) => t.parse::<u64>().unwrap_or_else(|_| exit_with_usage()),
+                None => configured_threads()
+            };
+            let status = hugepages::read_status().unwrap_or_else(|e| {
+                eprintln!("reading huge page status failed: {}", e);
+                process::exit(1);
+            });
+            let required = hugepages::required_pages(num_threads);
+            let target = hugepages::target_pages(required, &status);
+            println!("{} worker threads need {} huge pages of {} kB, reserved: {}, free: {}",
+                num_threads, required, hugepages::HUGE_PAGE_KB, status.total, status.free);
+
+            let status = if target == status.total {
+                status
+            } else if args.iter().any(|a| a == "--apply") {
+                println!("setting vm.nr_hugepages to {}", target);
+                if let Err(e) = hugepages::apply(target) {
+                    eprintln!("setting vm.nr_hugepages failed: {}", e);
+                    process::exit(1);
+                }
+                hugepages::read_status().unwrap_or_else(|e| {
+                    eprintln!("reading huge page status failed: {}", e);
+                    process::exit(1);
+                })
+            } else {
+                println!("run these commands (or setup-hugepages --apply) to reserve them:");
+                for command in hugepages::commands(target) {
+                    println!("    {}", command);
+                }
+                return;
+            };
+
+            let problems = hugepages::verify(required, &status);
+            if problems.is_empty() {
+                println!("OK: {} of {} reserved huge pages free", status.free, status.total);
+                println!("to keep the reservation after a reboot: {}", hugepages::commands(status.total)[1]);
+            } else {
+                for problem in &problems {
+                    println!("WARNING: {}", problem);
+                }
+                process::exit(1);
+            }
+        },
+        "soak" => {
+            let pool_address = arg_value(args, "--pool").unwrap_or_else(|| exit_with_usage());
+            let iterations = arg_value(args, "--iterations").map(|i| i.parse::<u64>().unwrap_or_else(|_| exit_with_usage())).unwrap_or(100);
+            let pause_ms = arg_value(args, "--pause-ms").map(|p| p.parse::<u64>().unwrap_or_else(|_| exit_with_usage())).unwrap_or(500);
+
+            let mut pool_conf = mithril_config::donation_conf();
+            pool_conf.pool_address = pool_address;
+            if let Some(wallet) = arg_value(args, "--wallet") {
+                pool_conf.wallet_address = wallet;
+            }
+            let result = soak::run(&pool_conf, iterations, Duration::from_millis(pause_ms));
+            println!("iterations: {}, failed logins: {}", result.iterations, result.failed_logins);
+            match (result.baseline, result.after) {
+                (Some(baseline), Some(after)) => println!("after first iteration: {:?}\nat end: {:?}", baseline, after),
+                _ => println!("process stats not supported on this platform")
+            }
+            let leaks = result.leaks();
+            for leak in &leaks {
+                println!("WARNING: possible leak, {}", leak);
+            }
+            if !leaks.is_empty() {
+                process::exit(1);
+            }
+        },
+        "export-shares" => {
+            let log = arg_value(args, "--log").or_else(configured_share_log).unwrap_or_else(|| exit_with_usage());
+            let pool = arg_value(args, "--pool");
+            let (entries, invalid) = accounting::read_log(Path::new(&log)).unwrap_or_else(|e| {
+                eprintln!("reading share log {} failed: {}", log, e);
+                process::exit(1);
+            });
+            if invalid > 0 {
+                eprintln!("WARNING: {} lines of {} could not be read", invalid, log);
+            }
+            let entries : Vec<accounting::ShareEntry> = entries.into_iter()
+                .filter(|e| pool.as_ref().map(|p| *p == e.pool_address).unwrap_or(true)).collect();
+            print!("{}", accounting::render_csv(&accounting::summarize(&entries)));
+        },
+        _ => exit_with_usage()
+    }
+}
+
+/// returns the value following `name` in the argument list
+fn arg_value(args: &[String], name: &str) -> Option<String> {
+    args.iter().position(|a| a == name).and_then(|ix| args.get(ix + 1)).cloned()
+}
+
+/// Threads the config in the working directory mines with, the largest
+/// thread count auto tuning can choose if it is enabled
+fn configured_threads() -> u64 {
+    let cwd_path = &format!("{}{}", "./", mithril_config::CONFIG_FILE_NAME);
+    match mithril_config::read_config(Path::new(cwd_path), mithril_config::CONFIG_FILE_NAME) {
+        Ok(ref config) if config.worker_conf.auto_tune => bandit_tools::max_threads(),
+        Ok(config) => config.worker_conf.num_threads,
+        Err(e) => {
+            eprintln!("reading {} failed ({}), use --threads", mithril_config::CONFIG_FILE_NAME, e);
+            process::exit(1);
+        }
+    }
+}
+
+/// The share log of the config in the working directory, if set
+fn configured_share_log() -> Option<String> {
+    let cwd_path = &format!("{}{}", "./", mithril_config::CONFIG_FILE_NAME);
+    mithril_config::read_config(Path::new(cwd_path), mithril_config::CONFIG_FILE_NAME).ok()
+        .and_then(|config| config.accounting_conf.share_log)
+}
+
+fn exit_with_usage() -> ! {
+    eprintln!("usage: mithril [--capture-session <file.jsonl>]");
+    eprintln!("       mithril [genconfig --fleet <hosts.txt> --template <base.toml> [--out <dir>]]");
+    eprintln!("       mithril [harness [--hashes <n>] [--sw-aes]]");
+    eprintln!("       mithril [membench [--threads <n>] [--rounds <n>] [--sw-aes]]");
+    eprintln!("       mithril [import-config <xmrig.json> [--out <config.toml>]]");
+    eprintln!("       mithril [soak --pool <host:port> [--iterations <n>] [--pause-ms <n>] [--wallet <address>]]");
+    eprintln!("       mithril [setup-hugepages [--threads <n>] [--apply]]");
+    eprintln!("       mithril [export-shares [--log <shares.csv>] [--pool <host:port>]]");
+    process::exit(2);
+}
+
+fn account_share(share_log: &mut Option<&mut ShareLog>, job_id: &str, result: ShareResult) {
+    if let Some(ref mut log) = *share_log {
+        if let Err(e) = log.record_share(job_id, result) {
+            warn!("writing the share log failed: {}", e);
+        }
+    }
+}
+
 fn await_timeout() {
     thread::sleep(Duration::from_secs(60))
 }
@@ -143,9 +792,38 @@ fn save_bandit_state(bandit: &mut bandit::softmax::AnnealingSoftmax<bandit_tools
 
 /// This function terminates if a non-recoverable error was detected (i.e. connection lost)
 fn start_main_event_loop(pool: &WorkerPool,
-    client_err_rx: &Receiver<Error>,
-    stratum_rx: &Receiver<StratumAction>,
-    timer_rx: &Receiver<timer::TickAction>) -> io::Result<MainLoopExit> {
+    share_tx: &Sender<stratum::StratumCmd>,
+    share_queue: &ShareQueue,
+    client_err_rx: &Receiver<StratumError>,
+    stratum_rx: &Receiver<StratumEvent>,
+    timer_rx: &Receiver<timer::TickAction>,
+    probe_rx: &Receiver<stratum::Tick>,
+    console_rx: &Receiver<ConsoleCmd>,
+    watchdog: &JobWatchdog,
+    schedule_check: Option<&ScheduleCheck>,
+    cpu_share_check: Option<&CpuShareCheck>,
+    counters: &HashCounters,
+    coverage: &NonceCoverage,
+    event_log: &mut EventLog,
+    mut share_log: Option<&mut ShareLog>,
+    mut failover: Option<&mut Failover>,
+    dry_run: &mut bool,
+    clamp_easy_targets: bool) -> Result<MainLoopExit, MithrilError> {
+
+    let mut job_received = false;
+    let mut last_job = Instant::now();
+    let mut rejected_shares : u64 = 0;
+    //per connection of the multi pool client (None: single pool clients)
+    let mut nonce_prefixes : HashMap<Option<usize>, Vec<u8>> = HashMap::new();
+    let mut last_seq : Option<u64> = None;
+    let mut sampler = Sampler::new(counters);
+    let mut coverage_start = coverage.snapshot();
+    let mut estimate_start = Instant::now();
+    let mut shares_found : u64 = 0;
+    let mut current_difficulty : Option<u64> = None;
+    let mut current_job_id : Option<String> = None;
+    //the workers of a job report its expiry one by one, it is logged once
+    let mut last_expired_job : Option<String> = None;
 
     let select = Select::new();
     let mut err_hnd = select.handle(client_err_rx);
@@ -154,17 +832,116 @@ fn start_main_event_loop(pool: &WorkerPool,
     unsafe {rcv_hnd.add()};
     let mut clock_hnd = select.handle(timer_rx);
     unsafe {clock_hnd.add()};
+    let mut probe_hnd = select.handle(probe_rx);
+    unsafe {probe_hnd.add()};
+    let mut watchdog_hnd = select.handle(watchdog.tick_rx);
+    unsafe {watchdog_hnd.add()};
+    let mut console_hnd = select.handle(console_rx);
+    unsafe {console_hnd.add()};
+    let mut worker_hnd = select.handle(pool.events());
+    unsafe {worker_hnd.add()};
 
     loop {
         let id = select.wait();
         if id == rcv_hnd.id() {
             let received = rcv_hnd.recv();
             if received.is_err() {
-                return Err(io::Error::new(io::ErrorKind::ConnectionAborted, "received error"));
+                return Err(MithrilError::Stratum(StratumError::ChannelClosed));
             }
-            match received.unwrap() {
-                StratumAction::Job{miner_id, blob, job_id, target} => {
-                    pool.job_change(&miner_id, &blob, &job_id, &target);
+            let event = received.unwrap();
+            if let Some(last) = last_seq {
+                if event.seq != last + 1 {
+                    warn!("stratum event {} received after {}, events were reordered or lost", event.seq, last);
+                }
+            }
+            last_seq = Some(event.seq);
+            debug!("stratum event {} dispatched {:?} after receive", event.seq, event.received.elapsed());
+            match event.action {
+                StratumAction::Job{miner_id, blob, job_id, mut target, difficulty: mut job_difficulty, algo, height, seed_hash, ..} => {
+                    let hash_version = match algo {
+                        Some(ref algo) => match HashVersion::from_algo(algo) {
+                            Some(version) => version,
+                            None => {
+                                error!("pool requested unsupported algorithm {} for job {}, job ignored", algo, job_id);
+                                continue;
+                            }
+                        },
+                        None => HashVersion::V8
+                    };
+                    if hash_version == HashVersion::VersionR && height.is_none() {
+                        error!("cryptonight-r job {} without block height, job ignored", job_id);
+                        continue;
+                    }
+                    if hash_version == HashVersion::RandomX && seed_hash.is_none() {
+                        error!("randomx job {} without seed hash, job ignored", job_id);
+                        continue;
+                    }
+                    if let Some(ref mut failover) = failover {
+                        if !job_received {
+                            info!("receiving jobs from pool {}", failover.active_address());
+                            failover.record_success();
+                        }
+                    }
+                    if !job_received {
+                        event_log.record_connection(ConnectionEvent::LoginOk);
+                    }
+                    if clamp_easy_targets && job_difficulty < difficulty::MIN_JOB_DIFFICULTY {
+                        if let Ok(value) = difficulty::parse_target(&target) {
+                            let clamped = difficulty::clamp_target(value);
+                            warn!("target {} of job {} clamped to difficulty {}", target, job_id, difficulty::MIN_JOB_DIFFICULTY);
+                            target = difficulty::target_hex(clamped);
+                            job_difficulty = difficulty::target_difficulty(clamped);
+                        }
+                    }
+                    debug!("job {} with difficulty {}", job_id, difficulty::format_difficulty(job_difficulty));
+                    if let Some(height) = height {
+                        debug!("job {} for block height {}", job_id, height);
+                    }
+                    job_received = true;
+                    last_job = Instant::now();
+                    current_difficulty = Some(job_difficulty);
+                    current_job_id = Some(job_id.clone());
+                    event_log.record_job(&job_id, &target);
+                    if let Some(ref mut log) = share_log {
+                        log.record_job(&job_id, job_difficulty);
+                    }
+                    if share_tx.send(stratum::StratumCmd::JobReceived{job_id: job_id.clone(), target: target.clone()}).is_err() {
+                        info!("stratum client already stopped");
+                    }
+                    let nonce_prefix = nonce_prefixes.get(&event.connection).cloned().unwrap_or_default();
+                    pool.job_change(event.connection, &miner_id, &blob, &job_id, &target, &nonce_prefix, hash_version, height.unwrap_or(0), seed_hash.as_ref().map(|s| s.as_str()), event.received)?;
+                    //kept shares are only valid if the pool resumed the session and their job
+                    if let Some(salvage) = share_queue.take_offline(&miner_id, &job_id) {
+                        let salvaged = salvage.shares.len();
+                        for share in salvage.shares {
+                            if let Err(e) = share_queue.submit(share_tx, share) {
+                                warn!("share of the previous connection not submitted: {:?}", e);
+                            }
+                        }
+                        info!("{} shares of the previous connection submitted again, {} discarded as stale", salvaged, salvage.discarded);
+                    }
+                },
+                StratumAction::ExtranonceSet{nonce_prefix: prefix} => {
+                    info!("pool reserved nonce prefix {:?} for the following jobs", prefix);
+                    nonce_prefixes.insert(event.connection, prefix);
+                },
+                StratumAction::JobCancelled{job_id} => {
+                    pool.cancel_job(event.connection, job_id.as_ref().map(|id| id.as_str()));
+                    //a client records the cancel_job of its pool when receiving it, the command covers
+                    //cancellations that did not come from the pool (e.g. a failover)
+                    if let Some(job_id) = job_id.or_else(|| current_job_id.clone()) {
+                        warn!("pool cancelled job {}, pending shares for it are dropped", job_id);
+                        if share_tx.send(stratum::StratumCmd::CancelJob{job_id}).is_err() {
+                            info!("stratum client already stopped");
+                        }
+                    }
+                },
+                StratumAction::SelectTemplate{job_id, ..} => {
+                    error!("self-select job {} received, but no self_select_daemon_address is configured, job ignored", job_id);
+                },
+                StratumAction::LoginRejected{reason} => {
+                    event_log.record_connection(ConnectionEvent::LoginRejected{reason: reason.clone()});
+                    error!("login rejected by the pool: {}, check wallet_address and pool_password", reason);
                 },
                 StratumAction::Error{err} => {
                     error!("Received stratum error: {}", err);
@@ -174,15 +951,70 @@ fn start_main_event_loop(pool: &WorkerPool,
                 },
                 StratumAction::KeepAliveOk => {
                     info!("Received keep alive ok");
+                },
+                StratumAction::Stats{stats, latency, score, socket} => {
+                    stratum::log_share_stats(&stats);
+                    info!("pool score {} (accept latency {:?}, {:.1}% rejected, last job {:?} ago)",
+                        score.score, score.accept_latency, score.reject_rate * 100.0, score.job_age);
+                    if let Some(report) = latency.render() {
+                        info!("{}", report);
+                    }
+                    if let Some(socket) = socket {
+                        info!("connection stats: {:?}", socket);
+                    }
+                },
+                StratumAction::ShareAccepted{job_id} => {
+                    info!("Share for job {} accepted", job_id);
+                    shares_found += 1;
+                    account_share(&mut share_log, &job_id, ShareResult::Accepted);
+                    event_log.record_share(&job_id, None);
+                },
+                StratumAction::ShareRejected{job_id, reason, code} => {
+                    rejected_shares += 1;
+                    shares_found += 1;
+                    let kind = RejectKind::from_reason(&reason);
+                    warn!("Share for job {} rejected ({:?}): {} (code {}), {} shares rejected on this connection",
+                        job_id, kind, reason, code, rejected_shares);
+                    if kind == RejectKind::Stale {
+                        warn!("job {} is stale, the pool probably moved on to a new block", job_id);
+                    }
+                    account_share(&mut share_log, &job_id, if kind == RejectKind::Stale { ShareResult::Stale } else { ShareResult::Rejected });
+                    event_log.record_share(&job_id, Some(reason));
+                    if event_log.reject_streak() == REJECT_STREAK_LOG {
+                        warn!("{} shares rejected in a row, recent pool events\n{}", REJECT_STREAK_LOG, event_log.render(RECENT_EVENTS));
+                    }
+                },
+                StratumAction::PoolUnhealthy{reason} => {
+                    warn!("pool unhealthy: {}", reason);
+                    //the multi pool client keeps the other pool connected anyway
+                    if event.connection.is_none() {
+                        if let Some(ref mut failover) = failover {
+                            let from = failover.active_address().to_string();
+                            if failover.record_unhealthy() {
+                                event_log.record_connection(ConnectionEvent::Failover{from, to: failover.active_address().to_string()});
+                                return Ok(MainLoopExit::PoolUnhealthy);
+                            }
+                        }
+                    }
+                    //without another pool the flood guard keeps probing this one
+                },
+                StratumAction::ShareWithheld{job_id} => {
+                    shares_found += 1;
+                    event_log.record_share(&job_id, Some("withheld, backing off from the pool".to_string()));
                 }
             }
         } else if id == err_hnd.id() {
             let err_received = client_err_rx.recv();
-            return Err(io::Error::new(io::ErrorKind::Other, format!("error received {:?}", err_received)));
+            if !job_received {
+                if let Some(ref mut failover) = failover {
+                    record_pool_failure(failover, event_log);
+                }
+            }
+            return Err(MithrilError::Stratum(err_received.unwrap_or(StratumError::ChannelClosed)));
         } else if id == clock_hnd.id() {
             let clock_res = timer_rx.recv();
             if clock_res.is_err() {
-                return Err(io::Error::new(io::ErrorKind::Other, format!("error received {:?}", clock_res)));
+                return Err(MithrilError::Io(io::Error::new(io::ErrorKind::Other, format!("error received {:?}", clock_res))));
             } else {
                 let tick_action = clock_res.expect("tickAction");
                 match tick_action {
@@ -192,14 +1024,100 @@ fn start_main_event_loop(pool: &WorkerPool,
                     },
                     timer::TickAction::DonationHashing => {
                         return Ok(MainLoopExit::DonationHashing)
+                    },
+                    timer::TickAction::DonationEnd => {
+                        return Ok(MainLoopExit::DonationEnd)
+                    }
+                }
+            }
+        } else if id == probe_hnd.id() {
+            let probe_res = probe_rx.recv();
+            let failed_over = failover.as_ref().map_or(false, |f| f.is_failed_over());
+            if probe_res.is_ok() && failed_over {
+                return Ok(MainLoopExit::ProbePrimaryPool)
+            }
+        } else if id == worker_hnd.id() {
+            match pool.events().recv() {
+                Ok(WorkerEvent::JobExpired{job_id, age, ..}) => {
+                    if last_expired_job.as_ref() != Some(&job_id) {
+                        warn!("job {} expired (received {}s ago), workers idle until the next job", job_id, age.as_secs());
+                        last_expired_job = Some(job_id);
+                    }
+                },
+                Err(_) => unsafe {worker_hnd.remove()} //all workers ended
+            }
+        } else if id == console_hnd.id() {
+            match console_rx.recv() {
+                Ok(ConsoleCmd::ConnectionHistory) => println!("{}", event_log.render_connections(RECENT_CONNECTION_EVENTS)),
+                Ok(ConsoleCmd::Events) => println!("{}", event_log.render(RECENT_EVENTS)),
+                Ok(ConsoleCmd::ToggleDryRun) => {
+                    *dry_run = !*dry_run;
+                    println!("share submission dry run {}", if *dry_run { "enabled, shares are validated but not sent" } else { "disabled" });
+                    if share_tx.send(stratum::StratumCmd::SetDryRun{enabled: *dry_run}).is_err() {
+                        info!("stratum client already stopped");
+                    }
+                },
+                Ok(ConsoleCmd::LogLevel(level)) => {
+                    log::set_max_level(level);
+                    println!("log level {}", level);
+                },
+                Ok(ConsoleCmd::ToggleTrace) => {
+                    trace::set_raw_trace(!trace::raw_trace());
+                    println!("raw pool traffic {}", if trace::raw_trace() { "logged at INFO level (wallet address redacted)" } else { "no longer logged" });
+                },
+                Ok(ConsoleCmd::Help) => println!("{}", console::HELP),
+                Err(_) => unsafe {console_hnd.remove()} //stdin closed
+            }
+        } else if id == watchdog_hnd.id() {
+            let _ = watchdog.tick_rx.recv();
+            if estimate_start.elapsed() >= Duration::from_secs(SHARE_ESTIMATE_SECS) {
+                let delta = sampler.sample(counters);
+                if let Some(job_difficulty) = current_difficulty {
+                    info!("{}", ShareEstimate::new(delta.hash_rate(), job_difficulty).render(shares_found, delta.elapsed));
+                }
+                let coverage_now = coverage.snapshot();
+                if let Some(average) = coverage_now.delta_since(&coverage_start).average() {
+                    info!("nonce coverage: {:.6}% of the assigned nonce space searched per job on average", average * 100.0);
+                }
+                coverage_start = coverage_now;
+                estimate_start = Instant::now();
+                shares_found = 0;
+            }
+            if let Some(check) = schedule_check {
+                let active = check.schedule.active(schedule::local_minute_of_day());
+                if active != check.active {
+                    match active {
+                        Some(window) => info!("schedule window {} begins, reconnecting", check.schedule.windows[window].describe()),
+                        None => info!("schedule window ended, reconnecting to the [pool] settings")
+                    }
+                    return Ok(MainLoopExit::ScheduleChange);
+                }
+            }
+            if let Some(check) = cpu_share_check {
+                if check.share.cores() != check.cores {
+                    info!("share of the cores changed from {} to {}, restarting the worker threads", check.cores, check.share.cores());
+                    return Ok(MainLoopExit::CpuShareChange);
+                }
+            }
+            let queue_stats = share_queue.stats();
+            if queue_stats.depth >= queue_stats.capacity {
+                warn!("share queue full, {} shares waiting for the pool ({} dropped)", queue_stats.depth, queue_stats.dropped);
+            }
+            if let Some(timeout) = watchdog.timeout {
+                if last_job.elapsed() > timeout {
+                    if !job_received {
+                        if let Some(ref mut failover) = failover {
+                            record_pool_failure(failover, event_log);
+                        }
                     }
+                    return Err(MithrilError::Stratum(StratumError::JobTimeout(timeout.as_secs())));
                 }
             }
         }
     }
 }
 
-fn sanity_check(aes_support: AESSupport) {
+fn sanity_check(aes_support: AESSupport) -> Result<(), MithrilError> {
 
     let aes = aes::new(aes_support);
 
@@ -207,8 +1125,9 @@ fn sanity_check(aes_support: AESSupport) {
     let result1 = hash::hash_alloc_scratchpad(&byte_string::string_to_u8_array("66666666d3e1057aad462f2d998d8b9adcf16e03a5bf1820728240eefe433735904fcf663eeb1d00000000b0203ca955ed446e47ab9e884941bc67c75ecb06e444036aafc7ff442c66d26666"), &aes);
     if result0 != "f12b181f2b5a84d8fca047206c605f20b6b3a9b29da3505152caaeee758e39fe" ||
        result1 != "f4e15a61d170cac5e21deff989b1db2af88455c1a8539c3fabfee5be077f32f9" {
-        panic!("hash sanity check failed, please report this at https://github.com/Ragnaroek/mithril/issues");
+        return Err(MithrilError::Hash("hash sanity check failed, please report this at https://github.com/Ragnaroek/mithril/issues".to_string()));
     }
+    Ok(())
 }
 
 fn print_donation_hint(percentage: f64) {
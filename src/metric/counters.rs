@@ -0,0 +1,95 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// A counter on its own cache line, so worker threads counting concurrently
+/// never write to the same line.
+#[repr(align(64))]
+struct PaddedCounter(AtomicU64);
+
+/// Hash counters of all worker threads. Every thread only adds to its own
+/// counter (relaxed, no contention in the hashing loop), readers aggregate
+/// them with `snapshot`.
+pub struct HashCounters {
+    counters: Vec<PaddedCounter>,
+}
+
+impl HashCounters {
+    pub fn new(num_threads: usize) -> HashCounters {
+        HashCounters{counters: (0..num_threads).map(|_| PaddedCounter(AtomicU64::new(0))).collect()}
+    }
+
+    /// Adds `hashes` to the counter of thread `thread_ix`
+    pub fn add(&self, thread_ix: usize, hashes: u64) {
+        self.counters[thread_ix].0.fetch_add(hashes, Ordering::Relaxed);
+    }
+
+    /// Total number of hashes of all threads
+    pub fn total(&self) -> u64 {
+        self.counters.iter().map(|c| c.0.load(Ordering::Relaxed)).sum()
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot{per_thread: self.counters.iter().map(|c| c.0.load(Ordering::Relaxed)).collect(), taken: Instant::now()}
+    }
+}
+
+/// Hash counts of all threads at one point in time
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub per_thread: Vec<u64>,
+    pub taken: Instant,
+}
+
+impl Snapshot {
+    pub fn total(&self) -> u64 {
+        self.per_thread.iter().sum()
+    }
+
+    /// The hashes counted between `earlier` and this snapshot
+    pub fn delta_since(&self, earlier: &Snapshot) -> Delta {
+        let per_thread = self.per_thread.iter().enumerate()
+            .map(|(ix, cnt)| cnt - earlier.per_thread.get(ix).cloned().unwrap_or(0))
+            .collect();
+        Delta{per_thread, elapsed: self.taken.duration_since(earlier.taken)}
+    }
+}
+
+/// Hash counts of all threads during a time span
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delta {
+    pub per_thread: Vec<u64>,
+    pub elapsed: Duration,
+}
+
+impl Delta {
+    pub fn total(&self) -> u64 {
+        self.per_thread.iter().sum()
+    }
+
+    /// Hashes per second of all threads
+    pub fn hash_rate(&self) -> f64 {
+        let secs = self.elapsed.as_secs() as f64 + f64::from(self.elapsed.subsec_nanos()) / 1e9;
+        if secs == 0.0 {
+            return 0.0;
+        }
+        self.total() as f64 / secs
+    }
+}
+
+/// Takes snapshots and returns the hashes counted since the previous one
+pub struct Sampler {
+    last: Snapshot,
+}
+
+impl Sampler {
+    pub fn new(counters: &HashCounters) -> Sampler {
+        Sampler{last: counters.snapshot()}
+    }
+
+    pub fn sample(&mut self, counters: &HashCounters) -> Delta {
+        let now = counters.snapshot();
+        let delta = now.delta_since(&self.last);
+        self.last = now;
+        delta
+    }
+}
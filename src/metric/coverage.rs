@@ -0,0 +1,63 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Coverage of a nonce partition is counted in fixed point (1.0 = `SCALE`), fine
+/// enough for the few thousand hashes a CPU thread manages per job
+const SCALE : f64 = 1e12;
+
+/// Fraction of the assigned nonce space the worker threads searched before
+/// their job was replaced (or cancelled or expired). Every thread records one
+/// sample per job for its nonce partition. A low average means the pool sends
+/// new jobs long before the partitions could be searched, more threads only
+/// split the nonce space further without searching more of it.
+pub struct NonceCoverage {
+    searched: AtomicU64,
+    samples: AtomicU64,
+}
+
+impl NonceCoverage {
+    pub fn new() -> NonceCoverage {
+        NonceCoverage{searched: AtomicU64::new(0), samples: AtomicU64::new(0)}
+    }
+
+    /// Records that `searched` nonces of a partition with `assigned` nonces were hashed
+    pub fn record(&self, searched: u64, assigned: u64) {
+        if assigned == 0 {
+            return;
+        }
+        let fraction = (searched.min(assigned) as f64 / assigned as f64 * SCALE).round() as u64;
+        self.searched.fetch_add(fraction, Ordering::Relaxed);
+        self.samples.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> CoverageSnapshot {
+        CoverageSnapshot{searched: self.searched.load(Ordering::Relaxed), samples: self.samples.load(Ordering::Relaxed)}
+    }
+}
+
+impl Default for NonceCoverage {
+    fn default() -> NonceCoverage {
+        NonceCoverage::new()
+    }
+}
+
+/// Recorded coverage samples at one point in time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CoverageSnapshot {
+    pub searched: u64,
+    pub samples: u64,
+}
+
+impl CoverageSnapshot {
+    /// The samples recorded between `earlier` and this snapshot
+    pub fn delta_since(&self, earlier: &CoverageSnapshot) -> CoverageSnapshot {
+        CoverageSnapshot{searched: self.searched - earlier.searched, samples: self.samples - earlier.samples}
+    }
+
+    /// Average searched fraction (0.0 - 1.0) of a job partition, `None` without samples
+    pub fn average(&self) -> Option<f64> {
+        if self.samples == 0 {
+            return None;
+        }
+        Some(self.searched as f64 / self.samples as f64 / SCALE)
+    }
+}
@@ -1,10 +1,13 @@
+pub mod counters;
+pub mod coverage;
+
 use std::thread;
 use std::time;
-use std::sync::mpsc::{channel, Sender, Receiver, RecvTimeoutError, Select};
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Sender, RecvTimeoutError};
 use std::sync::{Arc};
 use std::io::Write;
 use std::fs::OpenOptions;
+use self::counters::{HashCounters, Sampler};
 
 #[derive(Clone)]
 pub struct MetricConfig {
@@ -12,57 +15,25 @@ pub struct MetricConfig {
     pub resolution: u64,
     pub sample_interval_seconds: u64,
     pub report_file: String,
+    /// static `(name, value)` labels appended to every sample, sorted by name
+    pub labels: Vec<(String, String)>,
 }
 
 pub struct Metric {
-    /// This is the total hash count since the construction of the
-    /// metric struct.
-    total_hashes: Arc<AtomicU64>,
-    cnt_hnd: thread::JoinHandle<()>,
+    /// The hash counters of the worker threads, counting since the
+    /// construction of the metric struct.
+    counters: Arc<HashCounters>,
     tick_hnd: thread::JoinHandle<()>,
     stop_tick_tx: Sender<()>,
-    stop_cnt_tx: Sender<()>,
 }
 
-pub fn start(conf: MetricConfig, hash_cnt_receiver: Receiver<u64>) -> Metric {
-
-    let log_count = Arc::new(AtomicU64::new(0));
-    let total_count = Arc::new(AtomicU64::new(0));
-
-    let thread_log_count = log_count.clone();
-    let thread_total_count = total_count.clone();
-    let (stop_cnt_tx, stop_cnt_rx) = channel();
-
-    let cnt_hnd = thread::Builder::new().name("metric counting thread".to_string()).spawn(move || {
-        let select = Select::new();
-        let mut hash_hnd = select.handle(&hash_cnt_receiver);
-        unsafe {hash_hnd.add()};
-        let mut stop_hnd = select.handle(&stop_cnt_rx);
-        unsafe {stop_hnd.add()};
-
-        loop {
-            let id = select.wait();
-            if id == stop_hnd.id() {
-                let _ = stop_hnd.recv();
-                info!("stopping metric counting thread");
-                return;
-            } else {
-                let cnt_rcv = hash_hnd.recv();
-                if cnt_rcv.is_err() {
-                    info!("hash count sender closed, stopping metric thread");
-                    return;
-                } else {
-                    let cnt = cnt_rcv.unwrap();
-                    thread_log_count.fetch_add(cnt, Ordering::SeqCst);
-                    thread_total_count.fetch_add(cnt, Ordering::SeqCst);
-                }
-            }
-        }
-    }).expect("metric counting thread handle");
+pub fn start(conf: MetricConfig, counters: Arc<HashCounters>) -> Metric {
 
+    let thread_counters = counters.clone();
     let (stop_tick_tx, stop_tick_rx) = channel();
 
     let tick_hnd = thread::Builder::new().name("metric sample thread".to_string()).spawn(move || {
+        let mut sampler = Sampler::new(&thread_counters);
         loop {
             let recv_result = stop_tick_rx.recv_timeout(time::Duration::from_secs(conf.sample_interval_seconds));
             match recv_result {
@@ -73,7 +44,7 @@ pub fn start(conf: MetricConfig, hash_cnt_receiver: Receiver<u64>) -> Metric {
                 Err(RecvTimeoutError::Timeout) => {}, //continue with next loop
             }
 
-            let sample_cnt = log_count.swap(0, Ordering::SeqCst);
+            let sample_cnt = sampler.sample(&thread_counters).total();
 
             let timestamp_result = time::SystemTime::now().duration_since(time::UNIX_EPOCH);
             if timestamp_result.is_err() {
@@ -89,7 +60,7 @@ pub fn start(conf: MetricConfig, hash_cnt_receiver: Receiver<u64>) -> Metric {
                              .open(conf.report_file.clone());
             if file_result.is_ok() {
                 let mut file = file_result.unwrap();
-                let write_result = writeln!(file, "{};{}", millis, sample_cnt);
+                let write_result = writeln!(file, "{}", sample_line(millis, sample_cnt, &conf.labels));
                 if write_result.is_err() {
                     error!("could not write metric file");
                 }
@@ -102,12 +73,21 @@ pub fn start(conf: MetricConfig, hash_cnt_receiver: Receiver<u64>) -> Metric {
         }
     }).expect("metric sample thread handle");
 
-    Metric{total_hashes: total_count, cnt_hnd, tick_hnd, stop_tick_tx, stop_cnt_tx}
+    Metric{counters, tick_hnd, stop_tick_tx}
+}
+
+/// A line of the report file, `<timestamp>;<hashes>` followed by `;<name>=<value>` for every label
+pub fn sample_line(millis: u64, sample_cnt: u64, labels: &[(String, String)]) -> String {
+    let mut line = format!("{};{}", millis, sample_cnt);
+    for &(ref name, ref value) in labels {
+        line.push_str(&format!(";{}={}", name, value));
+    }
+    line
 }
 
 impl Metric {
     pub fn hash_count(&self) -> u64 {
-        self.total_hashes.load(Ordering::SeqCst)
+        self.counters.total()
     }
 
     pub fn stop(&self) {
@@ -117,16 +97,11 @@ impl Metric {
         if res_tick.is_err() {
             error!("sending tick stop failed {:?}", res_tick);
         }
-        let res_cnt = self.stop_cnt_tx.send(());
-        if res_cnt.is_err() {
-            error!("sending cnt stop failed {:?}", res_cnt);
-        }
 
         info!("metrics stopped");
     }
 
     pub fn join(self) {
         let _ = self.tick_hnd.join();
-        let _ = self.cnt_hnd.join();
     }
 }
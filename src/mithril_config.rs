@@ -2,13 +2,36 @@
 extern crate config;
 
 use metric::{MetricConfig};
-use stratum::stratum_data::{PoolConfig};
-use worker::worker_pool::{WorkerConfig};
+use stratum::stratum_data::{PoolConfig, SubmitOptions, SUBMIT_PARAM_NAMES, DEFAULT_KEEPALIVE_METHOD, DEFAULT_KEEPALIVE_JITTER_SECONDS,
+    DEFAULT_KEEPALIVE_INTERVAL_SECS, DEFAULT_CONNECT_TIMEOUT_SECS, DEFAULT_LOGIN_RETRIES,
+    DEFAULT_FAILOVER_MAX_FAILURES, DEFAULT_FAILOVER_PROBE_MINUTES, DEFAULT_SHARE_QUEUE_CAPACITY,
+    DEFAULT_STATS_INTERVAL_SECS, DEFAULT_SPLIT_PRIMARY_PERCENT, DEFAULT_OFFLINE_SHARE_CAPACITY, DEFAULT_OFFLINE_SHARE_MAX_AGE_SECS};
+pub use stratum::stratum_data::{default_agent};
+use stratum::pinning;
+use stratum::pinning::{IpRange};
+use stratum::resolve::{AddressPreference};
+use stratum::share_queue::{QueuePolicy};
+use stratum::flood_guard::{FloodGuardConfig, DEFAULT_FLOOD_MAX_REJECTS, DEFAULT_FLOOD_MAX_ERRORS, DEFAULT_FLOOD_BACKOFF_SECS,
+    DEFAULT_FLOOD_MAX_BACKOFF_SECS, DEFAULT_FLOOD_UNHEALTHY_AFTER};
+use stratum::multi_pool::{ArbitrationPolicy, PRIMARY, SECONDARY};
+use stratum::schedule;
+use stratum::schedule::{PoolSchedule, ScheduleWindow};
+use worker::worker_pool::{AlgorithmProfile, WorkerConfig, WorkerGroup};
+use worker::nonce::{NonceStrategyKind};
+use worker::throttle::{BackgroundLevel};
+use cryptonight::{HashVersion, supported_algo_names};
 use cryptonight::aes::{AESSupport};
+use update::{UpdateConfig};
+use cpu_share::{CpuShareConfig};
+use accounting::{AccountingConfig};
+use supervisor;
+use supervisor::{SupervisorConfig};
+use error::{MithrilError};
 
 use std;
+use std::collections::{HashMap};
 use std::path::{Path};
-use self::config::{Config, ConfigError, File};
+use self::config::{Config, ConfigError, File, Value};
 
 pub const CONFIG_FILE_NAME : &str = "config.toml";
 
@@ -20,40 +43,310 @@ pub struct MithrilConfig {
     pub metric_conf: MetricConfig,
     pub hw_conf: HardwareConfig,
     pub donation_conf: DonationConfig,
+    pub update_conf: UpdateConfig,
+    pub schedule_conf: PoolSchedule,
+    pub cpu_share_conf: CpuShareConfig,
+    pub accounting_conf: AccountingConfig,
+    pub supervisor_conf: SupervisorConfig,
+    /// `[[worker_group]]` entries, empty: all threads are one group
+    pub worker_groups: Vec<WorkerGroup>,
 }
 
+/// Pool of the donation hashing, if not configured otherwise
+pub const DEFAULT_DONATION_POOL_ADDRESS : &str = "xmrpool.eu:3333";
+pub const DEFAULT_DONATION_WALLET_ADDRESS : &str = "48y3RCT5SzSS4jumHm9rRL91eWWzd6xcVGSCF1KUZGWYJ6npqwFxHee4xkLLNUqY4NjiswdJhxFALeRqzncHoToeJMg2bhL";
+
 #[derive(Clone)]
 pub struct DonationConfig {
-    pub percentage: f64
+    pub percentage: f64,
+    /// pool and wallet the donation hashes go to
+    pub pool_address: String,
+    pub wallet_address: String,
+}
+
+impl DonationConfig {
+    /// Donation of `percentage` to the default donation pool and wallet
+    pub fn new(percentage: f64) -> DonationConfig {
+        DonationConfig{percentage, pool_address: DEFAULT_DONATION_POOL_ADDRESS.to_string(),
+            wallet_address: DEFAULT_DONATION_WALLET_ADDRESS.to_string()}
+    }
+
+    /// Settings of the donation pool connection
+    pub fn pool_config(&self) -> PoolConfig {
+        PoolConfig::new(&self.pool_address, &self.wallet_address, "x")
+    }
 }
 
 #[derive(Clone)]
 pub struct HardwareConfig {
-    pub aes_support: AESSupport
+    pub aes_support: AESSupport,
+    /// pin the worker threads by core type and L3 cache (see `worker::topology::plan`)
+    pub thread_affinity: bool,
+    /// run no threads on the efficiency cores of hybrid CPUs, implies `thread_affinity`
+    pub skip_efficiency_cores: bool,
 }
 
-pub fn read_config(conf_file: &Path, filename: &str) -> Result<MithrilConfig, config::ConfigError> {
+pub fn read_config(conf_file: &Path, filename: &str) -> Result<MithrilConfig, MithrilError> {
     let config = parse_conf(conf_file, filename)?;
 
     let pool_conf = pool_config(&config)?;
-    let worker_conf = worker_config(&config)?;
+    let mut worker_conf = worker_config(&config)?;
+    let worker_groups = worker_groups(&config, &pool_conf, &worker_conf)?;
+    if !worker_groups.is_empty() {
+        worker_conf.num_threads = worker_groups.iter().map(|g| g.num_threads).sum();
+    }
     let metric_conf = metric_config(&config)?;
     let hw_conf = hardware_config(&config)?;
     let donation_conf = donation_config(&config)?;
+    let update_conf = update_config(&config)?;
+    let schedule_conf = schedule_config(&config, &pool_conf)?;
+    let cpu_share_conf = cpu_share_config(&config)?;
+    let accounting_conf = accounting_config(&config)?;
+    let supervisor_conf = supervisor_config(&config)?;
+
+    Ok(MithrilConfig{pool_conf, worker_conf, metric_conf, hw_conf, donation_conf, update_conf, schedule_conf, cpu_share_conf, accounting_conf, supervisor_conf, worker_groups})
+}
+
+/// `[[worker_group]]` entries, named groups of worker threads replacing `worker.num_threads`
+fn worker_groups(conf: &Config, pool_conf: &PoolConfig, worker_conf: &WorkerConfig) -> Result<Vec<WorkerGroup>, ConfigError> {
+    let routed = pool_conf.pool_arbitration == ArbitrationPolicy::Routed;
+    let entries = match conf.get_array("worker_group") {
+        Ok(entries) => entries,
+        Err(ConfigError::NotFound(_)) if routed => return Err(ConfigError::Message("pool_arbitration = \"routed\" requires worker_group entries".to_string())),
+        Err(ConfigError::NotFound(_)) => return Ok(Vec::new()),
+        Err(e) => return Err(e)
+    };
+    if !entries.is_empty() && worker_conf.auto_tune {
+        return Err(ConfigError::Message("worker_group can not be used with worker.auto_tune".to_string()));
+    }
+    let mut groups : Vec<WorkerGroup> = Vec::with_capacity(entries.len());
+    for (ix, entry) in entries.into_iter().enumerate() {
+        let mut table = entry.into_table()?;
+        let name = take_str(&mut table, "name")?
+            .ok_or_else(|| ConfigError::Message(format!("worker_group {}: name is missing", ix + 1)))?;
+        if groups.iter().any(|g| g.name == name) {
+            return Err(ConfigError::Message(format!("worker_group {}: name {} is used twice", ix + 1, name)));
+        }
+        let num_threads = match table.remove("threads") {
+            Some(value) => value.into_int()?,
+            None => return Err(ConfigError::Message(format!("worker_group {}: threads is missing", ix + 1)))
+        };
+        if num_threads <= 0 {
+            return Err(ConfigError::Message(format!("worker_group {}: threads has to be > 0", ix + 1)));
+        }
+        let pool = match take_str(&mut table, "pool")?.as_ref().map(|p| p.as_str()) {
+            None | Some("primary") => PRIMARY,
+            Some("secondary") if routed => SECONDARY,
+            Some("secondary") => return Err(ConfigError::Message(format!("worker_group {}: pool = \"secondary\" requires pool_arbitration = \"routed\"", ix + 1))),
+            Some(other) => return Err(ConfigError::Message(format!("worker_group {}: unknown pool {}, has to be primary or secondary", ix + 1, other)))
+        };
+        if let Some(name) = table.keys().next() {
+            return Err(ConfigError::Message(format!("worker_group {}: unknown setting {}", ix + 1, name)));
+        }
+        //without routing all groups hash the same jobs, the names only label the threads
+        groups.push(WorkerGroup{name, num_threads: num_threads as u64, pool: if routed { Some(pool) } else { None }});
+    }
+    if routed && ![PRIMARY, SECONDARY].iter().all(|&conn| groups.iter().any(|g| g.pool == Some(conn))) {
+        return Err(ConfigError::Message("pool_arbitration = \"routed\" requires a worker_group for each pool".to_string()));
+    }
+    Ok(groups)
+}
+
+fn accounting_config(conf: &Config) -> Result<AccountingConfig, ConfigError> {
+    let share_log = get_optional_str(conf, "accounting.share_log")?;
+    Ok(AccountingConfig{share_log})
+}
 
-    Ok(MithrilConfig{pool_conf, worker_conf, metric_conf, hw_conf, donation_conf})
+fn supervisor_config(conf: &Config) -> Result<SupervisorConfig, ConfigError> {
+    let enabled = get_bool_or_default(conf, "supervisor.enabled", false)?;
+    let max_restarts = get_u64_or_default(conf, "supervisor.max_restarts", supervisor::DEFAULT_MAX_RESTARTS)?;
+    let window_minutes = get_u64_or_default(conf, "supervisor.window_minutes", supervisor::DEFAULT_WINDOW_MINUTES)?;
+    let initial_backoff_secs = get_u64_or_default(conf, "supervisor.initial_backoff_secs", supervisor::DEFAULT_INITIAL_BACKOFF_SECS)?;
+    let max_backoff_secs = get_u64_or_default(conf, "supervisor.max_backoff_secs", supervisor::DEFAULT_MAX_BACKOFF_SECS)?;
+    let alert_command = get_optional_str(conf, "supervisor.alert_command")?;
+    if max_restarts == 0 || window_minutes == 0 {
+        return Err(ConfigError::Message("supervisor.max_restarts and supervisor.window_minutes have to be > 0".to_string()));
+    }
+    if max_backoff_secs < initial_backoff_secs {
+        return Err(ConfigError::Message("supervisor.max_backoff_secs has to be >= supervisor.initial_backoff_secs".to_string()));
+    }
+    Ok(SupervisorConfig{enabled, max_restarts, window_minutes, initial_backoff_secs, max_backoff_secs, alert_command})
+}
+
+fn cpu_share_config(conf: &Config) -> Result<CpuShareConfig, ConfigError> {
+    let enabled = get_bool_or_default(conf, "cpu_share.enabled", false)?;
+    let socket_path = get_optional_str(conf, "cpu_share.socket_path")?;
+    Ok(CpuShareConfig{enabled, socket_path})
+}
+
+/// `[[schedule]]` entries, each a daily time window with its own pool
+fn schedule_config(conf: &Config, pool_conf: &PoolConfig) -> Result<PoolSchedule, ConfigError> {
+    let entries = match conf.get_array("schedule") {
+        Ok(entries) => entries,
+        Err(ConfigError::NotFound(_)) => return Ok(PoolSchedule::default()),
+        Err(e) => return Err(e)
+    };
+    if !entries.is_empty() && pool_conf.solo_daemon_address.is_some() {
+        return Err(ConfigError::Message("schedule can not be used with pool.solo_daemon_address".to_string()));
+    }
+    let mut windows = Vec::with_capacity(entries.len());
+    for (ix, entry) in entries.into_iter().enumerate() {
+        let mut table = entry.into_table()?;
+        let from_minute = schedule_time(&mut table, ix, "from")?;
+        let to_minute = schedule_time(&mut table, ix, "to")?;
+        if from_minute == to_minute {
+            return Err(ConfigError::Message(format!("schedule entry {}: from and to are equal", ix + 1)));
+        }
+        let pool_address = take_str(&mut table, "pool_address")?
+            .ok_or_else(|| ConfigError::Message(format!("schedule entry {}: pool_address is missing", ix + 1)))?;
+        let wallet_address = take_str(&mut table, "wallet_address")?;
+        let pool_password = take_str(&mut table, "pool_password")?;
+        if let Some(name) = table.keys().next() {
+            return Err(ConfigError::Message(format!("schedule entry {}: unknown setting {}", ix + 1, name)));
+        }
+        windows.push(ScheduleWindow{from_minute, to_minute, pool_address, wallet_address, pool_password});
+    }
+    Ok(PoolSchedule{windows})
+}
+
+fn schedule_time(table: &mut HashMap<String, Value>, ix: usize, name: &str) -> Result<u32, ConfigError> {
+    let time = take_str(table, name)?
+        .ok_or_else(|| ConfigError::Message(format!("schedule entry {}: {} is missing", ix + 1, name)))?;
+    schedule::parse_time(&time)
+        .ok_or_else(|| ConfigError::Message(format!("schedule entry {}: {} = {} is not a HH:MM time", ix + 1, name, time)))
+}
+
+fn take_str(table: &mut HashMap<String, Value>, name: &str) -> Result<Option<String>, ConfigError> {
+    match table.remove(name) {
+        Some(value) => value.into_str().map(Some),
+        None => Ok(None)
+    }
 }
 
 fn donation_config(conf: &Config) -> Result<DonationConfig, ConfigError> {
     let percentage = conf.get_float("donation.percentage")?;
-    Ok(DonationConfig{percentage})
+    let pool_address = get_str_or_default(conf, "donation.pool_address", DEFAULT_DONATION_POOL_ADDRESS)?;
+    let wallet_address = get_str_or_default(conf, "donation.wallet_address", DEFAULT_DONATION_WALLET_ADDRESS)?;
+    Ok(DonationConfig{percentage, pool_address, wallet_address})
 }
 
 fn pool_config(conf: &Config) -> Result<PoolConfig, ConfigError> {
     let pool_address = conf.get_str("pool.pool_address")?;
     let wallet_address = conf.get_str("pool.wallet_address")?;
     let pool_password = conf.get_str("pool.pool_password")?;
-    Ok(PoolConfig{pool_address, wallet_address, pool_password})
+    let max_shares_per_job = get_optional_u64_no_zero(conf, "pool.max_shares_per_job")?;
+    let rig_id = get_optional_str(conf, "pool.rig_id")?;
+    let agent = match get_str_or_default(conf, "pool.agent", &default_agent())? {
+        ref a if a.is_empty() => None,
+        a => Some(a)
+    };
+    let keepalive_method = get_str_or_default(conf, "pool.keepalive_method", DEFAULT_KEEPALIVE_METHOD)?;
+    //0 disables keep alive
+    let keepalive_interval_secs = match get_u64_or_default(conf, "pool.keepalive_interval_secs", DEFAULT_KEEPALIVE_INTERVAL_SECS)? {
+        0 => None,
+        secs => Some(secs)
+    };
+    let keepalive_jitter_seconds = get_u64_or_default(conf, "pool.keepalive_jitter_seconds", DEFAULT_KEEPALIVE_JITTER_SECONDS)?;
+    let connect_timeout_secs = get_u64_or_default(conf, "pool.connect_timeout_secs", DEFAULT_CONNECT_TIMEOUT_SECS)?;
+    if connect_timeout_secs == 0 {
+        return Err(ConfigError::Message("pool.connect_timeout_secs has to be > 0".to_string()));
+    }
+    let address_preference_name = get_str_or_default(conf, "pool.address_preference", "system")?;
+    let address_preference = match AddressPreference::from_name(&address_preference_name) {
+        Some(preference) => preference,
+        None => return Err(ConfigError::Message(format!("unknown address_preference {}, has to be system, ipv6, ipv4, ipv6_only or ipv4_only", address_preference_name)))
+    };
+    let login_retries = get_u64_or_default(conf, "pool.login_retries", DEFAULT_LOGIN_RETRIES)?;
+    let trace_file = get_optional_str(conf, "pool.trace_file")?;
+    let job_timeout_secs = get_optional_u64_no_zero(conf, "pool.job_timeout_secs")?;
+    let dry_run = get_bool_or_default(conf, "pool.dry_run", false)?;
+    let clamp_easy_targets = get_bool_or_default(conf, "pool.clamp_easy_targets", false)?;
+    let tls = get_bool_or_default(conf, "pool.tls", false)?;
+    let tls_fingerprints : Vec<String> = get_str_array_or_empty(conf, "pool.tls_fingerprints")?
+        .iter().map(|f| pinning::normalize_fingerprint(f)).collect();
+    if !tls_fingerprints.is_empty() && !tls {
+        return Err(ConfigError::Message("pool.tls_fingerprints requires pool.tls = true".to_string()));
+    }
+    let allowed_ips = get_str_array_or_empty(conf, "pool.allowed_ips")?
+        .iter().map(|r| IpRange::parse(r)).collect::<Result<Vec<IpRange>, String>>()
+        .map_err(|e| ConfigError::Message(format!("pool.allowed_ips: {}", e)))?;
+    let solo_daemon_address = get_optional_str(conf, "pool.solo_daemon_address")?;
+    let self_select_daemon_address = get_optional_str(conf, "pool.self_select_daemon_address")?;
+    if solo_daemon_address.is_some() && self_select_daemon_address.is_some() {
+        return Err(ConfigError::Message("pool.solo_daemon_address and pool.self_select_daemon_address can not be used together".to_string()));
+    }
+    let failover_pool_addresses = get_str_array_or_empty(conf, "pool.failover_pool_addresses")?;
+    let failover_max_failures = get_u64_or_default(conf, "pool.failover_max_failures", DEFAULT_FAILOVER_MAX_FAILURES)?;
+    if failover_max_failures == 0 {
+        return Err(ConfigError::Message("pool.failover_max_failures has to be > 0".to_string()));
+    }
+    let failover_probe_minutes = get_u64_or_default(conf, "pool.failover_probe_minutes", DEFAULT_FAILOVER_PROBE_MINUTES)?;
+    if failover_probe_minutes == 0 {
+        return Err(ConfigError::Message("pool.failover_probe_minutes has to be > 0".to_string()));
+    }
+    let secondary_pool_address = get_optional_str(conf, "pool.secondary_pool_address")?;
+    if secondary_pool_address.is_some() && solo_daemon_address.is_some() {
+        return Err(ConfigError::Message("pool.secondary_pool_address can not be used with pool.solo_daemon_address".to_string()));
+    }
+    let pool_arbitration_name = get_str_or_default(conf, "pool.pool_arbitration", "standby")?;
+    let split_primary_percent = get_u64_or_default(conf, "pool.split_primary_percent", DEFAULT_SPLIT_PRIMARY_PERCENT)?;
+    let pool_arbitration = match ArbitrationPolicy::from_name(&pool_arbitration_name, split_primary_percent) {
+        Some(policy) => policy,
+        None => return Err(ConfigError::Message(format!("invalid pool_arbitration {} (split_primary_percent {}), has to be standby, routed or split with a percentage <= 100", pool_arbitration_name, split_primary_percent)))
+    };
+    if pool_arbitration == ArbitrationPolicy::Routed && secondary_pool_address.is_none() {
+        return Err(ConfigError::Message("pool_arbitration = \"routed\" requires pool.secondary_pool_address".to_string()));
+    }
+    if pool_arbitration == ArbitrationPolicy::Routed && self_select_daemon_address.is_some() {
+        return Err(ConfigError::Message("pool_arbitration = \"routed\" can not be used with pool.self_select_daemon_address".to_string()));
+    }
+    let share_queue_capacity = get_u64_or_default(conf, "pool.share_queue_capacity", DEFAULT_SHARE_QUEUE_CAPACITY)?;
+    if share_queue_capacity == 0 {
+        return Err(ConfigError::Message("pool.share_queue_capacity has to be > 0".to_string()));
+    }
+    let share_queue_policy_name = get_str_or_default(conf, "pool.share_queue_policy", "error")?;
+    let share_queue_policy = match QueuePolicy::from_name(&share_queue_policy_name) {
+        Some(policy) => policy,
+        None => return Err(ConfigError::Message(format!("unknown share_queue_policy {}, has to be block or error", share_queue_policy_name)))
+    };
+    let late_share_grace_secs = get_optional_u64(conf, "pool.late_share_grace_secs")?;
+    //0 disables the share stats broadcast
+    let stats_interval_secs = match get_u64_or_default(conf, "pool.stats_interval_secs", DEFAULT_STATS_INTERVAL_SECS)? {
+        0 => None,
+        secs => Some(secs)
+    };
+    let min_pool_score = get_optional_u64(conf, "pool.min_pool_score")?;
+    if min_pool_score.map_or(false, |score| score > 100) {
+        return Err(ConfigError::Message("pool.min_pool_score has to be <= 100".to_string()));
+    }
+    let offline_share_capacity = get_u64_or_default(conf, "pool.offline_share_capacity", DEFAULT_OFFLINE_SHARE_CAPACITY)?;
+    let offline_share_max_age_secs = get_u64_or_default(conf, "pool.offline_share_max_age_secs", DEFAULT_OFFLINE_SHARE_MAX_AGE_SECS)?;
+    if offline_share_max_age_secs == 0 {
+        return Err(ConfigError::Message("pool.offline_share_max_age_secs has to be > 0".to_string()));
+    }
+    let submit_options = SubmitOptions{
+        uppercase_hex: get_bool_or_default(conf, "pool.submit_uppercase_hex", false)?,
+        algo: get_optional_str(conf, "pool.submit_algo")?,
+        extra_params: get_submit_params(conf, "pool.submit_extra_params")?
+    };
+    let flood_guard = FloodGuardConfig{
+        max_rejects: get_u64_or_default(conf, "pool.flood_max_rejects", DEFAULT_FLOOD_MAX_REJECTS)?,
+        max_errors: get_u64_or_default(conf, "pool.flood_max_errors", DEFAULT_FLOOD_MAX_ERRORS)?,
+        backoff_secs: get_u64_or_default(conf, "pool.flood_backoff_secs", DEFAULT_FLOOD_BACKOFF_SECS)?,
+        max_backoff_secs: get_u64_or_default(conf, "pool.flood_max_backoff_secs", DEFAULT_FLOOD_MAX_BACKOFF_SECS)?,
+        unhealthy_after: get_u64_or_default(conf, "pool.flood_unhealthy_after", DEFAULT_FLOOD_UNHEALTHY_AFTER)?
+    };
+    if flood_guard.backoff_secs == 0 {
+        return Err(ConfigError::Message("pool.flood_backoff_secs has to be > 0".to_string()));
+    }
+    if flood_guard.max_backoff_secs < flood_guard.backoff_secs {
+        return Err(ConfigError::Message("pool.flood_max_backoff_secs has to be >= pool.flood_backoff_secs".to_string()));
+    }
+    Ok(PoolConfig{pool_address, wallet_address, pool_password, max_shares_per_job,
+                  rig_id, agent, keepalive_method, keepalive_interval_secs, keepalive_jitter_seconds,
+                  connect_timeout_secs, address_preference, login_retries, trace_file, capture_session: None, job_timeout_secs, dry_run, clamp_easy_targets, tls, tls_fingerprints, allowed_ips, solo_daemon_address, self_select_daemon_address,
+                  failover_pool_addresses, failover_max_failures, failover_probe_minutes, secondary_pool_address, pool_arbitration, share_queue_capacity, share_queue_policy,
+                  late_share_grace_secs, stats_interval_secs, min_pool_score, offline_share_capacity, offline_share_max_age_secs, submit_options, flood_guard})
 }
 
 fn worker_config(conf: &Config) -> Result<WorkerConfig, ConfigError> {
@@ -71,10 +364,76 @@ fn worker_config(conf: &Config) -> Result<WorkerConfig, ConfigError> {
 
     let auto_tune_log = conf.get_str("worker.auto_tune_log")?;
 
+    let max_job_age_seconds = get_optional_u64_no_zero(conf, "worker.max_job_age_seconds")?;
+    let warmup_seconds = get_optional_u64_no_zero(conf, "worker.warmup_seconds")?;
+
+    let nonce_strategy_name = get_str_or_default(conf, "worker.nonce_strategy", "sequential")?;
+    let nonce_strategy = match NonceStrategyKind::from_name(&nonce_strategy_name) {
+        Some(kind) => kind,
+        None => return Err(ConfigError::Message(format!("unknown nonce_strategy {}, has to be sequential, strided or random", nonce_strategy_name)))
+    };
+
+    let deterministic_seed = get_optional_u64(conf, "worker.deterministic_seed")?;
+
+    let background_level_value = get_u64_or_default(conf, "worker.background_level", 0)?;
+    let background_level = match BackgroundLevel::from_level(background_level_value) {
+        Some(level) => level,
+        None => return Err(ConfigError::Message(format!("unknown background_level {}, has to be 0, 1, 2 or 3", background_level_value)))
+    };
+
+    let randomx_full_dataset = get_bool_or_default(conf, "worker.randomx_full_dataset", false)?;
+    let algorithm_profiles = algorithm_profiles(conf)?;
+
     Ok(WorkerConfig{num_threads: num_threads as u64,
                     auto_tune,
                     auto_tune_interval_minutes: auto_tune_interval_minutes as u64,
-                    auto_tune_log})
+                    auto_tune_log,
+                    max_job_age_seconds,
+                    warmup_seconds,
+                    nonce_strategy,
+                    deterministic_seed,
+                    background_level,
+                    randomx_full_dataset,
+                    algorithm_profiles})
+}
+
+/// `[worker.algo."<name>"]` sections with `num_threads` and `affinity` for the jobs of one algorithm
+fn algorithm_profiles(conf: &Config) -> Result<Vec<AlgorithmProfile>, ConfigError> {
+    let table = match conf.get_table("worker.algo") {
+        Ok(table) => table,
+        Err(ConfigError::NotFound(_)) => return Ok(Vec::new()),
+        Err(e) => return Err(e)
+    };
+    let mut profiles : Vec<AlgorithmProfile> = Vec::with_capacity(table.len());
+    for (name, value) in table {
+        let hash_version = HashVersion::from_algo(&name).ok_or_else(|| ConfigError::Message(
+            format!("worker.algo: unknown algorithm {}, has to be one of {}", name, supported_algo_names().join(", "))))?;
+        if profiles.iter().any(|p| p.hash_version == hash_version) {
+            return Err(ConfigError::Message(format!("worker.algo: {} is configured twice", name)));
+        }
+        let mut settings = value.into_table()?;
+        let num_threads = match settings.remove("num_threads") {
+            Some(value) => match value.into_int()? {
+                n if n <= 0 => return Err(ConfigError::Message(format!("worker.algo.{}: num_threads has to be > 0", name))),
+                n => Some(n as u64)
+            },
+            None => None
+        };
+        let mut affinity = Vec::new();
+        if let Some(value) = settings.remove("affinity") {
+            for cpu in value.into_array()? {
+                match cpu.into_int()? {
+                    cpu if cpu < 0 => return Err(ConfigError::Message(format!("worker.algo.{}: affinity has to list cpu numbers >= 0", name))),
+                    cpu => affinity.push(cpu as usize)
+                }
+            }
+        }
+        if let Some(setting) = settings.keys().next() {
+            return Err(ConfigError::Message(format!("worker.algo.{}: unknown setting {}", name, setting)));
+        }
+        profiles.push(AlgorithmProfile{hash_version, num_threads, affinity});
+    }
+    Ok(profiles)
 }
 
 fn metric_config(conf: &Config) -> Result<MetricConfig, ConfigError> {
@@ -83,11 +442,78 @@ fn metric_config(conf: &Config) -> Result<MetricConfig, ConfigError> {
         let resolution = get_u64_no_zero(conf, "metric.resolution")?;
         let sample_interval_seconds = get_u64_no_zero(conf, "metric.sample_interval_seconds")?;
         let report_file = conf.get_str("metric.report_file")?;
-        Ok(MetricConfig{enabled, resolution, sample_interval_seconds, report_file})
+        let labels = get_labels(conf, "metric.labels")?;
+        Ok(MetricConfig{enabled, resolution, sample_interval_seconds, report_file, labels})
     } else {
         Ok(MetricConfig{enabled: false, resolution: std::u32::MAX as u64,
-                        sample_interval_seconds: std::u32::MAX as u64, report_file: "/dev/null".to_string()})
+                        sample_interval_seconds: std::u32::MAX as u64, report_file: "/dev/null".to_string(),
+                        labels: Vec::new()})
+    }
+}
+
+/// Reads a table of static labels (e.g. rig, location), names have to be valid
+/// Prometheus label names and values must not break the report file format
+fn get_labels(conf: &Config, field: &str) -> Result<Vec<(String, String)>, ConfigError> {
+    let table = match conf.get_table(field) {
+        Ok(table) => table,
+        Err(ConfigError::NotFound(_)) => return Ok(Vec::new()),
+        Err(e) => return Err(e)
+    };
+    let mut labels = Vec::with_capacity(table.len());
+    for (name, value) in table {
+        if !is_label_name(&name) {
+            return Err(ConfigError::Message(format!("{}: invalid label name {}", field, name)));
+        }
+        let value = value.into_str()?;
+        if value.contains(|c: char| c == ';' || c == '\n' || c == '\r') {
+            return Err(ConfigError::Message(format!("{}: value of label {} must not contain ';' or line breaks", field, name)));
+        }
+        labels.push((name, value));
+    }
+    labels.sort();
+    Ok(labels)
+}
+
+/// Reads the table of extra submit params, the common params can not be replaced
+fn get_submit_params(conf: &Config, field: &str) -> Result<Vec<(String, String)>, ConfigError> {
+    let table = match conf.get_table(field) {
+        Ok(table) => table,
+        Err(ConfigError::NotFound(_)) => return Ok(Vec::new()),
+        Err(e) => return Err(e)
+    };
+    let mut params = Vec::with_capacity(table.len());
+    for (name, value) in table {
+        if SUBMIT_PARAM_NAMES.contains(&name.as_str()) {
+            return Err(ConfigError::Message(format!("{}: {} is always sent, use submit_algo for algo", field, name)));
+        }
+        params.push((name, value.into_str()?));
+    }
+    params.sort();
+    Ok(params)
+}
+
+fn is_label_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => chars.all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        _ => false
+    }
+}
+
+/// The update check is off unless enabled, a release endpoint is only trusted
+/// together with the key its manifest has to be signed with
+fn update_config(conf: &Config) -> Result<UpdateConfig, ConfigError> {
+    let check = get_bool_or_default(conf, "update.check", false)?;
+    let endpoint = get_str_or_default(conf, "update.endpoint", "")?;
+    let public_key = get_str_or_default(conf, "update.public_key", "")?;
+    let download = get_bool_or_default(conf, "update.download", false)?;
+    if check && (endpoint.is_empty() || public_key.is_empty()) {
+        return Err(ConfigError::Message("update.check requires update.endpoint and update.public_key".to_string()));
+    }
+    if download && !check {
+        return Err(ConfigError::Message("update.download requires update.check".to_string()));
     }
+    Ok(UpdateConfig{check, endpoint, public_key, download})
 }
 
 fn hardware_config(conf: &Config) -> Result<HardwareConfig, ConfigError> {
@@ -98,7 +524,9 @@ fn hardware_config(conf: &Config) -> Result<HardwareConfig, ConfigError> {
         warn!("software AES enabled: hashing performance will be low");
         AESSupport::SW
     };
-    Ok(HardwareConfig{aes_support})
+    let skip_efficiency_cores = get_bool_or_default(conf, "hardware.skip_efficiency_cores", false)?;
+    let thread_affinity = skip_efficiency_cores || get_bool_or_default(conf, "hardware.thread_affinity", false)?;
+    Ok(HardwareConfig{aes_support, thread_affinity, skip_efficiency_cores})
 }
 
 fn get_u64_no_zero(conf: &Config, field: &str) -> Result<u64, ConfigError> {
@@ -109,6 +537,67 @@ fn get_u64_no_zero(conf: &Config, field: &str) -> Result<u64, ConfigError> {
     Ok(val as u64)
 }
 
+/// Like `get_u64_no_zero`, but returns `None` if the field is not configured
+fn get_optional_u64_no_zero(conf: &Config, field: &str) -> Result<Option<u64>, ConfigError> {
+    match conf.get_int(field) {
+        Ok(_) => get_u64_no_zero(conf, field).map(Some),
+        Err(ConfigError::NotFound(_)) => Ok(None),
+        Err(e) => Err(e)
+    }
+}
+
+fn get_optional_u64(conf: &Config, field: &str) -> Result<Option<u64>, ConfigError> {
+    match conf.get_int(field) {
+        Ok(val) if val < 0 => Err(ConfigError::Message(format!("{} has to be >= 0", field))),
+        Ok(val) => Ok(Some(val as u64)),
+        Err(ConfigError::NotFound(_)) => Ok(None),
+        Err(e) => Err(e)
+    }
+}
+
+/// The agent sent on login if none is configured
+
+fn get_optional_str(conf: &Config, field: &str) -> Result<Option<String>, ConfigError> {
+    match conf.get_str(field) {
+        Ok(val) => Ok(Some(val)),
+        Err(ConfigError::NotFound(_)) => Ok(None),
+        Err(e) => Err(e)
+    }
+}
+
+fn get_str_or_default(conf: &Config, field: &str, default: &str) -> Result<String, ConfigError> {
+    match conf.get_str(field) {
+        Ok(val) => Ok(val),
+        Err(ConfigError::NotFound(_)) => Ok(default.to_string()),
+        Err(e) => Err(e)
+    }
+}
+
+fn get_u64_or_default(conf: &Config, field: &str, default: u64) -> Result<u64, ConfigError> {
+    match conf.get_int(field) {
+        Ok(val) if val < 0 => Err(ConfigError::Message(format!("{} has to be >= 0", field))),
+        Ok(val) => Ok(val as u64),
+        Err(ConfigError::NotFound(_)) => Ok(default),
+        Err(e) => Err(e)
+    }
+}
+
+fn get_str_array_or_empty(conf: &Config, field: &str) -> Result<Vec<String>, ConfigError> {
+    match conf.get_array(field) {
+        Ok(values) => values.into_iter().map(|v| v.into_str()).collect(),
+        Err(ConfigError::NotFound(_)) => Ok(Vec::new()),
+        Err(e) => Err(e)
+    }
+}
+
+fn get_bool_or_default(conf: &Config, field: &str, default: bool) -> Result<bool, ConfigError> {
+    match conf.get_bool(field) {
+        Ok(val) => Ok(val),
+        Err(ConfigError::NotFound(_)) => Ok(default),
+        Err(e) => Err(e)
+    }
+}
+
 fn parse_conf(conf_file: &Path, filename: &str) -> Result<Config, ConfigError> {
     if conf_file.exists() {
         let mut conf = Config::default();
@@ -118,10 +607,7 @@ fn parse_conf(conf_file: &Path, filename: &str) -> Result<Config, ConfigError> {
     Err(ConfigError::Message("config file not found".to_string()))
 }
 
+/// Settings of the default donation pool
 pub fn donation_conf() -> PoolConfig {
-    PoolConfig {
-        pool_address: "xmrpool.eu:3333".to_string(),
-        pool_password: "x".to_string(),
-        wallet_address: "48y3RCT5SzSS4jumHm9rRL91eWWzd6xcVGSCF1KUZGWYJ6npqwFxHee4xkLLNUqY4NjiswdJhxFALeRqzncHoToeJMg2bhL".to_string()
-    }
+    DonationConfig::new(0.0).pool_config()
 }
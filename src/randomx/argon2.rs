@@ -0,0 +1,177 @@
+use super::{blake2b, RandomXConfig, ARGON_LANES, ARGON_BLOCK_SIZE};
+
+/// 64 bit words per argon2 block
+pub const BLOCK_WORDS : usize = ARGON_BLOCK_SIZE / 8;
+const SYNC_POINTS : usize = 4;
+const VERSION : u32 = 0x13;
+/// argon2d
+const TYPE : u32 = 0;
+
+/// Fills the cache memory (`argon_memory` KiB of `config`) with argon2d of the key. RandomX
+/// uses the memory after the last pass, there is no final hash.
+pub fn fill_memory(config: &RandomXConfig, key: &[u8]) -> Vec<u64> {
+    let lane_length = config.argon_memory;
+    let segment_length = lane_length / SYNC_POINTS;
+    let mut memory = vec![0u64; lane_length * BLOCK_WORDS];
+
+    let h0 = initial_hash(config, key);
+    for i in 0..2u32 {
+        let mut input = Vec::with_capacity(72);
+        input.extend_from_slice(&h0);
+        input.extend_from_slice(&i.to_le_bytes());
+        input.extend_from_slice(&0u32.to_le_bytes());
+        let mut block = [0u8; ARGON_BLOCK_SIZE];
+        blake2b_long(&mut block, &input);
+        let start = i as usize * BLOCK_WORDS;
+        for (word, bytes) in memory[start..start + BLOCK_WORDS].iter_mut().zip(block.chunks(8)) {
+            let mut b = [0u8; 8];
+            b.copy_from_slice(bytes);
+            *word = u64::from_le_bytes(b);
+        }
+    }
+
+    for pass in 0..config.argon_iterations as usize {
+        for slice in 0..SYNC_POINTS {
+            let starting_index = if pass == 0 && slice == 0 { 2 } else { 0 };
+            let mut curr_offset = slice * segment_length + starting_index;
+            let mut prev_offset = if curr_offset % lane_length == 0 { curr_offset + lane_length - 1 } else { curr_offset - 1 };
+            for index in starting_index..segment_length {
+                if curr_offset % lane_length == 1 {
+                    prev_offset = curr_offset - 1;
+                }
+                let pseudo_rand = memory[prev_offset * BLOCK_WORDS];
+                //a single lane: the reference block is always in the same lane
+                let ref_index = index_alpha(pass, slice, index, segment_length, lane_length, pseudo_rand as u32);
+                fill_block(&mut memory, prev_offset, ref_index, curr_offset, pass != 0);
+                curr_offset += 1;
+                prev_offset += 1;
+            }
+        }
+    }
+    memory
+}
+
+fn initial_hash(config: &RandomXConfig, key: &[u8]) -> [u8; 64] {
+    let salt = config.argon_salt;
+    let mut input = Vec::with_capacity(40 + key.len() + salt.len());
+    for value in &[ARGON_LANES, 0, config.argon_memory as u32, config.argon_iterations, VERSION, TYPE] {
+        input.extend_from_slice(&value.to_le_bytes());
+    }
+    input.extend_from_slice(&(key.len() as u32).to_le_bytes());
+    input.extend_from_slice(key);
+    input.extend_from_slice(&(salt.len() as u32).to_le_bytes());
+    input.extend_from_slice(salt);
+    //no secret and no associated data
+    input.extend_from_slice(&0u32.to_le_bytes());
+    input.extend_from_slice(&0u32.to_le_bytes());
+    let mut h0 = [0u8; 64];
+    blake2b(&mut h0, &input);
+    h0
+}
+
+/// The variable length hash H' of argon2 for outputs longer than 64 bytes
+fn blake2b_long(out: &mut [u8], input: &[u8]) {
+    let mut first = Vec::with_capacity(4 + input.len());
+    first.extend_from_slice(&(out.len() as u32).to_le_bytes());
+    first.extend_from_slice(input);
+    let mut buffer = [0u8; 64];
+    blake2b(&mut buffer, &first);
+    out[..32].copy_from_slice(&buffer[..32]);
+    let mut pos = 32;
+    let mut to_produce = out.len() - 32;
+    while to_produce > 64 {
+        let previous = buffer;
+        blake2b(&mut buffer, &previous);
+        out[pos..pos + 32].copy_from_slice(&buffer[..32]);
+        pos += 32;
+        to_produce -= 32;
+    }
+    let previous = buffer;
+    blake2b(&mut out[pos..pos + to_produce], &previous);
+}
+
+/// Position of the reference block in the lane
+fn index_alpha(pass: usize, slice: usize, index: usize, segment_length: usize, lane_length: usize, pseudo_rand: u32) -> usize {
+    let reference_area_size = (if pass == 0 {
+        if slice == 0 {
+            index - 1
+        } else {
+            slice * segment_length + index - 1
+        }
+    } else {
+        lane_length - segment_length + index - 1
+    }) as u64;
+
+    let mut relative_position = u64::from(pseudo_rand);
+    relative_position = (relative_position * relative_position) >> 32;
+    relative_position = reference_area_size - 1 - ((reference_area_size * relative_position) >> 32);
+
+    let start_position = if pass != 0 && slice != SYNC_POINTS - 1 { (slice + 1) * segment_length } else { 0 };
+    (start_position + relative_position as usize) % lane_length
+}
+
+#[inline(always)]
+fn blamka(x: u64, y: u64) -> u64 {
+    let m = (x & 0xFFFF_FFFF).wrapping_mul(y & 0xFFFF_FFFF);
+    x.wrapping_add(y).wrapping_add(m.wrapping_mul(2))
+}
+
+#[inline(always)]
+fn g(v: &mut [u64; BLOCK_WORDS], a: usize, b: usize, c: usize, d: usize) {
+    v[a] = blamka(v[a], v[b]);
+    v[d] = (v[d] ^ v[a]).rotate_right(32);
+    v[c] = blamka(v[c], v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(24);
+    v[a] = blamka(v[a], v[b]);
+    v[d] = (v[d] ^ v[a]).rotate_right(16);
+    v[c] = blamka(v[c], v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(63);
+}
+
+/// The blake2 round without message on the 16 words at the indices `ix`
+#[inline(always)]
+fn round(v: &mut [u64; BLOCK_WORDS], ix: &[usize; 16]) {
+    g(v, ix[0], ix[4], ix[8], ix[12]);
+    g(v, ix[1], ix[5], ix[9], ix[13]);
+    g(v, ix[2], ix[6], ix[10], ix[14]);
+    g(v, ix[3], ix[7], ix[11], ix[15]);
+    g(v, ix[0], ix[5], ix[10], ix[15]);
+    g(v, ix[1], ix[6], ix[11], ix[12]);
+    g(v, ix[2], ix[7], ix[8], ix[13]);
+    g(v, ix[3], ix[4], ix[9], ix[14]);
+}
+
+/// Compresses the previous and the reference block into the current one (xored into it after the first pass)
+fn fill_block(memory: &mut [u64], prev: usize, reference: usize, current: usize, with_xor: bool) {
+    let mut r = [0u64; BLOCK_WORDS];
+    for i in 0..BLOCK_WORDS {
+        r[i] = memory[reference * BLOCK_WORDS + i] ^ memory[prev * BLOCK_WORDS + i];
+    }
+    let mut tmp = r;
+    if with_xor {
+        for i in 0..BLOCK_WORDS {
+            tmp[i] ^= memory[current * BLOCK_WORDS + i];
+        }
+    }
+
+    //columns of 16 consecutive words
+    for i in 0..8 {
+        let mut ix = [0usize; 16];
+        for (j, x) in ix.iter_mut().enumerate() {
+            *x = 16 * i + j;
+        }
+        round(&mut r, &ix);
+    }
+    //rows of word pairs
+    for i in 0..8 {
+        let mut ix = [0usize; 16];
+        for (j, x) in ix.iter_mut().enumerate() {
+            *x = 2 * i + (j / 2) * 16 + (j % 2);
+        }
+        round(&mut r, &ix);
+    }
+
+    for i in 0..BLOCK_WORDS {
+        memory[current * BLOCK_WORDS + i] = tmp[i] ^ r[i];
+    }
+}
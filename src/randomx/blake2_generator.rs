@@ -0,0 +1,43 @@
+use super::{blake2b};
+
+const MAX_SEED_SIZE : usize = 60;
+
+/// Random bytes from a seed, refilled with blake2b-512 of the data when used up
+pub struct Blake2Generator {
+    data: [u8; 64],
+    index: usize,
+}
+
+impl Blake2Generator {
+    pub fn new(seed: &[u8], nonce: u32) -> Blake2Generator {
+        let mut data = [0u8; 64];
+        let len = if seed.len() < MAX_SEED_SIZE { seed.len() } else { MAX_SEED_SIZE };
+        data[..len].copy_from_slice(&seed[..len]);
+        data[MAX_SEED_SIZE..].copy_from_slice(&nonce.to_le_bytes());
+        //starting past the end hashes the data before the first byte is used
+        Blake2Generator{data, index: 64}
+    }
+
+    fn check_data(&mut self, bytes_needed: usize) {
+        if self.index + bytes_needed > self.data.len() {
+            let input = self.data;
+            blake2b(&mut self.data, &input);
+            self.index = 0;
+        }
+    }
+
+    pub fn get_byte(&mut self) -> u8 {
+        self.check_data(1);
+        let b = self.data[self.index];
+        self.index += 1;
+        b
+    }
+
+    pub fn get_u32(&mut self) -> u32 {
+        self.check_data(4);
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(&self.data[self.index..self.index + 4]);
+        self.index += 4;
+        u32::from_le_bytes(bytes)
+    }
+}
@@ -0,0 +1,123 @@
+use std::thread;
+use super::{argon2, superscalar, RandomXConfig, ARGON_BLOCK_SIZE, CACHE_LINE_SIZE};
+use super::blake2_generator::{Blake2Generator};
+use super::superscalar::{SuperscalarProgram};
+
+const CACHE_LINE_WORDS : usize = CACHE_LINE_SIZE as usize / 8;
+
+const MUL0 : u64 = 6_364_136_223_846_793_005;
+const ADD : [u64; 8] = [0, 9_298_411_001_130_361_340, 12_065_312_585_734_608_966, 9_306_329_213_124_626_780,
+    5_281_919_268_842_080_866, 10_536_153_434_571_861_004, 3_398_623_926_847_679_864, 9_549_104_520_008_361_294];
+
+/// The cache of a seed hash (256 MiB for monero) and the superscalar programs, enough to
+/// compute any dataset item on the fly (light mode)
+pub struct Cache {
+    config: RandomXConfig,
+    seed: Vec<u8>,
+    memory: Vec<u64>,
+    /// cache lines of `memory`
+    lines: u64,
+    programs: Vec<SuperscalarProgram>,
+}
+
+impl Cache {
+    /// Builds the cache of `seed` (the seed hash of the job) for the variant `config`, this
+    /// takes seconds. Panics if `config` is not valid, see `RandomXConfig::validate`.
+    pub fn new(config: &RandomXConfig, seed: &[u8]) -> Cache {
+        if let Err(e) = config.validate() {
+            panic!("invalid randomx config: {}", e);
+        }
+        let memory = argon2::fill_memory(config, seed);
+        let lines = (config.argon_memory * ARGON_BLOCK_SIZE) as u64 / CACHE_LINE_SIZE;
+        let mut gen = Blake2Generator::new(seed, 0);
+        let programs = (0..config.cache_accesses).map(|_| superscalar::generate(&mut gen)).collect();
+        Cache{config: *config, seed: seed.to_vec(), memory, lines, programs}
+    }
+
+    /// The variant the cache was built for, the `Vm` hashes with it
+    pub fn config(&self) -> &RandomXConfig {
+        &self.config
+    }
+
+    pub fn seed(&self) -> &[u8] {
+        &self.seed
+    }
+
+    pub fn programs(&self) -> &[SuperscalarProgram] {
+        &self.programs
+    }
+
+    /// Computes the 64 byte dataset item `item`
+    pub fn dataset_item(&self, item: u64) -> [u64; 8] {
+        let mut r = [0u64; 8];
+        let mut register_value = item;
+        r[0] = (item + 1).wrapping_mul(MUL0);
+        for i in 1..8 {
+            r[i] = r[0] ^ ADD[i];
+        }
+        for program in &self.programs {
+            let line = (register_value % self.lines) as usize * CACHE_LINE_WORDS;
+            program.execute(&mut r);
+            for (reg, word) in r.iter_mut().zip(&self.memory[line..line + CACHE_LINE_WORDS]) {
+                *reg ^= *word;
+            }
+            register_value = r[program.address_register];
+        }
+        r
+    }
+}
+
+/// The full dataset (2080 MiB for monero), computed from the cache once per seed hash (fast mode)
+pub struct Dataset {
+    config: RandomXConfig,
+    seed: Vec<u8>,
+    memory: Vec<u64>,
+}
+
+impl Dataset {
+    /// Computes all items of the cache on `threads` threads
+    pub fn new(cache: &Cache, threads: usize) -> Dataset {
+        let threads = if threads == 0 { 1 } else { threads };
+        let item_count = cache.config.dataset_item_count() as usize;
+        let mut memory = vec![0u64; item_count * CACHE_LINE_WORDS];
+        let items_per_thread = (item_count + threads - 1) / threads;
+        {
+            let chunks = memory.chunks_mut(items_per_thread * CACHE_LINE_WORDS);
+            //scoped by hand: the cache outlives the threads since all are joined before returning
+            let cache_ptr = cache as *const Cache as usize;
+            let handles : Vec<_> = chunks.enumerate().map(|(t, chunk)| {
+                let ptr = chunk.as_mut_ptr() as usize;
+                let len = chunk.len();
+                thread::spawn(move || {
+                    let cache = unsafe { &*(cache_ptr as *const Cache) };
+                    let chunk = unsafe { ::std::slice::from_raw_parts_mut(ptr as *mut u64, len) };
+                    let first_item = (t * items_per_thread) as u64;
+                    for (i, line) in chunk.chunks_mut(CACHE_LINE_WORDS).enumerate() {
+                        line.copy_from_slice(&cache.dataset_item(first_item + i as u64));
+                    }
+                })
+            }).collect();
+            //all threads are joined before a failure is raised, none may write to freed memory
+            let results : Vec<_> = handles.into_iter().map(|h| h.join()).collect();
+            for result in results {
+                result.expect("dataset init thread");
+            }
+        }
+        Dataset{config: cache.config, seed: cache.seed.clone(), memory}
+    }
+
+    pub fn config(&self) -> &RandomXConfig {
+        &self.config
+    }
+
+    pub fn seed(&self) -> &[u8] {
+        &self.seed
+    }
+
+    pub fn item(&self, item: u64) -> [u64; 8] {
+        let start = item as usize * CACHE_LINE_WORDS;
+        let mut r = [0u64; 8];
+        r.copy_from_slice(&self.memory[start..start + CACHE_LINE_WORDS]);
+        r
+    }
+}
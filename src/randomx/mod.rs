@@ -0,0 +1,132 @@
+extern crate blake2_rfc;
+
+//RandomX (monero hard fork november 2019), an interpreter following the reference implementation.
+//The parameters of a variant are a `RandomXConfig` (`MONERO`, `WOWNERO`). The `Cache` is built
+//once per seed hash and shared by the worker threads, each thread hashes with its own `Vm`.
+
+pub mod blake2_generator;
+pub mod argon2;
+pub mod superscalar;
+pub mod soft_aes;
+pub mod cache;
+pub mod vm;
+
+pub use self::cache::{Cache, Dataset};
+pub use self::vm::{Vm, Memory};
+
+/// The argon2d fill has a single lane, as in all variants
+pub const ARGON_LANES : u32 = 1;
+pub const ARGON_BLOCK_SIZE : usize = 1024;
+
+/// The same for all variants, it sizes the scheduling tables of the superscalar generator
+pub const SUPERSCALAR_LATENCY : usize = 170;
+pub const CACHE_LINE_SIZE : u64 = 64;
+
+/// Opcode frequencies of `RandomXConfig` in the order of the reference configuration.h
+pub const OPCODE_COUNT : usize = 29;
+
+/// The parameters of a RandomX variant (configuration.h of the reference implementation), the
+/// `Cache` is built with them and the `Vm` runs the programs of its cache's variant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RandomXConfig {
+    /// Memory of the argon2d fill in KiB (the size of the cache)
+    pub argon_memory: usize,
+    pub argon_iterations: u32,
+    pub argon_salt: &'static [u8],
+    /// Superscalar programs (and cache lines read) per dataset item
+    pub cache_accesses: usize,
+    pub dataset_base_size: u64,
+    pub dataset_extra_size: u64,
+    pub program_size: usize,
+    pub program_iterations: usize,
+    /// Programs chained per hash
+    pub program_count: usize,
+    pub scratchpad_l1: usize,
+    pub scratchpad_l2: usize,
+    pub scratchpad_l3: usize,
+    pub jump_bits: u32,
+    pub jump_offset: u32,
+    /// Opcodes (out of 256) of each instruction, IADD_RS to ISTORE
+    pub opcode_frequencies: [usize; OPCODE_COUNT],
+}
+
+/// RandomX of monero (`rx/0`)
+pub const MONERO : RandomXConfig = RandomXConfig{
+    argon_memory: 262_144,
+    argon_iterations: 3,
+    argon_salt: b"RandomX\x03",
+    cache_accesses: 8,
+    dataset_base_size: 2_147_483_648,
+    dataset_extra_size: 33_554_368,
+    program_size: 256,
+    program_iterations: 2048,
+    program_count: 8,
+    scratchpad_l1: 16_384,
+    scratchpad_l2: 262_144,
+    scratchpad_l3: 2_097_152,
+    jump_bits: 8,
+    jump_offset: 8,
+    opcode_frequencies: [16, 7, 16, 7, 16, 4, 4, 1, 4, 1, 8, 2, 15, 5, 8, 2, 4, 4, 16, 5, 16, 5, 6, 32, 4, 6, 25, 1, 16],
+};
+
+/// RandomWOW of wownero (`rx/wow`)
+pub const WOWNERO : RandomXConfig = RandomXConfig{
+    argon_salt: b"RandomWOW\x01",
+    program_iterations: 1024,
+    program_count: 16,
+    scratchpad_l2: 131_072,
+    scratchpad_l3: 1_048_576,
+    opcode_frequencies: [25, 7, 16, 7, 16, 4, 4, 1, 4, 1, 8, 2, 15, 5, 10, 0, 4, 8, 20, 5, 20, 5, 6, 20, 4, 6, 16, 1, 16],
+    ..MONERO
+};
+
+impl RandomXConfig {
+    pub fn dataset_item_count(&self) -> u64 {
+        (self.dataset_base_size + self.dataset_extra_size) / CACHE_LINE_SIZE
+    }
+
+    pub fn dataset_extra_items(&self) -> u64 {
+        self.dataset_extra_size / CACHE_LINE_SIZE
+    }
+
+    /// The checks of the reference implementation (configuration.h static asserts) that this
+    /// interpreter depends on
+    pub fn validate(&self) -> Result<(), String> {
+        if self.argon_memory < 8 || self.argon_memory % 4 != 0 {
+            return Err(format!("argon memory {} KiB has to be a multiple of 4 and at least 8", self.argon_memory));
+        }
+        if self.argon_iterations == 0 || self.cache_accesses == 0 || self.program_size == 0 ||
+            self.program_iterations == 0 || self.program_count == 0 {
+            return Err("argon iterations, cache accesses, program size, iterations and count have to be > 0".to_string());
+        }
+        if !self.dataset_base_size.is_power_of_two() || self.dataset_base_size < CACHE_LINE_SIZE ||
+            self.dataset_base_size > 4_294_967_296 || self.dataset_extra_size % CACHE_LINE_SIZE != 0 {
+            return Err(format!("dataset base size {} has to be a power of 2 up to 4 GiB, the extra size {} a multiple of 64",
+                self.dataset_base_size, self.dataset_extra_size));
+        }
+        if !self.scratchpad_l1.is_power_of_two() || !self.scratchpad_l2.is_power_of_two() || !self.scratchpad_l3.is_power_of_two() ||
+            self.scratchpad_l1 < 64 || self.scratchpad_l2 < self.scratchpad_l1 || self.scratchpad_l3 < self.scratchpad_l2 {
+            return Err(format!("scratchpad sizes {}, {} and {} have to be increasing powers of 2 of at least 64 bytes",
+                self.scratchpad_l1, self.scratchpad_l2, self.scratchpad_l3));
+        }
+        if self.jump_bits == 0 || self.jump_offset == 0 || self.jump_bits + self.jump_offset + 15 > 64 {
+            return Err(format!("jump bits {} with offset {} do not fit a register", self.jump_bits, self.jump_offset));
+        }
+        let opcodes : usize = self.opcode_frequencies.iter().sum();
+        if opcodes != 256 {
+            return Err(format!("opcode frequencies add up to {} instead of 256", opcodes));
+        }
+        Ok(())
+    }
+}
+
+pub const HASH_SIZE : usize = 32;
+
+/// Stratum algorithm names of RandomX, the first is advertised on login
+pub const ALGO_NAMES : &[&str] = &["rx/0", "randomx"];
+
+/// Blake2b of `input` with `out.len()` (1 to 64) bytes of output
+pub fn blake2b(out: &mut [u8], input: &[u8]) {
+    let hash = self::blake2_rfc::blake2b::blake2b(out.len(), &[], input);
+    out.copy_from_slice(hash.as_bytes());
+}
@@ -0,0 +1,159 @@
+use cryptonight::sw_aes;
+use u64x2::{u64x2};
+use super::{blake2b};
+
+/// Single AES rounds (aesenc, aesdec) in software and the AES based generators and hash of
+/// RandomX. The round keys are derived from fixed strings as in the reference implementation.
+pub struct SoftAes {
+    inv_sbox: [u8; 256],
+    keys_1r: [u64x2; 4],
+    keys_4r: [u64x2; 8],
+    hash_state: [u64x2; 4],
+    hash_xkeys: [u64x2; 2],
+}
+
+fn keys_from(name: &[u8], out: &mut [u64x2]) {
+    let mut bytes = [0u8; 64];
+    blake2b(&mut bytes[..out.len() * 16], name);
+    for (i, key) in out.iter_mut().enumerate() {
+        let mut b = [0u8; 16];
+        b.copy_from_slice(&bytes[i * 16..i * 16 + 16]);
+        *key = u64x2::from_le_bytes(b);
+    }
+}
+
+/// Multiplication in GF(2^8) modulo the AES polynomial
+fn gf_mul(a: u8, b: u8) -> u8 {
+    let mut a = a;
+    let mut b = b;
+    let mut r = 0u8;
+    while b != 0 {
+        if b & 1 != 0 {
+            r ^= a;
+        }
+        a = (a << 1) ^ (if a & 0x80 != 0 { 0x1b } else { 0 });
+        b >>= 1;
+    }
+    r
+}
+
+impl SoftAes {
+    pub fn new() -> SoftAes {
+        let mut inv_sbox = [0u8; 256];
+        for x in 0..256u32 {
+            let s = sw_aes::sub_word(x) & 0xff;
+            inv_sbox[s as usize] = x as u8;
+        }
+        let mut aes = SoftAes{inv_sbox, keys_1r: [u64x2(0, 0); 4], keys_4r: [u64x2(0, 0); 8],
+            hash_state: [u64x2(0, 0); 4], hash_xkeys: [u64x2(0, 0); 2]};
+        keys_from(b"RandomX AesGenerator1R keys", &mut aes.keys_1r);
+        keys_from(b"RandomX AesGenerator4R keys 0-3", &mut aes.keys_4r[..4]);
+        keys_from(b"RandomX AesGenerator4R keys 4-7", &mut aes.keys_4r[4..]);
+        keys_from(b"RandomX AesHash1R state", &mut aes.hash_state);
+        keys_from(b"RandomX AesHash1R xkeys", &mut aes.hash_xkeys);
+        aes
+    }
+
+    /// One encryption round (aesenc)
+    #[inline(always)]
+    pub fn enc(&self, state: u64x2, key: u64x2) -> u64x2 {
+        sw_aes::aes_round(state, key)
+    }
+
+    /// One decryption round (aesdec): InvShiftRows, InvSubBytes, InvMixColumns and the round key
+    pub fn dec(&self, state: u64x2, key: u64x2) -> u64x2 {
+        let s = state.to_le_bytes();
+        let mut t = [0u8; 16];
+        //column-major, byte r + 4c is row r of column c
+        for c in 0..4 {
+            for r in 0..4 {
+                t[r + 4 * c] = self.inv_sbox[s[r + 4 * ((c + 4 - r) % 4)] as usize];
+            }
+        }
+        let mut m = [0u8; 16];
+        for c in 0..4 {
+            let a = [t[4 * c], t[4 * c + 1], t[4 * c + 2], t[4 * c + 3]];
+            m[4 * c] = gf_mul(a[0], 14) ^ gf_mul(a[1], 11) ^ gf_mul(a[2], 13) ^ gf_mul(a[3], 9);
+            m[4 * c + 1] = gf_mul(a[0], 9) ^ gf_mul(a[1], 14) ^ gf_mul(a[2], 11) ^ gf_mul(a[3], 13);
+            m[4 * c + 2] = gf_mul(a[0], 13) ^ gf_mul(a[1], 9) ^ gf_mul(a[2], 14) ^ gf_mul(a[3], 11);
+            m[4 * c + 3] = gf_mul(a[0], 11) ^ gf_mul(a[1], 13) ^ gf_mul(a[2], 9) ^ gf_mul(a[3], 14);
+        }
+        let r = u64x2::from_le_bytes(m);
+        u64x2(r.0 ^ key.0, r.1 ^ key.1)
+    }
+
+    /// Fills `out` (a multiple of 64 bytes) with the AES generator of the scratchpad, `state` is
+    /// updated for the next call
+    pub fn fill_1rx4(&self, state: &mut [u8; 64], out: &mut [u8]) {
+        let mut s = read_state(state);
+        let k = &self.keys_1r;
+        for chunk in out.chunks_mut(64) {
+            s[0] = self.dec(s[0], k[0]);
+            s[1] = self.enc(s[1], k[1]);
+            s[2] = self.dec(s[2], k[2]);
+            s[3] = self.enc(s[3], k[3]);
+            write_state(&s, chunk);
+        }
+        write_state(&s, state);
+    }
+
+    /// Fills `out` (a multiple of 64 bytes) with the 4 round AES generator of the programs
+    pub fn fill_4rx4(&self, seed: &[u8; 64], out: &mut [u8]) {
+        let mut s = read_state(seed);
+        let k = &self.keys_4r;
+        for chunk in out.chunks_mut(64) {
+            for i in 0..4 {
+                s[0] = self.dec(s[0], k[i]);
+                s[1] = self.enc(s[1], k[i]);
+                s[2] = self.dec(s[2], k[i + 4]);
+                s[3] = self.enc(s[3], k[i + 4]);
+            }
+            write_state(&s, chunk);
+        }
+    }
+
+    /// 64 byte AES hash of `input` (a multiple of 64 bytes, the scratchpad)
+    pub fn hash_1rx4(&self, input: &[u8]) -> [u8; 64] {
+        let mut s = self.hash_state;
+        for chunk in input.chunks(64) {
+            let mut b = [0u8; 64];
+            b.copy_from_slice(chunk);
+            let k = read_state(&b);
+            s[0] = self.enc(s[0], k[0]);
+            s[1] = self.dec(s[1], k[1]);
+            s[2] = self.enc(s[2], k[2]);
+            s[3] = self.dec(s[3], k[3]);
+        }
+        for xkey in &self.hash_xkeys {
+            s[0] = self.enc(s[0], *xkey);
+            s[1] = self.dec(s[1], *xkey);
+            s[2] = self.enc(s[2], *xkey);
+            s[3] = self.dec(s[3], *xkey);
+        }
+        let mut out = [0u8; 64];
+        write_state(&s, &mut out);
+        out
+    }
+}
+
+impl Default for SoftAes {
+    fn default() -> SoftAes {
+        SoftAes::new()
+    }
+}
+
+fn read_state(bytes: &[u8; 64]) -> [u64x2; 4] {
+    let mut s = [u64x2(0, 0); 4];
+    for (i, x) in s.iter_mut().enumerate() {
+        let mut b = [0u8; 16];
+        b.copy_from_slice(&bytes[i * 16..i * 16 + 16]);
+        *x = u64x2::from_le_bytes(b);
+    }
+    s
+}
+
+fn write_state(s: &[u64x2; 4], out: &mut [u8]) {
+    for (i, x) in s.iter().enumerate() {
+        out[i * 16..i * 16 + 16].copy_from_slice(&x.to_le_bytes());
+    }
+}
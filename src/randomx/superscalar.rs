@@ -0,0 +1,544 @@
+use std::cmp;
+use super::{SUPERSCALAR_LATENCY};
+use super::blake2_generator::{Blake2Generator};
+
+const CYCLE_MAP_SIZE : usize = SUPERSCALAR_LATENCY + 4;
+const LOOK_FORWARD_CYCLES : usize = 4;
+const MAX_THROWAWAY_COUNT : usize = 256;
+/// Upper bound of the program size, ports are saturated long before
+pub const MAX_PROGRAM_SIZE : usize = 3 * SUPERSCALAR_LATENCY + 2;
+/// r5 can not be the destination of IADD_RS (the x86 lea instruction needs a displacement for it)
+const REGISTER_NEEDS_DISPLACEMENT : usize = 5;
+
+/// Execution ports of a micro-op as bit set (P0, P1, P5), 0 for none
+const P0 : u8 = 1;
+const P1 : u8 = 2;
+const P5 : u8 = 4;
+const P01 : u8 = P0 | P1;
+const P05 : u8 = P0 | P5;
+const P015 : u8 = P0 | P1 | P5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Opcode {
+    IsubR,
+    IxorR,
+    IaddRs,
+    ImulR,
+    IrorC,
+    IaddC7,
+    IxorC7,
+    IaddC8,
+    IxorC8,
+    IaddC9,
+    IxorC9,
+    ImulhR,
+    IsmulhR,
+    ImulRcp,
+}
+
+impl Opcode {
+    fn is_multiplication(self) -> bool {
+        self == Opcode::ImulR || self == Opcode::ImulhR || self == Opcode::IsmulhR || self == Opcode::ImulRcp
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Instruction {
+    pub opcode: Opcode,
+    pub dst: usize,
+    /// the same as `dst` for instructions without source register
+    pub src: usize,
+    pub mod_: u8,
+    pub imm32: u32,
+    /// the reciprocal of `imm32` for IMUL_RCP, computed once on generation
+    pub reciprocal: u64,
+}
+
+/// A program of the dataset item computation, generated from the cache key
+#[derive(Debug, Clone, PartialEq)]
+pub struct SuperscalarProgram {
+    pub instructions: Vec<Instruction>,
+    /// register with the longest dependency chain, its value selects the next cache line
+    pub address_register: usize,
+}
+
+/// Macro-op as output of the x86 decoder
+#[derive(Debug, Clone, Copy)]
+struct MacroOp {
+    latency: usize,
+    uop1: u8,
+    uop2: u8,
+    /// depends on the previous macro-op of the instruction (IMUL_RCP)
+    dependent: bool,
+}
+
+impl MacroOp {
+    const fn new(latency: usize, uop1: u8, uop2: u8) -> MacroOp {
+        MacroOp{latency, uop1, uop2, dependent: false}
+    }
+
+    /// mov is eliminated by register renaming and needs no execution port
+    fn is_eliminated(&self) -> bool {
+        self.uop1 == 0
+    }
+
+    fn is_simple(&self) -> bool {
+        self.uop2 == 0
+    }
+}
+
+const ADD_RR : MacroOp = MacroOp::new(1, P015, 0);
+const SUB_RR : MacroOp = MacroOp::new(1, P015, 0);
+const XOR_RR : MacroOp = MacroOp::new(1, P015, 0);
+const IMULH_R : MacroOp = MacroOp::new(4, P1, P5);
+const MUL_R : MacroOp = MacroOp::new(4, P1, P5);
+const MOV_RR : MacroOp = MacroOp::new(0, 0, 0);
+const LEA_SIB : MacroOp = MacroOp::new(1, P01, 0);
+const IMUL_RR : MacroOp = MacroOp::new(3, P1, 0);
+const IMUL_RR_DEPENDENT : MacroOp = MacroOp{latency: 3, uop1: P1, uop2: 0, dependent: true};
+const ROR_RI : MacroOp = MacroOp::new(1, P05, 0);
+const ADD_RI : MacroOp = MacroOp::new(1, P015, 0);
+const XOR_RI : MacroOp = MacroOp::new(1, P015, 0);
+const MOV_RI64 : MacroOp = MacroOp::new(1, P015, 0);
+
+/// The macro-ops of an instruction and which of them reads the source, writes the destination
+/// and produces the result
+struct InstructionInfo {
+    opcode: Opcode,
+    ops: &'static [MacroOp],
+    result_op: usize,
+    dst_op: usize,
+    src_op: Option<usize>,
+}
+
+const INFO_ISUB_R : InstructionInfo = InstructionInfo{opcode: Opcode::IsubR, ops: &[SUB_RR], result_op: 0, dst_op: 0, src_op: Some(0)};
+const INFO_IXOR_R : InstructionInfo = InstructionInfo{opcode: Opcode::IxorR, ops: &[XOR_RR], result_op: 0, dst_op: 0, src_op: Some(0)};
+const INFO_IADD_RS : InstructionInfo = InstructionInfo{opcode: Opcode::IaddRs, ops: &[LEA_SIB], result_op: 0, dst_op: 0, src_op: Some(0)};
+const INFO_IMUL_R : InstructionInfo = InstructionInfo{opcode: Opcode::ImulR, ops: &[IMUL_RR], result_op: 0, dst_op: 0, src_op: Some(0)};
+const INFO_IROR_C : InstructionInfo = InstructionInfo{opcode: Opcode::IrorC, ops: &[ROR_RI], result_op: 0, dst_op: 0, src_op: None};
+const INFO_IADD_C7 : InstructionInfo = InstructionInfo{opcode: Opcode::IaddC7, ops: &[ADD_RI], result_op: 0, dst_op: 0, src_op: None};
+const INFO_IXOR_C7 : InstructionInfo = InstructionInfo{opcode: Opcode::IxorC7, ops: &[XOR_RI], result_op: 0, dst_op: 0, src_op: None};
+const INFO_IADD_C8 : InstructionInfo = InstructionInfo{opcode: Opcode::IaddC8, ops: &[ADD_RI], result_op: 0, dst_op: 0, src_op: None};
+const INFO_IXOR_C8 : InstructionInfo = InstructionInfo{opcode: Opcode::IxorC8, ops: &[XOR_RI], result_op: 0, dst_op: 0, src_op: None};
+const INFO_IADD_C9 : InstructionInfo = InstructionInfo{opcode: Opcode::IaddC9, ops: &[ADD_RI], result_op: 0, dst_op: 0, src_op: None};
+const INFO_IXOR_C9 : InstructionInfo = InstructionInfo{opcode: Opcode::IxorC9, ops: &[XOR_RI], result_op: 0, dst_op: 0, src_op: None};
+const INFO_IMULH_R : InstructionInfo = InstructionInfo{opcode: Opcode::ImulhR, ops: &[MOV_RR, MUL_R, MOV_RR], result_op: 1, dst_op: 0, src_op: Some(1)};
+const INFO_ISMULH_R : InstructionInfo = InstructionInfo{opcode: Opcode::IsmulhR, ops: &[MOV_RR, IMULH_R, MOV_RR], result_op: 1, dst_op: 0, src_op: Some(1)};
+const INFO_IMUL_RCP : InstructionInfo = InstructionInfo{opcode: Opcode::ImulRcp, ops: &[MOV_RI64, IMUL_RR_DEPENDENT], result_op: 1, dst_op: 1, src_op: None};
+
+const SLOT_3 : [&InstructionInfo; 2] = [&INFO_ISUB_R, &INFO_IXOR_R];
+const SLOT_3L : [&InstructionInfo; 4] = [&INFO_ISUB_R, &INFO_IXOR_R, &INFO_IMULH_R, &INFO_ISMULH_R];
+const SLOT_4 : [&InstructionInfo; 2] = [&INFO_IROR_C, &INFO_IADD_RS];
+const SLOT_7 : [&InstructionInfo; 2] = [&INFO_IXOR_C7, &INFO_IADD_C7];
+const SLOT_8 : [&InstructionInfo; 2] = [&INFO_IXOR_C8, &INFO_IADD_C8];
+const SLOT_9 : [&InstructionInfo; 2] = [&INFO_IXOR_C9, &INFO_IADD_C9];
+
+/// A split of the 16 bytes the decoder reads per cycle into instruction slots
+struct DecodeBuffer {
+    index: usize,
+    counts: &'static [usize],
+}
+
+const BUFFER_484 : DecodeBuffer = DecodeBuffer{index: 0, counts: &[4, 8, 4]};
+const BUFFER_7333 : DecodeBuffer = DecodeBuffer{index: 1, counts: &[7, 3, 3, 3]};
+const BUFFER_3733 : DecodeBuffer = DecodeBuffer{index: 2, counts: &[3, 7, 3, 3]};
+const BUFFER_493 : DecodeBuffer = DecodeBuffer{index: 3, counts: &[4, 9, 3]};
+const BUFFER_4444 : DecodeBuffer = DecodeBuffer{index: 4, counts: &[4, 4, 4, 4]};
+const BUFFER_3310 : DecodeBuffer = DecodeBuffer{index: 5, counts: &[3, 3, 10]};
+const RANDOM_BUFFERS : [&DecodeBuffer; 4] = [&BUFFER_484, &BUFFER_7333, &BUFFER_3733, &BUFFER_493];
+
+fn fetch_next(last: Option<Opcode>, cycle: usize, mul_count: usize, gen: &mut Blake2Generator) -> &'static DecodeBuffer {
+    //a full 128 bit multiplication decodes to 2 micro-ops, only 3-3-10 fits the 4 micro-ops per cycle
+    if last == Some(Opcode::ImulhR) || last == Some(Opcode::IsmulhR) {
+        return &BUFFER_3310;
+    }
+    //saturate the multiplication port
+    if mul_count < cycle + 1 {
+        return &BUFFER_4444;
+    }
+    //the multiplication of IMUL_RCP needs a 4 byte slot first
+    if last == Some(Opcode::ImulRcp) {
+        return if gen.get_byte() & 1 != 0 { &BUFFER_484 } else { &BUFFER_493 };
+    }
+    RANDOM_BUFFERS[(gen.get_byte() & 3) as usize]
+}
+
+#[derive(Clone, Copy)]
+struct RegisterInfo {
+    /// cycle the value is ready
+    latency: usize,
+    last_op_group: Option<Opcode>,
+    /// source of the last operation: -1 constant, 0-7 register (or a random value for the high multiplications)
+    last_op_par: i32,
+}
+
+/// The instruction being scheduled
+struct Candidate {
+    info: &'static InstructionInfo,
+    src: Option<usize>,
+    dst: Option<usize>,
+    mod_: u8,
+    imm32: u32,
+    op_group: Opcode,
+    op_group_par: i32,
+    can_reuse: bool,
+    group_par_is_source: bool,
+}
+
+fn is_zero_or_power_of_2(x: u32) -> bool {
+    x & x.wrapping_sub(1) == 0
+}
+
+fn select_register(available: &[usize], gen: &mut Blake2Generator) -> Option<usize> {
+    match available.len() {
+        0 => None,
+        1 => Some(available[0]),
+        n => Some(available[(gen.get_u32() as usize) % n])
+    }
+}
+
+impl Candidate {
+    fn for_slot(gen: &mut Blake2Generator, slot_size: usize, fetch_type: usize, is_last: bool) -> Candidate {
+        let info = match slot_size {
+            //IMULH only fits the last slot
+            3 if is_last => SLOT_3L[(gen.get_byte() & 3) as usize],
+            3 => SLOT_3[(gen.get_byte() & 1) as usize],
+            //the 4-4-4-4 buffer issues multiplications in the first 3 slots
+            4 if fetch_type == 4 && !is_last => &INFO_IMUL_R,
+            4 => SLOT_4[(gen.get_byte() & 1) as usize],
+            7 => SLOT_7[(gen.get_byte() & 1) as usize],
+            8 => SLOT_8[(gen.get_byte() & 1) as usize],
+            9 => SLOT_9[(gen.get_byte() & 1) as usize],
+            _ => &INFO_IMUL_RCP
+        };
+        Candidate::create(info, gen)
+    }
+
+    fn create(info: &'static InstructionInfo, gen: &mut Blake2Generator) -> Candidate {
+        let mut c = Candidate{info, src: None, dst: None, mod_: 0, imm32: 0, op_group: info.opcode, op_group_par: -1,
+            can_reuse: false, group_par_is_source: false};
+        match info.opcode {
+            Opcode::IsubR => {
+                c.op_group = Opcode::IaddRs;
+                c.group_par_is_source = true;
+            },
+            Opcode::IxorR | Opcode::ImulR => c.group_par_is_source = true,
+            Opcode::IaddRs => {
+                c.mod_ = gen.get_byte();
+                c.group_par_is_source = true;
+            },
+            Opcode::IrorC => {
+                while c.imm32 == 0 {
+                    c.imm32 = u32::from(gen.get_byte() & 63);
+                }
+            },
+            Opcode::IaddC7 | Opcode::IaddC8 | Opcode::IaddC9 => {
+                c.imm32 = gen.get_u32();
+                c.op_group = Opcode::IaddC7;
+            },
+            Opcode::IxorC7 | Opcode::IxorC8 | Opcode::IxorC9 => {
+                c.imm32 = gen.get_u32();
+                c.op_group = Opcode::IxorC7;
+            },
+            Opcode::ImulhR | Opcode::IsmulhR => {
+                c.can_reuse = true;
+                c.op_group_par = gen.get_u32() as i32;
+            },
+            Opcode::ImulRcp => {
+                c.imm32 = gen.get_u32();
+                while is_zero_or_power_of_2(c.imm32) {
+                    c.imm32 = gen.get_u32();
+                }
+            }
+        }
+        c
+    }
+
+    fn select_destination(&mut self, cycle: usize, allow_chained_mul: bool, registers: &[RegisterInfo; 8], gen: &mut Blake2Generator) -> bool {
+        //the value has to be ready, the source is not reused (xor r, r), no multiplication of a just multiplied
+        //register, no repetition of the last operation on the register and no IADD_RS to r5
+        let available : Vec<usize> = (0..8).filter(|&i| {
+            let reg = &registers[i];
+            reg.latency <= cycle
+                && (self.can_reuse || Some(i) != self.src)
+                && (allow_chained_mul || self.op_group != Opcode::ImulR || reg.last_op_group != Some(Opcode::ImulR))
+                && (reg.last_op_group != Some(self.op_group) || reg.last_op_par != self.op_group_par)
+                && (self.info.opcode != Opcode::IaddRs || i != REGISTER_NEEDS_DISPLACEMENT)
+        }).collect();
+        match select_register(&available, gen) {
+            Some(dst) => {
+                self.dst = Some(dst);
+                true
+            },
+            None => false
+        }
+    }
+
+    fn select_source(&mut self, cycle: usize, registers: &[RegisterInfo; 8], gen: &mut Blake2Generator) -> bool {
+        let available : Vec<usize> = (0..8).filter(|&i| registers[i].latency <= cycle).collect();
+        //with only 2 registers available for IADD_RS and one of them r5, r5 has to be the source
+        if available.len() == 2 && self.info.opcode == Opcode::IaddRs && available.contains(&REGISTER_NEEDS_DISPLACEMENT) {
+            self.src = Some(REGISTER_NEEDS_DISPLACEMENT);
+            self.op_group_par = REGISTER_NEEDS_DISPLACEMENT as i32;
+            return true;
+        }
+        match select_register(&available, gen) {
+            Some(src) => {
+                self.src = Some(src);
+                if self.group_par_is_source {
+                    self.op_group_par = src as i32;
+                }
+                true
+            },
+            None => false
+        }
+    }
+
+    fn to_instruction(&self) -> Instruction {
+        let dst = self.dst.expect("destination of a scheduled instruction");
+        let reciprocal = if self.info.opcode == Opcode::ImulRcp { reciprocal(u64::from(self.imm32)) } else { 0 };
+        Instruction{opcode: self.info.opcode, dst, src: self.src.unwrap_or(dst), mod_: self.mod_, imm32: self.imm32, reciprocal}
+    }
+}
+
+/// Earliest cycle the micro-op can execute at (ports are tried in the order P5, P0, P1 to keep
+/// P1 free for multiplications), the port is reserved if `commit`
+fn schedule_uop(uop: u8, port_busy: &mut [[bool; 3]; CYCLE_MAP_SIZE], cycle: usize, commit: bool) -> Option<usize> {
+    for c in cycle..CYCLE_MAP_SIZE {
+        for &(port, ix) in &[(P5, 2), (P0, 0), (P1, 1)] {
+            if uop & port != 0 && !port_busy[c][ix] {
+                if commit {
+                    port_busy[c][ix] = true;
+                }
+                return Some(c);
+            }
+        }
+    }
+    None
+}
+
+fn schedule_mop(mop: &MacroOp, port_busy: &mut [[bool; 3]; CYCLE_MAP_SIZE], cycle: usize, dep_cycle: usize, commit: bool) -> Option<usize> {
+    let cycle = if mop.dependent { cmp::max(cycle, dep_cycle) } else { cycle };
+    if mop.is_eliminated() {
+        return Some(cycle);
+    }
+    if mop.is_simple() {
+        return schedule_uop(mop.uop1, port_busy, cycle, commit);
+    }
+    //both micro-ops of a macro-op execute in the same cycle
+    for c in cycle..CYCLE_MAP_SIZE {
+        let cycle1 = schedule_uop(mop.uop1, port_busy, c, false);
+        let cycle2 = schedule_uop(mop.uop2, port_busy, c, false);
+        if cycle1.is_some() && cycle1 == cycle2 {
+            if commit {
+                schedule_uop(mop.uop1, port_busy, c, true);
+                schedule_uop(mop.uop2, port_busy, c, true);
+            }
+            return cycle1;
+        }
+    }
+    None
+}
+
+/// Generates a program by simulating the decoder and the execution ports of a superscalar
+/// x86 CPU until the ports are saturated (the reference implementation step by step, every
+/// random byte taken from `gen` has to match)
+pub fn generate(gen: &mut Blake2Generator) -> SuperscalarProgram {
+    let mut port_busy = [[false; 3]; CYCLE_MAP_SIZE];
+    let mut registers = [RegisterInfo{latency: 0, last_op_group: None, last_op_par: -1}; 8];
+    let mut instructions : Vec<Instruction> = Vec::with_capacity(MAX_PROGRAM_SIZE);
+
+    let mut current : Option<Candidate> = None;
+    let mut macro_op_index = 0;
+    let mut cycle = 0;
+    let mut dep_cycle = 0;
+    let mut ports_saturated = false;
+    let mut mul_count = 0;
+    let mut throw_away_count = 0;
+
+    let mut decode_cycle = 0;
+    while decode_cycle < SUPERSCALAR_LATENCY && !ports_saturated && instructions.len() < MAX_PROGRAM_SIZE {
+        let last = current.as_ref().map(|c| c.info.opcode);
+        let buffer = fetch_next(last, decode_cycle, mul_count, gen);
+
+        let mut buffer_index = 0;
+        while buffer_index < buffer.counts.len() {
+            let top_cycle = cycle;
+
+            if macro_op_index >= current.as_ref().map(|c| c.info.ops.len()).unwrap_or(0) {
+                if ports_saturated || instructions.len() >= MAX_PROGRAM_SIZE {
+                    break;
+                }
+                //an instruction whose first macro-op fits the slot
+                current = Some(Candidate::for_slot(gen, buffer.counts[buffer_index], buffer.index, buffer.counts.len() == buffer_index + 1));
+                macro_op_index = 0;
+            }
+            let mut abort = false;
+            {
+                let instr = current.as_mut().expect("instruction created for the slot");
+                let mop = instr.info.ops[macro_op_index];
+
+                let mut schedule_cycle = match schedule_mop(&mop, &mut port_busy, cycle, dep_cycle, false) {
+                    Some(c) => c,
+                    None => {
+                        ports_saturated = true;
+                        break;
+                    }
+                };
+
+                //a source register ready at the cycle, up to LOOK_FORWARD_CYCLES later
+                if instr.info.src_op == Some(macro_op_index) {
+                    let mut forward = 0;
+                    while forward < LOOK_FORWARD_CYCLES && !instr.select_source(schedule_cycle, &registers, gen) {
+                        schedule_cycle += 1;
+                        cycle += 1;
+                        forward += 1;
+                    }
+                    //no register found: the instruction is thrown away and another one is tried
+                    if forward == LOOK_FORWARD_CYCLES {
+                        if throw_away_count < MAX_THROWAWAY_COUNT {
+                            throw_away_count += 1;
+                            macro_op_index = instr.info.ops.len();
+                            continue;
+                        }
+                        abort = true;
+                    }
+                }
+                if !abort && instr.info.dst_op == macro_op_index {
+                    let mut forward = 0;
+                    while forward < LOOK_FORWARD_CYCLES && !instr.select_destination(schedule_cycle, throw_away_count > 0, &registers, gen) {
+                        schedule_cycle += 1;
+                        cycle += 1;
+                        forward += 1;
+                    }
+                    if forward == LOOK_FORWARD_CYCLES {
+                        if throw_away_count < MAX_THROWAWAY_COUNT {
+                            throw_away_count += 1;
+                            macro_op_index = instr.info.ops.len();
+                            continue;
+                        }
+                        abort = true;
+                    }
+                }
+
+                if !abort {
+                    throw_away_count = 0;
+
+                    //the operands are known, schedule for real
+                    schedule_cycle = match schedule_mop(&mop, &mut port_busy, schedule_cycle, schedule_cycle, true) {
+                        Some(c) => c,
+                        None => {
+                            ports_saturated = true;
+                            break;
+                        }
+                    };
+                    dep_cycle = schedule_cycle + mop.latency;
+
+                    if macro_op_index == instr.info.result_op {
+                        let reg = &mut registers[instr.dst.expect("destination selected before the result")];
+                        reg.latency = dep_cycle;
+                        reg.last_op_group = Some(instr.op_group);
+                        reg.last_op_par = instr.op_group_par;
+                    }
+                    buffer_index += 1;
+                    macro_op_index += 1;
+
+                    if schedule_cycle >= SUPERSCALAR_LATENCY {
+                        ports_saturated = true;
+                    }
+                    cycle = top_cycle;
+
+                    //all macro-ops issued, the instruction is complete
+                    if macro_op_index >= instr.info.ops.len() {
+                        instructions.push(instr.to_instruction());
+                        if instr.info.opcode.is_multiplication() {
+                            mul_count += 1;
+                        }
+                    }
+                }
+            }
+            if abort {
+                //no registers for this decode buffer
+                current = None;
+                break;
+            }
+        }
+        cycle += 1;
+        decode_cycle += 1;
+    }
+
+    //the address register has the longest dependency chain on a hypothetical ASIC
+    //(1 cycle per operation, unlimited parallelism)
+    let mut asic_latencies = [0usize; 8];
+    for instr in &instructions {
+        let lat_dst = asic_latencies[instr.dst] + 1;
+        let lat_src = if instr.dst != instr.src { asic_latencies[instr.src] + 1 } else { 0 };
+        asic_latencies[instr.dst] = cmp::max(lat_dst, lat_src);
+    }
+    let mut address_register = 0;
+    let mut max_latency = 0;
+    for (i, latency) in asic_latencies.iter().enumerate() {
+        if *latency > max_latency {
+            max_latency = *latency;
+            address_register = i;
+        }
+    }
+    SuperscalarProgram{instructions, address_register}
+}
+
+impl SuperscalarProgram {
+    pub fn execute(&self, r: &mut [u64; 8]) {
+        for instr in &self.instructions {
+            let src = r[instr.src];
+            let dst = r[instr.dst];
+            r[instr.dst] = match instr.opcode {
+                Opcode::IsubR => dst.wrapping_sub(src),
+                Opcode::IxorR => dst ^ src,
+                Opcode::IaddRs => dst.wrapping_add(src << ((instr.mod_ >> 2) % 4)),
+                Opcode::ImulR => dst.wrapping_mul(src),
+                Opcode::IrorC => dst.rotate_right(instr.imm32),
+                Opcode::IaddC7 | Opcode::IaddC8 | Opcode::IaddC9 => dst.wrapping_add(sign_extend(instr.imm32)),
+                Opcode::IxorC7 | Opcode::IxorC8 | Opcode::IxorC9 => dst ^ sign_extend(instr.imm32),
+                Opcode::ImulhR => mulh(dst, src),
+                Opcode::IsmulhR => smulh(dst, src),
+                Opcode::ImulRcp => dst.wrapping_mul(instr.reciprocal)
+            };
+        }
+    }
+}
+
+/// The 32 bit immediate as signed 64 bit value (two's complement)
+#[inline(always)]
+pub fn sign_extend(imm32: u32) -> u64 {
+    imm32 as i32 as i64 as u64
+}
+
+/// High 64 bits of the unsigned 128 bit product
+#[inline(always)]
+pub fn mulh(a: u64, b: u64) -> u64 {
+    ((u128::from(a) * u128::from(b)) >> 64) as u64
+}
+
+/// High 64 bits of the signed 128 bit product
+#[inline(always)]
+pub fn smulh(a: u64, b: u64) -> u64 {
+    ((i128::from(a as i64) * i128::from(b as i64)) >> 64) as u64
+}
+
+/// 2^x / divisor for the highest x that fits 64 bits, the divisor is no power of 2
+pub fn reciprocal(divisor: u64) -> u64 {
+    let p2exp63 = 1u64 << 63;
+    let mut quotient = p2exp63 / divisor;
+    let mut remainder = p2exp63 % divisor;
+    let bsr = 64 - divisor.leading_zeros();
+    for _ in 0..bsr {
+        if remainder >= divisor - remainder {
+            quotient = quotient.wrapping_mul(2).wrapping_add(1);
+            remainder = remainder.wrapping_mul(2).wrapping_sub(divisor);
+        } else {
+            quotient = quotient.wrapping_mul(2);
+            remainder = remainder.wrapping_mul(2);
+        }
+    }
+    quotient
+}
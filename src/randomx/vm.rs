@@ -0,0 +1,554 @@
+use std::arch::x86_64::{_mm_getcsr, _mm_setcsr};
+use std::sync::{Arc};
+use super::{blake2b, Cache, Dataset, RandomXConfig, OPCODE_COUNT, HASH_SIZE, CACHE_LINE_SIZE};
+use super::soft_aes::{SoftAes};
+use super::superscalar::{sign_extend, mulh, smulh, reciprocal};
+
+const REGISTER_NEEDS_DISPLACEMENT : usize = 5;
+const STORE_L3_CONDITION : u8 = 14;
+
+const MANTISSA_SIZE : u64 = 52;
+const MANTISSA_MASK : u64 = (1u64 << MANTISSA_SIZE) - 1;
+const EXPONENT_MASK : u64 = 2047;
+const EXPONENT_BIAS : u64 = 1023;
+const DYNAMIC_EXPONENT_BITS : u64 = 4;
+const STATIC_EXPONENT_BITS : u64 = 4;
+const CONST_EXPONENT_BITS : u64 = 0x300;
+const DYNAMIC_MANTISSA_MASK : u64 = (1u64 << (MANTISSA_SIZE + DYNAMIC_EXPONENT_BITS)) - 1;
+const FSCAL_MASK : u64 = 0x80F0_0000_0000_0000;
+
+/// Round to nearest with all exceptions masked, the rounding mode of a RandomX program
+const MXCSR_DEFAULT : u32 = 0x9FC0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    IaddRs, IaddM, IsubR, IsubM, ImulR, ImulM, ImulhR, ImulhM, IsmulhR, IsmulhM,
+    ImulRcp, InegR, IxorR, IxorM, IrorR, IrolR, IswapR, FswapR,
+    FaddR, FaddM, FsubR, FsubM, FscalR, FmulR, FdivM, FsqrtR,
+    Cbranch, Cfround, Istore,
+}
+
+/// The instructions in the order of the opcode frequencies of `RandomXConfig`
+const KINDS : [Kind; OPCODE_COUNT] = [
+    Kind::IaddRs, Kind::IaddM, Kind::IsubR, Kind::IsubM, Kind::ImulR, Kind::ImulM, Kind::ImulhR, Kind::ImulhM,
+    Kind::IsmulhR, Kind::IsmulhM, Kind::ImulRcp, Kind::InegR, Kind::IxorR, Kind::IxorM, Kind::IrorR, Kind::IrolR,
+    Kind::IswapR, Kind::FswapR, Kind::FaddR, Kind::FaddM, Kind::FsubR, Kind::FsubM, Kind::FscalR, Kind::FmulR,
+    Kind::FdivM, Kind::FsqrtR, Kind::Cbranch, Kind::Cfround, Kind::Istore,
+];
+
+fn opcode_table(frequencies: &[usize; OPCODE_COUNT]) -> [Kind; 256] {
+    let mut table = [Kind::IaddRs; 256];
+    let mut opcode = 0;
+    for (&kind, &frequency) in KINDS.iter().zip(frequencies.iter()) {
+        for _ in 0..frequency {
+            table[opcode] = kind;
+            opcode += 1;
+        }
+    }
+    table
+}
+
+/// Source operand of an integer instruction, an immediate if source and destination are the same
+#[derive(Debug, Clone, Copy)]
+enum Src {
+    Reg(usize),
+    Imm(u64),
+}
+
+/// Scratchpad operand, the address is `(r[base] + imm) & mask` (`base` none reads from 0)
+#[derive(Debug, Clone, Copy)]
+struct Mem {
+    base: Option<usize>,
+    imm: u64,
+    mask: u64,
+}
+
+impl Mem {
+    fn new(src: usize, dst: usize, mod_: u8, imm32: u32, masks: &Masks) -> Mem {
+        if src != dst {
+            Mem{base: Some(src), imm: sign_extend(imm32), mask: masks.mem(mod_)}
+        } else {
+            Mem{base: None, imm: sign_extend(imm32), mask: masks.l3}
+        }
+    }
+
+    #[inline(always)]
+    fn address(&self, r: &[u64; 8]) -> usize {
+        let base = self.base.map(|b| r[b]).unwrap_or(0);
+        (base.wrapping_add(self.imm) & self.mask) as usize
+    }
+}
+
+/// Address masks of the scratchpad levels and the dataset of a `RandomXConfig`
+#[derive(Debug, Clone, Copy)]
+struct Masks {
+    l1: u64,
+    l2: u64,
+    l3: u64,
+    /// a 64 byte line of the L3 scratchpad
+    l3_line: u64,
+    cache_line_align: u64,
+    condition: u64,
+}
+
+impl Masks {
+    fn new(config: &RandomXConfig) -> Masks {
+        Masks{
+            l1: (config.scratchpad_l1 as u64 - 1) & !7,
+            l2: (config.scratchpad_l2 as u64 - 1) & !7,
+            l3: (config.scratchpad_l3 as u64 - 1) & !7,
+            l3_line: (config.scratchpad_l3 as u64 - 1) & !63,
+            cache_line_align: (config.dataset_base_size - 1) & !(CACHE_LINE_SIZE - 1),
+            condition: (1u64 << config.jump_bits) - 1,
+        }
+    }
+
+    fn mem(&self, mod_: u8) -> u64 {
+        if mod_ % 4 != 0 { self.l1 } else { self.l2 }
+    }
+}
+
+/// An instruction with decoded operands
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    IaddRs{dst: usize, src: usize, shift: u32, imm: u64},
+    IaddM{dst: usize, mem: Mem},
+    IsubR{dst: usize, src: Src},
+    IsubM{dst: usize, mem: Mem},
+    ImulR{dst: usize, src: Src},
+    ImulM{dst: usize, mem: Mem},
+    ImulhR{dst: usize, src: usize},
+    ImulhM{dst: usize, mem: Mem},
+    IsmulhR{dst: usize, src: usize},
+    IsmulhM{dst: usize, mem: Mem},
+    InegR{dst: usize},
+    IxorR{dst: usize, src: Src},
+    IxorM{dst: usize, mem: Mem},
+    IrorR{dst: usize, src: Src},
+    IrolR{dst: usize, src: Src},
+    IswapR{dst: usize, src: usize},
+    FswapF{dst: usize},
+    FswapE{dst: usize},
+    FaddR{dst: usize, src: usize},
+    FaddM{dst: usize, mem: Mem},
+    FsubR{dst: usize, src: usize},
+    FsubM{dst: usize, mem: Mem},
+    FscalR{dst: usize},
+    FmulR{dst: usize, src: usize},
+    FdivM{dst: usize, mem: Mem},
+    FsqrtR{dst: usize},
+    Cbranch{dst: usize, imm: u64, mask: u64, target: isize},
+    Cfround{src: usize, rotate: u32},
+    Istore{dst: usize, src: usize, imm: u64, mask: u64},
+    Nop,
+}
+
+/// The registers hashed at the end of each program
+#[derive(Debug, Clone, Copy)]
+pub struct RegisterFile {
+    pub r: [u64; 8],
+    pub f: [[f64; 2]; 4],
+    pub e: [[f64; 2]; 4],
+    pub a: [[f64; 2]; 4],
+}
+
+impl RegisterFile {
+    fn new() -> RegisterFile {
+        RegisterFile{r: [0; 8], f: [[0.0; 2]; 4], e: [[0.0; 2]; 4], a: [[0.0; 2]; 4]}
+    }
+
+    /// The 256 bytes as in memory of the reference implementation
+    pub fn to_bytes(&self) -> [u8; 256] {
+        let mut bytes = [0u8; 256];
+        for (i, r) in self.r.iter().enumerate() {
+            bytes[i * 8..i * 8 + 8].copy_from_slice(&r.to_le_bytes());
+        }
+        for (group, regs) in [&self.f, &self.e, &self.a].iter().enumerate() {
+            for (i, reg) in regs.iter().enumerate() {
+                for (lane, x) in reg.iter().enumerate() {
+                    let offset = 64 + group * 64 + i * 16 + lane * 8;
+                    bytes[offset..offset + 8].copy_from_slice(&x.to_bits().to_le_bytes());
+                }
+            }
+        }
+        bytes
+    }
+}
+
+/// Dataset items are computed from the cache (light mode, 256 MiB shared) or read from the
+/// full dataset (fast mode, 2080 MiB shared)
+#[derive(Clone)]
+pub enum Memory {
+    Light(Arc<Cache>),
+    Full(Arc<Dataset>),
+}
+
+impl Memory {
+    pub fn seed(&self) -> &[u8] {
+        match *self {
+            Memory::Light(ref cache) => cache.seed(),
+            Memory::Full(ref dataset) => dataset.seed()
+        }
+    }
+
+    /// The variant of the cache or dataset
+    pub fn config(&self) -> &RandomXConfig {
+        match *self {
+            Memory::Light(ref cache) => cache.config(),
+            Memory::Full(ref dataset) => dataset.config()
+        }
+    }
+
+    #[inline(always)]
+    fn item(&self, item: u64) -> [u64; 8] {
+        match *self {
+            Memory::Light(ref cache) => cache.dataset_item(item),
+            Memory::Full(ref dataset) => dataset.item(item)
+        }
+    }
+}
+
+/// Program dependent configuration from the entropy words
+struct Config {
+    read_reg: [usize; 4],
+    e_mask: [u64; 2],
+}
+
+/// A RandomX virtual machine (interpreter), one per worker thread
+pub struct Vm {
+    memory: Memory,
+    /// the variant of `memory`
+    params: RandomXConfig,
+    masks: Masks,
+    aes: SoftAes,
+    opcodes: [Kind; 256],
+    scratchpad: Vec<u8>,
+    reg: RegisterFile,
+    ma: u32,
+    mx: u32,
+    dataset_offset: u64,
+    config: Config,
+}
+
+impl Vm {
+    /// A vm hashing with `memory` and the `RandomXConfig` it was built with
+    pub fn new(memory: Memory) -> Vm {
+        let params = *memory.config();
+        Vm{memory, params, masks: Masks::new(&params), aes: SoftAes::new(), opcodes: opcode_table(&params.opcode_frequencies),
+            scratchpad: vec![0u8; params.scratchpad_l3], reg: RegisterFile::new(), ma: 0, mx: 0, dataset_offset: 0,
+            config: Config{read_reg: [0, 2, 4, 6], e_mask: [0, 0]}}
+    }
+
+    /// Seed hash of the cache or dataset the hashes are computed with
+    pub fn seed(&self) -> &[u8] {
+        self.memory.seed()
+    }
+
+    /// The 32 byte RandomX hash of `input` (the hashing blob)
+    pub fn calculate_hash(&mut self, input: &[u8]) -> [u8; HASH_SIZE] {
+        let saved_mxcsr = unsafe { _mm_getcsr() };
+
+        let mut temp = [0u8; 64];
+        blake2b(&mut temp, input);
+        self.aes.fill_1rx4(&mut temp, &mut self.scratchpad);
+        set_rounding_mode(0);
+        for chain in 0..self.params.program_count {
+            self.run(&temp);
+            if chain + 1 < self.params.program_count {
+                blake2b(&mut temp, &self.reg.to_bytes()[..]);
+            }
+        }
+        let a = self.aes.hash_1rx4(&self.scratchpad);
+        for (i, reg) in self.reg.a.iter_mut().enumerate() {
+            reg[0] = f64::from_bits(read_u64(&a, i * 16));
+            reg[1] = f64::from_bits(read_u64(&a, i * 16 + 8));
+        }
+        let mut result = [0u8; HASH_SIZE];
+        blake2b(&mut result, &self.reg.to_bytes()[..]);
+
+        //cryptonight hashes on the same thread expect the default rounding
+        unsafe { _mm_setcsr(saved_mxcsr) };
+        result
+    }
+
+    /// Generates the program of `seed` and runs it on the scratchpad
+    fn run(&mut self, seed: &[u8; 64]) {
+        //program entropy words followed by the instructions, 8 bytes each
+        let mut buffer = vec![0u8; 16 * 8 + self.params.program_size * 8];
+        self.aes.fill_4rx4(seed, &mut buffer);
+        let mut entropy = [0u64; 16];
+        for (i, e) in entropy.iter_mut().enumerate() {
+            *e = read_u64(&buffer, i * 8);
+        }
+        self.initialize(&entropy);
+        let program = self.compile(&buffer[16 * 8..]);
+        self.execute(&program);
+    }
+
+    fn initialize(&mut self, entropy: &[u64; 16]) {
+        for i in 0..4 {
+            self.reg.a[i][0] = f64::from_bits(small_positive_float_bits(entropy[2 * i]));
+            self.reg.a[i][1] = f64::from_bits(small_positive_float_bits(entropy[2 * i + 1]));
+        }
+        self.ma = (entropy[8] & self.masks.cache_line_align) as u32;
+        self.mx = entropy[10] as u32;
+        let address_registers = entropy[12];
+        for i in 0..4 {
+            self.config.read_reg[i] = 2 * i + ((address_registers >> i) & 1) as usize;
+        }
+        self.dataset_offset = (entropy[13] % (self.params.dataset_extra_items() + 1)) * CACHE_LINE_SIZE;
+        self.config.e_mask = [float_mask(entropy[14]), float_mask(entropy[15])];
+    }
+
+    fn compile(&self, instructions: &[u8]) -> Vec<Op> {
+        //the last instruction modifying each register, the target of a branch on it
+        let mut register_usage = [-1isize; 8];
+        let masks = &self.masks;
+        let mut program = Vec::with_capacity(self.params.program_size);
+        for (i, bytes) in instructions.chunks(8).enumerate() {
+            let opcode = bytes[0];
+            let dst = bytes[1] as usize % 8;
+            let src = bytes[2] as usize % 8;
+            let mod_ = bytes[3];
+            let imm32 = read_u32(bytes, 4);
+            let src_or_imm = if src != dst { Src::Reg(src) } else { Src::Imm(sign_extend(imm32)) };
+            let fdst = dst % 4;
+            let fsrc = src % 4;
+
+            let op = match self.opcodes[opcode as usize] {
+                Kind::IaddRs => {
+                    let imm = if dst == REGISTER_NEEDS_DISPLACEMENT { sign_extend(imm32) } else { 0 };
+                    Op::IaddRs{dst, src, shift: u32::from((mod_ >> 2) % 4), imm}
+                },
+                Kind::IaddM => Op::IaddM{dst, mem: Mem::new(src, dst, mod_, imm32, masks)},
+                Kind::IsubR => Op::IsubR{dst, src: src_or_imm},
+                Kind::IsubM => Op::IsubM{dst, mem: Mem::new(src, dst, mod_, imm32, masks)},
+                Kind::ImulR => Op::ImulR{dst, src: src_or_imm},
+                Kind::ImulM => Op::ImulM{dst, mem: Mem::new(src, dst, mod_, imm32, masks)},
+                Kind::ImulhR => Op::ImulhR{dst, src},
+                Kind::ImulhM => Op::ImulhM{dst, mem: Mem::new(src, dst, mod_, imm32, masks)},
+                Kind::IsmulhR => Op::IsmulhR{dst, src},
+                Kind::IsmulhM => Op::IsmulhM{dst, mem: Mem::new(src, dst, mod_, imm32, masks)},
+                Kind::ImulRcp => {
+                    if imm32 == 0 || imm32.is_power_of_two() {
+                        Op::Nop
+                    } else {
+                        Op::ImulR{dst, src: Src::Imm(reciprocal(u64::from(imm32)))}
+                    }
+                },
+                Kind::InegR => Op::InegR{dst},
+                Kind::IxorR => Op::IxorR{dst, src: src_or_imm},
+                Kind::IxorM => Op::IxorM{dst, mem: Mem::new(src, dst, mod_, imm32, masks)},
+                Kind::IrorR => Op::IrorR{dst, src: src_or_imm},
+                Kind::IrolR => Op::IrolR{dst, src: src_or_imm},
+                Kind::IswapR => {
+                    if src != dst {
+                        register_usage[src] = i as isize;
+                        Op::IswapR{dst, src}
+                    } else {
+                        Op::Nop
+                    }
+                },
+                Kind::FswapR => if dst < 4 { Op::FswapF{dst} } else { Op::FswapE{dst: dst - 4} },
+                Kind::FaddR => Op::FaddR{dst: fdst, src: fsrc},
+                Kind::FaddM => Op::FaddM{dst: fdst, mem: Mem{base: Some(src), imm: sign_extend(imm32), mask: masks.mem(mod_)}},
+                Kind::FsubR => Op::FsubR{dst: fdst, src: fsrc},
+                Kind::FsubM => Op::FsubM{dst: fdst, mem: Mem{base: Some(src), imm: sign_extend(imm32), mask: masks.mem(mod_)}},
+                Kind::FscalR => Op::FscalR{dst: fdst},
+                Kind::FmulR => Op::FmulR{dst: fdst, src: fsrc},
+                Kind::FdivM => Op::FdivM{dst: fdst, mem: Mem{base: Some(src), imm: sign_extend(imm32), mask: masks.mem(mod_)}},
+                Kind::FsqrtR => Op::FsqrtR{dst: fdst},
+                Kind::Cbranch => {
+                    let shift = u32::from(mod_ >> 4) + self.params.jump_offset;
+                    let mut imm = sign_extend(imm32) | (1u64 << shift);
+                    imm &= !(1u64 << (shift - 1));
+                    let op = Op::Cbranch{dst, imm, mask: masks.condition << shift, target: register_usage[dst]};
+                    //all registers are modified by a taken branch
+                    for usage in register_usage.iter_mut() {
+                        *usage = i as isize;
+                    }
+                    op
+                },
+                Kind::Cfround => Op::Cfround{src, rotate: imm32 & 63},
+                Kind::Istore => {
+                    let mask = if mod_ >> 4 < STORE_L3_CONDITION { masks.mem(mod_) } else { masks.l3 };
+                    Op::Istore{dst, src, imm: sign_extend(imm32), mask}
+                }
+            };
+            match op {
+                Op::IaddRs{..} | Op::IaddM{..} | Op::IsubR{..} | Op::IsubM{..} | Op::ImulR{..} | Op::ImulM{..} |
+                Op::ImulhR{..} | Op::ImulhM{..} | Op::IsmulhR{..} | Op::IsmulhM{..} | Op::InegR{..} |
+                Op::IxorR{..} | Op::IxorM{..} | Op::IrorR{..} | Op::IrolR{..} | Op::IswapR{..} => register_usage[dst] = i as isize,
+                _ => {}
+            }
+            program.push(op);
+        }
+        program
+    }
+
+    fn execute(&mut self, program: &[Op]) {
+        self.reg.r = [0; 8];
+        let mut sp_addr0 = u64::from(self.mx);
+        let mut sp_addr1 = u64::from(self.ma);
+
+        for _ in 0..self.params.program_iterations {
+            let rr = self.config.read_reg;
+            let sp_mix = self.reg.r[rr[0]] ^ self.reg.r[rr[1]];
+            sp_addr0 = (sp_addr0 ^ sp_mix) & self.masks.l3_line;
+            sp_addr1 = (sp_addr1 ^ (sp_mix >> 32)) & self.masks.l3_line;
+            let addr0 = sp_addr0 as usize;
+            let addr1 = sp_addr1 as usize;
+
+            for i in 0..8 {
+                self.reg.r[i] ^= read_u64(&self.scratchpad, addr0 + 8 * i);
+            }
+            for i in 0..4 {
+                self.reg.f[i] = read_int_pair(&self.scratchpad, addr1 + 8 * i);
+                self.reg.e[i] = mask_exponent_mantissa(read_int_pair(&self.scratchpad, addr1 + 8 * (4 + i)), &self.config.e_mask);
+            }
+
+            execute_program(program, &mut self.reg, &mut self.scratchpad, &self.config.e_mask);
+
+            self.mx ^= (self.reg.r[rr[2]] ^ self.reg.r[rr[3]]) as u32;
+            self.mx &= self.masks.cache_line_align as u32;
+            let item = self.memory.item((self.dataset_offset + u64::from(self.ma)) / CACHE_LINE_SIZE);
+            for (r, x) in self.reg.r.iter_mut().zip(item.iter()) {
+                *r ^= *x;
+            }
+            ::std::mem::swap(&mut self.mx, &mut self.ma);
+
+            for i in 0..8 {
+                write_u64(&mut self.scratchpad, addr1 + 8 * i, self.reg.r[i]);
+            }
+            for i in 0..4 {
+                for lane in 0..2 {
+                    let x = self.reg.f[i][lane].to_bits() ^ self.reg.e[i][lane].to_bits();
+                    self.reg.f[i][lane] = f64::from_bits(x);
+                    write_u64(&mut self.scratchpad, addr0 + 16 * i + 8 * lane, x);
+                }
+            }
+            sp_addr0 = 0;
+            sp_addr1 = 0;
+        }
+    }
+}
+
+fn execute_program(program: &[Op], reg: &mut RegisterFile, scratchpad: &mut [u8], e_mask: &[u64; 2]) {
+    let r = &mut reg.r;
+    let f = &mut reg.f;
+    let e = &mut reg.e;
+    let a = &reg.a;
+    let mut pc : isize = 0;
+    while pc < program.len() as isize {
+        match program[pc as usize] {
+            Op::IaddRs{dst, src, shift, imm} => r[dst] = r[dst].wrapping_add(r[src] << shift).wrapping_add(imm),
+            Op::IaddM{dst, mem} => r[dst] = r[dst].wrapping_add(read_u64(scratchpad, mem.address(r))),
+            Op::IsubR{dst, src} => r[dst] = r[dst].wrapping_sub(src_value(r, src)),
+            Op::IsubM{dst, mem} => r[dst] = r[dst].wrapping_sub(read_u64(scratchpad, mem.address(r))),
+            Op::ImulR{dst, src} => r[dst] = r[dst].wrapping_mul(src_value(r, src)),
+            Op::ImulM{dst, mem} => r[dst] = r[dst].wrapping_mul(read_u64(scratchpad, mem.address(r))),
+            Op::ImulhR{dst, src} => r[dst] = mulh(r[dst], r[src]),
+            Op::ImulhM{dst, mem} => r[dst] = mulh(r[dst], read_u64(scratchpad, mem.address(r))),
+            Op::IsmulhR{dst, src} => r[dst] = smulh(r[dst], r[src]),
+            Op::IsmulhM{dst, mem} => r[dst] = smulh(r[dst], read_u64(scratchpad, mem.address(r))),
+            Op::InegR{dst} => r[dst] = r[dst].wrapping_neg(),
+            Op::IxorR{dst, src} => r[dst] ^= src_value(r, src),
+            Op::IxorM{dst, mem} => r[dst] ^= read_u64(scratchpad, mem.address(r)),
+            Op::IrorR{dst, src} => r[dst] = r[dst].rotate_right((src_value(r, src) & 63) as u32),
+            Op::IrolR{dst, src} => r[dst] = r[dst].rotate_left((src_value(r, src) & 63) as u32),
+            Op::IswapR{dst, src} => r.swap(dst, src),
+            Op::FswapF{dst} => f[dst].swap(0, 1),
+            Op::FswapE{dst} => e[dst].swap(0, 1),
+            Op::FaddR{dst, src} => f[dst] = [f[dst][0] + a[src][0], f[dst][1] + a[src][1]],
+            Op::FaddM{dst, mem} => {
+                let x = read_int_pair(scratchpad, mem.address(r));
+                f[dst] = [f[dst][0] + x[0], f[dst][1] + x[1]];
+            },
+            Op::FsubR{dst, src} => f[dst] = [f[dst][0] - a[src][0], f[dst][1] - a[src][1]],
+            Op::FsubM{dst, mem} => {
+                let x = read_int_pair(scratchpad, mem.address(r));
+                f[dst] = [f[dst][0] - x[0], f[dst][1] - x[1]];
+            },
+            Op::FscalR{dst} => f[dst] = [f64::from_bits(f[dst][0].to_bits() ^ FSCAL_MASK), f64::from_bits(f[dst][1].to_bits() ^ FSCAL_MASK)],
+            Op::FmulR{dst, src} => e[dst] = [e[dst][0] * a[src][0], e[dst][1] * a[src][1]],
+            Op::FdivM{dst, mem} => {
+                let x = mask_exponent_mantissa(read_int_pair(scratchpad, mem.address(r)), e_mask);
+                e[dst] = [e[dst][0] / x[0], e[dst][1] / x[1]];
+            },
+            Op::FsqrtR{dst} => e[dst] = [e[dst][0].sqrt(), e[dst][1].sqrt()],
+            Op::Cbranch{dst, imm, mask, target} => {
+                r[dst] = r[dst].wrapping_add(imm);
+                if r[dst] & mask == 0 {
+                    pc = target;
+                }
+            },
+            Op::Cfround{src, rotate} => set_rounding_mode((r[src].rotate_right(rotate) % 4) as u32),
+            Op::Istore{dst, src, imm, mask} => {
+                let address = (r[dst].wrapping_add(imm) & mask) as usize;
+                write_u64(scratchpad, address, r[src]);
+            },
+            Op::Nop => {}
+        }
+        pc += 1;
+    }
+}
+
+#[inline(always)]
+fn src_value(r: &[u64; 8], src: Src) -> u64 {
+    match src {
+        Src::Reg(i) => r[i],
+        Src::Imm(imm) => imm
+    }
+}
+
+/// 0 nearest, 1 down, 2 up, 3 towards zero (the order of the MXCSR rounding control bits)
+fn set_rounding_mode(mode: u32) {
+    unsafe { _mm_setcsr(MXCSR_DEFAULT | (mode << 13)) };
+}
+
+/// A positive float in [1, 2^32) from the entropy, for the `a` registers
+fn small_positive_float_bits(entropy: u64) -> u64 {
+    let exponent = ((entropy >> 59) + EXPONENT_BIAS) & EXPONENT_MASK;
+    (exponent << MANTISSA_SIZE) | (entropy & MANTISSA_MASK)
+}
+
+fn static_exponent(entropy: u64) -> u64 {
+    let exponent = CONST_EXPONENT_BITS | ((entropy >> (64 - STATIC_EXPONENT_BITS)) << DYNAMIC_EXPONENT_BITS);
+    exponent << MANTISSA_SIZE
+}
+
+/// Mask of the `e` registers: a fixed exponent and 22 low mantissa bits from the entropy
+fn float_mask(entropy: u64) -> u64 {
+    let mask22bit = (1u64 << 22) - 1;
+    (entropy & mask22bit) | static_exponent(entropy)
+}
+
+fn mask_exponent_mantissa(x: [f64; 2], e_mask: &[u64; 2]) -> [f64; 2] {
+    [f64::from_bits((x[0].to_bits() & DYNAMIC_MANTISSA_MASK) | e_mask[0]),
+     f64::from_bits((x[1].to_bits() & DYNAMIC_MANTISSA_MASK) | e_mask[1])]
+}
+
+/// Two signed 32 bit integers converted to floats
+#[inline(always)]
+fn read_int_pair(bytes: &[u8], offset: usize) -> [f64; 2] {
+    [f64::from(read_u32(bytes, offset) as i32), f64::from(read_u32(bytes, offset + 4) as i32)]
+}
+
+#[inline(always)]
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    let mut b = [0u8; 4];
+    b.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_le_bytes(b)
+}
+
+#[inline(always)]
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    let mut b = [0u8; 8];
+    b.copy_from_slice(&bytes[offset..offset + 8]);
+    u64::from_le_bytes(b)
+}
+
+#[inline(always)]
+fn write_u64(bytes: &mut [u8], offset: usize, value: u64) {
+    bytes[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+}
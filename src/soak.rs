@@ -0,0 +1,94 @@
+use std::fs;
+use std::thread;
+use std::sync::mpsc::{channel};
+use std::time::{Duration};
+use stratum::{StratumClient};
+use stratum::stratum_data::{PoolConfig};
+
+/// Growth of the resident memory that is still considered noise
+pub const RSS_TOLERANCE_KB : u64 = 10 * 1024;
+
+/// Resource usage of the process
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProcessStats {
+    pub threads: u64,
+    pub open_fds: u64,
+    pub rss_kb: u64,
+}
+
+#[derive(Debug)]
+pub struct SoakResult {
+    pub iterations: u64,
+    pub failed_logins: u64,
+    /// measured after the first iteration, None if not supported on this platform
+    pub baseline: Option<ProcessStats>,
+    pub after: Option<ProcessStats>,
+}
+
+impl SoakResult {
+    /// Descriptions of the resources that grew between the baseline and the end of the run
+    pub fn leaks(&self) -> Vec<String> {
+        let mut leaks = Vec::new();
+        if let (Some(baseline), Some(after)) = (self.baseline, self.after) {
+            if after.threads > baseline.threads {
+                leaks.push(format!("threads: {} -> {}", baseline.threads, after.threads));
+            }
+            if after.open_fds > baseline.open_fds {
+                leaks.push(format!("open file descriptors: {} -> {}", baseline.open_fds, after.open_fds));
+            }
+            if after.rss_kb > baseline.rss_kb + RSS_TOLERANCE_KB {
+                leaks.push(format!("resident memory: {} kB -> {} kB", baseline.rss_kb, after.rss_kb));
+            }
+        }
+        leaks
+    }
+}
+
+/// Logs in to the pool and stops the client again `iterations` times, waiting
+/// `pause` between the login and the stop and after every iteration.
+pub fn run(pool_conf: &PoolConfig, iterations: u64, pause: Duration) -> SoakResult {
+    let mut failed_logins = 0;
+    let mut baseline = None;
+    for i in 0..iterations {
+        let (err_tx, _err_rx) = channel();
+        let (action_tx, action_rx) = channel();
+        match StratumClient::login(pool_conf.clone(), err_tx, action_tx) {
+            Ok(client) => {
+                //give the pool the chance to answer the login
+                let _ = action_rx.recv_timeout(pause);
+                client.stop();
+            },
+            Err(e) => {
+                info!("soak iteration {}: login failed: {}", i, e);
+                failed_logins += 1;
+            }
+        }
+        thread::sleep(pause);
+        if i == 0 {
+            baseline = process_stats();
+        }
+    }
+    SoakResult{iterations, failed_logins, baseline, after: process_stats()}
+}
+
+#[cfg(target_os = "linux")]
+pub fn process_stats() -> Option<ProcessStats> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let (threads, rss_kb) = parse_status(&status)?;
+    let open_fds = fs::read_dir("/proc/self/fd").ok()?.count() as u64;
+    Some(ProcessStats{threads, open_fds, rss_kb})
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn process_stats() -> Option<ProcessStats> {
+    None
+}
+
+/// Thread count and resident memory (kB) from the content of `/proc/self/status`
+pub fn parse_status(status: &str) -> Option<(u64, u64)> {
+    let field = |name: &str| -> Option<u64> {
+        let line = status.lines().find(|l| l.starts_with(name))?;
+        line[name.len()..].split_whitespace().next()?.parse().ok()
+    };
+    Some((field("Threads:")?, field("VmRSS:")?))
+}
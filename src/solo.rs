@@ -0,0 +1,243 @@
+extern crate serde;
+extern crate serde_json;
+
+use std::thread;
+use std::sync::{Arc};
+use std::sync::mpsc::{channel, Receiver, Sender, RecvTimeoutError};
+use std::io;
+use std::io::{Read, Write, Error, ErrorKind};
+use std::time::{Duration};
+use error::{MithrilError};
+use stratum;
+use stratum::{StratumCmd, StratumAction, StratumError, StratumEvent};
+use stratum::stratum_data::{PoolConfig, Share};
+use stratum::share_queue::{ShareQueue};
+use worker::worker_pool;
+use difficulty;
+
+/// Seconds between block template requests, a new job is only sent if the chain tip changed
+const POLL_INTERVAL_SECS : u64 = 5;
+/// Timeout of a single JSON-RPC request to the daemon
+const RPC_TIMEOUT_SECS : u64 = 30;
+/// Miner id of solo jobs, there is no login
+const SOLO_MINER_ID : &str = "solo";
+
+#[derive(Serialize)]
+struct RpcRequest<'a, P> {
+    jsonrpc: &'a str,
+    id: &'a str,
+    method: &'a str,
+    params: P
+}
+
+#[derive(Serialize)]
+struct TemplateParams<'a> {
+    wallet_address: &'a str,
+    reserve_size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    extra_nonce: Option<&'a str>
+}
+
+/// The block template a job was created from
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    pub job_id: String,
+    pub prev_hash: String,
+    pub height: u64,
+    pub difficulty: u64,
+    /// the complete block, submitted with the nonce of a found block
+    pub blocktemplate_blob: String,
+    /// the blob to hash (block header, tree root and tx count)
+    pub blockhashing_blob: String,
+    pub seed_hash: Option<String>,
+    pub next_seed_hash: Option<String>,
+}
+
+/// Mines on a local monerod instead of a pool. Jobs are created from
+/// `get_block_template` and found blocks are sent with `submit_block`, using the
+/// same actions and commands as the `StratumClient`.
+pub struct SoloClient {
+    command_sender: Sender<StratumCmd>,
+    thread: thread::JoinHandle<()>,
+    share_queue: Arc<ShareQueue>,
+}
+
+impl SoloClient {
+    pub fn start(pool_conf: PoolConfig, daemon_address: String, err_receiver: Sender<StratumError>, action_rcv: Sender<StratumEvent>) -> Result<SoloClient, MithrilError> {
+        info!("solo mining on daemon: {}", daemon_address);
+        //fail early if the daemon is not reachable
+        let template = get_block_template(&daemon_address, &pool_conf.wallet_address, None, 0)?;
+
+        let (command_sender, command_rcv) = channel();
+        let share_queue = Arc::new(ShareQueue::new(pool_conf.share_queue_capacity as usize, pool_conf.share_queue_policy));
+        let thread_queue = share_queue.clone();
+        let thread = thread::Builder::new().name("Solo thread".to_string()).spawn(move || {
+            if let Err(err) = handle_solo(&command_rcv, &daemon_address, &pool_conf, template, &action_rcv, &thread_queue) {
+                if let Err(e) = err_receiver.send(err) {
+                    info!("error receiver already terminated, error was: {}", e.0);
+                }
+            }
+            thread_queue.close();
+            info!("solo thread ended");
+        }).map_err(StratumError::Io)?;
+
+        Ok(SoloClient{command_sender, thread, share_queue})
+    }
+
+    /// The queue workers submit their shares with
+    pub fn share_queue(&self) -> Arc<ShareQueue> {
+        self.share_queue.clone()
+    }
+
+    pub fn new_cmd_channel(&self) -> Sender<StratumCmd> {
+        self.command_sender.clone()
+    }
+
+    pub fn stop(self) {
+        info!("stopping solo client");
+        self.share_queue.close();
+        if self.command_sender.send(StratumCmd::Shutdown{}).is_err() {
+            info!("solo thread already ended");
+        }
+        if self.thread.join().is_err() {
+            error!("solo thread panicked");
+        }
+    }
+}
+
+fn handle_solo(command_rcv: &Receiver<StratumCmd>, daemon_address: &str, pool_conf: &PoolConfig,
+               first_template: Template, action_rcv: &Sender<StratumEvent>, share_queue: &ShareQueue) -> Result<(), StratumError> {
+    let mut template = first_template;
+    let mut job_seq = 0;
+    send_job(action_rcv, &template)?;
+
+    loop {
+        match command_rcv.recv_timeout(Duration::from_secs(POLL_INTERVAL_SECS)) {
+            Ok(StratumCmd::SubmitShare{share}) => {
+                share_queue.release();
+                let action = submit(daemon_address, &template, &share, pool_conf.dry_run)?;
+                send_action(action_rcv, action)?;
+            },
+            Ok(StratumCmd::Login{}) | Ok(StratumCmd::KeepAlive{..}) | Ok(StratumCmd::SetDryRun{..}) | Ok(StratumCmd::BlockTemplate{..}) | Ok(StratumCmd::CancelJob{..}) | Ok(StratumCmd::JobReceived{..}) | Ok(StratumCmd::Respond{..}) => {
+                //no session with the daemon
+            },
+            Ok(StratumCmd::Shutdown{}) | Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            Err(RecvTimeoutError::Timeout) => {
+                let next_seq = job_seq + 1;
+                let next = get_block_template(daemon_address, &pool_conf.wallet_address, None, next_seq)?;
+                if next.prev_hash != template.prev_hash {
+                    info!("new block template at height {}", next.height);
+                    job_seq = next_seq;
+                    template = next;
+                    send_job(action_rcv, &template)?;
+                }
+            }
+        }
+    }
+}
+
+fn send_job(action_rcv: &Sender<StratumEvent>, template: &Template) -> Result<(), StratumError> {
+    send_action(action_rcv, StratumAction::Job{
+        miner_id: SOLO_MINER_ID.to_string(),
+        blob: template.blockhashing_blob.clone(),
+        job_id: template.job_id.clone(),
+        target: difficulty_target(template.difficulty),
+        difficulty: template.difficulty,
+        algo: None,
+        height: Some(template.height),
+        seed_hash: template.seed_hash.clone(),
+        next_seed_hash: template.next_seed_hash.clone()
+    })
+}
+
+fn send_action(action_rcv: &Sender<StratumEvent>, action: StratumAction) -> Result<(), StratumError> {
+    action_rcv.send(StratumEvent::new(action)).map_err(|_| StratumError::ChannelClosed)
+}
+
+fn submit(daemon_address: &str, template: &Template, share: &Share, dry_run: bool) -> Result<StratumAction, StratumError> {
+    if share.job_id != template.job_id {
+        return Ok(StratumAction::ShareRejected{job_id: share.job_id.clone(), reason: "block expired".to_string(), code: 0});
+    }
+    if let Err(err) = stratum::validate_share(share, None) {
+        return Ok(StratumAction::ShareRejected{job_id: share.job_id.clone(), reason: err, code: 0});
+    }
+    let block = worker_pool::with_nonce(&template.blocktemplate_blob, &share.nonce);
+    if dry_run {
+        info!("dry run, block at height {} not submitted: {}", template.height, block);
+        return Ok(StratumAction::ShareAccepted{job_id: share.job_id.clone()});
+    }
+    info!("submitting block at height {} (hash {})", template.height, share.hash);
+    match json_rpc(daemon_address, "submit_block", vec![block]) {
+        Ok(_) => Ok(StratumAction::ShareAccepted{job_id: share.job_id.clone()}),
+        Err(StratumError::Protocol(reason)) => Ok(StratumAction::ShareRejected{job_id: share.job_id.clone(), reason, code: 0}),
+        Err(err) => Err(err)
+    }
+}
+
+/// Requests a block template paying to `wallet_address`, with `extra_nonce` in the
+/// extra field of the coinbase transaction if given.
+pub fn get_block_template(daemon_address: &str, wallet_address: &str, extra_nonce: Option<&str>, job_seq: u64) -> Result<Template, StratumError> {
+    let result = json_rpc(daemon_address, "get_block_template", TemplateParams{wallet_address, reserve_size: 0, extra_nonce})?;
+    parse_template(&result, job_seq)
+}
+
+/// Builds the template from a `get_block_template` result, `job_seq` makes the job id unique
+pub fn parse_template(result: &serde_json::Value, job_seq: u64) -> Result<Template, StratumError> {
+    let str_field = |name: &str| -> Result<String, StratumError> {
+        result[name].as_str().map(|s| s.to_string())
+            .ok_or_else(|| StratumError::Protocol(format!("{} missing in block template", name)))
+    };
+    let u64_field = |name: &str| -> Result<u64, StratumError> {
+        result[name].as_u64().ok_or_else(|| StratumError::Protocol(format!("{} missing in block template", name)))
+    };
+    let opt_str_field = |name: &str| result[name].as_str().map(|s| s.to_string());
+    let height = u64_field("height")?;
+    Ok(Template{
+        job_id: format!("{}-{}", height, job_seq),
+        prev_hash: str_field("prev_hash")?,
+        height,
+        difficulty: u64_field("difficulty")?,
+        blocktemplate_blob: str_field("blocktemplate_blob")?,
+        blockhashing_blob: str_field("blockhashing_blob")?,
+        seed_hash: opt_str_field("seed_hash"),
+        next_seed_hash: opt_str_field("next_seed_hash"),
+    })
+}
+
+/// The 64 bit job target (16 hex chars, little endian) for a difficulty
+pub fn difficulty_target(difficulty: u64) -> String {
+    difficulty::target_hex(u64::max_value() / difficulty.max(1))
+}
+
+/// Sends a JSON-RPC request to the daemon and returns the result. An error
+/// response of the daemon is returned as `StratumError::Protocol`.
+pub fn json_rpc<P: serde::Serialize>(daemon_address: &str, method: &str, params: P) -> Result<serde_json::Value, StratumError> {
+    let body = serde_json::to_string(&RpcRequest{jsonrpc: "2.0", id: "0", method, params})?;
+    let mut stream = stratum::connect_with_timeout(daemon_address, Duration::from_secs(RPC_TIMEOUT_SECS))?;
+    stream.set_read_timeout(Some(Duration::from_secs(RPC_TIMEOUT_SECS)))?;
+    stream.set_write_timeout(Some(Duration::from_secs(RPC_TIMEOUT_SECS)))?;
+    write!(stream, "POST /json_rpc HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+           daemon_address, body.len(), body)?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    let response_body = http_body(&response)?;
+
+    let mut value : serde_json::Value = serde_json::from_str(response_body)?;
+    if !value["error"].is_null() {
+        let message = value["error"]["message"].as_str().map(|m| m.to_string()).unwrap_or_else(|| value["error"].to_string());
+        return Err(StratumError::Protocol(format!("{} failed: {}", method, message)));
+    }
+    Ok(value["result"].take())
+}
+
+fn http_body(response: &str) -> io::Result<&str> {
+    let status_line = response.lines().next().unwrap_or("");
+    if !status_line.contains(" 200 ") {
+        return Err(Error::new(ErrorKind::Other, format!("unexpected HTTP response from daemon: {}", status_line)));
+    }
+    match response.find("\r\n\r\n") {
+        Some(ix) => Ok(&response[ix + 4..]),
+        None => Err(Error::new(ErrorKind::UnexpectedEof, "incomplete HTTP response from daemon"))
+    }
+}
@@ -0,0 +1,277 @@
+extern crate tokio;
+extern crate futures;
+
+use self::futures::{future, stream, Future, Stream};
+use self::futures::sync::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use self::tokio::codec::{Framed, LinesCodec};
+use self::tokio::net::TcpStream as AsyncTcpStream;
+use self::tokio::reactor::Handle;
+use self::tokio::runtime::current_thread;
+use self::tokio::timer::Delay;
+
+use std::thread;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex, Once};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::net::{Shutdown, TcpStream};
+use std::ptr;
+use std::io;
+use std::io::{Error, ErrorKind};
+use std::time::{Duration, Instant};
+use error::{MithrilError};
+use determinism;
+use super::{StratumCmd, StratumError, StratumEvent, MAX_LINE_LENGTH};
+use super::{join_thread, random_jitter, report_error, retry_transient};
+use super::async_transport::{Session, Ticks, is_shutdown, run_session};
+use super::stratum_data::{PoolConfig};
+use super::pending::{PendingRequests};
+use super::{latency, resolve, socket_stats, log_queue_stats, log_share_stats};
+use super::share_queue::{ShareQueue};
+use super::share_stats::{ShareStats};
+
+/// Builds a connection task on the runtime thread, the session future is not `Send`
+type Spawn = Box<dyn FnOnce() -> Box<dyn Future<Item=(), Error=()>> + Send>;
+
+/// The runtime of `AsyncRuntime::shared()`, allocated once by `shared_runtime()` and never freed
+static mut SHARED_RUNTIME : *const Mutex<Option<AsyncRuntime>> = ptr::null();
+static SHARED_RUNTIME_INIT : Once = Once::new();
+
+fn shared_runtime() -> &'static Mutex<Option<AsyncRuntime>> {
+    unsafe {
+        //written only once, before any read (call_once synchronizes the threads)
+        SHARED_RUNTIME_INIT.call_once(|| SHARED_RUNTIME = Box::into_raw(Box::new(Mutex::new(None))));
+        &*SHARED_RUNTIME
+    }
+}
+
+/// A thread running a tokio runtime, every pool connection on it is a task. All async
+/// clients of the process (including both connections of a `MultiPoolClient`) use the
+/// runtime of `shared()`, so one thread drives every connection.
+#[derive(Clone)]
+pub struct AsyncRuntime {
+    spawner: UnboundedSender<Spawn>,
+}
+
+impl AsyncRuntime {
+    /// Starts a runtime thread, it ends after all handles were dropped and its tasks ended
+    pub fn new() -> io::Result<AsyncRuntime> {
+        let (spawner, spawns) = unbounded::<Spawn>();
+        thread::Builder::new().name("Stratum async runtime thread".to_string()).spawn(move || {
+            let mut runtime = match current_thread::Runtime::new() {
+                Ok(runtime) => runtime,
+                Err(e) => {
+                    error!("starting the async runtime failed: {}", e);
+                    return;
+                }
+            };
+            let spawned = runtime.block_on(spawns.for_each(|spawn| {
+                current_thread::spawn(spawn());
+                Ok(())
+            }));
+            if spawned.and_then(|()| runtime.run().map_err(|_| ())).is_err() {
+                error!("async runtime failed");
+            }
+            info!("stratum async runtime thread ended");
+        })?;
+        Ok(AsyncRuntime{spawner})
+    }
+
+    /// The runtime shared by the async clients of the process, started by the first call
+    /// (and again if its thread ended)
+    pub fn shared() -> io::Result<AsyncRuntime> {
+        let mut shared = shared_runtime().lock().expect("async runtime lock");
+        if let Some(ref runtime) = *shared {
+            if !runtime.spawner.is_closed() {
+                return Ok(runtime.clone());
+            }
+        }
+        let runtime = AsyncRuntime::new()?;
+        *shared = Some(runtime.clone());
+        Ok(runtime)
+    }
+
+    /// Runs the future built by `task` on the runtime thread
+    fn spawn<F>(&self, task: F) -> Result<(), StratumError> where F: FnOnce() -> Box<dyn Future<Item=(), Error=()>> + Send + 'static {
+        self.spawner.unbounded_send(Box::new(task)).map_err(|_| StratumError::ChannelClosed)
+    }
+}
+
+/// Stratum client that runs the pool connection as one task on a shared tokio runtime,
+/// instead of the send, receive and keep alive threads of `StratumClient`. Commands and
+/// actions use the same channels, commands are handed to the task by a bridge thread. The
+/// protocol itself is `async_transport::run_session`, which does not depend on tokio.
+pub struct AsyncStratumClient {
+    command_sender: Sender<StratumCmd>,
+    bridge_thread: thread::JoinHandle<()>,
+    /// receives once the connection task ended
+    task_ended: Receiver<()>,
+    tcp_stream_hnd: TcpStream,
+    /// requests waiting for a response, shared with the connection task
+    pending: Arc<Mutex<PendingRequests>>,
+    /// set on stop(), errors of the connection task caused by the shutdown are not reported
+    stopping: Arc<AtomicBool>,
+    /// bounds the shares waiting for the connection task
+    share_queue: Arc<ShareQueue>,
+}
+
+impl AsyncStratumClient {
+    /// True if the pool connection can be handled by the async client, TLS, self-select mode,
+    /// traffic tracing and session capture are only implemented in `StratumClient`.
+    pub fn supports(pool_conf: &PoolConfig) -> bool {
+        !pool_conf.tls && pool_conf.self_select_daemon_address.is_none() && pool_conf.trace_file.is_none()
+            && pool_conf.capture_session.is_none()
+    }
+
+    /// Logs in with a connection task on `AsyncRuntime::shared()`
+    pub fn login(pool_conf: PoolConfig, err_receiver: Sender<StratumError>, action_rcv: Sender<StratumEvent>) -> Result<AsyncStratumClient, MithrilError> {
+        let runtime = AsyncRuntime::shared()?;
+        AsyncStratumClient::login_on(&runtime, pool_conf, err_receiver, action_rcv)
+    }
+
+    /// Logs in with a connection task on `runtime`
+    pub fn login_on(runtime: &AsyncRuntime, pool_conf: PoolConfig, err_receiver: Sender<StratumError>, action_rcv: Sender<StratumEvent>) -> Result<AsyncStratumClient, MithrilError> {
+        if !AsyncStratumClient::supports(&pool_conf) {
+            return Err(StratumError::Protocol("TLS, self-select mode, trace_file and session capture are not supported by the async stratum client".to_string()).into());
+        }
+
+        info!("connecting to address: {} (async client)", pool_conf.pool_address);
+
+        let stream = retry_transient(&pool_conf, || connect(&pool_conf)).map_err(StratumError::Io)?;
+        let tcp_stream_hnd = stream.try_clone()?;
+
+        let pending = Arc::new(Mutex::new(PendingRequests::with_flood_guard(pool_conf.flood_guard)));
+        let stopping = Arc::new(AtomicBool::new(false));
+        let share_queue = Arc::new(ShareQueue::new(pool_conf.share_queue_capacity as usize, pool_conf.share_queue_policy));
+        let (command_sender, command_receiver) = channel();
+        let (task_sender, task_receiver) = unbounded();
+
+        let bridge_thread = thread::Builder::new().name("Stratum command bridge thread".to_string()).spawn(move || {
+            forward_commands(&command_receiver, &task_sender);
+        })?;
+
+        let session = Session::new(pool_conf, action_rcv, pending.clone(), share_queue.clone());
+        let task_stopping = stopping.clone();
+        let task_queue = share_queue.clone();
+        let (ended_tx, task_ended) = channel();
+        runtime.spawn(move || {
+            let connection = future::result(AsyncTcpStream::from_std(stream, &Handle::default()).map_err(StratumError::Io))
+                .and_then(move |stream| run_connection(stream, task_receiver, session));
+            Box::new(connection.then(move |result| {
+                task_queue.close();
+                if let Err(err) = result {
+                    if task_stopping.load(Ordering::SeqCst) {
+                        info!("stratum connection task stopped ({})", err);
+                    } else {
+                        report_error(&err_receiver, err);
+                    }
+                }
+                if ended_tx.send(()).is_err() {
+                    info!("stratum client dropped before its connection task ended");
+                }
+                Ok(())
+            }))
+        })?;
+
+        command_sender.send(StratumCmd::Login{}).map_err(|_| StratumError::ChannelClosed)?;
+
+        Ok(AsyncStratumClient{command_sender, bridge_thread, task_ended, tcp_stream_hnd, pending, stopping, share_queue})
+    }
+
+    /// Returns a new channel for sending commands to the stratum client
+    pub fn new_cmd_channel(self: &Self) -> Sender<StratumCmd> {
+        self.command_sender.clone()
+    }
+
+    /// The queue workers submit their shares with
+    pub fn share_queue(self: &Self) -> Arc<ShareQueue> {
+        self.share_queue.clone()
+    }
+
+    /// TCP level statistics of the pool connection (None if not supported on this platform)
+    pub fn socket_stats(self: &Self) -> Option<socket_stats::SocketStats> {
+        socket_stats::socket_stats(&self.tcp_stream_hnd)
+    }
+
+    /// Round trip times of the recent submit and keep alive requests (None if no response was received yet)
+    pub fn latency(self: &Self) -> Option<latency::LatencyStats> {
+        self.pending.lock().expect("pending requests lock").latency()
+    }
+
+    /// Submitted, accepted, rejected and stale shares of this connection (no `StratumAction::Stats`
+    /// is sent by the async client)
+    pub fn stats(self: &Self) -> ShareStats {
+        self.pending.lock().expect("pending requests lock").share_stats()
+    }
+
+    /// Stops the client, the connection task ends after the `Shutdown` command.
+    pub fn stop(self: Self) {
+        info!("stopping async stratum client");
+        if let Some(stats) = self.socket_stats() {
+            info!("connection stats: {:?}", stats);
+        }
+        if let Some(latency) = self.latency() {
+            info!("pool latency over the last {} requests: median {:?}, mean {:?}, min {:?}, max {:?}",
+                latency.samples, latency.median, latency.mean, latency.min, latency.max);
+        }
+        log_share_stats(&self.stats());
+        self.stopping.store(true, Ordering::SeqCst);
+        log_queue_stats(&self.share_queue.stats());
+        self.share_queue.close();
+
+        if self.command_sender.send(StratumCmd::Shutdown{}).is_err() {
+            info!("stratum command bridge thread already ended");
+        }
+        join_thread("command bridge", self.bridge_thread);
+        //the runtime thread keeps running the tasks of the other connections
+        if self.task_ended.recv().is_err() {
+            info!("async runtime ended before the stratum connection task");
+        }
+
+        //the handle keeps the socket open after the task dropped its side
+        if let Err(e) = self.tcp_stream_hnd.shutdown(Shutdown::Both) {
+            info!("TcpStream shutdown failed {:?}", e);
+        }
+        info!("async stratum client stopped");
+    }
+}
+
+fn connect(pool_conf: &PoolConfig) -> io::Result<TcpStream> {
+    let stream = resolve::connect_allowed(&pool_conf.pool_address, Duration::from_secs(pool_conf.connect_timeout_secs), pool_conf.address_preference, &pool_conf.allowed_ips)?;
+    stream.set_write_timeout(Some(Duration::from_secs(10)))?;
+    Ok(stream)
+}
+
+/// Hands the commands to the connection task, until `Shutdown` was forwarded or the task ended.
+fn forward_commands(rx: &Receiver<StratumCmd>, tx: &UnboundedSender<StratumCmd>) {
+    while let Ok(cmd) = rx.recv() {
+        let shutdown = is_shutdown(&cmd);
+        if tx.unbounded_send(cmd).is_err() || shutdown {
+            break;
+        }
+    }
+    info!("stratum command bridge thread ended");
+}
+
+/// The session on a tokio TCP stream, keep alive ticks from the tokio timer
+fn run_connection(stream: AsyncTcpStream, commands: UnboundedReceiver<StratumCmd>, session: Session) -> Box<dyn Future<Item=(), Error=StratumError>> {
+    let lines = Framed::new(stream, LinesCodec::new_with_max_length(MAX_LINE_LENGTH));
+    let ticks = match session.pool_conf().keepalive_interval_secs {
+        Some(secs) => Some(keep_alive_ticks(Duration::from_secs(secs), Duration::from_secs(session.pool_conf().keepalive_jitter_seconds))),
+        None => {
+            info!("keep alive disabled");
+            None
+        }
+    };
+    run_session(lines, commands, ticks, session)
+}
+
+/// A tick every `interval` plus a random delay of up to `jitter`
+fn keep_alive_ticks(interval: Duration, jitter: Duration) -> Ticks {
+    let rng = determinism::rng(determinism::STREAM_KEEPALIVE_JITTER);
+    Box::new(stream::unfold(rng, move |mut rng| {
+        let delay = interval + random_jitter(&mut rng, jitter);
+        Some(Delay::new(Instant::now() + delay)
+            .map(move |()| ((), rng))
+            .map_err(|e| Error::new(ErrorKind::Other, e)))
+    }))
+}
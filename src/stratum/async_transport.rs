@@ -0,0 +1,144 @@
+extern crate futures;
+
+use self::futures::{future, stream, Future, Sink, Stream};
+use self::futures::stream::{SplitSink};
+use self::futures::sync::mpsc::{UnboundedReceiver};
+
+use std::io;
+use std::io::{Error, ErrorKind};
+use std::sync::mpsc::{Sender};
+use std::sync::{Arc, Mutex};
+use super::{StratumCmd, StratumError, StratumEvent, SendState, parse_line_dispatch, respond_command, Dispatched};
+use super::stratum_data::{PoolConfig};
+use super::pending::{PendingRequests};
+use super::share_queue::{ShareQueue};
+use super::rpc;
+use super::rpc::{RpcHandlers};
+
+/// Line based pool connection of the async client. Every futures `Stream` of received lines
+/// that is also a `Sink` for the lines to send is a transport, e.g. a tokio
+/// `Framed<TcpStream, LinesCodec>` or the socket of another runtime behind a line codec.
+/// Lines are passed without the line break.
+pub trait AsyncTransport: Stream<Item=String, Error=io::Error> + Sink<SinkItem=String, SinkError=io::Error> {}
+
+impl<T> AsyncTransport for T where T: Stream<Item=String, Error=io::Error> + Sink<SinkItem=String, SinkError=io::Error> {}
+
+/// Keep alive ticks, from the timer of the runtime that polls the session
+pub type Ticks = Box<dyn Stream<Item=(), Error=io::Error>>;
+
+/// Settings and shared state of a pool session
+pub struct Session {
+    pool_conf: PoolConfig,
+    action_rcv: Sender<StratumEvent>,
+    miner_id: Arc<Mutex<Option<String>>>,
+    pending: Arc<Mutex<PendingRequests>>,
+    share_queue: Arc<ShareQueue>,
+    rpc: Mutex<RpcHandlers>,
+}
+
+impl Session {
+    pub fn new(pool_conf: PoolConfig, action_rcv: Sender<StratumEvent>, pending: Arc<Mutex<PendingRequests>>, share_queue: Arc<ShareQueue>) -> Session {
+        Session{pool_conf, action_rcv, miner_id: Arc::new(Mutex::new(None)), pending, share_queue, rpc: Mutex::new(RpcHandlers::new())}
+    }
+
+    /// Requests of the pool are answered by `rpc` instead of the default handlers
+    pub fn with_rpc(self, rpc: RpcHandlers) -> Session {
+        Session{rpc: Mutex::new(rpc), ..self}
+    }
+
+    pub fn pool_conf(&self) -> &PoolConfig {
+        &self.pool_conf
+    }
+}
+
+enum Event {
+    Line(String),
+    Cmd(StratumCmd),
+    KeepAliveTick
+}
+
+type EventStream = Box<dyn Stream<Item=Event, Error=StratumError>>;
+type ConnectionFuture<T> = Box<dyn Future<Item=Connection<T>, Error=StratumError>>;
+
+/// Write side of the connection, passed from event to event
+struct Connection<T> {
+    sink: SplitSink<T>,
+    send_state: SendState,
+}
+
+/// Handles received lines, commands and keep alive ticks in arrival order until `Shutdown`
+/// is received or the connection fails. The future spawns nothing and uses no timer, so
+/// it can be polled by any runtime (or with `wait()` on a plain thread).
+pub fn run_session<T: AsyncTransport + 'static>(transport: T, commands: UnboundedReceiver<StratumCmd>, keepalive_ticks: Option<Ticks>, session: Session) -> Box<dyn Future<Item=(), Error=StratumError>> {
+    let (sink, lines) = transport.split();
+
+    let eof = stream::once(Err(StratumError::Io(Error::new(ErrorKind::UnexpectedEof, "connection terminated"))));
+    let line_events : EventStream = Box::new(lines.map(Event::Line).map_err(StratumError::Io).chain(eof));
+    let cmd_events : EventStream = Box::new(commands.map(Event::Cmd).map_err(|()| StratumError::ChannelClosed));
+    let mut events : EventStream = Box::new(line_events.select(cmd_events));
+    if let Some(ticks) = keepalive_ticks {
+        events = Box::new(events.select(ticks.map(|()| Event::KeepAliveTick).map_err(StratumError::Io)));
+    }
+
+    let send_state = SendState::with_queue(&session.pool_conf, session.share_queue.clone(), session.action_rcv.clone());
+    Box::new(events
+        .take_while(|event| Ok(match *event {
+            Event::Cmd(ref cmd) => !is_shutdown(cmd),
+            _ => true
+        }))
+        .fold(Connection{sink, send_state}, move |conn, event| handle_event(conn, event, &session))
+        .map(|_| info!("stratum connection task ended")))
+}
+
+pub fn is_shutdown(cmd: &StratumCmd) -> bool {
+    match *cmd {
+        StratumCmd::Shutdown{} => true,
+        _ => false
+    }
+}
+
+fn handle_event<T: AsyncTransport + 'static>(conn: Connection<T>, event: Event, session: &Session) -> ConnectionFuture<T> {
+    match event {
+        Event::Line(line) => handle_line(conn, &line, session),
+        Event::Cmd(cmd) => write_command(conn, cmd, session),
+        Event::KeepAliveTick => {
+            let miner_id = session.miner_id.lock().expect("miner_id lock").clone();
+            match miner_id {
+                Some(miner_id) => write_command(conn, StratumCmd::KeepAlive{miner_id}, session),
+                None => Box::new(future::ok(conn))
+            }
+        }
+    }
+}
+
+/// Answers requests of the pool, all other lines are dispatched as actions
+fn handle_line<T: AsyncTransport + 'static>(conn: Connection<T>, line: &str, session: &Session) -> ConnectionFuture<T> {
+    if let Some(request) = rpc::parse_request(line) {
+        let cmd = respond_command(&request, &session.rpc);
+        return write_command(conn, cmd, session);
+    }
+    match parse_line_dispatch(line, &session.action_rcv, &session.miner_id, &session.pending) {
+        Ok(Dispatched::Action) => Box::new(future::ok(conn)),
+        Ok(Dispatched::Relogin) => {
+            info!("pool session expired, logging in again");
+            write_command(conn, StratumCmd::Login{}, session)
+        },
+        Err(err) => Box::new(future::err(err))
+    }
+}
+
+/// Encodes the command with the same code as the threaded client and sends the resulting line
+fn write_command<T: AsyncTransport + 'static>(mut conn: Connection<T>, cmd: StratumCmd, session: &Session) -> ConnectionFuture<T> {
+    let mut buf = Vec::new();
+    if let Err(err) = conn.send_state.write_command(&mut buf, cmd, &session.pool_conf, &session.pending) {
+        return Box::new(future::err(err));
+    }
+    if buf.is_empty() { //share withheld or dry run
+        return Box::new(future::ok(conn));
+    }
+    let line = String::from_utf8_lossy(&buf).trim_end().to_string();
+    let Connection{sink, send_state} = conn;
+    Box::new(sink.send(line)
+        .map(move |sink| Connection{sink, send_state})
+        .map_err(StratumError::Io))
+}
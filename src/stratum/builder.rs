@@ -0,0 +1,163 @@
+extern crate serde_json;
+
+use std::sync::mpsc::{Sender};
+use error::{MithrilError};
+use super::{StratumClient, StratumAction, StratumError, StratumEvent};
+use super::stratum_data::{PoolConfig};
+use super::subscribers::{Subscribers};
+use super::rpc::{RpcHandlers};
+use super::pinning;
+
+/// Invalid settings found by `StratumClientBuilder` before connecting,
+/// or the error of the login itself.
+#[derive(Debug, Error)]
+pub enum BuildError {
+    #[error("no pool address set")]
+    MissingPoolAddress,
+    #[error("no wallet address set")]
+    MissingWalletAddress,
+    #[error("no error channel set")]
+    MissingErrorChannel,
+    #[error("no action channel set")]
+    MissingActionChannel,
+    #[error("{0} has to be > 0")]
+    Zero(&'static str),
+    #[error("tls fingerprints require tls")]
+    FingerprintsWithoutTls,
+    #[error("login failed: {0}")]
+    Login(#[from] MithrilError),
+}
+
+/// Sets up a `StratumClient` step by step. All settings are checked by
+/// `login` before anything is connected or started.
+pub struct StratumClientBuilder {
+    pool_conf: PoolConfig,
+    err_tx: Option<Sender<StratumError>>,
+    action_tx: Option<Sender<StratumEvent>>,
+    subscribers: Subscribers,
+    rpc: RpcHandlers,
+}
+
+impl StratumClientBuilder {
+    /// A builder with the default pool settings, the pool and wallet address have to be set
+    pub fn new() -> StratumClientBuilder {
+        StratumClientBuilder{pool_conf: PoolConfig::new("", "", "x"), err_tx: None, action_tx: None, subscribers: Subscribers::new(), rpc: RpcHandlers::new()}
+    }
+
+    /// Replaces all pool settings, e.g. with the `[pool]` section of the config file
+    pub fn pool_config(mut self, pool_conf: PoolConfig) -> StratumClientBuilder {
+        self.pool_conf = pool_conf;
+        self
+    }
+
+    pub fn pool_address(mut self, address: &str) -> StratumClientBuilder {
+        self.pool_conf.pool_address = address.to_string();
+        self
+    }
+
+    pub fn wallet(mut self, wallet_address: &str, pool_password: &str) -> StratumClientBuilder {
+        self.pool_conf.wallet_address = wallet_address.to_string();
+        self.pool_conf.pool_password = pool_password.to_string();
+        self
+    }
+
+    pub fn rig_id(mut self, rig_id: &str) -> StratumClientBuilder {
+        self.pool_conf.rig_id = Some(rig_id.to_string());
+        self
+    }
+
+    pub fn connect_timeout_secs(mut self, secs: u64) -> StratumClientBuilder {
+        self.pool_conf.connect_timeout_secs = secs;
+        self
+    }
+
+    pub fn job_timeout_secs(mut self, secs: Option<u64>) -> StratumClientBuilder {
+        self.pool_conf.job_timeout_secs = secs;
+        self
+    }
+
+    /// Seconds between keep alive requests (None disables keep alive) and the maximum random delay added
+    pub fn keepalive(mut self, interval_secs: Option<u64>, jitter_seconds: u64) -> StratumClientBuilder {
+        self.pool_conf.keepalive_interval_secs = interval_secs;
+        self.pool_conf.keepalive_jitter_seconds = jitter_seconds;
+        self
+    }
+
+    /// Connects via TLS, only certificates with one of the `fingerprints` are accepted (empty: all valid certificates)
+    pub fn tls(mut self, fingerprints: &[&str]) -> StratumClientBuilder {
+        self.pool_conf.tls = true;
+        self.pool_conf.tls_fingerprints = fingerprints.iter().map(|f| pinning::normalize_fingerprint(f)).collect();
+        self
+    }
+
+    /// Channel the errors ending the connection are reported to
+    pub fn errors(mut self, err_tx: Sender<StratumError>) -> StratumClientBuilder {
+        self.err_tx = Some(err_tx);
+        self
+    }
+
+    /// Channel all received actions are sent to
+    pub fn actions(mut self, action_tx: Sender<StratumEvent>) -> StratumClientBuilder {
+        self.action_tx = Some(action_tx);
+        self
+    }
+
+    /// Calls `f` for all actions, starting with the response to the login
+    pub fn subscribe_fn<F: Fn(StratumAction) + Send + 'static>(mut self, f: F) -> StratumClientBuilder {
+        self.subscribers.subscribe_fn(f);
+        self
+    }
+
+    /// Answers requests of the pool for `method` with `f`, e.g. to replace the default `client.get_version`
+    pub fn rpc_handler<F: Fn(&serde_json::Value) -> Result<serde_json::Value, String> + Send + 'static>(mut self, method: &str, f: F) -> StratumClientBuilder {
+        self.rpc.register(method, f);
+        self
+    }
+
+    /// Checks the settings without connecting
+    pub fn validate(&self) -> Result<(), BuildError> {
+        let conf = &self.pool_conf;
+        if conf.pool_address.is_empty() {
+            return Err(BuildError::MissingPoolAddress);
+        }
+        if conf.wallet_address.is_empty() {
+            return Err(BuildError::MissingWalletAddress);
+        }
+        if conf.connect_timeout_secs == 0 {
+            return Err(BuildError::Zero("connect_timeout_secs"));
+        }
+        if conf.keepalive_interval_secs == Some(0) {
+            return Err(BuildError::Zero("keepalive_interval_secs"));
+        }
+        if conf.job_timeout_secs == Some(0) {
+            return Err(BuildError::Zero("job_timeout_secs"));
+        }
+        if conf.share_queue_capacity == 0 {
+            return Err(BuildError::Zero("share_queue_capacity"));
+        }
+        if !conf.tls_fingerprints.is_empty() && !conf.tls {
+            return Err(BuildError::FingerprintsWithoutTls);
+        }
+        if self.err_tx.is_none() {
+            return Err(BuildError::MissingErrorChannel);
+        }
+        if self.action_tx.is_none() {
+            return Err(BuildError::MissingActionChannel);
+        }
+        Ok(())
+    }
+
+    /// Validates the settings, connects to the pool and logs in
+    pub fn login(self) -> Result<StratumClient, BuildError> {
+        self.validate()?;
+        let err_tx = self.err_tx.expect("validated error channel");
+        let action_tx = self.action_tx.expect("validated action channel");
+        Ok(StratumClient::login_with_rpc(self.pool_conf, err_tx, action_tx, self.subscribers, self.rpc)?)
+    }
+}
+
+impl Default for StratumClientBuilder {
+    fn default() -> StratumClientBuilder {
+        StratumClientBuilder::new()
+    }
+}
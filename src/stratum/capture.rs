@@ -0,0 +1,91 @@
+extern crate serde_json;
+
+use self::serde_json::{Value};
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Write};
+use std::time::{Instant};
+use super::trace;
+
+/// Replaces the pool password in captured login requests
+const PASSWORD_PLACEHOLDER : &str = "<password>";
+
+/// What a captured entry records
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// a new connection, the line is the pool address
+    Connected,
+    Sent,
+    Received
+}
+
+/// One line of a session capture file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CaptureEntry {
+    /// milliseconds since the connection was established
+    pub t_ms: u64,
+    pub dir: Direction,
+    pub line: String,
+}
+
+/// Records the stratum messages of a session as JSON lines (`--capture-session`), for
+/// reproducing protocol issues from a bug report. The wallet address and the pool
+/// password are redacted, every connection starts with a `connected` entry.
+pub struct SessionCapture {
+    file: File,
+    start: Instant,
+    wallet_address: String,
+}
+
+impl SessionCapture {
+    pub fn open(path: &str, pool_address: &str, wallet_address: &str) -> io::Result<SessionCapture> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let mut capture = SessionCapture{file, start: Instant::now(), wallet_address: wallet_address.to_string()};
+        capture.write(Direction::Connected, pool_address.to_string())?;
+        Ok(capture)
+    }
+
+    pub fn record(&mut self, dir: Direction, line: &str) -> io::Result<()> {
+        let line = redact_credentials(&trace::redact(line, &self.wallet_address));
+        self.write(dir, line)
+    }
+
+    fn write(&mut self, dir: Direction, line: String) -> io::Result<()> {
+        let elapsed = self.start.elapsed();
+        let t_ms = elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_millis());
+        let json = serde_json::to_string(&CaptureEntry{t_ms, dir, line}).map_err(|e| Error::new(ErrorKind::Other, e))?;
+        writeln!(self.file, "{}", json)
+    }
+}
+
+/// Replaces the password of a login request, other lines are returned unchanged
+pub fn redact_credentials(line: &str) -> String {
+    let mut json : Value = match serde_json::from_str(line) {
+        Ok(json) => json,
+        Err(_) => return line.to_string()
+    };
+    if json.get("method").and_then(Value::as_str) != Some("login") {
+        return line.to_string();
+    }
+    match json.get_mut("params").and_then(|p| p.get_mut("pass")) {
+        Some(pass) => *pass = Value::from(PASSWORD_PLACEHOLDER),
+        None => return line.to_string()
+    }
+    json.to_string()
+}
+
+/// Reads a session capture file, e.g. for replaying the received lines
+pub fn read_capture(path: &str) -> io::Result<Vec<CaptureEntry>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut entries = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        entries.push(serde_json::from_str(&line).map_err(|e| Error::new(ErrorKind::InvalidData, e))?);
+    }
+    Ok(entries)
+}
@@ -0,0 +1,66 @@
+use super::{StratumAction};
+
+/// Where the connection of a `StratumClient` is
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConnectionState {
+    /// connected, the login was sent but no job received yet
+    Connecting,
+    /// the pool sent a job
+    LoggedIn,
+    /// the login was rejected, the connection failed or the client was stopped
+    Disconnected,
+}
+
+/// The connection state and the last job of a client, updated before the action is dispatched
+#[derive(Debug, Clone)]
+pub struct ClientState {
+    state: ConnectionState,
+    /// the last `StratumAction::Job`, None before the first job or after it was cancelled
+    current_job: Option<StratumAction>,
+}
+
+impl ClientState {
+    pub fn new() -> ClientState {
+        ClientState{state: ConnectionState::Connecting, current_job: None}
+    }
+
+    pub fn observe(&mut self, action: &StratumAction) {
+        match *action {
+            StratumAction::Job{..} => {
+                self.current_job = Some(action.clone());
+                if self.state == ConnectionState::Connecting {
+                    self.state = ConnectionState::LoggedIn;
+                }
+            },
+            StratumAction::JobCancelled{ref job_id} => {
+                let cancelled = match (job_id, &self.current_job) {
+                    (&Some(ref job_id), &Some(StratumAction::Job{job_id: ref current, ..})) => job_id == current,
+                    _ => true
+                };
+                if cancelled {
+                    self.current_job = None;
+                }
+            },
+            StratumAction::LoginRejected{..} => self.state = ConnectionState::Disconnected,
+            _ => ()
+        }
+    }
+
+    pub fn disconnected(&mut self) {
+        self.state = ConnectionState::Disconnected;
+    }
+
+    pub fn state(&self) -> ConnectionState {
+        self.state
+    }
+
+    pub fn current_job(&self) -> Option<StratumAction> {
+        self.current_job.clone()
+    }
+}
+
+impl Default for ClientState {
+    fn default() -> ClientState {
+        ClientState::new()
+    }
+}
@@ -0,0 +1,46 @@
+use std::collections::{HashSet, VecDeque};
+
+/// Number of submitted (job id, nonce) pairs remembered
+const TRACKED_SHARES : usize = 256;
+
+/// Remembers recently submitted shares so that the same (job id, nonce) pair
+/// is never sent twice, pools may ban miners submitting duplicates.
+pub struct RecentShares {
+    seen: HashSet<(String, String)>,
+    order: VecDeque<(String, String)>,
+    dropped: u64,
+}
+
+impl RecentShares {
+    pub fn new() -> RecentShares {
+        RecentShares{seen: HashSet::new(), order: VecDeque::new(), dropped: 0}
+    }
+
+    /// Returns true if the share was submitted before, otherwise records it.
+    pub fn is_duplicate(&mut self, job_id: &str, nonce: &str) -> bool {
+        let key = (job_id.to_string(), nonce.to_string());
+        if self.seen.contains(&key) {
+            self.dropped += 1;
+            return true;
+        }
+        if self.order.len() >= TRACKED_SHARES {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        self.order.push_back(key.clone());
+        self.seen.insert(key);
+        false
+    }
+
+    /// Total number of duplicate shares detected
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+impl Default for RecentShares {
+    fn default() -> RecentShares {
+        RecentShares::new()
+    }
+}
@@ -0,0 +1,170 @@
+use std::fmt;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Number of jobs kept in the event log
+pub const MAX_JOBS : usize = 20;
+/// Number of share results kept in the event log
+pub const MAX_SHARES : usize = 100;
+/// Number of connection events kept in the event log
+pub const MAX_CONNECTION_EVENTS : usize = 50;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobRecord {
+    pub job_id: String,
+    pub target: String,
+    pub received: Instant,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShareRecord {
+    pub job_id: String,
+    /// None if the share was accepted, the reason of the pool otherwise
+    pub rejected: Option<String>,
+    pub received: Instant,
+}
+
+/// Changes of the pool connection, for diagnosing e.g. disconnects over night
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConnectionEvent {
+    /// connected to the pool and sent the login
+    Connected{address: String},
+    ConnectFailed{address: String, reason: String},
+    /// the first job after the login was received
+    LoginOk,
+    LoginRejected{reason: String},
+    /// the connection ended because of an error or a planned restart
+    Disconnected{reason: String},
+    /// another pool is used from now on
+    Failover{from: String, to: String},
+}
+
+impl fmt::Display for ConnectionEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConnectionEvent::Connected{ref address} => write!(f, "connected to {}", address),
+            ConnectionEvent::ConnectFailed{ref address, ref reason} => write!(f, "connecting to {} failed: {}", address, reason),
+            ConnectionEvent::LoginOk => write!(f, "login ok, first job received"),
+            ConnectionEvent::LoginRejected{ref reason} => write!(f, "login rejected: {}", reason),
+            ConnectionEvent::Disconnected{ref reason} => write!(f, "disconnected: {}", reason),
+            ConnectionEvent::Failover{ref from, ref to} => write!(f, "failover from {} to {}", from, to),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConnectionRecord {
+    pub event: ConnectionEvent,
+    pub at: Instant,
+}
+
+/// Fixed size buffer, the oldest entry is dropped when a new one is pushed into a full ring
+#[derive(Debug)]
+pub struct Ring<T> {
+    items: VecDeque<T>,
+    capacity: usize,
+}
+
+impl<T> Ring<T> {
+    pub fn new(capacity: usize) -> Ring<T> {
+        Ring{items: VecDeque::with_capacity(capacity), capacity}
+    }
+
+    pub fn push(&mut self, item: T) {
+        if self.items.len() == self.capacity {
+            self.items.pop_front();
+        }
+        if self.capacity > 0 {
+            self.items.push_back(item);
+        }
+    }
+
+    /// The last `n` entries, newest first
+    pub fn recent(&self, n: usize) -> Vec<&T> {
+        self.items.iter().rev().take(n).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+/// The most recent jobs and share results of all pool connections, for
+/// diagnosing e.g. a sudden streak of rejected shares.
+#[derive(Debug)]
+pub struct EventLog {
+    pub jobs: Ring<JobRecord>,
+    pub shares: Ring<ShareRecord>,
+    pub connections: Ring<ConnectionRecord>,
+}
+
+impl EventLog {
+    pub fn new() -> EventLog {
+        EventLog{jobs: Ring::new(MAX_JOBS), shares: Ring::new(MAX_SHARES), connections: Ring::new(MAX_CONNECTION_EVENTS)}
+    }
+
+    pub fn record_job(&mut self, job_id: &str, target: &str) {
+        self.jobs.push(JobRecord{job_id: job_id.to_string(), target: target.to_string(), received: Instant::now()});
+    }
+
+    pub fn record_share(&mut self, job_id: &str, rejected: Option<String>) {
+        self.shares.push(ShareRecord{job_id: job_id.to_string(), rejected, received: Instant::now()});
+    }
+
+    pub fn record_connection(&mut self, event: ConnectionEvent) {
+        self.connections.push(ConnectionRecord{event, at: Instant::now()});
+    }
+
+    /// Number of rejected shares in a row, counted from the newest share
+    pub fn reject_streak(&self) -> usize {
+        self.shares.items.iter().rev().take_while(|s| s.rejected.is_some()).count()
+    }
+
+    /// Human readable summary of the last `n` jobs and shares, newest first
+    pub fn render(&self, n: usize) -> String {
+        let mut lines = Vec::new();
+        lines.push(format!("last {} jobs:", self.jobs.recent(n).len()));
+        for job in self.jobs.recent(n) {
+            lines.push(format!("  {} target {} ({}s ago)", job.job_id, job.target, job.received.elapsed().as_secs()));
+        }
+        lines.push(format!("last {} shares:", self.shares.recent(n).len()));
+        for share in self.shares.recent(n) {
+            let result = match share.rejected {
+                Some(ref reason) => format!("rejected: {}", reason),
+                None => "accepted".to_string()
+            };
+            lines.push(format!("  job {} {} ({}s ago)", share.job_id, result, share.received.elapsed().as_secs()));
+        }
+        lines.join("\n")
+    }
+
+    /// Human readable history of the last `n` connection events, newest first
+    pub fn render_connections(&self, n: usize) -> String {
+        let mut lines = Vec::new();
+        lines.push(format!("last {} connection events:", self.connections.recent(n).len()));
+        for record in self.connections.recent(n) {
+            lines.push(format!("  {} ago: {}", format_age(record.at.elapsed()), record.event));
+        }
+        lines.join("\n")
+    }
+}
+
+/// Formats a duration as hours, minutes and seconds, e.g. "7h 03m 12s"
+pub fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    if secs < 3600 {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h {:02}m {:02}s", secs / 3600, (secs % 3600) / 60, secs % 60)
+    }
+}
+
+impl Default for EventLog {
+    fn default() -> EventLog {
+        EventLog::new()
+    }
+}
@@ -0,0 +1,93 @@
+/// Tracks which pool of a prioritized list is used. The pool at index 0 has the
+/// highest priority. After `max_failures` consecutive connection failures the
+/// next pool in the list is used (wrapping around to the primary after the last).
+/// While not connected to the primary, it can be probed periodically; a single
+/// failure of a probe switches back to the pool that was used before.
+#[derive(Debug)]
+pub struct Failover {
+    addresses: Vec<String>,
+    active: usize,
+    failures: u64,
+    max_failures: u64,
+    probing_from: Option<usize>,
+}
+
+impl Failover {
+    pub fn new(primary: &str, failover_addresses: &[String], max_failures: u64) -> Failover {
+        let mut addresses = vec![primary.to_string()];
+        addresses.extend(failover_addresses.iter().cloned());
+        Failover{addresses, active: 0, failures: 0, max_failures, probing_from: None}
+    }
+
+    pub fn active_address(&self) -> &str {
+        &self.addresses[self.active]
+    }
+
+    pub fn active_index(&self) -> usize {
+        self.active
+    }
+
+    /// true if a pool with lower priority than the primary is used
+    pub fn is_failed_over(&self) -> bool {
+        self.active != 0
+    }
+
+    /// The active pool delivered a job, the connection is considered working.
+    pub fn record_success(&mut self) {
+        if let Some(previous) = self.probing_from.take() {
+            info!("switched back from pool {} to higher priority pool {}", self.addresses[previous], self.active_address());
+        }
+        self.failures = 0;
+    }
+
+    /// The connection to the active pool failed (login or connection lost before a job was received).
+    pub fn record_failure(&mut self) {
+        if let Some(previous) = self.probing_from.take() {
+            info!("probing pool {} failed, switching back to {}", self.active_address(), self.addresses[previous]);
+            self.active = previous;
+            self.failures = 0;
+            return;
+        }
+
+        self.failures += 1;
+        if self.failures >= self.max_failures && self.addresses.len() > 1 {
+            let next = (self.active + 1) % self.addresses.len();
+            warn!("pool {} failed {} times, failing over to {}", self.active_address(), self.failures, self.addresses[next]);
+            self.active = next;
+            self.failures = 0;
+        }
+    }
+
+    /// The active pool kept rejecting shares or sending errors (see `flood_guard`), the next
+    /// pool is used right away. A probed primary switches back to the pool used before.
+    /// Returns false if there is no other pool.
+    pub fn record_unhealthy(&mut self) -> bool {
+        if let Some(previous) = self.probing_from.take() {
+            info!("probed pool {} is unhealthy, switching back to {}", self.active_address(), self.addresses[previous]);
+            self.active = previous;
+            self.failures = 0;
+            return true;
+        }
+        if self.addresses.len() < 2 {
+            return false;
+        }
+        let next = (self.active + 1) % self.addresses.len();
+        warn!("pool {} is unhealthy, failing over to {}", self.active_address(), self.addresses[next]);
+        self.active = next;
+        self.failures = 0;
+        true
+    }
+
+    /// Switches to the primary pool for a probe, if a lower priority pool is active.
+    /// Returns false if nothing is to be probed.
+    pub fn probe_primary(&mut self) -> bool {
+        if !self.is_failed_over() {
+            return false;
+        }
+        info!("probing primary pool {}", self.addresses[0]);
+        self.probing_from = Some(self.active);
+        self.active = 0;
+        self.failures = 0;
+        true
+    }
+}
@@ -0,0 +1,207 @@
+use std::cmp;
+use std::collections::{VecDeque};
+use std::time::{Duration, Instant};
+
+/// Errors older than this do not count towards `FloodGuardConfig::max_errors`
+pub const ERROR_WINDOW_SECS : u64 = 60;
+
+pub const DEFAULT_FLOOD_MAX_REJECTS : u64 = 10;
+pub const DEFAULT_FLOOD_MAX_ERRORS : u64 = 20;
+pub const DEFAULT_FLOOD_BACKOFF_SECS : u64 = 30;
+pub const DEFAULT_FLOOD_MAX_BACKOFF_SECS : u64 = 600;
+pub const DEFAULT_FLOOD_UNHEALTHY_AFTER : u64 = 3;
+
+/// Reason of the `PoolUnhealthy` action
+pub const UNHEALTHY_REASON : &str = "shares rejected, unanswered or errors received through repeated backoffs";
+
+/// When `FloodGuard` stops submitting shares to a pool
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FloodGuardConfig {
+    /// rejected shares in a row that start a backoff, stale shares are not counted (0: never)
+    pub max_rejects: u64,
+    /// errors from the pool within `ERROR_WINDOW_SECS` that start a backoff (0: never)
+    pub max_errors: u64,
+    /// length of the first backoff, doubled for every further backoff in a row
+    pub backoff_secs: u64,
+    pub max_backoff_secs: u64,
+    /// backoffs in a row after which the pool is reported unhealthy (0: never)
+    pub unhealthy_after: u64,
+}
+
+impl Default for FloodGuardConfig {
+    fn default() -> FloodGuardConfig {
+        FloodGuardConfig{
+            max_rejects: DEFAULT_FLOOD_MAX_REJECTS,
+            max_errors: DEFAULT_FLOOD_MAX_ERRORS,
+            backoff_secs: DEFAULT_FLOOD_BACKOFF_SECS,
+            max_backoff_secs: DEFAULT_FLOOD_MAX_BACKOFF_SECS,
+            unhealthy_after: DEFAULT_FLOOD_UNHEALTHY_AFTER
+        }
+    }
+}
+
+impl FloodGuardConfig {
+    /// A guard that never backs off
+    pub fn disabled() -> FloodGuardConfig {
+        FloodGuardConfig{max_rejects: 0, max_errors: 0, unhealthy_after: 0, ..FloodGuardConfig::default()}
+    }
+}
+
+/// What happens to a share that is about to be sent
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Decision {
+    Submit,
+    /// the backoff is over, this share tests whether the pool accepts shares again
+    Probe,
+    /// the share is dropped while backing off
+    Withhold
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Healthy,
+    BackedOff{until: Instant},
+    /// a probe share was sent, the next ones are withheld until its response arrives
+    Probing{sent: Instant},
+}
+
+/// Stops submitting shares to a pool that rejects every share or floods the connection
+/// with errors. After `max_rejects` rejections in a row (or `max_errors` errors within
+/// `ERROR_WINDOW_SECS`) shares are withheld for the backoff, then a single share probes
+/// the pool: accepted, submissions resume, rejected (or unanswered for the backoff), the
+/// next backoff is twice as long. After `unhealthy_after` backoffs in a row the pool is
+/// reported unhealthy once, so the failover can switch to another pool.
+#[derive(Debug)]
+pub struct FloodGuard {
+    config: FloodGuardConfig,
+    state: State,
+    rejects_in_row: u64,
+    errors: VecDeque<Instant>,
+    backoffs_in_row: u64,
+    backoff: Duration,
+    unhealthy_reported: bool,
+    unhealthy_pending: bool,
+    withheld: u64,
+}
+
+impl FloodGuard {
+    pub fn new(config: FloodGuardConfig) -> FloodGuard {
+        FloodGuard{config, state: State::Healthy, rejects_in_row: 0, errors: VecDeque::new(), backoffs_in_row: 0,
+            backoff: Duration::from_secs(config.backoff_secs), unhealthy_reported: false, unhealthy_pending: false, withheld: 0}
+    }
+
+    /// Decides whether a share is sent at `now`
+    pub fn allow(&mut self, now: Instant) -> Decision {
+        self.tick(now);
+        match self.state {
+            State::Healthy => Decision::Submit,
+            State::BackedOff{until} if now >= until => {
+                info!("backoff of {}s over, probing the pool with one share", self.backoff.as_secs());
+                self.state = State::Probing{sent: now};
+                Decision::Probe
+            },
+            State::BackedOff{..} | State::Probing{..} => self.withhold()
+        }
+    }
+
+    /// A probe without response for the length of the backoff counts as rejected. Also called
+    /// without shares (e.g. on keep alives), so a pool that went silent becomes unhealthy too.
+    pub fn tick(&mut self, now: Instant) {
+        if let State::Probing{sent} = self.state {
+            if now >= sent + self.backoff {
+                self.back_off(now, "probe share unanswered");
+            }
+        }
+    }
+
+    fn withhold(&mut self) -> Decision {
+        self.withheld += 1;
+        Decision::Withhold
+    }
+
+    pub fn record_accepted(&mut self) {
+        if self.state != State::Healthy || self.backoffs_in_row > 0 {
+            info!("pool accepts shares again, submissions resumed ({} shares withheld so far)", self.withheld);
+        }
+        self.state = State::Healthy;
+        self.rejects_in_row = 0;
+        self.errors.clear();
+        self.backoffs_in_row = 0;
+        self.backoff = Duration::from_secs(self.config.backoff_secs);
+        self.unhealthy_reported = false;
+    }
+
+    /// A share was rejected, `stale` if the job was no longer valid (not held against the pool)
+    pub fn record_rejected(&mut self, stale: bool, now: Instant) {
+        if let State::Probing{..} = self.state {
+            if stale {
+                //says nothing about the pool, the next share probes again
+                self.state = State::BackedOff{until: now};
+            } else {
+                self.back_off(now, "probe share rejected");
+            }
+            return;
+        }
+        if stale {
+            return;
+        }
+        self.rejects_in_row += 1;
+        if self.config.max_rejects > 0 && self.rejects_in_row >= self.config.max_rejects && self.state == State::Healthy {
+            let reason = format!("{} shares rejected in a row", self.rejects_in_row);
+            self.back_off(now, &reason);
+        }
+    }
+
+    /// The pool sent an error (or a line that could not be understood)
+    pub fn record_error(&mut self, now: Instant) {
+        if self.config.max_errors == 0 {
+            return;
+        }
+        self.errors.push_back(now);
+        let window = Duration::from_secs(ERROR_WINDOW_SECS);
+        while self.errors.front().map_or(false, |&first| now.duration_since(first) > window) {
+            self.errors.pop_front();
+        }
+        if self.errors.len() as u64 >= self.config.max_errors && self.state == State::Healthy {
+            let reason = format!("{} errors within {}s", self.errors.len(), ERROR_WINDOW_SECS);
+            self.back_off(now, &reason);
+        }
+    }
+
+    fn back_off(&mut self, now: Instant, reason: &str) {
+        if self.backoffs_in_row > 0 {
+            self.backoff = cmp::min(self.backoff * 2, Duration::from_secs(self.config.max_backoff_secs));
+        }
+        self.backoffs_in_row += 1;
+        self.rejects_in_row = 0;
+        self.errors.clear();
+        self.state = State::BackedOff{until: now + self.backoff};
+        warn!("{}, no shares are submitted for {}s (backoff {} in a row)", reason, self.backoff.as_secs(), self.backoffs_in_row);
+        if self.is_unhealthy() && !self.unhealthy_reported {
+            self.unhealthy_reported = true;
+            self.unhealthy_pending = true;
+        }
+    }
+
+    /// true if shares are currently withheld or probed
+    pub fn is_backing_off(&self) -> bool {
+        self.state != State::Healthy
+    }
+
+    /// true after `unhealthy_after` backoffs in a row, until a share is accepted again
+    pub fn is_unhealthy(&self) -> bool {
+        self.config.unhealthy_after > 0 && self.backoffs_in_row >= self.config.unhealthy_after
+    }
+
+    /// true once when the pool became unhealthy
+    pub fn take_unhealthy(&mut self) -> bool {
+        let unhealthy = self.unhealthy_pending;
+        self.unhealthy_pending = false;
+        unhealthy
+    }
+
+    /// Shares not sent because of a backoff
+    pub fn withheld(&self) -> u64 {
+        self.withheld
+    }
+}
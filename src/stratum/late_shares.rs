@@ -0,0 +1,68 @@
+use std::time::{Duration, Instant};
+
+/// Decides about shares found for a job the pool already replaced. Workers
+/// switch to a new job as soon as it arrives, but a share found just before
+/// is still sent afterwards. Many pools accept shares of the previous job for
+/// a short time, shares of older jobs are rejected as stale.
+pub struct LateShares {
+    /// None: late shares are always submitted
+    grace: Option<Duration>,
+    current_job: Option<String>,
+    /// the previous job and when it was replaced
+    previous_job: Option<(String, Instant)>,
+    submitted: u64,
+    dropped: u64,
+}
+
+impl LateShares {
+    pub fn new(grace: Option<Duration>) -> LateShares {
+        LateShares{grace, current_job: None, previous_job: None, submitted: 0, dropped: 0}
+    }
+
+    /// Records that the pool sent `job_id`, a repeated job id (retarget) changes nothing
+    pub fn job_received(&mut self, job_id: &str) {
+        if self.current_job.as_ref().map_or(false, |id| id == job_id) {
+            return;
+        }
+        self.previous_job = self.current_job.take().map(|id| (id, Instant::now()));
+        self.current_job = Some(job_id.to_string());
+    }
+
+    /// Returns true if `job_id` is not the current job (no job received yet: no share is late)
+    pub fn is_late(&self, job_id: &str) -> bool {
+        self.current_job.as_ref().map_or(false, |id| id != job_id)
+    }
+
+    /// Returns true if a share for `job_id` may be submitted. Shares of the current job
+    /// always are, shares of the previous job only within the grace window and shares
+    /// of older jobs only if no grace window is set. Refused shares are counted.
+    pub fn allow(&mut self, job_id: &str) -> bool {
+        if !self.is_late(job_id) {
+            return true;
+        }
+        let allowed = match (self.grace, self.previous_job.as_ref()) {
+            (None, _) => true,
+            (Some(grace), Some(&(ref id, replaced))) if id == job_id => replaced.elapsed() <= grace,
+            (Some(_), _) => false
+        };
+        if !allowed {
+            self.dropped += 1;
+        }
+        allowed
+    }
+
+    /// Counts a late share that was submitted
+    pub fn record_submitted(&mut self) {
+        self.submitted += 1;
+    }
+
+    /// Total number of submitted shares for replaced jobs
+    pub fn submitted(&self) -> u64 {
+        self.submitted
+    }
+
+    /// Total number of shares dropped because their job was replaced too long ago
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
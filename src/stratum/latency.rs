@@ -0,0 +1,136 @@
+use std::time::{Duration, Instant};
+use super::event_log::{Ring};
+
+/// Number of round trips the latency statistic is computed over
+pub const LATENCY_WINDOW : usize = 50;
+
+/// Round trip times (request sent to response received) of the most recent
+/// submit and keep alive requests.
+#[derive(Debug)]
+pub struct LatencyWindow {
+    samples: Ring<Duration>,
+}
+
+/// Statistic of the round trip times in the window
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LatencyStats {
+    pub samples: usize,
+    pub min: Duration,
+    pub max: Duration,
+    pub mean: Duration,
+    pub median: Duration,
+}
+
+impl LatencyWindow {
+    pub fn new() -> LatencyWindow {
+        LatencyWindow{samples: Ring::new(LATENCY_WINDOW)}
+    }
+
+    pub fn record(&mut self, round_trip: Duration) {
+        self.samples.push(round_trip);
+    }
+
+    /// `None` if no round trip was measured yet
+    pub fn stats(&self) -> Option<LatencyStats> {
+        let mut sorted : Vec<Duration> = self.samples.recent(LATENCY_WINDOW).into_iter().cloned().collect();
+        if sorted.is_empty() {
+            return None;
+        }
+        sorted.sort();
+        let total = sorted.iter().fold(Duration::from_secs(0), |acc, d| acc + *d);
+        Some(LatencyStats{
+            samples: sorted.len(),
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            mean: total / sorted.len() as u32,
+            median: sorted[sorted.len() / 2],
+        })
+    }
+}
+
+impl Default for LatencyWindow {
+    fn default() -> LatencyWindow {
+        LatencyWindow::new()
+    }
+}
+
+/// When a share passed the stages inside mithril, carried with the share to the pool client
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShareTimes {
+    /// the job of the share was received from the pool
+    pub job_received: Instant,
+    /// the job was handed to the worker threads
+    pub dispatched: Instant,
+    /// the nonce was found
+    pub found: Instant,
+}
+
+/// Latency of every stage of the recent shares the pool answered, from the job
+/// to the response. Dispatch and submit are spent in mithril, the response time
+/// on the network and in the pool.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct StageLatency {
+    /// job received from the pool until it was handed to the worker threads
+    pub dispatch: Option<LatencyStats>,
+    /// job handed to the worker threads until the nonce was found
+    pub hashing: Option<LatencyStats>,
+    /// nonce found until the submit was written to the pool
+    pub submit: Option<LatencyStats>,
+    /// submit written until the response of the pool was parsed
+    pub response: Option<LatencyStats>,
+}
+
+impl StageLatency {
+    /// One line with median and max of every stage, `None` if no share was answered yet
+    pub fn render(&self) -> Option<String> {
+        let response = self.response?;
+        let stage = |name: &str, stats: Option<LatencyStats>| match stats {
+            Some(stats) => format!("{} {:?} (max {:?})", name, stats.median, stats.max),
+            None => format!("{} -", name)
+        };
+        Some(format!("share latency over the last {} shares (median): {}, {}, {}, {}",
+            response.samples, stage("dispatch", self.dispatch), stage("hashing", self.hashing),
+            stage("submit", self.submit), stage("pool response", Some(response))))
+    }
+}
+
+/// Latency windows of the share stages
+#[derive(Debug, Default)]
+pub struct StageWindows {
+    dispatch: LatencyWindow,
+    hashing: LatencyWindow,
+    submit: LatencyWindow,
+    response: LatencyWindow,
+}
+
+impl StageWindows {
+    pub fn new() -> StageWindows {
+        StageWindows::default()
+    }
+
+    /// Records a share that was written to the pool at `written` and answered at `answered`
+    pub fn record(&mut self, times: &ShareTimes, written: Instant, answered: Instant) {
+        self.dispatch.record(between(times.job_received, times.dispatched));
+        self.hashing.record(between(times.dispatched, times.found));
+        self.submit.record(between(times.found, written));
+        self.response.record(between(written, answered));
+    }
+
+    pub fn stats(&self) -> StageLatency {
+        StageLatency{
+            dispatch: self.dispatch.stats(),
+            hashing: self.hashing.stats(),
+            submit: self.submit.stats(),
+            response: self.response.stats(),
+        }
+    }
+}
+
+/// Time from `from` to `to`, zero if `to` is earlier
+fn between(from: Instant, to: Instant) -> Duration {
+    if to > from {
+        to - from
+    } else {
+        Duration::from_secs(0)
+    }
+}
@@ -1,15 +1,72 @@
 pub mod stratum_data;
+pub mod share_cap;
+pub mod duplicates;
+pub mod tls;
+pub mod failover;
+pub mod flood_guard;
+pub mod multi_pool;
+pub mod schedule;
+pub mod pending;
+pub mod latency;
+pub mod socket_stats;
+pub mod pinning;
+pub mod event_log;
+pub mod self_select;
+pub mod trace;
+pub mod capture;
+pub mod resolve;
+pub mod subscribers;
+pub mod share_queue;
+pub mod offline;
+pub mod client_state;
+pub mod rpc;
+pub mod share_stats;
+pub mod pool_score;
+pub mod late_shares;
+pub mod transport;
+pub mod builder;
+pub mod testserver;
+#[cfg(feature = "async-transport")]
+pub mod async_transport;
+#[cfg(feature = "async-stratum")]
+pub mod async_client;
 
 extern crate serde;
 extern crate serde_json;
+extern crate rand;
+
+use self::rand::Rng;
 
 use std::thread;
-use std::sync::mpsc::{channel, Receiver, Sender, SendError};
+use std::sync::mpsc::{channel, Receiver, Sender, SendError, RecvTimeoutError};
 use std::sync::{Arc, Mutex};
-use std::net::{Shutdown, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::net::{TcpStream};
+use std::collections::{VecDeque};
 use std::io;
-use std::io::{BufReader, BufRead, BufWriter, Write, Error, ErrorKind};
-use std::time::{Duration};
+use std::io::{BufRead, Write, Error, ErrorKind};
+use std::time::{Duration, Instant};
+use error::{MithrilError};
+use self::transport::{LimitedLine, StratumTransport, read_line_limited};
+use hex;
+use cryptonight;
+use determinism;
+use difficulty;
+use byte_string;
+
+/// Maximum number of nonce bytes a pool can reserve, at least one byte is left for the workers
+pub const MAX_NONCE_PREFIX_BYTES : usize = 3;
+
+/// Seconds between attempts to connect to the pool during login
+pub const LOGIN_RETRY_DELAY_SECS : u64 = 5;
+
+/// Maximum accepted length of a single line received from the pool
+pub const MAX_LINE_LENGTH : usize = 64 * 1024;
+
+/// Number of cancelled job ids remembered for dropping their shares
+pub const CANCELLED_JOBS_KEPT : usize = 16;
+/// Number of job targets remembered for validating shares in dry run mode
+pub const JOB_TARGETS_KEPT : usize = 16;
 
 /// command send to the stratum server
 #[derive(Debug)]
@@ -21,6 +78,28 @@ pub enum StratumCmd {
     KeepAlive{
         miner_id: String
     },
+    /// self-select mode: the block template chosen for a job
+    BlockTemplate{
+        params: stratum_data::BlockTemplateParams
+    },
+    /// switches the dry run mode (shares are validated and logged, but not sent) on or off
+    SetDryRun{
+        enabled: bool
+    },
+    /// the pool cancelled the job, shares for it are not sent anymore
+    CancelJob{
+        job_id: String
+    },
+    /// the pool sent a job (or retargeted it), shares of the replaced jobs are late from now on
+    JobReceived{
+        job_id: String,
+        target: String
+    },
+    /// answers the request of the pool with the id
+    Respond{
+        id: serde_json::Value,
+        result: Result<serde_json::Value, rpc::RpcError>
+    },
     Shutdown {},
 }
 
@@ -31,104 +110,363 @@ pub enum StratumAction {
         miner_id: String,
         blob: String,
         job_id: String,
-        target: String
+        /// raw little endian hex target as sent by the pool, 8 (32 bit) or 16 (64 bit) chars
+        target: String,
+        /// difficulty of the target
+        difficulty: u64,
+        /// algorithm requested by the pool, None if not sent (cryptonight v8 is assumed then)
+        algo: Option<String>,
+        height: Option<u64>,
+        seed_hash: Option<String>,
+        next_seed_hash: Option<String>
+    },
+    /// self-select mode: a job without blob, to be completed with a local block template
+    SelectTemplate{
+        miner_id: String,
+        job_id: String,
+        target: String,
+        extra_nonce: String,
+        pool_wallet: String
+    },
+    /// the pool reserved the leading nonce bytes, applies to all following jobs
+    ExtranonceSet{
+        nonce_prefix: Vec<u8>
+    },
+    /// the pool cancelled a job (None: all current work), workers stop until the next job
+    JobCancelled{
+        job_id: Option<String>
+    },
+    /// the pool refused the login (e.g. invalid wallet address or banned), the connection ends
+    LoginRejected{
+        reason: String
     },
     Error{
         err: String
     },
     Ok,
     KeepAliveOk,
+    /// a submitted share was accepted
+    ShareAccepted{
+        job_id: String
+    },
+    /// a submitted share was rejected, `reason` and `code` as sent by the pool
+    ShareRejected{
+        job_id: String,
+        reason: String,
+        code: i64
+    },
+    /// share counts, share stage latency, pool score and TCP statistics (None if not supported
+    /// on this platform) of the connection, sent every `stats_interval_secs`
+    Stats{
+        stats: share_stats::ShareStats,
+        latency: latency::StageLatency,
+        score: pool_score::ScoreStats,
+        socket: Option<socket_stats::SocketStats>
+    },
+    /// the pool kept rejecting shares or sending errors through several backoffs, see `flood_guard`
+    PoolUnhealthy{
+        reason: String
+    },
+    /// a share was not sent because the flood guard backs off from the pool, it is dropped
+    ShareWithheld{
+        job_id: String
+    },
+}
+
+/// Classification of the rejection reasons pools send for shares
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RejectKind {
+    /// the share does not meet the target (e.g. "Low difficulty share")
+    LowDifficulty,
+    /// the job is no longer valid (e.g. "Block expired", "Invalid job id")
+    Stale,
+    /// the share was already submitted
+    Duplicate,
+    Other
 }
 
+impl RejectKind {
+    /// Classifies a rejection reason, the messages differ between pool implementations
+    pub fn from_reason(reason: &str) -> RejectKind {
+        let reason = reason.to_lowercase();
+        if reason.contains("low difficulty") || reason.contains("low diff") {
+            RejectKind::LowDifficulty
+        } else if reason.contains("expired") || reason.contains("stale") || reason.contains("invalid job") || reason.contains("job not found") {
+            RejectKind::Stale
+        } else if reason.contains("duplicate") {
+            RejectKind::Duplicate
+        } else {
+            RejectKind::Other
+        }
+    }
+}
+
+/// True if a rejection means the pool dropped the session (e.g. after a pool restart),
+/// a new login is needed then.
+pub fn is_unauthenticated(reason: &str) -> bool {
+    let reason = reason.to_lowercase();
+    reason.contains("unauthenticated") || reason.contains("not authenticated")
+}
+
+/// Outcome of handling a line received from the pool
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dispatched {
+    Action,
+    /// a share was rejected because the session is gone, the client has to log in again
+    Relogin
+}
+
+/// Sequence number of the next dispatched action, shared by all clients
+static NEXT_EVENT_SEQ : AtomicU64 = AtomicU64::new(0);
+
+/// A `StratumAction` as dispatched to the subscriber, stamped with a monotonic
+/// receive timestamp and a sequence number for detecting reordering or gaps.
+#[derive(Debug, Clone)]
+pub struct StratumEvent {
+    pub seq: u64,
+    pub received: Instant,
+    /// the connection of a `MultiPoolClient` the action belongs to (see `multi_pool::Arbiter::source`),
+    /// None for single pool clients and for actions of all connections
+    pub connection: Option<usize>,
+    pub action: StratumAction,
+}
+
+impl StratumEvent {
+    pub fn new(action: StratumAction) -> StratumEvent {
+        StratumEvent{seq: NEXT_EVENT_SEQ.fetch_add(1, Ordering::SeqCst), received: Instant::now(), connection: None, action}
+    }
+}
+
+/// Errors ending the communication with the pool, reported via the error channel
+#[derive(Debug, Error)]
 pub enum StratumError {
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+    #[error("protocol error: {0}")]
+    Protocol(String),
+    #[error("json error: {0}")]
+    Serde(#[from] serde_json::Error),
+    #[error("channel closed")]
+    ChannelClosed,
+    #[error("login rejected: {0}")]
+    LoginRejected(String),
+    #[error("no job received for {0} seconds")]
+    JobTimeout(u64),
 }
 
 pub struct StratumClient {
     command_sender: Sender<StratumCmd>,
     send_thread: thread::JoinHandle<()>,
     rcv_thread: thread::JoinHandle<()>,
-    keep_alive_thread: thread::JoinHandle<()>,
-    tcp_stream_hnd: TcpStream,
-    tick_tx: Sender<()>,
+    /// template assembly of self-select mode, None if not enabled
+    template_thread: Option<thread::JoinHandle<()>>,
+    /// hands the actions to the login channel and the subscribers
+    dispatch_thread: thread::JoinHandle<()>,
+    /// None if keep alive is disabled
+    keep_alive: Option<TickThreads>,
+    /// broadcasts the share counts, None if disabled
+    stats_broadcast: Option<TickThreads>,
+    /// the connection, shared with the send and receive thread
+    transport: Arc<dyn StratumTransport>,
+    /// requests waiting for a response, shared with the send and receive thread
+    pending: Arc<Mutex<pending::PendingRequests>>,
+    /// set on stop(), errors of the receive thread caused by the shutdown are not reported
+    stopping: Arc<AtomicBool>,
+    /// receivers of the actions registered after login, shared with the dispatch thread
+    subscribers: Arc<Mutex<subscribers::Subscribers>>,
+    /// bounds the shares waiting in the command channel
+    share_queue: Arc<share_queue::ShareQueue>,
+    /// connection state and last job, shared with the dispatch and receive thread
+    state: Arc<Mutex<client_state::ClientState>>,
+    /// answer the requests of the pool, shared with the receive thread
+    rpc: Arc<Mutex<rpc::RpcHandlers>>,
+}
+
+/// A thread acting on the ticks of a tick thread, both end when `stop_tx` is used or dropped
+struct TickThreads {
+    thread: thread::JoinHandle<()>,
+    tick_thread: thread::JoinHandle<()>,
+    stop_tx: Sender<()>,
 }
 
 /// All operation in the client are async
 impl StratumClient {
-    pub fn login(pool_conf: stratum_data::PoolConfig, err_receiver: Sender<Error>, action_rcv: Sender<StratumAction>) -> io::Result<StratumClient> {
+    pub fn login(pool_conf: stratum_data::PoolConfig, err_receiver: Sender<StratumError>, action_rcv: Sender<StratumEvent>) -> Result<StratumClient, MithrilError> {
+        StratumClient::login_with_subscribers(pool_conf, err_receiver, action_rcv, subscribers::Subscribers::new())
+    }
+
+    /// Like `login`, `subscribers` receive the actions from the first response on
+    /// (see `builder::StratumClientBuilder`).
+    pub fn login_with_subscribers(pool_conf: stratum_data::PoolConfig, err_receiver: Sender<StratumError>, action_rcv: Sender<StratumEvent>, subscribers: subscribers::Subscribers) -> Result<StratumClient, MithrilError> {
+        StratumClient::login_with_rpc(pool_conf, err_receiver, action_rcv, subscribers, rpc::RpcHandlers::new())
+    }
+
+    /// Like `login_with_subscribers`, requests of the pool are answered by `rpc`.
+    pub fn login_with_rpc(pool_conf: stratum_data::PoolConfig, err_receiver: Sender<StratumError>, action_rcv: Sender<StratumEvent>, subscribers: subscribers::Subscribers, rpc: rpc::RpcHandlers) -> Result<StratumClient, MithrilError> {
 
         info!("connecting to address: {}", pool_conf.pool_address);
 
-        let (tcp_stream_hnd, reader, writer) = StratumClient::connect_tcp(&pool_conf.pool_address)?;
+        let transport = StratumClient::connect_with_retries(&pool_conf).map_err(StratumError::Io)?;
+        StratumClient::start_with_rpc(Arc::new(transport), pool_conf, err_receiver, action_rcv, subscribers, rpc)
+    }
+
+    /// Starts the client on an established connection and logs in, e.g. with a
+    /// transport other than TCP.
+    pub fn start(transport: Arc<dyn StratumTransport>, pool_conf: stratum_data::PoolConfig, err_receiver: Sender<StratumError>, action_rcv: Sender<StratumEvent>, subscribers: subscribers::Subscribers) -> Result<StratumClient, MithrilError> {
+        StratumClient::start_with_rpc(transport, pool_conf, err_receiver, action_rcv, subscribers, rpc::RpcHandlers::new())
+    }
 
+    /// Like `start`, requests of the pool are answered by `rpc`.
+    pub fn start_with_rpc(transport: Arc<dyn StratumTransport>, pool_conf: stratum_data::PoolConfig, err_receiver: Sender<StratumError>, action_rcv: Sender<StratumEvent>, subscribers: subscribers::Subscribers, rpc: rpc::RpcHandlers) -> Result<StratumClient, MithrilError> {
         let miner_id = Arc::new(Mutex::new(Option::None));
+        let pending = Arc::new(Mutex::new(pending::PendingRequests::with_flood_guard(pool_conf.flood_guard)));
         let (command_sender, command_receiver) = channel();
+        let share_queue = Arc::new(share_queue::ShareQueue::new(pool_conf.share_queue_capacity as usize, pool_conf.share_queue_policy));
+        let stopping = Arc::new(AtomicBool::new(false));
+        let keep_alive_jitter = Duration::from_secs(pool_conf.keepalive_jitter_seconds);
+        let keep_alive_interval = pool_conf.keepalive_interval_secs.map(Duration::from_secs);
+        let stats_interval = pool_conf.stats_interval_secs.map(Duration::from_secs);
+        let min_pool_score = pool_conf.min_pool_score;
+
+        let state = Arc::new(Mutex::new(client_state::ClientState::new()));
+        let rpc = Arc::new(Mutex::new(rpc));
 
-        let send_thread = StratumClient::start_send_thread(writer, command_receiver, pool_conf, err_receiver.clone())?;
-        let rcv_thread = StratumClient::start_receive_thread(reader, action_rcv, miner_id.clone(), err_receiver)?;
-        let (keep_alive_thread, tick_tx) = StratumClient::start_keep_alive_thread(command_sender.clone(), miner_id.clone())?;
+        let subscribers = Arc::new(Mutex::new(subscribers));
+        let (action_rcv, dispatch_thread) = subscribers::start_dispatch_thread_with_state(action_rcv, subscribers.clone(), state.clone()).map_err(StratumError::Io)?;
 
-        command_sender.send(StratumCmd::Login{}).expect("login command send");
+        let (action_rcv, template_thread) = match pool_conf.self_select_daemon_address.clone() {
+            Some(daemon_address) => {
+                info!("self-select mode, block templates from daemon {}", daemon_address);
+                let (tx, hnd) = self_select::start_template_thread(daemon_address, command_sender.clone(), action_rcv).map_err(StratumError::Io)?;
+                (tx, Some(hnd))
+            },
+            None => (action_rcv, None)
+        };
+
+        let send_thread = StratumClient::start_send_thread(transport.clone(), command_receiver, action_rcv.clone(), pool_conf, pending.clone(), share_queue.clone(), err_receiver.clone())?;
+        let stats_broadcast = match stats_interval {
+            Some(interval) => Some(StratumClient::start_stats_thread(transport.clone(), action_rcv.clone(), pending.clone(), interval, min_pool_score)?),
+            None => None
+        };
+        let rcv_thread = StratumClient::start_receive_thread(transport.clone(), action_rcv, command_sender.clone(), miner_id.clone(), pending.clone(), stopping.clone(), state.clone(), rpc.clone(), err_receiver)?;
+        let keep_alive = match keep_alive_interval {
+            Some(interval) => Some(StratumClient::start_keep_alive_thread(command_sender.clone(), miner_id.clone(), interval, keep_alive_jitter)?),
+            None => {
+                info!("keep alive disabled");
+                None
+            }
+        };
+
+        command_sender.send(StratumCmd::Login{}).map_err(|_| StratumError::ChannelClosed)?;
 
         Ok(StratumClient{
             command_sender,
             send_thread,
             rcv_thread,
-            keep_alive_thread,
-            tcp_stream_hnd,
-            tick_tx
+            template_thread,
+            dispatch_thread,
+            keep_alive,
+            stats_broadcast,
+            transport,
+            pending,
+            stopping,
+            subscribers,
+            share_queue,
+            state,
+            rpc,
         })
     }
 
-    fn connect_tcp(pool_address: &str) -> io::Result<(TcpStream, BufReader<TcpStream>, BufWriter<TcpStream>)> {
-        let stream = TcpStream::connect(pool_address)?;
-        stream.set_read_timeout(None)?;
-        stream.set_write_timeout(Some(Duration::from_secs(10)))?;
-
-        let reader = BufReader::new(stream.try_clone()?);
-        let writer = BufWriter::new(stream.try_clone()?);
-
-        Ok((stream, reader, writer))
+    /// Connects to the pool, transient failures are retried `login_retries` times.
+    fn connect_with_retries(pool_conf: &stratum_data::PoolConfig) -> io::Result<transport::TcpTransport> {
+        retry_transient(pool_conf, || transport::TcpTransport::connect(pool_conf))
     }
 
-    fn start_send_thread(writer: BufWriter<TcpStream>, command_rcv: Receiver<StratumCmd>, pool_conf: stratum_data::PoolConfig, err_receiver: Sender<Error>) -> io::Result<thread::JoinHandle<()>> {
+    fn start_send_thread(transport: Arc<dyn StratumTransport>, command_rcv: Receiver<StratumCmd>, action_rcv: Sender<StratumEvent>, pool_conf: stratum_data::PoolConfig, pending: Arc<Mutex<pending::PendingRequests>>, share_queue: Arc<share_queue::ShareQueue>, err_receiver: Sender<StratumError>) -> Result<thread::JoinHandle<()>, StratumError> {
         Ok(thread::Builder::new().name("Stratum send thread".to_string()).spawn(move || {
-            let result = handle_stratum_send(&command_rcv, writer, &pool_conf);
-            if result.is_err() {
-                err_receiver.send(result.err().expect("result error send thread")).expect("sending error in send thread");
+            let state = SendState::with_queue(&pool_conf, share_queue.clone(), action_rcv);
+            if let Err(err) = send_commands(&command_rcv, transport::TransportWriter::new(&*transport), state, &pool_conf, &pending) {
+                report_error(&err_receiver, err);
+            }
+            //no one takes shares from the channel anymore
+            share_queue.close();
+            //shares still waiting in the channel are kept for the next connection
+            for cmd in command_rcv.try_iter() {
+                if let StratumCmd::SubmitShare{share} = cmd {
+                    share_queue.release();
+                    share_queue.keep(share);
+                }
             }
             info!("stratum send thread ended");
         })?)
     }
 
-    fn start_receive_thread(reader: BufReader<TcpStream>, action_rcv: Sender<StratumAction>, miner_id: Arc<Mutex<Option<String>>>, err_receiver: Sender<Error>) -> io::Result<thread::JoinHandle<()>> {
+    fn start_receive_thread(transport: Arc<dyn StratumTransport>, action_rcv: Sender<StratumEvent>, cmd_tx: Sender<StratumCmd>, miner_id: Arc<Mutex<Option<String>>>, pending: Arc<Mutex<pending::PendingRequests>>, stopping: Arc<AtomicBool>, state: Arc<Mutex<client_state::ClientState>>, rpc: Arc<Mutex<rpc::RpcHandlers>>, err_receiver: Sender<StratumError>) -> Result<thread::JoinHandle<()>, StratumError> {
         Ok(thread::Builder::new().name("Stratum receive thread".to_string()).spawn(move || {
-            let result = handle_stratum_receive(reader, &action_rcv, &miner_id);
-            if result.is_err() {
-                err_receiver.send(result.err().expect("result error recv thread")).expect("sending error in recv thread");
+            let result = handle_transport_receive_with_rpc(&*transport, &action_rcv, &cmd_tx, &miner_id, &pending, &rpc);
+            //nothing is received anymore, the connection is gone
+            state.lock().expect("client state lock").disconnected();
+            if let Err(err) = result {
+                if stopping.load(Ordering::SeqCst) {
+                    info!("stratum receive thread stopped ({})", err);
+                } else {
+                    report_error(&err_receiver, err);
+                }
             }
             info!("stratum receive thread ended");
         })?)
     }
 
-    fn start_keep_alive_thread(cmd_alive: Sender<StratumCmd>, alive_miner_id: Arc<Mutex<Option<String>>>) -> io::Result<(thread::JoinHandle<()>, Sender<()>)> {
+    fn start_keep_alive_thread(cmd_alive: Sender<StratumCmd>, alive_miner_id: Arc<Mutex<Option<String>>>, interval: Duration, jitter: Duration) -> Result<TickThreads, StratumError> {
         let (stop_tx, stop_rx) = channel();
 
-        let (tick_rcv, _) = start_tick_thread(Duration::from_secs(60), stop_rx);
-        Ok((thread::Builder::new().name("keep alive thread".to_string()).spawn(move || {
+        let (tick_rcv, tick_thread) = start_tick_thread(interval, jitter, stop_rx);
+        let thread = thread::Builder::new().name("keep alive thread".to_string()).spawn(move || {
             loop {
                 let tick_result = tick_rcv.recv();
                 if tick_result.is_err() || tick_result.expect("tick result") == Tick::Stop {
                     break;
                 }//else: normal tick, loop around
 
-                let miner_id_guard = &*alive_miner_id.lock().expect("miner_id lock");
-                if miner_id_guard.is_some() {
-                    let miner_id = miner_id_guard.clone().expect("miner_id clone");
-                    cmd_alive.send(StratumCmd::KeepAlive{miner_id}).expect("KeepAlive send failed");
+                let miner_id = alive_miner_id.lock().expect("miner_id lock").clone();
+                if let Some(miner_id) = miner_id {
+                    if cmd_alive.send(StratumCmd::KeepAlive{miner_id}).is_err() {
+                        info!("send thread already terminated, ending keep alive");
+                        break;
+                    }
                 }
             }
             info!("keep alive thread ended");
-        })?, stop_tx))
+        })?;
+        Ok(TickThreads{thread, tick_thread, stop_tx})
+    }
+
+    fn start_stats_thread(transport: Arc<dyn StratumTransport>, action_rcv: Sender<StratumEvent>, pending: Arc<Mutex<pending::PendingRequests>>, interval: Duration, min_pool_score: Option<u64>) -> Result<TickThreads, StratumError> {
+        let (stop_tx, stop_rx) = channel();
+
+        let (tick_rcv, tick_thread) = start_tick_thread(interval, Duration::from_secs(0), stop_rx);
+        let thread = thread::Builder::new().name("share stats thread".to_string()).spawn(move || {
+            while let Ok(Tick::Tick) = tick_rcv.recv() {
+                let (stats, latency, score, unhealthy, low_score) = {
+                    let mut pending = pending.lock().expect("pending requests lock");
+                    //a silent pool sends nothing the receive thread could check the flood guard on
+                    (pending.share_stats(), pending.stage_latency(), pending.pool_score(), pending.poll_flood_guard(), pending.poll_score(min_pool_score))
+                };
+                if unhealthy {
+                    send_action(&action_rcv, StratumAction::PoolUnhealthy{reason: flood_guard::UNHEALTHY_REASON.to_string()});
+                }
+                if let Some(low) = low_score {
+                    send_action(&action_rcv, StratumAction::PoolUnhealthy{reason: format!("pool score {} below min_pool_score {}", low.score, min_pool_score.unwrap_or(0))});
+                }
+                let socket = transport.socket_stats();
+                if action_rcv.send(StratumEvent::new(StratumAction::Stats{stats, latency, score, socket})).is_err() {
+                    info!("action receiver already terminated, ending share stats");
+                    break;
+                }
+            }
+            info!("share stats thread ended");
+        })?;
+        Ok(TickThreads{thread, tick_thread, stop_tx})
     }
 
     /// Returns a new channel for sending commands to the stratum client
@@ -136,27 +474,176 @@ impl StratumClient {
         self.command_sender.clone()
     }
 
+    /// The queue workers submit their shares with
+    pub fn share_queue(self: &Self) -> Arc<share_queue::ShareQueue> {
+        self.share_queue.clone()
+    }
+
+    /// Returns a receiver for all actions dispatched from now on, in addition to the channel
+    /// given on login. The subscription ends when the receiver is dropped.
+    pub fn subscribe(self: &Self) -> Receiver<StratumAction> {
+        self.subscribers.lock().expect("subscribers lock").subscribe()
+    }
+
+    /// Calls `f` for all actions dispatched from now on, a panicking callback is removed.
+    pub fn subscribe_fn<F: Fn(StratumAction) + Send + 'static>(self: &Self, f: F) {
+        self.subscribers.lock().expect("subscribers lock").subscribe_fn(f)
+    }
+
+    /// TCP level statistics of the pool connection (None if not supported on this platform)
+    pub fn socket_stats(self: &Self) -> Option<socket_stats::SocketStats> {
+        self.transport.socket_stats()
+    }
+
+    /// Round trip times of the recent submit and keep alive requests (None if no response was received yet)
+    pub fn latency(self: &Self) -> Option<latency::LatencyStats> {
+        self.pending.lock().expect("pending requests lock").latency()
+    }
+
+    /// Latency of the stages of the recent shares, from the job to the response of the pool
+    pub fn stage_latency(self: &Self) -> latency::StageLatency {
+        self.pending.lock().expect("pending requests lock").stage_latency()
+    }
+
+    /// Submitted, accepted, rejected and stale shares of this connection
+    pub fn stats(self: &Self) -> share_stats::ShareStats {
+        self.pending.lock().expect("pending requests lock").share_stats()
+    }
+
+    /// Rolling score of the pool from the recent share responses and jobs
+    pub fn pool_score(self: &Self) -> pool_score::ScoreStats {
+        self.pending.lock().expect("pending requests lock").pool_score()
+    }
+
+    /// The last `StratumAction::Job` of the pool, e.g. for workers started after it was
+    /// dispatched (None before the first job and after the job was cancelled)
+    pub fn current_job(self: &Self) -> Option<StratumAction> {
+        self.state.lock().expect("client state lock").current_job()
+    }
+
+    /// Connecting until the first job, Disconnected after the connection ended or stop()
+    pub fn state(self: &Self) -> client_state::ConnectionState {
+        self.state.lock().expect("client state lock").state()
+    }
+
+    /// Answers requests of the pool for `method` with `f` from now on, replacing the
+    /// handler registered before (e.g. the default `client.get_version` or `ping`)
+    pub fn rpc_handler<F: Fn(&serde_json::Value) -> Result<serde_json::Value, String> + Send + 'static>(self: &Self, method: &str, f: F) {
+        self.rpc.lock().expect("rpc handlers lock").register(method, f);
+    }
+
     /// Stops the StratumClient, ending all communication with the server end.
+    /// Signals all threads and waits for them, threads that already ended
+    /// (e.g. after a connection error) are fine.
     pub fn stop(self: Self) {
         info!("stopping stratum client");
+        if let Some(stats) = self.socket_stats() {
+            info!("connection stats: {:?}", stats);
+        }
+        if let Some(latency) = self.latency() {
+            info!("pool latency over the last {} requests: median {:?}, mean {:?}, min {:?}, max {:?}",
+                latency.samples, latency.median, latency.mean, latency.min, latency.max);
+        }
+        if let Some(report) = self.stage_latency().render() {
+            info!("{}", report);
+        }
+        log_share_stats(&self.stats());
+        self.stopping.store(true, Ordering::SeqCst);
+        self.state.lock().expect("client state lock").disconnected();
+
+        log_queue_stats(&self.share_queue.stats());
+        //wake workers waiting for a free slot
+        self.share_queue.close();
 
         //stop send thread
-        self.command_sender.send(StratumCmd::Shutdown{}).expect("shutdown command send");
+        if self.command_sender.send(StratumCmd::Shutdown{}).is_err() {
+            info!("stratum send thread already ended");
+        }
 
         //stop receive thread
-        let shutdown_result = self.tcp_stream_hnd.shutdown(Shutdown::Both);
+        let shutdown_result = self.transport.shutdown();
         if shutdown_result.is_err() {
-            info!("TcpStream shutdown failed {:?}", shutdown_result);
+            info!("transport shutdown failed {:?}", shutdown_result);
         } else {
-            info!("TcpStream shutdown ok");
+            info!("transport shutdown ok");
+        }
+
+
+        //stop keep alive and share stats thread (via stopping tick thread)
+        for ticked in self.keep_alive.iter().chain(self.stats_broadcast.iter()) {
+            if ticked.stop_tx.send(()).is_err() {
+                info!("tick thread already ended");
+            }
+        }
+        join_thread("send", self.send_thread);
+        join_thread("receive", self.rcv_thread);
+        if let Some(template_thread) = self.template_thread {
+            join_thread("template", template_thread);
+        }
+        join_thread("dispatch", self.dispatch_thread);
+        if let Some(keep_alive) = self.keep_alive {
+            join_thread("tick", keep_alive.tick_thread);
+            join_thread("keep alive", keep_alive.thread);
+        }
+        if let Some(stats_broadcast) = self.stats_broadcast {
+            join_thread("tick", stats_broadcast.tick_thread);
+            join_thread("share stats", stats_broadcast.thread);
+        }
+        info!("stratum client stopped");
+    }
+}
+
+/// Connects to the first reachable address `address` resolves to, waiting at most
+/// `timeout` for each address.
+pub fn connect_with_timeout(address: &str, timeout: Duration) -> io::Result<TcpStream> {
+    resolve::connect(address, timeout, resolve::AddressPreference::System)
+}
+
+/// Logs how many shares had to wait for the pool and how many were dropped
+pub fn log_queue_stats(stats: &share_queue::QueueStats) {
+    if stats.dropped > 0 {
+        warn!("share queue: {} shares dropped, at most {} of {} slots used", stats.dropped, stats.max_depth, stats.capacity);
+    } else {
+        info!("share queue: at most {} of {} slots used", stats.max_depth, stats.capacity);
+    }
+}
+
+/// Logs the share counts of a connection
+pub fn log_share_stats(stats: &share_stats::ShareStats) {
+    info!("shares: {} submitted, {} accepted, {} rejected, {} stale, {} unanswered",
+        stats.submitted, stats.accepted, stats.rejected, stats.stale, stats.unanswered());
+}
+
+/// Calls `connect` until it succeeds, a transient failure is retried `login_retries` times.
+pub fn retry_transient<T, F: FnMut() -> io::Result<T>>(pool_conf: &stratum_data::PoolConfig, mut connect: F) -> io::Result<T> {
+    let mut attempt = 0;
+    loop {
+        match connect() {
+            Err(ref err) if attempt < pool_conf.login_retries && is_transient(err) => {
+                attempt += 1;
+                warn!("connecting to {} failed: {}, retry {} of {} in {} seconds",
+                    pool_conf.pool_address, err, attempt, pool_conf.login_retries, LOGIN_RETRY_DELAY_SECS);
+                thread::sleep(Duration::from_secs(LOGIN_RETRY_DELAY_SECS));
+            },
+            result => return result
         }
+    }
+}
 
+/// Returns true for connection errors that may go away by trying again. Refused
+/// pinning checks or unresolvable addresses are not transient.
+pub fn is_transient(err: &io::Error) -> bool {
+    match err.kind() {
+        ErrorKind::ConnectionRefused | ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted |
+        ErrorKind::NotConnected | ErrorKind::TimedOut | ErrorKind::WouldBlock |
+        ErrorKind::Interrupted | ErrorKind::UnexpectedEof | ErrorKind::BrokenPipe => true,
+        _ => false
+    }
+}
 
-        //stop keep alive thread (via stopping tick thread)
-        self.tick_tx.send(()).expect("ending tick thread");
-        self.send_thread.join().expect("join send thread");
-        self.rcv_thread.join().expect("join rcv thread");
-        self.keep_alive_thread.join().expect("keep alive thread");
+fn join_thread(name: &str, hnd: thread::JoinHandle<()>) {
+    if hnd.join().is_err() {
+        error!("stratum {} thread panicked", name);
     }
 }
 
@@ -166,17 +653,19 @@ pub enum Tick {
     Stop
 }
 
-pub fn start_tick_thread(interval: Duration, stop_rcv: Receiver<()>) -> (Receiver<Tick>, thread::JoinHandle<()>) {
+/// Sends a tick every `interval` plus a random delay of up to `jitter`, until stopped.
+pub fn start_tick_thread(interval: Duration, jitter: Duration, stop_rcv: Receiver<()>) -> (Receiver<Tick>, thread::JoinHandle<()>) {
     let (tx, rx) = channel();
     let hnd = thread::Builder::new().name("tick thread".to_string()).spawn(move || {
+        let mut rng = determinism::rng(determinism::STREAM_KEEPALIVE_JITTER);
         loop {
-            let result = stop_rcv.recv_timeout(interval);
-            if result.is_err() { //err means timeout reached and not a "normal" shutdown
+            let result = stop_rcv.recv_timeout(interval + random_jitter(&mut rng, jitter));
+            if result == Err(RecvTimeoutError::Timeout) { //timeout reached and not a "normal" shutdown
                 let send_result = tx.send(Tick::Tick);
                 if send_result.is_err() {
                     info!("sending tick signal failed {:?}", send_result);
                 }
-            } else { //shutdown received, end everything
+            } else { //shutdown received (or stop sender dropped), end everything
                 let stop_send_result = tx.send(Tick::Stop);
                 if stop_send_result.is_err() {
                     info!("sending tick stop signal failed, {:?}", stop_send_result);
@@ -188,119 +677,434 @@ pub fn start_tick_thread(interval: Duration, stop_rcv: Receiver<()>) -> (Receive
     (rx, hnd)
 }
 
+fn report_error(err_receiver: &Sender<StratumError>, err: StratumError) {
+    if let Err(e) = err_receiver.send(err) {
+        info!("error receiver already terminated, error was: {}", e.0);
+    }
+}
+
+fn random_jitter<R: Rng>(rng: &mut R, max: Duration) -> Duration {
+    let max_millis = max.as_secs() * 1000 + u64::from(max.subsec_millis());
+    if max_millis == 0 {
+        return Duration::from_secs(0);
+    }
+    Duration::from_millis(rng.gen_range(0, max_millis + 1))
+}
+
 pub fn submit_share(tx: &Sender<StratumCmd>, share: stratum_data::Share) -> Result<(), SendError<StratumCmd>> {
     info!("submitting share: {:?}", share);
     tx.send(StratumCmd::SubmitShare{share})
 }
 
-fn handle_stratum_send(rx: &Receiver<StratumCmd>, mut writer: BufWriter<TcpStream>, pool_conf: &stratum_data::PoolConfig) -> Result<(), Error> {
-    loop {
-        match rx.recv().expect("stratum receiver") {
-            StratumCmd::Login{} => do_stratum_login(&mut writer, pool_conf)?,
-            StratumCmd::SubmitShare{share} => do_stratum_submit_share(&mut writer, share)?,
-            StratumCmd::KeepAlive{miner_id} => do_stratum_keep_alive(&mut writer, miner_id)?,
+/// Writes all commands received on `rx` to the writer until a `Shutdown` command is received.
+/// Every request gets a new id, registered in `pending` for correlating the response.
+pub fn handle_stratum_send<W: Write>(rx: &Receiver<StratumCmd>, writer: W, pool_conf: &stratum_data::PoolConfig, pending: &Arc<Mutex<pending::PendingRequests>>) -> Result<(), StratumError> {
+    send_commands(rx, writer, SendState::new(pool_conf), pool_conf, pending)
+}
+
+fn send_commands<W: Write>(rx: &Receiver<StratumCmd>, mut writer: W, mut state: SendState, pool_conf: &stratum_data::PoolConfig, pending: &Arc<Mutex<pending::PendingRequests>>) -> Result<(), StratumError> {
+    while state.write_command(&mut writer, rx.recv().map_err(|_| StratumError::ChannelClosed)?, pool_conf, pending)? {}
+    Ok(())
+}
+
+/// Share filters and the dry run switch of a connection, applied to the commands before writing them.
+pub struct SendState {
+    share_cap: share_cap::ShareCap,
+    recent_shares: duplicates::RecentShares,
+    late_shares: late_shares::LateShares,
+    /// job id and target of the most recently received jobs, oldest first
+    job_targets: VecDeque<(String, String)>,
+    dry_run: bool,
+    /// released for every share taken from the channel, None if shares are not bounded
+    share_queue: Option<Arc<share_queue::ShareQueue>>,
+    /// receives withheld shares and `PoolUnhealthy` found on the send side (None: only logged)
+    events: Option<Sender<StratumEvent>>,
+}
+
+impl SendState {
+    pub fn new(pool_conf: &stratum_data::PoolConfig) -> SendState {
+        SendState{
+            share_cap: share_cap::ShareCap::new(pool_conf.max_shares_per_job),
+            recent_shares: duplicates::RecentShares::new(),
+            late_shares: late_shares::LateShares::new(pool_conf.late_share_grace_secs.map(Duration::from_secs)),
+            job_targets: VecDeque::with_capacity(JOB_TARGETS_KEPT),
+            dry_run: pool_conf.dry_run,
+            share_queue: None,
+            events: None,
+        }
+    }
+
+    pub fn with_queue(pool_conf: &stratum_data::PoolConfig, share_queue: Arc<share_queue::ShareQueue>, events: Sender<StratumEvent>) -> SendState {
+        SendState{share_queue: Some(share_queue), events: Some(events), ..SendState::new(pool_conf)}
+    }
+
+    fn send_event(&self, action: StratumAction) {
+        if let Some(ref events) = self.events {
+            send_action(events, action);
+        }
+    }
+
+    /// Reports `PoolUnhealthy` if the flood guard gave up on the pool since the last command,
+    /// also without a response from the pool (e.g. an unanswered probe)
+    fn check_flood_guard(&self, pending: &Arc<Mutex<pending::PendingRequests>>) {
+        if pending.lock().expect("pending requests lock").poll_flood_guard() {
+            warn!("pool unhealthy: {}", flood_guard::UNHEALTHY_REASON);
+            self.send_event(StratumAction::PoolUnhealthy{reason: flood_guard::UNHEALTHY_REASON.to_string()});
+        }
+    }
+
+    /// Writes the request for `cmd` (if any), returns false for `Shutdown`.
+    pub fn write_command<W: Write>(&mut self, writer: &mut W, cmd: StratumCmd, pool_conf: &stratum_data::PoolConfig, pending: &Arc<Mutex<pending::PendingRequests>>) -> Result<bool, StratumError> {
+        match cmd {
+            StratumCmd::Login{} => {
+                let id = register_request(pending, pending::Request::Login);
+                do_stratum_login(writer, id, pool_conf)?
+            },
+            StratumCmd::SubmitShare{share} => {
+                if let Some(ref queue) = self.share_queue {
+                    queue.release();
+                }
+                if self.dry_run {
+                    let target = self.job_targets.iter().rev().find(|t| t.0 == share.job_id).map(|t| t.1.as_str());
+                    match validate_share(&share, target) {
+                        Ok(()) => info!("dry run: valid share for job {} not sent: {:?}", share.job_id, share),
+                        Err(e) => warn!("dry run: invalid share for job {} ({}): {:?}", share.job_id, e, share)
+                    }
+                } else if pending.lock().expect("pending requests lock").is_cancelled(&share.job_id) {
+                    info!("share for cancelled job {} not sent", share.job_id);
+                } else if self.recent_shares.is_duplicate(&share.job_id, &share.nonce) {
+                    warn!("duplicate share for job {} with nonce {} not sent ({} duplicates dropped so far)", share.job_id, share.nonce, self.recent_shares.dropped());
+                } else if !self.late_shares.allow(&share.job_id) {
+                    info!("share for replaced job {} not sent, grace window passed ({} late shares dropped so far)", share.job_id, self.late_shares.dropped());
+                } else if !self.share_cap.allow(&share.job_id) {
+                    info!("share for job {} not sent, per job limit reached ({} shares withheld so far)", share.job_id, self.share_cap.withheld());
+                } else {
+                    let mut pending_requests = pending.lock().expect("pending requests lock");
+                    let decision = pending_requests.allow_share();
+                    if decision == flood_guard::Decision::Withhold {
+                        warn!("share for job {} dropped, backing off from the pool ({} shares withheld so far)", share.job_id, pending_requests.withheld_shares());
+                        drop(pending_requests);
+                        self.send_event(StratumAction::ShareWithheld{job_id: share.job_id});
+                    } else {
+                        if decision == flood_guard::Decision::Probe {
+                            info!("share for job {} sent as probe", share.job_id);
+                        }
+                        if self.late_shares.is_late(&share.job_id) {
+                            self.late_shares.record_submitted();
+                            info!("late share for replaced job {} submitted ({} late shares submitted so far)", share.job_id, self.late_shares.submitted());
+                        }
+                        let id = pending_requests.register_share(share.job_id.clone(), share.times);
+                        drop(pending_requests);
+                        do_stratum_submit_share(writer, id, share, pool_conf)?
+                    }
+                }
+            },
+            StratumCmd::KeepAlive{miner_id} => {
+                let id = register_request(pending, pending::Request::KeepAlive);
+                do_stratum_keep_alive(writer, id, miner_id, pool_conf)?
+            },
+            StratumCmd::BlockTemplate{params} => {
+                let id = register_request(pending, pending::Request::BlockTemplate{job_id: params.job_id.clone()});
+                do_stratum_block_template(writer, id, params)?
+            },
+            StratumCmd::SetDryRun{enabled} => {
+                info!("share submission dry run {}", if enabled { "enabled" } else { "disabled" });
+                self.dry_run = enabled;
+            },
+            StratumCmd::CancelJob{job_id} => {
+                pending.lock().expect("pending requests lock").cancel_job(Some(&job_id));
+            },
+            StratumCmd::JobReceived{job_id, target} => {
+                self.late_shares.job_received(&job_id);
+                self.job_targets.retain(|t| t.0 != job_id);
+                if self.job_targets.len() == JOB_TARGETS_KEPT {
+                    self.job_targets.pop_front();
+                }
+                self.job_targets.push_back((job_id, target));
+            },
+            StratumCmd::Respond{id, result} => {
+                do_stratum_respond(writer, id, result)?
+            },
             StratumCmd::Shutdown{} => {
                 info!("stopping stratum send thread");
-                break;
+                return Ok(false);
             }
         }
+        self.check_flood_guard(pending);
+        Ok(true)
+    }
+}
+
+/// Checks that the share is well formed: ids are present, the nonce is 4 and the hash 32 hex encoded bytes.
+/// With the `target` of the share's job the hash must also meet it.
+pub fn validate_share(share: &stratum_data::Share, target: Option<&str>) -> Result<(), String> {
+    if share.miner_id.is_empty() {
+        return Err("miner_id missing".to_string());
+    }
+    if share.job_id.is_empty() {
+        return Err("job_id missing".to_string());
+    }
+    match hex::decode(&share.nonce) {
+        Ok(ref nonce) if nonce.len() == 4 => (),
+        Ok(nonce) => return Err(format!("nonce has {} bytes, expected 4", nonce.len())),
+        Err(e) => return Err(format!("nonce is not hex: {:?}", e))
+    }
+    match hex::decode(&share.hash) {
+        Ok(ref hash) if hash.len() == 32 => (),
+        Ok(hash) => return Err(format!("hash has {} bytes, expected 32", hash.len())),
+        Err(e) => return Err(format!("hash is not hex: {:?}", e))
+    }
+    if let Some(target) = target {
+        let target_val = difficulty::parse_target(target).map_err(|e| format!("job target invalid: {}", e))?;
+        let hash_val = byte_string::hex2_u64_le(&share.hash[48..]);
+        if hash_val >= target_val {
+            return Err(format!("hash does not meet the job target (share diff {} / job {})",
+                difficulty::format_difficulty(difficulty::hash_difficulty(hash_val)),
+                difficulty::format_difficulty(difficulty::target_difficulty(target_val))));
+        }
     }
     Ok(())
 }
 
-fn do_stratum_keep_alive(writer: &mut BufWriter<TcpStream>, miner_id: String) -> Result<(), Error> {
+fn register_request(pending: &Arc<Mutex<pending::PendingRequests>>, request: pending::Request) -> u64 {
+    pending.lock().expect("pending requests lock").register(request)
+}
+
+fn do_stratum_keep_alive<W: Write>(writer: &mut W, id: u64, miner_id: String, pool_conf: &stratum_data::PoolConfig) -> Result<(), StratumError> {
     let keep_alive_req = stratum_data::KeepAliveRequest{
-        id: 1,
-        method: "keepalived".to_string(),
+        id,
+        method: pool_conf.keepalive_method.clone(),
         params: stratum_data::KeepAliveParams {
             id: miner_id
         }
     };
 
-    let json = serde_json::to_string(&keep_alive_req).expect("marshaling keep alive json");
+    let json = serde_json::to_string(&keep_alive_req)?;
     writeln!(writer, "{}", json)?;
     writer.flush()?;
     Ok(())
 }
 
-fn do_stratum_submit_share(writer: &mut BufWriter<TcpStream>, share: stratum_data::Share) -> Result<(), Error> {
+fn do_stratum_submit_share<W: Write>(writer: &mut W, id: u64, share: stratum_data::Share, pool_conf: &stratum_data::PoolConfig) -> Result<(), StratumError> {
     let submit_req = stratum_data::SubmitRequest{
-        id: 1,
+        id,
         method: "submit".to_string(),
-        params: stratum_data::SubmitParams {
-            id: share.miner_id,
-            job_id: share.job_id,
-            nonce: share.nonce,
-            result: share.hash
-        }
+        params: pool_conf.submit_options.params(share, pool_conf.rig_id.as_ref().map(|r| r.as_str()))
     };
-    let json = serde_json::to_string(&submit_req).expect("marshaling submit json");
+    let json = serde_json::to_string(&submit_req)?;
     writeln!(writer, "{}", json)?;
     writer.flush()?;
     Ok(())
 }
 
-fn do_stratum_login(writer: &mut BufWriter<TcpStream>, pool_conf: &stratum_data::PoolConfig) -> Result<(), Error> {
+fn do_stratum_block_template<W: Write>(writer: &mut W, id: u64, params: stratum_data::BlockTemplateParams) -> Result<(), StratumError> {
+    let template_req = stratum_data::BlockTemplateRequest{
+        id,
+        method: "block_template".to_string(),
+        params
+    };
+    let json = serde_json::to_string(&template_req)?;
+    writeln!(writer, "{}", json)?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn do_stratum_respond<W: Write>(writer: &mut W, id: serde_json::Value, result: Result<serde_json::Value, rpc::RpcError>) -> Result<(), StratumError> {
+    let (result, error) = match result {
+        Ok(value) => (Some(value), None),
+        Err(rpc::RpcError{code, message}) => (None, Some(stratum_data::RpcErrorBody{code, message}))
+    };
+    let response = stratum_data::RpcResponse{id, jsonrpc: "2.0".to_string(), result, error};
+    let json = serde_json::to_string(&response)?;
+    writeln!(writer, "{}", json)?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn do_stratum_login<W: Write>(writer: &mut W, id: u64, pool_conf: &stratum_data::PoolConfig) -> Result<(), StratumError> {
     let login_req = stratum_data::LoginRequest {
-        id: 1,
+        id,
         method: "login".to_string(),
         params: stratum_data::LoginParams {
             login: pool_conf.wallet_address.clone(),
-            pass: pool_conf.pool_password.clone()
+            pass: pool_conf.pool_password.clone(),
+            algo: cryptonight::supported_algo_names(),
+            rig_id: pool_conf.rig_id.clone(),
+            agent: pool_conf.agent.clone(),
+            mode: pool_conf.self_select_daemon_address.as_ref().map(|_| "self-select".to_string())
         }
     };
-    let json = serde_json::to_string(&login_req).expect("marshaling login json");
+    let json = serde_json::to_string(&login_req)?;
     writeln!(writer, "{}",json)?;
     writer.flush()?;
     Ok(())
 }
 
-fn handle_stratum_receive(mut reader: BufReader<TcpStream>, rcv: &Sender<StratumAction>, miner_id: &Arc<Mutex<Option<String>>>) -> Result<(), Error> {
+/// Reads lines from the reader and dispatches the parsed actions until EOF or a read error.
+/// Lines longer than `MAX_LINE_LENGTH` or containing invalid UTF-8 are dropped and
+/// reported as a `StratumAction::Error`, the connection is kept open.
+/// Requests of the pool are answered by the default `rpc::RpcHandlers`.
+pub fn handle_stratum_receive<R: BufRead>(mut reader: R, rcv: &Sender<StratumEvent>, cmd_tx: &Sender<StratumCmd>, miner_id: &Arc<Mutex<Option<String>>>, pending: &Arc<Mutex<pending::PendingRequests>>) -> Result<(), StratumError> {
+    let rpc = Mutex::new(rpc::RpcHandlers::new());
+    receive_lines(|| read_line_limited(&mut reader, MAX_LINE_LENGTH), rcv, cmd_tx, miner_id, pending, &rpc)
+}
+
+/// Like `handle_stratum_receive`, reading the lines from `transport`
+pub fn handle_transport_receive(transport: &dyn StratumTransport, rcv: &Sender<StratumEvent>, cmd_tx: &Sender<StratumCmd>, miner_id: &Arc<Mutex<Option<String>>>, pending: &Arc<Mutex<pending::PendingRequests>>) -> Result<(), StratumError> {
+    let rpc = Mutex::new(rpc::RpcHandlers::new());
+    handle_transport_receive_with_rpc(transport, rcv, cmd_tx, miner_id, pending, &rpc)
+}
+
+/// Like `handle_transport_receive`, requests of the pool are answered by the handlers in `rpc`
+pub fn handle_transport_receive_with_rpc(transport: &dyn StratumTransport, rcv: &Sender<StratumEvent>, cmd_tx: &Sender<StratumCmd>, miner_id: &Arc<Mutex<Option<String>>>, pending: &Arc<Mutex<pending::PendingRequests>>, rpc: &Mutex<rpc::RpcHandlers>) -> Result<(), StratumError> {
+    receive_lines(|| transport.read_line(MAX_LINE_LENGTH), rcv, cmd_tx, miner_id, pending, rpc)
+}
+
+/// The command answering `request` with the handler for its method
+pub fn respond_command(request: &rpc::RpcRequest, rpc: &Mutex<rpc::RpcHandlers>) -> StratumCmd {
+    let result = rpc.lock().expect("rpc handlers lock").respond(request);
+    match result {
+        Ok(_) => debug!("answering pool request {} (id {})", request.method, request.id),
+        Err(ref e) => warn!("pool request {} (id {}) answered with error: {}", request.method, request.id, e.message)
+    }
+    StratumCmd::Respond{id: request.id.clone(), result}
+}
+
+fn receive_lines<F: FnMut() -> io::Result<LimitedLine>>(mut read_line: F, rcv: &Sender<StratumEvent>, cmd_tx: &Sender<StratumCmd>, miner_id: &Arc<Mutex<Option<String>>>, pending: &Arc<Mutex<pending::PendingRequests>>, rpc: &Mutex<rpc::RpcHandlers>) -> Result<(), StratumError> {
+    let mut dropped_lines : u64 = 0;
     loop {
-        let mut line = String::new();
-        match reader.read_line(&mut line) {
-            Ok(n) => {
-                if n == 0 { //that means EOF in the TCPStream was reached
-                    return Err(Error::new(ErrorKind::Other, "connection terminated"));
+        //read_line fails (maybe connection lost, dispatch err to channel)
+        //=> Terminate loop
+        let line = match read_line()? {
+            LimitedLine::Eof => {
+                //that means EOF in the TCPStream was reached
+                return Err(StratumError::Io(Error::new(ErrorKind::UnexpectedEof, "connection terminated")));
+            },
+            LimitedLine::Line(bytes) => String::from_utf8(bytes),
+            LimitedLine::TooLong => {
+                dropped_lines += 1;
+                warn!("dropped line exceeding {} bytes ({} lines dropped so far)", MAX_LINE_LENGTH, dropped_lines);
+                send_recorded_action(rcv, pending, StratumAction::Error{err: format!("line exceeding {} bytes dropped", MAX_LINE_LENGTH)});
+                continue;
+            }
+        };
+
+        match line {
+            Ok(l) => {
+                if let Some(request) = rpc::parse_request(&l) {
+                    //the response goes through the send thread, which owns the writing side
+                    cmd_tx.send(respond_command(&request, rpc)).map_err(|_| StratumError::ChannelClosed)?;
+                } else if parse_line_dispatch(&l, &rcv, miner_id, pending)? == Dispatched::Relogin {
+                    info!("pool session expired, logging in again");
+                    cmd_tx.send(StratumCmd::Login{}).map_err(|_| StratumError::ChannelClosed)?;
                 }
-                parse_line_dispatch_result(&line, &rcv, miner_id);
             },
             Err(e) => {
-                //read_line fails (maybe connection lost, dispatch err to channel)
-                //=> Terminate loop
-                return Err(e);
+                dropped_lines += 1;
+                warn!("dropped line with invalid UTF-8 ({} lines dropped so far)", dropped_lines);
+                send_recorded_action(rcv, pending, StratumAction::Error{err: format!("line with invalid UTF-8 dropped: {}", e)});
             }
-        };
+        }
     }
 }
 
-fn is_known_ok(result: Result<stratum_data::OkResponse, serde_json::Error>) -> Option<StratumAction> {
+fn send_action(rcv: &Sender<StratumEvent>, action: StratumAction) {
+    let send_result = rcv.send(StratumEvent::new(action));
+    if send_result.is_err() {
+        info!("sending action to receiver failed (receiver probably already terminated)");
+    }
+}
+
+/// Maps an OK response to an action, by the request with the same id if it is pending
+/// and by the status otherwise.
+fn is_known_ok(result: Result<stratum_data::OkResponse, serde_json::Error>, pending: &Arc<Mutex<pending::PendingRequests>>) -> Option<StratumAction> {
     if result.is_ok() {
         let unwrapped = result.expect("result unwrap");
-        if unwrapped.result.status == "OK" && unwrapped.result.id.is_none() {
-            return Some(StratumAction::Ok);
-        } else if unwrapped.result.status == "KEEPALIVED" && unwrapped.result.id.is_none() {
-            return Some(StratumAction::KeepAliveOk);
+        if unwrapped.result.id.is_some() {
+            return None;
+        }
+        if unwrapped.result.status == "OK" || unwrapped.result.status == "KEEPALIVED" {
+            return match take_request(pending, Some(unwrapped.id)) {
+                Some(pending::Request::Submit{job_id}) => Some(StratumAction::ShareAccepted{job_id}),
+                Some(pending::Request::KeepAlive) => Some(StratumAction::KeepAliveOk),
+                _ if unwrapped.result.status == "KEEPALIVED" => Some(StratumAction::KeepAliveOk),
+                _ => Some(StratumAction::Ok)
+            };
         }
     }
     None
 }
 
+fn take_request(pending: &Arc<Mutex<pending::PendingRequests>>, id: Option<u64>) -> Option<pending::Request> {
+    id.and_then(|id| pending.lock().expect("pending requests lock").take(id))
+}
+
+/// Feeds share results and errors into the share stats and the flood guard of the connection.
+/// Received and cancelled jobs are recorded before the action is sent on, so that the send
+/// thread drops shares of a cancelled job even if they were queued before the cancellation.
+fn record_response(pending: &Arc<Mutex<pending::PendingRequests>>, action: &StratumAction) {
+    match *action {
+        StratumAction::Job{ref job_id, ..} | StratumAction::SelectTemplate{ref job_id, ..} => pending.lock().expect("pending requests lock").job_received(job_id),
+        StratumAction::JobCancelled{ref job_id} => {
+            pending.lock().expect("pending requests lock").cancel_job(job_id.as_ref().map(|id| id.as_str()));
+        },
+        StratumAction::ShareAccepted{..} => pending.lock().expect("pending requests lock").record_share_result(None),
+        StratumAction::ShareRejected{ref reason, ..} => pending.lock().expect("pending requests lock").record_share_result(Some(reason)),
+        StratumAction::Error{..} => pending.lock().expect("pending requests lock").record_error(),
+        _ => ()
+    }
+}
+
+/// Sends `action` and, if the flood guard just found the pool unhealthy, `PoolUnhealthy` after it
+fn send_recorded_action(rcv: &Sender<StratumEvent>, pending: &Arc<Mutex<pending::PendingRequests>>, action: StratumAction) {
+    record_response(pending, &action);
+    let unhealthy = pending.lock().expect("pending requests lock").take_unhealthy();
+    send_action(rcv, action);
+    if unhealthy {
+        send_action(rcv, StratumAction::PoolUnhealthy{reason: flood_guard::UNHEALTHY_REASON.to_string()});
+    }
+}
+
 //TODO Refactor this method (it is very ugly) - its probably better to use generic value parsing and not using struct for every case
-pub fn parse_line_dispatch_result(line: &str, rcv: &Sender<StratumAction>, miner_id_mutx: &Arc<Mutex<Option<String>>>) {
+/// Parses a line received from the pool and sends the resulting action to `rcv`.
+/// A rejected login (not OK status or error response to the login request) is also
+/// returned as error, since the connection is useless then.
+pub fn parse_line_dispatch_result(line: &str, rcv: &Sender<StratumEvent>, miner_id_mutx: &Arc<Mutex<Option<String>>>, pending: &Arc<Mutex<pending::PendingRequests>>) -> Result<(), StratumError> {
+    parse_line_dispatch(line, rcv, miner_id_mutx, pending).map(|_| ())
+}
+
+/// Like `parse_line_dispatch_result`, but also returns if a new login is needed. The miner id
+/// is cleared then, so that keep alives pause and further rejections of the old session
+/// do not request another login until the new login response arrived.
+pub fn parse_line_dispatch(line: &str, rcv: &Sender<StratumEvent>, miner_id_mutx: &Arc<Mutex<Option<String>>>, pending: &Arc<Mutex<pending::PendingRequests>>) -> Result<Dispatched, StratumError> {
 
     let action;
+    let mut login_rejected = None;
+    let mut dispatched = Dispatched::Action;
 
     let error : Result<stratum_data::ErrorResult, serde_json::Error> = serde_json::from_str(line);
     if error.is_ok() {
         match error.expect("error unwrap") {
-            stratum_data::ErrorResult{error: err_details} => {
-                action = StratumAction::Error{err: format!("error received: {} (code {}, raw json {})", err_details.message, err_details.code, line)}
+            stratum_data::ErrorResult{id, error} => {
+                let err_details = error.into_details();
+                action = match take_request(pending, id) {
+                    Some(pending::Request::Submit{job_id}) => {
+                        if is_unauthenticated(&err_details.message) && miner_id_mutx.lock().expect("miner_id lock").take().is_some() {
+                            dispatched = Dispatched::Relogin;
+                        }
+                        StratumAction::ShareRejected{job_id, reason: err_details.message, code: err_details.code}
+                    },
+                    Some(pending::Request::Login) => {
+                        login_rejected = Some(err_details.message.clone());
+                        StratumAction::LoginRejected{reason: err_details.message}
+                    },
+                    _ => StratumAction::Error{err: format!("error received: {} (code {}, raw json {})", err_details.message, err_details.code, line)}
+                }
             }
         }
     } else {
         let ok_result : Result<stratum_data::OkResponse, serde_json::Error> = serde_json::from_str(line);
-        let known_ok = is_known_ok(ok_result);
+        let known_ok = is_known_ok(ok_result, pending);
         if known_ok.is_some() {
             action = known_ok.expect("known_ok unwrap");
         } else {
@@ -310,6 +1114,8 @@ pub fn parse_line_dispatch_result(line: &str, rcv: &Sender<StratumAction>, miner
                     stratum_data::Method{method} => {
                         match method.as_ref() {
                             "job" => action = parse_job(line, miner_id_mutx),
+                            "set_extranonce" => action = parse_extranonce(line),
+                            "cancel_job" => action = parse_cancel_job(line),
                             _ => action = StratumAction::Error{err: format!("unknown method received: {}", method)}
                         }
                     }
@@ -318,14 +1124,21 @@ pub fn parse_line_dispatch_result(line: &str, rcv: &Sender<StratumAction>, miner
                 //try parsing intial job
                 let initial : Result<stratum_data::LoginResponse, serde_json::Error> = serde_json::from_str(line);
                 match initial {
-                    Ok(stratum_data::LoginResponse{result: stratum_data::LoginResult{status, job: stratum_data::Job{blob, job_id, target}, id: miner_id}, .. })
+                    Ok(stratum_data::LoginResponse{id, result: login_result})
                         => {
+                              take_request(pending, Some(id));
+                              if !login_result.supports_keepalive() {
+                                  warn!("pool does not list keepalive in its extensions {:?}, set keepalive_interval_secs = 0 if keep alives are rejected",
+                                        login_result.extensions);
+                              }
+                              let stratum_data::LoginResult{status, job, id: miner_id, ..} = login_result;
                               if status == "OK" {
-                                  action = StratumAction::Job{miner_id: miner_id.clone(), blob, job_id, target};
+                                  action = job_action(miner_id.clone(), job);
                                   let mut miner_id_guard = miner_id_mutx.lock().expect("miner_id lock");
                                   *miner_id_guard = Option::Some(miner_id.clone());
                               } else {
-                                  action = StratumAction::Error{err: format!("Not OK initial job received, status was {}", status)}
+                                  action = StratumAction::LoginRejected{reason: status.clone()};
+                                  login_rejected = Some(status);
                               }
                            },
                     Err(e) => action = StratumAction::Error{err: format!("{:?}, json received {}", e, line)}
@@ -334,9 +1147,52 @@ pub fn parse_line_dispatch_result(line: &str, rcv: &Sender<StratumAction>, miner
         }
     }
 
-    let send_result = rcv.send(action.clone());
-    if send_result.is_err() {
-        info!("sending action to receiver failed (receiver probably already terminated), trying next receiver");
+    send_recorded_action(rcv, pending, action);
+    match login_rejected {
+        Some(status) => Err(StratumError::LoginRejected(status)),
+        None => Ok(dispatched)
+    }
+}
+
+fn job_action(miner_id: String, mut job: stratum_data::Job) -> StratumAction {
+    //32 and 64 bit targets are both accepted, the numeric target is the same for the workers
+    job.target = job.target.to_lowercase();
+    let target_value = match difficulty::parse_target(&job.target).and_then(difficulty::check_target) {
+        Ok(target) => target,
+        Err(e) => return StratumAction::Error{err: format!("job {} ignored, invalid target: {}", job.job_id, e)}
+    };
+    if difficulty::is_too_easy(target_value) {
+        warn!("target {} of job {} is easier than difficulty {}, the pool may be misconfigured (see clamp_easy_targets)",
+            job.target, job.job_id, difficulty::MIN_JOB_DIFFICULTY);
+    }
+    if let (Some(extra_nonce), Some(pool_wallet)) = (job.extra_nonce.clone(), job.pool_wallet.clone()) {
+        return StratumAction::SelectTemplate{miner_id, job_id: job.job_id, target: job.target, extra_nonce, pool_wallet};
+    }
+    let algo = job.algo_name();
+    StratumAction::Job{miner_id, blob: job.blob, job_id: job.job_id, target: job.target,
+                       difficulty: difficulty::target_difficulty(target_value), algo,
+                       height: job.height, seed_hash: job.seed_hash, next_seed_hash: job.next_seed_hash}
+}
+
+fn parse_extranonce(line: &str) -> StratumAction {
+    let result : Result<stratum_data::ExtranonceNotification, serde_json::Error> = serde_json::from_str(line);
+    match result {
+        Ok(notification) => match hex::decode(&notification.params.extra_nonce) {
+            Ok(ref prefix) if prefix.len() > MAX_NONCE_PREFIX_BYTES => StratumAction::Error{
+                err: format!("extranonce {} reserves more than {} nonce bytes", notification.params.extra_nonce, MAX_NONCE_PREFIX_BYTES)
+            },
+            Ok(nonce_prefix) => StratumAction::ExtranonceSet{nonce_prefix},
+            Err(e) => StratumAction::Error{err: format!("extranonce is not hex: {:?}", e)}
+        },
+        Err(e) => StratumAction::Error{err: format!("Error parsing extranonce: {}", e)}
+    }
+}
+
+fn parse_cancel_job(line: &str) -> StratumAction {
+    let result : Result<stratum_data::CancelJobNotification, serde_json::Error> = serde_json::from_str(line);
+    match result {
+        Ok(notification) => StratumAction::JobCancelled{job_id: notification.params.job_id},
+        Err(e) => StratumAction::Error{err: format!("Error parsing job cancellation: {}", e)}
     }
 }
 
@@ -350,9 +1206,7 @@ fn parse_job(line: &str, miner_id_mutx: &Arc<Mutex<Option<String>>>) -> StratumA
     let miner_id = miner_id_guard.clone().expect("miner_id clone");
 
     match result {
-        Ok(stratum_data::JobResponse{params: stratum_data::Job{blob, job_id, target}}) => {
-            StratumAction::Job{miner_id, blob, job_id, target}
-        },
+        Ok(stratum_data::JobResponse{params: job}) => job_action(miner_id, job),
         _ => StratumAction::Error{err: "Error parsing job response".to_string()}
     }
 }
@@ -0,0 +1,701 @@
+use std::collections::{VecDeque};
+use std::sync::mpsc::{channel, Receiver, Sender, SendError};
+use std::sync::{Arc};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use error::{MithrilError};
+use super::{StratumClient, StratumCmd, StratumAction, StratumError, StratumEvent, Tick, start_tick_thread, join_thread};
+use super::stratum_data::{PoolConfig, Share};
+use super::share_queue::{ShareQueue, SubmitError};
+#[cfg(feature = "async-stratum")]
+use super::async_client::{AsyncStratumClient};
+
+/// Index of the connection to `pool_address`
+pub const PRIMARY : ConnectionId = 0;
+/// Index of the connection to `secondary_pool_address`
+pub const SECONDARY : ConnectionId = 1;
+
+/// Length of the time slices the split policy hands to one of the pools
+pub const SPLIT_SLICE_SECS : u64 = 30;
+
+/// Seconds before a failed connection is established again, while the other pool is used
+pub const RECONNECT_DELAY_SECS : u64 = 60;
+
+/// Number of recent jobs remembered per connection for routing shares
+const JOBS_KEPT : usize = 16;
+
+/// Which of the two pool connections an action, share or command belongs to
+pub type ConnectionId = usize;
+
+/// How the work of two connected pools is given to the workers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArbitrationPolicy {
+    /// the jobs of the primary pool are hashed, the secondary pool stays logged in and
+    /// takes over without a reconnect while the primary has no job (e.g. disconnected)
+    WarmStandby,
+    /// the primary pool gets `primary_percent` of the hashing time, the secondary the rest
+    Split{primary_percent: u8},
+    /// the jobs of both pools are handed out at the same time, tagged with their connection,
+    /// each worker group hashes the jobs of its pool
+    Routed
+}
+
+impl ArbitrationPolicy {
+    pub fn from_name(name: &str, primary_percent: u64) -> Option<ArbitrationPolicy> {
+        match name {
+            "standby" => Some(ArbitrationPolicy::WarmStandby),
+            "split" if primary_percent <= 100 => Some(ArbitrationPolicy::Split{primary_percent: primary_percent as u8}),
+            "routed" => Some(ArbitrationPolicy::Routed),
+            _ => None
+        }
+    }
+}
+
+/// Work of one connection as far as the arbiter knows it
+#[derive(Debug, Clone, Default)]
+struct ConnectionState {
+    miner_id: Option<String>,
+    /// the current job, None before the first job, after a cancellation and while disconnected
+    job: Option<StratumAction>,
+    nonce_prefix: Vec<u8>,
+    /// (miner_id, job_id) of the recent jobs, the oldest first
+    jobs: VecDeque<(String, String)>,
+}
+
+/// Decides whose job the workers hash and which connection a share or a job related
+/// command belongs to. The arbiter holds no connection, `MultiPoolClient` applies the
+/// returned actions.
+#[derive(Debug)]
+pub struct Arbiter {
+    policy: ArbitrationPolicy,
+    connections: [ConnectionState; 2],
+    active: Option<ConnectionId>,
+    /// nonce prefix of the work the workers got last
+    worker_prefix: Vec<u8>,
+    /// time slices each connection got under the split policy
+    slices: [u64; 2],
+}
+
+impl Arbiter {
+    pub fn new(policy: ArbitrationPolicy) -> Arbiter {
+        Arbiter{policy, connections: [ConnectionState::default(), ConnectionState::default()],
+            active: None, worker_prefix: Vec::new(), slices: [0, 0]}
+    }
+
+    /// The connection whose job the workers hash, None if no connection has a job
+    /// (always None under the routed policy, the workers hash the jobs of both)
+    pub fn active(&self) -> Option<ConnectionId> {
+        self.active
+    }
+
+    /// The connection the actions returned for an action of `conn` are tagged with
+    pub fn source(&self, conn: ConnectionId) -> Option<ConnectionId> {
+        if self.routed() { Some(conn) } else { self.active }
+    }
+
+    fn routed(&self) -> bool {
+        self.policy == ArbitrationPolicy::Routed
+    }
+
+    /// Handles an action received on `conn`, returns the actions for the workers
+    pub fn record_action(&mut self, conn: ConnectionId, action: StratumAction) -> Vec<StratumAction> {
+        match action {
+            StratumAction::Job{ref miner_id, ref job_id, ..} => {
+                {
+                    let state = &mut self.connections[conn];
+                    state.miner_id = Some(miner_id.clone());
+                    state.jobs.push_back((miner_id.clone(), job_id.clone()));
+                    if state.jobs.len() > JOBS_KEPT {
+                        state.jobs.pop_front();
+                    }
+                    state.job = Some(action.clone());
+                }
+                if self.active == Some(conn) || self.routed() {
+                    vec![action.clone()]
+                } else {
+                    self.arbitrate()
+                }
+            },
+            StratumAction::ExtranonceSet{ref nonce_prefix} => {
+                self.connections[conn].nonce_prefix = nonce_prefix.clone();
+                if self.routed() {
+                    vec![action.clone()]
+                } else if self.active == Some(conn) {
+                    self.worker_prefix = nonce_prefix.clone();
+                    vec![action.clone()]
+                } else {
+                    Vec::new()
+                }
+            },
+            StratumAction::JobCancelled{ref job_id} => {
+                let cancelled = match self.connections[conn].job {
+                    Some(StratumAction::Job{job_id: ref current, ..}) => job_id.as_ref().map_or(true, |id| id == current),
+                    _ => false
+                };
+                if cancelled {
+                    self.connections[conn].job = None;
+                }
+                if self.routed() {
+                    return vec![action.clone()];
+                }
+                if self.active != Some(conn) {
+                    return Vec::new();
+                }
+                let mut actions = vec![action.clone()];
+                let switched = self.arbitrate();
+                //without any work left the cancellation is all the workers need
+                if self.active.is_some() {
+                    actions.extend(switched);
+                }
+                actions
+            },
+            StratumAction::SelectTemplate{..} => {
+                if self.active == Some(conn) || self.routed() { vec![action] } else { Vec::new() }
+            },
+            _ => vec![action]
+        }
+    }
+
+    /// The connection ended, the workers switch to the other pool if it has a job
+    pub fn record_disconnect(&mut self, conn: ConnectionId) -> Vec<StratumAction> {
+        {
+            //the recent jobs are kept, shares of them must not go to the other pool
+            let state = &mut self.connections[conn];
+            state.miner_id = None;
+            state.job = None;
+            state.nonce_prefix.clear();
+        }
+        if self.routed() {
+            //only the workers of this pool stop
+            return vec![StratumAction::JobCancelled{job_id: None}];
+        }
+        self.arbitrate()
+    }
+
+    /// Starts the next time slice of the split policy, returns the actions for the
+    /// workers if the pool changes
+    pub fn next_slice(&mut self) -> Vec<StratumAction> {
+        let primary_percent = match self.policy {
+            ArbitrationPolicy::Split{primary_percent} => u64::from(primary_percent),
+            ArbitrationPolicy::WarmStandby | ArbitrationPolicy::Routed => return Vec::new()
+        };
+        let total = self.slices[PRIMARY] + self.slices[SECONDARY] + 1;
+        let preferred = if self.slices[PRIMARY] * 100 < primary_percent * total { PRIMARY } else { SECONDARY };
+        let next = self.with_job(preferred);
+        if let Some(conn) = next {
+            self.slices[conn] += 1;
+        }
+        self.switch_to(next)
+    }
+
+    /// The connection `job_id` (of `miner_id`, if known) was received on. Job ids are
+    /// only unique per pool, the active connection is checked first.
+    pub fn route(&self, miner_id: Option<&str>, job_id: &str) -> Option<ConnectionId> {
+        let first = self.active.unwrap_or(PRIMARY);
+        [first, other(first)].iter().cloned().find(|&conn| {
+            self.connections[conn].jobs.iter().any(|&(ref job_miner_id, ref id)| {
+                id == job_id && miner_id.map_or(true, |m| m == job_miner_id)
+            })
+        })
+    }
+
+    /// The connection that logged in with `miner_id`
+    pub fn route_miner_id(&self, miner_id: &str) -> Option<ConnectionId> {
+        [PRIMARY, SECONDARY].iter().cloned().find(|&conn| self.connections[conn].miner_id.as_ref().map(|m| m.as_str()) == Some(miner_id))
+    }
+
+    fn arbitrate(&mut self) -> Vec<StratumAction> {
+        let preferred = match self.policy {
+            ArbitrationPolicy::WarmStandby | ArbitrationPolicy::Routed => PRIMARY,
+            //the pool of the current time slice is kept until the slice ends
+            ArbitrationPolicy::Split{..} => self.active.unwrap_or(PRIMARY)
+        };
+        let next = self.with_job(preferred);
+        self.switch_to(next)
+    }
+
+    /// `preferred` if it has a job, otherwise the other connection if it has one
+    fn with_job(&self, preferred: ConnectionId) -> Option<ConnectionId> {
+        [preferred, other(preferred)].iter().cloned().find(|&conn| self.connections[conn].job.is_some())
+    }
+
+    fn switch_to(&mut self, next: Option<ConnectionId>) -> Vec<StratumAction> {
+        if next == self.active {
+            return Vec::new();
+        }
+        self.active = next;
+        let conn = match next {
+            Some(conn) => conn,
+            None => {
+                warn!("no pool connection has a job, workers are stopped");
+                return vec![StratumAction::JobCancelled{job_id: None}];
+            }
+        };
+        info!("workers switch to the job of the {} pool", connection_name(conn));
+        let state = &self.connections[conn];
+        let mut actions = Vec::new();
+        if state.nonce_prefix != self.worker_prefix {
+            self.worker_prefix = state.nonce_prefix.clone();
+            actions.push(StratumAction::ExtranonceSet{nonce_prefix: state.nonce_prefix.clone()});
+        }
+        actions.extend(state.job.clone());
+        actions
+    }
+}
+
+fn other(conn: ConnectionId) -> ConnectionId {
+    if conn == PRIMARY { SECONDARY } else { PRIMARY }
+}
+
+pub fn connection_name(conn: ConnectionId) -> &'static str {
+    if conn == PRIMARY { "primary" } else { "secondary" }
+}
+
+enum Input {
+    Action(ConnectionId, u64, StratumEvent),
+    Failed(ConnectionId, u64, StratumError),
+    Connected(ConnectionId, u64, Result<Connection, String>),
+    Cmd(StratumCmd),
+    SliceTick
+}
+
+/// The client of one pool, connections the async client supports are tasks on the
+/// shared runtime of `AsyncStratumClient`
+enum Client {
+    Stratum(StratumClient),
+    #[cfg(feature = "async-stratum")]
+    AsyncStratum(AsyncStratumClient),
+}
+
+impl Client {
+    #[cfg(feature = "async-stratum")]
+    fn login(conf: PoolConfig, err_tx: Sender<StratumError>, action_tx: Sender<StratumEvent>) -> Result<Client, MithrilError> {
+        if AsyncStratumClient::supports(&conf) {
+            AsyncStratumClient::login(conf, err_tx, action_tx).map(Client::AsyncStratum)
+        } else {
+            StratumClient::login(conf, err_tx, action_tx).map(Client::Stratum)
+        }
+    }
+
+    #[cfg(not(feature = "async-stratum"))]
+    fn login(conf: PoolConfig, err_tx: Sender<StratumError>, action_tx: Sender<StratumEvent>) -> Result<Client, MithrilError> {
+        StratumClient::login(conf, err_tx, action_tx).map(Client::Stratum)
+    }
+
+    fn new_cmd_channel(&self) -> Sender<StratumCmd> {
+        match *self {
+            Client::Stratum(ref client) => client.new_cmd_channel(),
+            #[cfg(feature = "async-stratum")]
+            Client::AsyncStratum(ref client) => client.new_cmd_channel()
+        }
+    }
+
+    fn share_queue(&self) -> Arc<ShareQueue> {
+        match *self {
+            Client::Stratum(ref client) => client.share_queue(),
+            #[cfg(feature = "async-stratum")]
+            Client::AsyncStratum(ref client) => client.share_queue()
+        }
+    }
+
+    fn stop(self) {
+        match self {
+            Client::Stratum(client) => client.stop(),
+            #[cfg(feature = "async-stratum")]
+            Client::AsyncStratum(client) => client.stop()
+        }
+    }
+}
+
+/// A logged in pool connection and the threads tagging its actions and errors
+struct Connection {
+    client: Client,
+    commands: Sender<StratumCmd>,
+    forwarders: Vec<thread::JoinHandle<()>>,
+}
+
+impl Connection {
+    fn stop(self) {
+        self.client.stop();
+        for forwarder in self.forwarders {
+            join_thread("forward", forwarder);
+        }
+    }
+}
+
+struct Slot {
+    conf: PoolConfig,
+    /// incremented whenever the connection ends, actions of ended connections are ignored
+    generation: u64,
+    connection: Option<Connection>,
+}
+
+/// Keeps two pools connected at once (`pool_address` and `secondary_pool_address`). The
+/// workers get the jobs of one of them, chosen by the `pool_arbitration` policy, and
+/// every share is sent to the pool its job came from. To the workers and the main loop
+/// it looks like a single `StratumClient`: actions are sent to `action_rcv`, the error
+/// is only reported if both connections failed. A single failed connection is logged in
+/// again after `RECONNECT_DELAY_SECS`.
+pub struct MultiPoolClient {
+    command_sender: Sender<StratumCmd>,
+    share_queue: Arc<ShareQueue>,
+    cmd_thread: thread::JoinHandle<()>,
+    router_thread: thread::JoinHandle<()>,
+}
+
+impl MultiPoolClient {
+    pub fn login(pool_conf: PoolConfig, err_receiver: Sender<StratumError>, action_rcv: Sender<StratumEvent>) -> Result<MultiPoolClient, MithrilError> {
+        let secondary_address = match pool_conf.secondary_pool_address.clone() {
+            Some(address) => address,
+            None => return Err(MithrilError::Stratum(StratumError::Protocol("no secondary_pool_address configured".to_string())))
+        };
+        let mut secondary_conf = pool_conf.clone();
+        secondary_conf.pool_address = secondary_address;
+        secondary_conf.failover_pool_addresses = Vec::new();
+
+        let (input_tx, input_rx) = channel();
+        let mut slots = [
+            Slot{conf: pool_conf.clone(), generation: 0, connection: None},
+            Slot{conf: secondary_conf, generation: 0, connection: None}
+        ];
+        let mut login_err = None;
+        for conn in 0..slots.len() {
+            match connect(conn, 0, slots[conn].conf.clone(), &input_tx) {
+                Ok(connection) => slots[conn].connection = Some(connection),
+                Err(e) => {
+                    warn!("login to the {} pool {} failed: {}", connection_name(conn), slots[conn].conf.pool_address, e);
+                    login_err = login_err.or(Some(e));
+                }
+            }
+        }
+        if slots.iter().all(|slot| slot.connection.is_none()) {
+            return Err(MithrilError::Stratum(StratumError::Protocol(login_err.unwrap_or_default())));
+        }
+
+        let (command_sender, command_rcv) = channel();
+        let share_queue = Arc::new(ShareQueue::new(pool_conf.share_queue_capacity as usize, pool_conf.share_queue_policy));
+
+        let cmd_input = input_tx.clone();
+        let cmd_thread = thread::Builder::new().name("multi pool command thread".to_string()).spawn(move || {
+            for cmd in command_rcv {
+                let shutdown = match cmd {
+                    StratumCmd::Shutdown{} => true,
+                    _ => false
+                };
+                if cmd_input.send(Input::Cmd(cmd)).is_err() || shutdown {
+                    break;
+                }
+            }
+            info!("multi pool command thread ended");
+        }).map_err(StratumError::Io)?;
+
+        let router = Router{
+            slots,
+            arbiter: Arbiter::new(pool_conf.pool_arbitration),
+            input_tx,
+            action_rcv,
+            err_receiver,
+            share_queue: share_queue.clone(),
+            next_seq: 0,
+            stopping: Arc::new(AtomicBool::new(false)),
+        };
+        let router_thread = thread::Builder::new().name("multi pool router thread".to_string()).spawn(move || {
+            router.run(input_rx);
+            info!("multi pool router thread ended");
+        }).map_err(StratumError::Io)?;
+
+        Ok(MultiPoolClient{command_sender, share_queue, cmd_thread, router_thread})
+    }
+
+    /// Returns a new channel for sending commands, shares are routed to the pool of their job
+    pub fn new_cmd_channel(self: &Self) -> Sender<StratumCmd> {
+        self.command_sender.clone()
+    }
+
+    /// The queue workers submit their shares with
+    pub fn share_queue(self: &Self) -> Arc<ShareQueue> {
+        self.share_queue.clone()
+    }
+
+    /// Stops both pool connections and waits for all threads
+    pub fn stop(self: Self) {
+        info!("stopping multi pool client");
+        self.share_queue.close();
+        if self.command_sender.send(StratumCmd::Shutdown{}).is_err() {
+            info!("multi pool command thread already ended");
+        }
+        join_thread("multi pool command", self.cmd_thread);
+        join_thread("multi pool router", self.router_thread);
+        info!("multi pool client stopped");
+    }
+}
+
+struct Router {
+    slots: [Slot; 2],
+    arbiter: Arbiter,
+    input_tx: Sender<Input>,
+    action_rcv: Sender<StratumEvent>,
+    err_receiver: Sender<StratumError>,
+    share_queue: Arc<ShareQueue>,
+    /// the actions of both connections are numbered anew, so the receiver sees no gaps
+    next_seq: u64,
+    /// set when the router ends, pending reconnects are abandoned
+    stopping: Arc<AtomicBool>,
+}
+
+impl Router {
+    fn run(mut self, input_rx: Receiver<Input>) {
+        let slice_ticks = match self.arbiter.policy {
+            ArbitrationPolicy::Split{..} => Some(self.start_slice_ticks()),
+            ArbitrationPolicy::WarmStandby | ArbitrationPolicy::Routed => None
+        };
+        for conn in 0..self.slots.len() {
+            if self.slots[conn].connection.is_none() {
+                self.reconnect_later(conn);
+            }
+        }
+
+        for input in input_rx.iter() {
+            match input {
+                Input::Action(conn, generation, event) => {
+                    if generation != self.slots[conn].generation {
+                        continue;
+                    }
+                    let received = event.received;
+                    let actions = self.arbiter.record_action(conn, event.action);
+                    let source = self.arbiter.source(conn);
+                    for action in actions {
+                        self.dispatch(received, source, action);
+                    }
+                },
+                Input::Failed(conn, generation, err) => {
+                    if generation != self.slots[conn].generation {
+                        continue;
+                    }
+                    self.connection_failed(conn, err);
+                },
+                Input::Connected(conn, generation, result) => {
+                    if generation != self.slots[conn].generation {
+                        if let Ok(connection) = result {
+                            connection.stop();
+                        }
+                        continue;
+                    }
+                    match result {
+                        Ok(connection) => {
+                            info!("{} pool {} connected again", connection_name(conn), self.slots[conn].conf.pool_address);
+                            self.slots[conn].connection = Some(connection);
+                        },
+                        Err(e) => {
+                            warn!("reconnecting to the {} pool failed: {}", connection_name(conn), e);
+                            self.reconnect_later(conn);
+                        }
+                    }
+                },
+                Input::Cmd(StratumCmd::Shutdown{}) => break,
+                Input::Cmd(cmd) => self.route_command(cmd),
+                Input::SliceTick => {
+                    let now = Instant::now();
+                    let actions = self.arbiter.next_slice();
+                    let source = self.arbiter.active();
+                    for action in actions {
+                        self.dispatch(now, source, action);
+                    }
+                }
+            }
+        }
+
+        self.stopping.store(true, Ordering::SeqCst);
+        self.share_queue.close();
+        for slot in self.slots.iter_mut() {
+            slot.generation += 1;
+            if let Some(connection) = slot.connection.take() {
+                connection.stop();
+            }
+        }
+        if let Some((stop_tx, tick_thread, forwarder)) = slice_ticks {
+            drop(stop_tx);
+            join_thread("tick", tick_thread);
+            join_thread("forward", forwarder);
+        }
+    }
+
+    fn dispatch(&mut self, received: Instant, connection: Option<ConnectionId>, action: StratumAction) {
+        let event = StratumEvent{seq: self.next_seq, received, connection, action};
+        self.next_seq += 1;
+        if self.action_rcv.send(event).is_err() {
+            info!("action receiver already terminated");
+        }
+    }
+
+    fn connection_failed(&mut self, conn: ConnectionId, err: StratumError) {
+        warn!("connection to the {} pool {} failed: {}", connection_name(conn), self.slots[conn].conf.pool_address, err);
+        self.slots[conn].generation += 1;
+        if let Some(connection) = self.slots[conn].connection.take() {
+            connection.stop();
+        }
+        let now = Instant::now();
+        let actions = self.arbiter.record_disconnect(conn);
+        let source = self.arbiter.source(conn);
+        for action in actions {
+            self.dispatch(now, source, action);
+        }
+        if self.slots.iter().all(|slot| slot.connection.is_none()) {
+            if self.err_receiver.send(err).is_err() {
+                info!("error receiver already terminated");
+            }
+        } else {
+            self.reconnect_later(conn);
+        }
+    }
+
+    /// Logs in to the pool of `conn` again after `RECONNECT_DELAY_SECS`, on a separate
+    /// thread as connecting blocks for up to the connect timeout
+    fn reconnect_later(&self, conn: ConnectionId) {
+        let generation = self.slots[conn].generation;
+        let conf = self.slots[conn].conf.clone();
+        let input_tx = self.input_tx.clone();
+        let stopping = self.stopping.clone();
+        let spawned = thread::Builder::new().name("multi pool reconnect thread".to_string()).spawn(move || {
+            thread::sleep(Duration::from_secs(RECONNECT_DELAY_SECS));
+            if stopping.load(Ordering::SeqCst) {
+                return;
+            }
+            let result = connect(conn, generation, conf, &input_tx);
+            if let Err(SendError(Input::Connected(_, _, Ok(connection)))) = input_tx.send(Input::Connected(conn, generation, result)) {
+                connection.stop();
+            }
+        });
+        if let Err(e) = spawned {
+            error!("reconnect of the {} pool not started: {}", connection_name(conn), e);
+        }
+    }
+
+    fn route_command(&mut self, cmd: StratumCmd) {
+        match cmd {
+            StratumCmd::SubmitShare{share} => {
+                self.share_queue.release();
+                self.submit(share);
+            },
+            StratumCmd::CancelJob{job_id} => {
+                let conn = self.arbiter.route(None, &job_id);
+                self.send(conn, StratumCmd::CancelJob{job_id});
+            },
+            StratumCmd::JobReceived{job_id, target} => {
+                let conn = self.arbiter.route(None, &job_id);
+                self.send(conn, StratumCmd::JobReceived{job_id, target});
+            },
+            StratumCmd::KeepAlive{miner_id} => {
+                let conn = self.arbiter.route_miner_id(&miner_id);
+                self.send(conn, StratumCmd::KeepAlive{miner_id});
+            },
+            StratumCmd::BlockTemplate{params} => {
+                let conn = self.arbiter.active();
+                self.send(conn, StratumCmd::BlockTemplate{params});
+            },
+            StratumCmd::SetDryRun{enabled} => {
+                for conn in 0..self.slots.len() {
+                    self.send(Some(conn), StratumCmd::SetDryRun{enabled});
+                }
+            },
+            StratumCmd::Login{} => {
+                for conn in 0..self.slots.len() {
+                    self.send(Some(conn), StratumCmd::Login{});
+                }
+            },
+            StratumCmd::Respond{id, ..} => {
+                //requests are answered by the connection that received them
+                debug!("response to pool request {} not routed", id);
+            },
+            StratumCmd::Shutdown{} => {}
+        }
+    }
+
+    fn submit(&self, share: Share) {
+        let conn = match self.arbiter.route(Some(&share.miner_id), &share.job_id) {
+            Some(conn) => conn,
+            None => {
+                warn!("share for unknown job {} dropped", share.job_id);
+                return;
+            }
+        };
+        let connection = match self.slots[conn].connection {
+            Some(ref connection) => connection,
+            None => {
+                warn!("share for job {} dropped, the {} pool is not connected", share.job_id, connection_name(conn));
+                return;
+            }
+        };
+        match connection.client.share_queue().submit(&connection.commands, share) {
+            Ok(()) => {},
+            Err(SubmitError::Full) => warn!("share queue of the {} pool full, share dropped", connection_name(conn)),
+            Err(SubmitError::Disconnected) => warn!("share dropped, the {} pool connection ended", connection_name(conn)),
+            Err(SubmitError::Kept) => {} //logged by the offline buffer
+        }
+    }
+
+    fn send(&self, conn: Option<ConnectionId>, cmd: StratumCmd) {
+        let connection = match conn.and_then(|c| self.slots[c].connection.as_ref()) {
+            Some(connection) => connection,
+            None => {
+                debug!("command {:?} dropped, its pool is not connected", cmd);
+                return;
+            }
+        };
+        if connection.commands.send(cmd).is_err() {
+            info!("stratum send thread already ended");
+        }
+    }
+
+    fn start_slice_ticks(&self) -> (Sender<()>, thread::JoinHandle<()>, thread::JoinHandle<()>) {
+        let (stop_tx, stop_rx) = channel();
+        let (tick_rcv, tick_thread) = start_tick_thread(Duration::from_secs(SPLIT_SLICE_SECS), Duration::from_secs(0), stop_rx);
+        let input_tx = self.input_tx.clone();
+        let forwarder = thread::Builder::new().name("split slice thread".to_string()).spawn(move || {
+            while let Ok(Tick::Tick) = tick_rcv.recv() {
+                if input_tx.send(Input::SliceTick).is_err() {
+                    break;
+                }
+            }
+        }).expect("split slice thread handle");
+        (stop_tx, tick_thread, forwarder)
+    }
+}
+
+/// Logs in to the pool, the actions and errors of the connection are tagged with
+/// `conn` and `generation` and sent to `input_tx`
+fn connect(conn: ConnectionId, generation: u64, conf: PoolConfig, input_tx: &Sender<Input>) -> Result<Connection, String> {
+    let (action_tx, action_rx) = channel();
+    let (err_tx, err_rx) = channel();
+    let action_input = input_tx.clone();
+    let action_forwarder = thread::Builder::new().name("multi pool action thread".to_string()).spawn(move || {
+        for event in action_rx {
+            if action_input.send(Input::Action(conn, generation, event)).is_err() {
+                break;
+            }
+        }
+    }).map_err(|e| e.to_string())?;
+    let err_input = input_tx.clone();
+    let err_forwarder = thread::Builder::new().name("multi pool error thread".to_string()).spawn(move || {
+        for err in err_rx {
+            if err_input.send(Input::Failed(conn, generation, err)).is_err() {
+                break;
+            }
+        }
+    }).map_err(|e| e.to_string())?;
+
+    info!("logging in to the {} pool {}", connection_name(conn), conf.pool_address);
+    match Client::login(conf, err_tx, action_tx) {
+        Ok(client) => {
+            let commands = client.new_cmd_channel();
+            Ok(Connection{client, commands, forwarders: vec![action_forwarder, err_forwarder]})
+        },
+        Err(e) => {
+            //the senders were dropped with the failed client
+            join_thread("forward", action_forwarder);
+            join_thread("forward", err_forwarder);
+            Err(e.to_string())
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use super::stratum_data::{Share};
+
+/// Shares found while (or just before) the pool connection ended. They are kept
+/// until the next login and sent to the pool again if they are not older than
+/// `max_age` and their job is still valid: the pool is the same, it resumed the
+/// session (same miner id) and sent the job of the shares again.
+pub struct OfflineShares {
+    capacity: usize,
+    max_age: Duration,
+    state: Mutex<OfflineState>,
+}
+
+struct OfflineState {
+    shares: VecDeque<KeptShare>,
+    /// shares pushed out because the buffer was full
+    dropped: u64,
+}
+
+struct KeptShare {
+    share: Share,
+    pool_address: String,
+    /// the nonce was found (or the share was kept, if unknown)
+    found: Instant,
+}
+
+/// The kept shares taken for a new connection
+#[derive(Debug)]
+pub struct Salvage {
+    /// shares that are still fresh, oldest first
+    pub shares: Vec<Share>,
+    /// shares that were too old or found for another pool, session or job
+    pub discarded: usize,
+}
+
+impl OfflineShares {
+    /// A capacity of 0 keeps no shares
+    pub fn new(capacity: usize, max_age: Duration) -> OfflineShares {
+        OfflineShares{capacity, max_age, state: Mutex::new(OfflineState{shares: VecDeque::new(), dropped: 0})}
+    }
+
+    /// Keeps the share of the connection to `pool_address`, the oldest share is dropped if the buffer is full
+    pub fn keep(&self, share: Share, pool_address: &str) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut state = self.state.lock().expect("offline shares lock");
+        if state.shares.len() >= self.capacity {
+            state.shares.pop_front();
+            state.dropped += 1;
+            warn!("offline share buffer full, oldest share dropped ({} dropped so far)", state.dropped);
+        }
+        let found = share.times.map(|t| t.found).unwrap_or_else(Instant::now);
+        info!("pool connection ended, share for job {} kept for the next connection", share.job_id);
+        state.shares.push_back(KeptShare{share, pool_address: pool_address.to_string(), found});
+    }
+
+    /// Takes all kept shares, the ones for `pool_address`, `miner_id` and `job_id` found at most
+    /// `max_age` before `now` are returned. Shares of other sessions or jobs would be rejected.
+    pub fn take(&self, pool_address: &str, miner_id: &str, job_id: &str, now: Instant) -> Salvage {
+        let mut state = self.state.lock().expect("offline shares lock");
+        let mut shares = Vec::new();
+        let mut discarded = 0;
+        for kept in state.shares.drain(..) {
+            let age = if now > kept.found { now - kept.found } else { Duration::from_secs(0) };
+            let valid_job = kept.share.miner_id == miner_id && kept.share.job_id == job_id;
+            if kept.pool_address == pool_address && valid_job && age <= self.max_age {
+                shares.push(kept.share);
+            } else {
+                discarded += 1;
+            }
+        }
+        Salvage{shares, discarded}
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().expect("offline shares lock").shares.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.state.lock().expect("offline shares lock").dropped
+    }
+}
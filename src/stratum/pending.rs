@@ -0,0 +1,220 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::time::Instant;
+use super::latency::{LatencyWindow, LatencyStats, ShareTimes, StageWindows, StageLatency};
+use super::share_stats::{ShareStats};
+use super::flood_guard::{Decision, FloodGuard, FloodGuardConfig};
+use super::pool_score::{PoolScore, ScoreStats, SCORE_MIN_SHARES};
+use super::{RejectKind, CANCELLED_JOBS_KEPT};
+
+/// Requests without a response are forgotten (oldest first) above this number,
+/// so a pool that never answers e.g. keep alives does not grow the map unbounded.
+const MAX_PENDING : usize = 1024;
+
+/// A request sent to the pool that is waiting for its response
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    Login,
+    Submit{job_id: String},
+    KeepAlive,
+    BlockTemplate{job_id: String},
+}
+
+/// Assigns monotonically increasing JSON-RPC ids to requests and remembers the
+/// request for every id, so that a response can be correlated with its request.
+/// The round trip times of submit and keep alive requests are measured on the way,
+/// as well as the share counts of the connection and the stage latency of shares.
+/// The share results and errors also feed the flood guard and the score of the connection.
+/// Jobs cancelled by the pool are recorded here by the receiving side, so that shares
+/// for them still waiting to be sent are dropped.
+#[derive(Debug)]
+pub struct PendingRequests {
+    next_id: u64,
+    pending: BTreeMap<u64, (Request, Instant)>,
+    latency: LatencyWindow,
+    shares: ShareStats,
+    /// stage times of the pending submits that carry them
+    share_times: BTreeMap<u64, ShareTimes>,
+    stages: StageWindows,
+    flood_guard: FloodGuard,
+    score: PoolScore,
+    /// the score was reported below `min_pool_score` on this connection
+    score_reported: bool,
+    /// the most recently received job, cancelled by a `cancel_job` without job id
+    current_job: Option<String>,
+    /// the most recently cancelled jobs, oldest first
+    cancelled_jobs: VecDeque<String>,
+}
+
+impl PendingRequests {
+    /// Without flood protection, see `with_flood_guard`
+    pub fn new() -> PendingRequests {
+        PendingRequests::with_flood_guard(FloodGuardConfig::disabled())
+    }
+
+    pub fn with_flood_guard(config: FloodGuardConfig) -> PendingRequests {
+        PendingRequests{next_id: 1, pending: BTreeMap::new(), latency: LatencyWindow::new(), shares: ShareStats::default(),
+            share_times: BTreeMap::new(), stages: StageWindows::new(), flood_guard: FloodGuard::new(config),
+            score: PoolScore::new(), score_reported: false,
+            current_job: None, cancelled_jobs: VecDeque::with_capacity(CANCELLED_JOBS_KEPT)}
+    }
+
+    /// Returns the id to be used for sending `request`
+    pub fn register(&mut self, request: Request) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        if let Request::Submit{..} = request {
+            self.shares.record_submitted();
+        }
+        self.pending.insert(id, (request, Instant::now()));
+        if self.pending.len() > MAX_PENDING {
+            let oldest = *self.pending.keys().next().expect("oldest pending id");
+            let forgotten = self.pending.remove(&oldest).map(|(request, _)| request);
+            self.share_times.remove(&oldest);
+            warn!("no response received for request {} ({:?}), forgetting it", oldest, forgotten);
+        }
+        id
+    }
+
+    /// Registers the submit of a share, `times` are the stages the share passed in mithril
+    pub fn register_share(&mut self, job_id: String, times: Option<ShareTimes>) -> u64 {
+        let id = self.register(Request::Submit{job_id});
+        if let Some(times) = times {
+            self.share_times.insert(id, times);
+        }
+        id
+    }
+
+    /// Removes and returns the request with the id, if it is pending
+    pub fn take(&mut self, id: u64) -> Option<Request> {
+        let (request, sent) = self.pending.remove(&id)?;
+        if let Some(times) = self.share_times.remove(&id) {
+            self.stages.record(&times, sent, Instant::now());
+        }
+        match request {
+            Request::Submit{..} | Request::KeepAlive => {
+                let round_trip = sent.elapsed();
+                debug!("response to request {} ({:?}) after {:?}", id, request, round_trip);
+                self.latency.record(round_trip);
+                if let Request::Submit{..} = request {
+                    self.score.record_round_trip(round_trip);
+                }
+            },
+            Request::Login | Request::BlockTemplate{..} => ()
+        }
+        Some(request)
+    }
+
+    /// Round trip times of the recent submit and keep alive requests
+    pub fn latency(&self) -> Option<LatencyStats> {
+        self.latency.stats()
+    }
+
+    /// Latency of the share stages, from the job to the response of the pool
+    pub fn stage_latency(&self) -> StageLatency {
+        self.stages.stats()
+    }
+
+    /// Counts the response to a submitted share, `rejection` is the reason if it was rejected
+    pub fn record_share_result(&mut self, rejection: Option<&str>) {
+        match rejection {
+            Some(reason) => {
+                self.shares.record_rejected(reason);
+                self.score.record_result(false);
+                self.flood_guard.record_rejected(RejectKind::from_reason(reason) == RejectKind::Stale, Instant::now());
+            },
+            None => {
+                self.shares.record_accepted();
+                self.score.record_result(true);
+                self.flood_guard.record_accepted();
+            }
+        }
+    }
+
+    /// Counts an error sent by the pool for the flood guard
+    pub fn record_error(&mut self) {
+        self.flood_guard.record_error(Instant::now());
+    }
+
+    /// Whether the flood guard lets a share through now
+    pub fn allow_share(&mut self) -> Decision {
+        self.flood_guard.allow(Instant::now())
+    }
+
+    /// true once when the flood guard found the pool unhealthy
+    pub fn take_unhealthy(&mut self) -> bool {
+        self.flood_guard.take_unhealthy()
+    }
+
+    /// Checks the flood guard for an unanswered probe, true once when the pool became unhealthy
+    pub fn poll_flood_guard(&mut self) -> bool {
+        self.flood_guard.tick(Instant::now());
+        self.flood_guard.take_unhealthy()
+    }
+
+    /// A job was received from the pool, it is the current job now
+    pub fn job_received(&mut self, job_id: &str) {
+        self.cancelled_jobs.retain(|id| id != job_id);
+        self.current_job = Some(job_id.to_string());
+        self.score.record_job(Instant::now());
+    }
+
+    /// The pool cancelled the job (None: the current job), shares for it are not sent anymore.
+    /// Returns the cancelled job, None if there is no current job.
+    pub fn cancel_job(&mut self, job_id: Option<&str>) -> Option<String> {
+        let job_id = job_id.map(|id| id.to_string()).or_else(|| self.current_job.clone())?;
+        if self.current_job.as_ref() == Some(&job_id) {
+            self.current_job = None;
+        }
+        if !self.cancelled_jobs.contains(&job_id) {
+            if self.cancelled_jobs.len() == CANCELLED_JOBS_KEPT {
+                self.cancelled_jobs.pop_front();
+            }
+            self.cancelled_jobs.push_back(job_id.clone());
+        }
+        Some(job_id)
+    }
+
+    pub fn is_cancelled(&self, job_id: &str) -> bool {
+        self.cancelled_jobs.iter().any(|id| id == job_id)
+    }
+
+    /// Shares withheld by the flood guard
+    pub fn withheld_shares(&self) -> u64 {
+        self.flood_guard.withheld()
+    }
+
+    /// The rolling score of the pool, see `pool_score`
+    pub fn pool_score(&self) -> ScoreStats {
+        self.score.stats(Instant::now())
+    }
+
+    /// The score once it dropped below `min_score` after at least `SCORE_MIN_SHARES` share
+    /// responses, reported once per connection (None: not below or no minimum set)
+    pub fn poll_score(&mut self, min_score: Option<u64>) -> Option<ScoreStats> {
+        let min_score = min_score?;
+        let stats = self.pool_score();
+        if self.score_reported || stats.shares < SCORE_MIN_SHARES || stats.score >= min_score {
+            return None;
+        }
+        self.score_reported = true;
+        Some(stats)
+    }
+
+    pub fn share_stats(&self) -> ShareStats {
+        self.shares
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+impl Default for PendingRequests {
+    fn default() -> PendingRequests {
+        PendingRequests::new()
+    }
+}
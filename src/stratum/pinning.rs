@@ -0,0 +1,91 @@
+extern crate sha2;
+
+use self::sha2::{Digest, Sha256};
+
+use std::io;
+use std::io::{Error, ErrorKind};
+use std::net::{IpAddr};
+use std::str::FromStr;
+use hex;
+
+/// An IP address or subnet in CIDR notation (e.g. "10.0.0.0/8")
+#[derive(Debug, Clone, PartialEq)]
+pub struct IpRange {
+    addr: IpAddr,
+    prefix_len: u8
+}
+
+impl IpRange {
+    pub fn parse(range: &str) -> Result<IpRange, String> {
+        let mut parts = range.trim().splitn(2, '/');
+        let addr_str = parts.next().unwrap_or("");
+        let addr = IpAddr::from_str(addr_str).map_err(|e| format!("invalid IP address {}: {}", addr_str, e))?;
+        let max_len = max_prefix_len(&addr);
+        let prefix_len = match parts.next() {
+            Some(len) => len.parse::<u8>().map_err(|e| format!("invalid prefix length in {}: {}", range, e))?,
+            None => max_len
+        };
+        if prefix_len > max_len {
+            return Err(format!("prefix length of {} has to be <= {}", range, max_len));
+        }
+        Ok(IpRange{addr, prefix_len})
+    }
+
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.addr, *ip) {
+            (IpAddr::V4(range), IpAddr::V4(ip)) => {
+                prefix_matches(u128::from(u32::from(range)), u128::from(u32::from(ip)), 32, self.prefix_len)
+            },
+            (IpAddr::V6(range), IpAddr::V6(ip)) => {
+                prefix_matches(u128::from(range), u128::from(ip), 128, self.prefix_len)
+            },
+            _ => false
+        }
+    }
+}
+
+fn max_prefix_len(addr: &IpAddr) -> u8 {
+    match *addr {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128
+    }
+}
+
+fn prefix_matches(range: u128, ip: u128, bits: u8, prefix_len: u8) -> bool {
+    let shift = u32::from(bits - prefix_len);
+    if shift >= 128 {
+        return true;
+    }
+    (range >> shift) == (ip >> shift)
+}
+
+/// Refuses the connection if `peer` is not in `allowed` (an empty list allows all addresses)
+pub fn check_peer_ip(peer: &IpAddr, allowed: &[IpRange]) -> io::Result<()> {
+    if allowed.is_empty() || allowed.iter().any(|r| r.contains(peer)) {
+        return Ok(());
+    }
+    Err(Error::new(ErrorKind::PermissionDenied, format!("pool address {} is not in allowed_ips, refusing to connect", peer)))
+}
+
+/// SHA-256 fingerprint of a DER encoded certificate as lower case hex
+pub fn fingerprint(cert_der: &[u8]) -> String {
+    hex::encode(&Sha256::digest(cert_der))
+}
+
+/// Fingerprints are accepted with or without ':' separators and in any case
+pub fn normalize_fingerprint(fingerprint: &str) -> String {
+    fingerprint.trim().chars().filter(|c| *c != ':').collect::<String>().to_lowercase()
+}
+
+/// Refuses the connection if the certificate does not match one of the pinned
+/// fingerprints (an empty list accepts all certificates valid for the pool)
+pub fn check_fingerprint(cert_der: &[u8], pinned: &[String]) -> io::Result<()> {
+    if pinned.is_empty() {
+        return Ok(());
+    }
+    let actual = fingerprint(cert_der);
+    if pinned.iter().any(|p| *p == actual) {
+        return Ok(());
+    }
+    Err(Error::new(ErrorKind::PermissionDenied, format!("pool certificate fingerprint {} is not pinned, refusing to connect", actual)))
+}
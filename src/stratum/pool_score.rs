@@ -0,0 +1,85 @@
+use std::time::{Duration, Instant};
+use super::event_log::{Ring};
+
+/// Number of recent share responses the score is computed from
+pub const SCORE_WINDOW : usize = 50;
+/// Median submit round trip at which the latency part of the score is halved
+pub const SCORE_LATENCY_MILLIS : u64 = 500;
+/// Jobs received longer ago than this are not fresh, the freshness part decays afterwards
+pub const SCORE_FRESH_JOB_SECS : u64 = 120;
+/// Answered shares needed before the score is compared to `min_pool_score`
+pub const SCORE_MIN_SHARES : usize = 10;
+
+/// The rolling score of a pool connection and the parts it is made of
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScoreStats {
+    /// 0 (unusable) to 100 (instant responses, no rejects, fresh jobs)
+    pub score: u64,
+    /// median round trip of the recent submits, None if none was answered yet
+    pub accept_latency: Option<Duration>,
+    /// rejected and stale shares of the recent responses, 0.0 to 1.0
+    pub reject_rate: f64,
+    /// time since the last job of the pool, None before the first job
+    pub job_age: Option<Duration>,
+    /// share responses in the window
+    pub shares: usize,
+}
+
+/// Scores a pool by the latency of its share responses (40%), the share of accepted
+/// shares (40%) and how fresh its jobs are (20%), over the last `SCORE_WINDOW` shares.
+#[derive(Debug)]
+pub struct PoolScore {
+    round_trips: Ring<Duration>,
+    /// true: accepted
+    results: Ring<bool>,
+    last_job: Option<Instant>,
+}
+
+impl PoolScore {
+    pub fn new() -> PoolScore {
+        PoolScore{round_trips: Ring::new(SCORE_WINDOW), results: Ring::new(SCORE_WINDOW), last_job: None}
+    }
+
+    /// The response to a submit arrived after `round_trip`
+    pub fn record_round_trip(&mut self, round_trip: Duration) {
+        self.round_trips.push(round_trip);
+    }
+
+    pub fn record_result(&mut self, accepted: bool) {
+        self.results.push(accepted);
+    }
+
+    pub fn record_job(&mut self, received: Instant) {
+        self.last_job = Some(received);
+    }
+
+    pub fn stats(&self, now: Instant) -> ScoreStats {
+        let mut round_trips : Vec<Duration> = self.round_trips.recent(SCORE_WINDOW).into_iter().cloned().collect();
+        round_trips.sort();
+        let accept_latency = round_trips.get(round_trips.len() / 2).cloned();
+        let latency_part = accept_latency.map_or(1.0, |median| {
+            let millis = median.as_secs() as f64 * 1000.0 + f64::from(median.subsec_millis());
+            SCORE_LATENCY_MILLIS as f64 / (SCORE_LATENCY_MILLIS as f64 + millis)
+        });
+
+        let shares = self.results.len();
+        let rejected = self.results.recent(SCORE_WINDOW).into_iter().filter(|accepted| !**accepted).count();
+        let reject_rate = if shares == 0 { 0.0 } else { rejected as f64 / shares as f64 };
+
+        let job_age = self.last_job.map(|received| if now > received { now - received } else { Duration::from_secs(0) });
+        let fresh_part = match job_age {
+            Some(age) if age.as_secs() < SCORE_FRESH_JOB_SECS => 1.0,
+            Some(age) => SCORE_FRESH_JOB_SECS as f64 / age.as_secs() as f64,
+            None => 0.0
+        };
+
+        let score = 100.0 * (0.4 * latency_part + 0.4 * (1.0 - reject_rate) + 0.2 * fresh_part);
+        ScoreStats{score: score.round() as u64, accept_latency, reject_rate, job_age, shares}
+    }
+}
+
+impl Default for PoolScore {
+    fn default() -> PoolScore {
+        PoolScore::new()
+    }
+}
@@ -0,0 +1,99 @@
+use std::io;
+use std::io::{Error, ErrorKind};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::{Duration};
+use super::pinning;
+use super::pinning::{IpRange};
+
+/// Order in which the resolved addresses of a pool are tried
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AddressPreference {
+    /// the order returned by the resolver
+    System,
+    /// IPv6 and IPv4 addresses alternately, starting with IPv6 (as in happy eyeballs)
+    Ipv6First,
+    /// IPv4 and IPv6 addresses alternately, starting with IPv4
+    Ipv4First,
+    /// only IPv4 addresses
+    Ipv4Only,
+    /// only IPv6 addresses
+    Ipv6Only
+}
+
+impl AddressPreference {
+    pub fn from_name(name: &str) -> Option<AddressPreference> {
+        match name {
+            "system" => Some(AddressPreference::System),
+            "ipv6" => Some(AddressPreference::Ipv6First),
+            "ipv4" => Some(AddressPreference::Ipv4First),
+            "ipv4_only" => Some(AddressPreference::Ipv4Only),
+            "ipv6_only" => Some(AddressPreference::Ipv6Only),
+            _ => None
+        }
+    }
+}
+
+/// Sorts resolved addresses by `preference`. The resolver order is kept within each
+/// address family, mixed orders alternate between the families.
+pub fn order_addresses(addrs: Vec<SocketAddr>, preference: AddressPreference) -> Vec<SocketAddr> {
+    let (v6, v4) : (Vec<SocketAddr>, Vec<SocketAddr>) = addrs.iter().partition(|a| a.is_ipv6());
+    match preference {
+        AddressPreference::System => addrs,
+        AddressPreference::Ipv6First => interleave(v6, v4),
+        AddressPreference::Ipv4First => interleave(v4, v6),
+        AddressPreference::Ipv4Only => v4,
+        AddressPreference::Ipv6Only => v6
+    }
+}
+
+fn interleave(first: Vec<SocketAddr>, second: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let mut result = Vec::with_capacity(first.len() + second.len());
+    let mut first = first.into_iter();
+    let mut second = second.into_iter();
+    loop {
+        match (first.next(), second.next()) {
+            (None, None) => return result,
+            (a, b) => {
+                result.extend(a);
+                result.extend(b);
+            }
+        }
+    }
+}
+
+/// Resolves all A/AAAA records of `address` and connects to the first reachable one in
+/// the order given by `preference`, waiting at most `timeout` for each address.
+/// The connected address is logged and available as `peer_addr` of the stream.
+pub fn connect(address: &str, timeout: Duration, preference: AddressPreference) -> io::Result<TcpStream> {
+    connect_allowed(address, timeout, preference, &[])
+}
+
+/// Like `connect`, but only addresses in `allowed_ips` are connected to (an empty list
+/// allows all addresses). No connection is opened to any other address.
+pub fn connect_allowed(address: &str, timeout: Duration, preference: AddressPreference, allowed_ips: &[IpRange]) -> io::Result<TcpStream> {
+    let resolved : Vec<SocketAddr> = address.to_socket_addrs()?.collect();
+    let ordered = order_addresses(resolved.clone(), preference);
+    let addrs : Vec<SocketAddr> = ordered.iter().cloned()
+        .filter(|addr| pinning::check_peer_ip(&addr.ip(), allowed_ips).is_ok())
+        .collect();
+    let mut last_err = if resolved.is_empty() {
+        Error::new(ErrorKind::InvalidInput, format!("{} did not resolve to any address", address))
+    } else if ordered.is_empty() {
+        Error::new(ErrorKind::InvalidInput, format!("{} did not resolve to any address allowed by the address preference {:?} ({:?})", address, preference, resolved))
+    } else {
+        Error::new(ErrorKind::PermissionDenied, format!("no address of {} is in allowed_ips ({:?}), refusing to connect", address, ordered))
+    };
+    for addr in addrs {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(stream) => {
+                info!("connected to {} via {}", address, addr);
+                return Ok(stream);
+            },
+            Err(err) => {
+                info!("connecting to {} ({}) failed: {}", address, addr, err);
+                last_err = err;
+            }
+        }
+    }
+    Err(last_err)
+}
@@ -0,0 +1,99 @@
+extern crate serde_json;
+
+use std::collections::HashMap;
+use std::panic;
+use std::panic::{AssertUnwindSafe};
+use self::serde_json::{Value};
+use super::stratum_data::{default_agent, UNKNOWN_ERROR_CODE};
+
+/// Methods the pool sends as notification, they are never answered (even if they carry an id)
+pub const NOTIFICATIONS : &[&str] = &["job", "set_extranonce", "cancel_job"];
+/// JSON-RPC error code for requests no handler is registered for
+pub const METHOD_NOT_FOUND : i64 = -32601;
+
+/// Computes the result of a request from its params, the error message is sent to the pool otherwise
+pub type RpcHandler = Box<dyn Fn(&Value) -> Result<Value, String> + Send>;
+
+/// A request the pool sends to the miner, it expects a response with the same id
+#[derive(Debug, Clone, PartialEq)]
+pub struct RpcRequest {
+    pub id: Value,
+    pub method: String,
+    /// `Value::Null` if the request has no params
+    pub params: Value,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+/// The request in the line, None for responses, notifications and lines that are no JSON object
+pub fn parse_request(line: &str) -> Option<RpcRequest> {
+    let value : Value = serde_json::from_str(line).ok()?;
+    let id = value.get("id")?;
+    if id.is_null() {
+        return None;
+    }
+    let method = value.get("method")?.as_str()?;
+    if NOTIFICATIONS.contains(&method) {
+        return None;
+    }
+    Some(RpcRequest{id: id.clone(), method: method.to_string(), params: value.get("params").cloned().unwrap_or(Value::Null)})
+}
+
+/// Handlers for the requests of the pool by method. Methods can be added or replaced
+/// (see `StratumClient::rpc_handler`), requests without handler are answered with an error.
+pub struct RpcHandlers {
+    handlers: HashMap<String, RpcHandler>
+}
+
+impl RpcHandlers {
+    /// The default handlers: `client.get_version` returns the mithril agent, `ping` returns "pong"
+    pub fn new() -> RpcHandlers {
+        let mut handlers = RpcHandlers::empty();
+        handlers.register("client.get_version", |_| Ok(Value::String(default_agent())));
+        handlers.register("ping", |_| Ok(Value::String("pong".to_string())));
+        handlers
+    }
+
+    pub fn empty() -> RpcHandlers {
+        RpcHandlers{handlers: HashMap::new()}
+    }
+
+    /// Sets the handler of `method`, a handler registered before is replaced
+    pub fn register<F: Fn(&Value) -> Result<Value, String> + Send + 'static>(&mut self, method: &str, f: F) {
+        self.handlers.insert(method.to_string(), Box::new(f));
+    }
+
+    /// Returns false if no handler was registered for `method`
+    pub fn remove(&mut self, method: &str) -> bool {
+        self.handlers.remove(method).is_some()
+    }
+
+    pub fn handles(&self, method: &str) -> bool {
+        self.handlers.contains_key(method)
+    }
+
+    /// The result of the handler of the request. A panicking handler is answered with an error, but kept.
+    pub fn respond(&self, request: &RpcRequest) -> Result<Value, RpcError> {
+        let handler = match self.handlers.get(&request.method) {
+            Some(handler) => handler,
+            None => return Err(RpcError{code: METHOD_NOT_FOUND, message: format!("method not found: {}", request.method)})
+        };
+        match panic::catch_unwind(AssertUnwindSafe(|| handler(&request.params))) {
+            Ok(result) => result.map_err(|message| RpcError{code: UNKNOWN_ERROR_CODE, message}),
+            Err(_) => {
+                error!("handler for pool request {} panicked", request.method);
+                Err(RpcError{code: UNKNOWN_ERROR_CODE, message: "internal error".to_string()})
+            }
+        }
+    }
+}
+
+impl Default for RpcHandlers {
+    fn default() -> RpcHandlers {
+        RpcHandlers::new()
+    }
+}
@@ -0,0 +1,111 @@
+extern crate libc;
+
+use std::time::{SystemTime, UNIX_EPOCH};
+use super::stratum_data::{PoolConfig};
+
+pub const MINUTES_PER_DAY : u32 = 24 * 60;
+
+/// A pool (and optionally another wallet) that is mined to during a daily time window
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduleWindow {
+    /// minute of the day (local time) the window begins
+    pub from_minute: u32,
+    /// minute of the day the window ends, before `from_minute` for windows over midnight
+    pub to_minute: u32,
+    pub pool_address: String,
+    /// None: the wallet of the `[pool]` section
+    pub wallet_address: Option<String>,
+    /// None: the password of the `[pool]` section
+    pub pool_password: Option<String>,
+}
+
+impl ScheduleWindow {
+    pub fn contains(&self, minute: u32) -> bool {
+        if self.from_minute <= self.to_minute {
+            minute >= self.from_minute && minute < self.to_minute
+        } else {
+            minute >= self.from_minute || minute < self.to_minute
+        }
+    }
+
+    /// e.g. `22:00-06:00`
+    pub fn describe(&self) -> String {
+        format!("{}-{}", format_time(self.from_minute), format_time(self.to_minute))
+    }
+}
+
+/// Pools by time of day. The first window containing the current minute is used,
+/// outside of all windows the `[pool]` settings apply.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PoolSchedule {
+    pub windows: Vec<ScheduleWindow>,
+}
+
+impl PoolSchedule {
+    pub fn is_empty(&self) -> bool {
+        self.windows.is_empty()
+    }
+
+    /// Index of the window active at `minute`, None if the `[pool]` settings apply
+    pub fn active(&self, minute: u32) -> Option<usize> {
+        self.windows.iter().position(|w| w.contains(minute))
+    }
+
+    /// `pool_conf` with pool, wallet and password of the window (if any)
+    pub fn pool_config(&self, pool_conf: &PoolConfig, window: Option<usize>) -> PoolConfig {
+        let mut conf = pool_conf.clone();
+        if let Some(window) = window.and_then(|ix| self.windows.get(ix)) {
+            conf.pool_address = window.pool_address.clone();
+            if let Some(ref wallet_address) = window.wallet_address {
+                conf.wallet_address = wallet_address.clone();
+            }
+            if let Some(ref pool_password) = window.pool_password {
+                conf.pool_password = pool_password.clone();
+            }
+        }
+        conf
+    }
+}
+
+/// Parses `HH:MM` (24 hour clock) into the minute of the day
+pub fn parse_time(time: &str) -> Option<u32> {
+    let mut parts = time.trim().splitn(2, ':');
+    let hours = parts.next()?.parse::<u32>().ok()?;
+    let minutes = parts.next()?.parse::<u32>().ok()?;
+    if hours >= 24 || minutes >= 60 {
+        return None;
+    }
+    Some(hours * 60 + minutes)
+}
+
+pub fn format_time(minute: u32) -> String {
+    format!("{:02}:{:02}", minute / 60, minute % 60)
+}
+
+/// The current minute of the day in local time
+#[cfg(unix)]
+pub fn local_minute_of_day() -> u32 {
+    let now = unix_time() as libc::time_t;
+    unsafe {
+        let mut tm : libc::tm = ::std::mem::zeroed();
+        if libc::localtime_r(&now, &mut tm).is_null() {
+            utc_minute_of_day()
+        } else {
+            (tm.tm_hour * 60 + tm.tm_min) as u32
+        }
+    }
+}
+
+/// The current minute of the day, UTC on platforms without the local time zone
+#[cfg(not(unix))]
+pub fn local_minute_of_day() -> u32 {
+    utc_minute_of_day()
+}
+
+fn utc_minute_of_day() -> u32 {
+    ((unix_time() % 86_400) / 60) as u32
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
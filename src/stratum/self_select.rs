@@ -0,0 +1,74 @@
+use std::io;
+use std::thread;
+use std::sync::mpsc::{channel, Sender};
+use solo;
+use difficulty;
+use worker::worker_pool;
+use super::{StratumCmd, StratumAction, StratumError, StratumEvent};
+use super::stratum_data::{BlockTemplateParams};
+
+/// Starts the template assembly step of self-select mode between the receive thread
+/// and the subscriber. Self-select jobs are completed with a block template of the
+/// local daemon, which is sent to the pool before the job is handed on. All other
+/// events are passed through. The thread ends when the returned sender is dropped.
+pub fn start_template_thread(daemon_address: String, cmd_sender: Sender<StratumCmd>, action_rcv: Sender<StratumEvent>) -> io::Result<(Sender<StratumEvent>, thread::JoinHandle<()>)> {
+    let (tx, rx) = channel::<StratumEvent>();
+    let hnd = thread::Builder::new().name("Stratum template thread".to_string()).spawn(move || {
+        for event in rx {
+            let action = match event.action {
+                StratumAction::SelectTemplate{miner_id, job_id, target, extra_nonce, pool_wallet} => {
+                    match assemble(&daemon_address, miner_id, job_id, target, &extra_nonce, &pool_wallet) {
+                        Ok((params, job)) => {
+                            if cmd_sender.send(StratumCmd::BlockTemplate{params}).is_err() {
+                                info!("stratum send thread ended, block template not sent");
+                                break;
+                            }
+                            job
+                        },
+                        Err(err) => StratumAction::Error{err: format!("block template for self-select job failed: {}", err)}
+                    }
+                },
+                other => other
+            };
+            let forwarded = StratumEvent{seq: event.seq, received: event.received, connection: event.connection, action};
+            if action_rcv.send(forwarded).is_err() {
+                info!("action receiver already terminated, stopping template thread");
+                break;
+            }
+        }
+        info!("stratum template thread ended");
+    })?;
+    Ok((tx, hnd))
+}
+
+/// Requests a block template for the pool wallet and builds the block template
+/// request for the pool and the job for the workers from it.
+pub fn assemble(daemon_address: &str, miner_id: String, job_id: String, target: String, extra_nonce: &str, pool_wallet: &str) -> Result<(BlockTemplateParams, StratumAction), StratumError> {
+    let template = solo::get_block_template(daemon_address, pool_wallet, Some(extra_nonce), 0)?;
+    Ok(template_job(miner_id, job_id, target, template))
+}
+
+pub fn template_job(miner_id: String, job_id: String, target: String, template: solo::Template) -> (BlockTemplateParams, StratumAction) {
+    let job = StratumAction::Job{
+        miner_id: miner_id.clone(),
+        blob: template.blockhashing_blob,
+        job_id: job_id.clone(),
+        difficulty: difficulty::target_difficulty(worker_pool::job_target(&target)),
+        target,
+        algo: None,
+        height: Some(template.height),
+        seed_hash: template.seed_hash.clone(),
+        next_seed_hash: template.next_seed_hash.clone()
+    };
+    let params = BlockTemplateParams{
+        id: miner_id,
+        job_id,
+        blob: template.blocktemplate_blob,
+        height: template.height,
+        difficulty: template.difficulty,
+        prev_hash: template.prev_hash,
+        seed_hash: template.seed_hash,
+        next_seed_hash: template.next_seed_hash
+    };
+    (params, job)
+}
@@ -0,0 +1,51 @@
+use std::collections::{HashMap, VecDeque};
+
+/// Number of recent jobs for which share counts are remembered
+const TRACKED_JOBS : usize = 8;
+
+/// Limits the number of shares submitted per job. Shares above the limit
+/// are counted but should not be sent to the pool.
+pub struct ShareCap {
+    max_per_job: Option<u64>,
+    counts: HashMap<String, u64>,
+    job_order: VecDeque<String>,
+    withheld: u64,
+}
+
+impl ShareCap {
+    pub fn new(max_per_job: Option<u64>) -> ShareCap {
+        ShareCap{max_per_job, counts: HashMap::new(), job_order: VecDeque::new(), withheld: 0}
+    }
+
+    /// Returns true if a share for the job may be sent and records it.
+    pub fn allow(&mut self, job_id: &str) -> bool {
+        let max = match self.max_per_job {
+            Some(max) => max,
+            None => return true
+        };
+
+        if !self.counts.contains_key(job_id) {
+            if self.job_order.len() >= TRACKED_JOBS {
+                if let Some(oldest) = self.job_order.pop_front() {
+                    self.counts.remove(&oldest);
+                }
+            }
+            self.job_order.push_back(job_id.to_string());
+            self.counts.insert(job_id.to_string(), 0);
+        }
+
+        let count = self.counts.get_mut(job_id).expect("job count");
+        if *count >= max {
+            self.withheld += 1;
+            false
+        } else {
+            *count += 1;
+            true
+        }
+    }
+
+    /// Total number of shares that were not allowed because of the cap
+    pub fn withheld(&self) -> u64 {
+        self.withheld
+    }
+}
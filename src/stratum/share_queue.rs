@@ -0,0 +1,153 @@
+use std::sync::{Arc, Mutex, Condvar};
+use std::sync::mpsc::{Sender, SendError};
+use std::time::{Instant};
+use super::{StratumCmd, submit_share};
+use super::stratum_data::{Share};
+use super::offline::{OfflineShares, Salvage};
+
+/// Behaviour of `ShareQueue::submit` if the queue is full
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum QueuePolicy {
+    /// the worker waits until the client took a share from the queue (or the connection ended)
+    Block,
+    /// the share is dropped and counted, the worker continues hashing
+    Error
+}
+
+impl QueuePolicy {
+    pub fn from_name(name: &str) -> Option<QueuePolicy> {
+        match name {
+            "block" => Some(QueuePolicy::Block),
+            "error" => Some(QueuePolicy::Error),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SubmitError {
+    /// the queue was full, the share was dropped
+    Full,
+    /// the connection ended, the share was not queued
+    Disconnected,
+    /// the connection ended, the share was kept for the next connection
+    Kept
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QueueStats {
+    pub capacity: usize,
+    /// shares waiting to be written to the pool
+    pub depth: usize,
+    pub max_depth: usize,
+    /// shares dropped because the queue was full
+    pub dropped: u64,
+}
+
+struct QueueState {
+    depth: usize,
+    max_depth: usize,
+    dropped: u64,
+    closed: bool,
+}
+
+/// Bounds the number of shares waiting in the command channel of a client, so that
+/// shares do not pile up without limit if the pool stalls. The send side calls
+/// `release` for every share it takes from the channel.
+pub struct ShareQueue {
+    capacity: usize,
+    policy: QueuePolicy,
+    state: Mutex<QueueState>,
+    not_full: Condvar,
+    /// where shares go that can not be sent anymore, with the address of the pool
+    offline: Mutex<Option<(Arc<OfflineShares>, String)>>,
+}
+
+impl ShareQueue {
+    pub fn new(capacity: usize, policy: QueuePolicy) -> ShareQueue {
+        ShareQueue{
+            capacity,
+            policy,
+            state: Mutex::new(QueueState{depth: 0, max_depth: 0, dropped: 0, closed: false}),
+            not_full: Condvar::new(),
+            offline: Mutex::new(None),
+        }
+    }
+
+    /// Shares that can not be sent anymore are kept in `offline` for the next connection to the pool
+    pub fn keep_offline(&self, offline: Arc<OfflineShares>, pool_address: &str) {
+        *self.offline.lock().expect("offline shares lock") = Some((offline, pool_address.to_string()));
+    }
+
+    /// Keeps a share that was not sent for the next connection, `Disconnected` if there is no offline buffer
+    pub fn keep(&self, share: Share) -> SubmitError {
+        match *self.offline.lock().expect("offline shares lock") {
+            Some((ref offline, ref pool_address)) => {
+                offline.keep(share, pool_address);
+                SubmitError::Kept
+            },
+            None => SubmitError::Disconnected
+        }
+    }
+
+    /// The kept shares that are still fresh for the session `miner_id` and its job `job_id`,
+    /// None if there is no offline buffer
+    pub fn take_offline(&self, miner_id: &str, job_id: &str) -> Option<Salvage> {
+        match *self.offline.lock().expect("offline shares lock") {
+            Some((ref offline, ref pool_address)) if !offline.is_empty() => Some(offline.take(pool_address, miner_id, job_id, Instant::now())),
+            _ => None
+        }
+    }
+
+    /// Queues the share for submission, applying the policy if the queue is full.
+    pub fn submit(&self, tx: &Sender<StratumCmd>, share: Share) -> Result<(), SubmitError> {
+        {
+            let mut state = self.state.lock().expect("share queue lock");
+            while state.depth >= self.capacity && !state.closed {
+                match self.policy {
+                    QueuePolicy::Block => state = self.not_full.wait(state).expect("share queue lock"),
+                    QueuePolicy::Error => {
+                        state.dropped += 1;
+                        warn!("share queue full ({} shares waiting), share for job {} dropped ({} dropped so far)",
+                            state.depth, share.job_id, state.dropped);
+                        return Err(SubmitError::Full);
+                    }
+                }
+            }
+            if state.closed {
+                return Err(self.keep(share));
+            }
+            state.depth += 1;
+            if state.depth > state.max_depth {
+                state.max_depth = state.depth;
+            }
+        }
+        if let Err(SendError(cmd)) = submit_share(tx, share) {
+            self.release();
+            return match cmd {
+                StratumCmd::SubmitShare{share} => Err(self.keep(share)),
+                _ => Err(SubmitError::Disconnected)
+            };
+        }
+        Ok(())
+    }
+
+    /// A share was taken from the channel
+    pub fn release(&self) {
+        let mut state = self.state.lock().expect("share queue lock");
+        state.depth = state.depth.saturating_sub(1);
+        self.not_full.notify_one();
+    }
+
+    /// The connection ended, blocked and later submits return `Disconnected`
+    pub fn close(&self) {
+        let mut state = self.state.lock().expect("share queue lock");
+        state.closed = true;
+        self.not_full.notify_all();
+    }
+
+    pub fn stats(&self) -> QueueStats {
+        let state = self.state.lock().expect("share queue lock");
+        QueueStats{capacity: self.capacity, depth: state.depth, max_depth: state.max_depth, dropped: state.dropped}
+    }
+}
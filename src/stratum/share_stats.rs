@@ -0,0 +1,35 @@
+use super::{RejectKind};
+
+/// Share counts of one pool connection. A share counts as submitted when it is sent and as
+/// accepted, rejected or stale when the response with its request id arrives. Stale shares
+/// (rejected because the job was no longer valid) are not included in `rejected`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ShareStats {
+    pub submitted: u64,
+    pub accepted: u64,
+    pub rejected: u64,
+    pub stale: u64,
+}
+
+impl ShareStats {
+    pub fn record_submitted(&mut self) {
+        self.submitted += 1;
+    }
+
+    pub fn record_accepted(&mut self) {
+        self.accepted += 1;
+    }
+
+    /// Counts the rejection as stale or rejected by the reason sent by the pool
+    pub fn record_rejected(&mut self, reason: &str) {
+        match RejectKind::from_reason(reason) {
+            RejectKind::Stale => self.stale += 1,
+            _ => self.rejected += 1
+        }
+    }
+
+    /// Shares without a response (yet), e.g. lost with the connection
+    pub fn unanswered(&self) -> u64 {
+        self.submitted.saturating_sub(self.accepted + self.rejected + self.stale)
+    }
+}
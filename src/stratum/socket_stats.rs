@@ -0,0 +1,84 @@
+use std::net::TcpStream;
+use std::time::Duration;
+
+/// TCP level statistics of a connection, helps telling network problems
+/// apart from a slow pool.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SocketStats {
+    /// smoothed round trip time
+    pub rtt: Duration,
+    /// round trip time variance
+    pub rtt_var: Duration,
+    /// retransmits of the currently unacknowledged segment
+    pub retransmits: u8,
+    /// retransmitted segments over the lifetime of the connection
+    pub total_retransmits: u32,
+}
+
+/// Reads the statistics from the kernel, only supported on Linux (TCP_INFO).
+#[cfg(target_os = "linux")]
+pub fn socket_stats(stream: &TcpStream) -> Option<SocketStats> {
+    extern crate libc;
+    use std::mem;
+    use std::os::unix::io::AsRawFd;
+
+    /// Prefix of the kernel `struct tcp_info` up to `tcpi_total_retrans`,
+    /// the kernel copies at most the requested length.
+    #[repr(C)]
+    #[derive(Default)]
+    struct TcpInfo {
+        state: u8,
+        ca_state: u8,
+        retransmits: u8,
+        probes: u8,
+        backoff: u8,
+        options: u8,
+        wscale: u8,
+        app_limited: u8,
+        rto: u32,
+        ato: u32,
+        snd_mss: u32,
+        rcv_mss: u32,
+        unacked: u32,
+        sacked: u32,
+        lost: u32,
+        retrans: u32,
+        fackets: u32,
+        last_data_sent: u32,
+        last_ack_sent: u32,
+        last_data_recv: u32,
+        last_ack_recv: u32,
+        pmtu: u32,
+        rcv_ssthresh: u32,
+        rtt: u32,
+        rttvar: u32,
+        snd_ssthresh: u32,
+        snd_cwnd: u32,
+        advmss: u32,
+        reordering: u32,
+        rcv_rtt: u32,
+        rcv_space: u32,
+        total_retrans: u32,
+    }
+
+    let mut info = TcpInfo::default();
+    let mut len = mem::size_of::<TcpInfo>() as libc::socklen_t;
+    let result = unsafe {
+        libc::getsockopt(stream.as_raw_fd(), libc::IPPROTO_TCP, libc::TCP_INFO,
+                         &mut info as *mut TcpInfo as *mut libc::c_void, &mut len)
+    };
+    if result != 0 || (len as usize) < mem::size_of::<TcpInfo>() {
+        return None;
+    }
+    Some(SocketStats{
+        rtt: Duration::from_micros(u64::from(info.rtt)),
+        rtt_var: Duration::from_micros(u64::from(info.rttvar)),
+        retransmits: info.retransmits,
+        total_retransmits: info.total_retrans,
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn socket_stats(_stream: &TcpStream) -> Option<SocketStats> {
+    None
+}
@@ -1,6 +1,15 @@
 extern crate serde;
 extern crate serde_json;
 
+use super::pinning::{IpRange};
+use super::resolve::{AddressPreference};
+use super::share_queue::{QueuePolicy};
+use super::multi_pool::{ArbitrationPolicy};
+use super::latency::{ShareTimes};
+use super::flood_guard::{FloodGuardConfig};
+
+use std::collections::{BTreeMap};
+
 /// For checking the method in the json content and parsing further
 #[derive(Deserialize, Debug)]
 pub struct Method {
@@ -13,28 +22,85 @@ pub struct ErrorDetails {
     pub message: String,
 }
 
+/// Code used for errors that are sent as plain message without a code
+pub const UNKNOWN_ERROR_CODE : i64 = -1;
+
+/// Most pools send errors as `{"code":..,"message":..}`, some only as message string
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum ErrorValue {
+    Details(ErrorDetails),
+    Message(String)
+}
+
+impl ErrorValue {
+    pub fn into_details(self) -> ErrorDetails {
+        match self {
+            ErrorValue::Details(details) => details,
+            ErrorValue::Message(message) => ErrorDetails{code: UNKNOWN_ERROR_CODE, message}
+        }
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub struct ErrorResult {
-    pub error: ErrorDetails
+    pub id: Option<u64>,
+    pub error: ErrorValue
 }
 
 #[derive(Deserialize)]
 pub struct Job {
+    /// empty for self-select jobs, the blob is built from a local block template then
+    #[serde(default)]
     pub blob: String,
     pub job_id: String,
     pub target: String,
+    pub algo: Option<String>,
+    /// older pools send the variant separately (e.g. algo "cn" with variant 2)
+    pub variant: Option<serde_json::Value>,
+    /// block height of the job
+    pub height: Option<u64>,
+    /// seed of the algorithm epoch (e.g. for RandomX)
+    pub seed_hash: Option<String>,
+    /// seed of the next epoch, sent by some pools ahead of the epoch change
+    pub next_seed_hash: Option<String>,
+    /// self-select mode: extra nonce to request the block template with
+    pub extra_nonce: Option<String>,
+    /// self-select mode: wallet of the pool the block template is requested for
+    pub pool_wallet: Option<String>,
+}
+
+impl Job {
+    /// The algorithm of the job as "<algo>/<variant>", None if the pool did not send one
+    pub fn algo_name(&self) -> Option<String> {
+        let algo = self.algo.as_ref()?;
+        match self.variant {
+            Some(serde_json::Value::Number(ref n)) if !algo.contains('/') => Some(format!("{}/{}", algo, n)),
+            Some(serde_json::Value::String(ref v)) if !algo.contains('/') => Some(format!("{}/{}", algo, v)),
+            _ => Some(algo.clone())
+        }
+    }
 }
 
 #[derive(Deserialize)]
 pub struct LoginResult {
     pub id: String,
     pub job: Job,
-    pub status: String
+    pub status: String,
+    /// protocol extensions supported by the pool (e.g. "algo", "keepalive"), not sent by all pools
+    pub extensions: Option<Vec<String>>
+}
+
+impl LoginResult {
+    /// False only if the pool lists its extensions and keep alive is not among them
+    pub fn supports_keepalive(&self) -> bool {
+        self.extensions.as_ref().map_or(true, |e| e.iter().any(|x| x == "keepalive"))
+    }
 }
 
 #[derive(Deserialize)]
 pub struct LoginResponse {
-    pub id: u32,
+    pub id: u64,
     pub result: LoginResult
 }
 
@@ -46,7 +112,7 @@ pub struct OkResult {
 
 #[derive(Deserialize, Clone)]
 pub struct OkResponse {
-    pub id: u32,
+    pub id: u64,
     pub result: OkResult
 }
 
@@ -55,16 +121,49 @@ pub struct JobResponse {
     pub params: Job
 }
 
+#[derive(Deserialize)]
+pub struct ExtranonceParams {
+    /// hex encoded leading nonce bytes reserved by the pool
+    pub extra_nonce: String
+}
+
+#[derive(Deserialize)]
+pub struct ExtranonceNotification {
+    pub params: ExtranonceParams
+}
+
+#[derive(Deserialize, Default)]
+pub struct CancelJobParams {
+    /// the cancelled job, all current work if not sent
+    pub job_id: Option<String>
+}
+
+#[derive(Deserialize)]
+pub struct CancelJobNotification {
+    #[serde(default)]
+    pub params: CancelJobParams
+}
+
 
 #[derive(Serialize)]
 pub struct LoginParams {
     pub login: String,
-    pub pass: String
+    pub pass: String,
+    /// algorithms supported by the miner
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub algo: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rig_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub agent: Option<String>,
+    /// "self-select" if block templates are chosen by the miner
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>
 }
 
 #[derive(Serialize)]
 pub struct LoginRequest {
-    pub id: u32,
+    pub id: u64,
     pub method: String,
     pub params: LoginParams,
 }
@@ -76,37 +175,247 @@ pub struct KeepAliveParams {
 
 #[derive(Serialize)]
 pub struct KeepAliveRequest {
-    pub id: u32,
+    pub id: u64,
     pub method: String,
     pub params: KeepAliveParams
 }
 
+/// Response to a request of the pool, `result` or `error` is null
+#[derive(Serialize)]
+pub struct RpcResponse {
+    pub id: serde_json::Value,
+    pub jsonrpc: String,
+    pub result: Option<serde_json::Value>,
+    pub error: Option<RpcErrorBody>,
+}
+
+#[derive(Serialize)]
+pub struct RpcErrorBody {
+    pub code: i64,
+    pub message: String,
+}
+
 #[derive(Serialize)]
 pub struct SubmitParams {
     pub id: String,
     pub job_id: String,
     pub nonce: String,
-    pub result: String
+    pub result: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub algo: Option<String>,
+    /// pool specific fields (`SubmitOptions::extra_params`)
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, String>
+}
+
+/// Names of the submit params sent anyway, they can not be used as extra params
+pub const SUBMIT_PARAM_NAMES : [&str; 5] = ["id", "job_id", "nonce", "result", "algo"];
+
+/// Pool specific additions to the submit request, for pools that expect more
+/// than the common fields
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SubmitOptions {
+    /// nonce and result are sent as upper case hex
+    pub uppercase_hex: bool,
+    /// sent as `algo` param (None: not sent)
+    pub algo: Option<String>,
+    /// additional string params, sorted by name. `{miner_id}`, `{job_id}` and `{rig_id}`
+    /// in the values are replaced.
+    pub extra_params: Vec<(String, String)>,
+}
+
+impl SubmitOptions {
+    /// The submit params of `share` with these options applied
+    pub fn params(&self, share: Share, rig_id: Option<&str>) -> SubmitParams {
+        let extra = self.extra_params.iter().map(|&(ref name, ref template)| {
+            let value = template.replace("{miner_id}", &share.miner_id)
+                .replace("{job_id}", &share.job_id)
+                .replace("{rig_id}", rig_id.unwrap_or(""));
+            (name.clone(), value)
+        }).collect();
+        let (nonce, result) = if self.uppercase_hex {
+            (share.nonce.to_uppercase(), share.hash.to_uppercase())
+        } else {
+            (share.nonce, share.hash)
+        };
+        SubmitParams{id: share.miner_id, job_id: share.job_id, nonce, result, algo: self.algo.clone(), extra}
+    }
 }
 
 #[derive(Serialize)]
 pub struct SubmitRequest {
-    pub id: u32,
+    pub id: u64,
     pub method: String,
     pub params: SubmitParams
 }
 
+/// A block template chosen by the miner in self-select mode
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct BlockTemplateParams {
+    /// miner id
+    pub id: String,
+    pub job_id: String,
+    pub blob: String,
+    pub height: u64,
+    pub difficulty: u64,
+    pub prev_hash: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed_hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_seed_hash: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct BlockTemplateRequest {
+    pub id: u64,
+    pub method: String,
+    pub params: BlockTemplateParams
+}
+
 #[derive(Debug)]
 pub struct Share {
     pub miner_id: String,
     pub job_id: String,
     pub nonce: String,
-    pub hash: String
+    pub hash: String,
+    /// stages the share passed in mithril, for the share latency report (None if unknown)
+    pub times: Option<ShareTimes>
 }
 
 #[derive(Debug, Clone)]
 pub struct PoolConfig {
     pub pool_address: String,
     pub wallet_address: String,
-    pub pool_password: String
+    pub pool_password: String,
+    /// shares above this number per job are not submitted (None = no limit)
+    pub max_shares_per_job: Option<u64>,
+    /// worker name shown in the per-worker stats of the pool
+    pub rig_id: Option<String>,
+    /// miner identification sent on login
+    pub agent: Option<String>,
+    /// method name of the keep alive request (most pools use "keepalived")
+    pub keepalive_method: String,
+    /// seconds between keep alive requests, None if no keep alive is sent
+    pub keepalive_interval_secs: Option<u64>,
+    /// a random delay of up to this many seconds is added to the keep alive interval
+    pub keepalive_jitter_seconds: u64,
+    /// timeout for establishing the TCP connection to the pool
+    pub connect_timeout_secs: u64,
+    /// order in which the resolved IPv4 and IPv6 addresses of the pool are tried
+    pub address_preference: AddressPreference,
+    /// number of retries if connecting to the pool fails on login with a transient error
+    pub login_retries: u64,
+    /// raw lines exchanged with the pool are appended to this file (wallet address redacted)
+    pub trace_file: Option<String>,
+    /// messages exchanged with the pool are recorded with timing to this JSON lines file,
+    /// credentials redacted (`--capture-session`)
+    pub capture_session: Option<String>,
+    /// the connection is restarted if no job was received for this many seconds (None = no limit)
+    pub job_timeout_secs: Option<u64>,
+    /// shares are validated and logged, but not sent to the pool
+    pub dry_run: bool,
+    /// targets easier than `difficulty::MIN_JOB_DIFFICULTY` are raised to it instead of only logged
+    pub clamp_easy_targets: bool,
+    /// connect to the pool via TLS (with certificate validation)
+    pub tls: bool,
+    /// SHA-256 fingerprints (lower case hex) of accepted pool certificates, empty accepts all valid certificates
+    pub tls_fingerprints: Vec<String>,
+    /// addresses the pool connection may go to, empty allows all
+    pub allowed_ips: Vec<IpRange>,
+    /// address of a monerod for solo mining, the pool settings are not used then
+    pub solo_daemon_address: Option<String>,
+    /// address of a monerod for self-select mode, block templates are chosen locally
+    pub self_select_daemon_address: Option<String>,
+    /// pools used if `pool_address` fails, in order of priority
+    pub failover_pool_addresses: Vec<String>,
+    /// consecutive connection failures before the next pool is used
+    pub failover_max_failures: u64,
+    /// how often the primary pool is probed while a failover pool is used
+    pub failover_probe_minutes: u64,
+    /// a second pool kept connected at the same time as `pool_address` (None: only one connection)
+    pub secondary_pool_address: Option<String>,
+    /// which of the two connected pools the workers get jobs from
+    pub pool_arbitration: ArbitrationPolicy,
+    /// maximum number of shares waiting to be sent to the pool
+    pub share_queue_capacity: u64,
+    /// what happens to a share if `share_queue_capacity` shares are already waiting
+    pub share_queue_policy: QueuePolicy,
+    /// shares of the previous job are still sent for this many seconds after a job change,
+    /// shares of older jobs are dropped (None: shares of replaced jobs are always sent)
+    pub late_share_grace_secs: Option<u64>,
+    /// the share counts of the connection are sent as `StratumAction::Stats` this often
+    /// (None: not sent, `StratumClient::stats` still returns them)
+    pub stats_interval_secs: Option<u64>,
+    /// the pool is reported unhealthy (and the next failover pool used) once its score drops
+    /// below this, checked with the stats (None: the score is only reported)
+    pub min_pool_score: Option<u64>,
+    /// shares kept while the connection is down, sent again after the next login (0: none are kept)
+    pub offline_share_capacity: u64,
+    /// kept shares found longer ago than this are discarded as stale
+    pub offline_share_max_age_secs: u64,
+    /// pool specific additions to the submit request
+    pub submit_options: SubmitOptions,
+    /// when submissions back off from a pool that rejects every share or sends error storms
+    pub flood_guard: FloodGuardConfig
+}
+
+pub const DEFAULT_KEEPALIVE_METHOD : &str = "keepalived";
+pub const DEFAULT_KEEPALIVE_JITTER_SECONDS : u64 = 10;
+pub const DEFAULT_KEEPALIVE_INTERVAL_SECS : u64 = 60;
+pub const DEFAULT_CONNECT_TIMEOUT_SECS : u64 = 30;
+pub const DEFAULT_LOGIN_RETRIES : u64 = 3;
+pub const DEFAULT_FAILOVER_MAX_FAILURES : u64 = 3;
+pub const DEFAULT_FAILOVER_PROBE_MINUTES : u64 = 30;
+pub const DEFAULT_SHARE_QUEUE_CAPACITY : u64 = 64;
+pub const DEFAULT_STATS_INTERVAL_SECS : u64 = 60;
+pub const DEFAULT_SPLIT_PRIMARY_PERCENT : u64 = 50;
+pub const DEFAULT_OFFLINE_SHARE_CAPACITY : u64 = 16;
+pub const DEFAULT_OFFLINE_SHARE_MAX_AGE_SECS : u64 = 60;
+
+pub fn default_agent() -> String {
+    format!("mithril/{}", env!("CARGO_PKG_VERSION"))
+}
+
+impl PoolConfig {
+    /// Settings for the given pool and wallet, all optional settings have their default value
+    pub fn new(pool_address: &str, wallet_address: &str, pool_password: &str) -> PoolConfig {
+        PoolConfig {
+            pool_address: pool_address.to_string(),
+            pool_password: pool_password.to_string(),
+            wallet_address: wallet_address.to_string(),
+            max_shares_per_job: None,
+            rig_id: None,
+            agent: Some(default_agent()),
+            keepalive_method: DEFAULT_KEEPALIVE_METHOD.to_string(),
+            keepalive_interval_secs: Some(DEFAULT_KEEPALIVE_INTERVAL_SECS),
+            keepalive_jitter_seconds: DEFAULT_KEEPALIVE_JITTER_SECONDS,
+            connect_timeout_secs: DEFAULT_CONNECT_TIMEOUT_SECS,
+            address_preference: AddressPreference::System,
+            login_retries: DEFAULT_LOGIN_RETRIES,
+            trace_file: None,
+            capture_session: None,
+            job_timeout_secs: None,
+            dry_run: false,
+            clamp_easy_targets: false,
+            tls: false,
+            tls_fingerprints: Vec::new(),
+            allowed_ips: Vec::new(),
+            solo_daemon_address: None,
+            self_select_daemon_address: None,
+            failover_pool_addresses: Vec::new(),
+            failover_max_failures: DEFAULT_FAILOVER_MAX_FAILURES,
+            failover_probe_minutes: DEFAULT_FAILOVER_PROBE_MINUTES,
+            secondary_pool_address: None,
+            pool_arbitration: ArbitrationPolicy::WarmStandby,
+            share_queue_capacity: DEFAULT_SHARE_QUEUE_CAPACITY,
+            share_queue_policy: QueuePolicy::Error,
+            late_share_grace_secs: None,
+            stats_interval_secs: Some(DEFAULT_STATS_INTERVAL_SECS),
+            min_pool_score: None,
+            offline_share_capacity: DEFAULT_OFFLINE_SHARE_CAPACITY,
+            offline_share_max_age_secs: DEFAULT_OFFLINE_SHARE_MAX_AGE_SECS,
+            submit_options: SubmitOptions::default(),
+            flood_guard: FloodGuardConfig::default()
+        }
+    }
 }
@@ -0,0 +1,99 @@
+use std::io;
+use std::thread;
+use std::panic;
+use std::panic::{AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use super::{StratumAction, StratumEvent};
+use super::client_state::{ClientState};
+
+enum Subscriber {
+    Channel(Sender<StratumAction>),
+    Callback(Box<dyn Fn(StratumAction) + Send>)
+}
+
+impl Subscriber {
+    /// Returns false if the subscriber is dead (receiver dropped or callback panicked)
+    fn deliver(&self, action: &StratumAction) -> bool {
+        match *self {
+            Subscriber::Channel(ref tx) => tx.send(action.clone()).is_ok(),
+            Subscriber::Callback(ref f) => {
+                let result = panic::catch_unwind(AssertUnwindSafe(|| f(action.clone())));
+                if result.is_err() {
+                    error!("action callback panicked, callback removed");
+                }
+                result.is_ok()
+            }
+        }
+    }
+}
+
+/// Receivers of stratum actions registered after the client was started
+pub struct Subscribers {
+    subscribers: Vec<Subscriber>
+}
+
+impl Subscribers {
+    pub fn new() -> Subscribers {
+        Subscribers{subscribers: Vec::new()}
+    }
+
+    pub fn subscribe(&mut self) -> Receiver<StratumAction> {
+        let (tx, rx) = channel();
+        self.subscribers.push(Subscriber::Channel(tx));
+        rx
+    }
+
+    pub fn subscribe_fn<F: Fn(StratumAction) + Send + 'static>(&mut self, f: F) {
+        self.subscribers.push(Subscriber::Callback(Box::new(f)));
+    }
+
+    /// Hands the action to all subscribers, dead subscribers are removed
+    pub fn dispatch(&mut self, action: &StratumAction) {
+        self.subscribers.retain(|s| s.deliver(action));
+    }
+
+    pub fn len(&self) -> usize {
+        self.subscribers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.subscribers.is_empty()
+    }
+}
+
+impl Default for Subscribers {
+    fn default() -> Subscribers {
+        Subscribers::new()
+    }
+}
+
+/// Starts the fan out of the received events to `action_rcv` and the `subscribers`.
+/// A dropped `action_rcv` does not end the dispatching, the thread ends when the
+/// returned sender is dropped.
+pub fn start_dispatch_thread(action_rcv: Sender<StratumEvent>, subscribers: Arc<Mutex<Subscribers>>) -> io::Result<(Sender<StratumEvent>, thread::JoinHandle<()>)> {
+    start_dispatch_thread_with_state(action_rcv, subscribers, Arc::new(Mutex::new(ClientState::new())))
+}
+
+/// Like `start_dispatch_thread`, `state` observes every action before it is dispatched
+pub fn start_dispatch_thread_with_state(action_rcv: Sender<StratumEvent>, subscribers: Arc<Mutex<Subscribers>>, state: Arc<Mutex<ClientState>>) -> io::Result<(Sender<StratumEvent>, thread::JoinHandle<()>)> {
+    let (tx, rx) = channel::<StratumEvent>();
+    let hnd = thread::Builder::new().name("Stratum dispatch thread".to_string()).spawn(move || {
+        let mut action_rcv = Some(action_rcv);
+        for event in rx {
+            let action = event.action.clone();
+            state.lock().expect("client state lock").observe(&action);
+            let receiver_gone = match action_rcv {
+                Some(ref rcv) => rcv.send(event).is_err(),
+                None => false
+            };
+            if receiver_gone {
+                info!("action receiver terminated, dispatching to subscribers only");
+                action_rcv = None;
+            }
+            subscribers.lock().expect("subscribers lock").dispatch(&action);
+        }
+        info!("stratum dispatch thread ended");
+    })?;
+    Ok((tx, hnd))
+}
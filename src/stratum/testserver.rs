@@ -0,0 +1,208 @@
+extern crate serde_json;
+
+use self::serde_json::{Map, Value};
+
+use std::io;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::time::{Duration};
+use super::stratum_data::{PoolConfig};
+
+/// Blob of a real job, long enough for the workers to hash
+pub const TEST_BLOB : &str = "0606fcb29bcf051b9c7bfc60c98885de404ef48f721f09b8f51d37faf280470880bd120d4e9e0500000000577192c076fed53a24372bc43a3bed1d448a061ad06a262ac5e7f6803a28ccc705";
+/// Target of difficulty 25000
+pub const TEST_TARGET : &str = "169f0200";
+
+/// Pool side of the stratum protocol on localhost, for end-to-end tests of the client.
+/// The test accepts the client connection and scripts the pool with the methods of
+/// `TestConnection`, every request of the client is read and checked explicitly.
+pub struct TestServer {
+    listener: TcpListener,
+    read_timeout: Duration,
+}
+
+/// A request sent by the client
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientRequest {
+    pub id: Option<u64>,
+    pub method: String,
+    pub params: Value,
+}
+
+/// A job sent to the client, on login or as `job` notification
+#[derive(Debug, Clone)]
+pub struct TestJob {
+    pub job_id: String,
+    pub blob: String,
+    pub target: String,
+}
+
+impl TestJob {
+    pub fn new(job_id: &str) -> TestJob {
+        TestJob{job_id: job_id.to_string(), blob: TEST_BLOB.to_string(), target: TEST_TARGET.to_string()}
+    }
+
+    fn to_json(&self) -> Value {
+        object(vec![
+            ("blob", Value::from(self.blob.clone())),
+            ("job_id", Value::from(self.job_id.clone())),
+            ("target", Value::from(self.target.clone())),
+        ])
+    }
+}
+
+/// One accepted client connection
+pub struct TestConnection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl TestServer {
+    /// Listens on a free port of 127.0.0.1
+    pub fn bind() -> io::Result<TestServer> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        Ok(TestServer{listener, read_timeout: Duration::from_secs(10)})
+    }
+
+    /// How long a `TestConnection` waits for the next request, 10 seconds by default
+    pub fn set_read_timeout(&mut self, timeout: Duration) {
+        self.read_timeout = timeout;
+    }
+
+    pub fn address(&self) -> io::Result<String> {
+        Ok(self.listener.local_addr()?.to_string())
+    }
+
+    /// Pool settings for connecting to this server, without keep alive requests
+    pub fn pool_config(&self) -> io::Result<PoolConfig> {
+        let mut pool_conf = PoolConfig::new(&self.address()?, "test_wallet", "x");
+        pool_conf.keepalive_interval_secs = None;
+        pool_conf.login_retries = 0;
+        Ok(pool_conf)
+    }
+
+    /// Waits for the next client connection
+    pub fn accept(&self) -> io::Result<TestConnection> {
+        let (stream, _) = self.listener.accept()?;
+        stream.set_read_timeout(Some(self.read_timeout))?;
+        Ok(TestConnection{reader: BufReader::new(stream.try_clone()?), writer: stream})
+    }
+}
+
+impl TestConnection {
+    /// Reads the next request, an error if the client closed the connection
+    pub fn read_request(&mut self) -> io::Result<ClientRequest> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "client closed the connection"));
+        }
+        let json : Value = serde_json::from_str(&line).map_err(|e| Error::new(ErrorKind::InvalidData, format!("{} in {}", e, line.trim_end())))?;
+        let method = match json.get("method").and_then(Value::as_str) {
+            Some(method) => method.to_string(),
+            None => return Err(Error::new(ErrorKind::InvalidData, format!("request without method: {}", line.trim_end())))
+        };
+        Ok(ClientRequest{
+            id: json.get("id").and_then(Value::as_u64),
+            method,
+            params: json.get("params").cloned().unwrap_or(Value::Null)
+        })
+    }
+
+    /// Reads the next request and checks its method
+    pub fn expect(&mut self, method: &str) -> io::Result<ClientRequest> {
+        let request = self.read_request()?;
+        if request.method != method {
+            return Err(Error::new(ErrorKind::InvalidData, format!("expected {} request, got {:?}", method, request)));
+        }
+        Ok(request)
+    }
+
+    /// Sends a raw line, e.g. for malformed responses
+    pub fn send_line(&mut self, line: &str) -> io::Result<()> {
+        writeln!(self.writer, "{}", line)?;
+        self.writer.flush()
+    }
+
+    pub fn send(&mut self, json: &Value) -> io::Result<()> {
+        self.send_line(&json.to_string())
+    }
+
+    /// Reads the login request and logs the client in with `job` as first job
+    pub fn login(&mut self, miner_id: &str, job: &TestJob) -> io::Result<ClientRequest> {
+        let request = self.expect("login")?;
+        let result = object(vec![
+            ("id", Value::from(miner_id)),
+            ("job", job.to_json()),
+            ("status", Value::from("OK")),
+        ]);
+        self.respond(request.id, result)?;
+        Ok(request)
+    }
+
+    /// Reads the login request and answers it with an error
+    pub fn reject_login(&mut self, code: i64, message: &str) -> io::Result<ClientRequest> {
+        let request = self.expect("login")?;
+        self.respond_error(request.id, code, message)?;
+        Ok(request)
+    }
+
+    /// Sends a new job with a `job` notification
+    pub fn send_job(&mut self, job: &TestJob) -> io::Result<()> {
+        self.send(&object(vec![
+            ("jsonrpc", Value::from("2.0")),
+            ("method", Value::from("job")),
+            ("params", job.to_json()),
+        ]))
+    }
+
+    /// Reads the next share and accepts it
+    pub fn accept_share(&mut self) -> io::Result<ClientRequest> {
+        let request = self.expect("submit")?;
+        self.respond(request.id, object(vec![("status", Value::from("OK"))]))?;
+        Ok(request)
+    }
+
+    /// Reads the next share and rejects it
+    pub fn reject_share(&mut self, code: i64, message: &str) -> io::Result<ClientRequest> {
+        let request = self.expect("submit")?;
+        self.respond_error(request.id, code, message)?;
+        Ok(request)
+    }
+
+    /// Reads the next keep alive request (with the default method name) and answers it
+    pub fn answer_keepalive(&mut self) -> io::Result<ClientRequest> {
+        let request = self.expect("keepalived")?;
+        self.respond(request.id, object(vec![("status", Value::from("KEEPALIVED"))]))?;
+        Ok(request)
+    }
+
+    pub fn respond(&mut self, id: Option<u64>, result: Value) -> io::Result<()> {
+        self.send(&object(vec![
+            ("id", id.map(Value::from).unwrap_or(Value::Null)),
+            ("jsonrpc", Value::from("2.0")),
+            ("error", Value::Null),
+            ("result", result),
+        ]))
+    }
+
+    pub fn respond_error(&mut self, id: Option<u64>, code: i64, message: &str) -> io::Result<()> {
+        self.send(&object(vec![
+            ("id", id.map(Value::from).unwrap_or(Value::Null)),
+            ("jsonrpc", Value::from("2.0")),
+            ("error", object(vec![("code", Value::from(code)), ("message", Value::from(message))])),
+        ]))
+    }
+
+    /// Closes the connection from the pool side
+    pub fn close(self) -> io::Result<()> {
+        self.writer.shutdown(Shutdown::Both)
+    }
+}
+
+fn object(fields: Vec<(&str, Value)>) -> Value {
+    let mut map = Map::new();
+    for (key, value) in fields {
+        map.insert(key.to_string(), value);
+    }
+    Value::Object(map)
+}
@@ -0,0 +1,85 @@
+extern crate native_tls;
+
+use self::native_tls::{TlsConnector, TlsStream};
+
+use std::io;
+use std::io::{Read, Write, Error, ErrorKind};
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use super::pinning;
+
+/// How long a read holds the stream lock before giving writers a chance
+const READ_POLL_INTERVAL_MILLIS : u64 = 100;
+
+/// Pause between two reads without data, without it the reader takes the lock again
+/// before a waiting writer gets it
+const READ_BACKOFF_MILLIS : u64 = 5;
+
+/// A TLS stream that can be shared between the send and the receive thread.
+/// A TLS session cannot be split into independent read and write halves like a
+/// `TcpStream`, so both sides lock the session. Reads use a short timeout on the
+/// underlying socket so that a pending read does not block writes.
+#[derive(Clone)]
+pub struct SharedTlsStream {
+    inner: Arc<Mutex<TlsStream<TcpStream>>>
+}
+
+/// Establishes a TLS session over `stream`, validating the certificate of the
+/// pool against `pool_address` (also used for SNI). If `pinned_fingerprints` is not
+/// empty the certificate additionally has to match one of them.
+pub fn connect(pool_address: &str, stream: TcpStream, pinned_fingerprints: &[String]) -> io::Result<SharedTlsStream> {
+    let connector = TlsConnector::new().map_err(|e| Error::new(ErrorKind::Other, e))?;
+    let tls_stream = connector.connect(host_name(pool_address), stream)
+                              .map_err(|e| Error::new(ErrorKind::Other, format!("TLS handshake failed: {}", e)))?;
+    if !pinned_fingerprints.is_empty() {
+        let cert = tls_stream.peer_certificate().map_err(|e| Error::new(ErrorKind::Other, e))?
+                             .ok_or_else(|| Error::new(ErrorKind::PermissionDenied, "pool sent no certificate"))?;
+        let der = cert.to_der().map_err(|e| Error::new(ErrorKind::Other, e))?;
+        pinning::check_fingerprint(&der, pinned_fingerprints)?;
+    }
+    tls_stream.get_ref().set_read_timeout(Some(Duration::from_millis(READ_POLL_INTERVAL_MILLIS)))?;
+    Ok(SharedTlsStream{inner: Arc::new(Mutex::new(tls_stream))})
+}
+
+/// Strips the port from a `host:port` pool address, and the brackets from an IPv6
+/// address (`[::1]:3333` is `::1`)
+pub fn host_name(pool_address: &str) -> &str {
+    if pool_address.starts_with('[') {
+        return match pool_address.find(']') {
+            Some(ix) => &pool_address[1..ix],
+            None => pool_address
+        };
+    }
+    match pool_address.rfind(':') {
+        //an IPv6 address without brackets has no port
+        Some(ix) if pool_address[..ix].contains(':') => pool_address,
+        Some(ix) => &pool_address[..ix],
+        None => pool_address
+    }
+}
+
+impl Read for SharedTlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let result = self.inner.lock().expect("tls stream lock").read(buf);
+            match result {
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {
+                    thread::sleep(Duration::from_millis(READ_BACKOFF_MILLIS));
+                },
+                other => return other
+            }
+        }
+    }
+}
+
+impl Write for SharedTlsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.lock().expect("tls stream lock").write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.lock().expect("tls stream lock").flush()
+    }
+}
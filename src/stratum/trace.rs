@@ -0,0 +1,180 @@
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{BufRead, Read, Write};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use log::Level;
+use super::capture::{Direction, SessionCapture};
+
+/// Lines longer than this are cut off in the trace
+const MAX_TRACED_LINE : usize = 64 * 1024;
+/// Replaces the wallet address in traced lines
+const WALLET_PLACEHOLDER : &str = "<wallet>";
+const SENT_PREFIX : &str = "> ";
+const RECEIVED_PREFIX : &str = "< ";
+
+/// Connections opened after `install_switch` always get a tracer
+static SWITCH_INSTALLED : AtomicBool = AtomicBool::new(false);
+/// Raw lines are logged at INFO level (not only TRACE) while set
+static RAW_TRACE : AtomicBool = AtomicBool::new(false);
+
+/// Lets the raw trace be switched on at runtime (see `set_raw_trace`), e.g. from the console,
+/// for the connections opened from now on
+pub fn install_switch() {
+    SWITCH_INSTALLED.store(true, Ordering::SeqCst);
+}
+
+/// Logs the raw lines of every traced connection at INFO level while `enabled`
+pub fn set_raw_trace(enabled: bool) {
+    RAW_TRACE.store(enabled, Ordering::SeqCst);
+}
+
+pub fn raw_trace() -> bool {
+    RAW_TRACE.load(Ordering::SeqCst)
+}
+
+/// Writes the raw lines exchanged with the pool to the log (TRACE level) and,
+/// if configured, to a capture file. Sent lines are prefixed with `> `,
+/// received lines with `< `. The wallet address is redacted.
+/// A session capture (see `capture`) records the same lines with timing.
+pub struct Tracer {
+    wallet_address: String,
+    capture: Option<Mutex<File>>,
+    session: Option<Mutex<SessionCapture>>,
+}
+
+impl Tracer {
+    /// Returns `None` if neither TRACE logging, a capture file nor the raw trace switch is enabled
+    pub fn open(wallet_address: &str, capture_file: Option<&str>) -> io::Result<Option<Arc<Tracer>>> {
+        Tracer::open_with_session(wallet_address, capture_file, None)
+    }
+
+    /// Like `open`, additionally recording a session capture (file, pool address) of the
+    /// connection, the tracer is always returned then
+    pub fn open_with_session(wallet_address: &str, capture_file: Option<&str>, session: Option<(&str, &str)>) -> io::Result<Option<Arc<Tracer>>> {
+        let capture = match capture_file {
+            Some(path) => Some(Mutex::new(OpenOptions::new().create(true).append(true).open(path)?)),
+            None => None
+        };
+        let session = match session {
+            Some((path, pool_address)) => Some(Mutex::new(SessionCapture::open(path, pool_address, wallet_address)?)),
+            None => None
+        };
+        if capture.is_none() && session.is_none() && !log_enabled!(Level::Trace) && !SWITCH_INSTALLED.load(Ordering::SeqCst) {
+            return Ok(None);
+        }
+        Ok(Some(Arc::new(Tracer{wallet_address: wallet_address.to_string(), capture, session})))
+    }
+
+    fn line(&self, prefix: &str, bytes: &[u8]) {
+        let raw = String::from_utf8_lossy(bytes);
+        let line = redact(&raw, &self.wallet_address);
+        if raw_trace() {
+            info!("{}{}", prefix, line);
+        } else {
+            trace!("{}{}", prefix, line);
+        }
+        if let Some(ref capture) = self.capture {
+            let mut file = capture.lock().expect("capture file lock");
+            if let Err(e) = writeln!(file, "{}{}", prefix, line) {
+                warn!("writing stratum capture file failed: {}", e);
+            }
+        }
+        if let Some(ref session) = self.session {
+            let dir = if prefix == SENT_PREFIX { Direction::Sent } else { Direction::Received };
+            if let Err(e) = session.lock().expect("session capture lock").record(dir, &raw) {
+                warn!("writing stratum session capture failed: {}", e);
+            }
+        }
+    }
+}
+
+/// Replaces every occurrence of the wallet address in the line
+pub fn redact(line: &str, wallet_address: &str) -> String {
+    if wallet_address.is_empty() {
+        return line.to_string();
+    }
+    line.replace(wallet_address, WALLET_PLACEHOLDER)
+}
+
+/// Collects bytes until a full line is available
+struct LineBuffer {
+    partial: Vec<u8>,
+}
+
+impl LineBuffer {
+    fn new() -> LineBuffer {
+        LineBuffer{partial: Vec::new()}
+    }
+
+    fn feed(&mut self, bytes: &[u8], tracer: &Tracer, prefix: &str) {
+        for b in bytes {
+            if *b == b'\n' {
+                tracer.line(prefix, &self.partial);
+                self.partial.clear();
+            } else if self.partial.len() < MAX_TRACED_LINE {
+                self.partial.push(*b);
+            }
+        }
+    }
+}
+
+/// Traces every line read from the pool
+pub struct TracingReader<R> {
+    inner: R,
+    tracer: Arc<Tracer>,
+    lines: LineBuffer,
+}
+
+impl<R: BufRead> TracingReader<R> {
+    pub fn new(inner: R, tracer: Arc<Tracer>) -> TracingReader<R> {
+        TracingReader{inner, tracer, lines: LineBuffer::new()}
+    }
+}
+
+impl<R: BufRead> Read for TracingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.lines.feed(&buf[..n], &self.tracer, RECEIVED_PREFIX);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for TracingReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if let Ok(available) = self.inner.fill_buf() {
+            let n = amt.min(available.len());
+            self.lines.feed(&available[..n], &self.tracer, RECEIVED_PREFIX);
+        }
+        self.inner.consume(amt);
+    }
+}
+
+/// Traces every line sent to the pool
+pub struct TracingWriter<W> {
+    inner: W,
+    tracer: Arc<Tracer>,
+    lines: LineBuffer,
+}
+
+impl<W: Write> TracingWriter<W> {
+    pub fn new(inner: W, tracer: Arc<Tracer>) -> TracingWriter<W> {
+        TracingWriter{inner, tracer, lines: LineBuffer::new()}
+    }
+}
+
+impl<W: Write> Write for TracingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.lines.feed(&buf[..n], &self.tracer, SENT_PREFIX);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
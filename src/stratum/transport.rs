@@ -0,0 +1,208 @@
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Cursor, Read, Write, Error, ErrorKind};
+use std::net::{Shutdown, TcpStream};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use super::stratum_data::{PoolConfig};
+use super::{resolve, socket_stats, tls, trace};
+
+type StreamReader = Box<dyn BufRead + Send>;
+type StreamWriter = Box<dyn Write + Send>;
+
+/// A line read from a transport
+#[derive(Debug, PartialEq)]
+pub enum LimitedLine {
+    /// the line without the newline
+    Line(Vec<u8>),
+    /// the line exceeded the limit and was discarded
+    TooLong,
+    Eof
+}
+
+/// Line based connection to the pool. The receive thread reads while the send
+/// thread writes, so reading must not block writing. `shutdown` ends a pending
+/// `read_line` from another thread.
+pub trait StratumTransport: Send + Sync {
+    fn connect(pool_conf: &PoolConfig) -> io::Result<Self> where Self: Sized;
+
+    /// Reads one line with at most `max_length` bytes (excluding the newline)
+    fn read_line(&self, max_length: usize) -> io::Result<LimitedLine>;
+
+    /// Sends `line` followed by a newline
+    fn write_line(&self, line: &str) -> io::Result<()>;
+
+    fn shutdown(&self) -> io::Result<()>;
+
+    /// TCP level statistics of the connection (None if not available)
+    fn socket_stats(&self) -> Option<socket_stats::SocketStats> {
+        None
+    }
+}
+
+/// TCP connection to the pool, optionally with TLS and traced (see `trace`)
+pub struct TcpTransport {
+    /// only used for shutting down the connection and reading its statistics
+    stream: TcpStream,
+    reader: Mutex<StreamReader>,
+    writer: Mutex<StreamWriter>,
+}
+
+impl StratumTransport for TcpTransport {
+    fn connect(pool_conf: &PoolConfig) -> io::Result<TcpTransport> {
+        let stream = resolve::connect_allowed(&pool_conf.pool_address, Duration::from_secs(pool_conf.connect_timeout_secs), pool_conf.address_preference, &pool_conf.allowed_ips)?;
+        stream.set_read_timeout(None)?;
+        stream.set_write_timeout(Some(Duration::from_secs(10)))?;
+
+        let (reader, writer) : (StreamReader, StreamWriter) = if pool_conf.tls {
+            let tls_stream = tls::connect(&pool_conf.pool_address, stream.try_clone()?, &pool_conf.tls_fingerprints)?;
+            (Box::new(BufReader::new(tls_stream.clone())), Box::new(BufWriter::new(tls_stream)))
+        } else {
+            (Box::new(BufReader::new(stream.try_clone()?)), Box::new(BufWriter::new(stream.try_clone()?)))
+        };
+
+        let trace_file = pool_conf.trace_file.as_ref().map(|f| f.as_str());
+        let session = pool_conf.capture_session.as_ref().map(|f| (f.as_str(), pool_conf.pool_address.as_str()));
+        let (reader, writer) = match trace::Tracer::open_with_session(&pool_conf.wallet_address, trace_file, session)? {
+            Some(tracer) => {
+                let reader : StreamReader = Box::new(trace::TracingReader::new(reader, tracer.clone()));
+                let writer : StreamWriter = Box::new(trace::TracingWriter::new(writer, tracer));
+                (reader, writer)
+            },
+            None => (reader, writer)
+        };
+        Ok(TcpTransport{stream, reader: Mutex::new(reader), writer: Mutex::new(writer)})
+    }
+
+    fn read_line(&self, max_length: usize) -> io::Result<LimitedLine> {
+        read_line_limited(&mut *self.reader.lock().expect("transport reader lock"), max_length)
+    }
+
+    fn write_line(&self, line: &str) -> io::Result<()> {
+        let mut writer = self.writer.lock().expect("transport writer lock");
+        writeln!(writer, "{}", line)?;
+        writer.flush()
+    }
+
+    fn shutdown(&self) -> io::Result<()> {
+        self.stream.shutdown(Shutdown::Both)
+    }
+
+    fn socket_stats(&self) -> Option<socket_stats::SocketStats> {
+        socket_stats::socket_stats(&self.stream)
+    }
+}
+
+/// Transport reading from a fixed input and recording the written lines, for tests
+pub struct MemoryTransport {
+    input: Mutex<Cursor<Vec<u8>>>,
+    written: Mutex<Vec<String>>,
+    shut_down: AtomicBool,
+}
+
+impl MemoryTransport {
+    /// `input` is what the "pool" sends, reading ends with EOF afterwards
+    pub fn new(input: &str) -> MemoryTransport {
+        MemoryTransport{input: Mutex::new(Cursor::new(input.as_bytes().to_vec())), written: Mutex::new(Vec::new()), shut_down: AtomicBool::new(false)}
+    }
+
+    /// The lines written so far
+    pub fn written(&self) -> Vec<String> {
+        self.written.lock().expect("written lines lock").clone()
+    }
+
+    pub fn is_shut_down(&self) -> bool {
+        self.shut_down.load(Ordering::SeqCst)
+    }
+}
+
+impl StratumTransport for MemoryTransport {
+    fn connect(_pool_conf: &PoolConfig) -> io::Result<MemoryTransport> {
+        Err(Error::new(ErrorKind::Other, "in-memory transport has no pool to connect to"))
+    }
+
+    fn read_line(&self, max_length: usize) -> io::Result<LimitedLine> {
+        if self.is_shut_down() {
+            return Ok(LimitedLine::Eof);
+        }
+        read_line_limited(&mut *self.input.lock().expect("input lock"), max_length)
+    }
+
+    fn write_line(&self, line: &str) -> io::Result<()> {
+        if self.is_shut_down() {
+            return Err(Error::new(ErrorKind::BrokenPipe, "transport shut down"));
+        }
+        self.written.lock().expect("written lines lock").push(line.to_string());
+        Ok(())
+    }
+
+    fn shutdown(&self) -> io::Result<()> {
+        self.shut_down.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+/// Adapts a transport to `Write` for the send path, complete lines are
+/// written to the transport on `flush`.
+pub struct TransportWriter<'a> {
+    transport: &'a dyn StratumTransport,
+    buf: Vec<u8>,
+}
+
+impl<'a> TransportWriter<'a> {
+    pub fn new(transport: &'a dyn StratumTransport) -> TransportWriter<'a> {
+        TransportWriter{transport, buf: Vec::new()}
+    }
+}
+
+impl<'a> Write for TransportWriter<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        while let Some(ix) = self.buf.iter().position(|b| *b == b'\n') {
+            let line : Vec<u8> = self.buf.drain(..=ix).collect();
+            let line = String::from_utf8(line).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+            self.transport.write_line(line.trim_end_matches('\n'))?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads one line with at most `limit` bytes (excluding the newline). The remainder
+/// of a longer line is consumed and discarded.
+pub fn read_line_limited<R: BufRead>(reader: &mut R, limit: usize) -> Result<LimitedLine, Error> {
+    let mut buf = Vec::new();
+    let n = reader.by_ref().take(limit as u64 + 1).read_until(b'\n', &mut buf)?;
+    if n == 0 {
+        return Ok(LimitedLine::Eof);
+    }
+    if buf.last() == Some(&b'\n') {
+        buf.pop();
+    } else if buf.len() > limit {
+        discard_until_newline(reader)?;
+        return Ok(LimitedLine::TooLong);
+    }
+    Ok(LimitedLine::Line(buf))
+}
+
+fn discard_until_newline<R: BufRead>(reader: &mut R) -> Result<(), Error> {
+    loop {
+        let (found, used) = {
+            let available = reader.fill_buf()?;
+            if available.is_empty() {
+                return Ok(());
+            }
+            match available.iter().position(|b| *b == b'\n') {
+                Some(ix) => (true, ix + 1),
+                None => (false, available.len())
+            }
+        };
+        reader.consume(used);
+        if found {
+            return Ok(());
+        }
+    }
+}
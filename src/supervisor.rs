@@ -0,0 +1,115 @@
+use std::any::{Any};
+use std::cmp;
+use std::collections::{VecDeque};
+use std::panic;
+use std::panic::{AssertUnwindSafe};
+use std::process::{Command};
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_MAX_RESTARTS : u64 = 5;
+pub const DEFAULT_WINDOW_MINUTES : u64 = 30;
+pub const DEFAULT_INITIAL_BACKOFF_SECS : u64 = 5;
+pub const DEFAULT_MAX_BACKOFF_SECS : u64 = 300;
+
+/// `[supervisor]` section, restarts of the mining pipeline after a panic
+#[derive(Clone, Debug)]
+pub struct SupervisorConfig {
+    pub enabled: bool,
+    /// more restarts than this within `window_minutes` stop mithril
+    pub max_restarts: u64,
+    pub window_minutes: u64,
+    /// wait before the first restart of a window, doubled for every further one
+    pub initial_backoff_secs: u64,
+    pub max_backoff_secs: u64,
+    /// run with `sh -c` when the restarts are given up, the reason is in `MITHRIL_ALERT`
+    pub alert_command: Option<String>,
+}
+
+impl Default for SupervisorConfig {
+    fn default() -> SupervisorConfig {
+        SupervisorConfig{
+            enabled: false,
+            max_restarts: DEFAULT_MAX_RESTARTS,
+            window_minutes: DEFAULT_WINDOW_MINUTES,
+            initial_backoff_secs: DEFAULT_INITIAL_BACKOFF_SECS,
+            max_backoff_secs: DEFAULT_MAX_BACKOFF_SECS,
+            alert_command: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Decision {
+    /// restart the pipeline after the backoff
+    Restart(Duration),
+    /// crash loop, too many restarts within the window
+    GiveUp,
+}
+
+/// Decides on restarts of the mining pipeline. Failed logins are retried by the pipeline
+/// itself and are not recorded here.
+pub struct Supervisor {
+    conf: SupervisorConfig,
+    /// restarts within the window, oldest first
+    restarts: VecDeque<Instant>,
+}
+
+impl Supervisor {
+    pub fn new(conf: SupervisorConfig) -> Supervisor {
+        Supervisor{conf, restarts: VecDeque::new()}
+    }
+
+    /// Records a failure of the pipeline at `now`. The backoff doubles with each restart
+    /// in the window, failures older than the window are forgotten.
+    pub fn record_failure(&mut self, now: Instant) -> Decision {
+        let window = Duration::from_secs(self.conf.window_minutes * 60);
+        while self.restarts.front().map(|t| now.duration_since(*t) >= window).unwrap_or(false) {
+            self.restarts.pop_front();
+        }
+        if self.restarts.len() as u64 >= self.conf.max_restarts {
+            return Decision::GiveUp;
+        }
+        self.restarts.push_back(now);
+        Decision::Restart(backoff(&self.conf, self.restarts.len() as u64))
+    }
+
+    /// Restarts within the current window
+    pub fn restarts(&self) -> usize {
+        self.restarts.len()
+    }
+
+    /// Runs the alert command with the reason, if one is configured
+    pub fn alert(&self, reason: &str) {
+        let command = match self.conf.alert_command {
+            Some(ref command) => command,
+            None => return
+        };
+        match Command::new("sh").arg("-c").arg(command).env("MITHRIL_ALERT", reason).status() {
+            Ok(status) if status.success() => info!("alert command run"),
+            Ok(status) => warn!("alert command failed: {}", status),
+            Err(e) => warn!("alert command not run: {}", e)
+        }
+    }
+}
+
+/// Wait before the `restart`th restart (1 for the first) of a window
+pub fn backoff(conf: &SupervisorConfig, restart: u64) -> Duration {
+    let doublings = cmp::min(restart.saturating_sub(1), 32) as u32;
+    let secs = conf.initial_backoff_secs.saturating_mul(1u64 << doublings);
+    Duration::from_secs(cmp::min(secs, conf.max_backoff_secs))
+}
+
+/// Runs `f`, a panic is returned as error with its message
+pub fn catch_panic<T, F: FnOnce() -> T>(f: F) -> Result<T, String> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| panic_message(&*payload))
+}
+
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
@@ -12,7 +12,9 @@ const DONATION_THRESHOLD : f64 = 1.0/10.0;
 #[derive(Debug, PartialEq)]
 pub enum TickAction {
     ArmChange,
-    DonationHashing
+    DonationHashing,
+    /// the donation period is over, mining continues on the configured pool
+    DonationEnd
 }
 
 pub fn interval_mod_setup(worker_conf: &WorkerConfig, donation_conf: &DonationConfig) -> (u64, Option<u64>) {
@@ -40,6 +42,18 @@ pub fn interval_mod_setup(worker_conf: &WorkerConfig, donation_conf: &DonationCo
     (interval, donation_mod)
 }
 
+/// The action of tick number `tick` (counting from 1). The tick after a donation
+/// period ends it, unless everything is donated.
+pub fn tick_action(tick: u64, donation_mod: Option<u64>, after_donation: bool) -> TickAction {
+    if after_donation {
+        return TickAction::DonationEnd;
+    }
+    match donation_mod {
+        Some(d_mod) if tick % d_mod == 0 => TickAction::DonationHashing,
+        _ => TickAction::ArmChange
+    }
+}
+
 /// clock for bandit arm change and donation
 pub fn setup(worker_conf: &WorkerConfig, donation_conf: &DonationConfig) -> Receiver<TickAction>{
     let (clock_tx, clock_rx) = channel();
@@ -53,18 +67,12 @@ pub fn setup(worker_conf: &WorkerConfig, donation_conf: &DonationConfig) -> Rece
     thread::Builder::new().name("clock signal thread".to_string()).spawn(move ||{
 
         let mut arm_changes = 1;
+        let mut donating = false;
         loop {
             thread::sleep(Duration::from_secs(interval));
 
-            let action = if let Some(d_mod) = donation_mod {
-                if arm_changes % d_mod == 0 {
-                    TickAction::DonationHashing
-                } else {
-                    TickAction::ArmChange
-                }
-            } else {
-                TickAction::ArmChange
-            };
+            let action = tick_action(arm_changes, donation_mod, donating && donation_percentage < 100.0);
+            donating = action == TickAction::DonationHashing;
 
             interval = if action == TickAction::DonationHashing {
                 (donation_percentage * 60.0).ceil() as u64
@@ -67,12 +67,54 @@ impl u64x2 {
         }
     }
 
+    /// Creates u64x2 from 16 little-endian bytes, independent of the target endianness
+    pub fn from_le_bytes(bytes: [u8; 16]) -> Self {
+        let mut lo = [0u8; 8];
+        let mut hi = [0u8; 8];
+        lo.copy_from_slice(&bytes[0..8]);
+        hi.copy_from_slice(&bytes[8..16]);
+        u64x2(u64::from_le_bytes(lo), u64::from_le_bytes(hi))
+    }
+
+    /// Returns the 16 little-endian bytes, independent of the target endianness
+    pub fn to_le_bytes(self) -> [u8; 16] {
+        let mut bytes = [0u8; 16];
+        bytes[0..8].copy_from_slice(&self.0.to_le_bytes());
+        bytes[8..16].copy_from_slice(&self.1.to_le_bytes());
+        bytes
+    }
+
     pub fn to_u128(self) -> u128 {
         let mut r = u128::from(self.1);
         r <<= 64;
         r |= u128::from(self.0);
         r
     }
+
+    /// Exchanges the two 64 bit lanes
+    #[inline(always)]
+    pub fn swap(self) -> Self {
+        u64x2(self.1, self.0)
+    }
+
+    /// Reverses the byte order within each lane
+    #[inline(always)]
+    pub fn swap_bytes(self) -> Self {
+        u64x2(self.0.swap_bytes(), self.1.swap_bytes())
+    }
+
+    /// Lane wise addition, wrapping on overflow
+    #[inline(always)]
+    pub fn wrapping_add(self, rhs: Self) -> Self {
+        u64x2(self.0.wrapping_add(rhs.0), self.1.wrapping_add(rhs.1))
+    }
+
+    /// Full 128 bit product of the low lanes, returned as `u64x2(high, low)`
+    #[inline(always)]
+    pub fn widening_mul_lo(self, rhs: Self) -> Self {
+        let r = u128::from(self.0) * u128::from(rhs.0);
+        u64x2((r >> 64) as u64, r as u64)
+    }
 }
 
 impl fmt::Debug for u64x2 {
@@ -84,15 +126,27 @@ impl fmt::Debug for u64x2 {
 impl BitXor for u64x2 {
     type Output = Self;
 
+    #[cfg(target_arch = "x86_64")]
     fn bitxor(self, rhs: Self) -> Self {
         sse::_mm_xor_si128(self, rhs)
     }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    fn bitxor(self, rhs: Self) -> Self {
+        u64x2(self.0 ^ rhs.0, self.1 ^ rhs.1)
+    }
 }
 
 impl Add for u64x2 {
     type Output = Self;
 
+    #[cfg(target_arch = "x86_64")]
     fn add(self, rhs: Self) -> Self {
         sse::_mm_add_epi64(self, rhs)
     }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    fn add(self, rhs: Self) -> Self {
+        self.wrapping_add(rhs)
+    }
 }
@@ -0,0 +1,272 @@
+extern crate ed25519_dalek;
+extern crate native_tls;
+extern crate serde_json;
+extern crate sha2;
+
+use self::ed25519_dalek::{PublicKey, Signature};
+use self::native_tls::{TlsConnector};
+use self::sha2::{Digest, Sha256};
+
+use std::fs;
+use std::io;
+use std::io::{Read, Write, Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration};
+use hex;
+use stratum;
+
+/// The detached signature of the manifest is expected at the manifest URL plus this suffix
+pub const SIGNATURE_SUFFIX : &str = ".sig";
+const HTTP_TIMEOUT_SECS : u64 = 30;
+const MAX_REDIRECTS : usize = 3;
+/// Responses larger than this (the downloaded binary included) are refused
+pub const MAX_RESPONSE_BYTES : u64 = 64 * 1024 * 1024;
+
+/// `[update]` section, checking for new versions is opt-in
+#[derive(Clone, Debug)]
+pub struct UpdateConfig {
+    /// look for a new version on startup
+    pub check: bool,
+    /// URL of the release manifest (JSON with version, url and sha256 of the binary)
+    pub endpoint: String,
+    /// Ed25519 key (hex) the manifest has to be signed with
+    pub public_key: String,
+    /// download a newer binary next to the running one, otherwise only a note is logged
+    pub download: bool,
+}
+
+/// A release as described by a verified manifest
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Release {
+    pub version: String,
+    /// where the binary can be downloaded
+    pub url: String,
+    /// SHA-256 (hex) of the binary
+    pub sha256: String,
+}
+
+#[derive(Debug, Error)]
+pub enum UpdateError {
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+    #[error("signature check failed: {0}")]
+    Signature(String),
+    #[error("invalid release manifest: {0}")]
+    Manifest(String),
+    #[error("checksum of the download is {actual}, the manifest says {expected}")]
+    Checksum{expected: String, actual: String},
+}
+
+/// Parses a `major.minor.patch` version, a pre-release suffix (e.g. `-beta`) is ignored.
+/// Versions with characters other than `[0-9A-Za-z.-]` are invalid.
+pub fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let version = version.trim().trim_start_matches('v');
+    if !version.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-') {
+        return None;
+    }
+    let core = version.split('-').next().unwrap_or("");
+    let parts = core.split('.').map(|p| p.parse::<u64>().ok()).collect::<Option<Vec<u64>>>()?;
+    match parts.as_slice() {
+        [major, minor, patch] => Some((*major, *minor, *patch)),
+        _ => None
+    }
+}
+
+pub fn is_newer(candidate: &str, current: &str) -> bool {
+    match (parse_version(candidate), parse_version(current)) {
+        (Some(candidate), Some(current)) => candidate > current,
+        _ => false
+    }
+}
+
+/// Checks the detached signature (64 bytes, raw or hex) of the manifest and parses it
+pub fn verify_manifest(manifest: &[u8], signature: &[u8], public_key_hex: &str) -> Result<Release, UpdateError> {
+    let key_bytes = hex::decode(public_key_hex.trim()).map_err(|e| UpdateError::Signature(format!("public key is not hex: {:?}", e)))?;
+    let public_key = PublicKey::from_bytes(&key_bytes).map_err(|e| UpdateError::Signature(format!("invalid public key: {}", e)))?;
+    let signature_bytes = match String::from_utf8(signature.to_vec()) {
+        Ok(ref text) if text.trim().len() == 128 => hex::decode(text.trim()).map_err(|e| UpdateError::Signature(format!("signature is not hex: {:?}", e)))?,
+        _ => signature.to_vec()
+    };
+    let signature = Signature::from_bytes(&signature_bytes).map_err(|e| UpdateError::Signature(format!("invalid signature: {}", e)))?;
+    public_key.verify_strict(manifest, &signature).map_err(|_| UpdateError::Signature("manifest was not signed with the configured key".to_string()))?;
+
+    let release : Release = serde_json::from_slice(manifest).map_err(|e| UpdateError::Manifest(e.to_string()))?;
+    if parse_version(&release.version).is_none() {
+        return Err(UpdateError::Manifest(format!("invalid version {}", release.version)));
+    }
+    Ok(release)
+}
+
+/// Fetches and verifies the manifest, returns the release if it is newer than this binary
+pub fn check(conf: &UpdateConfig) -> Result<Option<Release>, UpdateError> {
+    let manifest = http_get(&conf.endpoint)?;
+    let signature = http_get(&format!("{}{}", conf.endpoint, SIGNATURE_SUFFIX))?;
+    let release = verify_manifest(&manifest, &signature, &conf.public_key)?;
+    if is_newer(&release.version, env!("CARGO_PKG_VERSION")) {
+        Ok(Some(release))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Downloads the binary of the release into `dir` as `mithril-<major>.<minor>.<patch>`,
+/// if its checksum matches the (signed) manifest. The running binary is not replaced.
+pub fn download(release: &Release, dir: &Path) -> Result<PathBuf, UpdateError> {
+    let (major, minor, patch) = parse_version(&release.version)
+        .ok_or_else(|| UpdateError::Manifest(format!("invalid version {}", release.version)))?;
+    let binary = http_get(&release.url)?;
+    let actual = hex::encode(&Sha256::digest(&binary));
+    if actual != release.sha256.to_lowercase() {
+        return Err(UpdateError::Checksum{expected: release.sha256.clone(), actual});
+    }
+    let path = dir.join(format!("mithril-{}.{}.{}", major, minor, patch));
+    fs::write(&path, &binary)?;
+    make_executable(&path)?;
+    Ok(path)
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Runs the check in the background, mining does not wait for it. Nothing is done
+/// if checking is not enabled.
+pub fn spawn_check(conf: UpdateConfig, download_dir: PathBuf) -> Option<thread::JoinHandle<()>> {
+    if !conf.check {
+        return None;
+    }
+    let spawned = thread::Builder::new().name("update check thread".to_string()).spawn(move || {
+        match check(&conf) {
+            Ok(Some(release)) if conf.download => match download(&release, &download_dir) {
+                Ok(path) => info!("mithril {} downloaded to {}, restart with it to upgrade", release.version, path.display()),
+                Err(e) => warn!("download of mithril {} failed: {}", release.version, e)
+            },
+            Ok(Some(release)) => info!("mithril {} is available (running {}), see {}", release.version, env!("CARGO_PKG_VERSION"), release.url),
+            Ok(None) => info!("mithril {} is up to date", env!("CARGO_PKG_VERSION")),
+            Err(e) => warn!("update check failed: {}", e)
+        }
+    });
+    match spawned {
+        Ok(hnd) => Some(hnd),
+        Err(e) => {
+            warn!("update check not started: {}", e);
+            None
+        }
+    }
+}
+
+/// Splits `http(s)://host[:port]/path` into (tls, host, port, path)
+pub fn parse_url(url: &str) -> io::Result<(bool, String, u16, String)> {
+    let (tls, rest) = if url.starts_with("https://") {
+        (true, &url[8..])
+    } else if url.starts_with("http://") {
+        (false, &url[7..])
+    } else {
+        return Err(Error::new(ErrorKind::InvalidInput, format!("unsupported url {}", url)));
+    };
+    let (authority, path) = match rest.find('/') {
+        Some(ix) => (&rest[..ix], &rest[ix..]),
+        None => (rest, "/")
+    };
+    let (host, port) = match authority.rfind(':') {
+        Some(ix) => {
+            let port = authority[ix + 1..].parse::<u16>().map_err(|_| Error::new(ErrorKind::InvalidInput, format!("invalid port in {}", url)))?;
+            (&authority[..ix], port)
+        },
+        None => (authority, if tls { 443 } else { 80 })
+    };
+    if host.is_empty() {
+        return Err(Error::new(ErrorKind::InvalidInput, format!("no host in {}", url)));
+    }
+    Ok((tls, host.to_string(), port, path.to_string()))
+}
+
+/// GET request (HTTP/1.0, no chunked responses), redirects are followed
+fn http_get(url: &str) -> io::Result<Vec<u8>> {
+    let mut url = url.to_string();
+    for _ in 0..=MAX_REDIRECTS {
+        let (tls, host, port, path) = parse_url(&url)?;
+        let stream = stratum::connect_with_timeout(&format!("{}:{}", host, port), Duration::from_secs(HTTP_TIMEOUT_SECS))?;
+        stream.set_read_timeout(Some(Duration::from_secs(HTTP_TIMEOUT_SECS)))?;
+        stream.set_write_timeout(Some(Duration::from_secs(HTTP_TIMEOUT_SECS)))?;
+        let request = format!("GET {} HTTP/1.0\r\nHost: {}\r\nUser-Agent: mithril/{}\r\n\r\n", path, host, env!("CARGO_PKG_VERSION"));
+        let response = if tls {
+            let connector = TlsConnector::new().map_err(|e| Error::new(ErrorKind::Other, e))?;
+            let mut tls_stream = connector.connect(&host, stream).map_err(|e| Error::new(ErrorKind::Other, format!("TLS handshake failed: {}", e)))?;
+            exchange(&mut tls_stream, &request)?
+        } else {
+            let mut stream = stream;
+            exchange(&mut stream, &request)?
+        };
+        match parse_response(&response)? {
+            (200, _, body) => return Ok(body.to_vec()),
+            (301, Some(location), _) | (302, Some(location), _) | (303, Some(location), _) | (307, Some(location), _) | (308, Some(location), _) => {
+                url = resolve_location(&url, &location);
+            },
+            (status, _, _) => return Err(Error::new(ErrorKind::Other, format!("HTTP status {} for {}", status, url)))
+        }
+    }
+    Err(Error::new(ErrorKind::Other, format!("more than {} redirects", MAX_REDIRECTS)))
+}
+
+fn exchange<S: Read + Write>(stream: &mut S, request: &str) -> io::Result<Vec<u8>> {
+    stream.write_all(request.as_bytes())?;
+    stream.flush()?;
+    read_limited(stream, MAX_RESPONSE_BYTES)
+}
+
+/// Reads until EOF, fails if there are more than `limit` bytes
+pub fn read_limited<R: Read>(reader: R, limit: u64) -> io::Result<Vec<u8>> {
+    let mut response = Vec::new();
+    reader.take(limit + 1).read_to_end(&mut response)?;
+    if response.len() as u64 > limit {
+        return Err(Error::new(ErrorKind::InvalidData, format!("HTTP response larger than {} bytes", limit)));
+    }
+    Ok(response)
+}
+
+/// The URL a `Location` header of a response to `url` points to, relative locations
+/// (`/path`, `//host/path` or `path`) are resolved against `url`
+pub fn resolve_location(url: &str, location: &str) -> String {
+    if location.starts_with("http://") || location.starts_with("https://") {
+        return location.to_string();
+    }
+    let scheme_end = url.find("://").map_or(0, |ix| ix + 3);
+    if location.starts_with("//") {
+        return format!("{}{}", &url[..scheme_end], &location[2..]);
+    }
+    let path_start = url[scheme_end..].find('/').map_or(url.len(), |ix| scheme_end + ix);
+    if location.starts_with('/') {
+        return format!("{}{}", &url[..path_start], location);
+    }
+    let path = &url[path_start..];
+    let path = &path[..path.find('?').unwrap_or_else(|| path.len())];
+    let dir = &path[..path.rfind('/').map_or(0, |ix| ix + 1)];
+    format!("{}{}{}{}", &url[..path_start], if dir.is_empty() { "/" } else { "" }, dir, location)
+}
+
+/// Status, `Location` header and body of an HTTP response
+pub fn parse_response(response: &[u8]) -> io::Result<(u16, Option<String>, &[u8])> {
+    let header_end = response.windows(4).position(|w| w == b"\r\n\r\n")
+        .ok_or_else(|| Error::new(ErrorKind::UnexpectedEof, "incomplete HTTP response"))?;
+    let header = String::from_utf8_lossy(&response[..header_end]);
+    let mut lines = header.lines();
+    let status = lines.next().and_then(|l| l.split_whitespace().nth(1)).and_then(|s| s.parse::<u16>().ok())
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "invalid HTTP status line"))?;
+    let location = lines.filter_map(|l| {
+        let mut parts = l.splitn(2, ':');
+        match (parts.next(), parts.next()) {
+            (Some(name), Some(value)) if name.trim().eq_ignore_ascii_case("location") => Some(value.trim().to_string()),
+            _ => None
+        }
+    }).next();
+    Ok((status, location, &response[header_end + 4..]))
+}
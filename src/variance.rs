@@ -0,0 +1,86 @@
+use std::time::Duration;
+use difficulty;
+
+/// Quantiles of the reported share count interval (95%)
+const LOWER_QUANTILE : f64 = 0.025;
+const UPPER_QUANTILE : f64 = 0.975;
+/// z-score of the 95% interval, used for large expected counts
+const Z_95 : f64 = 1.96;
+/// Above this expected count the Poisson distribution is approximated by a normal distribution
+const NORMAL_APPROXIMATION_FROM : f64 = 500.0;
+
+/// Expected number of shares for a hash rate and job difficulty. Finding shares
+/// is a Poisson process, so long stretches without a share are normal if the
+/// difficulty is high compared to the hash rate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShareEstimate {
+    /// hashes per second
+    pub hash_rate: f64,
+    pub difficulty: u64,
+}
+
+impl ShareEstimate {
+    pub fn new(hash_rate: f64, difficulty: u64) -> ShareEstimate {
+        ShareEstimate{hash_rate, difficulty}
+    }
+
+    /// Expected number of shares during `duration`
+    pub fn expected(&self, duration: Duration) -> f64 {
+        if self.difficulty == 0 {
+            return 0.0;
+        }
+        self.hash_rate * secs(duration) / self.difficulty as f64
+    }
+
+    pub fn shares_per_hour(&self) -> f64 {
+        self.expected(Duration::from_secs(3600))
+    }
+
+    /// Range of share counts during `duration` that occurs with 95% probability
+    pub fn interval_95(&self, duration: Duration) -> (u64, u64) {
+        poisson_interval(self.expected(duration))
+    }
+
+    /// Probability that no share at all is found during `duration`
+    pub fn p_no_share(&self, duration: Duration) -> f64 {
+        (-self.expected(duration)).exp()
+    }
+
+    /// Human readable summary, `found` is the number of shares found during `duration`
+    pub fn render(&self, found: u64, duration: Duration) -> String {
+        let (low, high) = self.interval_95(duration);
+        format!("{:.1} H/s at difficulty {}: {:.2} shares/hour expected, {} found in the last {} minutes (95% range {}-{}, no share with probability {:.1}%)",
+            self.hash_rate, difficulty::format_difficulty(self.difficulty), self.shares_per_hour(),
+            found, duration.as_secs() / 60, low, high, self.p_no_share(duration) * 100.0)
+    }
+}
+
+/// Central 95% interval of a Poisson distribution with mean `lambda`
+pub fn poisson_interval(lambda: f64) -> (u64, u64) {
+    if lambda <= 0.0 {
+        return (0, 0);
+    }
+    if lambda > NORMAL_APPROXIMATION_FROM {
+        let spread = Z_95 * lambda.sqrt();
+        return ((lambda - spread).floor().max(0.0) as u64, (lambda + spread).ceil() as u64);
+    }
+    let mut pmf = (-lambda).exp();
+    let mut cdf = pmf;
+    let mut k = 0;
+    let mut low = None;
+    loop {
+        if low.is_none() && cdf >= LOWER_QUANTILE {
+            low = Some(k);
+        }
+        if cdf >= UPPER_QUANTILE {
+            return (low.unwrap_or(k), k);
+        }
+        k += 1;
+        pmf *= lambda / k as f64;
+        cdf += pmf;
+    }
+}
+
+fn secs(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) / 1e9
+}
@@ -0,0 +1,65 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use super::super::cryptonight::hash;
+use super::super::cryptonight::hash::{MEM_SIZE};
+use super::super::cryptonight::aes;
+use super::super::cryptonight::aes::{AES, AESSupport};
+use super::super::u64x2::{u64x2};
+
+/// Number of worker threads that fell back to software AES since start
+static FALLBACK_COUNT : AtomicU64 = AtomicU64::new(0);
+
+/// The AES implementation of a worker thread.
+///
+/// Shares found with hardware AES are cross checked against software AES before
+/// they are submitted. On the first mismatch the thread permanently falls back to
+/// software AES, hashing slower but correct.
+pub struct CheckedAes {
+    support: AESSupport,
+    aes: AES,
+    reference: AES,
+}
+
+impl CheckedAes {
+    pub fn new(support: AESSupport) -> CheckedAes {
+        CheckedAes{support, aes: aes::new(support), reference: aes::new(AESSupport::SW)}
+    }
+
+    /// The implementation used for hashing
+    pub fn aes(&self) -> &AES {
+        &self.aes
+    }
+
+    pub fn support(&self) -> AESSupport {
+        self.support
+    }
+
+    /// Recomputes `hash_result` of `input` with software AES. Returns false if the
+    /// result differs, the thread uses software AES from then on and the share must
+    /// not be submitted. Shares hashed with software AES are not checked again.
+    pub fn check_share(&mut self, scratchpad: &mut [u64x2; MEM_SIZE], input: &[u8], hash_result: &str) -> bool {
+        self.check_share_variant(&mut scratchpad[..], input, hash_result, &hash::Variant::V8)
+    }
+
+    /// Like `check_share`, for a hash computed with the main loop of `variant`, `scratchpad`
+    /// is the one the hash was computed with (see `hash::hash_variant`)
+    pub fn check_share_variant(&mut self, scratchpad: &mut [u64x2], input: &[u8], hash_result: &str, variant: &hash::Variant) -> bool {
+        if self.support == AESSupport::SW {
+            return true;
+        }
+        let expected = hash::hash_variant(scratchpad, input, &self.reference, variant);
+        if expected == hash_result {
+            return true;
+        }
+        error!("hardware AES hash {} differs from software AES hash {}, share dropped, \
+                falling back to software AES for this thread", hash_result, expected);
+        self.support = AESSupport::SW;
+        self.aes = aes::new(AESSupport::SW);
+        FALLBACK_COUNT.fetch_add(1, Ordering::SeqCst);
+        false
+    }
+}
+
+/// Number of worker threads that fell back to software AES
+pub fn fallback_count() -> u64 {
+    FALLBACK_COUNT.load(Ordering::SeqCst)
+}
@@ -1,2 +1,7 @@
-
 pub mod worker_pool;
+pub mod nonce;
+pub mod checked_aes;
+pub mod throttle;
+pub mod scratchpad;
+pub mod topology;
+pub mod randomx_memory;
@@ -0,0 +1,117 @@
+extern crate rand;
+
+use self::rand::Rng;
+use determinism;
+
+/// Number of bits of the nonce in the hashing blob
+pub const NONCE_BITS : u8 = 32;
+
+/// Multiplier of the random walk LCG, `a % 4 == 1` guarantees a full period
+/// for every power of two modulus.
+const RANDOM_WALK_MULTIPLIER : u64 = 6_364_136_223_846_793_005;
+
+/// Order in which a worker iterates the nonces of its nonce partition
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NonceStrategyKind {
+    /// consecutive nonces within the partition
+    Sequential,
+    /// every n-th nonce of the whole nonce space, offset by the partition index
+    Strided,
+    /// pseudo random permutation of the partition
+    RandomWalk
+}
+
+impl NonceStrategyKind {
+    pub fn from_name(name: &str) -> Option<NonceStrategyKind> {
+        match name {
+            "sequential" => Some(NonceStrategyKind::Sequential),
+            "strided" => Some(NonceStrategyKind::Strided),
+            "random" => Some(NonceStrategyKind::RandomWalk),
+            _ => None
+        }
+    }
+}
+
+/// Value of a nonce prefix reserved by the pool (most significant bytes of the nonce)
+/// and the number of nonce bits left for the workers.
+pub fn prefix_value(prefix: &[u8]) -> (u64, u8) {
+    let prefix_bits = (prefix.len() * 8) as u8;
+    let value = prefix.iter().fold(0u64, |acc, b| (acc << 8) | u64::from(*b));
+    (value << (NONCE_BITS - prefix_bits), NONCE_BITS - prefix_bits)
+}
+
+pub trait NonceStrategy {
+    /// Returns the next nonce to hash or `None` if the assigned nonce space is exhausted.
+    fn next_nonce(&mut self) -> Option<u64>;
+}
+
+/// Creates the strategy for partition `partition` out of `2^partition_bits` partitions
+/// of a nonce space with `nonce_bits` bits. Each nonce of a partition is returned exactly once.
+pub fn new_strategy(kind: NonceStrategyKind, partition: u64, partition_bits: u8, nonce_bits: u8) -> Box<dyn NonceStrategy + Send> {
+    let size = 1u64 << (nonce_bits - partition_bits);
+    match kind {
+        NonceStrategyKind::Sequential => Box::new(Sequential{next: 0, offset: partition << (nonce_bits - partition_bits), size}),
+        NonceStrategyKind::Strided => Box::new(Strided{next: 0, offset: partition, stride: 1u64 << partition_bits, size}),
+        NonceStrategyKind::RandomWalk => {
+            let mut rng = determinism::rng(partition);
+            let increment = rng.gen::<u64>() | 1;
+            let state = rng.gen::<u64>() & (size - 1);
+            Box::new(RandomWalk{state, increment, count: 0, offset: partition << (nonce_bits - partition_bits), size})
+        }
+    }
+}
+
+struct Sequential {
+    next: u64,
+    offset: u64,
+    size: u64
+}
+
+impl NonceStrategy for Sequential {
+    fn next_nonce(&mut self) -> Option<u64> {
+        if self.next >= self.size {
+            return None;
+        }
+        let nonce = self.offset | self.next;
+        self.next += 1;
+        Some(nonce)
+    }
+}
+
+struct Strided {
+    next: u64,
+    offset: u64,
+    stride: u64,
+    size: u64
+}
+
+impl NonceStrategy for Strided {
+    fn next_nonce(&mut self) -> Option<u64> {
+        if self.next >= self.size {
+            return None;
+        }
+        let nonce = self.next * self.stride + self.offset;
+        self.next += 1;
+        Some(nonce)
+    }
+}
+
+struct RandomWalk {
+    state: u64,
+    increment: u64,
+    count: u64,
+    offset: u64,
+    size: u64
+}
+
+impl NonceStrategy for RandomWalk {
+    fn next_nonce(&mut self) -> Option<u64> {
+        if self.count >= self.size {
+            return None;
+        }
+        let nonce = self.offset | self.state;
+        self.state = self.state.wrapping_mul(RANDOM_WALK_MULTIPLIER).wrapping_add(self.increment) & (self.size - 1);
+        self.count += 1;
+        Some(nonce)
+    }
+}
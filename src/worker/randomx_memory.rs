@@ -0,0 +1,83 @@
+use std::panic;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Instant};
+use super::super::hex;
+use super::super::randomx::{Cache, Dataset, Memory, MONERO};
+
+enum State {
+    Empty,
+    /// the builder thread computes the memory of the requested seed
+    Building,
+    Ready(Memory),
+}
+
+/// The RandomX cache (or full dataset) of the current seed hash, shared by the worker threads
+/// of a pool. Only one seed is kept, a new seed hash replaces the memory of the previous one.
+/// The memory is built on a dedicated thread, so workers keep handling commands meanwhile.
+pub struct RandomxMemory {
+    state: Arc<Mutex<State>>,
+    full_dataset: bool,
+    init_threads: usize,
+}
+
+impl RandomxMemory {
+    /// `full_dataset` computes the 2080 MiB dataset on `init_threads` threads for fast mode,
+    /// otherwise items are computed from the 256 MiB cache (light mode)
+    pub fn new(full_dataset: bool, init_threads: usize) -> RandomxMemory {
+        RandomxMemory{state: Arc::new(Mutex::new(State::Empty)), full_dataset, init_threads}
+    }
+
+    /// The memory of `seed`, None while it is built. The first call for a new seed starts the
+    /// build, a build for another seed is finished first (only one memory is built at a time).
+    /// The memory of the previous seed is released by this pool here, it is freed once the
+    /// last `Vm` holding it is dropped.
+    pub fn get(&self, seed: &[u8]) -> Option<Memory> {
+        let mut state = self.state.lock().expect("randomx memory lock");
+        match *state {
+            State::Ready(ref memory) if memory.seed() == seed => return Some(memory.clone()),
+            State::Building => return None,
+            State::Empty | State::Ready(_) => {}
+        }
+        *state = State::Building;
+        drop(state);
+        self.start_build(seed.to_vec());
+        None
+    }
+
+    fn start_build(&self, seed: Vec<u8>) {
+        let state = self.state.clone();
+        let full_dataset = self.full_dataset;
+        let init_threads = self.init_threads;
+        let spawned = thread::Builder::new().name("randomx init".to_string()).spawn(move || {
+            let built = panic::catch_unwind(|| build(&seed, full_dataset, init_threads));
+            let mut state = state.lock().expect("randomx memory lock");
+            *state = match built {
+                Ok(memory) => State::Ready(memory),
+                Err(_) => {
+                    //the next job with this seed tries again
+                    error!("building randomx memory for seed hash {} failed", hex::encode(&seed));
+                    State::Empty
+                }
+            };
+        });
+        if let Err(e) = spawned {
+            error!("starting randomx init thread failed: {}", e);
+            *self.state.lock().expect("randomx memory lock") = State::Empty;
+        }
+    }
+}
+
+fn build(seed: &[u8], full_dataset: bool, init_threads: usize) -> Memory {
+    let started = Instant::now();
+    info!("building randomx cache for seed hash {}", hex::encode(seed));
+    let cache = Arc::new(Cache::new(&MONERO, seed));
+    let memory = if full_dataset {
+        info!("computing randomx dataset on {} threads", init_threads);
+        Memory::Full(Arc::new(Dataset::new(&cache, init_threads)))
+    } else {
+        Memory::Light(cache)
+    };
+    info!("randomx {} ready after {}s", if full_dataset { "dataset" } else { "cache" }, started.elapsed().as_secs());
+    memory
+}
@@ -0,0 +1,100 @@
+extern crate libc;
+
+use std::ops::{Deref, DerefMut};
+use super::super::cryptonight::hash;
+use super::super::cryptonight::hash::{MEM_SIZE};
+use super::super::u64x2::{u64x2};
+
+/// Size of one scratchpad in bytes (v8 and cryptonight-r), cryptonight-heavy workers allocate
+/// a second scratchpad of twice this size on their first heavy job
+pub const SCRATCHPAD_BYTES : usize = MEM_SIZE * 16;
+
+/// Scratchpad of a worker thread. It is mapped on huge pages if the kernel has
+/// enough of them reserved (see `mithril setup-hugepages`), otherwise it is
+/// allocated on the heap.
+pub enum Scratchpad {
+    Heap(Box<[u64x2; MEM_SIZE]>),
+    HugePages(*mut [u64x2; MEM_SIZE]),
+}
+
+//the mapping is owned by exactly one worker thread
+unsafe impl Send for Scratchpad {}
+
+impl Scratchpad {
+    /// Huge pages first, the heap as fallback, `None` if neither has memory left
+    pub fn alloc() -> Option<Scratchpad> {
+        match map_huge_pages() {
+            Some(ptr) => Some(Scratchpad::HugePages(ptr)),
+            None => hash::try_alloc_scratchpad().map(Scratchpad::Heap)
+        }
+    }
+
+    pub fn is_huge_pages(&self) -> bool {
+        match *self {
+            Scratchpad::HugePages(_) => true,
+            Scratchpad::Heap(_) => false
+        }
+    }
+}
+
+impl Deref for Scratchpad {
+    type Target = [u64x2; MEM_SIZE];
+
+    fn deref(&self) -> &[u64x2; MEM_SIZE] {
+        match *self {
+            Scratchpad::Heap(ref scratchpad) => &**scratchpad,
+            Scratchpad::HugePages(ptr) => unsafe { &*ptr }
+        }
+    }
+}
+
+impl DerefMut for Scratchpad {
+    fn deref_mut(&mut self) -> &mut [u64x2; MEM_SIZE] {
+        match *self {
+            Scratchpad::Heap(ref mut scratchpad) => &mut **scratchpad,
+            Scratchpad::HugePages(ptr) => unsafe { &mut *ptr }
+        }
+    }
+}
+
+impl Drop for Scratchpad {
+    fn drop(&mut self) {
+        if let Scratchpad::HugePages(ptr) = *self {
+            unmap_huge_pages(ptr);
+        }
+    }
+}
+
+/// `MAP_HUGE_2MB`, the page size is requested explicitly because the default huge page size may be 1 GB
+#[cfg(target_os = "linux")]
+const MAP_HUGE_2MB : libc::c_int = 21 << 26;
+
+#[cfg(target_os = "linux")]
+fn map_huge_pages() -> Option<*mut [u64x2; MEM_SIZE]> {
+    //anonymous mappings are zeroed by the kernel
+    let ptr = unsafe {
+        libc::mmap(::std::ptr::null_mut(), SCRATCHPAD_BYTES, libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_HUGETLB | MAP_HUGE_2MB, -1, 0)
+    };
+    if ptr == libc::MAP_FAILED {
+        None
+    } else {
+        Some(ptr as *mut [u64x2; MEM_SIZE])
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn unmap_huge_pages(ptr: *mut [u64x2; MEM_SIZE]) {
+    unsafe {
+        libc::munmap(ptr as *mut libc::c_void, SCRATCHPAD_BYTES);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn map_huge_pages() -> Option<*mut [u64x2; MEM_SIZE]> {
+    None
+}
+
+#[cfg(not(target_os = "linux"))]
+fn unmap_huge_pages(_ptr: *mut [u64x2; MEM_SIZE]) {
+}
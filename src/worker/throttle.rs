@@ -0,0 +1,63 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How much a worker thread backs off after each hash, for running on shared
+/// hosts. Lowering the process priority alone still keeps every core busy,
+/// which costs co-tenants cache and memory bandwidth and turbo headroom.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BackgroundLevel {
+    /// hash without pausing
+    Off,
+    /// give up the time slice after each hash (sched_yield)
+    Yield,
+    /// sleep a quarter of the hash time after each hash
+    Light,
+    /// sleep as long as the hash took, about half the hash rate
+    Heavy
+}
+
+impl BackgroundLevel {
+    pub fn from_level(level: u64) -> Option<BackgroundLevel> {
+        match level {
+            0 => Some(BackgroundLevel::Off),
+            1 => Some(BackgroundLevel::Yield),
+            2 => Some(BackgroundLevel::Light),
+            3 => Some(BackgroundLevel::Heavy),
+            _ => None
+        }
+    }
+
+    /// The time to sleep after a hash that took `hash_time`,
+    /// `None` if the thread only yields or does not pause at all.
+    pub fn sleep_time(self, hash_time: Duration) -> Option<Duration> {
+        match self {
+            BackgroundLevel::Off | BackgroundLevel::Yield => None,
+            BackgroundLevel::Light => Some(hash_time / 4),
+            BackgroundLevel::Heavy => Some(hash_time)
+        }
+    }
+}
+
+/// Pauses a worker thread between hashes according to the background level
+pub struct Throttle {
+    level: BackgroundLevel,
+    hash_start: Instant
+}
+
+impl Throttle {
+    pub fn new(level: BackgroundLevel) -> Throttle {
+        Throttle{level, hash_start: Instant::now()}
+    }
+
+    /// Called after each hash, pauses and starts timing the next hash
+    pub fn pause(&mut self) {
+        match self.level {
+            BackgroundLevel::Off => return,
+            BackgroundLevel::Yield => thread::yield_now(),
+            level => if let Some(sleep) = level.sleep_time(self.hash_start.elapsed()) {
+                thread::sleep(sleep);
+            }
+        }
+        self.hash_start = Instant::now();
+    }
+}
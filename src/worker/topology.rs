@@ -0,0 +1,257 @@
+extern crate libc;
+
+use std::cmp;
+use std::collections::{HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path};
+use super::scratchpad::{SCRATCHPAD_BYTES};
+
+/// The sysfs folder the topology is read from on Linux
+pub const SYSFS_DEVICES : &str = "/sys/devices";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CoreKind {
+    Performance,
+    /// the E-cores of Intel hybrid CPUs (listed in `cpu_atom`)
+    Efficiency,
+}
+
+/// A logical cpu of the OS
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cpu {
+    pub id: usize,
+    /// package and core id, the same for the SMT siblings of a physical core
+    pub core: (usize, usize),
+    /// index of the L3 cache in `Topology::l3_sizes`, each CCX (or CCD) of AMD CPUs has its own
+    pub l3: usize,
+    pub kind: CoreKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Topology {
+    pub cpus: Vec<Cpu>,
+    /// size of each L3 cache in bytes, 0 if unknown
+    pub l3_sizes: Vec<u64>,
+}
+
+impl Topology {
+    pub fn is_hybrid(&self) -> bool {
+        self.cpus.iter().any(|c| c.kind == CoreKind::Efficiency)
+    }
+
+    pub fn logical_cpus(&self, kind: CoreKind) -> usize {
+        self.cpus.iter().filter(|c| c.kind == kind).count()
+    }
+
+    pub fn physical_cores(&self, kind: CoreKind) -> usize {
+        let mut cores : Vec<(usize, usize)> = self.cpus.iter().filter(|c| c.kind == kind).map(|c| c.core).collect();
+        cores.sort();
+        cores.dedup();
+        cores.len()
+    }
+}
+
+/// The cpus the worker threads are pinned to
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placement {
+    /// cpu of each thread in thread order, threads after the last entry are not pinned
+    pub cpus: Vec<usize>,
+    /// threads not started because only efficiency cores were left for them
+    pub skipped: u64,
+}
+
+/// Places `num_threads` threads: one thread per physical core (performance cores first)
+/// before SMT siblings are used, each on the L3 cache that leaves the most cache per thread
+/// (this spreads the scratchpads over the CCDs of AMD CPUs). Cryptonight threads on
+/// efficiency cores often lower the total hash rate, they can be skipped.
+pub fn plan(topology: &Topology, num_threads: u64, skip_efficiency_cores: bool) -> Placement {
+    let candidates : Vec<&Cpu> = topology.cpus.iter()
+        .filter(|c| !(skip_efficiency_cores && c.kind == CoreKind::Efficiency))
+        .collect();
+    let mut used = vec![false; candidates.len()];
+    let mut core_threads : HashMap<(usize, usize), usize> = HashMap::new();
+    let mut l3_threads = vec![0u64; topology.l3_sizes.len()];
+    let mut cpus = Vec::new();
+
+    while (cpus.len() as u64) < num_threads {
+        let next = candidates.iter().enumerate()
+            .filter(|&(ix, _)| !used[ix])
+            .min_by_key(|&(_, cpu)| {
+                let kind_rank = if cpu.kind == CoreKind::Performance { 0 } else { 1 };
+                let siblings = core_threads.get(&cpu.core).cloned().unwrap_or(0);
+                let l3_share = topology.l3_sizes.get(cpu.l3).map(|size| size / (l3_threads[cpu.l3] + 1)).unwrap_or(0);
+                (siblings, kind_rank, cmp::Reverse(l3_share), cpu.id)
+            })
+            .map(|(ix, cpu)| (ix, cpu.id, cpu.core, cpu.l3));
+        match next {
+            Some((ix, id, core, l3)) => {
+                used[ix] = true;
+                *core_threads.entry(core).or_insert(0) += 1;
+                if let Some(count) = l3_threads.get_mut(l3) {
+                    *count += 1;
+                }
+                cpus.push(id);
+            },
+            None => break
+        }
+    }
+    let left = num_threads - cpus.len() as u64;
+    let skipped = if skip_efficiency_cores && topology.is_hybrid() { left } else { 0 };
+    Placement{cpus, skipped}
+}
+
+/// The smallest L3 share of the threads of the placement, None if the cache sizes are unknown
+pub fn l3_per_thread(topology: &Topology, placement: &Placement) -> Option<u64> {
+    let mut threads = vec![0u64; topology.l3_sizes.len()];
+    for id in &placement.cpus {
+        if let Some(cpu) = topology.cpus.iter().find(|c| c.id == *id) {
+            threads[cpu.l3] += 1;
+        }
+    }
+    topology.l3_sizes.iter().zip(threads.iter())
+        .filter(|&(_, count)| *count > 0)
+        .map(|(size, count)| if *size == 0 { None } else { Some(size / count) })
+        .fold(Some(u64::max_value()), |min, share| match (min, share) {
+            (Some(min), Some(share)) => Some(cmp::min(min, share)),
+            _ => None
+        })
+        .and_then(|min| if min == u64::max_value() { None } else { Some(min) })
+}
+
+/// The hardware part of the startup report: cores, L3 caches and the thread placement (if any)
+pub fn report(topology: &Topology, placement: Option<&Placement>) -> String {
+    let mut lines = Vec::new();
+    if topology.is_hybrid() {
+        lines.push(format!("cpu: hybrid, {} performance cores ({} threads), {} efficiency cores ({} threads)",
+            topology.physical_cores(CoreKind::Performance), topology.logical_cpus(CoreKind::Performance),
+            topology.physical_cores(CoreKind::Efficiency), topology.logical_cpus(CoreKind::Efficiency)));
+    } else {
+        lines.push(format!("cpu: {} cores ({} threads)", topology.physical_cores(CoreKind::Performance), topology.logical_cpus(CoreKind::Performance)));
+    }
+    if topology.l3_sizes.iter().all(|s| *s == 0) {
+        lines.push("l3 cache: unknown".to_string());
+    } else {
+        let sizes : Vec<String> = topology.l3_sizes.iter().map(|s| format_mb(*s)).collect();
+        lines.push(format!("l3 cache: {} x ({})", sizes.len(), sizes.join(", ")));
+    }
+    match placement {
+        Some(placement) => {
+            let cpus : Vec<String> = placement.cpus.iter().map(|c| c.to_string()).collect();
+            lines.push(format!("thread affinity: cpus {}", cpus.join(", ")));
+            if let Some(share) = l3_per_thread(topology, placement) {
+                let hint = if share < SCRATCHPAD_BYTES as u64 { ", less than a scratchpad" } else { "" };
+                lines.push(format!("l3 cache per thread: {}{}", format_mb(share), hint));
+            }
+            if placement.skipped > 0 {
+                lines.push(format!("efficiency cores skipped, {} threads not started", placement.skipped));
+            } else if topology.is_hybrid() && placement.cpus.iter().any(|id| topology.cpus.iter().any(|c| c.id == *id && c.kind == CoreKind::Efficiency)) {
+                lines.push("efficiency cores used (see hardware.skip_efficiency_cores)".to_string());
+            }
+        },
+        None => lines.push("thread affinity: none".to_string())
+    }
+    lines.join("\n")
+}
+
+fn format_mb(bytes: u64) -> String {
+    if bytes % 1_048_576 == 0 {
+        format!("{} MB", bytes / 1_048_576)
+    } else {
+        format!("{:.1} MB", bytes as f64 / 1_048_576.0)
+    }
+}
+
+/// The topology of this machine (Linux only, None elsewhere or if sysfs is not readable)
+#[cfg(target_os = "linux")]
+pub fn detect() -> Option<Topology> {
+    detect_from(Path::new(SYSFS_DEVICES))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn detect() -> Option<Topology> {
+    None
+}
+
+/// Reads the topology from a sysfs `devices` folder, None if the online cpus are not listed
+pub fn detect_from(devices: &Path) -> Option<Topology> {
+    let cpu_dir = devices.join("system/cpu");
+    let online = parse_cpu_list(&read_trimmed(&cpu_dir.join("online"))?)?;
+    let efficiency = read_trimmed(&devices.join("cpu_atom/cpus")).and_then(|l| parse_cpu_list(&l)).unwrap_or_default();
+
+    let mut l3_domains : Vec<Vec<usize>> = Vec::new();
+    let mut l3_sizes = Vec::new();
+    let mut cpus = Vec::new();
+    for id in online {
+        let dir = cpu_dir.join(format!("cpu{}", id));
+        let package = read_trimmed(&dir.join("topology/physical_package_id")).and_then(|s| s.parse().ok()).unwrap_or(0);
+        let core_id = read_trimmed(&dir.join("topology/core_id")).and_then(|s| s.parse().ok()).unwrap_or(id);
+        //cpus without a listed L3 share one domain of unknown size
+        let shared = read_trimmed(&dir.join("cache/index3/shared_cpu_list")).and_then(|l| parse_cpu_list(&l)).unwrap_or_default();
+        let l3 = match l3_domains.iter().position(|d| *d == shared) {
+            Some(ix) => ix,
+            None => {
+                l3_domains.push(shared);
+                l3_sizes.push(read_trimmed(&dir.join("cache/index3/size")).and_then(|s| parse_cache_size(&s)).unwrap_or(0));
+                l3_domains.len() - 1
+            }
+        };
+        let kind = if efficiency.contains(&id) { CoreKind::Efficiency } else { CoreKind::Performance };
+        cpus.push(Cpu{id, core: (package, core_id), l3, kind});
+    }
+    Some(Topology{cpus, l3_sizes})
+}
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok().map(|s| s.trim().to_string())
+}
+
+/// Parses a kernel cpu list like `0-3,8,10-11`
+pub fn parse_cpu_list(list: &str) -> Option<Vec<usize>> {
+    let mut cpus = Vec::new();
+    for part in list.trim().split(',').filter(|p| !p.is_empty()) {
+        let mut bounds = part.splitn(2, '-');
+        let from = bounds.next()?.trim().parse::<usize>().ok()?;
+        let to = match bounds.next() {
+            Some(to) => to.trim().parse::<usize>().ok()?,
+            None => from
+        };
+        if to < from {
+            return None;
+        }
+        cpus.extend(from..=to);
+    }
+    Some(cpus)
+}
+
+/// Parses a sysfs cache size like `32768K` into bytes
+pub fn parse_cache_size(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let (number, factor) = if size.ends_with('K') {
+        (&size[..size.len() - 1], 1024)
+    } else if size.ends_with('M') {
+        (&size[..size.len() - 1], 1_048_576)
+    } else {
+        (size, 1)
+    };
+    number.parse::<u64>().ok().map(|n| n * factor)
+}
+
+/// Pins the calling thread to `cpu`
+#[cfg(target_os = "linux")]
+pub fn pin_current_thread(cpu: usize) -> io::Result<()> {
+    unsafe {
+        let mut set : libc::cpu_set_t = ::std::mem::zeroed();
+        libc::CPU_SET(cpu, &mut set);
+        if libc::sched_setaffinity(0, ::std::mem::size_of::<libc::cpu_set_t>(), &set) == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn pin_current_thread(_cpu: usize) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Other, "thread affinity is only supported on Linux"))
+}
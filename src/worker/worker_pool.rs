@@ -1,18 +1,116 @@
+use std::cell::{Cell};
+use std::cmp;
+use std::iter;
+use std::mem;
 use std::thread;
-use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::mpsc::{channel, Receiver, Sender, RecvTimeoutError};
+use std::time::{Duration, Instant};
+use std::sync::Arc;
 use super::super::cryptonight::hash;
 use super::super::cryptonight::hash::{MEM_SIZE};
-use super::super::cryptonight::aes;
-use super::super::cryptonight::aes::{AES, AESSupport};
+use super::super::cryptonight::{HashVersion};
+use super::super::cryptonight::aes::{AESSupport};
 use super::super::stratum;
 use super::super::stratum::stratum_data;
+use super::super::stratum::share_queue::{ShareQueue, SubmitError};
+use super::super::stratum::latency::{ShareTimes};
+use super::super::stratum::multi_pool::{connection_name};
 use super::super::byte_string;
+use super::super::error::{MithrilError};
+use super::super::hex;
+use super::super::difficulty;
+pub use super::super::difficulty::{target_u64};
 use super::super::u64x2::{u64x2};
+use super::super::randomx::{Vm};
+use super::super::metric::counters::{HashCounters};
+use super::super::metric::coverage::{NonceCoverage};
+use super::nonce;
+use super::nonce::{NonceStrategyKind};
+use super::checked_aes::{CheckedAes};
+use super::scratchpad::{Scratchpad};
+use super::throttle::{BackgroundLevel, Throttle};
+use super::topology;
+use super::randomx_memory::{RandomxMemory};
+
+/// Byte offset of the nonce in the hashing blob
+pub const NONCE_OFFSET : usize = 39;
+
+/// How often a worker checks whether the RandomX memory of its job is built
+const RANDOMX_MEMORY_POLL_MILLIS : u64 = 50;
 
 pub struct WorkerPool {
     thread_chan : Vec<Sender<WorkerCmd>>,
     thread_hnd : Vec<thread::JoinHandle<()>>,
-    num_threads: u64
+    /// the pool of the group of each thread, see `WorkerGroup::pool`
+    thread_pools: Vec<Option<usize>>,
+    /// the cpu of each thread the pool was started with, see `start_groups`
+    affinity: Vec<usize>,
+    profiles: Vec<AlgorithmProfile>,
+    /// algorithm of the last job, the profile is logged when it changes
+    hash_version: Cell<Option<HashVersion>>,
+    events: Receiver<WorkerEvent>,
+}
+
+/// Reported by the worker threads, see `WorkerPool::events`
+#[derive(Debug, Clone, PartialEq)]
+pub enum WorkerEvent {
+    /// thread `thread_ix` stopped hashing `job_id` after `max_job_age`, it idles until the next job
+    JobExpired{
+        job_id: String,
+        thread_ix: usize,
+        age: Duration
+    },
+}
+
+/// A named group of worker threads. With `pool_arbitration = "routed"` each group hashes
+/// the jobs of one pool of the multi pool client, e.g. the performance cores the jobs of
+/// the primary and the efficiency cores the jobs of a low difficulty secondary pool.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WorkerGroup {
+    pub name: String,
+    pub num_threads: u64,
+    /// the connection (`multi_pool::PRIMARY` or `SECONDARY`) whose jobs the group hashes, None: every job
+    pub pool: Option<usize>,
+}
+
+impl WorkerGroup {
+    /// One group of `num_threads` threads hashing every job
+    pub fn all(num_threads: u64) -> WorkerGroup {
+        WorkerGroup{name: "workers".to_string(), num_threads, pool: None}
+    }
+
+    /// If the group hashes a job of connection `source` (None: a job for all workers)
+    pub fn hashes(&self, source: Option<usize>) -> bool {
+        hashes_jobs_of(self.pool, source)
+    }
+}
+
+fn hashes_jobs_of(pool: Option<usize>, source: Option<usize>) -> bool {
+    match (pool, source) {
+        (Some(pool), Some(source)) => pool == source,
+        _ => true
+    }
+}
+
+/// The groups with their threads reduced proportionally to `num_threads` in total (e.g.
+/// for the cpu share), every group keeps at least one thread
+pub fn limit_groups(groups: &[WorkerGroup], num_threads: u64) -> Vec<WorkerGroup> {
+    let total : u64 = groups.iter().map(|g| g.num_threads).sum();
+    if num_threads >= total {
+        return groups.to_vec();
+    }
+    groups.iter().map(|g| WorkerGroup{num_threads: cmp::max(1, g.num_threads * num_threads / total), ..g.clone()}).collect()
+}
+
+/// Threads and affinity used while jobs of one algorithm are hashed, e.g. fewer threads
+/// for RandomX than for cryptonight because of the memory of the dataset
+#[derive(Clone, Debug, PartialEq)]
+pub struct AlgorithmProfile {
+    pub hash_version: HashVersion,
+    /// threads hashing the jobs, the others idle (None: all started threads)
+    pub num_threads: Option<u64>,
+    /// cpu of each hashing thread in order (empty: the affinity the pool was started with)
+    pub affinity: Vec<usize>,
 }
 
 #[derive(Clone)]
@@ -21,6 +119,29 @@ pub struct WorkerConfig {
     pub auto_tune: bool,
     pub auto_tune_interval_minutes: u64,
     pub auto_tune_log: String,
+    /// jobs older than this are not hashed any further (None = no limit)
+    pub max_job_age_seconds: Option<u64>,
+    /// if set, threads are started one after another spread over this time
+    pub warmup_seconds: Option<u64>,
+    pub nonce_strategy: NonceStrategyKind,
+    /// if set, every random decision is derived from this seed (see `determinism`)
+    pub deterministic_seed: Option<u64>,
+    /// pauses between hashes to leave the cores to other tenants (see `throttle`)
+    pub background_level: BackgroundLevel,
+    /// RandomX fast mode with the full 2080 MiB dataset instead of the 256 MiB cache
+    pub randomx_full_dataset: bool,
+    /// `[worker.algo."<name>"]` sections, applied by `WorkerPool::job_change`
+    pub algorithm_profiles: Vec<AlgorithmProfile>,
+}
+
+/// Settings for a started worker pool
+#[derive(Clone, Copy, Debug)]
+pub struct WorkerOptions {
+    pub max_job_age: Option<Duration>,
+    pub warmup: Option<Duration>,
+    pub nonce_strategy: NonceStrategyKind,
+    pub background_level: BackgroundLevel,
+    pub randomx_full_dataset: bool,
 }
 
 #[derive(Debug, PartialEq)]
@@ -30,7 +151,20 @@ pub struct JobData {
     pub job_id: String,
     pub target: String,
     pub nonce_partition: u8,
-    pub nonce_partition_num_bits: u8
+    pub nonce_partition_num_bits: u8,
+    /// leading nonce bytes reserved by the pool (extranonce), only the rest is iterated
+    pub nonce_prefix: Vec<u8>,
+    pub hash_version: HashVersion,
+    /// block height of the job, the random math of cryptonight-r is generated from it
+    pub height: u64,
+    /// hex seed hash of a RandomX job, the key of the cache
+    pub seed_hash: Option<String>,
+    /// the thread moves to this cpu before hashing the job (None: it stays where it is)
+    pub cpu: Option<usize>,
+    /// the job was handed to the worker threads
+    pub received: Instant,
+    /// the job was received from the pool
+    pub pool_received: Instant
 }
 
 #[derive(Debug)]
@@ -38,45 +172,163 @@ pub enum WorkerCmd {
     NewJob {
         job_data: JobData
     },
+    /// stops hashing the job with this id (None: any job) until a new job is received
+    Cancel {
+        job_id: Option<String>
+    },
     Stop
 }
 
 #[derive(Debug, PartialEq)]
 enum WorkerExit {
     NonceSpaceExhausted,
+    JobExpired,
     NewJob {
         job_data: JobData
     },
+    Cancelled,
     Stopped
 }
 
 pub fn start(num_threads: u64,
              aes_support: AESSupport,
              share_tx: &Sender<stratum::StratumCmd>,
+             share_queue: &Arc<ShareQueue>,
              metric_resolution: u64,
-             metric_tx: &Sender<u64>) -> WorkerPool {
+             counters: &Arc<HashCounters>,
+             coverage: &Arc<NonceCoverage>,
+             options: WorkerOptions) -> Result<WorkerPool, MithrilError> {
+    start_groups(&[WorkerGroup::all(num_threads)], &[], aes_support, share_tx, share_queue, metric_resolution, counters, coverage, options)
+}
+
+/// Like `start`, with the threads of each group in order. If there is not enough memory
+/// for all scratchpads the last groups get fewer threads. Thread `i` is pinned to the cpu
+/// `affinity[i]`, threads without an entry are not pinned. If a thread cannot be started,
+/// the threads started before are stopped again.
+pub fn start_groups(groups: &[WorkerGroup],
+             affinity: &[usize],
+             aes_support: AESSupport,
+             share_tx: &Sender<stratum::StratumCmd>,
+             share_queue: &Arc<ShareQueue>,
+             metric_resolution: u64,
+             counters: &Arc<HashCounters>,
+             coverage: &Arc<NonceCoverage>,
+             options: WorkerOptions) -> Result<WorkerPool, MithrilError> {
+    let num_threads = groups.iter().map(|g| g.num_threads).sum();
+    let scratchpads = allocate_scratchpads(num_threads, Scratchpad::alloc);
+    let num_threads = scratchpads.len() as u64;
+    let num_huge_pages = scratchpads.iter().filter(|s| s.is_huge_pages()).count() as u64;
+    if num_huge_pages < num_threads {
+        info!("{} of {} scratchpads on huge pages, see mithril setup-hugepages", num_huge_pages, num_threads);
+    }
     let mut thread_chan : Vec<Sender<WorkerCmd>> = Vec::with_capacity(num_threads as usize);
     let mut thread_hnd : Vec<thread::JoinHandle<()>> = Vec::with_capacity(num_threads as usize);
-    for i in 0..num_threads {
+    let thread_groups : Vec<&WorkerGroup> = groups.iter().flat_map(|g| iter::repeat(g).take(g.num_threads as usize)).collect();
+    if groups.len() > 1 {
+        for group in groups {
+            let started = thread_groups.iter().take(num_threads as usize).filter(|g| g.name == group.name).count();
+            match group.pool {
+                Some(pool) => info!("worker group {}: {} threads hashing the jobs of the {} pool", group.name, started, connection_name(pool)),
+                None => info!("worker group {}: {} threads", group.name, started)
+            }
+        }
+    }
+    let mut thread_pools = Vec::with_capacity(num_threads as usize);
+    let (event_tx, events) = channel();
+    let randomx_memory = Arc::new(RandomxMemory::new(options.randomx_full_dataset, num_threads as usize));
+    for (i, (scratchpad, group)) in (0..num_threads).zip(scratchpads.into_iter().zip(thread_groups)) {
         let (tx, rx) = channel();
         let share_tx_thread = share_tx.clone();
-        let metric_tx_thread = metric_tx.clone();
+        let share_queue_thread = share_queue.clone();
+        let counters_thread = counters.clone();
+        let coverage_thread = coverage.clone();
+        let randomx_memory_thread = randomx_memory.clone();
+        let event_tx_thread = event_tx.clone();
         let aes_support_thread = aes_support;
+        let start_delay = options.warmup.map(|w| start_delay(w, i, num_threads));
+        let cpu = affinity.get(i as usize).cloned();
 
-        let hnd = thread::Builder::new().name(format!("worker thread {}", i)).spawn(move || {
-            work(&rx, &share_tx_thread, aes_support_thread, metric_resolution, &metric_tx_thread)
-        }).expect("worker thread handle");
+        let name = if groups.len() > 1 { format!("worker thread {} ({})", i, group.name) } else { format!("worker thread {}", i) };
+        let spawned = thread::Builder::new().name(name).spawn(move || {
+            if let Some(cpu) = cpu {
+                if let Err(e) = topology::pin_current_thread(cpu) {
+                    warn!("could not pin worker thread {} to cpu {}: {}", i, cpu, e);
+                }
+            }
+            work(&rx, scratchpad, &share_tx_thread, &share_queue_thread, &event_tx_thread, aes_support_thread, &randomx_memory_thread, metric_resolution, &counters_thread, &coverage_thread, i as usize, options, start_delay)
+        });
+        let hnd = match spawned {
+            Ok(hnd) => hnd,
+            Err(e) => {
+                let started = WorkerPool{thread_chan, thread_hnd, thread_pools, affinity: affinity.to_vec(), profiles: Vec::new(), hash_version: Cell::new(None), events};
+                started.stop();
+                started.join();
+                return Err(MithrilError::Worker(format!("starting worker thread {} failed: {}", i, e)));
+            }
+        };
         thread_chan.push(tx);
         thread_hnd.push(hnd);
+        thread_pools.push(group.pool);
+    }
+    Ok(WorkerPool{thread_chan, thread_hnd, thread_pools, affinity: affinity.to_vec(), profiles: Vec::new(), hash_version: Cell::new(None), events})
+}
+
+/// Allocates up to `num_threads` scratchpads with `alloc`. If memory runs out,
+/// fewer scratchpads are returned and the worker count is reduced accordingly.
+pub fn allocate_scratchpads<T, F: FnMut() -> Option<T>>(num_threads: u64, mut alloc: F) -> Vec<T> {
+    let mut scratchpads = Vec::with_capacity(num_threads as usize);
+    for _ in 0..num_threads {
+        match alloc() {
+            Some(scratchpad) => scratchpads.push(scratchpad),
+            None => break
+        }
+    }
+    if scratchpads.is_empty() && num_threads > 0 {
+        error!("not enough memory for a single scratchpad ({} MB), no worker thread started", MEM_SIZE * 16 / 1_048_576);
+    } else if (scratchpads.len() as u64) < num_threads {
+        warn!("not enough memory for {} scratchpads ({} MB each), mining with {} threads only",
+            num_threads, MEM_SIZE * 16 / 1_048_576, scratchpads.len());
     }
-    WorkerPool{thread_chan, num_threads, thread_hnd}
+    scratchpads
 }
 
 impl WorkerPool {
-    pub fn job_change(&self, miner_id: &str, blob: &str, job_id: &str, target: &str) {
+    /// Threads and affinity for the jobs of each algorithm, jobs of algorithms without a
+    /// profile are hashed by all threads
+    pub fn set_algorithm_profiles(&mut self, profiles: Vec<AlgorithmProfile>) {
+        self.profiles = profiles;
+    }
+
+    /// Hands a new job of connection `source` (see `StratumEvent::connection`) to the worker
+    /// threads of the groups hashing it, `pool_received` is when it arrived from the pool.
+    /// With a profile for the algorithm of the job only its number of threads hash the job,
+    /// on the cpus of the profile, the other threads idle. Fails if a worker thread already ended.
+    pub fn job_change(&self, source: Option<usize>, miner_id: &str, blob: &str, job_id: &str, target: &str, nonce_prefix: &[u8], hash_version: HashVersion, height: u64, seed_hash: Option<&str>, pool_received: Instant) -> Result<(), MithrilError> {
         info!("job change, blob {}", blob);
-        let num_bits = num_bits(self.num_threads);
-        for (partition_ix, tx) in self.thread_chan.iter().enumerate() {
+        let receivers = self.receivers(source);
+        let profile = self.profiles.iter().find(|p| p.hash_version == hash_version);
+        let num_hashing = profile.and_then(|p| p.num_threads).map_or(receivers.len(), |n| cmp::min(n as usize, receivers.len()));
+        if self.hash_version.replace(Some(hash_version)) != Some(hash_version) {
+            match profile {
+                Some(profile) => info!("{} jobs hashed by {} of {} threads{}", hash_version.algo_names()[0], num_hashing, receivers.len(),
+                    if profile.affinity.is_empty() { String::new() } else { format!(" on cpus {:?}", profile.affinity) }),
+                None => info!("{} jobs hashed by all {} threads", hash_version.algo_names()[0], receivers.len())
+            }
+        }
+        //the nonces are partitioned among the threads hashing the job
+        let num_bits = num_bits(num_hashing as u64);
+        let received = Instant::now();
+        for (partition_ix, &(thread_ix, tx)) in receivers.iter().enumerate() {
+            if partition_ix >= num_hashing {
+                //nothing to do for this algorithm, the thread idles until the next job for it
+                tx.send(WorkerCmd::Cancel{job_id: None})
+                    .map_err(|_| MithrilError::Worker(format!("worker thread {} ended, job {} not handed out", thread_ix, job_id)))?;
+                continue;
+            }
+            let cpu = match profile {
+                Some(profile) if !profile.affinity.is_empty() => profile.affinity.get(partition_ix).cloned(),
+                _ => self.affinity.get(thread_ix).cloned()
+            };
             tx.send(WorkerCmd::NewJob{
                 job_data: JobData {
                     miner_id: miner_id.to_string(),
@@ -84,9 +336,38 @@ impl WorkerPool {
                     job_id: job_id.to_string(),
                     target: target.to_string(),
                     nonce_partition: partition_ix as u8,
-                    nonce_partition_num_bits: num_bits
-                }}).expect("sending new job command");
+                    nonce_partition_num_bits: num_bits,
+                    nonce_prefix: nonce_prefix.to_vec(),
+                    hash_version,
+                    height,
+                    seed_hash: seed_hash.map(|s| s.to_string()),
+                    cpu,
+                    received,
+                    pool_received
+                }}).map_err(|_| MithrilError::Worker(format!("worker thread for nonce partition {} ended, job {} not handed out", partition_ix, job_id)))?;
         }
+        Ok(())
+    }
+
+    /// Stops hashing `job_id` (None: the current job) of connection `source` until the next `job_change`
+    pub fn cancel_job(&self, source: Option<usize>, job_id: Option<&str>) {
+        info!("cancelling job {}", job_id.unwrap_or("(current)"));
+        for (_, tx) in self.receivers(source) {
+            let _ = tx.send(WorkerCmd::Cancel{job_id: job_id.map(|id| id.to_string())});
+        }
+    }
+
+    /// Events of the worker threads, e.g. for a select in the event loop
+    pub fn events(&self) -> &Receiver<WorkerEvent> {
+        &self.events
+    }
+
+    /// The index and channel of the threads hashing the jobs of `source`
+    fn receivers(&self, source: Option<usize>) -> Vec<(usize, &Sender<WorkerCmd>)> {
+        self.thread_chan.iter().zip(self.thread_pools.iter()).enumerate()
+            .filter(|&(_, (_, pool))| hashes_jobs_of(*pool, source))
+            .map(|(thread_ix, (tx, _))| (thread_ix, tx))
+            .collect()
     }
 
     pub fn stop(&self) {
@@ -98,8 +379,8 @@ impl WorkerPool {
     }
 
     //Waits for completing of all threads in the pool
-    pub fn join(self) {
-        for hnd in self.thread_hnd {
+    pub fn join(mut self) {
+        for hnd in mem::replace(&mut self.thread_hnd, Vec::new()) {
             let join_result = hnd.join();
             if join_result.is_err() {
                 error!("thread join failed {:?}, waiting for next", join_result)
@@ -108,6 +389,26 @@ impl WorkerPool {
     }
 }
 
+/// A pool dropped without `stop` (e.g. while a panic unwinds) stops its threads, they
+/// would keep hashing the last job otherwise
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        for tx in &self.thread_chan {
+            let _ = tx.send(WorkerCmd::Stop);
+        }
+    }
+}
+
+/// Delay before thread `thread_ix` starts hashing, so that all threads
+/// are running after the warmup time.
+pub fn start_delay(warmup: Duration, thread_ix: u64, num_threads: u64) -> Duration {
+    if num_threads == 0 {
+        return Duration::from_secs(0);
+    }
+    let warmup_millis = warmup.as_secs() * 1000 + u64::from(warmup.subsec_millis());
+    Duration::from_millis(warmup_millis * thread_ix / num_threads)
+}
+
 pub fn num_bits(num_threads: u64) -> u8 {
     match num_threads {
         0 => 0,
@@ -117,42 +418,76 @@ pub fn num_bits(num_threads: u64) -> u8 {
 }
 
 fn work(rcv: &Receiver<WorkerCmd>,
+        mut scratchpad: Scratchpad,
         share_tx: &Sender<stratum::StratumCmd>,
+        share_queue: &ShareQueue,
+        events: &Sender<WorkerEvent>,
         aes_support: AESSupport,
+        randomx_memory: &RandomxMemory,
         metric_resolution: u64,
-        metric_tx: &Sender<u64>) {
+        counters: &HashCounters,
+        coverage: &NonceCoverage,
+        thread_ix: usize,
+        options: WorkerOptions,
+        start_delay: Option<Duration>) {
 
-    let aes = aes::new(aes_support);
-    let mut scratchpad : Box<[u64x2; MEM_SIZE]> = box [u64x2(0,0); MEM_SIZE];
-
-    let first_job = rcv.recv();
-    if first_job.is_err() {
-        error!("job channel was droppped: {:?}", first_job);
-        return;
+    let mut warmup_job = None;
+    if let Some(delay) = start_delay {
+        match await_start_delay(rcv, delay) {
+            Some(job) => warmup_job = job,
+            None => {
+                info!("Worker stopped during warmup");
+                return
+            }
+        }
     }
-    let mut job = match first_job.unwrap() {
-        WorkerCmd::NewJob{job_data} => job_data,
-        WorkerCmd::Stop => {
-            info!("Worker immediately stopped");
-            return
+
+    let mut aes = CheckedAes::new(aes_support);
+    //created on the first RandomX job, kept as long as the seed hash does not change
+    let mut vm : Option<Vm> = None;
+    //allocated on the first cryptonight-heavy job, it does not fit into the regular scratchpad
+    let mut heavy_scratchpad : Option<Box<[u64x2]>> = None;
+
+    let mut job = match warmup_job {
+        Some(job_data) => job_data,
+        None => match await_job(rcv) {
+            Some(job_data) => job_data,
+            None => {
+                info!("Worker immediately stopped");
+                return
+            }
         }
     };
 
+    //the cpu the thread was pinned to for its last job
+    let mut pinned = None;
     loop {
-        let exit_reason = work_job(&mut scratchpad, &job, rcv, share_tx, &aes, metric_resolution, metric_tx);
-        //if work_job returns the nonce space was exhausted or a new job was received.
-        //In case the nonce space was exhausted, we have to wait blocking for a new job and "idle".
-        match exit_reason {
-            WorkerExit::NonceSpaceExhausted => {
-                warn!("nonce space exhausted, thread idle");
-                let job_blocking = rcv.recv();
-                if job_blocking.is_err() {
-                    error!("job channel was droppped: {:?}", job_blocking);
-                    return;
+        if job.cpu.is_some() && job.cpu != pinned {
+            pinned = job.cpu;
+            if let Some(cpu) = pinned {
+                if let Err(e) = topology::pin_current_thread(cpu) {
+                    warn!("could not pin worker thread {} to cpu {}: {}", thread_ix, cpu, e);
                 }
-                job = match job_blocking.unwrap() {
-                    WorkerCmd::NewJob{job_data} => job_data,
-                    WorkerCmd::Stop => break //Terminate thread
+            }
+        }
+        let exit_reason = work_job(&mut scratchpad, &job, rcv, share_tx, share_queue, &mut aes, &mut vm, &mut heavy_scratchpad, randomx_memory, metric_resolution, counters, coverage, thread_ix, options);
+        //if work_job returns the nonce space was exhausted, the job expired or a new job was received.
+        //In case the nonce space was exhausted or the job expired, we have to wait blocking for a new job and "idle".
+        if exit_reason == WorkerExit::JobExpired {
+            let age = job.received.elapsed();
+            debug!("job {} expired (received {}s ago), thread idle", job.job_id, age.as_secs());
+            //nobody listens anymore once the pool is stopped
+            let _ = events.send(WorkerEvent::JobExpired{job_id: job.job_id.clone(), thread_ix, age});
+        } else if exit_reason == WorkerExit::NonceSpaceExhausted {
+            warn!("nonce space exhausted, thread idle");
+        } else if exit_reason == WorkerExit::Cancelled {
+            info!("job {} cancelled, thread idle", job.job_id);
+        }
+        match exit_reason {
+            WorkerExit::NonceSpaceExhausted | WorkerExit::JobExpired | WorkerExit::Cancelled => {
+                job = match await_job(rcv) {
+                    Some(job_data) => job_data,
+                    None => break //Terminate thread
                 };
             },
             WorkerExit::NewJob{job_data} => {
@@ -165,9 +500,48 @@ fn work(rcv: &Receiver<WorkerCmd>,
     info!("Worker stopped")
 }
 
+/// Waits blocking for the next job, cancellations are ignored while idle.
+/// Returns `None` if the worker was stopped or the channel dropped.
+fn await_job(rcv: &Receiver<WorkerCmd>) -> Option<JobData> {
+    loop {
+        match rcv.recv() {
+            Ok(WorkerCmd::NewJob{job_data}) => return Some(job_data),
+            Ok(WorkerCmd::Cancel{..}) => {},
+            Ok(WorkerCmd::Stop) => return None,
+            Err(e) => {
+                error!("job channel was droppped: {:?}", e);
+                return None;
+            }
+        }
+    }
+}
+
+/// Waits for `delay`, remembering the most recent job received in the meantime.
+/// Returns `None` if the worker was stopped while waiting.
+fn await_start_delay(rcv: &Receiver<WorkerCmd>, delay: Duration) -> Option<Option<JobData>> {
+    let deadline = Instant::now() + delay;
+    let mut latest_job = None;
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            return Some(latest_job);
+        }
+        match rcv.recv_timeout(deadline - now) {
+            Ok(WorkerCmd::NewJob{job_data}) => latest_job = Some(job_data),
+            Ok(WorkerCmd::Cancel{job_id}) => {
+                if latest_job.as_ref().map_or(false, |job| is_cancelled(job, &job_id)) {
+                    latest_job = None;
+                }
+            },
+            Ok(WorkerCmd::Stop) | Err(RecvTimeoutError::Disconnected) => return None,
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+    }
+}
+
 pub fn with_nonce(blob: &str, nonce: &str) -> String {
-    let (a, _) = blob.split_at(78);
-    let (_, b) = blob.split_at(86);
+    let (a, _) = blob.split_at(NONCE_OFFSET * 2);
+    let (_, b) = blob.split_at(NONCE_OFFSET * 2 + 8);
     return format!("{}{}{}", a, nonce, b);
 }
 
@@ -175,70 +549,197 @@ fn work_job(scratchpad : &mut [u64x2; MEM_SIZE],
     job: &JobData,
     rcv: &Receiver<WorkerCmd>,
     share_tx: &Sender<stratum::StratumCmd>,
-    aes: &AES,
+    share_queue: &ShareQueue,
+    aes: &mut CheckedAes,
+    vm: &mut Option<Vm>,
+    heavy_scratchpad: &mut Option<Box<[u64x2]>>,
+    randomx_memory: &RandomxMemory,
     metric_resolution: u64,
-    metric_tx: &Sender<u64>) -> WorkerExit {
+    counters: &HashCounters,
+    coverage: &NonceCoverage,
+    thread_ix: usize,
+    options: WorkerOptions) -> WorkerExit {
+
+    let mut num_target = job_target(&job.target);
+    //the target of the last retarget, `job.target` is the one the job was received with
+    let mut current_target = job.target.clone();
+    let (nonce_prefix, nonce_bits) = nonce::prefix_value(&job.nonce_prefix);
+    if job.nonce_partition_num_bits > nonce_bits {
+        error!("nonce prefix of job {} leaves too few nonces for {} partition bits", job.job_id, job.nonce_partition_num_bits);
+        return WorkerExit::NonceSpaceExhausted;
+    }
+    let mut nonces = nonce::new_strategy(options.nonce_strategy, u64::from(job.nonce_partition),
+                                         job.nonce_partition_num_bits, nonce_bits);
+
+    let mut bytes_in = byte_string::string_to_u8_array(&job.blob);
+    if bytes_in.len() < NONCE_OFFSET + 4 {
+        error!("blob of job {} too short for a nonce", job.job_id);
+        return WorkerExit::NonceSpaceExhausted;
+    }
+
+    //the random math of cryptonight-r is the same for all nonces of the job,
+    //RandomX jobs are hashed by the vm of the job's seed hash instead
+    let variant = if job.hash_version == HashVersion::RandomX {
+        let seed = match job.seed_hash {
+            Some(ref seed_hash) => byte_string::string_to_u8_array(seed_hash),
+            None => {
+                error!("randomx job {} without seed hash", job.job_id);
+                return WorkerExit::NonceSpaceExhausted;
+            }
+        };
+        if vm.as_ref().map_or(true, |vm| vm.seed() != &seed[..]) {
+            //releases this thread's share of the previous seed's memory
+            *vm = None;
+            loop {
+                if let Some(memory) = randomx_memory.get(&seed) {
+                    *vm = Some(Vm::new(memory));
+                    break;
+                }
+                //the memory is built on its own thread, commands are handled meanwhile
+                match rcv.recv_timeout(Duration::from_millis(RANDOMX_MEMORY_POLL_MILLIS)) {
+                    Ok(WorkerCmd::NewJob{job_data}) => return WorkerExit::NewJob{job_data},
+                    Ok(WorkerCmd::Cancel{ref job_id}) if is_cancelled(job, job_id) => return WorkerExit::Cancelled,
+                    Ok(WorkerCmd::Cancel{..}) | Err(RecvTimeoutError::Timeout) => {},
+                    Ok(WorkerCmd::Stop) | Err(RecvTimeoutError::Disconnected) => return WorkerExit::Stopped
+                }
+            }
+        }
+        None
+    } else {
+        hash::Variant::new(job.hash_version, job.height)
+    };
 
-    let num_target = target_u64(byte_string::hex2_u32_le(&job.target));
-    let first_byte = job.nonce_partition << (8 - job.nonce_partition_num_bits);
+    if variant == Some(hash::Variant::Heavy) && heavy_scratchpad.is_none() {
+        *heavy_scratchpad = hash::try_alloc_heavy_scratchpad();
+        if heavy_scratchpad.is_none() {
+            error!("no memory for the cryptonight-heavy scratchpad of job {}, thread idle", job.job_id);
+            return WorkerExit::NonceSpaceExhausted;
+        }
+    }
+    //cryptonight-heavy does not fit into the regular scratchpad
+    let hash_scratchpad : &mut [u64x2] = if variant == Some(hash::Variant::Heavy) {
+        &mut heavy_scratchpad.as_mut().expect("cryptonight-heavy scratchpad")[..]
+    } else {
+        &mut scratchpad[..]
+    };
 
+    let partition_size = 1u64 << (nonce_bits - job.nonce_partition_num_bits);
     let mut hash_count : u64 = 0;
+    let mut searched : u64 = 0;
+    let mut throttle = Throttle::new(options.background_level);
 
-    for i in 0..2^(8 - job.nonce_partition_num_bits) {
-        for j in 0..u8::max_value() {
-            for k in 0..u8::max_value() {
-                for l in 0..u8::max_value() {
-
-                    let nonce = format!("{:02x}{:02x}{:02x}{:02x}", first_byte | i, j, k, l);
-                    let hash_in = with_nonce(&job.blob, &nonce);
-                    let bytes_in = byte_string::string_to_u8_array(&hash_in);
-
-                    let hash_result = hash::hash(scratchpad, &bytes_in, aes);
-                    let hash_val = byte_string::hex2_u64_le(&hash_result[48..]);
-
-                    if hash_val < num_target {
-                        let share = stratum_data::Share{
-                            miner_id: job.miner_id.clone(),
-                            job_id: job.job_id.clone(),
-                            nonce,
-                            hash: hash_result
-                        };
-
-                        let submit_result = stratum::submit_share(share_tx, share);
-                        if submit_result.is_err() {
-                            error!("submitting share failed: {:?}", submit_result);
-                        }
-                    }
+    while let Some(nonce_part) = nonces.next_nonce() {
+        let nonce_val = nonce_prefix | nonce_part;
+        let nonce_bytes = [(nonce_val >> 24) as u8, (nonce_val >> 16) as u8, (nonce_val >> 8) as u8, nonce_val as u8];
+        bytes_in[NONCE_OFFSET..NONCE_OFFSET+4].copy_from_slice(&nonce_bytes);
 
-                    hash_count += 1;
-                    if hash_count % metric_resolution == 0 {
-                        let send_result = metric_tx.send(hash_count);
-                        if send_result.is_err() {
-                            error!("metric submit failed {:?}", send_result);
-                        }
-                        hash_count = 0;
-                    }
+        let hash_result = match variant {
+            Some(ref variant) => hash::hash_variant(hash_scratchpad, &bytes_in, aes.aes(), variant),
+            None => hex::encode(&vm.as_mut().expect("randomx vm of the job").calculate_hash(&bytes_in))
+        };
+        let hash_val = byte_string::hex2_u64_le(&hash_result[48..]);
+
+        //RandomX is hashed with software AES only, there is nothing to cross check
+        if hash_val < num_target && variant.as_ref().map_or(true, |v| aes.check_share_variant(hash_scratchpad, &bytes_in, &hash_result, v)) {
+            info!("share found for job {}: share diff {} / job {}", job.job_id,
+                difficulty::format_difficulty(difficulty::hash_difficulty(hash_val)),
+                difficulty::format_difficulty(difficulty::target_difficulty(num_target)));
+            let share = stratum_data::Share{
+                miner_id: job.miner_id.clone(),
+                job_id: job.job_id.clone(),
+                nonce: hex::encode(&nonce_bytes),
+                hash: hash_result,
+                times: Some(ShareTimes{job_received: job.pool_received, dispatched: job.received, found: Instant::now()})
+            };
+
+            match share_queue.submit(share_tx, share) {
+                Ok(()) => {},
+                Err(SubmitError::Full) => {}, //logged by the queue
+                Err(SubmitError::Disconnected) => error!("submitting share failed, pool connection ended"),
+                Err(SubmitError::Kept) => {} //logged by the offline buffer
+            }
+        }
 
-                    let cmd = check_command_available(rcv);
-                    if cmd.is_some() {
-                        match cmd.unwrap() {
-                            WorkerCmd::NewJob{job_data} => {
-                                let send_result = metric_tx.send(hash_count);
-                                if send_result.is_err() { //flush hash_count
-                                    error!("metric submit failed {:?}", send_result);
-                                }
-                                return WorkerExit::NewJob{job_data};
-                            },
-                            WorkerCmd::Stop => return WorkerExit::Stopped
-                        }
+        throttle.pause();
+
+        hash_count += 1;
+        searched += 1;
+        if hash_count % metric_resolution == 0 {
+            counters.add(thread_ix, hash_count);
+            hash_count = 0;
+        }
+
+        if is_expired(job, options.max_job_age) {
+            counters.add(thread_ix, hash_count);
+            record_coverage(coverage, job, searched, partition_size);
+            return WorkerExit::JobExpired;
+        }
+
+        let cmd = check_command_available(rcv);
+        if cmd.is_some() {
+            match cmd.unwrap() {
+                WorkerCmd::NewJob{ref job_data} if is_retarget(job, job_data) => {
+                    //vardiff retarget: continue at the current nonce, shares found
+                    //from now on are checked against the new target
+                    if job_data.target != current_target {
+                        let new_target = job_target(&job_data.target);
+                        info!("retarget for job {}: diff {} -> {}", job.job_id,
+                            difficulty::format_difficulty(difficulty::target_difficulty(num_target)),
+                            difficulty::format_difficulty(difficulty::target_difficulty(new_target)));
+                        num_target = new_target;
+                        current_target = job_data.target.clone();
                     }
-                }
+                },
+                WorkerCmd::NewJob{job_data} => {
+                    counters.add(thread_ix, hash_count);
+                    record_coverage(coverage, job, searched, partition_size);
+                    return WorkerExit::NewJob{job_data};
+                },
+                WorkerCmd::Cancel{ref job_id} if is_cancelled(job, job_id) => {
+                    counters.add(thread_ix, hash_count);
+                    record_coverage(coverage, job, searched, partition_size);
+                    return WorkerExit::Cancelled;
+                },
+                WorkerCmd::Cancel{..} => {}, //another job, keep hashing
+                WorkerCmd::Stop => return WorkerExit::Stopped
             }
         }
     }
+    record_coverage(coverage, job, searched, partition_size);
     WorkerExit::NonceSpaceExhausted
 }
 
+fn record_coverage(coverage: &NonceCoverage, job: &JobData, searched: u64, partition_size: u64) {
+    debug!("job {} left after searching {} of {} nonces of partition {}", job.job_id, searched, partition_size, job.nonce_partition);
+    coverage.record(searched, partition_size);
+}
+
+/// Returns true if a cancellation of `job_id` (None: any job) applies to `job`
+pub fn is_cancelled(job: &JobData, job_id: &Option<String>) -> bool {
+    job_id.as_ref().map_or(true, |id| *id == job.job_id)
+}
+
+/// Returns true if `new_job` is the same job as `job` (every field but the target
+/// is equal), hashing continues at the current nonce with the target of `new_job`.
+pub fn is_retarget(job: &JobData, new_job: &JobData) -> bool {
+    job.job_id == new_job.job_id &&
+    job.blob == new_job.blob &&
+    job.miner_id == new_job.miner_id &&
+    job.nonce_partition == new_job.nonce_partition &&
+    job.nonce_partition_num_bits == new_job.nonce_partition_num_bits &&
+    job.nonce_prefix == new_job.nonce_prefix &&
+    job.hash_version == new_job.hash_version &&
+    job.height == new_job.height &&
+    job.seed_hash == new_job.seed_hash
+}
+
+fn is_expired(job: &JobData, max_job_age: Option<Duration>) -> bool {
+    match max_job_age {
+        Some(max_age) => job.received.elapsed() > max_age,
+        None => false
+    }
+}
+
 fn check_command_available(rcv: &Receiver<WorkerCmd>) -> Option<WorkerCmd> {
     let try_result = rcv.try_recv();
     match try_result {
@@ -247,6 +748,14 @@ fn check_command_available(rcv: &Receiver<WorkerCmd>) -> Option<WorkerCmd> {
     }
 }
 
-pub fn target_u64(t: u32) -> u64 {
-    u64::max_value() / (u64::from(u32::max_value()) / u64::from(t))
+/// The 64 bit target of a job (see `difficulty::parse_target`), an invalid
+/// target yields 0, no hash meets it.
+pub fn job_target(target: &str) -> u64 {
+    match difficulty::parse_target(target) {
+        Ok(value) => value,
+        Err(e) => {
+            error!("invalid job target: {}", e);
+            0
+        }
+    }
 }
@@ -0,0 +1,147 @@
+extern crate serde_json;
+extern crate num_cpus;
+
+use std::fs;
+use std::io;
+use std::path::{Path};
+use self::serde_json::Value;
+
+/// Pool url schemes of xmrig, the `ssl` ones mean TLS
+const TCP_SCHEMES : [&str; 2] = ["stratum+tcp://", "stratum://"];
+const TLS_SCHEMES : [&str; 2] = ["stratum+ssl://", "stratum+tls://"];
+/// Thread lists in the xmrig `cpu` section, looked up in this order
+const THREAD_PROFILES : [&str; 3] = ["rx", "cn", "cn/2"];
+
+/// Reads an xmrig `config.json` and returns the equivalent mithril config
+pub fn import(xmrig_file: &Path) -> io::Result<String> {
+    convert(&fs::read_to_string(xmrig_file)?)
+}
+
+/// Converts the content of an xmrig config to a mithril config. Settings
+/// without a mithril equivalent are listed as comments at the top.
+pub fn convert(xmrig_json: &str) -> io::Result<String> {
+    let xmrig : Value = serde_json::from_str(xmrig_json).map_err(|e| invalid_data(format!("not an xmrig config: {}", e)))?;
+    let mut notes = Vec::new();
+
+    let pools : Vec<&Value> = match xmrig["pools"].as_array() {
+        Some(pools) => pools.iter().filter(|p| p["enabled"].as_bool() != Some(false)).collect(),
+        None => Vec::new()
+    };
+    let pool = match pools.first() {
+        Some(pool) => *pool,
+        None => return Err(invalid_data("xmrig config contains no enabled pool".to_string()))
+    };
+    let (pool_address, url_tls) = pool_url(pool)?;
+    let tls = url_tls || pool["tls"].as_bool() == Some(true);
+    let mut failover = Vec::new();
+    for other in &pools[1..] {
+        let (address, _) = pool_url(other)?;
+        if other["user"] != pool["user"] || other["pass"] != pool["pass"] {
+            notes.push(format!("pool {} uses other credentials, mithril logs in to failover pools with the first pool's", address));
+        }
+        failover.push(address);
+    }
+
+    let mut lines = vec!["[pool]".to_string()];
+    lines.push(format!("pool_address = {}", toml_str(&pool_address)));
+    if pool["daemon"].as_bool() == Some(true) {
+        lines.push(format!("solo_daemon_address = {}", toml_str(&pool_address)));
+    }
+    lines.push(format!("wallet_address = {}", toml_str(pool["user"].as_str().unwrap_or(""))));
+    lines.push(format!("pool_password = {}", toml_str(pool["pass"].as_str().unwrap_or("x"))));
+    if let Some(rig_id) = pool["rig-id"].as_str() {
+        lines.push(format!("rig_id = {}", toml_str(rig_id)));
+    }
+    if pool["keepalive"].as_bool() == Some(false) {
+        lines.push("keepalive_interval_secs = 0".to_string());
+    }
+    if tls {
+        lines.push("tls = true".to_string());
+        if let Some(fingerprint) = pool["tls-fingerprint"].as_str() {
+            lines.push(format!("tls_fingerprints = [{}]", toml_str(fingerprint)));
+        }
+    }
+    if !failover.is_empty() {
+        let addresses : Vec<String> = failover.iter().map(|a| toml_str(a)).collect();
+        lines.push(format!("failover_pool_addresses = [{}]", addresses.join(", ")));
+    }
+
+    let cpu = &xmrig["cpu"];
+    lines.push(String::new());
+    lines.push("[worker]".to_string());
+    match thread_count(cpu) {
+        Some(num_threads) => {
+            lines.push(format!("num_threads = {}", num_threads));
+            lines.push("auto_tune = false".to_string());
+        },
+        None => {
+            lines.push(format!("num_threads = {}", num_cpus::get()));
+            lines.push("auto_tune = true".to_string());
+        }
+    }
+    lines.push("auto_tune_interval_minutes = 15".to_string());
+    lines.push("auto_tune_log = \"./bandit.log\"".to_string());
+    if cpu["huge-pages"].as_bool() == Some(true) {
+        notes.push("huge-pages is not a setting, mithril uses huge pages if reserved (see mithril setup-hugepages)".to_string());
+    }
+
+    lines.push(String::new());
+    lines.push("[metric]".to_string());
+    lines.push("enabled = false".to_string());
+    if xmrig["http"]["enabled"].as_bool() == Some(true) || !xmrig["api"]["id"].is_null() || !xmrig["api"]["worker-id"].is_null() {
+        notes.push("the xmrig HTTP API has no mithril equivalent, see the [metric] section for hash rate logging".to_string());
+    }
+
+    lines.push(String::new());
+    lines.push("[hardware]".to_string());
+    lines.push(format!("has_aes = {}", cpu["hw-aes"].as_bool() != Some(false)));
+
+    lines.push(String::new());
+    lines.push("[donation]".to_string());
+    let donate_level = xmrig["donate-level"].as_f64().unwrap_or(2.5);
+    lines.push(format!("percentage = {:?}", donate_level));
+
+    let mut config : Vec<String> = notes.iter().map(|n| format!("# not imported: {}", n)).collect();
+    if !config.is_empty() {
+        config.push(String::new());
+    }
+    config.extend(lines);
+    config.push(String::new());
+    Ok(config.join("\n"))
+}
+
+/// The address of an xmrig pool entry without the url scheme, and whether
+/// the scheme requires TLS.
+fn pool_url(pool: &Value) -> io::Result<(String, bool)> {
+    let url = pool["url"].as_str().ok_or_else(|| invalid_data("pool without url".to_string()))?;
+    for scheme in TLS_SCHEMES.iter() {
+        if url.starts_with(scheme) {
+            return Ok((url[scheme.len()..].to_string(), true));
+        }
+    }
+    for scheme in TCP_SCHEMES.iter() {
+        if url.starts_with(scheme) {
+            return Ok((url[scheme.len()..].to_string(), false));
+        }
+    }
+    if url.contains("://") {
+        return Err(invalid_data(format!("unsupported pool url {}", url)));
+    }
+    Ok((url.to_string(), false))
+}
+
+/// Number of threads of the first configured thread list, `None` if xmrig chooses them
+fn thread_count(cpu: &Value) -> Option<u64> {
+    THREAD_PROFILES.iter()
+        .filter_map(|profile| cpu[*profile].as_array())
+        .map(|threads| threads.len() as u64)
+        .find(|n| *n > 0)
+}
+
+fn toml_str(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
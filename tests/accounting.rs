@@ -0,0 +1,76 @@
+#![cfg(feature = "miner-bin")]
+
+extern crate mithril;
+
+use std::env;
+use std::fs;
+
+use mithril::accounting;
+use mithril::accounting::{ShareEntry, ShareLog, ShareResult, DaySummary, SECS_PER_DAY};
+
+fn entry(time: u64, pool_address: &str, difficulty: u64, result: ShareResult) -> ShareEntry {
+    ShareEntry{time, pool_address: pool_address.to_string(), difficulty, result}
+}
+
+#[test]
+fn test_format_date() {
+    assert_eq!(accounting::format_date(0), "1970-01-01");
+    assert_eq!(accounting::format_date(59), "1970-03-01");
+    assert_eq!(accounting::format_date(11_016), "2000-02-29");
+    assert_eq!(accounting::format_date(19_723), "2024-01-01");
+}
+
+#[test]
+fn test_entry_line_round_trip() {
+    let share = entry(1_700_000_000, "pool:3333", 120_001, ShareResult::Stale);
+    assert_eq!(share.to_line(), "1700000000,pool:3333,120001,stale");
+    assert_eq!(ShareEntry::parse(&share.to_line()), Some(share));
+    assert_eq!(ShareEntry::parse("1700000000,pool:3333,120001,lost"), None);
+    assert_eq!(ShareEntry::parse("1700000000,pool:3333"), None);
+}
+
+#[test]
+fn test_summarize_per_day_and_pool() {
+    let day = 19_723 * SECS_PER_DAY;
+    let entries = vec![
+        entry(day + 10, "pool:3333", 1000, ShareResult::Accepted),
+        entry(day + 20, "pool:3333", 3000, ShareResult::Accepted),
+        entry(day + 30, "pool:3333", 3000, ShareResult::Rejected),
+        entry(day + 40, "other:3333", 500, ShareResult::Accepted),
+        entry(day + SECS_PER_DAY, "pool:3333", 2000, ShareResult::Stale),
+    ];
+    let summaries = accounting::summarize(&entries);
+    assert_eq!(summaries.len(), 3);
+    assert_eq!(summaries[1], DaySummary{day: 19_723, pool_address: "pool:3333".to_string(),
+        valid_shares: 2, invalid_shares: 1, stale_shares: 0, total_hashes: 4000});
+    assert_eq!(accounting::render_csv(&summaries),
+        "date,pool,valid_shares,invalid_shares,stale_shares,total_hashes\n\
+         2024-01-01,other:3333,1,0,0,500\n\
+         2024-01-01,pool:3333,2,1,0,4000\n\
+         2024-01-02,pool:3333,0,0,1,0\n");
+}
+
+#[test]
+fn test_share_log_accounts_job_difficulty() {
+    let path = env::temp_dir().join(format!("mithril-share-log-{}.csv", std::process::id()));
+    let _ = fs::remove_file(&path);
+
+    {
+        let mut log = ShareLog::open(&path).unwrap();
+        log.connected("pool:3333");
+        log.record_job("job1", 1000);
+        log.record_job("job2", 2000);
+        log.record_share("job1", ShareResult::Accepted).unwrap();
+        log.record_share("job2", ShareResult::Rejected).unwrap();
+        //jobs of the previous connection are not known anymore
+        log.connected("other:3333");
+        log.record_share("job1", ShareResult::Accepted).unwrap();
+    }
+
+    let (entries, invalid) = accounting::read_log(&path).unwrap();
+    assert_eq!(invalid, 0);
+    let shares : Vec<(&str, u64, ShareResult)> = entries.iter().map(|e| (e.pool_address.as_str(), e.difficulty, e.result)).collect();
+    assert_eq!(shares, vec![("pool:3333", 1000, ShareResult::Accepted), ("pool:3333", 2000, ShareResult::Rejected),
+        ("other:3333", 0, ShareResult::Accepted)]);
+    let _ = fs::remove_file(&path);
+}
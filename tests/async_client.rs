@@ -0,0 +1,153 @@
+#![cfg(feature = "async-stratum")]
+
+extern crate mithril;
+#[macro_use]
+extern crate serde_json;
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{channel, Receiver};
+use std::time::{Duration};
+
+use mithril::stratum;
+use mithril::stratum::stratum_data;
+use mithril::stratum::async_client::{AsyncRuntime, AsyncStratumClient};
+
+fn default_pool_conf() -> stratum_data::PoolConfig {
+    stratum_data::PoolConfig::new("pool.example.com:3333", "wallet", "x")
+}
+
+fn read_request(reader: &mut BufReader<TcpStream>) -> serde_json::Value {
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    serde_json::from_str(&line).unwrap()
+}
+
+fn start_client(listener: &TcpListener) -> (AsyncStratumClient, BufReader<TcpStream>, TcpStream, Receiver<stratum::StratumEvent>) {
+    start_client_on(&AsyncRuntime::shared().unwrap(), listener)
+}
+
+fn start_client_on(runtime: &AsyncRuntime, listener: &TcpListener) -> (AsyncStratumClient, BufReader<TcpStream>, TcpStream, Receiver<stratum::StratumEvent>) {
+    let mut pool_conf = default_pool_conf();
+    pool_conf.pool_address = listener.local_addr().unwrap().to_string();
+    pool_conf.tls = false;
+    pool_conf.keepalive_interval_secs = None;
+
+    let (err_tx, _err_rx) = channel();
+    let (action_tx, action_rx) = channel();
+    let client = AsyncStratumClient::login_on(runtime, pool_conf, err_tx, action_tx).unwrap();
+    let (stream, _) = listener.accept().unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+    (client, BufReader::new(stream.try_clone().unwrap()), stream, action_rx)
+}
+
+fn login_response(miner_id: &str, job_id: &str) -> serde_json::Value {
+    json!({
+        "id": 1,
+        "jsonrpc": "2.0",
+        "error": null,
+        "result": {
+            "id": miner_id,
+            "job": {"blob": "00", "job_id": job_id, "target": "169f0200"},
+            "status": "OK"
+        }
+    })
+}
+
+fn expect_job(action_rx: &Receiver<stratum::StratumEvent>, expected_job_id: &str) {
+    match action_rx.recv_timeout(Duration::from_secs(10)).unwrap().action {
+        stratum::StratumAction::Job{job_id, ..} => assert_eq!(job_id, expected_job_id),
+        other => assert!(false, "Wrong action received: {:?}", other)
+    }
+}
+
+#[test]
+fn test_async_client_supports() {
+    let mut pool_conf = default_pool_conf();
+    pool_conf.tls = false;
+    assert!(AsyncStratumClient::supports(&pool_conf));
+
+    pool_conf.tls = true;
+    assert!(!AsyncStratumClient::supports(&pool_conf));
+
+    pool_conf.tls = false;
+    pool_conf.self_select_daemon_address = Some("127.0.0.1:18081".to_string());
+    assert!(!AsyncStratumClient::supports(&pool_conf));
+}
+
+#[test]
+fn test_async_client_login_job_and_submit() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let (client, mut reader, mut writer, action_rx) = start_client(&listener);
+
+    let login = read_request(&mut reader);
+    assert_eq!(login["method"], "login");
+    assert_eq!(login["id"], 1);
+    writeln!(writer, "{}", json!({
+        "id": 1,
+        "jsonrpc": "2.0",
+        "error": null,
+        "result": {
+            "id": "miner1",
+            "job": {"blob": "00", "job_id": "job1", "target": "169f0200"},
+            "status": "OK"
+        }
+    })).unwrap();
+
+    match action_rx.recv_timeout(Duration::from_secs(10)).unwrap().action {
+        stratum::StratumAction::Job{miner_id, job_id, ..} => {
+            assert_eq!(miner_id, "miner1");
+            assert_eq!(job_id, "job1");
+        },
+        other => assert!(false, "Wrong action received: {:?}", other)
+    }
+
+    stratum::submit_share(&client.new_cmd_channel(), stratum_data::Share{
+        miner_id: "miner1".to_string(),
+        job_id: "job1".to_string(),
+        nonce: "00000001".to_string(),
+        hash: "hash".to_string(),
+        times: None
+    }).unwrap();
+
+    assert_eq!(read_request(&mut reader), json!({
+        "id": 2,
+        "method": "submit",
+        "params": {"id": "miner1", "job_id": "job1", "nonce": "00000001", "result": "hash"}
+    }));
+    writeln!(writer, "{}", json!({"id": 2, "jsonrpc": "2.0", "error": null, "result": {"status": "OK"}})).unwrap();
+
+    match action_rx.recv_timeout(Duration::from_secs(10)).unwrap().action {
+        stratum::StratumAction::ShareAccepted{job_id} => assert_eq!(job_id, "job1"),
+        other => assert!(false, "Wrong action received: {:?}", other)
+    }
+
+    client.stop();
+}
+
+#[test]
+fn test_async_clients_share_one_runtime() {
+    let runtime = AsyncRuntime::new().unwrap();
+    let first_listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let second_listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let (first, mut first_reader, mut first_writer, first_rx) = start_client_on(&runtime, &first_listener);
+    let (second, mut second_reader, mut second_writer, second_rx) = start_client_on(&runtime, &second_listener);
+
+    assert_eq!(read_request(&mut first_reader)["method"], "login");
+    assert_eq!(read_request(&mut second_reader)["method"], "login");
+    writeln!(first_writer, "{}", login_response("miner1", "job1")).unwrap();
+    writeln!(second_writer, "{}", login_response("miner2", "job2")).unwrap();
+    expect_job(&first_rx, "job1");
+    expect_job(&second_rx, "job2");
+
+    //stopping one connection leaves the task of the other running
+    first.stop();
+    writeln!(second_writer, "{}", json!({
+        "jsonrpc": "2.0",
+        "method": "job",
+        "params": {"blob": "00", "job_id": "job3", "target": "169f0200"}
+    })).unwrap();
+    expect_job(&second_rx, "job3");
+
+    second.stop();
+}
@@ -0,0 +1,84 @@
+#![cfg(feature = "async-transport")]
+
+extern crate mithril;
+extern crate futures;
+
+use futures::{Async, AsyncSink, Future, Poll, Sink, StartSend, Stream};
+use futures::sync::mpsc::{unbounded, UnboundedReceiver};
+
+use std::io;
+use std::sync::mpsc::{channel};
+use std::sync::{Arc, Mutex};
+
+use mithril::stratum;
+use mithril::stratum::stratum_data;
+use mithril::stratum::async_transport::{Session, run_session};
+use mithril::stratum::pending::{PendingRequests};
+use mithril::stratum::share_queue::{ShareQueue, QueuePolicy};
+
+/// Lines from a channel, written lines are collected
+struct ChannelTransport {
+    input: UnboundedReceiver<String>,
+    written: Arc<Mutex<Vec<String>>>,
+}
+
+impl Stream for ChannelTransport {
+    type Item = String;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<String>, io::Error> {
+        self.input.poll().map_err(|()| io::Error::new(io::ErrorKind::Other, "input closed"))
+    }
+}
+
+impl Sink for ChannelTransport {
+    type SinkItem = String;
+    type SinkError = io::Error;
+
+    fn start_send(&mut self, line: String) -> StartSend<String, io::Error> {
+        self.written.lock().unwrap().push(line);
+        Ok(AsyncSink::Ready)
+    }
+
+    fn poll_complete(&mut self) -> Poll<(), io::Error> {
+        Ok(Async::Ready(()))
+    }
+}
+
+fn session() -> Session {
+    let pool_conf = stratum_data::PoolConfig::new("pool.example.com:3333", "wallet", "x");
+    let (action_tx, _action_rx) = channel();
+    Session::new(pool_conf, action_tx, Arc::new(Mutex::new(PendingRequests::new())), Arc::new(ShareQueue::new(8, QueuePolicy::Error)))
+}
+
+#[test]
+fn test_run_session_without_runtime_writes_commands_until_shutdown() {
+    let (_input_tx, input_rx) = unbounded();
+    let written = Arc::new(Mutex::new(Vec::new()));
+    let transport = ChannelTransport{input: input_rx, written: written.clone()};
+    let (cmd_tx, cmd_rx) = unbounded();
+
+    cmd_tx.unbounded_send(stratum::StratumCmd::Login{}).unwrap();
+    cmd_tx.unbounded_send(stratum::StratumCmd::KeepAlive{miner_id: "miner1".to_string()}).unwrap();
+    cmd_tx.unbounded_send(stratum::StratumCmd::Shutdown{}).unwrap();
+
+    run_session(transport, cmd_rx, None, session()).wait().unwrap();
+
+    let written = written.lock().unwrap();
+    assert_eq!(written.len(), 2);
+    assert!(written[0].contains("\"method\":\"login\""));
+    assert!(written[1].contains("\"method\":\"keepalived\""));
+}
+
+#[test]
+fn test_run_session_ends_with_error_on_eof() {
+    let (input_tx, input_rx) = unbounded::<String>();
+    drop(input_tx);
+    let transport = ChannelTransport{input: input_rx, written: Arc::new(Mutex::new(Vec::new()))};
+    let (_cmd_tx, cmd_rx) = unbounded();
+
+    match run_session(transport, cmd_rx, None, session()).wait() {
+        Err(stratum::StratumError::Io(ref e)) if e.kind() == io::ErrorKind::UnexpectedEof => {},
+        other => assert!(false, "Wrong result returned: {:?}", other)
+    }
+}
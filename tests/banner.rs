@@ -0,0 +1,36 @@
+#![cfg(feature = "miner-bin")]
+
+extern crate mithril;
+
+use mithril::banner;
+use mithril::mithril_config;
+
+use std::path::Path;
+
+#[test]
+fn test_render_default_config() {
+    let mut config = mithril_config::read_config(Path::new("default_config.toml"), "default_config.toml").unwrap();
+    config.pool_conf.wallet_address = "secret_wallet".to_string();
+    config.pool_conf.failover_pool_addresses = vec!["backup:3333".to_string()];
+
+    let rendered = banner::render(&config);
+
+    assert!(rendered.starts_with(&format!("mithril {}", env!("CARGO_PKG_VERSION"))));
+    assert!(rendered.contains("pool: xmrpool.eu:3333\n"));
+    assert!(rendered.contains("failover pool 1: backup:3333"));
+    assert!(rendered.contains("algorithms: randomx, cryptonight-r, cryptonight v8, cryptonight-heavy"));
+    assert!(rendered.contains("randomx: light mode (256 MiB cache)\n"));
+    assert!(rendered.contains("threads: chosen by auto-tuning, new arm every 15 minutes"));
+    assert!(rendered.contains("thread affinity: none\n"));
+    assert!(!rendered.contains("secret_wallet"));
+}
+
+#[test]
+fn test_render_thread_affinity() {
+    let mut config = mithril_config::read_config(Path::new("default_config.toml"), "default_config.toml").unwrap();
+    config.hw_conf.thread_affinity = true;
+    assert!(banner::render(&config).contains("thread affinity: by core type and l3 cache\n"));
+
+    config.hw_conf.skip_efficiency_cores = true;
+    assert!(banner::render(&config).contains("thread affinity: performance cores only\n"));
+}
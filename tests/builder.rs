@@ -0,0 +1,99 @@
+#![cfg(feature = "stratum")]
+
+extern crate mithril;
+
+use mithril::stratum::StratumAction;
+use mithril::stratum::builder::{StratumClientBuilder, BuildError};
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::mpsc::channel;
+use std::time::Duration;
+
+fn valid_builder() -> StratumClientBuilder {
+    let (err_tx, _) = channel();
+    let (action_tx, _) = channel();
+    StratumClientBuilder::new()
+        .pool_address("127.0.0.1:3333")
+        .wallet("wallet", "x")
+        .errors(err_tx)
+        .actions(action_tx)
+}
+
+#[test]
+fn test_validate_ok() {
+    assert!(valid_builder().validate().is_ok());
+}
+
+#[test]
+fn test_validate_missing_settings() {
+    match StratumClientBuilder::new().validate() {
+        Err(BuildError::MissingPoolAddress) => {},
+        other => panic!("unexpected {:?}", other)
+    }
+    match StratumClientBuilder::new().pool_address("pool:3333").validate() {
+        Err(BuildError::MissingWalletAddress) => {},
+        other => panic!("unexpected {:?}", other)
+    }
+    match StratumClientBuilder::new().pool_address("pool:3333").wallet("wallet", "x").validate() {
+        Err(BuildError::MissingErrorChannel) => {},
+        other => panic!("unexpected {:?}", other)
+    }
+}
+
+#[test]
+fn test_validate_invalid_values() {
+    match valid_builder().connect_timeout_secs(0).validate() {
+        Err(BuildError::Zero("connect_timeout_secs")) => {},
+        other => panic!("unexpected {:?}", other)
+    }
+    match valid_builder().keepalive(Some(0), 10).validate() {
+        Err(BuildError::Zero("keepalive_interval_secs")) => {},
+        other => panic!("unexpected {:?}", other)
+    }
+    assert!(valid_builder().keepalive(None, 0).validate().is_ok());
+}
+
+#[test]
+fn test_tls_with_fingerprints_is_valid() {
+    assert!(valid_builder().tls(&["AB:CD"]).validate().is_ok());
+}
+
+#[test]
+fn test_invalid_settings_fail_before_connecting() {
+    //nothing listens on the address, an attempt to connect would fail with an io error
+    match valid_builder().pool_address("127.0.0.1:1").connect_timeout_secs(0).login() {
+        Err(BuildError::Zero(_)) => {},
+        Err(e) => panic!("unexpected error {}", e),
+        Ok(_) => panic!("login with invalid settings")
+    }
+}
+
+#[test]
+fn test_subscriber_receives_login_response() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let (sub_tx, sub_rx) = channel();
+    let (err_tx, _err_rx) = channel();
+    let (action_tx, _action_rx) = channel();
+    let client = StratumClientBuilder::new()
+        .pool_address(&listener.local_addr().unwrap().to_string())
+        .wallet("wallet", "x")
+        .keepalive(None, 0)
+        .errors(err_tx)
+        .actions(action_tx)
+        .subscribe_fn(move |action| { let _ = sub_tx.send(action); })
+        .login()
+        .unwrap();
+
+    let (pool_side, _) = listener.accept().unwrap();
+    let mut login = String::new();
+    BufReader::new(pool_side.try_clone().unwrap()).read_line(&mut login).unwrap();
+    assert!(login.contains("\"login\""));
+    (&pool_side).write_all(b"{\"id\":1,\"jsonrpc\":\"2.0\",\"error\":{\"code\":-1,\"message\":\"Invalid address\"}}\n").unwrap();
+
+    match sub_rx.recv_timeout(Duration::from_secs(5)).unwrap() {
+        StratumAction::LoginRejected{reason} => assert_eq!(reason, "Invalid address"),
+        other => panic!("unexpected action {:?}", other)
+    }
+    client.stop();
+}
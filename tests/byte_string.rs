@@ -27,6 +27,16 @@ fn test_conv_back_and_forth() {
     assert_eq!(str_in, str_out);
 }
 
+#[test]
+fn test_string_to_u8_array_stops_at_invalid_char() {
+    assert_eq!(string_to_u8_array("00ff1"), vec![0x00, 0xff]);
+    assert_eq!(string_to_u8_array("00fg11"), vec![0x00]);
+    assert_eq!(string_to_u8_array("é"), Vec::<u8>::new());
+    assert_eq!(string_to_u8_array("0é"), Vec::<u8>::new());
+    assert_eq!(string_to_u8_array("00é11"), vec![0x00]);
+    assert_eq!(string_to_u8_array("001€"), vec![0x00]);
+}
+
 #[test]
 fn test_hex2_u64x2_be() {
     let u = hex2_u64x2_be("ef49b24c5ec09109bc268b0a0e0fca62");
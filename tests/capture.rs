@@ -0,0 +1,63 @@
+#![cfg(feature = "stratum")]
+
+extern crate mithril;
+
+use std::env;
+use std::fs;
+use std::io::{Write};
+
+use mithril::stratum::capture;
+use mithril::stratum::capture::{CaptureEntry, Direction, SessionCapture};
+use mithril::stratum::trace::{Tracer, TracingWriter};
+
+fn capture_path(name: &str) -> String {
+    let path = env::temp_dir().join(format!("mithril-capture-{}-{}.jsonl", name, std::process::id()));
+    let _ = fs::remove_file(&path);
+    path.to_str().unwrap().to_string()
+}
+
+#[test]
+fn test_redact_credentials() {
+    assert_eq!(capture::redact_credentials(r#"{"id":1,"method":"login","params":{"login":"<wallet>","pass":"secret"}}"#),
+        r#"{"id":1,"method":"login","params":{"login":"<wallet>","pass":"<password>"}}"#);
+    let submit = r#"{"id":2,"method":"submit","params":{"pass":"kept"}}"#;
+    assert_eq!(capture::redact_credentials(submit), submit);
+    assert_eq!(capture::redact_credentials("not json"), "not json");
+}
+
+#[test]
+fn test_session_capture_round_trip() {
+    let path = capture_path("round-trip");
+    {
+        let mut session = SessionCapture::open(&path, "pool:3333", "4wallet").unwrap();
+        session.record(Direction::Sent, r#"{"id":1,"method":"login","params":{"login":"4wallet","pass":"secret"}}"#).unwrap();
+        session.record(Direction::Received, r#"{"id":1,"result":{"status":"OK"}}"#).unwrap();
+    }
+
+    let entries = capture::read_capture(&path).unwrap();
+    let lines : Vec<(Direction, &str)> = entries.iter().map(|e| (e.dir, e.line.as_str())).collect();
+    assert_eq!(lines, vec![
+        (Direction::Connected, "pool:3333"),
+        (Direction::Sent, r#"{"id":1,"method":"login","params":{"login":"<wallet>","pass":"<password>"}}"#),
+        (Direction::Received, r#"{"id":1,"result":{"status":"OK"}}"#)
+    ]);
+    assert!(entries.windows(2).all(|w| w[0].t_ms <= w[1].t_ms));
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_tracer_records_session() {
+    let path = capture_path("tracer");
+    let tracer = Tracer::open_with_session("4wallet", None, Some((path.as_str(), "pool:3333"))).unwrap().unwrap();
+    {
+        let mut out : Vec<u8> = Vec::new();
+        let mut writer = TracingWriter::new(&mut out, tracer.clone());
+        writeln!(writer, "{{\"id\":2,\"method\":\"keepalived\",\"params\":{{\"id\":\"miner\"}}}}").unwrap();
+    }
+
+    let entries = capture::read_capture(&path).unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[1], CaptureEntry{t_ms: entries[1].t_ms, dir: Direction::Sent,
+        line: "{\"id\":2,\"method\":\"keepalived\",\"params\":{\"id\":\"miner\"}}".to_string()});
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,40 @@
+#![cfg(feature = "stratum")]
+
+#![feature(box_syntax)]
+
+extern crate mithril;
+
+use mithril::byte_string;
+use mithril::cryptonight::aes::{AESSupport};
+use mithril::cryptonight::hash::{MEM_SIZE};
+use mithril::u64x2::u64x2;
+use mithril::worker::checked_aes;
+use mithril::worker::checked_aes::{CheckedAes};
+
+const INPUT : &str = "09099aebd3e1057aad462f2d998d8b9adcf16e03a5bf1820728240eefe433735904fcf663eeb1d00000000b0203ca955ed446e47ab9e884941bc67c75ecb06e444036aafc7ff442c60d2f907";
+const HASH : &str = "f12b181f2b5a84d8fca047206c605f20b6b3a9b29da3505152caaeee758e39fe";
+
+#[test]
+fn test_check_share_matching_hash() {
+    let mut scratchpad : Box<[u64x2; MEM_SIZE]> = box [u64x2(0,0); MEM_SIZE];
+    let mut aes = CheckedAes::new(AESSupport::HW);
+    let input = byte_string::string_to_u8_array(INPUT);
+
+    assert!(aes.check_share(&mut scratchpad, &input, HASH));
+    assert_eq!(aes.support(), AESSupport::HW);
+}
+
+#[test]
+fn test_check_share_mismatch_falls_back() {
+    let mut scratchpad : Box<[u64x2; MEM_SIZE]> = box [u64x2(0,0); MEM_SIZE];
+    let mut aes = CheckedAes::new(AESSupport::HW);
+    let input = byte_string::string_to_u8_array(INPUT);
+    let wrong_hash = "0000000000000000000000000000000000000000000000000000000000000000";
+
+    assert!(!aes.check_share(&mut scratchpad, &input, wrong_hash));
+    assert_eq!(aes.support(), AESSupport::SW);
+    assert!(checked_aes::fallback_count() >= 1);
+
+    //software AES is not checked again
+    assert!(aes.check_share(&mut scratchpad, &input, wrong_hash));
+}
@@ -0,0 +1,53 @@
+#![cfg(feature = "stratum")]
+
+extern crate mithril;
+
+use mithril::stratum::StratumAction;
+use mithril::stratum::client_state::{ClientState, ConnectionState};
+
+fn job(job_id: &str) -> StratumAction {
+    StratumAction::Job{miner_id: "miner".to_string(), blob: "00".to_string(), job_id: job_id.to_string(),
+        target: "169f0200".to_string(), difficulty: 1, algo: None, height: None, seed_hash: None, next_seed_hash: None}
+}
+
+#[test]
+fn test_first_job_logs_in() {
+    let mut state = ClientState::new();
+    assert_eq!(state.state(), ConnectionState::Connecting);
+    assert_eq!(state.current_job(), None);
+
+    state.observe(&StratumAction::KeepAliveOk);
+    assert_eq!(state.state(), ConnectionState::Connecting);
+
+    state.observe(&job("job1"));
+    state.observe(&job("job2"));
+    assert_eq!(state.state(), ConnectionState::LoggedIn);
+    assert_eq!(state.current_job(), Some(job("job2")));
+
+    state.disconnected();
+    assert_eq!(state.state(), ConnectionState::Disconnected);
+    //the last job stays available after the connection ended
+    assert_eq!(state.current_job(), Some(job("job2")));
+}
+
+#[test]
+fn test_cancelled_job_is_forgotten() {
+    let mut state = ClientState::new();
+    state.observe(&job("job1"));
+    state.observe(&StratumAction::JobCancelled{job_id: Some("job0".to_string())});
+    assert_eq!(state.current_job(), Some(job("job1")));
+
+    state.observe(&StratumAction::JobCancelled{job_id: Some("job1".to_string())});
+    assert_eq!(state.current_job(), None);
+
+    state.observe(&job("job2"));
+    state.observe(&StratumAction::JobCancelled{job_id: None});
+    assert_eq!(state.current_job(), None);
+}
+
+#[test]
+fn test_rejected_login_disconnects() {
+    let mut state = ClientState::new();
+    state.observe(&StratumAction::LoginRejected{reason: "invalid wallet".to_string()});
+    assert_eq!(state.state(), ConnectionState::Disconnected);
+}
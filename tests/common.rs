@@ -0,0 +1,64 @@
+extern crate mithril;
+
+use mithril::cryptonight::common;
+
+#[test]
+fn test_state_is_little_endian() {
+    let mut bytes = [0u8; 200];
+    bytes[0] = 0x01;
+    bytes[7] = 0x80;
+    bytes[192] = 0xFF;
+    let words = common::state_to_u64(&bytes);
+    assert_eq!(words[0], 0x8000_0000_0000_0001);
+    assert_eq!(words[24], 0xFF);
+    for w in &words[1..24] {
+        assert_eq!(*w, 0);
+    }
+}
+
+#[test]
+fn test_state_round_trip() {
+    let mut bytes = [0u8; 200];
+    for (i, b) in bytes.iter_mut().enumerate() {
+        *b = i as u8;
+    }
+    let words = common::state_to_u64(&bytes);
+    assert_eq!(words[1], 0x0F0E_0D0C_0B0A_0908);
+    assert_eq!(&common::state_to_u8(&words)[..], &bytes[..]);
+}
+
+#[test]
+fn test_read_u64_le() {
+    let bytes = [0u8, 0, 0, 0, 0, 0, 0, 0, 0x34, 0x12, 0, 0, 0, 0, 0, 0];
+    assert_eq!(common::read_u64_le(&bytes, 0), 0);
+    assert_eq!(common::read_u64_le(&bytes, 1), 0x1234);
+}
+
+#[test]
+#[cfg(target_endian = "little")]
+fn test_state_matches_native_order_on_little_endian() {
+    let mut bytes = [0u8; 200];
+    bytes[0] = 0x01;
+    let words = common::state_to_u64(&bytes);
+    assert_eq!(&words[0].to_ne_bytes()[..], &bytes[..8]);
+}
+
+#[test]
+#[cfg(target_endian = "big")]
+fn test_state_ignores_native_order_on_big_endian() {
+    let mut bytes = [0u8; 200];
+    bytes[0] = 0x01;
+    let words = common::state_to_u64(&bytes);
+    assert_eq!(words[0], 0x01);
+    //a transmute of the state would have read 0x0100_0000_0000_0000
+    assert_eq!(&words[0].to_ne_bytes()[..], &[0, 0, 0, 0, 0, 0, 0, 0x01]);
+    assert_eq!(&common::state_to_u8(&words)[..8], &[0x01, 0, 0, 0, 0, 0, 0, 0]);
+}
+
+#[test]
+#[cfg(target_endian = "big")]
+fn test_read_u64_le_on_big_endian() {
+    let bytes = [0x34u8, 0x12, 0, 0, 0, 0, 0, 0];
+    assert_eq!(common::read_u64_le(&bytes, 0), 0x1234);
+    assert_eq!(common::read_u64_le(&bytes, 0).to_ne_bytes(), [0, 0, 0, 0, 0, 0, 0x12, 0x34]);
+}
@@ -0,0 +1,31 @@
+#![cfg(feature = "miner-bin")]
+
+extern crate mithril;
+
+extern crate log;
+
+use log::{LevelFilter};
+use mithril::console::{ConsoleCmd};
+
+#[test]
+fn test_console_cmd_parse() {
+    assert_eq!(ConsoleCmd::parse("h\n"), Some(ConsoleCmd::ConnectionHistory));
+    assert_eq!(ConsoleCmd::parse("history"), Some(ConsoleCmd::ConnectionHistory));
+    assert_eq!(ConsoleCmd::parse(" e "), Some(ConsoleCmd::Events));
+    assert_eq!(ConsoleCmd::parse("d"), Some(ConsoleCmd::ToggleDryRun));
+    assert_eq!(ConsoleCmd::parse("dryrun"), Some(ConsoleCmd::ToggleDryRun));
+    assert_eq!(ConsoleCmd::parse("?"), Some(ConsoleCmd::Help));
+    assert_eq!(ConsoleCmd::parse("x"), Some(ConsoleCmd::Help));
+    assert_eq!(ConsoleCmd::parse(""), None);
+}
+
+#[test]
+fn test_console_cmd_parse_log_level_and_trace() {
+    assert_eq!(ConsoleCmd::parse("l debug"), Some(ConsoleCmd::LogLevel(LevelFilter::Debug)));
+    assert_eq!(ConsoleCmd::parse("log TRACE\n"), Some(ConsoleCmd::LogLevel(LevelFilter::Trace)));
+    assert_eq!(ConsoleCmd::parse("l off"), Some(ConsoleCmd::LogLevel(LevelFilter::Off)));
+    assert_eq!(ConsoleCmd::parse("l verbose"), Some(ConsoleCmd::Help));
+    assert_eq!(ConsoleCmd::parse("l"), Some(ConsoleCmd::Help));
+    assert_eq!(ConsoleCmd::parse("t"), Some(ConsoleCmd::ToggleTrace));
+    assert_eq!(ConsoleCmd::parse("trace"), Some(ConsoleCmd::ToggleTrace));
+}
@@ -0,0 +1,80 @@
+#![cfg(feature = "miner-bin")]
+
+extern crate mithril;
+
+use std::env;
+use std::fs;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use mithril::cpu_share;
+use mithril::cpu_share::{CpuShare};
+
+#[test]
+fn test_known_miner() {
+    assert_eq!(cpu_share::known_miner("xmrig\n"), Some("xmrig"));
+    assert_eq!(cpu_share::known_miner("xmrig-notls"), Some("xmrig"));
+    assert_eq!(cpu_share::known_miner("xmr-stak-rx"), Some("xmr-stak-rx"));
+    assert_eq!(cpu_share::known_miner("mithril"), Some("mithril"));
+    assert_eq!(cpu_share::known_miner("xmrigger"), None);
+    assert_eq!(cpu_share::known_miner("bash"), None);
+}
+
+#[test]
+fn test_split_cores() {
+    assert_eq!(cpu_share::split_cores(8, 1, 0), 8);
+    assert_eq!(cpu_share::split_cores(8, 3, 0), 3);
+    assert_eq!(cpu_share::split_cores(8, 3, 1), 3);
+    assert_eq!(cpu_share::split_cores(8, 3, 2), 2);
+    assert_eq!(cpu_share::split_cores(2, 3, 2), 1);
+    assert_eq!(cpu_share::split_cores(8, 0, 0), 8);
+}
+
+#[test]
+fn test_limit_threads() {
+    assert_eq!(cpu_share::limit_threads(16, 8, 8), 16);
+    assert_eq!(cpu_share::limit_threads(16, 4, 8), 8);
+    assert_eq!(cpu_share::limit_threads(5, 3, 8), 2);
+    assert_eq!(cpu_share::limit_threads(1, 1, 8), 1);
+}
+
+#[test]
+fn test_cores_message() {
+    assert_eq!(cpu_share::parse_cores(&cpu_share::format_cores(3, 8)), Some((3, 8)));
+    assert_eq!(cpu_share::parse_cores("join 42"), None);
+    assert_eq!(cpu_share::parse_cores("cores x 8"), None);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_instances_split_the_cores() {
+    let socket_path = env::temp_dir().join(format!("mithril_cpu_share_test_{}.sock", std::process::id()));
+    let _ = fs::remove_file(&socket_path);
+
+    let first = CpuShare::start(&socket_path, 8).unwrap();
+    assert_eq!(first.cores(), 8);
+    let second = CpuShare::start(&socket_path, 8).unwrap();
+    assert_eq!(second.cores(), 4);
+
+    //the coordinator updates its own share when the member joined
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while first.cores() != 4 && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(first.cores(), 4);
+    assert_eq!(second.total(), 8);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_stale_socket_is_replaced() {
+    use std::os::unix::net::{UnixListener};
+
+    let socket_path = env::temp_dir().join(format!("mithril_cpu_share_stale_test_{}.sock", std::process::id()));
+    let _ = fs::remove_file(&socket_path);
+    //the socket file stays after the listener is closed, connects to it are refused
+    drop(UnixListener::bind(&socket_path).unwrap());
+
+    let share = CpuShare::start(&socket_path, 8).unwrap();
+    assert_eq!(share.cores(), 8);
+}
@@ -0,0 +1,41 @@
+#![cfg(feature = "stratum")]
+
+extern crate mithril;
+extern crate rand;
+
+use rand::Rng;
+
+use mithril::determinism;
+use mithril::worker::nonce;
+use mithril::worker::nonce::{NonceStrategyKind};
+
+fn draw(stream: u64) -> Vec<u64> {
+    let mut rng = determinism::seeded_rng(42, stream);
+    (0..8).map(|_| rng.gen::<u64>()).collect()
+}
+
+#[test]
+fn test_seeded_rng_is_reproducible() {
+    assert_eq!(draw(0), draw(0));
+    assert_eq!(draw(determinism::STREAM_KEEPALIVE_JITTER), draw(determinism::STREAM_KEEPALIVE_JITTER));
+}
+
+#[test]
+fn test_seeded_rng_streams_differ() {
+    assert_ne!(draw(0), draw(1));
+    assert_ne!(draw(1), draw(determinism::STREAM_KEEPALIVE_JITTER));
+}
+
+#[test]
+fn test_random_walk_is_reproducible_with_seed() {
+    //all tests of this file run with the seed set
+    determinism::set_seed(7);
+    assert_eq!(determinism::seed(), Some(7));
+
+    let nonces = |partition: u64| -> Vec<u64> {
+        let mut strategy = nonce::new_strategy(NonceStrategyKind::RandomWalk, partition, 2, 10);
+        (0..16).map(|_| strategy.next_nonce().unwrap()).collect()
+    };
+    assert_eq!(nonces(1), nonces(1));
+    assert_eq!(nonces(3), nonces(3));
+}
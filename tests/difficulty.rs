@@ -0,0 +1,69 @@
+extern crate mithril;
+
+use mithril::difficulty;
+
+#[test]
+fn test_hash_difficulty() {
+    assert_eq!(difficulty::hash_difficulty(0), u64::max_value());
+    assert_eq!(difficulty::hash_difficulty(u64::max_value()), 1);
+    assert_eq!(difficulty::hash_difficulty(u64::max_value() / 1000), 1000);
+}
+
+#[test]
+fn test_target_difficulty() {
+    let target = difficulty::target_u64(171798); //target 169f0200
+    assert_eq!(difficulty::target_difficulty(target), 25000);
+}
+
+#[test]
+fn test_format_difficulty() {
+    assert_eq!(difficulty::format_difficulty(0), "0");
+    assert_eq!(difficulty::format_difficulty(999), "999");
+    assert_eq!(difficulty::format_difficulty(1000), "1k");
+    assert_eq!(difficulty::format_difficulty(142_500), "142.5k");
+    assert_eq!(difficulty::format_difficulty(120_000), "120k");
+    assert_eq!(difficulty::format_difficulty(2_340_000), "2.3M");
+    assert_eq!(difficulty::format_difficulty(u64::max_value()), "18.4E");
+}
+
+#[test]
+fn test_parse_target_32_and_64_bit() {
+    assert_eq!(difficulty::parse_target("169f0200"), Ok(difficulty::target_u64(171798)));
+    assert_eq!(difficulty::parse_target("1027000000000000"), Ok(10000));
+    assert_eq!(difficulty::parse_target("ffffffffffffffff"), Ok(u64::max_value()));
+}
+
+#[test]
+fn test_parse_target_invalid() {
+    assert_eq!(difficulty::parse_target("00000000"), Err(difficulty::TargetError::Zero));
+    assert_eq!(difficulty::parse_target("0000000000000000"), Err(difficulty::TargetError::Zero));
+    assert_eq!(difficulty::parse_target("169f02"), Err(difficulty::TargetError::Malformed("169f02".to_string())));
+    assert_eq!(difficulty::parse_target("+69f0200"), Err(difficulty::TargetError::Malformed("+69f0200".to_string())));
+    assert_eq!(difficulty::parse_target("zz9f0200"), Err(difficulty::TargetError::Malformed("zz9f0200".to_string())));
+}
+
+#[test]
+fn test_check_target() {
+    let target = difficulty::target_u64(171798);
+    assert_eq!(difficulty::check_target(target), Ok(target));
+    //too easy, accepted
+    assert_eq!(difficulty::check_target(u64::max_value()), Ok(u64::max_value()));
+    //too hard
+    assert_eq!(difficulty::check_target(1), Err(difficulty::TargetError::TooHard(u64::max_value())));
+}
+
+#[test]
+fn test_clamp_target() {
+    let target = difficulty::target_u64(171798);
+    assert!(!difficulty::is_too_easy(target));
+    assert_eq!(difficulty::clamp_target(target), target);
+    assert!(difficulty::is_too_easy(u64::max_value()));
+    assert_eq!(difficulty::clamp_target(u64::max_value()), u64::max_value() / difficulty::MIN_JOB_DIFFICULTY);
+}
+
+#[test]
+fn test_target_hex() {
+    assert_eq!(difficulty::target_hex(10000), "1027000000000000");
+    let target = difficulty::target_u64(171798);
+    assert_eq!(difficulty::parse_target(&difficulty::target_hex(target)), Ok(target));
+}
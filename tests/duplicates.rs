@@ -0,0 +1,28 @@
+#![cfg(feature = "stratum")]
+
+extern crate mithril;
+
+use mithril::stratum::duplicates::RecentShares;
+
+#[test]
+fn test_recent_shares_detects_duplicate() {
+    let mut recent = RecentShares::new();
+    assert!(!recent.is_duplicate("job1", "00000001"));
+    assert!(!recent.is_duplicate("job1", "00000002"));
+    assert!(!recent.is_duplicate("job2", "00000001"));
+    assert!(recent.is_duplicate("job1", "00000001"));
+    assert!(recent.is_duplicate("job1", "00000001"));
+    assert_eq!(recent.dropped(), 2);
+}
+
+#[test]
+fn test_recent_shares_forgets_old_shares() {
+    let mut recent = RecentShares::new();
+    assert!(!recent.is_duplicate("job", "00000000"));
+    for i in 1..1000 {
+        assert!(!recent.is_duplicate("job", &format!("{:08x}", i)));
+    }
+    //the first share was evicted
+    assert!(!recent.is_duplicate("job", "00000000"));
+    assert_eq!(recent.dropped(), 0);
+}
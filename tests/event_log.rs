@@ -0,0 +1,76 @@
+#![cfg(feature = "stratum")]
+
+extern crate mithril;
+
+use std::time::{Duration};
+
+use mithril::stratum::event_log;
+use mithril::stratum::event_log::{EventLog, Ring, ConnectionEvent, MAX_SHARES};
+
+#[test]
+fn test_ring_drops_oldest() {
+    let mut ring = Ring::new(3);
+    for i in 0..5 {
+        ring.push(i);
+    }
+    assert_eq!(ring.len(), 3);
+    assert_eq!(ring.recent(10), vec![&4, &3, &2]);
+    assert_eq!(ring.recent(2), vec![&4, &3]);
+}
+
+#[test]
+fn test_ring_zero_capacity() {
+    let mut ring = Ring::new(0);
+    ring.push(1);
+    assert!(ring.is_empty());
+}
+
+#[test]
+fn test_event_log_reject_streak() {
+    let mut log = EventLog::new();
+    assert_eq!(log.reject_streak(), 0);
+    log.record_share("a", Some("Low difficulty share".to_string()));
+    log.record_share("a", None);
+    log.record_share("b", Some("Block expired".to_string()));
+    log.record_share("b", Some("Block expired".to_string()));
+    assert_eq!(log.reject_streak(), 2);
+
+    for _ in 0..MAX_SHARES + 1 {
+        log.record_share("c", None);
+    }
+    assert_eq!(log.shares.len(), MAX_SHARES);
+    assert_eq!(log.reject_streak(), 0);
+}
+
+#[test]
+fn test_event_log_render() {
+    let mut log = EventLog::new();
+    log.record_job("job1", "169f0200");
+    log.record_share("job1", Some("Low difficulty share".to_string()));
+    let rendered = log.render(20);
+    assert!(rendered.contains("job1 target 169f0200"));
+    assert!(rendered.contains("job job1 rejected: Low difficulty share"));
+}
+
+#[test]
+fn test_event_log_render_connections() {
+    let mut log = EventLog::new();
+    log.record_connection(ConnectionEvent::Connected{address: "pool:3333".to_string()});
+    log.record_connection(ConnectionEvent::LoginOk);
+    log.record_connection(ConnectionEvent::Disconnected{reason: "io error: connection reset".to_string()});
+    log.record_connection(ConnectionEvent::Failover{from: "pool:3333".to_string(), to: "backup:3333".to_string()});
+
+    let rendered = log.render_connections(10);
+    let lines : Vec<&str> = rendered.lines().collect();
+    assert_eq!(lines.len(), 5);
+    assert_eq!(lines[0], "last 4 connection events:");
+    assert!(lines[1].ends_with("failover from pool:3333 to backup:3333"));
+    assert!(lines[2].ends_with("disconnected: io error: connection reset"));
+    assert!(lines[4].ends_with("connected to pool:3333"));
+}
+
+#[test]
+fn test_format_age() {
+    assert_eq!(event_log::format_age(Duration::from_secs(5)), "0m 05s");
+    assert_eq!(event_log::format_age(Duration::from_secs(3 * 3600 + 7 * 60 + 9)), "3h 07m 09s");
+}
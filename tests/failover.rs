@@ -0,0 +1,97 @@
+#![cfg(feature = "stratum")]
+
+extern crate mithril;
+
+use mithril::stratum::failover::{Failover};
+
+fn failover() -> Failover {
+    Failover::new("primary:3333", &["second:3333".to_string(), "third:3333".to_string()], 2)
+}
+
+#[test]
+fn test_starts_with_primary() {
+    let f = failover();
+    assert_eq!(f.active_address(), "primary:3333");
+    assert!(!f.is_failed_over());
+}
+
+#[test]
+fn test_fails_over_after_max_failures() {
+    let mut f = failover();
+    f.record_failure();
+    assert_eq!(f.active_index(), 0);
+    f.record_failure();
+    assert_eq!(f.active_address(), "second:3333");
+    f.record_failure();
+    f.record_failure();
+    assert_eq!(f.active_address(), "third:3333");
+    f.record_failure();
+    f.record_failure();
+    assert_eq!(f.active_address(), "primary:3333");
+}
+
+#[test]
+fn test_success_resets_failures() {
+    let mut f = failover();
+    f.record_failure();
+    f.record_success();
+    f.record_failure();
+    assert_eq!(f.active_index(), 0);
+}
+
+#[test]
+fn test_no_failover_without_alternatives() {
+    let mut f = Failover::new("primary:3333", &[], 1);
+    f.record_failure();
+    f.record_failure();
+    assert_eq!(f.active_address(), "primary:3333");
+}
+
+#[test]
+fn test_probe_primary_switches_back_on_success() {
+    let mut f = failover();
+    assert!(!f.probe_primary());
+    f.record_failure();
+    f.record_failure();
+    assert!(f.probe_primary());
+    assert_eq!(f.active_address(), "primary:3333");
+    f.record_success();
+    f.record_failure();
+    assert_eq!(f.active_address(), "primary:3333");
+}
+
+#[test]
+fn test_failed_probe_returns_to_previous_pool() {
+    let mut f = failover();
+    for _ in 0..4 {
+        f.record_failure();
+    }
+    assert_eq!(f.active_address(), "third:3333");
+    assert!(f.probe_primary());
+    f.record_failure();
+    assert_eq!(f.active_address(), "third:3333");
+}
+
+#[test]
+fn test_unhealthy_fails_over_immediately() {
+    let mut f = failover();
+    assert!(f.record_unhealthy());
+    assert_eq!(f.active_address(), "second:3333");
+}
+
+#[test]
+fn test_unhealthy_without_other_pool() {
+    let mut f = Failover::new("primary:3333", &[], 2);
+    assert!(!f.record_unhealthy());
+    assert_eq!(f.active_address(), "primary:3333");
+}
+
+#[test]
+fn test_unhealthy_probe_switches_back() {
+    let mut f = failover();
+    f.record_failure();
+    f.record_failure();
+    assert!(f.probe_primary());
+    assert!(f.record_unhealthy());
+    assert_eq!(f.active_address(), "second:3333");
+}
@@ -0,0 +1,38 @@
+#![cfg(feature = "miner-bin")]
+
+extern crate mithril;
+
+use mithril::fleet;
+use mithril::fleet::{Host};
+
+#[test]
+fn test_parse_hosts() {
+    let inventory = "# inventory\nrig01 8\n\n  rig02   16 # big one\n";
+    let hosts = fleet::parse_hosts(inventory).unwrap();
+    assert_eq!(hosts, vec![Host{name: "rig01".to_string(), num_threads: 8},
+                           Host{name: "rig02".to_string(), num_threads: 16}]);
+}
+
+#[test]
+fn test_parse_hosts_invalid_threads() {
+    assert!(fleet::parse_hosts("rig01 0").is_err());
+    assert!(fleet::parse_hosts("rig01 many").is_err());
+    assert!(fleet::parse_hosts("rig01").is_err());
+}
+
+#[test]
+fn test_parse_hosts_rejects_paths() {
+    assert!(fleet::parse_hosts("../rig01 4").is_err());
+    assert!(fleet::parse_hosts("/etc/rig01 4").is_err());
+    assert!(fleet::parse_hosts("rigs/rig01 4").is_err());
+    assert!(fleet::parse_hosts("rigs\\rig01 4").is_err());
+    assert!(fleet::parse_hosts("rig..01 4").is_err());
+    assert!(fleet::parse_hosts("rig-01.example.com 4").is_ok());
+}
+
+#[test]
+fn test_render() {
+    let template = "[pool]\npool_password = \"{{host}}\"\n[worker]\nnum_threads = {{num_threads}}\n";
+    let host = Host{name: "rig01".to_string(), num_threads: 4};
+    assert_eq!(fleet::render(template, &host), "[pool]\npool_password = \"rig01\"\n[worker]\nnum_threads = 4\n");
+}
@@ -0,0 +1,147 @@
+#![cfg(feature = "stratum")]
+
+extern crate mithril;
+
+use mithril::stratum::flood_guard::{Decision, FloodGuard, FloodGuardConfig, ERROR_WINDOW_SECS};
+use std::time::{Duration, Instant};
+
+fn config() -> FloodGuardConfig {
+    FloodGuardConfig{max_rejects: 3, max_errors: 4, backoff_secs: 10, max_backoff_secs: 30, unhealthy_after: 2}
+}
+
+fn secs(s: u64) -> Duration {
+    Duration::from_secs(s)
+}
+
+#[test]
+fn test_disabled_never_backs_off() {
+    let mut guard = FloodGuard::new(FloodGuardConfig::disabled());
+    let now = Instant::now();
+    for _ in 0..100 {
+        guard.record_rejected(false, now);
+        guard.record_error(now);
+    }
+    assert_eq!(guard.allow(now), Decision::Submit);
+    assert!(!guard.is_unhealthy());
+}
+
+#[test]
+fn test_backs_off_after_rejects_in_row() {
+    let mut guard = FloodGuard::new(config());
+    let now = Instant::now();
+    guard.record_rejected(false, now);
+    guard.record_rejected(false, now);
+    guard.record_accepted();
+    guard.record_rejected(false, now);
+    guard.record_rejected(false, now);
+    assert_eq!(guard.allow(now), Decision::Submit);
+    guard.record_rejected(false, now);
+    assert!(guard.is_backing_off());
+    assert_eq!(guard.allow(now + secs(9)), Decision::Withhold);
+    assert_eq!(guard.withheld(), 1);
+}
+
+#[test]
+fn test_stale_shares_are_not_held_against_the_pool() {
+    let mut guard = FloodGuard::new(config());
+    let now = Instant::now();
+    for _ in 0..10 {
+        guard.record_rejected(true, now);
+    }
+    assert_eq!(guard.allow(now), Decision::Submit);
+}
+
+#[test]
+fn test_backs_off_on_error_storm() {
+    let mut guard = FloodGuard::new(config());
+    let now = Instant::now();
+    guard.record_error(now);
+    guard.record_error(now);
+    guard.record_error(now);
+    //the first errors left the window
+    guard.record_error(now + secs(ERROR_WINDOW_SECS + 1));
+    assert_eq!(guard.allow(now), Decision::Submit);
+    for _ in 0..3 {
+        guard.record_error(now + secs(ERROR_WINDOW_SECS + 2));
+    }
+    assert_eq!(guard.allow(now + secs(ERROR_WINDOW_SECS + 2)), Decision::Withhold);
+}
+
+#[test]
+fn test_accepted_probe_resumes() {
+    let mut guard = FloodGuard::new(config());
+    let now = Instant::now();
+    for _ in 0..3 {
+        guard.record_rejected(false, now);
+    }
+    assert_eq!(guard.allow(now + secs(10)), Decision::Probe);
+    assert_eq!(guard.allow(now + secs(11)), Decision::Withhold);
+    guard.record_accepted();
+    assert_eq!(guard.allow(now + secs(12)), Decision::Submit);
+    assert!(!guard.is_backing_off());
+}
+
+#[test]
+fn test_rejected_probe_doubles_backoff_and_reports_unhealthy_once() {
+    let mut guard = FloodGuard::new(config());
+    let now = Instant::now();
+    for _ in 0..3 {
+        guard.record_rejected(false, now);
+    }
+    assert!(!guard.take_unhealthy());
+    assert_eq!(guard.allow(now + secs(10)), Decision::Probe);
+    guard.record_rejected(false, now + secs(10));
+    assert!(guard.is_unhealthy());
+    assert!(guard.take_unhealthy());
+    assert!(!guard.take_unhealthy());
+    assert_eq!(guard.allow(now + secs(29)), Decision::Withhold);
+    assert_eq!(guard.allow(now + secs(30)), Decision::Probe);
+    //capped at max_backoff_secs
+    guard.record_rejected(false, now + secs(30));
+    assert_eq!(guard.allow(now + secs(59)), Decision::Withhold);
+    assert_eq!(guard.allow(now + secs(60)), Decision::Probe);
+    assert!(!guard.take_unhealthy());
+}
+
+#[test]
+fn test_unanswered_probe_backs_off() {
+    let mut guard = FloodGuard::new(config());
+    let now = Instant::now();
+    for _ in 0..3 {
+        guard.record_rejected(false, now);
+    }
+    assert_eq!(guard.allow(now + secs(10)), Decision::Probe);
+    assert_eq!(guard.allow(now + secs(20)), Decision::Withhold);
+    assert_eq!(guard.allow(now + secs(39)), Decision::Withhold);
+    assert_eq!(guard.allow(now + secs(40)), Decision::Probe);
+}
+
+#[test]
+fn test_stale_probe_probes_again() {
+    let mut guard = FloodGuard::new(config());
+    let now = Instant::now();
+    for _ in 0..3 {
+        guard.record_rejected(false, now);
+    }
+    assert_eq!(guard.allow(now + secs(10)), Decision::Probe);
+    guard.record_rejected(true, now + secs(11));
+    assert_eq!(guard.allow(now + secs(11)), Decision::Probe);
+    assert!(!guard.is_unhealthy());
+}
+
+#[test]
+fn test_tick_reports_silent_pool_unhealthy() {
+    let mut guard = FloodGuard::new(config());
+    let now = Instant::now();
+    for _ in 0..3 {
+        guard.record_rejected(false, now);
+    }
+    assert_eq!(guard.allow(now + secs(10)), Decision::Probe);
+    guard.tick(now + secs(19));
+    assert!(!guard.take_unhealthy());
+    //no share and no response after the probe, only the tick notices
+    guard.tick(now + secs(20));
+    assert!(guard.is_unhealthy());
+    assert!(guard.take_unhealthy());
+    assert!(!guard.take_unhealthy());
+}
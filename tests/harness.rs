@@ -0,0 +1,59 @@
+#![cfg(feature = "miner-bin")]
+
+extern crate mithril;
+
+use mithril::harness::{HashStats, BandwidthSample};
+
+fn record_n(stats: &mut HashStats, n: u64, selector: u8, first_byte: u8) {
+    let mut state = [0u8; 200];
+    state[0] = selector;
+    for _ in 0..n {
+        stats.record(&state, &[first_byte]);
+    }
+}
+
+#[test]
+fn test_uniform_distribution_not_biased() {
+    let mut stats = HashStats::default();
+    for selector in 0..4 {
+        record_n(&mut stats, 500, selector, 0x00);
+        record_n(&mut stats, 500, selector, 0xFF);
+    }
+    assert_eq!(stats.samples, 4000);
+    assert_eq!(stats.selector_counts, [1000, 1000, 1000, 1000]);
+    assert!(!stats.selector_biased());
+    assert!(stats.biased_bits().is_empty());
+}
+
+#[test]
+fn test_skewed_distribution_biased() {
+    let mut stats = HashStats::default();
+    record_n(&mut stats, 2000, 0, 0x01);
+    record_n(&mut stats, 2000, 2, 0x00);
+    assert!(stats.selector_biased());
+    assert_eq!(stats.biased_bits(), vec![1, 2, 3, 4, 5, 6, 7]);
+}
+
+#[test]
+fn test_too_few_samples_never_biased() {
+    let mut stats = HashStats::default();
+    record_n(&mut stats, 10, 3, 0xFF);
+    assert!(!stats.selector_biased());
+    assert!(stats.biased_bits().is_empty());
+}
+
+fn samples(rates: &[f64]) -> Vec<BandwidthSample> {
+    rates.iter().enumerate().map(|(i, r)| BandwidthSample{num_threads: i + 1, bytes_per_second: *r}).collect()
+}
+
+#[test]
+fn test_saturation_point() {
+    assert_eq!(harness_saturation(&[100.0, 190.0, 270.0, 280.0, 285.0]), Some(3));
+    assert_eq!(harness_saturation(&[100.0, 200.0, 300.0]), None);
+    assert_eq!(harness_saturation(&[100.0, 90.0]), Some(1));
+    assert_eq!(harness_saturation(&[100.0]), None);
+}
+
+fn harness_saturation(rates: &[f64]) -> Option<usize> {
+    mithril::harness::saturation_point(&samples(rates))
+}
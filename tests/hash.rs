@@ -6,6 +6,7 @@ use mithril::byte_string;
 use mithril::cryptonight::hash;
 use mithril::cryptonight::hash::{MEM_SIZE, ebyte_mul, shuffle_0, shuffle_1, division};
 use mithril::cryptonight::keccak;
+use mithril::cryptonight::{HashVersion};
 use mithril::cryptonight::aes;
 use mithril::cryptonight::aes::{AESSupport};
 use mithril::u64x2::{u64x2};
@@ -102,6 +103,33 @@ fn test_hash_software_v8() {
     assert_eq!(hash::hash_alloc_scratchpad(&input, &aes), "f4e15a61d170cac5e21deff989b1db2af88455c1a8539c3fabfee5be077f32f9");
 }
 
+#[test]
+fn test_hash_software_r() {
+    let aes = aes::new(AESSupport::SW);
+    let mut scratchpad : Box<[u64x2; MEM_SIZE]> = box [u64x2(0,0); MEM_SIZE];
+
+    //test vectors of the monero reference implementation
+    let input = byte_string::string_to_u8_array("5468697320697320612074657374205468697320697320612074657374205468697320697320612074657374");
+    let variant = hash::Variant::new(HashVersion::VersionR, 1806260).unwrap();
+    assert_eq!(hash::hash_variant(&mut scratchpad[..], &input, &aes, &variant), "f759588ad57e758467295443a9bd71490abff8e9dad1b95b6bf2f5d0d78387bc");
+
+    let input = byte_string::string_to_u8_array("4c6f72656d20697073756d20646f6c6f722073697420616d65742c20636f6e73656374657475722061646970697363696e67");
+    let variant = hash::Variant::new(HashVersion::VersionR, 1806261).unwrap();
+    assert_eq!(hash::hash_variant(&mut scratchpad[..], &input, &aes, &variant), "5bb833deca2bdd7252a9ccd7b4ce0b6a4854515794b56c207262f7a5b9bdb566");
+}
+
+#[test]
+fn test_hash_variant_v8_is_hash() {
+    let aes = aes::new(AESSupport::SW);
+    let mut scratchpad : Box<[u64x2; MEM_SIZE]> = box [u64x2(0,0); MEM_SIZE];
+
+    let input = byte_string::string_to_u8_array("09099aebd3e1057aad462f2d998d8b9adcf16e03a5bf1820728240eefe433735904fcf663eeb1d00000000b0203ca955ed446e47ab9e884941bc67c75ecb06e444036aafc7ff442c60d2f907");
+    let variant = hash::Variant::new(HashVersion::V8, 1806260).unwrap();
+    assert_eq!(variant, hash::Variant::V8);
+    assert_eq!(hash::Variant::new(HashVersion::RandomX, 1806260), None);
+    assert_eq!(hash::hash_variant(&mut scratchpad[..], &input, &aes, &variant), "f12b181f2b5a84d8fca047206c605f20b6b3a9b29da3505152caaeee758e39fe");
+}
+
 #[test]
 fn test_shuffle_0() {
     let mut scratchpad : Box<[u64x2; MEM_SIZE]> = box [u64x2(0,0); MEM_SIZE];
@@ -175,3 +203,69 @@ fn test_ebyte_mul() {
 fn test_xoru64() {
     assert_eq!(0x7cdcb5631830db27 as u64, 0x995fb21afb79db83 as u64 ^ 0xe5830779e34900a4 as u64);
 }
+
+#[test]
+fn test_try_alloc_scratchpad_is_zeroed() {
+    let scratchpad = hash::try_alloc_scratchpad().expect("scratchpad");
+    assert!(scratchpad.iter().all(|v| v.0 == 0 && v.1 == 0));
+    let heavy = hash::try_alloc_heavy_scratchpad().expect("heavy scratchpad");
+    assert_eq!(heavy.len(), hash::HEAVY_MEM_SIZE);
+    assert!(heavy.iter().all(|v| v.0 == 0 && v.1 == 0));
+}
+
+#[test]
+fn test_heavy_params() {
+    assert_eq!(hash::HEAVY_MEM_SIZE, 2 * MEM_SIZE);
+    assert_eq!(hash::HEAVY_PARAMS.iterations * 2, hash::PARAMS.iterations);
+    assert_eq!(hash::HEAVY_PARAMS.addr_mask, (hash::HEAVY_MEM_SIZE as u64 * 16 - 1) & !0xF);
+    assert_eq!(hash::PARAMS.addr_mask, (MEM_SIZE as u64 * 16 - 1) & !0xF);
+    assert!(hash::HEAVY_PARAMS.heavy && !hash::PARAMS.heavy);
+}
+
+#[test]
+fn test_explode_scratchpad_v8_is_init_scratchpad() {
+    let input = byte_string::string_to_u8_array("0505a9e6c9cc0529b1608dbf9840e20164ee24efd67979e6a937ce174f9aff423a96a7cc5bdcd504008000ca5d84112bf941d3df2c44132b2df08fb766ebf0cc0ad4ccc4012b0929e4edeb04");
+    let aes = aes::new(AESSupport::SW);
+    let mut state = keccak::keccak(&input);
+    let mut scratchpad : Box<[u64x2; MEM_SIZE]> = box [u64x2(0,0); MEM_SIZE];
+    let mut exploded = vec![u64x2(0,0); MEM_SIZE];
+
+    hash::init_scratchpad(&mut scratchpad, &mut state, &aes);
+    hash::explode_scratchpad(&mut exploded, &state, &aes, &hash::PARAMS);
+    assert!(scratchpad[..] == exploded[..]);
+    let imploded = hash::implode_scratchpad(&exploded, &state, &aes, &hash::PARAMS);
+    assert_eq!(hash::finalise_scratchpad(&mut scratchpad, &mut state, &aes), imploded);
+}
+
+#[test]
+fn test_hash_heavy() {
+    let sw_aes = aes::new(AESSupport::SW);
+    let hw_aes = aes::new(AESSupport::HW);
+    let mut heavy_scratchpad = hash::try_alloc_heavy_scratchpad().expect("heavy scratchpad");
+    let mut scratchpad : Box<[u64x2; MEM_SIZE]> = box [u64x2(0,0); MEM_SIZE];
+
+    let input = byte_string::string_to_u8_array("5468697320697320612074657374205468697320697320612074657374205468697320697320612074657374");
+    let heavy = hash::hash_heavy(&mut heavy_scratchpad, &input, &sw_aes);
+    assert_eq!(heavy.len(), 64);
+    assert_eq!(hash::hash_heavy(&mut heavy_scratchpad, &input, &hw_aes), heavy);
+    assert_eq!(hash::hash_variant(&mut heavy_scratchpad, &input, &sw_aes, &hash::Variant::new(HashVersion::Heavy, 0).unwrap()), heavy);
+    assert_ne!(hash::hash(&mut scratchpad, &input, &sw_aes), heavy);
+}
+
+#[test]
+fn test_hash_heavy_reference() {
+    let aes = aes::new(AESSupport::SW);
+    let mut heavy_scratchpad = hash::try_alloc_heavy_scratchpad().expect("heavy scratchpad");
+
+    //cn-heavy/0 test vector of xmrig
+    let input = byte_string::string_to_u8_array("0305a0dbd6bf05cf16e503f3a66f78007cbf34144332ecbfc22ed95c8700383b309ace1923a0964b00000008ba939a62724c0d7581fce5761e9d8a0e6a1c3f924fdd8493d1115649c05eb601");
+    assert_eq!(hash::hash_heavy(&mut heavy_scratchpad, &input, &aes), "9983f21bdf2010a8d707bb2f14d78664bbe1187f55014b39e5f3d69328e48fc2");
+}
+
+#[test]
+#[should_panic(expected = "cryptonight-heavy scratchpad size")]
+fn test_hash_heavy_scratchpad_size() {
+    let aes = aes::new(AESSupport::SW);
+    let mut scratchpad = vec![u64x2(0,0); MEM_SIZE];
+    hash::hash_heavy(&mut scratchpad, b"input", &aes);
+}
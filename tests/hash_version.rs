@@ -0,0 +1,28 @@
+extern crate mithril;
+
+use mithril::cryptonight::{self, HashVersion};
+
+#[test]
+fn test_from_algo() {
+    assert_eq!(HashVersion::from_algo("cn/2"), Some(HashVersion::V8));
+    assert_eq!(HashVersion::from_algo("cryptonight/2"), Some(HashVersion::V8));
+    assert_eq!(HashVersion::from_algo("cn/r"), Some(HashVersion::VersionR));
+    assert_eq!(HashVersion::from_algo("cryptonight/r"), Some(HashVersion::VersionR));
+    assert_eq!(HashVersion::from_algo("rx/0"), Some(HashVersion::RandomX));
+    assert_eq!(HashVersion::from_algo("randomx"), Some(HashVersion::RandomX));
+    assert_eq!(HashVersion::from_algo("cn-heavy/0"), Some(HashVersion::Heavy));
+    assert_eq!(HashVersion::from_algo("cryptonight-heavy"), Some(HashVersion::Heavy));
+    assert_eq!(HashVersion::from_algo("rx/wow"), None);
+}
+
+#[test]
+fn test_supported_algo_names() {
+    assert_eq!(cryptonight::supported_algo_names(), vec!["rx/0".to_string(), "cn/r".to_string(), "cn/2".to_string(), "cn-heavy/0".to_string()]);
+}
+
+#[test]
+fn test_algorithm_is_exported_at_the_crate_root() {
+    use mithril::prelude::*;
+    assert_eq!(Algorithm::from_algo("rx/0"), Some(HashVersion::RandomX));
+    assert_eq!(mithril::Algorithm::from_algo("cn/r"), Some(HashVersion::VersionR));
+}
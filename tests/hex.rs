@@ -0,0 +1,60 @@
+extern crate mithril;
+extern crate rand;
+
+use mithril::hex;
+use mithril::hex::{HexError};
+use mithril::byte_string;
+use rand::Rng;
+
+#[test]
+fn test_encode() {
+    assert_eq!(hex::encode(&[]), "");
+    assert_eq!(hex::encode(&[0x00, 0x0f, 0xa5, 0xff]), "000fa5ff");
+}
+
+#[test]
+fn test_decode() {
+    assert_eq!(hex::decode(""), Ok(vec![]));
+    assert_eq!(hex::decode("000fa5ff"), Ok(vec![0x00, 0x0f, 0xa5, 0xff]));
+    assert_eq!(hex::decode("000FA5FF"), Ok(vec![0x00, 0x0f, 0xa5, 0xff]));
+}
+
+#[test]
+fn test_decode_errors() {
+    assert_eq!(hex::decode("abc"), Err(HexError::OddLength));
+    assert_eq!(hex::decode("0g"), Err(HexError::InvalidChar{index: 1}));
+    assert_eq!(hex::decode("00 0"), Err(HexError::InvalidChar{index: 2}));
+    assert_eq!(hex::decode("ä"), Err(HexError::InvalidChar{index: 0}));
+    assert_eq!(hex::decode("0ä1"), Err(HexError::InvalidChar{index: 1}));
+    assert_eq!(hex::decode_bytes(b"0\xc3"), Err(HexError::InvalidChar{index: 1}));
+}
+
+#[test]
+fn test_decode_into_wrong_length() {
+    let mut out = [0u8; 3];
+    assert_eq!(hex::decode_into("0011", &mut out), Err(HexError::OddLength));
+}
+
+#[test]
+fn test_round_trip_random() {
+    let mut rng = rand::thread_rng();
+    for _ in 0..1000 {
+        let len = rng.gen_range(0, 200);
+        let bytes : Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+        let encoded = hex::encode(&bytes);
+        assert_eq!(encoded, encoded.to_lowercase());
+        assert_eq!(hex::decode(&encoded), Ok(bytes));
+    }
+}
+
+#[test]
+fn test_decode_random_garbage_does_not_panic() {
+    let mut rng = rand::thread_rng();
+    for _ in 0..1000 {
+        let len = rng.gen_range(0, 64);
+        //any utf-8, multi byte chars end at odd byte indices too
+        let garbage : String = (0..len).map(|_| if rng.gen() { rng.gen::<char>() } else { rng.gen_range(0x20u8, 0x7f) as char }).collect();
+        let _ = hex::decode(&garbage);
+        let _ = byte_string::string_to_u8_array(&garbage);
+    }
+}
@@ -0,0 +1,49 @@
+#![cfg(feature = "miner-bin")]
+
+extern crate mithril;
+
+use mithril::hugepages;
+use mithril::hugepages::{HugePageStatus};
+
+const MEMINFO : &str = "MemTotal:       16314664 kB
+AnonHugePages:         0 kB
+HugePages_Total:      10
+HugePages_Free:        6
+HugePages_Rsvd:        0
+HugePages_Surp:        0
+Hugepagesize:       2048 kB
+Hugetlb:           20480 kB
+";
+
+#[test]
+fn test_required_pages_one_page_per_thread() {
+    assert_eq!(hugepages::required_pages(0), 0);
+    assert_eq!(hugepages::required_pages(1), 1);
+    assert_eq!(hugepages::required_pages(8), 8);
+}
+
+#[test]
+fn test_parse_meminfo() {
+    assert_eq!(hugepages::parse_meminfo(MEMINFO), Some(HugePageStatus{total: 10, free: 6, page_size_kb: 2048}));
+    assert_eq!(hugepages::parse_meminfo("MemTotal:       16314664 kB\n"), None);
+}
+
+#[test]
+fn test_target_pages_keeps_pages_in_use_and_larger_reservation() {
+    let status = HugePageStatus{total: 10, free: 6, page_size_kb: 2048};
+    assert_eq!(hugepages::target_pages(8, &status), 12);
+    assert_eq!(hugepages::target_pages(2, &status), 10);
+}
+
+#[test]
+fn test_verify() {
+    assert!(hugepages::verify(6, &HugePageStatus{total: 10, free: 6, page_size_kb: 2048}).is_empty());
+    assert_eq!(hugepages::verify(8, &HugePageStatus{total: 10, free: 6, page_size_kb: 2048}).len(), 1);
+    assert_eq!(hugepages::verify(1, &HugePageStatus{total: 10, free: 6, page_size_kb: 1_048_576}).len(), 1);
+}
+
+#[test]
+fn test_commands() {
+    assert_eq!(hugepages::commands(12), vec!["sudo sysctl -w vm.nr_hugepages=12".to_string(),
+        format!("echo 'vm.nr_hugepages=12' | sudo tee {}", hugepages::SYSCTL_CONF_PATH)]);
+}
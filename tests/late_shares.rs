@@ -0,0 +1,55 @@
+#![cfg(feature = "stratum")]
+
+extern crate mithril;
+
+use mithril::stratum::late_shares::LateShares;
+
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn test_no_job_received_nothing_is_late() {
+    let mut late = LateShares::new(Some(Duration::from_secs(0)));
+    assert!(!late.is_late("job"));
+    assert!(late.allow("job"));
+}
+
+#[test]
+fn test_without_grace_window_all_shares_are_allowed() {
+    let mut late = LateShares::new(None);
+    late.job_received("job1");
+    late.job_received("job2");
+    late.job_received("job3");
+    assert!(late.is_late("job1"));
+    assert!(late.allow("job1"));
+    assert!(late.allow("job2"));
+    assert_eq!(late.dropped(), 0);
+}
+
+#[test]
+fn test_previous_job_allowed_within_grace_window() {
+    let mut late = LateShares::new(Some(Duration::from_millis(50)));
+    late.job_received("job1");
+    late.job_received("job2");
+    assert!(late.allow("job1"));
+    assert!(late.allow("job2"));
+    thread::sleep(Duration::from_millis(100));
+    assert!(!late.allow("job1"));
+    assert!(late.allow("job2"));
+    assert_eq!(late.dropped(), 1);
+}
+
+#[test]
+fn test_older_jobs_and_retargets() {
+    let mut late = LateShares::new(Some(Duration::from_secs(60)));
+    late.job_received("job1");
+    late.job_received("job2");
+    late.job_received("job2"); //retarget, job1 stays the previous job
+    assert!(late.allow("job1"));
+    late.job_received("job3");
+    assert!(!late.allow("job1"));
+    assert!(late.allow("job2"));
+    late.record_submitted();
+    assert_eq!(late.submitted(), 1);
+    assert_eq!(late.dropped(), 1);
+}
@@ -0,0 +1,96 @@
+#![cfg(feature = "stratum")]
+
+extern crate mithril;
+
+use std::time::{Duration, Instant};
+
+use mithril::stratum::latency::{LatencyWindow, LATENCY_WINDOW, ShareTimes, StageWindows, StageLatency};
+use mithril::stratum::pending::{PendingRequests, Request};
+
+fn millis(ms: u64) -> Duration {
+    Duration::from_millis(ms)
+}
+
+#[test]
+fn test_latency_window_stats() {
+    let mut window = LatencyWindow::new();
+    assert_eq!(window.stats(), None);
+
+    for ms in &[40, 10, 30, 20, 100] {
+        window.record(millis(*ms));
+    }
+    let stats = window.stats().unwrap();
+    assert_eq!(stats.samples, 5);
+    assert_eq!(stats.min, millis(10));
+    assert_eq!(stats.max, millis(100));
+    assert_eq!(stats.mean, millis(40));
+    assert_eq!(stats.median, millis(30));
+}
+
+#[test]
+fn test_latency_window_keeps_recent_samples() {
+    let mut window = LatencyWindow::new();
+    window.record(millis(1000));
+    for _ in 0..LATENCY_WINDOW {
+        window.record(millis(5));
+    }
+    let stats = window.stats().unwrap();
+    assert_eq!(stats.samples, LATENCY_WINDOW);
+    assert_eq!(stats.max, millis(5));
+}
+
+#[test]
+fn test_pending_requests_measure_submit_and_keepalive() {
+    let mut pending = PendingRequests::new();
+    let login = pending.register(Request::Login);
+    let submit = pending.register(Request::Submit{job_id: "job".to_string()});
+    let keepalive = pending.register(Request::KeepAlive);
+
+    pending.take(login);
+    assert_eq!(pending.latency(), None);
+
+    pending.take(submit);
+    pending.take(keepalive);
+    assert_eq!(pending.latency().unwrap().samples, 2);
+
+    //unknown ids are not measured
+    pending.take(42);
+    assert_eq!(pending.latency().unwrap().samples, 2);
+}
+
+#[test]
+fn test_stage_windows_measure_every_stage() {
+    let start = Instant::now();
+    let times = ShareTimes{job_received: start, dispatched: start + millis(1), found: start + millis(501)};
+    let mut stages = StageWindows::new();
+    stages.record(&times, start + millis(503), start + millis(603));
+
+    let latency = stages.stats();
+    assert_eq!(latency.dispatch.unwrap().median, millis(1));
+    assert_eq!(latency.hashing.unwrap().median, millis(500));
+    assert_eq!(latency.submit.unwrap().median, millis(2));
+    assert_eq!(latency.response.unwrap().median, millis(100));
+    assert!(latency.render().unwrap().contains("pool response 100ms"));
+
+    //a job handed over before it was received (clock order of threads) counts as 0
+    let times = ShareTimes{job_received: start + millis(5), dispatched: start, found: start};
+    stages.record(&times, start, start);
+    assert_eq!(stages.stats().dispatch.unwrap().min, millis(0));
+}
+
+#[test]
+fn test_pending_requests_record_stages_of_answered_shares() {
+    let mut pending = PendingRequests::new();
+    assert_eq!(pending.stage_latency(), StageLatency::default());
+    assert_eq!(StageLatency::default().render(), None);
+
+    let now = Instant::now();
+    let times = ShareTimes{job_received: now, dispatched: now, found: now};
+    let with_times = pending.register_share("job".to_string(), Some(times));
+    let without_times = pending.register_share("job".to_string(), None);
+    assert_eq!(pending.take(with_times), Some(Request::Submit{job_id: "job".to_string()}));
+    pending.take(without_times);
+
+    assert_eq!(pending.stage_latency().response.unwrap().samples, 1);
+    assert_eq!(pending.latency().unwrap().samples, 2);
+}
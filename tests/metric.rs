@@ -0,0 +1,99 @@
+#![cfg(feature = "stratum")]
+
+extern crate mithril;
+
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use mithril::metric;
+use mithril::metric::counters::{HashCounters, Sampler, Delta};
+use mithril::metric::coverage::{NonceCoverage};
+
+#[test]
+fn test_sample_line_without_labels() {
+    assert_eq!(metric::sample_line(1546300800000, 4200, &[]), "1546300800000;4200");
+}
+
+#[test]
+fn test_sample_line_with_labels() {
+    let labels = vec![("location".to_string(), "basement".to_string()), ("rig".to_string(), "rig01".to_string())];
+    assert_eq!(metric::sample_line(1546300800000, 4200, &labels), "1546300800000;4200;location=basement;rig=rig01");
+}
+
+#[test]
+fn test_hash_counters_total_and_delta() {
+    let counters = HashCounters::new(3);
+    let before = counters.snapshot();
+    counters.add(0, 100);
+    counters.add(2, 50);
+    counters.add(0, 1);
+    assert_eq!(counters.total(), 151);
+
+    let after = counters.snapshot();
+    assert_eq!(after.per_thread, vec![101, 0, 50]);
+    let delta = after.delta_since(&before);
+    assert_eq!(delta.per_thread, vec![101, 0, 50]);
+    assert_eq!(delta.total(), 151);
+}
+
+#[test]
+fn test_sampler_returns_hashes_since_last_sample() {
+    let counters = HashCounters::new(2);
+    counters.add(1, 10);
+    let mut sampler = Sampler::new(&counters);
+    counters.add(0, 5);
+    assert_eq!(sampler.sample(&counters).total(), 5);
+    assert_eq!(sampler.sample(&counters).total(), 0);
+    counters.add(1, 7);
+    assert_eq!(sampler.sample(&counters).per_thread, vec![0, 7]);
+}
+
+#[test]
+fn test_hash_counters_concurrent_add() {
+    let counters = Arc::new(HashCounters::new(4));
+    let handles : Vec<_> = (0..4).map(|ix| {
+        let counters = counters.clone();
+        thread::spawn(move || {
+            for _ in 0..1000 {
+                counters.add(ix, 1);
+            }
+        })
+    }).collect();
+    for hnd in handles {
+        hnd.join().unwrap();
+    }
+    assert_eq!(counters.total(), 4000);
+    assert_eq!(counters.snapshot().per_thread, vec![1000; 4]);
+}
+
+#[test]
+fn test_delta_hash_rate() {
+    let delta = Delta{per_thread: vec![100, 50], elapsed: Duration::from_secs(10)};
+    assert_eq!(delta.hash_rate(), 15.0);
+    let empty = Delta{per_thread: vec![100], elapsed: Duration::from_secs(0)};
+    assert_eq!(empty.hash_rate(), 0.0);
+}
+
+#[test]
+fn test_nonce_coverage_average() {
+    let coverage = NonceCoverage::new();
+    assert_eq!(coverage.snapshot().average(), None);
+    coverage.record(250, 1000);
+    coverage.record(750, 1000);
+    assert_eq!(coverage.snapshot().samples, 2);
+    assert_eq!(coverage.snapshot().average(), Some(0.5));
+}
+
+#[test]
+fn test_nonce_coverage_delta_and_bounds() {
+    let coverage = NonceCoverage::new();
+    coverage.record(1, 1 << 30);
+    let before = coverage.snapshot();
+    coverage.record(2000, 1000); //exhausted partition counts as fully searched
+    coverage.record(10, 0); //ignored
+    let delta = coverage.snapshot().delta_since(&before);
+    assert_eq!(delta.samples, 1);
+    assert_eq!(delta.average(), Some(1.0));
+    assert!(before.average().unwrap() > 0.0);
+}
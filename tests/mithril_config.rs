@@ -1,8 +1,21 @@
+#![cfg(feature = "miner-bin")]
+
 extern crate mithril;
 
 use mithril::mithril_config;
 use mithril::cryptonight::aes::AESSupport;
+use mithril::worker::nonce::NonceStrategyKind;
+use mithril::worker::throttle::BackgroundLevel;
+use mithril::stratum::resolve::AddressPreference;
+use mithril::stratum::share_queue::QueuePolicy;
+use mithril::stratum::multi_pool::ArbitrationPolicy;
+use mithril::stratum::stratum_data::SubmitOptions;
+use mithril::stratum::flood_guard::FloodGuardConfig;
+use mithril::cryptonight::HashVersion;
+use mithril::worker::worker_pool::AlgorithmProfile;
 
+use std::env;
+use std::fs;
 use std::time::{Duration, Instant};
 use std::path::Path;
 
@@ -14,20 +27,82 @@ fn test_read_default_config() {
     assert_eq!(config.pool_conf.pool_address, "xmrpool.eu:3333");
     assert_eq!(config.pool_conf.wallet_address, "");
     assert_eq!(config.pool_conf.pool_password, "");
+    assert_eq!(config.pool_conf.max_shares_per_job, None);
+    assert_eq!(config.pool_conf.rig_id, None);
+    assert_eq!(config.pool_conf.agent, Some(format!("mithril/{}", env!("CARGO_PKG_VERSION"))));
+    assert_eq!(config.pool_conf.keepalive_method, "keepalived");
+    assert_eq!(config.pool_conf.keepalive_interval_secs, Some(60));
+    assert_eq!(config.pool_conf.keepalive_jitter_seconds, 10);
+    assert_eq!(config.pool_conf.connect_timeout_secs, 30);
+    assert_eq!(config.pool_conf.address_preference, AddressPreference::System);
+    assert_eq!(config.pool_conf.login_retries, 3);
+    assert_eq!(config.pool_conf.trace_file, None);
+    assert_eq!(config.pool_conf.job_timeout_secs, None);
+    assert_eq!(config.pool_conf.dry_run, false);
+    assert_eq!(config.pool_conf.clamp_easy_targets, false);
+    assert_eq!(config.pool_conf.tls, false);
+    assert!(config.pool_conf.tls_fingerprints.is_empty());
+    assert!(config.pool_conf.allowed_ips.is_empty());
+    assert_eq!(config.pool_conf.solo_daemon_address, None);
+    assert_eq!(config.pool_conf.self_select_daemon_address, None);
+    assert!(config.pool_conf.failover_pool_addresses.is_empty());
+    assert_eq!(config.pool_conf.failover_max_failures, 3);
+    assert_eq!(config.pool_conf.failover_probe_minutes, 30);
+    assert_eq!(config.pool_conf.secondary_pool_address, None);
+    assert_eq!(config.pool_conf.pool_arbitration, ArbitrationPolicy::WarmStandby);
+    assert_eq!(config.pool_conf.share_queue_capacity, 64);
+    assert_eq!(config.pool_conf.share_queue_policy, QueuePolicy::Error);
+    assert_eq!(config.pool_conf.late_share_grace_secs, None);
+    assert_eq!(config.pool_conf.stats_interval_secs, Some(60));
+    assert_eq!(config.pool_conf.min_pool_score, None);
+    assert_eq!(config.pool_conf.offline_share_capacity, 16);
+    assert_eq!(config.pool_conf.offline_share_max_age_secs, 60);
+    assert_eq!(config.pool_conf.submit_options, SubmitOptions::default());
+    assert_eq!(config.pool_conf.flood_guard, FloodGuardConfig::default());
 
     assert_eq!(config.worker_conf.num_threads, 8);
     assert_eq!(config.worker_conf.auto_tune, true);
     assert_eq!(config.worker_conf.auto_tune_interval_minutes , 15);
     assert_eq!(config.worker_conf.auto_tune_log, "./bandit.log");
+    assert_eq!(config.worker_conf.max_job_age_seconds, None);
+    assert_eq!(config.worker_conf.warmup_seconds, None);
+    assert_eq!(config.worker_conf.nonce_strategy, NonceStrategyKind::Sequential);
+    assert_eq!(config.worker_conf.deterministic_seed, None);
+    assert_eq!(config.worker_conf.background_level, BackgroundLevel::Off);
+    assert_eq!(config.worker_conf.randomx_full_dataset, false);
+    assert!(config.worker_conf.algorithm_profiles.is_empty());
 
     assert_eq!(config.metric_conf.enabled, false);
     assert_eq!(config.metric_conf.resolution, std::u32::MAX as u64);
     assert_eq!(config.metric_conf.sample_interval_seconds, std::u32::MAX as u64);
     assert_eq!(config.metric_conf.report_file, "/dev/null");
+    assert!(config.metric_conf.labels.is_empty());
 
     assert_eq!(config.hw_conf.aes_support, AESSupport::HW);
+    assert_eq!(config.hw_conf.thread_affinity, false);
+    assert_eq!(config.hw_conf.skip_efficiency_cores, false);
+
+    assert_eq!(config.supervisor_conf.enabled, false);
+    assert_eq!(config.supervisor_conf.max_restarts, 5);
+    assert_eq!(config.supervisor_conf.window_minutes, 30);
+    assert_eq!(config.supervisor_conf.initial_backoff_secs, 5);
+    assert_eq!(config.supervisor_conf.max_backoff_secs, 300);
+    assert_eq!(config.supervisor_conf.alert_command, None);
 
     assert_eq!(config.donation_conf.percentage, 2.5);
+    assert_eq!(config.donation_conf.pool_address, mithril_config::DEFAULT_DONATION_POOL_ADDRESS);
+    assert_eq!(config.donation_conf.wallet_address, mithril_config::DEFAULT_DONATION_WALLET_ADDRESS);
+
+    assert_eq!(config.update_conf.check, false);
+    assert_eq!(config.update_conf.download, false);
+
+    assert!(config.schedule_conf.is_empty());
+
+    assert_eq!(config.cpu_share_conf.enabled, false);
+    assert_eq!(config.cpu_share_conf.socket_path, None);
+
+    assert_eq!(config.accounting_conf.share_log, None);
+    assert!(config.worker_groups.is_empty());
 }
 
 #[test] //Bugfix test, there should be some "room" so that this value can be added to a time instant
@@ -39,8 +114,33 @@ fn test_disabled_metric_value_should_be_addable_to_now() {
     //Ok if it doesn't panic
 }
 
+#[test]
+fn test_read_algorithm_profiles() {
+    let profiles = "\n[worker.algo.\"rx/0\"]\nnum_threads = 4\naffinity = [0, 2, 4, 6]\n\n[worker.algo.\"cn/r\"]\nnum_threads = 8\n";
+    let config = read_config_with("algo", profiles).unwrap();
+    let mut read = config.worker_conf.algorithm_profiles.clone();
+    read.sort_by_key(|p| p.num_threads);
+    assert_eq!(read, vec![
+        AlgorithmProfile{hash_version: HashVersion::RandomX, num_threads: Some(4), affinity: vec![0, 2, 4, 6]},
+        AlgorithmProfile{hash_version: HashVersion::VersionR, num_threads: Some(8), affinity: Vec::new()}
+    ]);
+
+    assert!(read_config_with("algo_unknown", "\n[worker.algo.\"rx/wow\"]\nnum_threads = 4\n").is_err());
+    assert!(read_config_with("algo_zero", "\n[worker.algo.\"rx/0\"]\nnum_threads = 0\n").is_err());
+    assert!(read_config_with("algo_setting", "\n[worker.algo.\"rx/0\"]\nthreads = 4\n").is_err());
+}
+
 //helper
 
+/// The default config with `appended` at the end
+fn read_config_with(name: &str, appended: &str) -> Result<mithril_config::MithrilConfig, mithril::MithrilError> {
+    let path = env::temp_dir().join(format!("mithril-config-{}-{}.toml", name, std::process::id()));
+    fs::write(&path, format!("{}{}", fs::read_to_string("default_config.toml").unwrap(), appended)).unwrap();
+    let config = mithril_config::read_config(&path, path.to_str().unwrap());
+    let _ = fs::remove_file(&path);
+    config
+}
+
 fn read_default_config() -> mithril_config::MithrilConfig {
     let path = &format!("{}{}", "./", "default_config.toml");
     return mithril_config::read_config(Path::new(path), "default_config.toml").unwrap();
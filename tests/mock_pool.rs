@@ -0,0 +1,191 @@
+#![cfg(feature = "stratum")]
+
+extern crate mithril;
+#[macro_use]
+extern crate serde_json;
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use mithril::stratum;
+use mithril::stratum::stratum_data;
+use mithril::stratum::client_state::{ConnectionState};
+
+fn default_pool_conf() -> stratum_data::PoolConfig {
+    stratum_data::PoolConfig::new("pool.example.com:3333", "wallet", "x")
+}
+
+/// Pool side of a connection, reads the requests of the client line by line
+struct MockPool {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl MockPool {
+    fn accept(listener: &TcpListener) -> MockPool {
+        let (stream, _) = listener.accept().unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+        MockPool{reader: BufReader::new(stream.try_clone().unwrap()), writer: stream}
+    }
+
+    /// Asserts that the next request equals `expected`, the order of fields does not matter
+    fn expect_request(&mut self, expected: serde_json::Value) {
+        let mut line = String::new();
+        self.reader.read_line(&mut line).unwrap();
+        let request : serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(request, expected, "unexpected request: {}", line);
+    }
+
+    fn respond(&mut self, response: serde_json::Value) {
+        writeln!(self.writer, "{}", response).unwrap();
+    }
+}
+
+fn start_client(listener: &TcpListener) -> (stratum::StratumClient, MockPool, Receiver<stratum::StratumEvent>) {
+    let mut pool_conf = default_pool_conf();
+    pool_conf.pool_address = listener.local_addr().unwrap().to_string();
+    pool_conf.rig_id = Some("rig1".to_string());
+    pool_conf.keepalive_interval_secs = None;
+
+    let (err_tx, _err_rx) = channel();
+    let (action_tx, action_rx) = channel();
+    let client = stratum::StratumClient::login(pool_conf, err_tx, action_tx).unwrap();
+    let pool = MockPool::accept(listener);
+    (client, pool, action_rx)
+}
+
+fn login_and_receive_job(pool: &mut MockPool, action_rx: &Receiver<stratum::StratumEvent>) {
+    let conf = default_pool_conf();
+    pool.expect_request(json!({
+        "id": 1,
+        "method": "login",
+        "params": {
+            "login": conf.wallet_address,
+            "pass": "x",
+            "algo": ["rx/0", "cn/r", "cn/2", "cn-heavy/0"],
+            "rig_id": "rig1",
+            "agent": stratum_data::default_agent()
+        }
+    }));
+    pool.respond(json!({
+        "id": 1,
+        "jsonrpc": "2.0",
+        "error": null,
+        "result": {
+            "id": "miner1",
+            "job": {"blob": "00", "job_id": "job1", "target": "169f0200"},
+            "status": "OK"
+        }
+    }));
+
+    match action_rx.recv_timeout(Duration::from_secs(10)).unwrap().action {
+        stratum::StratumAction::Job{miner_id, job_id, ..} => {
+            assert_eq!(miner_id, "miner1");
+            assert_eq!(job_id, "job1");
+        },
+        other => assert!(false, "Wrong action received: {:?}", other)
+    }
+}
+
+#[test]
+fn test_mock_pool_login_request() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let (client, mut pool, action_rx) = start_client(&listener);
+
+    login_and_receive_job(&mut pool, &action_rx);
+
+    client.stop();
+}
+
+#[test]
+fn test_mock_pool_submit_request() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let (client, mut pool, action_rx) = start_client(&listener);
+    login_and_receive_job(&mut pool, &action_rx);
+
+    stratum::submit_share(&client.new_cmd_channel(), stratum_data::Share{
+        miner_id: "miner1".to_string(),
+        job_id: "job1".to_string(),
+        nonce: "00000001".to_string(),
+        hash: "hash".to_string(),
+        times: None
+    }).unwrap();
+
+    pool.expect_request(json!({
+        "id": 2,
+        "method": "submit",
+        "params": {"id": "miner1", "job_id": "job1", "nonce": "00000001", "result": "hash"}
+    }));
+    pool.respond(json!({"id": 2, "jsonrpc": "2.0", "error": null, "result": {"status": "OK"}}));
+
+    match action_rx.recv_timeout(Duration::from_secs(10)).unwrap().action {
+        stratum::StratumAction::ShareAccepted{job_id} => assert_eq!(job_id, "job1"),
+        other => assert!(false, "Wrong action received: {:?}", other)
+    }
+
+    client.stop();
+}
+
+#[test]
+fn test_mock_pool_keepalive_request() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let (client, mut pool, action_rx) = start_client(&listener);
+    login_and_receive_job(&mut pool, &action_rx);
+
+    client.new_cmd_channel().send(stratum::StratumCmd::KeepAlive{miner_id: "miner1".to_string()}).unwrap();
+
+    pool.expect_request(json!({
+        "id": 2,
+        "method": "keepalived",
+        "params": {"id": "miner1"}
+    }));
+    pool.respond(json!({"id": 2, "jsonrpc": "2.0", "error": null, "result": {"status": "KEEPALIVED"}}));
+
+    assert_eq!(action_rx.recv_timeout(Duration::from_secs(10)).unwrap().action, stratum::StratumAction::KeepAliveOk);
+
+    client.stop();
+}
+
+#[test]
+fn test_mock_pool_subscriber_after_login() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let (client, mut pool, action_rx) = start_client(&listener);
+    let subscription = client.subscribe();
+
+    login_and_receive_job(&mut pool, &action_rx);
+
+    match subscription.recv_timeout(Duration::from_secs(10)).unwrap() {
+        stratum::StratumAction::Job{job_id, ..} => assert_eq!(job_id, "job1"),
+        other => assert!(false, "Wrong action received: {:?}", other)
+    }
+
+    client.stop();
+}
+
+#[test]
+fn test_mock_pool_current_job_and_state() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let (client, mut pool, action_rx) = start_client(&listener);
+    assert_eq!(client.state(), ConnectionState::Connecting);
+    assert_eq!(client.current_job(), None);
+
+    login_and_receive_job(&mut pool, &action_rx);
+
+    assert_eq!(client.state(), ConnectionState::LoggedIn);
+    match client.current_job() {
+        Some(stratum::StratumAction::Job{job_id, ..}) => assert_eq!(job_id, "job1"),
+        other => assert!(false, "Wrong current job: {:?}", other)
+    }
+
+    drop(pool);
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while client.state() != ConnectionState::Disconnected && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(client.state(), ConnectionState::Disconnected);
+
+    client.stop();
+}
@@ -0,0 +1,198 @@
+#![cfg(feature = "stratum")]
+
+extern crate mithril;
+
+use std::sync::mpsc::{channel, Receiver};
+use std::time::{Duration};
+
+use mithril::stratum::{StratumAction, StratumEvent};
+use mithril::stratum::stratum_data;
+use mithril::stratum::multi_pool::{Arbiter, ArbitrationPolicy, MultiPoolClient, PRIMARY, SECONDARY};
+use mithril::stratum::testserver::{TestServer, TestJob};
+
+fn job(miner_id: &str, job_id: &str) -> StratumAction {
+    StratumAction::Job{miner_id: miner_id.to_string(), blob: "blob".to_string(), job_id: job_id.to_string(),
+        target: "169f0200".to_string(), difficulty: 25000, algo: None, height: None, seed_hash: None, next_seed_hash: None}
+}
+
+fn share(miner_id: &str, job_id: &str) -> stratum_data::Share {
+    stratum_data::Share{miner_id: miner_id.to_string(), job_id: job_id.to_string(), nonce: "00000001".to_string(), hash: "hash".to_string(), times: None}
+}
+
+/// The next action that is not a share stats broadcast
+fn next_action(action_rx: &Receiver<StratumEvent>) -> StratumAction {
+    loop {
+        match action_rx.recv_timeout(Duration::from_secs(10)).unwrap().action {
+            StratumAction::Stats{..} => continue,
+            action => return action
+        }
+    }
+}
+
+fn expect_job(action_rx: &Receiver<StratumEvent>, expected_miner_id: &str, expected_job_id: &str) {
+    match next_action(action_rx) {
+        StratumAction::Job{miner_id, job_id, ..} => {
+            assert_eq!(miner_id, expected_miner_id);
+            assert_eq!(job_id, expected_job_id);
+        },
+        other => assert!(false, "Wrong action received: {:?}", other)
+    }
+}
+
+#[test]
+fn test_warm_standby_hashes_primary_and_takes_over_on_disconnect() {
+    let mut arbiter = Arbiter::new(ArbitrationPolicy::WarmStandby);
+
+    assert_eq!(arbiter.record_action(SECONDARY, job("miner2", "b1")), vec![job("miner2", "b1")]);
+    assert_eq!(arbiter.active(), Some(SECONDARY));
+    assert_eq!(arbiter.record_action(PRIMARY, job("miner1", "a1")), vec![job("miner1", "a1")]);
+    assert_eq!(arbiter.active(), Some(PRIMARY));
+
+    //jobs of the standby pool are only remembered
+    assert!(arbiter.record_action(SECONDARY, job("miner2", "b2")).is_empty());
+
+    assert_eq!(arbiter.record_disconnect(PRIMARY), vec![job("miner2", "b2")]);
+    assert_eq!(arbiter.active(), Some(SECONDARY));
+    assert_eq!(arbiter.record_disconnect(SECONDARY), vec![StratumAction::JobCancelled{job_id: None}]);
+    assert_eq!(arbiter.active(), None);
+}
+
+#[test]
+fn test_split_alternates_slices_by_percentage() {
+    let mut arbiter = Arbiter::new(ArbitrationPolicy::Split{primary_percent: 75});
+    arbiter.record_action(PRIMARY, job("miner1", "a1"));
+    arbiter.record_action(SECONDARY, job("miner2", "b1"));
+
+    let mut slices = [0, 0];
+    for _ in 0..100 {
+        arbiter.next_slice();
+        slices[arbiter.active().unwrap()] += 1;
+    }
+    assert_eq!(slices, [75, 25]);
+}
+
+#[test]
+fn test_split_uses_other_pool_without_job() {
+    let mut arbiter = Arbiter::new(ArbitrationPolicy::Split{primary_percent: 0});
+    arbiter.record_action(PRIMARY, job("miner1", "a1"));
+
+    assert!(arbiter.next_slice().is_empty());
+    assert_eq!(arbiter.active(), Some(PRIMARY));
+
+    arbiter.record_action(SECONDARY, job("miner2", "b1"));
+    assert_eq!(arbiter.next_slice(), vec![job("miner2", "b1")]);
+    assert_eq!(arbiter.active(), Some(SECONDARY));
+}
+
+#[test]
+fn test_routed_hands_out_the_jobs_of_both_pools() {
+    let mut arbiter = Arbiter::new(ArbitrationPolicy::Routed);
+    assert_eq!(ArbitrationPolicy::from_name("routed", 50), Some(ArbitrationPolicy::Routed));
+
+    assert_eq!(arbiter.record_action(PRIMARY, job("miner1", "a1")), vec![job("miner1", "a1")]);
+    assert_eq!(arbiter.record_action(SECONDARY, job("miner2", "b1")), vec![job("miner2", "b1")]);
+    let prefix = StratumAction::ExtranonceSet{nonce_prefix: vec![7]};
+    assert_eq!(arbiter.record_action(SECONDARY, prefix.clone()), vec![prefix]);
+    assert_eq!(arbiter.source(PRIMARY), Some(PRIMARY));
+    assert_eq!(arbiter.source(SECONDARY), Some(SECONDARY));
+    assert_eq!(arbiter.active(), None);
+
+    //only the workers of the disconnected pool stop
+    assert_eq!(arbiter.record_disconnect(SECONDARY), vec![StratumAction::JobCancelled{job_id: None}]);
+    assert!(arbiter.next_slice().is_empty());
+    assert_eq!(arbiter.route(Some("miner1"), "a1"), Some(PRIMARY));
+    assert_eq!(arbiter.route(Some("miner2"), "b1"), Some(SECONDARY));
+}
+
+#[test]
+fn test_switch_hands_over_nonce_prefix() {
+    let mut arbiter = Arbiter::new(ArbitrationPolicy::WarmStandby);
+    arbiter.record_action(PRIMARY, job("miner1", "a1"));
+    assert!(arbiter.record_action(SECONDARY, StratumAction::ExtranonceSet{nonce_prefix: vec![7]}).is_empty());
+    arbiter.record_action(SECONDARY, job("miner2", "b1"));
+
+    assert_eq!(arbiter.record_disconnect(PRIMARY), vec![StratumAction::ExtranonceSet{nonce_prefix: vec![7]}, job("miner2", "b1")]);
+}
+
+#[test]
+fn test_cancelled_active_job_switches_to_other_pool() {
+    let mut arbiter = Arbiter::new(ArbitrationPolicy::WarmStandby);
+    arbiter.record_action(PRIMARY, job("miner1", "a1"));
+    arbiter.record_action(SECONDARY, job("miner2", "b1"));
+
+    let cancel = StratumAction::JobCancelled{job_id: Some("a1".to_string())};
+    assert_eq!(arbiter.record_action(PRIMARY, cancel.clone()), vec![cancel, job("miner2", "b1")]);
+    assert_eq!(arbiter.active(), Some(SECONDARY));
+}
+
+#[test]
+fn test_route_shares_by_miner_id_for_equal_job_ids() {
+    let mut arbiter = Arbiter::new(ArbitrationPolicy::WarmStandby);
+    arbiter.record_action(PRIMARY, job("miner1", "1"));
+    arbiter.record_action(SECONDARY, job("miner2", "1"));
+    arbiter.record_action(SECONDARY, job("miner2", "2"));
+
+    assert_eq!(arbiter.route(Some("miner1"), "1"), Some(PRIMARY));
+    assert_eq!(arbiter.route(Some("miner2"), "1"), Some(SECONDARY));
+    assert_eq!(arbiter.route(None, "1"), Some(PRIMARY));
+    assert_eq!(arbiter.route(None, "2"), Some(SECONDARY));
+    assert_eq!(arbiter.route(Some("miner1"), "2"), None);
+
+    //shares of a disconnected pool are not sent to the other pool
+    arbiter.record_disconnect(PRIMARY);
+    assert_eq!(arbiter.route(Some("miner1"), "1"), Some(PRIMARY));
+    assert_eq!(arbiter.route_miner_id("miner1"), None);
+    assert_eq!(arbiter.route_miner_id("miner2"), Some(SECONDARY));
+}
+
+#[test]
+fn test_multi_pool_client_routes_shares_and_fails_over() {
+    let primary = TestServer::bind().unwrap();
+    let secondary = TestServer::bind().unwrap();
+    let mut pool_conf = primary.pool_config().unwrap();
+    pool_conf.secondary_pool_address = Some(secondary.address().unwrap());
+
+    let (err_tx, err_rx) = channel();
+    let (action_tx, action_rx) = channel();
+    let client = MultiPoolClient::login(pool_conf, err_tx, action_tx).unwrap();
+    let mut primary_conn = primary.accept().unwrap();
+    let mut secondary_conn = secondary.accept().unwrap();
+
+    //the secondary pool has the only job until the primary pool logged in
+    secondary_conn.login("miner2", &TestJob::new("job1")).unwrap();
+    expect_job(&action_rx, "miner2", "job1");
+    primary_conn.login("miner1", &TestJob::new("job1")).unwrap();
+    expect_job(&action_rx, "miner1", "job1");
+
+    //same job id on both pools, the miner id decides
+    client.share_queue().submit(&client.new_cmd_channel(), share("miner2", "job1")).unwrap();
+    assert_eq!(secondary_conn.accept_share().unwrap().params["id"], "miner2");
+    assert_eq!(next_action(&action_rx), StratumAction::ShareAccepted{job_id: "job1".to_string()});
+    client.share_queue().submit(&client.new_cmd_channel(), share("miner1", "job1")).unwrap();
+    assert_eq!(primary_conn.accept_share().unwrap().params["id"], "miner1");
+    assert_eq!(next_action(&action_rx), StratumAction::ShareAccepted{job_id: "job1".to_string()});
+
+    //the secondary pool takes over without reconnect, no error is reported
+    primary_conn.close().unwrap();
+    expect_job(&action_rx, "miner2", "job1");
+    assert!(err_rx.try_recv().is_err());
+
+    client.stop();
+}
+
+#[test]
+fn test_multi_pool_client_reports_error_if_both_pools_fail() {
+    let primary = TestServer::bind().unwrap();
+    let secondary = TestServer::bind().unwrap();
+    let mut pool_conf = primary.pool_config().unwrap();
+    pool_conf.secondary_pool_address = Some(secondary.address().unwrap());
+
+    let (err_tx, err_rx) = channel();
+    let (action_tx, _action_rx) = channel();
+    let client = MultiPoolClient::login(pool_conf, err_tx, action_tx).unwrap();
+    primary.accept().unwrap().close().unwrap();
+    secondary.accept().unwrap().close().unwrap();
+
+    err_rx.recv_timeout(Duration::from_secs(10)).unwrap();
+    client.stop();
+}
@@ -0,0 +1,69 @@
+#![cfg(feature = "stratum")]
+
+extern crate mithril;
+
+use mithril::worker::nonce;
+use mithril::worker::nonce::{NonceStrategyKind};
+
+const TEST_NONCE_BITS : u8 = 12;
+
+/// Runs all partitions and checks that every nonce is produced exactly once
+fn assert_full_coverage_without_duplicates(kind: NonceStrategyKind, partition_bits: u8) {
+    let mut seen = vec![false; 1 << TEST_NONCE_BITS];
+    for partition in 0..(1u64 << partition_bits) {
+        let mut strategy = nonce::new_strategy(kind, partition, partition_bits, TEST_NONCE_BITS);
+        while let Some(n) = strategy.next_nonce() {
+            assert!(!seen[n as usize], "duplicate nonce {} for {:?}", n, kind);
+            seen[n as usize] = true;
+        }
+    }
+    assert!(seen.iter().all(|s| *s), "nonce space not covered by {:?}", kind);
+}
+
+#[test]
+fn test_sequential_no_duplicates() {
+    assert_full_coverage_without_duplicates(NonceStrategyKind::Sequential, 0);
+    assert_full_coverage_without_duplicates(NonceStrategyKind::Sequential, 3);
+}
+
+#[test]
+fn test_strided_no_duplicates() {
+    assert_full_coverage_without_duplicates(NonceStrategyKind::Strided, 0);
+    assert_full_coverage_without_duplicates(NonceStrategyKind::Strided, 3);
+}
+
+#[test]
+fn test_random_walk_no_duplicates() {
+    assert_full_coverage_without_duplicates(NonceStrategyKind::RandomWalk, 0);
+    assert_full_coverage_without_duplicates(NonceStrategyKind::RandomWalk, 3);
+}
+
+#[test]
+fn test_sequential_order() {
+    let mut strategy = nonce::new_strategy(NonceStrategyKind::Sequential, 1, 1, nonce::NONCE_BITS);
+    assert_eq!(strategy.next_nonce(), Some(0x8000_0000));
+    assert_eq!(strategy.next_nonce(), Some(0x8000_0001));
+}
+
+#[test]
+fn test_strided_order() {
+    let mut strategy = nonce::new_strategy(NonceStrategyKind::Strided, 1, 2, nonce::NONCE_BITS);
+    assert_eq!(strategy.next_nonce(), Some(1));
+    assert_eq!(strategy.next_nonce(), Some(5));
+    assert_eq!(strategy.next_nonce(), Some(9));
+}
+
+#[test]
+fn test_from_name() {
+    assert_eq!(NonceStrategyKind::from_name("sequential"), Some(NonceStrategyKind::Sequential));
+    assert_eq!(NonceStrategyKind::from_name("strided"), Some(NonceStrategyKind::Strided));
+    assert_eq!(NonceStrategyKind::from_name("random"), Some(NonceStrategyKind::RandomWalk));
+    assert_eq!(NonceStrategyKind::from_name("unknown"), None);
+}
+
+#[test]
+fn test_prefix_value() {
+    assert_eq!(nonce::prefix_value(&[]), (0, 32));
+    assert_eq!(nonce::prefix_value(&[0xa1]), (0xa100_0000, 24));
+    assert_eq!(nonce::prefix_value(&[0xa1, 0xb2, 0xc3]), (0xa1b2_c300, 8));
+}
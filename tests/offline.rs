@@ -0,0 +1,71 @@
+#![cfg(feature = "stratum")]
+
+extern crate mithril;
+
+use std::time::{Duration, Instant};
+
+use mithril::stratum::latency::{ShareTimes};
+use mithril::stratum::offline::{OfflineShares};
+use mithril::stratum::stratum_data::{Share};
+
+fn share(job_id: &str, found: Instant) -> Share {
+    Share{
+        miner_id: "miner".to_string(),
+        job_id: job_id.to_string(),
+        nonce: "00000001".to_string(),
+        hash: "hash".to_string(),
+        times: Some(ShareTimes{job_received: found, dispatched: found, found})
+    }
+}
+
+#[test]
+fn test_fresh_shares_of_the_same_pool_are_salvaged() {
+    let offline = OfflineShares::new(8, Duration::from_secs(60));
+    let now = Instant::now();
+    offline.keep(Share{nonce: "00000001".to_string(), ..share("job", now)}, "pool:3333");
+    offline.keep(Share{nonce: "00000002".to_string(), ..share("job", now + Duration::from_secs(50))}, "pool:3333");
+    offline.keep(Share{nonce: "00000003".to_string(), ..share("job", now + Duration::from_secs(50))}, "other:3333");
+    assert_eq!(offline.len(), 3);
+
+    let salvage = offline.take("pool:3333", "miner", "job", now + Duration::from_secs(90));
+    let nonces : Vec<&str> = salvage.shares.iter().map(|s| s.nonce.as_str()).collect();
+    assert_eq!(nonces, vec!["00000002"]);
+    assert_eq!(salvage.discarded, 2);
+    assert!(offline.is_empty());
+}
+
+#[test]
+fn test_full_buffer_drops_the_oldest_share() {
+    let offline = OfflineShares::new(2, Duration::from_secs(60));
+    let now = Instant::now();
+    for nonce in &["00000001", "00000002", "00000003"] {
+        offline.keep(Share{nonce: nonce.to_string(), ..share("job", now)}, "pool:3333");
+    }
+    assert_eq!(offline.len(), 2);
+    assert_eq!(offline.dropped(), 1);
+
+    let salvage = offline.take("pool:3333", "miner", "job", now);
+    let nonces : Vec<&str> = salvage.shares.iter().map(|s| s.nonce.as_str()).collect();
+    assert_eq!(nonces, vec!["00000002", "00000003"]);
+}
+
+#[test]
+fn test_shares_of_another_session_or_job_are_discarded() {
+    let offline = OfflineShares::new(8, Duration::from_secs(60));
+    let now = Instant::now();
+    offline.keep(share("job", now), "pool:3333");
+    offline.keep(Share{miner_id: "old session".to_string(), ..share("job", now)}, "pool:3333");
+    offline.keep(share("replaced job", now), "pool:3333");
+
+    let salvage = offline.take("pool:3333", "miner", "job", now);
+    assert_eq!(salvage.shares.len(), 1);
+    assert_eq!(salvage.shares[0].miner_id, "miner");
+    assert_eq!(salvage.discarded, 2);
+}
+
+#[test]
+fn test_zero_capacity_keeps_nothing() {
+    let offline = OfflineShares::new(0, Duration::from_secs(60));
+    offline.keep(share("job1", Instant::now()), "pool:3333");
+    assert!(offline.is_empty());
+}
@@ -0,0 +1,61 @@
+#![cfg(feature = "stratum")]
+
+extern crate mithril;
+
+use std::net::{IpAddr};
+use std::str::FromStr;
+
+use mithril::stratum::pinning;
+use mithril::stratum::pinning::{IpRange};
+
+fn ip(addr: &str) -> IpAddr {
+    IpAddr::from_str(addr).unwrap()
+}
+
+#[test]
+fn test_ip_range_contains() {
+    let single = IpRange::parse("203.0.113.7").unwrap();
+    assert!(single.contains(&ip("203.0.113.7")));
+    assert!(!single.contains(&ip("203.0.113.8")));
+
+    let subnet = IpRange::parse("10.0.0.0/8").unwrap();
+    assert!(subnet.contains(&ip("10.200.1.1")));
+    assert!(!subnet.contains(&ip("11.0.0.1")));
+    assert!(!subnet.contains(&ip("::1")));
+
+    let all = IpRange::parse("0.0.0.0/0").unwrap();
+    assert!(all.contains(&ip("198.51.100.1")));
+
+    let v6 = IpRange::parse("2001:db8::/32").unwrap();
+    assert!(v6.contains(&ip("2001:db8:1::1")));
+    assert!(!v6.contains(&ip("2001:db9::1")));
+}
+
+#[test]
+fn test_ip_range_parse_errors() {
+    assert!(IpRange::parse("pool.example.com").is_err());
+    assert!(IpRange::parse("10.0.0.0/33").is_err());
+    assert!(IpRange::parse("10.0.0.0/x").is_err());
+}
+
+#[test]
+fn test_check_peer_ip() {
+    let allowed = vec![IpRange::parse("10.0.0.0/8").unwrap()];
+    assert!(pinning::check_peer_ip(&ip("10.1.2.3"), &allowed).is_ok());
+    assert!(pinning::check_peer_ip(&ip("192.168.1.1"), &allowed).is_err());
+    assert!(pinning::check_peer_ip(&ip("192.168.1.1"), &[]).is_ok());
+}
+
+#[test]
+fn test_fingerprint() {
+    assert_eq!(pinning::fingerprint(b"abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    assert_eq!(pinning::normalize_fingerprint(" BA:78:16 "), "ba7816");
+}
+
+#[test]
+fn test_check_fingerprint() {
+    let pinned = vec!["ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".to_string()];
+    assert!(pinning::check_fingerprint(b"abc", &pinned).is_ok());
+    assert!(pinning::check_fingerprint(b"abd", &pinned).is_err());
+    assert!(pinning::check_fingerprint(b"abd", &[]).is_ok());
+}
@@ -0,0 +1,61 @@
+#![cfg(feature = "stratum")]
+
+extern crate mithril;
+
+use std::time::{Duration, Instant};
+
+use mithril::stratum::pending::{PendingRequests};
+use mithril::stratum::pool_score::{PoolScore, SCORE_MIN_SHARES};
+
+#[test]
+fn test_score_without_responses_depends_on_jobs_only() {
+    let mut score = PoolScore::new();
+    let now = Instant::now();
+    assert_eq!(score.stats(now).score, 80);
+    assert_eq!(score.stats(now).job_age, None);
+
+    score.record_job(now);
+    let stats = score.stats(now + Duration::from_secs(1));
+    assert_eq!(stats.score, 100);
+    assert_eq!(stats.job_age, Some(Duration::from_secs(1)));
+    assert_eq!(stats.shares, 0);
+
+    //twice as old as a fresh job, the freshness part is halved
+    assert_eq!(score.stats(now + Duration::from_secs(240)).score, 90);
+}
+
+#[test]
+fn test_score_combines_latency_and_rejects() {
+    let mut score = PoolScore::new();
+    let now = Instant::now();
+    score.record_job(now);
+    for _ in 0..3 {
+        score.record_round_trip(Duration::from_millis(500));
+        score.record_result(true);
+    }
+    score.record_round_trip(Duration::from_millis(500));
+    score.record_result(false);
+
+    let stats = score.stats(now);
+    assert_eq!(stats.accept_latency, Some(Duration::from_millis(500)));
+    assert_eq!(stats.reject_rate, 0.25);
+    assert_eq!(stats.shares, 4);
+    //0.4 * 0.5 + 0.4 * 0.75 + 0.2
+    assert_eq!(stats.score, 70);
+}
+
+#[test]
+fn test_low_score_is_reported_once_after_enough_shares() {
+    let mut pending = PendingRequests::new();
+    pending.job_received("job1");
+    assert_eq!(pending.poll_score(Some(90)), None);
+    for _ in 0..SCORE_MIN_SHARES {
+        pending.record_share_result(Some("Low difficulty share"));
+    }
+    assert_eq!(pending.poll_score(None), None);
+    assert_eq!(pending.poll_score(Some(50)), None);
+    let low = pending.poll_score(Some(90)).unwrap();
+    assert_eq!(low.score, 60);
+    assert_eq!(low.reject_rate, 1.0);
+    assert_eq!(pending.poll_score(Some(90)), None);
+}
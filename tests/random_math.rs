@@ -0,0 +1,54 @@
+extern crate mithril;
+
+use mithril::cryptonight::random_math;
+use mithril::cryptonight::random_math::{Instruction, Opcode, NUM_INSTRUCTIONS_MIN, NUM_INSTRUCTIONS_MAX};
+
+#[test]
+fn test_generate_is_deterministic_per_height() {
+    assert_eq!(random_math::generate(1806260), random_math::generate(1806260));
+    assert_ne!(random_math::generate(1806260), random_math::generate(1806261));
+}
+
+#[test]
+fn test_generated_programs_are_valid() {
+    for height in 1806260..1806360 {
+        let code = random_math::generate(height);
+        assert!(code.len() >= NUM_INSTRUCTIONS_MIN && code.len() <= NUM_INSTRUCTIONS_MAX, "{} instructions for height {}", code.len(), height);
+        assert!(code.iter().any(|op| op.src == 8), "r8 not used for height {}", height);
+        for op in &code {
+            assert!(op.dst < 4);
+            assert!(op.src < 9);
+            if op.opcode != Opcode::Add {
+                assert_eq!(op.c, 0);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_execute() {
+    let op = |opcode, dst, src, c| Instruction{opcode, dst, src, c};
+    let mut r = [3, 0x8000_0001, 5, 0xffff_ffff, 4, 0, 0, 0, 1];
+    random_math::execute(&[
+        op(Opcode::Mul, 0, 2, 0),
+        op(Opcode::Add, 2, 8, 10),
+        op(Opcode::Ror, 1, 8, 0),
+        op(Opcode::Sub, 3, 4, 0),
+        op(Opcode::Rol, 1, 4, 0),
+        op(Opcode::Xor, 0, 2, 0),
+    ], &mut r);
+    assert_eq!(r[0], 15 ^ 16);
+    assert_eq!(r[1], 0xc000_0000u32.rotate_left(4));
+    assert_eq!(r[2], 16);
+    assert_eq!(r[3], 0xffff_fffb);
+    //constant registers are not changed
+    assert_eq!(&r[4..], &[4, 0, 0, 0, 1]);
+}
+
+#[test]
+fn test_execute_wraps() {
+    let mut r = [0xffff_ffff, 2, 0, 0, 0, 0, 0, 0, 0];
+    random_math::execute(&[Instruction{opcode: Opcode::Mul, dst: 0, src: 1, c: 0}, Instruction{opcode: Opcode::Add, dst: 1, src: 0, c: 0xffff_ffff}], &mut r);
+    assert_eq!(r[0], 0xffff_fffe);
+    assert_eq!(r[1], 0xffff_ffff);
+}
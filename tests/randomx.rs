@@ -0,0 +1,99 @@
+extern crate mithril;
+
+use mithril::hex;
+use mithril::randomx::{Cache, Memory, Vm, RandomXConfig, MONERO, WOWNERO};
+use mithril::randomx::blake2_generator::{Blake2Generator};
+use mithril::randomx::soft_aes::{SoftAes};
+use mithril::randomx::superscalar;
+use mithril::u64x2::{u64x2};
+use std::sync::{Arc};
+
+#[test]
+fn test_reciprocal() {
+    assert_eq!(superscalar::reciprocal(3), 12297829382473034410);
+    assert_eq!(superscalar::reciprocal(13), 11351842506898185609);
+    assert_eq!(superscalar::reciprocal(33), 17887751829051686415);
+    assert_eq!(superscalar::reciprocal(65537), 18446462603027742720);
+    assert_eq!(superscalar::reciprocal(0xFFFF_FFFF), 9223372039002259456);
+}
+
+#[test]
+fn test_mulh() {
+    assert_eq!(superscalar::mulh(u64::max_value(), 2), 1);
+    assert_eq!(superscalar::smulh(u64::max_value(), 2), u64::max_value()); //-1 * 2
+    assert_eq!(superscalar::sign_extend(0xFFFF_FFFF), u64::max_value());
+    assert_eq!(superscalar::sign_extend(0x7FFF_FFFF), 0x7FFF_FFFF);
+}
+
+#[test]
+fn test_soft_aes_rounds() {
+    let aes = SoftAes::new();
+    //SubBytes(0) = 0x63, InvSubBytes(0) = 0x52, columns of equal bytes are kept by (Inv)MixColumns
+    assert_eq!(aes.enc(u64x2(0, 0), u64x2(0, 0)), u64x2(0x6363_6363_6363_6363, 0x6363_6363_6363_6363));
+    assert_eq!(aes.dec(u64x2(0, 0), u64x2(0, 0)), u64x2(0x5252_5252_5252_5252, 0x5252_5252_5252_5252));
+    assert_eq!(aes.dec(u64x2(0, 0), u64x2(1, 2)), u64x2(0x5252_5252_5252_5253, 0x5252_5252_5252_5250));
+}
+
+#[test]
+fn test_blake2_generator() {
+    let mut gen = Blake2Generator::new(b"test key 000", 0);
+    let mut same = Blake2Generator::new(b"test key 000", 0);
+    let mut other = Blake2Generator::new(b"test key 000", 1);
+    let bytes : Vec<u8> = (0..200).map(|_| gen.get_byte()).collect();
+    let same_bytes : Vec<u8> = (0..200).map(|_| same.get_byte()).collect();
+    let other_bytes : Vec<u8> = (0..200).map(|_| other.get_byte()).collect();
+    assert_eq!(bytes, same_bytes);
+    assert_ne!(bytes, other_bytes);
+}
+
+#[test]
+fn test_superscalar_generate() {
+    let program = superscalar::generate(&mut Blake2Generator::new(b"test key 000", 0));
+    assert_eq!(program, superscalar::generate(&mut Blake2Generator::new(b"test key 000", 0)));
+    assert!(program.address_register < 8);
+    assert!(!program.instructions.is_empty());
+    assert!(program.instructions.len() <= superscalar::MAX_PROGRAM_SIZE);
+    //r5 needs a displacement in IADD_RS and is never its destination
+    assert!(program.instructions.iter().all(|i| i.opcode != superscalar::Opcode::IaddRs || i.dst != 5));
+}
+
+#[test]
+fn test_config_validate() {
+    assert_eq!(MONERO.validate(), Ok(()));
+    assert_eq!(WOWNERO.validate(), Ok(()));
+    assert_eq!(WOWNERO.dataset_item_count(), MONERO.dataset_item_count());
+    assert_eq!(MONERO.dataset_item_count(), 34_078_719);
+    assert_eq!(MONERO.dataset_extra_items(), 524_287);
+
+    let invalid = [
+        RandomXConfig{scratchpad_l3: 3_000_000, ..MONERO},
+        RandomXConfig{scratchpad_l2: 8_192, ..MONERO},
+        RandomXConfig{dataset_base_size: 2_000_000_000, ..MONERO},
+        RandomXConfig{dataset_extra_size: 33_554_367, ..MONERO},
+        RandomXConfig{program_count: 0, ..MONERO},
+        RandomXConfig{jump_bits: 48, ..MONERO},
+        RandomXConfig{argon_memory: 262_145, ..MONERO},
+        RandomXConfig{opcode_frequencies: [1; 29], ..MONERO},
+    ];
+    for config in invalid.iter() {
+        assert!(config.validate().is_err(), "{:?}", config);
+    }
+}
+
+//reference test vectors (tests.cpp of the reference implementation), the cache of one seed
+//is built once for all of them, about 10s in debug builds
+#[test]
+fn test_cache_light_mode() {
+    let cache = Arc::new(Cache::new(&MONERO, b"test key 000"));
+    assert_eq!(cache.seed(), b"test key 000");
+    assert_eq!(cache.config(), &MONERO);
+    assert_eq!(cache.dataset_item(0)[0], 0x6805_88a8_5ae2_22db);
+    assert_eq!(cache.dataset_item(10_000_000)[0], 0x7943_a1f6_186f_fb72);
+    assert_eq!(cache.dataset_item(20_000_000)[0], 0x9035_244d_7180_95e1);
+    assert_eq!(cache.dataset_item(30_000_000)[0], 0x145a_5091_f785_3099);
+
+    let mut vm = Vm::new(Memory::Light(cache));
+    assert_eq!(vm.seed(), b"test key 000");
+    assert_eq!(hex::encode(&vm.calculate_hash(b"This is a test")), "639183aae1bf4c9a35884cb46b09cad9175f04efd7684e7262a0ac1c2f0b4e3f");
+    assert_eq!(hex::encode(&vm.calculate_hash(b"Lorem ipsum dolor sit amet")), "300a0adb47603dedb42228ccb2b211104f4da45af709cd7547cd049e9489c969");
+}
@@ -0,0 +1,74 @@
+#![cfg(feature = "stratum")]
+
+extern crate mithril;
+
+use std::io::{ErrorKind};
+use std::net::{SocketAddr, TcpListener};
+use std::str::FromStr;
+use std::time::{Duration};
+
+use mithril::stratum::resolve;
+use mithril::stratum::resolve::{AddressPreference};
+use mithril::stratum::pinning::{IpRange};
+
+fn addrs(list: &[&str]) -> Vec<SocketAddr> {
+    list.iter().map(|a| SocketAddr::from_str(a).unwrap()).collect()
+}
+
+#[test]
+fn test_address_preference_from_name() {
+    assert_eq!(AddressPreference::from_name("system"), Some(AddressPreference::System));
+    assert_eq!(AddressPreference::from_name("ipv6"), Some(AddressPreference::Ipv6First));
+    assert_eq!(AddressPreference::from_name("ipv4"), Some(AddressPreference::Ipv4First));
+    assert_eq!(AddressPreference::from_name("ipv4_only"), Some(AddressPreference::Ipv4Only));
+    assert_eq!(AddressPreference::from_name("ipv6_only"), Some(AddressPreference::Ipv6Only));
+    assert_eq!(AddressPreference::from_name("both"), None);
+}
+
+#[test]
+fn test_order_addresses() {
+    let resolved = addrs(&["10.0.0.1:3333", "10.0.0.2:3333", "[2001:db8::1]:3333", "10.0.0.3:3333"]);
+
+    assert_eq!(resolve::order_addresses(resolved.clone(), AddressPreference::System), resolved);
+    assert_eq!(resolve::order_addresses(resolved.clone(), AddressPreference::Ipv6First),
+        addrs(&["[2001:db8::1]:3333", "10.0.0.1:3333", "10.0.0.2:3333", "10.0.0.3:3333"]));
+    assert_eq!(resolve::order_addresses(resolved.clone(), AddressPreference::Ipv4First),
+        addrs(&["10.0.0.1:3333", "[2001:db8::1]:3333", "10.0.0.2:3333", "10.0.0.3:3333"]));
+    assert_eq!(resolve::order_addresses(resolved.clone(), AddressPreference::Ipv4Only),
+        addrs(&["10.0.0.1:3333", "10.0.0.2:3333", "10.0.0.3:3333"]));
+    assert_eq!(resolve::order_addresses(resolved, AddressPreference::Ipv6Only),
+        addrs(&["[2001:db8::1]:3333"]));
+}
+
+#[test]
+fn test_connect_reports_used_address() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let stream = resolve::connect(&format!("localhost:{}", port), Duration::from_secs(5), AddressPreference::Ipv4First).unwrap();
+    assert_eq!(stream.peer_addr().unwrap(), listener.local_addr().unwrap());
+}
+
+#[test]
+fn test_connect_without_matching_address_fails() {
+    let result = resolve::connect("127.0.0.1:3333", Duration::from_secs(1), AddressPreference::Ipv6Only);
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_connect_allowed_skips_addresses_outside_the_allowlist() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+
+    let allowed = vec![IpRange::parse("10.0.0.0/8").unwrap()];
+    let err = resolve::connect_allowed(&address, Duration::from_secs(1), AddressPreference::System, &allowed).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+
+    //nothing connected to the refused address
+    listener.set_nonblocking(true).unwrap();
+    assert_eq!(listener.accept().unwrap_err().kind(), ErrorKind::WouldBlock);
+
+    let allowed = vec![IpRange::parse("127.0.0.0/8").unwrap()];
+    let stream = resolve::connect_allowed(&address, Duration::from_secs(1), AddressPreference::System, &allowed).unwrap();
+    assert_eq!(stream.peer_addr().unwrap(), listener.local_addr().unwrap());
+}
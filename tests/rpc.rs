@@ -0,0 +1,65 @@
+#![cfg(feature = "stratum")]
+
+extern crate mithril;
+#[macro_use]
+extern crate serde_json;
+
+use mithril::stratum::rpc::{RpcHandlers, RpcRequest, RpcError, parse_request, METHOD_NOT_FOUND};
+use mithril::stratum::stratum_data::{default_agent, UNKNOWN_ERROR_CODE};
+
+fn request(method: &str) -> RpcRequest {
+    RpcRequest{id: json!(1), method: method.to_string(), params: json!(null)}
+}
+
+#[test]
+fn test_parse_request() {
+    assert_eq!(parse_request(r#"{"id":3,"jsonrpc":"2.0","method":"client.get_version"}"#),
+        Some(RpcRequest{id: json!(3), method: "client.get_version".to_string(), params: json!(null)}));
+    assert_eq!(parse_request(r#"{"id":"a","method":"ping","params":{"t":1}}"#),
+        Some(RpcRequest{id: json!("a"), method: "ping".to_string(), params: json!({"t": 1})}));
+}
+
+#[test]
+fn test_parse_request_ignores_responses_and_notifications() {
+    assert_eq!(parse_request(r#"{"id":1,"jsonrpc":"2.0","error":null,"result":{"status":"OK"}}"#), None);
+    assert_eq!(parse_request(r#"{"jsonrpc":"2.0","method":"ping"}"#), None);
+    assert_eq!(parse_request(r#"{"id":null,"method":"ping"}"#), None);
+    //some pools send jobs with an id, they are still notifications
+    assert_eq!(parse_request(r#"{"id":5,"jsonrpc":"2.0","method":"job","params":{}}"#), None);
+    assert_eq!(parse_request("not json"), None);
+}
+
+#[test]
+fn test_default_handlers() {
+    let handlers = RpcHandlers::new();
+    assert_eq!(handlers.respond(&request("client.get_version")), Ok(json!(default_agent())));
+    assert_eq!(handlers.respond(&request("ping")), Ok(json!("pong")));
+    assert_eq!(handlers.respond(&request("client.reconnect")).unwrap_err().code, METHOD_NOT_FOUND);
+    assert!(!RpcHandlers::empty().handles("ping"));
+}
+
+#[test]
+fn test_register_replaces_and_remove() {
+    let mut handlers = RpcHandlers::new();
+    handlers.register("client.get_version", |_| Ok(json!("custom/1.0")));
+    handlers.register("echo", |params| Ok(params.clone()));
+    handlers.register("fail", |_| Err("not now".to_string()));
+
+    assert_eq!(handlers.respond(&request("client.get_version")), Ok(json!("custom/1.0")));
+    let echo = RpcRequest{id: json!(2), method: "echo".to_string(), params: json!([1, 2])};
+    assert_eq!(handlers.respond(&echo), Ok(json!([1, 2])));
+    assert_eq!(handlers.respond(&request("fail")), Err(RpcError{code: UNKNOWN_ERROR_CODE, message: "not now".to_string()}));
+
+    assert!(handlers.remove("ping"));
+    assert!(!handlers.remove("ping"));
+    assert_eq!(handlers.respond(&request("ping")).unwrap_err().code, METHOD_NOT_FOUND);
+}
+
+#[test]
+fn test_panicking_handler_is_answered_with_error() {
+    let mut handlers = RpcHandlers::empty();
+    handlers.register("boom", |_| panic!("handler failed"));
+
+    assert_eq!(handlers.respond(&request("boom")).unwrap_err().code, UNKNOWN_ERROR_CODE);
+    assert!(handlers.handles("boom"));
+}
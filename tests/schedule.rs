@@ -0,0 +1,65 @@
+#![cfg(feature = "stratum")]
+
+extern crate mithril;
+
+use mithril::stratum::schedule;
+use mithril::stratum::schedule::{PoolSchedule, ScheduleWindow};
+use mithril::stratum::stratum_data::{PoolConfig};
+
+fn window(from: &str, to: &str, pool_address: &str) -> ScheduleWindow {
+    ScheduleWindow{from_minute: schedule::parse_time(from).unwrap(), to_minute: schedule::parse_time(to).unwrap(),
+        pool_address: pool_address.to_string(), wallet_address: None, pool_password: None}
+}
+
+#[test]
+fn test_parse_time() {
+    assert_eq!(schedule::parse_time("00:00"), Some(0));
+    assert_eq!(schedule::parse_time("6:30"), Some(390));
+    assert_eq!(schedule::parse_time(" 23:59 "), Some(1439));
+    assert_eq!(schedule::parse_time("24:00"), None);
+    assert_eq!(schedule::parse_time("12:60"), None);
+    assert_eq!(schedule::parse_time("12"), None);
+    assert_eq!(schedule::format_time(390), "06:30");
+}
+
+#[test]
+fn test_window_over_midnight() {
+    let night = window("22:00", "06:00", "night.example.com:3333");
+    assert!(night.contains(schedule::parse_time("22:00").unwrap()));
+    assert!(night.contains(schedule::parse_time("00:00").unwrap()));
+    assert!(night.contains(schedule::parse_time("05:59").unwrap()));
+    assert!(!night.contains(schedule::parse_time("06:00").unwrap()));
+    assert!(!night.contains(schedule::parse_time("21:59").unwrap()));
+    assert_eq!(night.describe(), "22:00-06:00");
+}
+
+#[test]
+fn test_first_matching_window_is_active() {
+    let pool_schedule = PoolSchedule{windows: vec![window("08:00", "18:00", "day.example.com:3333"), window("12:00", "13:00", "noon.example.com:3333")]};
+    assert_eq!(pool_schedule.active(schedule::parse_time("12:30").unwrap()), Some(0));
+    assert_eq!(pool_schedule.active(schedule::parse_time("18:00").unwrap()), None);
+    assert!(PoolSchedule::default().is_empty());
+    assert_eq!(PoolSchedule::default().active(0), None);
+}
+
+#[test]
+fn test_pool_config_of_window_keeps_unset_values() {
+    let mut night = window("22:00", "06:00", "night.example.com:3333");
+    night.wallet_address = Some("night-wallet".to_string());
+    let pool_schedule = PoolSchedule{windows: vec![night]};
+    let pool_conf = PoolConfig::new("day.example.com:3333", "wallet", "x");
+
+    let conf = pool_schedule.pool_config(&pool_conf, Some(0));
+    assert_eq!(conf.pool_address, "night.example.com:3333");
+    assert_eq!(conf.wallet_address, "night-wallet");
+    assert_eq!(conf.pool_password, "x");
+
+    let conf = pool_schedule.pool_config(&pool_conf, None);
+    assert_eq!(conf.pool_address, "day.example.com:3333");
+    assert_eq!(conf.wallet_address, "wallet");
+}
+
+#[test]
+fn test_local_minute_of_day_is_in_range() {
+    assert!(schedule::local_minute_of_day() < schedule::MINUTES_PER_DAY);
+}
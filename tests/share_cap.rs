@@ -0,0 +1,36 @@
+#![cfg(feature = "stratum")]
+
+extern crate mithril;
+
+use mithril::stratum::share_cap::ShareCap;
+
+#[test]
+fn test_share_cap_disabled() {
+    let mut cap = ShareCap::new(None);
+    for _ in 0..100 {
+        assert!(cap.allow("job"));
+    }
+    assert_eq!(cap.withheld(), 0);
+}
+
+#[test]
+fn test_share_cap_per_job() {
+    let mut cap = ShareCap::new(Some(2));
+    assert!(cap.allow("job1"));
+    assert!(cap.allow("job1"));
+    assert!(!cap.allow("job1"));
+    assert!(cap.allow("job2"));
+    assert!(!cap.allow("job1"));
+    assert_eq!(cap.withheld(), 2);
+}
+
+#[test]
+fn test_share_cap_forgets_old_jobs() {
+    let mut cap = ShareCap::new(Some(1));
+    assert!(cap.allow("job0"));
+    for i in 1..20 {
+        assert!(cap.allow(&format!("job{}", i)));
+    }
+    //job0 was evicted, counting starts again
+    assert!(cap.allow("job0"));
+}
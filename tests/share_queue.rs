@@ -0,0 +1,121 @@
+#![cfg(feature = "stratum")]
+
+extern crate mithril;
+
+use std::sync::{Arc};
+use std::sync::mpsc::{channel};
+use std::thread;
+use std::time::{Duration};
+
+use mithril::stratum::StratumCmd;
+use mithril::stratum::stratum_data::{Share};
+use mithril::stratum::share_queue::{ShareQueue, QueuePolicy, QueueStats, SubmitError};
+use mithril::stratum::offline::{OfflineShares};
+
+fn share(job_id: &str) -> Share {
+    Share{
+        miner_id: "miner".to_string(),
+        job_id: job_id.to_string(),
+        nonce: "00000001".to_string(),
+        hash: "hash".to_string(),
+        times: None
+    }
+}
+
+#[test]
+fn test_queue_policy_from_name() {
+    assert_eq!(QueuePolicy::from_name("block"), Some(QueuePolicy::Block));
+    assert_eq!(QueuePolicy::from_name("error"), Some(QueuePolicy::Error));
+    assert_eq!(QueuePolicy::from_name("drop_oldest"), None);
+}
+
+#[test]
+fn test_error_policy_drops_share_if_full() {
+    let (tx, rx) = channel();
+    let queue = ShareQueue::new(2, QueuePolicy::Error);
+
+    assert_eq!(queue.submit(&tx, share("job1")), Ok(()));
+    assert_eq!(queue.submit(&tx, share("job2")), Ok(()));
+    assert_eq!(queue.submit(&tx, share("job3")), Err(SubmitError::Full));
+
+    assert_eq!(queue.stats(), QueueStats{capacity: 2, depth: 2, max_depth: 2, dropped: 1});
+    assert_eq!(rx.try_iter().count(), 2);
+}
+
+#[test]
+fn test_release_frees_a_slot() {
+    let (tx, _rx) = channel();
+    let queue = ShareQueue::new(1, QueuePolicy::Error);
+
+    assert_eq!(queue.submit(&tx, share("job1")), Ok(()));
+    queue.release();
+    assert_eq!(queue.submit(&tx, share("job2")), Ok(()));
+
+    assert_eq!(queue.stats(), QueueStats{capacity: 1, depth: 1, max_depth: 1, dropped: 0});
+}
+
+#[test]
+fn test_block_policy_waits_for_release() {
+    let (tx, rx) = channel();
+    let queue = Arc::new(ShareQueue::new(1, QueuePolicy::Block));
+    queue.submit(&tx, share("job1")).unwrap();
+
+    let thread_queue = queue.clone();
+    let hnd = thread::spawn(move || thread_queue.submit(&tx, share("job2")));
+
+    match rx.recv().unwrap() {
+        StratumCmd::SubmitShare{share} => assert_eq!(share.job_id, "job1"),
+        other => assert!(false, "Wrong command received: {:?}", other)
+    }
+    thread::sleep(Duration::from_millis(50));
+    assert!(rx.try_recv().is_err(), "second share queued while the queue was full");
+
+    queue.release();
+    assert_eq!(hnd.join().unwrap(), Ok(()));
+    match rx.recv_timeout(Duration::from_secs(10)).unwrap() {
+        StratumCmd::SubmitShare{share} => assert_eq!(share.job_id, "job2"),
+        other => assert!(false, "Wrong command received: {:?}", other)
+    }
+}
+
+#[test]
+fn test_close_unblocks_waiting_submit() {
+    let (tx, _rx) = channel();
+    let queue = Arc::new(ShareQueue::new(1, QueuePolicy::Block));
+    queue.submit(&tx, share("job1")).unwrap();
+
+    let thread_queue = queue.clone();
+    let hnd = thread::spawn(move || thread_queue.submit(&tx, share("job2")));
+    thread::sleep(Duration::from_millis(50));
+    queue.close();
+
+    assert_eq!(hnd.join().unwrap(), Err(SubmitError::Disconnected));
+}
+
+#[test]
+fn test_submit_to_dropped_channel_is_disconnected() {
+    let (tx, rx) = channel();
+    drop(rx);
+    let queue = ShareQueue::new(4, QueuePolicy::Error);
+
+    assert_eq!(queue.submit(&tx, share("job1")), Err(SubmitError::Disconnected));
+    assert_eq!(queue.stats().depth, 0);
+}
+
+#[test]
+fn test_closed_queue_keeps_shares_offline() {
+    let (tx, _rx) = channel();
+    let queue = ShareQueue::new(4, QueuePolicy::Error);
+    assert!(queue.take_offline("miner", "job1").is_none());
+
+    queue.keep_offline(Arc::new(OfflineShares::new(4, Duration::from_secs(60))), "pool:3333");
+    queue.close();
+    assert_eq!(queue.submit(&tx, share("job1")), Err(SubmitError::Kept));
+    assert_eq!(queue.stats().depth, 0);
+
+    let salvage = queue.take_offline("miner", "job1").unwrap();
+    assert_eq!(salvage.shares.len(), 1);
+    assert_eq!(salvage.shares[0].job_id, "job1");
+    assert_eq!(salvage.discarded, 0);
+    assert!(queue.take_offline("miner", "job1").is_none(), "kept shares taken twice");
+}
@@ -0,0 +1,54 @@
+#![cfg(feature = "stratum")]
+
+extern crate mithril;
+
+use std::sync::mpsc::{channel};
+use std::sync::{Arc, Mutex};
+
+use mithril::stratum;
+use mithril::stratum::pending::{PendingRequests, Request};
+use mithril::stratum::share_stats::{ShareStats};
+
+#[test]
+fn test_share_stats_counts_stale_separately() {
+    let mut stats = ShareStats::default();
+    for _ in 0..4 {
+        stats.record_submitted();
+    }
+    stats.record_accepted();
+    stats.record_rejected("Low difficulty share");
+    stats.record_rejected("Block expired");
+
+    assert_eq!(stats, ShareStats{submitted: 4, accepted: 1, rejected: 1, stale: 1});
+    assert_eq!(stats.unanswered(), 1);
+}
+
+#[test]
+fn test_pending_requests_count_submitted_shares_only() {
+    let mut pending = PendingRequests::new();
+    pending.register(Request::Login);
+    pending.register(Request::Submit{job_id: "job1".to_string()});
+    pending.register(Request::KeepAlive);
+
+    assert_eq!(pending.share_stats().submitted, 1);
+}
+
+#[test]
+fn test_share_results_correlated_by_request_id() {
+    let (tx, rx) = channel();
+    let miner_id = Arc::new(Mutex::new(Some("miner".to_string())));
+    let pending = Arc::new(Mutex::new(PendingRequests::new()));
+    let accepted_id = pending.lock().unwrap().register(Request::Submit{job_id: "job1".to_string()});
+    let stale_id = pending.lock().unwrap().register(Request::Submit{job_id: "job1".to_string()});
+    let keepalive_id = pending.lock().unwrap().register(Request::KeepAlive);
+
+    let accepted = format!(r#"{{"id":{},"jsonrpc":"2.0","error":null,"result":{{"status":"OK"}}}}"#, accepted_id);
+    let stale = format!(r#"{{"id":{},"jsonrpc":"2.0","error":{{"code":-1,"message":"Block expired"}}}}"#, stale_id);
+    let keepalive = format!(r#"{{"id":{},"jsonrpc":"2.0","error":null,"result":{{"status":"KEEPALIVED"}}}}"#, keepalive_id);
+    for line in &[accepted, stale, keepalive] {
+        stratum::parse_line_dispatch_result(line, &tx, &miner_id, &pending).unwrap();
+        rx.recv().unwrap();
+    }
+
+    assert_eq!(pending.lock().unwrap().share_stats(), ShareStats{submitted: 2, accepted: 1, rejected: 0, stale: 1});
+}
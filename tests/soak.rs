@@ -0,0 +1,57 @@
+#![cfg(feature = "miner-bin")]
+
+extern crate mithril;
+
+use std::net::TcpListener;
+use std::time::Duration;
+
+use mithril::mithril_config;
+use mithril::soak;
+
+fn stats(threads: u64, open_fds: u64, rss_kb: u64) -> Option<soak::ProcessStats> {
+    Some(soak::ProcessStats{threads, open_fds, rss_kb})
+}
+
+#[test]
+fn test_parse_status() {
+    let status = "Name:\tmithril\nVmPeak:\t  20000 kB\nVmRSS:\t   4096 kB\nThreads:\t3\n";
+    assert_eq!(soak::parse_status(status), Some((3, 4096)));
+    assert_eq!(soak::parse_status("Name:\tmithril\n"), None);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_process_stats() {
+    let stats = soak::process_stats().unwrap();
+    assert!(stats.threads >= 1);
+    assert!(stats.open_fds >= 1);
+    assert!(stats.rss_kb > 0);
+}
+
+#[test]
+fn test_leaks() {
+    let result = soak::SoakResult{iterations: 10, failed_logins: 0, baseline: stats(4, 10, 5000), after: stats(4, 10, 5000 + soak::RSS_TOLERANCE_KB)};
+    assert!(result.leaks().is_empty());
+
+    let result = soak::SoakResult{iterations: 10, failed_logins: 0, baseline: stats(4, 10, 5000), after: stats(5, 12, 6000 + soak::RSS_TOLERANCE_KB)};
+    assert_eq!(result.leaks().len(), 3);
+
+    let result = soak::SoakResult{iterations: 10, failed_logins: 0, baseline: None, after: None};
+    assert!(result.leaks().is_empty());
+}
+
+#[test]
+fn test_run_counts_failed_logins() {
+    let address = {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().to_string()
+    };
+    let mut pool_conf = mithril_config::donation_conf();
+    pool_conf.pool_address = address;
+    pool_conf.connect_timeout_secs = 1;
+    pool_conf.login_retries = 0;
+
+    let result = soak::run(&pool_conf, 3, Duration::from_millis(10));
+    assert_eq!(result.iterations, 3);
+    assert_eq!(result.failed_logins, 3);
+}
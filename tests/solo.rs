@@ -0,0 +1,93 @@
+#![cfg(feature = "stratum")]
+
+extern crate mithril;
+extern crate serde_json;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener};
+use std::thread;
+
+use mithril::solo;
+use mithril::stratum::StratumError;
+use mithril::worker::worker_pool;
+
+/// Answers one HTTP request with `body`, returns the body of the request
+fn serve_once(listener: TcpListener, body: &'static str) -> thread::JoinHandle<String> {
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line == "\r\n" {
+                break;
+            }
+            if line.to_lowercase().starts_with("content-length:") {
+                content_length = line[15..].trim().parse().unwrap();
+            }
+        }
+        let mut request = vec![0; content_length];
+        reader.read_exact(&mut request).unwrap();
+        let mut writer = stream;
+        write!(writer, "HTTP/1.1 200 Ok\r\nContent-Length: {}\r\nContent-Type: application/json\r\n\r\n{}", body.len(), body).unwrap();
+        String::from_utf8(request).unwrap()
+    })
+}
+
+#[test]
+fn test_difficulty_target() {
+    assert_eq!(solo::difficulty_target(1), "ffffffffffffffff");
+    assert_eq!(solo::difficulty_target(0), "ffffffffffffffff");
+    let target = solo::difficulty_target(171798);
+    assert_eq!(target.len(), 16);
+    assert_eq!(worker_pool::job_target(&target), u64::max_value() / 171798);
+}
+
+#[test]
+fn test_job_target_32_bit() {
+    assert_eq!(worker_pool::job_target("169f0200"), worker_pool::target_u64(171798));
+}
+
+#[test]
+fn test_parse_template() {
+    let result : serde_json::Value = serde_json::from_str(r#"{"blockhashing_blob":"0a0b","blocktemplate_blob":"0a0b0c",
+        "difficulty":1000,"height":42,"prev_hash":"ab","reserved_offset":130,"status":"OK"}"#).unwrap();
+    let template = solo::parse_template(&result, 3).unwrap();
+    assert_eq!(template.job_id, "42-3");
+    assert_eq!(template.height, 42);
+    assert_eq!(template.difficulty, 1000);
+    assert_eq!(template.prev_hash, "ab");
+    assert_eq!(template.blockhashing_blob, "0a0b");
+    assert_eq!(template.blocktemplate_blob, "0a0b0c");
+
+    let incomplete : serde_json::Value = serde_json::from_str(r#"{"height":42}"#).unwrap();
+    assert!(solo::parse_template(&incomplete, 0).is_err());
+}
+
+#[test]
+fn test_json_rpc_result() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let server = serve_once(listener, r#"{"id":"0","jsonrpc":"2.0","result":{"status":"OK"}}"#);
+
+    let result = solo::json_rpc(&address, "submit_block", vec!["00".to_string()]).unwrap();
+    assert_eq!(result["status"], "OK");
+
+    let request : serde_json::Value = serde_json::from_str(&server.join().unwrap()).unwrap();
+    let expected : serde_json::Value = serde_json::from_str(r#"{"jsonrpc":"2.0","id":"0","method":"submit_block","params":["00"]}"#).unwrap();
+    assert_eq!(request, expected);
+}
+
+#[test]
+fn test_json_rpc_error() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let server = serve_once(listener, r#"{"id":"0","jsonrpc":"2.0","error":{"code":-7,"message":"Block not accepted"}}"#);
+
+    match solo::json_rpc(&address, "submit_block", vec!["00".to_string()]) {
+        Err(StratumError::Protocol(msg)) => assert!(msg.contains("Block not accepted")),
+        other => assert!(false, "Wrong result returned: {:?}", other)
+    }
+    server.join().unwrap();
+}
@@ -1,14 +1,32 @@
+#![cfg(feature = "stratum")]
+
 extern crate mithril;
 extern crate serde;
+#[macro_use]
 extern crate serde_json;
 
 use std::sync::mpsc::{channel};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration};
+use std::io::{Cursor};
+use std::collections::{BTreeMap};
 
 use mithril::stratum::stratum_data;
 use mithril::stratum;
+use mithril::stratum::pending::{PendingRequests, Request};
+use mithril::stratum::resolve::{AddressPreference};
+use mithril::stratum::share_queue::{QueuePolicy, ShareQueue};
+use mithril::stratum::multi_pool::{ArbitrationPolicy};
+use mithril::stratum::flood_guard::{FloodGuardConfig};
+
+fn default_pool_conf() -> stratum_data::PoolConfig {
+    stratum_data::PoolConfig::new("pool.example.com:3333", "wallet", "x")
+}
+
+fn pending() -> Arc<Mutex<PendingRequests>> {
+    Arc::new(Mutex::new(PendingRequests::new()))
+}
 
 #[test]
 fn test_ser_submit_json() {
@@ -19,13 +37,28 @@ fn test_ser_submit_json() {
             id: "id".to_string(),
             job_id: "job_id".to_string(),
             nonce: "nonce".to_string(),
-            result: "result".to_string()
+            result: "result".to_string(),
+            algo: None,
+            extra: BTreeMap::new()
         }
     };
 
     assert_eq!(serde_json::to_string(&submit_req).unwrap(), "{\"id\":1,\"method\":\"submit\",\"params\":{\"id\":\"id\",\"job_id\":\"job_id\",\"nonce\":\"nonce\",\"result\":\"result\"}}");
 }
 
+#[test]
+fn test_submit_options_add_pool_specific_params() {
+    let options = stratum_data::SubmitOptions{
+        uppercase_hex: true,
+        algo: Some("rx/0".to_string()),
+        extra_params: vec![("worker".to_string(), "{rig_id}".to_string()), ("extra_id".to_string(), "{miner_id}-{job_id}".to_string())]
+    };
+    let share = stratum_data::Share{miner_id: "miner".to_string(), job_id: "job".to_string(), nonce: "0000abcd".to_string(), hash: "ef01".to_string(), times: None};
+    let submit_req = stratum_data::SubmitRequest{id: 1, method: "submit".to_string(), params: options.params(share, Some("rig1"))};
+
+    assert_eq!(serde_json::to_string(&submit_req).unwrap(), "{\"id\":1,\"method\":\"submit\",\"params\":{\"id\":\"miner\",\"job_id\":\"job\",\"nonce\":\"0000ABCD\",\"result\":\"EF01\",\"algo\":\"rx/0\",\"extra_id\":\"miner-job\",\"worker\":\"rig1\"}}");
+}
+
 #[test]
 fn test_ser_login_json() {
     let login_req = stratum_data::LoginRequest {
@@ -33,7 +66,11 @@ fn test_ser_login_json() {
         method: "login".to_string(),
         params: stratum_data::LoginParams {
             login: "foo".to_string(),
-            pass: "bar".to_string()
+            pass: "bar".to_string(),
+            algo: Vec::new(),
+            rig_id: None,
+            agent: None,
+            mode: None
         }
     };
 
@@ -61,10 +98,10 @@ fn test_parse_line_dispatch_result_error() {
 
     let mutex_thread = miner_id_mutex.clone();
     thread::spawn(move || {
-        stratum::parse_line_dispatch_result(line, &tx, &mutex_thread);
+        stratum::parse_line_dispatch_result(line, &tx, &mutex_thread, &pending()).unwrap();
     });
 
-    let result = rx.recv().unwrap();
+    let result = rx.recv().unwrap().action;
 
     match result {
         stratum::StratumAction::Error{err} => assert_eq!(err, "error received: Low difficulty share (code -1, raw json {\"id\":1,\"jsonrpc\":\"2.0\",\"error\":{\"code\":-1,\"message\":\"Low difficulty share\"}})".to_string()),
@@ -94,20 +131,23 @@ fn test_parse_line_dispatch_result_initial_job() {
 
     let mutex_thread = miner_id_mutex.clone();
     thread::spawn(move || {
-        stratum::parse_line_dispatch_result(line, &tx, &mutex_thread);
+        stratum::parse_line_dispatch_result(line, &tx, &mutex_thread, &pending()).unwrap();
     });
 
-    let result = rx.recv().unwrap();
+    let result = rx.recv().unwrap().action;
 
     let miner_id_guard = &*miner_id_mutex.lock().unwrap();
     assert_eq!(miner_id_guard.clone().unwrap(), "930717205908149");
 
     match result {
-        stratum::StratumAction::Job{miner_id, blob, job_id, target} => {
+        stratum::StratumAction::Job{miner_id, blob, job_id, target, difficulty, algo, height, ..} => {
             assert_eq!(miner_id, "930717205908149");
             assert_eq!(blob, "0606fdb09bcf056875870cb2750c2db9d179d1e8cf22a2c89e4e43bc4aaaabda227e2fd1ad14f2000000007e6fe370e8ec9594b111fe7fa47d9a0f2efc52454d24fc610f59acbb399d098806");
             assert_eq!(job_id, "738478949642740");
             assert_eq!(target, "169f0200");
+            assert_eq!(difficulty, 25000);
+            assert_eq!(algo, None);
+            assert_eq!(height, None);
         },
         _ => assert!(false, "Wrong result returned: {:?}", result)
     }
@@ -134,10 +174,10 @@ fn test_parse_line_dispatch_result_initial_job_with_non_ok_result() {
         }}"#;
 
     thread::spawn(move || {
-        stratum::parse_line_dispatch_result(line, &tx, &miner_id_mutex);
+        stratum::parse_line_dispatch_result(line, &tx, &miner_id_mutex, &pending()).unwrap_err();
     });
 
-    let result = rx.recv().unwrap();
+    let result = rx.recv().unwrap().action;
 
     match result {
         stratum::StratumAction::Error{..} => assert!(true),
@@ -159,10 +199,10 @@ fn test_parse_line_dispatch_unknown_method() {
         }}"#;
 
     thread::spawn(move || {
-        stratum::parse_line_dispatch_result(line, &tx, &miner_id_mutex);
+        stratum::parse_line_dispatch_result(line, &tx, &miner_id_mutex, &pending()).unwrap();
     });
 
-    let result = rx.recv().unwrap();
+    let result = rx.recv().unwrap().action;
 
     match result {
         stratum::StratumAction::Error{..} => assert!(true),
@@ -186,16 +226,18 @@ fn test_parse_line_dispatch_job_method() {
         }}"#;
 
     thread::spawn(move || {
-        stratum::parse_line_dispatch_result(line, &tx, &miner_id_mutex);
+        stratum::parse_line_dispatch_result(line, &tx, &miner_id_mutex, &pending()).unwrap();
     });
 
-    let result = rx.recv().unwrap();
+    let result = rx.recv().unwrap().action;
     match result {
-        stratum::StratumAction::Job{miner_id, blob, job_id, target} => {
+        stratum::StratumAction::Job{miner_id, blob, job_id, target, algo, height, ..} => {
             assert_eq!(miner_id, "test_miner_id");
             assert_eq!(blob, "0606fcb29bcf051b9c7bfc60c98885de404ef48f721f09b8f51d37faf280470880bd120d4e9e0500000000577192c076fed53a24372bc43a3bed1d448a061ad06a262ac5e7f6803a28ccc705");
             assert_eq!(job_id, "878440772206522");
             assert_eq!(target, "169f0200");
+            assert_eq!(algo, None);
+            assert_eq!(height, None);
         },
         _ => assert!(false, "Wrong result returned: {:?}", result)
     }
@@ -217,10 +259,10 @@ fn test_parse_line_dispatch_job_method_missing_miner_id() {
         }}"#;
 
     thread::spawn(move || {
-        stratum::parse_line_dispatch_result(line, &tx, &miner_id_mutex);
+        stratum::parse_line_dispatch_result(line, &tx, &miner_id_mutex, &pending()).unwrap();
     });
 
-    let result = rx.recv().unwrap();
+    let result = rx.recv().unwrap().action;
 
     match result {
         stratum::StratumAction::Error{err} => assert_eq!(err, "miner_id not available for first mining job (login failed previously, this is a bug)"),
@@ -237,10 +279,10 @@ fn test_parse_line_dispatch_job_ok_result_share_submit() {
     let line = r#"{"id":1,"jsonrpc":"2.0","error":null,"result":{"status":"OK"}}"#;
 
     thread::spawn(move || {
-        stratum::parse_line_dispatch_result(line, &tx, &miner_id_mutex);
+        stratum::parse_line_dispatch_result(line, &tx, &miner_id_mutex, &pending()).unwrap();
     });
 
-    let result = rx.recv().unwrap();
+    let result = rx.recv().unwrap().action;
     assert_eq!(stratum::StratumAction::Ok, result);
 }
 
@@ -252,19 +294,827 @@ fn test_parse_line_dispatch_keepalive() {
     let line = r#"{"id":1,"jsonrpc":"2.0","error":null,"result":{"status":"KEEPALIVED"}}"#;
 
     thread::spawn(move || {
-        stratum::parse_line_dispatch_result(line, &tx, &miner_id_mutex);
+        stratum::parse_line_dispatch_result(line, &tx, &miner_id_mutex, &pending()).unwrap();
     });
 
-    let result = rx.recv().unwrap();
+    let result = rx.recv().unwrap().action;
     assert_eq!(stratum::StratumAction::KeepAliveOk, result);
 }
 
 #[test]
 fn test_start_tick_thread_shutdown() {
     let (stop_tx, stop_rx) = channel();
-    let (rx, hnd) = stratum::start_tick_thread(Duration::from_secs(60), stop_rx);
+    let (rx, hnd) = stratum::start_tick_thread(Duration::from_secs(60), Duration::from_secs(10), stop_rx);
     stop_tx.send(()).expect("sending stop signal");
     let result = rx.recv().expect("stop signal");
     assert_eq!(stratum::Tick::Stop, result);
     hnd.join().expect("tick thread join");
 }
+
+#[test]
+fn test_handle_stratum_send_writes_login_and_share() {
+    let (tx, rx) = channel();
+    let pool_conf = stratum_data::PoolConfig{
+        pool_address: "localhost:3333".to_string(),
+        wallet_address: "wallet".to_string(),
+        pool_password: "x".to_string(),
+        max_shares_per_job: None,
+        rig_id: None,
+        agent: None,
+        keepalive_method: "keepalived".to_string(),
+        keepalive_interval_secs: Some(60),
+        keepalive_jitter_seconds: 0,
+        connect_timeout_secs: 30,
+        address_preference: AddressPreference::System,
+        login_retries: 3,
+        trace_file: None,
+        capture_session: None,
+        job_timeout_secs: None,
+        dry_run: false,
+        clamp_easy_targets: false,
+        tls: false,
+        tls_fingerprints: Vec::new(),
+        allowed_ips: Vec::new(),
+        solo_daemon_address: None,
+        self_select_daemon_address: None,
+        failover_pool_addresses: Vec::new(),
+        failover_max_failures: 3,
+        failover_probe_minutes: 30,
+        secondary_pool_address: None,
+        pool_arbitration: ArbitrationPolicy::WarmStandby,
+        share_queue_capacity: 64,
+        share_queue_policy: QueuePolicy::Error,
+        late_share_grace_secs: None,
+        stats_interval_secs: None,
+        min_pool_score: None,
+        offline_share_capacity: 0,
+        offline_share_max_age_secs: 60,
+        submit_options: stratum_data::SubmitOptions::default(),
+        flood_guard: FloodGuardConfig::default()
+    };
+
+    tx.send(stratum::StratumCmd::Login{}).unwrap();
+    stratum::submit_share(&tx, stratum_data::Share{
+        miner_id: "miner".to_string(),
+        job_id: "job".to_string(),
+        nonce: "00000001".to_string(),
+        hash: "hash".to_string(),
+        times: None
+    }).unwrap();
+    tx.send(stratum::StratumCmd::KeepAlive{miner_id: "miner".to_string()}).unwrap();
+    tx.send(stratum::StratumCmd::Shutdown{}).unwrap();
+
+    let mut out : Vec<u8> = Vec::new();
+    let pending = pending();
+    stratum::handle_stratum_send(&rx, &mut out, &pool_conf, &pending).unwrap();
+
+    let written = String::from_utf8(out).unwrap();
+    let lines : Vec<&str> = written.lines().collect();
+    assert_eq!(lines, vec![
+        "{\"id\":1,\"method\":\"login\",\"params\":{\"login\":\"wallet\",\"pass\":\"x\",\"algo\":[\"rx/0\",\"cn/r\",\"cn/2\",\"cn-heavy/0\"]}}",
+        "{\"id\":2,\"method\":\"submit\",\"params\":{\"id\":\"miner\",\"job_id\":\"job\",\"nonce\":\"00000001\",\"result\":\"hash\"}}",
+        "{\"id\":3,\"method\":\"keepalived\",\"params\":{\"id\":\"miner\"}}"
+    ]);
+    let mut pending = pending.lock().unwrap();
+    assert_eq!(pending.len(), 3);
+    assert_eq!(pending.take(2), Some(stratum::pending::Request::Submit{job_id: "job".to_string()}));
+}
+
+#[test]
+fn test_handle_stratum_send_withholds_shares_while_backing_off() {
+    let (tx, rx) = channel();
+    let pool_conf = stratum_data::PoolConfig::new("localhost:3333", "wallet", "x");
+    let pending = Arc::new(Mutex::new(PendingRequests::with_flood_guard(FloodGuardConfig{max_rejects: 2, ..FloodGuardConfig::default()})));
+    pending.lock().unwrap().record_share_result(Some("Low difficulty share"));
+    pending.lock().unwrap().record_share_result(Some("Low difficulty share"));
+
+    stratum::submit_share(&tx, stratum_data::Share{
+        miner_id: "miner".to_string(),
+        job_id: "job".to_string(),
+        nonce: "00000001".to_string(),
+        hash: "hash".to_string(),
+        times: None
+    }).unwrap();
+    tx.send(stratum::StratumCmd::KeepAlive{miner_id: "miner".to_string()}).unwrap();
+    tx.send(stratum::StratumCmd::Shutdown{}).unwrap();
+
+    let mut out : Vec<u8> = Vec::new();
+    stratum::handle_stratum_send(&rx, &mut out, &pool_conf, &pending).unwrap();
+
+    let written = String::from_utf8(out).unwrap();
+    assert_eq!(written.lines().collect::<Vec<&str>>(), vec!["{\"id\":1,\"method\":\"keepalived\",\"params\":{\"id\":\"miner\"}}"]);
+    assert_eq!(pending.lock().unwrap().withheld_shares(), 1);
+}
+
+#[test]
+fn test_send_state_reports_withheld_shares() {
+    let (events_tx, events_rx) = channel();
+    let pool_conf = stratum_data::PoolConfig::new("localhost:3333", "wallet", "x");
+    let queue = Arc::new(ShareQueue::new(8, QueuePolicy::Error));
+    let pending = Arc::new(Mutex::new(PendingRequests::with_flood_guard(FloodGuardConfig{max_rejects: 1, ..FloodGuardConfig::default()})));
+    pending.lock().unwrap().record_share_result(Some("Low difficulty share"));
+
+    let mut state = stratum::SendState::with_queue(&pool_conf, queue, events_tx);
+    let mut out : Vec<u8> = Vec::new();
+    assert!(state.write_command(&mut out, stratum::StratumCmd::SubmitShare{share: share("job")}, &pool_conf, &pending).unwrap());
+
+    assert!(out.is_empty());
+    assert_eq!(events_rx.try_recv().unwrap().action, stratum::StratumAction::ShareWithheld{job_id: "job".to_string()});
+    assert!(events_rx.try_recv().is_err());
+}
+
+#[test]
+fn test_send_state_reports_unhealthy_pool_that_never_answers() {
+    let (events_tx, events_rx) = channel();
+    let pool_conf = stratum_data::PoolConfig::new("localhost:3333", "wallet", "x");
+    let queue = Arc::new(ShareQueue::new(8, QueuePolicy::Error));
+    let config = FloodGuardConfig{max_rejects: 1, max_errors: 0, backoff_secs: 0, max_backoff_secs: 0, unhealthy_after: 2};
+    let pending = Arc::new(Mutex::new(PendingRequests::with_flood_guard(config)));
+    pending.lock().unwrap().record_share_result(Some("Low difficulty share"));
+
+    let mut state = stratum::SendState::with_queue(&pool_conf, queue, events_tx);
+    let mut out : Vec<u8> = Vec::new();
+    //the probe share is sent, the pool never responds to it
+    assert!(state.write_command(&mut out, stratum::StratumCmd::SubmitShare{share: share("job")}, &pool_conf, &pending).unwrap());
+
+    assert_eq!(String::from_utf8(out).unwrap().lines().count(), 1);
+    match events_rx.try_recv().unwrap().action {
+        stratum::StratumAction::PoolUnhealthy{..} => assert!(true),
+        other => assert!(false, "Wrong action sent: {:?}", other)
+    }
+    assert!(events_rx.try_recv().is_err());
+}
+
+#[test]
+fn test_handle_stratum_receive_dispatches_until_eof() {
+    let (tx, rx) = channel();
+    let miner_id_mutex = Arc::new(Mutex::new(Option::Some("test_miner_id".to_string())));
+
+    let input = "{\"id\":1,\"jsonrpc\":\"2.0\",\"error\":null,\"result\":{\"status\":\"KEEPALIVED\"}}\n\
+                 this is not json\n";
+    let result = stratum::handle_stratum_receive(Cursor::new(input.as_bytes()), &tx, &channel().0, &miner_id_mutex, &pending());
+
+    assert!(result.is_err(), "EOF should be reported as error");
+    assert_eq!(rx.recv().unwrap().action, stratum::StratumAction::KeepAliveOk);
+    match rx.recv().unwrap().action {
+        stratum::StratumAction::Error{..} => assert!(true),
+        other => assert!(false, "Wrong result returned: {:?}", other)
+    }
+}
+
+#[test]
+fn test_handle_stratum_receive_drops_oversized_line() {
+    let (tx, rx) = channel();
+    let miner_id_mutex = Arc::new(Mutex::new(Option::Some("test_miner_id".to_string())));
+
+    let mut input = vec![b'x'; stratum::MAX_LINE_LENGTH * 2];
+    input.push(b'\n');
+    input.extend_from_slice(br#"{"id":1,"jsonrpc":"2.0","error":null,"result":{"status":"KEEPALIVED"}}"#);
+    input.push(b'\n');
+
+    let result = stratum::handle_stratum_receive(Cursor::new(input), &tx, &channel().0, &miner_id_mutex, &pending());
+
+    assert!(result.is_err(), "EOF should be reported as error");
+    match rx.recv().unwrap().action {
+        stratum::StratumAction::Error{err} => assert!(err.contains("dropped"), "unexpected error {}", err),
+        other => assert!(false, "Wrong result returned: {:?}", other)
+    }
+    assert_eq!(rx.recv().unwrap().action, stratum::StratumAction::KeepAliveOk);
+}
+
+#[test]
+fn test_handle_stratum_receive_drops_invalid_utf8() {
+    let (tx, rx) = channel();
+    let miner_id_mutex = Arc::new(Mutex::new(Option::Some("test_miner_id".to_string())));
+
+    let mut input : Vec<u8> = vec![0xff, 0xfe, 0x00, 0x80, b'\n'];
+    input.extend_from_slice(br#"{"id":1,"jsonrpc":"2.0","error":null,"result":{"status":"KEEPALIVED"}}"#);
+    input.push(b'\n');
+
+    let result = stratum::handle_stratum_receive(Cursor::new(input), &tx, &channel().0, &miner_id_mutex, &pending());
+
+    assert!(result.is_err(), "EOF should be reported as error");
+    match rx.recv().unwrap().action {
+        stratum::StratumAction::Error{err} => assert!(err.contains("invalid UTF-8"), "unexpected error {}", err),
+        other => assert!(false, "Wrong result returned: {:?}", other)
+    }
+    assert_eq!(rx.recv().unwrap().action, stratum::StratumAction::KeepAliveOk);
+}
+
+#[test]
+fn test_tls_host_name() {
+    assert_eq!(stratum::tls::host_name("pool.example.com:443"), "pool.example.com");
+    assert_eq!(stratum::tls::host_name("pool.example.com"), "pool.example.com");
+    assert_eq!(stratum::tls::host_name("[::1]:3333"), "::1");
+    assert_eq!(stratum::tls::host_name("[2001:db8::1]"), "2001:db8::1");
+    assert_eq!(stratum::tls::host_name("2001:db8::1"), "2001:db8::1");
+    assert_eq!(stratum::tls::host_name("127.0.0.1:3333"), "127.0.0.1");
+}
+
+#[test]
+fn test_parse_line_dispatch_submit_result_by_id() {
+    let (tx, rx) = channel();
+    let miner_id_mutex = Arc::new(Mutex::new(Option::Some("test_miner_id".to_string())));
+    let pending = pending();
+    let accepted_id = pending.lock().unwrap().register(Request::Submit{job_id: "job_a".to_string()});
+    let rejected_id = pending.lock().unwrap().register(Request::Submit{job_id: "job_b".to_string()});
+
+    let accepted = format!(r#"{{"id":{},"jsonrpc":"2.0","error":null,"result":{{"status":"OK"}}}}"#, accepted_id);
+    let rejected = format!(r#"{{"id":{},"jsonrpc":"2.0","error":{{"code":-1,"message":"Low difficulty share"}}}}"#, rejected_id);
+    stratum::parse_line_dispatch_result(&accepted, &tx, &miner_id_mutex, &pending).unwrap();
+    stratum::parse_line_dispatch_result(&rejected, &tx, &miner_id_mutex, &pending).unwrap();
+
+    assert_eq!(rx.recv().unwrap().action, stratum::StratumAction::ShareAccepted{job_id: "job_a".to_string()});
+    assert_eq!(rx.recv().unwrap().action, stratum::StratumAction::ShareRejected{
+        job_id: "job_b".to_string(),
+        reason: "Low difficulty share".to_string(),
+        code: -1
+    });
+    assert!(pending.lock().unwrap().is_empty());
+}
+
+#[test]
+fn test_parse_line_dispatch_keepalive_ok_by_id() {
+    let (tx, rx) = channel();
+    let miner_id_mutex = Arc::new(Mutex::new(Option::Some("test_miner_id".to_string())));
+    let pending = pending();
+    let id = pending.lock().unwrap().register(Request::KeepAlive);
+
+    //some pools answer keep alives with a plain OK status
+    let line = format!(r#"{{"id":{},"jsonrpc":"2.0","error":null,"result":{{"status":"OK"}}}}"#, id);
+    stratum::parse_line_dispatch_result(&line, &tx, &miner_id_mutex, &pending).unwrap();
+
+    assert_eq!(rx.recv().unwrap().action, stratum::StratumAction::KeepAliveOk);
+}
+
+fn share(job_id: &str) -> stratum_data::Share {
+    stratum_data::Share{
+        miner_id: "miner".to_string(),
+        job_id: job_id.to_string(),
+        nonce: "00000001".to_string(),
+        hash: "f12b181f2b5a84d8fca047206c605f20b6b3a9b29da3505152caaeee758e39fe".to_string(),
+        times: None
+    }
+}
+
+#[test]
+fn test_validate_share() {
+    assert_eq!(stratum::validate_share(&share("job"), None), Ok(()));
+    //the hash has difficulty 1
+    assert_eq!(stratum::validate_share(&share("job"), Some("ffffffff")), Ok(()));
+    assert!(stratum::validate_share(&share("job"), Some("169f0200")).is_err());
+    assert!(stratum::validate_share(&share("job"), Some("xyz")).is_err());
+
+    let mut invalid = share("");
+    assert!(stratum::validate_share(&invalid, None).is_err());
+    invalid = share("job");
+    invalid.nonce = "0001".to_string();
+    assert!(stratum::validate_share(&invalid, None).is_err());
+    invalid = share("job");
+    invalid.hash = "xyz".to_string();
+    assert!(stratum::validate_share(&invalid, None).is_err());
+}
+
+#[test]
+fn test_handle_stratum_send_dry_run() {
+    let (tx, rx) = channel();
+    let mut pool_conf = default_pool_conf();
+    pool_conf.dry_run = true;
+
+    stratum::submit_share(&tx, share("job_a")).unwrap();
+    tx.send(stratum::StratumCmd::SetDryRun{enabled: false}).unwrap();
+    stratum::submit_share(&tx, share("job_b")).unwrap();
+    tx.send(stratum::StratumCmd::Shutdown{}).unwrap();
+
+    let mut out : Vec<u8> = Vec::new();
+    stratum::handle_stratum_send(&rx, &mut out, &pool_conf, &pending()).unwrap();
+
+    let written = String::from_utf8(out).unwrap();
+    let lines : Vec<&str> = written.lines().collect();
+    assert_eq!(lines.len(), 1);
+    assert!(lines[0].contains("\"job_id\":\"job_b\""));
+}
+
+#[test]
+fn test_handle_stratum_send_drops_duplicate_share() {
+    let (tx, rx) = channel();
+    let pool_conf = default_pool_conf();
+
+    stratum::submit_share(&tx, share("job_a")).unwrap();
+    stratum::submit_share(&tx, share("job_a")).unwrap();
+    stratum::submit_share(&tx, share("job_b")).unwrap();
+    tx.send(stratum::StratumCmd::Shutdown{}).unwrap();
+
+    let mut out : Vec<u8> = Vec::new();
+    stratum::handle_stratum_send(&rx, &mut out, &pool_conf, &pending()).unwrap();
+
+    let written = String::from_utf8(out).unwrap();
+    let lines : Vec<&str> = written.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].contains("\"job_id\":\"job_a\""));
+    assert!(lines[1].contains("\"job_id\":\"job_b\""));
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_socket_stats_of_local_connection() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let stream = std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let stats = stratum::socket_stats::socket_stats(&stream).expect("socket stats");
+    assert_eq!(stats.total_retransmits, 0);
+}
+
+#[test]
+fn test_handle_stratum_receive_ends_on_rejected_login() {
+    let (tx, rx) = channel();
+    let miner_id_mutex = Arc::new(Mutex::new(Option::None));
+
+    let input = "{\"id\":1,\"jsonrpc\":\"2.0\",\"error\":null,\"result\":{\"id\":\"m\",\"job\":{\"blob\":\"00\",\"job_id\":\"j\",\"target\":\"169f0200\"},\"status\":\"BANNED\"}}\n\
+                 {\"id\":2,\"jsonrpc\":\"2.0\",\"error\":null,\"result\":{\"status\":\"KEEPALIVED\"}}\n";
+    let result = stratum::handle_stratum_receive(Cursor::new(input.as_bytes()), &tx, &channel().0, &miner_id_mutex, &pending());
+
+    match result {
+        Err(stratum::StratumError::LoginRejected(status)) => assert_eq!(status, "BANNED"),
+        other => assert!(false, "Wrong result returned: {:?}", other)
+    }
+    assert_eq!(rx.recv().unwrap().action, stratum::StratumAction::LoginRejected{reason: "BANNED".to_string()});
+    assert!(rx.try_recv().is_err(), "no line after the rejected login should be dispatched");
+}
+
+#[test]
+fn test_handle_stratum_receive_ends_on_login_error_response() {
+    let (tx, rx) = channel();
+    let miner_id_mutex = Arc::new(Mutex::new(Option::None));
+    let pending = pending();
+    let id = pending.lock().unwrap().register(Request::Login);
+
+    let input = "{\"id\":1,\"jsonrpc\":\"2.0\",\"error\":{\"code\":-1,\"message\":\"Invalid address used for login\"}}\n";
+    assert_eq!(id, 1);
+    let result = stratum::handle_stratum_receive(Cursor::new(input.as_bytes()), &tx, &channel().0, &miner_id_mutex, &pending);
+
+    match result {
+        Err(stratum::StratumError::LoginRejected(reason)) => assert_eq!(reason, "Invalid address used for login"),
+        other => assert!(false, "Wrong result returned: {:?}", other)
+    }
+    assert_eq!(rx.recv().unwrap().action, stratum::StratumAction::LoginRejected{reason: "Invalid address used for login".to_string()});
+}
+
+#[test]
+fn test_parse_line_dispatch_job_with_invalid_target() {
+    let (tx, rx) = channel();
+    let miner_id_mutex = Arc::new(Mutex::new(Option::Some("m".to_string())));
+    let line = "{\"jsonrpc\":\"2.0\",\"method\":\"job\",\"params\":{\"blob\":\"00\",\"job_id\":\"j\",\"target\":\"00000000\"}}";
+    stratum::parse_line_dispatch_result(line, &tx, &miner_id_mutex, &pending()).unwrap();
+    match rx.recv().unwrap().action {
+        stratum::StratumAction::Error{err} => assert_eq!(err, "job j ignored, invalid target: target is zero"),
+        other => assert!(false, "Wrong result returned: {:?}", other)
+    }
+}
+
+#[test]
+fn test_parse_line_dispatch_job_with_64_bit_target() {
+    let (tx, rx) = channel();
+    let miner_id_mutex = Arc::new(Mutex::new(Option::Some("m".to_string())));
+    let line = "{\"jsonrpc\":\"2.0\",\"method\":\"job\",\"params\":{\"blob\":\"00\",\"job_id\":\"j\",\"target\":\"B88D0600A8C10100\"}}";
+    stratum::parse_line_dispatch_result(line, &tx, &miner_id_mutex, &pending()).unwrap();
+    match rx.recv().unwrap().action {
+        stratum::StratumAction::Job{target, difficulty, ..} => {
+            assert_eq!(target, "b88d0600a8c10100");
+            assert_eq!(difficulty, mithril::difficulty::target_difficulty(0x0001_c1a8_0006_8db8));
+        },
+        other => assert!(false, "Wrong result returned: {:?}", other)
+    }
+}
+
+#[test]
+fn test_parse_line_dispatch_job_accepts_easy_target() {
+    let (tx, rx) = channel();
+    let miner_id_mutex = Arc::new(Mutex::new(Option::Some("m".to_string())));
+    let line = "{\"jsonrpc\":\"2.0\",\"method\":\"job\",\"params\":{\"blob\":\"00\",\"job_id\":\"j\",\"target\":\"ffffffff\"}}";
+    stratum::parse_line_dispatch_result(line, &tx, &miner_id_mutex, &pending()).unwrap();
+    match rx.recv().unwrap().action {
+        stratum::StratumAction::Job{target, difficulty, ..} => {
+            assert_eq!(target, "ffffffff");
+            assert_eq!(difficulty, 1);
+        },
+        other => assert!(false, "Wrong result returned: {:?}", other)
+    }
+}
+
+#[test]
+fn test_is_transient() {
+    use std::io::{Error, ErrorKind};
+    assert!(stratum::is_transient(&Error::new(ErrorKind::ConnectionRefused, "refused")));
+    assert!(stratum::is_transient(&Error::new(ErrorKind::TimedOut, "timeout")));
+    assert!(!stratum::is_transient(&Error::new(ErrorKind::PermissionDenied, "not in allowed_ips")));
+    assert!(!stratum::is_transient(&Error::new(ErrorKind::InvalidInput, "unresolvable")));
+}
+
+#[test]
+fn test_handle_stratum_send_reports_closed_channel() {
+    let (tx, rx) = channel::<stratum::StratumCmd>();
+    drop(tx);
+    let mut out : Vec<u8> = Vec::new();
+    match stratum::handle_stratum_send(&rx, &mut out, &default_pool_conf(), &pending()) {
+        Err(stratum::StratumError::ChannelClosed) => assert!(true),
+        other => assert!(false, "Wrong result returned: {:?}", other)
+    }
+}
+
+#[test]
+fn test_start_tick_thread_stops_when_stop_sender_dropped() {
+    let (stop_tx, stop_rx) = channel::<()>();
+    let (rx, hnd) = stratum::start_tick_thread(Duration::from_secs(60), Duration::from_secs(0), stop_rx);
+    drop(stop_tx);
+    assert_eq!(stratum::Tick::Stop, rx.recv().expect("stop signal"));
+    hnd.join().expect("tick thread join");
+}
+
+#[test]
+fn test_stratum_client_stop_joins_all_threads() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut pool_conf = default_pool_conf();
+    pool_conf.pool_address = listener.local_addr().unwrap().to_string();
+
+    let (err_tx, err_rx) = channel();
+    let (action_tx, _action_rx) = channel();
+    let client = stratum::StratumClient::login(pool_conf, err_tx, action_tx).unwrap();
+    let (_pool_side, _) = listener.accept().unwrap();
+
+    client.stop();
+
+    assert!(err_rx.try_recv().is_err(), "stopping must not be reported as error");
+}
+
+#[test]
+fn test_dispatched_events_are_sequenced() {
+    let (tx, rx) = channel();
+    let miner_id_mutex = Arc::new(Mutex::new(Option::Some("test_miner_id".to_string())));
+    let input = "{\"id\":1,\"jsonrpc\":\"2.0\",\"error\":null,\"result\":{\"status\":\"KEEPALIVED\"}}\n\
+                 {\"id\":2,\"jsonrpc\":\"2.0\",\"error\":null,\"result\":{\"status\":\"OK\"}}\n";
+    stratum::handle_stratum_receive(Cursor::new(input.as_bytes()), &tx, &channel().0, &miner_id_mutex, &pending()).unwrap_err();
+
+    let first = rx.recv().unwrap();
+    let second = rx.recv().unwrap();
+    assert_eq!(first.action, stratum::StratumAction::KeepAliveOk);
+    assert_eq!(second.action, stratum::StratumAction::Ok);
+    assert!(second.seq > first.seq);
+    assert!(second.received >= first.received);
+}
+
+#[test]
+fn test_ser_login_with_rig_id_and_agent() {
+    let login_req = stratum_data::LoginRequest{
+        id: 1,
+        method: "login".to_string(),
+        params: stratum_data::LoginParams {
+            login: "wallet".to_string(),
+            pass: "x".to_string(),
+            algo: Vec::new(),
+            rig_id: Some("rig1".to_string()),
+            agent: Some("mithril/0.10.0".to_string()),
+            mode: None
+        }
+    };
+    let json = serde_json::to_string(&login_req).unwrap();
+    assert_eq!(json, "{\"id\":1,\"method\":\"login\",\"params\":{\"login\":\"wallet\",\"pass\":\"x\",\"rig_id\":\"rig1\",\"agent\":\"mithril/0.10.0\"}}");
+}
+
+#[test]
+fn test_job_algo_name() {
+    let job : stratum_data::Job = serde_json::from_str(r#"{"blob":"00","job_id":"j","target":"169f0200","algo":"cn/r"}"#).unwrap();
+    assert_eq!(job.algo_name(), Some("cn/r".to_string()));
+    let job : stratum_data::Job = serde_json::from_str(r#"{"blob":"00","job_id":"j","target":"169f0200","algo":"cn","variant":2}"#).unwrap();
+    assert_eq!(job.algo_name(), Some("cn/2".to_string()));
+    let job : stratum_data::Job = serde_json::from_str(r#"{"blob":"00","job_id":"j","target":"169f0200"}"#).unwrap();
+    assert_eq!(job.algo_name(), None);
+}
+
+#[test]
+fn test_parse_line_dispatch_job_with_algo() {
+    let (tx, rx) = channel();
+    let miner_id_mutex = Arc::new(Mutex::new(Option::Some("test_miner_id".to_string())));
+    let line = r#"{"jsonrpc":"2.0","method":"job","params":{"blob":"00","job_id":"j","target":"169f0200","algo":"rx/0"}}"#;
+    stratum::parse_line_dispatch_result(line, &tx, &miner_id_mutex, &pending()).unwrap();
+
+    match rx.recv().unwrap().action {
+        stratum::StratumAction::Job{algo, ..} => assert_eq!(algo, Some("rx/0".to_string())),
+        other => assert!(false, "Wrong result returned: {:?}", other)
+    }
+}
+
+#[test]
+fn test_connect_with_timeout() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let stream = stratum::connect_with_timeout(&address, Duration::from_secs(5)).unwrap();
+    assert_eq!(stream.peer_addr().unwrap(), listener.local_addr().unwrap());
+
+    assert!(stratum::connect_with_timeout("no port", Duration::from_secs(5)).is_err());
+}
+
+#[test]
+fn test_parse_line_dispatch_job_with_height_and_seed() {
+    let (tx, rx) = channel();
+    let miner_id_mutex = Arc::new(Mutex::new(Option::Some("test_miner_id".to_string())));
+    let line = r#"{"jsonrpc":"2.0","method":"job","params":{"blob":"00","job_id":"j","target":"169f0200","algo":"rx/0","height":2000000,"seed_hash":"aa","next_seed_hash":"bb"}}"#;
+    stratum::parse_line_dispatch_result(line, &tx, &miner_id_mutex, &pending()).unwrap();
+
+    match rx.recv().unwrap().action {
+        stratum::StratumAction::Job{height, seed_hash, next_seed_hash, ..} => {
+            assert_eq!(height, Some(2000000));
+            assert_eq!(seed_hash, Some("aa".to_string()));
+            assert_eq!(next_seed_hash, Some("bb".to_string()));
+        },
+        other => assert!(false, "Wrong result returned: {:?}", other)
+    }
+}
+
+#[test]
+fn test_reject_kind_from_reason() {
+    use stratum::RejectKind;
+    assert_eq!(RejectKind::from_reason("Low difficulty share"), RejectKind::LowDifficulty);
+    assert_eq!(RejectKind::from_reason("Block expired"), RejectKind::Stale);
+    assert_eq!(RejectKind::from_reason("Invalid job id"), RejectKind::Stale);
+    assert_eq!(RejectKind::from_reason("Duplicate share"), RejectKind::Duplicate);
+    assert_eq!(RejectKind::from_reason("Unauthenticated"), RejectKind::Other);
+}
+
+#[test]
+fn test_parse_line_dispatch_string_error() {
+    let (tx, rx) = channel();
+    let miner_id_mutex = Arc::new(Mutex::new(Option::Some("test_miner_id".to_string())));
+    let pending = pending();
+    let id = pending.lock().unwrap().register(Request::Submit{job_id: "job_a".to_string()});
+
+    //some pools send the error as plain message
+    let line = format!(r#"{{"id":{},"jsonrpc":"2.0","error":"Block expired"}}"#, id);
+    stratum::parse_line_dispatch_result(&line, &tx, &miner_id_mutex, &pending).unwrap();
+
+    assert_eq!(rx.recv().unwrap().action, stratum::StratumAction::ShareRejected{
+        job_id: "job_a".to_string(),
+        reason: "Block expired".to_string(),
+        code: stratum_data::UNKNOWN_ERROR_CODE
+    });
+}
+
+#[test]
+fn test_is_unauthenticated() {
+    assert!(stratum::is_unauthenticated("Unauthenticated"));
+    assert!(stratum::is_unauthenticated("Miner not authenticated"));
+    assert!(!stratum::is_unauthenticated("Low difficulty share"));
+}
+
+#[test]
+fn test_parse_line_dispatch_unauthenticated_requests_relogin_once() {
+    let (tx, rx) = channel();
+    let miner_id_mutex = Arc::new(Mutex::new(Option::Some("test_miner_id".to_string())));
+    let pending = pending();
+    let first = pending.lock().unwrap().register(Request::Submit{job_id: "job_a".to_string()});
+    let second = pending.lock().unwrap().register(Request::Submit{job_id: "job_a".to_string()});
+
+    let line = format!(r#"{{"id":{},"jsonrpc":"2.0","error":{{"code":-1,"message":"Unauthenticated"}}}}"#, first);
+    assert_eq!(stratum::parse_line_dispatch(&line, &tx, &miner_id_mutex, &pending).unwrap(), stratum::Dispatched::Relogin);
+    assert_eq!(*miner_id_mutex.lock().unwrap(), None);
+    match rx.recv().unwrap().action {
+        stratum::StratumAction::ShareRejected{job_id, ..} => assert_eq!(job_id, "job_a"),
+        other => assert!(false, "Wrong result returned: {:?}", other)
+    }
+
+    //the login is already on its way
+    let line = format!(r#"{{"id":{},"jsonrpc":"2.0","error":{{"code":-1,"message":"Unauthenticated"}}}}"#, second);
+    assert_eq!(stratum::parse_line_dispatch(&line, &tx, &miner_id_mutex, &pending).unwrap(), stratum::Dispatched::Action);
+}
+
+#[test]
+fn test_handle_stratum_receive_sends_login_on_unauthenticated() {
+    let (tx, _rx) = channel();
+    let (cmd_tx, cmd_rx) = channel();
+    let miner_id_mutex = Arc::new(Mutex::new(Option::Some("test_miner_id".to_string())));
+    let pending = pending();
+    let id = pending.lock().unwrap().register(Request::Submit{job_id: "job_a".to_string()});
+
+    let input = format!("{{\"id\":{},\"jsonrpc\":\"2.0\",\"error\":{{\"code\":-1,\"message\":\"Unauthenticated\"}}}}\n", id);
+    stratum::handle_stratum_receive(Cursor::new(input.as_bytes()), &tx, &cmd_tx, &miner_id_mutex, &pending).unwrap_err();
+
+    match cmd_rx.try_recv() {
+        Ok(stratum::StratumCmd::Login{}) => assert!(true),
+        other => assert!(false, "Wrong result returned: {:?}", other)
+    }
+}
+
+#[test]
+fn test_login_result_supports_keepalive() {
+    let job = r#"{"blob":"00","job_id":"j","target":"169f0200"}"#;
+    let parse = |extensions: &str| -> stratum_data::LoginResult {
+        serde_json::from_str(&format!(r#"{{"id":"m","job":{},"status":"OK"{}}}"#, job, extensions)).unwrap()
+    };
+    assert!(parse("").supports_keepalive());
+    assert!(parse(r#","extensions":["algo","keepalive"]"#).supports_keepalive());
+    assert!(!parse(r#","extensions":["algo"]"#).supports_keepalive());
+}
+
+#[test]
+fn test_parse_line_dispatch_self_select_job() {
+    let (tx, rx) = channel();
+    let miner_id_mutex = Arc::new(Mutex::new(Option::Some("test_miner_id".to_string())));
+    let line = r#"{"jsonrpc":"2.0","method":"job","params":{"job_id":"j","target":"169f0200","extra_nonce":"0a0b","pool_wallet":"wallet"}}"#;
+    stratum::parse_line_dispatch_result(line, &tx, &miner_id_mutex, &pending()).unwrap();
+
+    assert_eq!(rx.recv().unwrap().action, stratum::StratumAction::SelectTemplate{
+        miner_id: "test_miner_id".to_string(),
+        job_id: "j".to_string(),
+        target: "169f0200".to_string(),
+        extra_nonce: "0a0b".to_string(),
+        pool_wallet: "wallet".to_string()
+    });
+}
+
+#[test]
+fn test_self_select_template_job() {
+    let template = mithril::solo::Template{
+        job_id: "42-0".to_string(),
+        prev_hash: "ab".to_string(),
+        height: 42,
+        difficulty: 1000,
+        blocktemplate_blob: "0a0b0c".to_string(),
+        blockhashing_blob: "0a0b".to_string(),
+        seed_hash: Some("cd".to_string()),
+        next_seed_hash: None
+    };
+    let (params, job) = stratum::self_select::template_job("miner".to_string(), "j".to_string(), "169f0200".to_string(), template);
+
+    match job {
+        stratum::StratumAction::Job{blob, job_id, target, difficulty, height, ..} => {
+            assert_eq!(blob, "0a0b");
+            assert_eq!(job_id, "j");
+            assert_eq!(target, "169f0200");
+            assert_eq!(difficulty, 25000);
+            assert_eq!(height, Some(42));
+        },
+        other => assert!(false, "Wrong action returned: {:?}", other)
+    }
+
+    let (tx, rx) = channel();
+    tx.send(stratum::StratumCmd::BlockTemplate{params}).unwrap();
+    tx.send(stratum::StratumCmd::Shutdown{}).unwrap();
+    let mut out : Vec<u8> = Vec::new();
+    stratum::handle_stratum_send(&rx, &mut out, &default_pool_conf(), &pending()).unwrap();
+
+    assert_eq!(String::from_utf8(out).unwrap().trim(), "{\"id\":1,\"method\":\"block_template\",\"params\":{\"id\":\"miner\",\"job_id\":\"j\",\
+        \"blob\":\"0a0b0c\",\"height\":42,\"difficulty\":1000,\"prev_hash\":\"ab\",\"seed_hash\":\"cd\"}}");
+}
+
+#[test]
+fn test_parse_line_dispatch_set_extranonce() {
+    let (tx, rx) = channel();
+    let miner_id_mutex = Arc::new(Mutex::new(Option::Some("test_miner_id".to_string())));
+    let line = r#"{"jsonrpc":"2.0","method":"set_extranonce","params":{"extra_nonce":"a1b2"}}"#;
+    stratum::parse_line_dispatch_result(line, &tx, &miner_id_mutex, &pending()).unwrap();
+    assert_eq!(rx.recv().unwrap().action, stratum::StratumAction::ExtranonceSet{nonce_prefix: vec![0xa1, 0xb2]});
+
+    let too_long = r#"{"jsonrpc":"2.0","method":"set_extranonce","params":{"extra_nonce":"a1b2c3d4"}}"#;
+    stratum::parse_line_dispatch_result(too_long, &tx, &miner_id_mutex, &pending()).unwrap();
+    match rx.recv().unwrap().action {
+        stratum::StratumAction::Error{..} => {},
+        other => assert!(false, "Wrong result returned: {:?}", other)
+    }
+}
+
+#[test]
+fn test_parse_line_dispatch_cancel_job() {
+    let (tx, rx) = channel();
+    let miner_id_mutex = Arc::new(Mutex::new(Option::Some("test_miner_id".to_string())));
+    let line = r#"{"jsonrpc":"2.0","method":"cancel_job","params":{"job_id":"job1"}}"#;
+    stratum::parse_line_dispatch_result(line, &tx, &miner_id_mutex, &pending()).unwrap();
+    assert_eq!(rx.recv().unwrap().action, stratum::StratumAction::JobCancelled{job_id: Some("job1".to_string())});
+
+    let all = r#"{"jsonrpc":"2.0","method":"cancel_job"}"#;
+    stratum::parse_line_dispatch_result(all, &tx, &miner_id_mutex, &pending()).unwrap();
+    assert_eq!(rx.recv().unwrap().action, stratum::StratumAction::JobCancelled{job_id: None});
+}
+
+#[test]
+fn test_handle_stratum_send_drops_shares_of_cancelled_job() {
+    let (tx, rx) = channel();
+    let pool_conf = default_pool_conf();
+
+    tx.send(stratum::StratumCmd::CancelJob{job_id: "job_a".to_string()}).unwrap();
+    stratum::submit_share(&tx, share("job_a")).unwrap();
+    stratum::submit_share(&tx, share("job_b")).unwrap();
+    tx.send(stratum::StratumCmd::Shutdown{}).unwrap();
+
+    let mut out : Vec<u8> = Vec::new();
+    stratum::handle_stratum_send(&rx, &mut out, &pool_conf, &pending()).unwrap();
+
+    let written = String::from_utf8(out).unwrap();
+    let lines : Vec<&str> = written.lines().collect();
+    assert_eq!(lines.len(), 1);
+    assert!(lines[0].contains("\"job_id\":\"job_b\""));
+}
+
+#[test]
+fn test_handle_stratum_send_drops_queued_shares_of_job_cancelled_later() {
+    let (tx, rx) = channel();
+    let (action_tx, action_rx) = channel();
+    let pool_conf = default_pool_conf();
+    let miner_id_mutex = Arc::new(Mutex::new(Option::Some("test_miner_id".to_string())));
+    let pending = pending();
+
+    //the share is waiting in the channel when the pool cancels its job
+    stratum::submit_share(&tx, share("job_a")).unwrap();
+    stratum::submit_share(&tx, share("job_b")).unwrap();
+    let cancel = r#"{"jsonrpc":"2.0","method":"cancel_job","params":{"job_id":"job_a"}}"#;
+    stratum::parse_line_dispatch_result(cancel, &action_tx, &miner_id_mutex, &pending).unwrap();
+    assert_eq!(action_rx.recv().unwrap().action, stratum::StratumAction::JobCancelled{job_id: Some("job_a".to_string())});
+    tx.send(stratum::StratumCmd::Shutdown{}).unwrap();
+
+    let mut out : Vec<u8> = Vec::new();
+    stratum::handle_stratum_send(&rx, &mut out, &pool_conf, &pending).unwrap();
+
+    let written = String::from_utf8(out).unwrap();
+    let lines : Vec<&str> = written.lines().collect();
+    assert_eq!(lines.len(), 1);
+    assert!(lines[0].contains("\"job_id\":\"job_b\""));
+}
+
+#[test]
+fn test_cancel_job_without_id_cancels_the_current_job() {
+    let mut pending = PendingRequests::new();
+    assert_eq!(pending.cancel_job(None), None);
+    pending.job_received("job_a");
+    assert_eq!(pending.cancel_job(None), Some("job_a".to_string()));
+    assert!(pending.is_cancelled("job_a"));
+    assert_eq!(pending.cancel_job(None), None);
+    pending.job_received("job_a");
+    assert!(!pending.is_cancelled("job_a"));
+}
+
+#[test]
+fn test_handle_stratum_send_late_share_grace_window() {
+    let (tx, rx) = channel();
+    let mut pool_conf = default_pool_conf();
+    pool_conf.late_share_grace_secs = Some(60);
+
+    tx.send(stratum::StratumCmd::JobReceived{job_id: "job_a".to_string(), target: "169f0200".to_string()}).unwrap();
+    tx.send(stratum::StratumCmd::JobReceived{job_id: "job_b".to_string(), target: "169f0200".to_string()}).unwrap();
+    tx.send(stratum::StratumCmd::JobReceived{job_id: "job_c".to_string(), target: "169f0200".to_string()}).unwrap();
+    stratum::submit_share(&tx, share("job_a")).unwrap(); //older than the previous job
+    stratum::submit_share(&tx, share("job_b")).unwrap(); //previous job, within the grace window
+    stratum::submit_share(&tx, share("job_c")).unwrap();
+    tx.send(stratum::StratumCmd::Shutdown{}).unwrap();
+
+    let mut out : Vec<u8> = Vec::new();
+    stratum::handle_stratum_send(&rx, &mut out, &pool_conf, &pending()).unwrap();
+
+    let written = String::from_utf8(out).unwrap();
+    let lines : Vec<&str> = written.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].contains("\"job_id\":\"job_b\""));
+    assert!(lines[1].contains("\"job_id\":\"job_c\""));
+}
+
+#[test]
+fn test_handle_stratum_receive_answers_pool_requests() {
+    let (tx, rx) = channel();
+    let (cmd_tx, cmd_rx) = channel();
+    let miner_id_mutex = Arc::new(Mutex::new(Option::None));
+
+    let input = "{\"id\":7,\"jsonrpc\":\"2.0\",\"method\":\"ping\"}\n{\"id\":\"v\",\"method\":\"client.get_version\",\"params\":[]}\n";
+    stratum::handle_stratum_receive(Cursor::new(input.as_bytes()), &tx, &cmd_tx, &miner_id_mutex, &pending()).unwrap_err();
+
+    match cmd_rx.try_recv() {
+        Ok(stratum::StratumCmd::Respond{id, result}) => {
+            assert_eq!(id, json!(7));
+            assert_eq!(result.unwrap(), json!("pong"));
+        },
+        other => assert!(false, "Wrong result returned: {:?}", other)
+    }
+    match cmd_rx.try_recv() {
+        Ok(stratum::StratumCmd::Respond{id, result}) => {
+            assert_eq!(id, json!("v"));
+            assert_eq!(result.unwrap(), json!(stratum_data::default_agent()));
+        },
+        other => assert!(false, "Wrong result returned: {:?}", other)
+    }
+    //requests are not dispatched as actions
+    assert!(rx.try_recv().is_err());
+}
+
+#[test]
+fn test_handle_stratum_send_writes_responses() {
+    let (tx, rx) = channel();
+    tx.send(stratum::StratumCmd::Respond{id: json!(7), result: Ok(json!("pong"))}).unwrap();
+    tx.send(stratum::StratumCmd::Respond{id: json!(8), result: Err(stratum::rpc::RpcError{code: -32601, message: "method not found: x".to_string()})}).unwrap();
+    tx.send(stratum::StratumCmd::Shutdown{}).unwrap();
+
+    let mut out : Vec<u8> = Vec::new();
+    stratum::handle_stratum_send(&rx, &mut out, &default_pool_conf(), &pending()).unwrap();
+
+    let written = String::from_utf8(out).unwrap();
+    let lines : Vec<&str> = written.lines().collect();
+    assert_eq!(lines, vec![
+        r#"{"id":7,"jsonrpc":"2.0","result":"pong","error":null}"#,
+        r#"{"id":8,"jsonrpc":"2.0","result":null,"error":{"code":-32601,"message":"method not found: x"}}"#
+    ]);
+}
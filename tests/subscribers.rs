@@ -0,0 +1,58 @@
+#![cfg(feature = "stratum")]
+
+extern crate mithril;
+
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel};
+use std::time::{Duration};
+
+use mithril::stratum::{StratumAction, StratumEvent};
+use mithril::stratum::subscribers;
+use mithril::stratum::subscribers::{Subscribers};
+
+#[test]
+fn test_dispatch_to_channel_and_callback() {
+    let mut subscribers = Subscribers::new();
+    let rx = subscribers.subscribe();
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let received_cb = received.clone();
+    subscribers.subscribe_fn(move |action| received_cb.lock().unwrap().push(action));
+
+    subscribers.dispatch(&StratumAction::Ok);
+
+    assert_eq!(rx.recv().unwrap(), StratumAction::Ok);
+    assert_eq!(*received.lock().unwrap(), vec![StratumAction::Ok]);
+    assert_eq!(subscribers.len(), 2);
+}
+
+#[test]
+fn test_dead_subscribers_are_removed() {
+    let mut subscribers = Subscribers::new();
+    let rx = subscribers.subscribe();
+    drop(rx);
+    subscribers.subscribe_fn(|_| panic!("callback failed"));
+    let alive = subscribers.subscribe();
+
+    subscribers.dispatch(&StratumAction::KeepAliveOk);
+
+    assert_eq!(subscribers.len(), 1);
+    assert_eq!(alive.recv().unwrap(), StratumAction::KeepAliveOk);
+}
+
+#[test]
+fn test_dispatch_thread_survives_dropped_action_receiver() {
+    let (action_tx, action_rx) = channel();
+    let subs = Arc::new(Mutex::new(Subscribers::new()));
+    let (tx, hnd) = subscribers::start_dispatch_thread(action_tx, subs.clone()).unwrap();
+    drop(action_rx);
+
+    let rx = subs.lock().unwrap().subscribe();
+    tx.send(StratumEvent::new(StratumAction::Ok)).unwrap();
+    tx.send(StratumEvent::new(StratumAction::KeepAliveOk)).unwrap();
+
+    assert_eq!(rx.recv_timeout(Duration::from_secs(5)).unwrap(), StratumAction::Ok);
+    assert_eq!(rx.recv_timeout(Duration::from_secs(5)).unwrap(), StratumAction::KeepAliveOk);
+
+    drop(tx);
+    hnd.join().unwrap();
+}
@@ -0,0 +1,54 @@
+#![cfg(feature = "miner-bin")]
+
+extern crate mithril;
+
+use std::time::{Duration, Instant};
+
+use mithril::supervisor;
+use mithril::supervisor::{Decision, Supervisor, SupervisorConfig};
+
+fn conf() -> SupervisorConfig {
+    SupervisorConfig{enabled: true, max_restarts: 3, window_minutes: 10, initial_backoff_secs: 5, max_backoff_secs: 12, alert_command: None}
+}
+
+#[test]
+fn test_backoff_doubles_up_to_max() {
+    let conf = conf();
+    assert_eq!(supervisor::backoff(&conf, 1), Duration::from_secs(5));
+    assert_eq!(supervisor::backoff(&conf, 2), Duration::from_secs(10));
+    assert_eq!(supervisor::backoff(&conf, 3), Duration::from_secs(12));
+    assert_eq!(supervisor::backoff(&conf, 200), Duration::from_secs(12));
+}
+
+#[test]
+fn test_gives_up_after_max_restarts_in_window() {
+    let mut supervisor = Supervisor::new(conf());
+    let start = Instant::now();
+
+    assert_eq!(supervisor.record_failure(start), Decision::Restart(Duration::from_secs(5)));
+    assert_eq!(supervisor.record_failure(start + Duration::from_secs(60)), Decision::Restart(Duration::from_secs(10)));
+    assert_eq!(supervisor.record_failure(start + Duration::from_secs(120)), Decision::Restart(Duration::from_secs(12)));
+    assert_eq!(supervisor.restarts(), 3);
+    assert_eq!(supervisor.record_failure(start + Duration::from_secs(180)), Decision::GiveUp);
+}
+
+#[test]
+fn test_old_restarts_leave_the_window() {
+    let mut supervisor = Supervisor::new(conf());
+    let start = Instant::now();
+    for i in 0..3 {
+        supervisor.record_failure(start + Duration::from_secs(i));
+    }
+
+    //10 minutes after the first two restarts only the third one is left, the backoff shrinks again
+    let later = start + Duration::from_secs(10 * 60 + 1);
+    assert_eq!(supervisor.record_failure(later), Decision::Restart(Duration::from_secs(10)));
+    assert_eq!(supervisor.restarts(), 2);
+}
+
+#[test]
+fn test_catch_panic() {
+    assert_eq!(supervisor::catch_panic(|| 7), Ok(7));
+    assert_eq!(supervisor::catch_panic(|| -> u32 { panic!("worker died") }), Err("worker died".to_string()));
+    assert_eq!(supervisor::catch_panic(|| -> u32 { panic!("job {} lost", 3) }), Err("job 3 lost".to_string()));
+}
@@ -0,0 +1,172 @@
+#![cfg(feature = "stratum")]
+
+extern crate mithril;
+
+use std::sync::mpsc::{channel, Receiver};
+use std::time::{Duration};
+
+use mithril::stratum;
+use mithril::stratum::{StratumAction, StratumError, StratumEvent};
+use mithril::stratum::stratum_data;
+use mithril::stratum::share_stats::{ShareStats};
+use mithril::stratum::testserver::{TestServer, TestConnection, TestJob, TEST_BLOB};
+
+fn start_client(server: &TestServer) -> (stratum::StratumClient, TestConnection, Receiver<StratumEvent>, Receiver<StratumError>) {
+    let (err_tx, err_rx) = channel();
+    let (action_tx, action_rx) = channel();
+    let client = stratum::StratumClient::login(server.pool_config().unwrap(), err_tx, action_tx).unwrap();
+    let conn = server.accept().unwrap();
+    (client, conn, action_rx, err_rx)
+}
+
+fn next_action(action_rx: &Receiver<StratumEvent>) -> StratumAction {
+    action_rx.recv_timeout(Duration::from_secs(10)).unwrap().action
+}
+
+fn share(job_id: &str) -> stratum_data::Share {
+    stratum_data::Share{miner_id: "miner1".to_string(), job_id: job_id.to_string(), nonce: "00000001".to_string(), hash: "hash".to_string(), times: None}
+}
+
+fn login(conn: &mut TestConnection, action_rx: &Receiver<StratumEvent>) {
+    let request = conn.login("miner1", &TestJob::new("job1")).unwrap();
+    assert_eq!(request.params["login"], "test_wallet");
+    assert_eq!(request.params["pass"], "x");
+
+    match next_action(action_rx) {
+        StratumAction::Job{miner_id, job_id, blob, ..} => {
+            assert_eq!(miner_id, "miner1");
+            assert_eq!(job_id, "job1");
+            assert_eq!(blob, TEST_BLOB);
+        },
+        other => assert!(false, "Wrong action received: {:?}", other)
+    }
+}
+
+#[test]
+fn test_testserver_login_and_job_dispatch() {
+    let server = TestServer::bind().unwrap();
+    let (client, mut conn, action_rx, _err_rx) = start_client(&server);
+    login(&mut conn, &action_rx);
+
+    conn.send_job(&TestJob::new("job2")).unwrap();
+    match next_action(&action_rx) {
+        StratumAction::Job{miner_id, job_id, ..} => {
+            assert_eq!(miner_id, "miner1");
+            assert_eq!(job_id, "job2");
+        },
+        other => assert!(false, "Wrong action received: {:?}", other)
+    }
+
+    client.stop();
+}
+
+#[test]
+fn test_testserver_share_accepted_and_rejected() {
+    let server = TestServer::bind().unwrap();
+    let (client, mut conn, action_rx, _err_rx) = start_client(&server);
+    login(&mut conn, &action_rx);
+
+    stratum::submit_share(&client.new_cmd_channel(), share("job1")).unwrap();
+    let request = conn.accept_share().unwrap();
+    assert_eq!(request.params["job_id"], "job1");
+    assert_eq!(request.params["nonce"], "00000001");
+    assert_eq!(next_action(&action_rx), StratumAction::ShareAccepted{job_id: "job1".to_string()});
+
+    conn.send_job(&TestJob::new("job2")).unwrap();
+    next_action(&action_rx);
+    stratum::submit_share(&client.new_cmd_channel(), share("job2")).unwrap();
+    conn.reject_share(-1, "Low difficulty share").unwrap();
+    assert_eq!(next_action(&action_rx), StratumAction::ShareRejected{job_id: "job2".to_string(), reason: "Low difficulty share".to_string(), code: -1});
+    assert_eq!(client.stats(), ShareStats{submitted: 2, accepted: 1, rejected: 1, stale: 0});
+
+    client.stop();
+}
+
+#[test]
+fn test_testserver_keepalive() {
+    let server = TestServer::bind().unwrap();
+    let (client, mut conn, action_rx, _err_rx) = start_client(&server);
+    login(&mut conn, &action_rx);
+
+    client.new_cmd_channel().send(stratum::StratumCmd::KeepAlive{miner_id: "miner1".to_string()}).unwrap();
+    let request = conn.answer_keepalive().unwrap();
+    assert_eq!(request.params["id"], "miner1");
+    assert_eq!(next_action(&action_rx), StratumAction::KeepAliveOk);
+
+    client.stop();
+}
+
+#[test]
+fn test_testserver_login_rejected() {
+    let server = TestServer::bind().unwrap();
+    let (client, mut conn, action_rx, err_rx) = start_client(&server);
+
+    conn.reject_login(-1, "Invalid address used for login").unwrap();
+    assert_eq!(next_action(&action_rx), StratumAction::LoginRejected{reason: "Invalid address used for login".to_string()});
+    match err_rx.recv_timeout(Duration::from_secs(10)).unwrap() {
+        StratumError::LoginRejected(reason) => assert_eq!(reason, "Invalid address used for login"),
+        other => assert!(false, "Wrong error received: {:?}", other)
+    }
+
+    client.stop();
+}
+
+#[test]
+fn test_testserver_connection_closed_by_pool() {
+    let server = TestServer::bind().unwrap();
+    let (client, mut conn, action_rx, err_rx) = start_client(&server);
+    login(&mut conn, &action_rx);
+
+    conn.close().unwrap();
+    match err_rx.recv_timeout(Duration::from_secs(10)).unwrap() {
+        StratumError::Io(_) => {},
+        other => assert!(false, "Wrong error received: {:?}", other)
+    }
+
+    client.stop();
+}
+
+#[test]
+fn test_testserver_unmatched_error_response_is_reported() {
+    let server = TestServer::bind().unwrap();
+    let (client, mut conn, action_rx, _err_rx) = start_client(&server);
+    login(&mut conn, &action_rx);
+
+    conn.send_line("{\"id\":7,\"jsonrpc\":\"2.0\",\"error\":{\"code\":-1,\"message\":\"Unknown\"}}").unwrap();
+    match next_action(&action_rx) {
+        StratumAction::Error{err} => assert!(err.contains("Unknown"), "unexpected error {}", err),
+        other => assert!(false, "Wrong action received: {:?}", other)
+    }
+
+    client.stop();
+}
+
+#[test]
+fn test_testserver_stats_broadcast() {
+    let server = TestServer::bind().unwrap();
+    let mut pool_conf = server.pool_config().unwrap();
+    pool_conf.stats_interval_secs = Some(1);
+    let (err_tx, _err_rx) = channel();
+    let (action_tx, action_rx) = channel();
+    let client = stratum::StratumClient::login(pool_conf, err_tx, action_tx).unwrap();
+    let mut conn = server.accept().unwrap();
+    login(&mut conn, &action_rx);
+
+    stratum::submit_share(&client.new_cmd_channel(), share("job1")).unwrap();
+    conn.accept_share().unwrap();
+
+    //the ShareAccepted action and earlier broadcasts are skipped
+    loop {
+        if let StratumAction::Stats{stats, socket, ..} = next_action(&action_rx) {
+            if stats.accepted == 1 {
+                assert_eq!(stats, ShareStats{submitted: 1, accepted: 1, rejected: 0, stale: 0});
+                if cfg!(target_os = "linux") {
+                    assert!(socket.is_some());
+                }
+                break;
+            }
+        }
+    }
+
+    client.stop();
+}
@@ -0,0 +1,34 @@
+#![cfg(feature = "stratum")]
+
+extern crate mithril;
+
+use mithril::worker::throttle::{BackgroundLevel, Throttle};
+
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_background_level_from_level() {
+    assert_eq!(BackgroundLevel::from_level(0), Some(BackgroundLevel::Off));
+    assert_eq!(BackgroundLevel::from_level(1), Some(BackgroundLevel::Yield));
+    assert_eq!(BackgroundLevel::from_level(2), Some(BackgroundLevel::Light));
+    assert_eq!(BackgroundLevel::from_level(3), Some(BackgroundLevel::Heavy));
+    assert_eq!(BackgroundLevel::from_level(4), None);
+}
+
+#[test]
+fn test_sleep_time_scales_with_hash_time() {
+    let hash_time = Duration::from_millis(20);
+    assert_eq!(BackgroundLevel::Off.sleep_time(hash_time), None);
+    assert_eq!(BackgroundLevel::Yield.sleep_time(hash_time), None);
+    assert_eq!(BackgroundLevel::Light.sleep_time(hash_time), Some(Duration::from_millis(5)));
+    assert_eq!(BackgroundLevel::Heavy.sleep_time(hash_time), Some(Duration::from_millis(20)));
+}
+
+#[test]
+fn test_heavy_throttle_sleeps_as_long_as_the_hash() {
+    let mut throttle = Throttle::new(BackgroundLevel::Heavy);
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_millis(20) {} //the "hash"
+    throttle.pause();
+    assert!(start.elapsed() >= Duration::from_millis(40));
+}
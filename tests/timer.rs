@@ -1,8 +1,12 @@
+#![cfg(feature = "miner-bin")]
+
 extern crate mithril;
 
 use mithril::timer;
 use mithril::worker::worker_pool::{WorkerConfig};
 use mithril::mithril_config::{DonationConfig};
+use mithril::worker::nonce::{NonceStrategyKind};
+use mithril::worker::throttle::{BackgroundLevel};
 
 #[test]
 fn test_interval_mod_setup_donation_disabled_auto_tune_enabled() {
@@ -10,11 +14,16 @@ fn test_interval_mod_setup_donation_disabled_auto_tune_enabled() {
         auto_tune: true,
         auto_tune_interval_minutes: 15,
         auto_tune_log: "/log/file".to_string(),
-        num_threads: 8
-    };
-    let donation_conf = DonationConfig{
-        percentage: 0.0
+        num_threads: 8,
+        max_job_age_seconds: None,
+        warmup_seconds: None,
+        nonce_strategy: NonceStrategyKind::Sequential,
+        deterministic_seed: None,
+        background_level: BackgroundLevel::Off,
+        randomx_full_dataset: false,
+        algorithm_profiles: Vec::new()
     };
+    let donation_conf = DonationConfig::new(0.0);
 
     let (interval, donation_mod) = timer::interval_mod_setup(&worker_conf, &donation_conf);
     assert_eq!(interval, 60 * 15);
@@ -27,11 +36,16 @@ fn test_interval_mod_setup_donation_below_threshold_auto_tune_enabled() {
         auto_tune: true,
         auto_tune_interval_minutes: 15,
         auto_tune_log: "/log/file".to_string(),
-        num_threads: 8
-    };
-    let donation_conf = DonationConfig{
-        percentage: 1.0/10.0 - std::f64::EPSILON
+        num_threads: 8,
+        max_job_age_seconds: None,
+        warmup_seconds: None,
+        nonce_strategy: NonceStrategyKind::Sequential,
+        deterministic_seed: None,
+        background_level: BackgroundLevel::Off,
+        randomx_full_dataset: false,
+        algorithm_profiles: Vec::new()
     };
+    let donation_conf = DonationConfig::new(1.0/10.0 - std::f64::EPSILON);
 
     let (interval, donation_mod) = timer::interval_mod_setup(&worker_conf, &donation_conf);
     assert_eq!(interval, 60 * 15);
@@ -44,11 +58,16 @@ fn test_interval_mod_setup_donation_disabled_auto_tune_disabled() {
         auto_tune: false,
         auto_tune_interval_minutes: 15,
         auto_tune_log: "/log/file".to_string(),
-        num_threads: 8
-    };
-    let donation_conf = DonationConfig{
-        percentage: 0.0
+        num_threads: 8,
+        max_job_age_seconds: None,
+        warmup_seconds: None,
+        nonce_strategy: NonceStrategyKind::Sequential,
+        deterministic_seed: None,
+        background_level: BackgroundLevel::Off,
+        randomx_full_dataset: false,
+        algorithm_profiles: Vec::new()
     };
+    let donation_conf = DonationConfig::new(0.0);
 
     let (interval, donation_mod) = timer::interval_mod_setup(&worker_conf, &donation_conf);
     assert_eq!(interval, std::u64::MAX);
@@ -61,11 +80,16 @@ fn test_interval_mod_setup_donation_enabled_auto_tune_disabled() {
         auto_tune: false,
         auto_tune_interval_minutes: 15,
         auto_tune_log: "/log/file".to_string(),
-        num_threads: 8
-    };
-    let donation_conf = DonationConfig{
-        percentage: 2.5
+        num_threads: 8,
+        max_job_age_seconds: None,
+        warmup_seconds: None,
+        nonce_strategy: NonceStrategyKind::Sequential,
+        deterministic_seed: None,
+        background_level: BackgroundLevel::Off,
+        randomx_full_dataset: false,
+        algorithm_profiles: Vec::new()
     };
+    let donation_conf = DonationConfig::new(2.5);
 
     let (interval, donation_mod) = timer::interval_mod_setup(&worker_conf, &donation_conf);
     assert_eq!(interval, 100 * 60);
@@ -78,11 +102,16 @@ fn test_interval_mod_setup_donation_enabled_auto_tune_enabled() {
         auto_tune: true,
         auto_tune_interval_minutes: 15,
         auto_tune_log: "/log/file".to_string(),
-        num_threads: 8
-    };
-    let donation_conf = DonationConfig{
-        percentage: 2.5
+        num_threads: 8,
+        max_job_age_seconds: None,
+        warmup_seconds: None,
+        nonce_strategy: NonceStrategyKind::Sequential,
+        deterministic_seed: None,
+        background_level: BackgroundLevel::Off,
+        randomx_full_dataset: false,
+        algorithm_profiles: Vec::new()
     };
+    let donation_conf = DonationConfig::new(2.5);
 
     let (interval, donation_mod) = timer::interval_mod_setup(&worker_conf, &donation_conf);
     assert_eq!(interval, 15 * 60);
@@ -95,13 +124,30 @@ fn test_interval_mod_setup_donation_100_percent_enabled_auto_tune_enabled() {
         auto_tune: true,
         auto_tune_interval_minutes: 15,
         auto_tune_log: "/log/file".to_string(),
-        num_threads: 8
-    };
-    let donation_conf = DonationConfig{
-        percentage: 100.0
+        num_threads: 8,
+        max_job_age_seconds: None,
+        warmup_seconds: None,
+        nonce_strategy: NonceStrategyKind::Sequential,
+        deterministic_seed: None,
+        background_level: BackgroundLevel::Off,
+        randomx_full_dataset: false,
+        algorithm_profiles: Vec::new()
     };
+    let donation_conf = DonationConfig::new(100.0);
 
     let (interval, donation_mod) = timer::interval_mod_setup(&worker_conf, &donation_conf);
     assert_eq!(interval, 15 * 60);
     assert_eq!(donation_mod, Some(1));
 }
+
+#[test]
+fn test_tick_action_ends_donation_with_the_next_tick() {
+    assert_eq!(timer::tick_action(1, Some(1), false), timer::TickAction::DonationHashing);
+    assert_eq!(timer::tick_action(2, Some(1), true), timer::TickAction::DonationEnd);
+    assert_eq!(timer::tick_action(3, Some(1), false), timer::TickAction::DonationHashing);
+
+    assert_eq!(timer::tick_action(6, Some(7), false), timer::TickAction::ArmChange);
+    assert_eq!(timer::tick_action(7, Some(7), false), timer::TickAction::DonationHashing);
+    assert_eq!(timer::tick_action(8, Some(7), true), timer::TickAction::DonationEnd);
+    assert_eq!(timer::tick_action(7, None, false), timer::TickAction::ArmChange);
+}
@@ -0,0 +1,161 @@
+#![cfg(feature = "stratum")]
+
+extern crate mithril;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use mithril::worker::topology;
+use mithril::worker::topology::{CoreKind, Cpu, Placement, Topology};
+
+const MB : u64 = 1_048_576;
+
+fn cpu(id: usize, core_id: usize, l3: usize, kind: CoreKind) -> Cpu {
+    Cpu{id, core: (0, core_id), l3, kind}
+}
+
+/// 2 P-cores with SMT (cpus 0-3) and 4 E-cores (cpus 4-7), one 12 MB L3
+fn hybrid() -> Topology {
+    let mut cpus = Vec::new();
+    for id in 0..4 {
+        cpus.push(cpu(id, id / 2, 0, CoreKind::Performance));
+    }
+    for id in 4..8 {
+        cpus.push(cpu(id, id * 2, 0, CoreKind::Efficiency));
+    }
+    Topology{cpus, l3_sizes: vec![12 * MB]}
+}
+
+/// 2 CCDs with 2 cores each and SMT, the siblings are numbered after all cores (0/4, 1/5, ...)
+fn two_ccds() -> Topology {
+    let cpus = (0..8).map(|id| cpu(id, id % 4, (id % 4) / 2, CoreKind::Performance)).collect();
+    Topology{cpus, l3_sizes: vec![32 * MB, 32 * MB]}
+}
+
+fn write(root: &Path, file: &str, content: &str) {
+    let path = root.join(file);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}
+
+fn sysfs(name: &str) -> PathBuf {
+    let root = env::temp_dir().join(format!("mithril-topology-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    root
+}
+
+#[test]
+fn test_parse_cpu_list() {
+    assert_eq!(topology::parse_cpu_list("0-3,8,10-11\n"), Some(vec![0, 1, 2, 3, 8, 10, 11]));
+    assert_eq!(topology::parse_cpu_list("5"), Some(vec![5]));
+    assert_eq!(topology::parse_cpu_list(""), Some(vec![]));
+    assert_eq!(topology::parse_cpu_list("3-1"), None);
+    assert_eq!(topology::parse_cpu_list("a-b"), None);
+}
+
+#[test]
+fn test_parse_cache_size() {
+    assert_eq!(topology::parse_cache_size("32768K"), Some(32 * MB));
+    assert_eq!(topology::parse_cache_size("12M\n"), Some(12 * MB));
+    assert_eq!(topology::parse_cache_size("512"), Some(512));
+    assert_eq!(topology::parse_cache_size("big"), None);
+}
+
+#[test]
+fn test_detect_hybrid() {
+    let root = sysfs("hybrid");
+    write(&root, "system/cpu/online", "0-3\n");
+    write(&root, "cpu_atom/cpus", "2-3\n");
+    for (id, core_id) in [(0, 0), (1, 0), (2, 8), (3, 9)].iter() {
+        write(&root, &format!("system/cpu/cpu{}/topology/physical_package_id", id), "0\n");
+        write(&root, &format!("system/cpu/cpu{}/topology/core_id", id), &format!("{}\n", core_id));
+        write(&root, &format!("system/cpu/cpu{}/cache/index3/shared_cpu_list", id), "0-3\n");
+        write(&root, &format!("system/cpu/cpu{}/cache/index3/size", id), "12288K\n");
+    }
+
+    let detected = topology::detect_from(&root).unwrap();
+
+    assert!(detected.is_hybrid());
+    assert_eq!(detected.l3_sizes, vec![12 * MB]);
+    assert_eq!(detected.cpus[1], cpu(1, 0, 0, CoreKind::Performance));
+    assert_eq!(detected.cpus[3], cpu(3, 9, 0, CoreKind::Efficiency));
+    assert_eq!(detected.physical_cores(CoreKind::Performance), 1);
+    assert_eq!(detected.logical_cpus(CoreKind::Performance), 2);
+    assert_eq!(detected.physical_cores(CoreKind::Efficiency), 2);
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn test_detect_l3_domains_and_missing_files() {
+    let root = sysfs("ccds");
+    write(&root, "system/cpu/online", "0-2\n");
+    write(&root, "system/cpu/cpu0/cache/index3/shared_cpu_list", "0\n");
+    write(&root, "system/cpu/cpu0/cache/index3/size", "32768K\n");
+    write(&root, "system/cpu/cpu1/cache/index3/shared_cpu_list", "1\n");
+    write(&root, "system/cpu/cpu1/cache/index3/size", "16384K\n");
+
+    let detected = topology::detect_from(&root).unwrap();
+
+    assert!(!detected.is_hybrid());
+    //cpu 2 lists no L3, its size is unknown
+    assert_eq!(detected.l3_sizes, vec![32 * MB, 16 * MB, 0]);
+    assert_eq!(detected.cpus[2], cpu(2, 2, 2, CoreKind::Performance));
+    let _ = fs::remove_dir_all(&root);
+
+    assert_eq!(topology::detect_from(&sysfs("missing")), None);
+}
+
+#[test]
+fn test_plan_prefers_performance_cores_without_siblings() {
+    let placement = topology::plan(&hybrid(), 4, false);
+    //one thread per P-core first, then the E-cores before the SMT siblings of the P-cores
+    assert_eq!(placement, Placement{cpus: vec![0, 2, 4, 5], skipped: 0});
+
+    let placement = topology::plan(&hybrid(), 7, false);
+    assert_eq!(placement.cpus, vec![0, 2, 4, 5, 6, 7, 1]);
+}
+
+#[test]
+fn test_plan_skips_efficiency_cores() {
+    let placement = topology::plan(&hybrid(), 6, true);
+    assert_eq!(placement, Placement{cpus: vec![0, 2, 1, 3], skipped: 2});
+
+    //no efficiency cores, nothing to skip: threads over the cpu count are not pinned
+    let placement = topology::plan(&two_ccds(), 10, true);
+    assert_eq!(placement.cpus.len(), 8);
+    assert_eq!(placement.skipped, 0);
+}
+
+#[test]
+fn test_plan_spreads_over_l3_caches() {
+    let ccds = two_ccds();
+    let placement = topology::plan(&ccds, 4, false);
+    //alternating CCDs, SMT siblings (4-7) last
+    assert_eq!(placement.cpus, vec![0, 2, 1, 3]);
+    assert_eq!(topology::l3_per_thread(&ccds, &placement), Some(16 * MB));
+
+    let placement = topology::plan(&ccds, 2, false);
+    assert_eq!(placement.cpus, vec![0, 2]);
+    assert_eq!(topology::l3_per_thread(&ccds, &placement), Some(32 * MB));
+}
+
+#[test]
+fn test_report() {
+    let hybrid = hybrid();
+    let report = topology::report(&hybrid, None);
+    assert!(report.contains("cpu: hybrid, 2 performance cores (4 threads), 4 efficiency cores (4 threads)"));
+    assert!(report.contains("l3 cache: 1 x (12 MB)"));
+    assert!(report.ends_with("thread affinity: none"));
+
+    let placement = topology::plan(&hybrid, 8, false);
+    let report = topology::report(&hybrid, Some(&placement));
+    assert!(report.contains("l3 cache per thread: 1.5 MB, less than a scratchpad"));
+    assert!(report.contains("efficiency cores used"));
+
+    let placement = topology::plan(&hybrid, 8, true);
+    let report = topology::report(&hybrid, Some(&placement));
+    assert!(report.contains("thread affinity: cpus 0, 2, 1, 3\n"));
+    assert!(report.contains("l3 cache per thread: 3 MB\n"));
+    assert!(report.contains("efficiency cores skipped, 4 threads not started"));
+}
@@ -0,0 +1,51 @@
+#![cfg(feature = "stratum")]
+
+extern crate mithril;
+
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Cursor, Write};
+
+use mithril::stratum::trace;
+use mithril::stratum::trace::{Tracer, TracingReader, TracingWriter};
+
+fn capture_path(name: &str) -> String {
+    let path = env::temp_dir().join(format!("mithril-trace-{}-{}", name, std::process::id()));
+    let _ = fs::remove_file(&path);
+    path.to_str().unwrap().to_string()
+}
+
+#[test]
+fn test_redact() {
+    assert_eq!(trace::redact("{\"login\":\"4wallet\",\"pass\":\"x\"}", "4wallet"), "{\"login\":\"<wallet>\",\"pass\":\"x\"}");
+    assert_eq!(trace::redact("{\"id\":1}", ""), "{\"id\":1}");
+}
+
+#[test]
+fn test_tracer_disabled_without_capture_file() {
+    //no logger is installed in the tests, TRACE is not enabled
+    assert!(Tracer::open("wallet", None).unwrap().is_none());
+}
+
+#[test]
+fn test_tracing_writer_and_reader_capture_lines() {
+    let path = capture_path("lines");
+    let tracer = Tracer::open("4wallet", Some(path.as_str())).unwrap().unwrap();
+
+    let mut out : Vec<u8> = Vec::new();
+    {
+        let mut writer = TracingWriter::new(&mut out, tracer.clone());
+        write!(writer, "{{\"login\":\"4wallet\"}}\n{{\"id\":").unwrap();
+        writeln!(writer, "2}}").unwrap();
+    }
+    assert_eq!(String::from_utf8(out).unwrap(), "{\"login\":\"4wallet\"}\n{\"id\":2}\n");
+
+    let mut reader = TracingReader::new(BufReader::new(Cursor::new("{\"status\":\"OK\"}\npartial".as_bytes())), tracer);
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    assert_eq!(line, "{\"status\":\"OK\"}\n");
+
+    let capture = fs::read_to_string(&path).unwrap();
+    let _ = fs::remove_file(&path);
+    assert_eq!(capture, "> {\"login\":\"<wallet>\"}\n> {\"id\":2}\n< {\"status\":\"OK\"}\n");
+}
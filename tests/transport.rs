@@ -0,0 +1,90 @@
+#![cfg(feature = "stratum")]
+
+extern crate mithril;
+
+use mithril::stratum;
+use mithril::stratum::stratum_data;
+use mithril::stratum::StratumAction;
+use mithril::stratum::pending::PendingRequests;
+use mithril::stratum::subscribers::Subscribers;
+use mithril::stratum::transport::{LimitedLine, MemoryTransport, StratumTransport, TransportWriter};
+
+use std::io::Write;
+use std::sync::mpsc::channel;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn default_pool_conf() -> stratum_data::PoolConfig {
+    stratum_data::PoolConfig::new("pool.example.com:3333", "wallet", "x")
+}
+
+fn pending() -> Arc<Mutex<PendingRequests>> {
+    Arc::new(Mutex::new(PendingRequests::new()))
+}
+
+#[test]
+fn test_memory_transport_reads_lines_until_eof() {
+    let transport = MemoryTransport::new("first\nsecond line that is too long\nthird");
+    assert_eq!(transport.read_line(100).unwrap(), LimitedLine::Line(b"first".to_vec()));
+    assert_eq!(transport.read_line(10).unwrap(), LimitedLine::TooLong);
+    assert_eq!(transport.read_line(100).unwrap(), LimitedLine::Line(b"third".to_vec()));
+    assert_eq!(transport.read_line(100).unwrap(), LimitedLine::Eof);
+}
+
+#[test]
+fn test_memory_transport_shutdown() {
+    let transport = MemoryTransport::new("line\n");
+    transport.shutdown().unwrap();
+    assert!(transport.is_shut_down());
+    assert_eq!(transport.read_line(100).unwrap(), LimitedLine::Eof);
+    assert!(transport.write_line("line").is_err());
+}
+
+#[test]
+fn test_transport_writer_writes_complete_lines_on_flush() {
+    let transport = MemoryTransport::new("");
+    {
+        let mut writer = TransportWriter::new(&transport);
+        write!(writer, "{{\"id\":1}}\n{{\"id\"").unwrap();
+        assert!(transport.written().is_empty());
+        writer.flush().unwrap();
+        write!(writer, ":2}}\n").unwrap();
+        writer.flush().unwrap();
+    }
+    assert_eq!(transport.written(), vec!["{\"id\":1}", "{\"id\":2}"]);
+}
+
+#[test]
+fn test_handle_transport_receive_dispatches_until_eof() {
+    let (tx, rx) = channel();
+    let miner_id = Arc::new(Mutex::new(Some("miner".to_string())));
+    let transport = MemoryTransport::new("{\"id\":1,\"jsonrpc\":\"2.0\",\"error\":null,\"result\":{\"status\":\"KEEPALIVED\"}}\n");
+
+    let result = stratum::handle_transport_receive(&transport, &tx, &channel().0, &miner_id, &pending());
+
+    assert!(result.is_err(), "EOF should be reported as error");
+    assert_eq!(rx.recv().unwrap().action, StratumAction::KeepAliveOk);
+}
+
+#[test]
+fn test_client_on_memory_transport_logs_in_and_shuts_down() {
+    let transport = Arc::new(MemoryTransport::new(""));
+    let (err_tx, _err_rx) = channel();
+    let (action_tx, _action_rx) = channel();
+    let mut pool_conf = default_pool_conf();
+    pool_conf.keepalive_interval_secs = None;
+
+    let client = stratum::StratumClient::start(transport.clone(), pool_conf, err_tx, action_tx, Subscribers::new()).unwrap();
+
+    let start = Instant::now();
+    while transport.written().is_empty() && start.elapsed() < Duration::from_secs(5) {
+        thread::sleep(Duration::from_millis(10));
+    }
+    client.stop();
+
+    let written = transport.written();
+    assert_eq!(written.len(), 1);
+    assert!(written[0].contains("\"method\":\"login\""));
+    assert!(transport.is_shut_down());
+}
@@ -53,3 +53,31 @@ fn test_write_offset() {
     assert_eq!(dst[80],0x44);
     assert_eq!(dst[81],0x33);
 }
+
+#[test]
+fn test_le_bytes_round_trip() {
+    let v = u64x2(0xFFEEDDCCBBAA1122, 0x33445566778899FF);
+    let bytes = v.to_le_bytes();
+    assert_eq!(bytes[0], 0x22);
+    assert_eq!(bytes[15], 0x33);
+    assert_eq!(u64x2::from_le_bytes(bytes), v);
+    assert_eq!(u64x2::read(&bytes), v);
+}
+
+#[test]
+fn test_swap() {
+    assert_eq!(u64x2(1, 2).swap(), u64x2(2, 1));
+    assert_eq!(u64x2(0x0102030405060708, 0x1112131415161718).swap_bytes(), u64x2(0x0807060504030201, 0x1817161514131211));
+}
+
+#[test]
+fn test_wrapping_add() {
+    assert_eq!(u64x2(u64::max_value(), 1).wrapping_add(u64x2(2, 2)), u64x2(1, 3));
+    assert_eq!(u64x2(u64::max_value(), 1).wrapping_add(u64x2(2, 2)), u64x2(u64::max_value(), 1) + u64x2(2, 2));
+}
+
+#[test]
+fn test_widening_mul_lo() {
+    assert_eq!(u64x2(5, 99).widening_mul_lo(u64x2(10, 99)), u64x2(0, 50));
+    assert_eq!(u64x2(u64::max_value(), 0).widening_mul_lo(u64x2(u64::max_value(), 0)), u64x2(u64::max_value()-1, 1));
+}
@@ -0,0 +1,168 @@
+#![cfg(feature = "miner-bin")]
+
+extern crate mithril;
+extern crate ed25519_dalek;
+extern crate sha2;
+
+use ed25519_dalek::{SecretKey, PublicKey, ExpandedSecretKey};
+use sha2::{Digest, Sha256};
+
+use std::env;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{TcpListener};
+use std::thread;
+
+use mithril::hex;
+use mithril::update;
+use mithril::update::{Release, UpdateError};
+
+const MANIFEST : &str = r#"{"version": "9.1.0", "url": "https://example.com/mithril-9.1.0", "sha256": "00"}"#;
+
+fn keys(seed: u8) -> (ExpandedSecretKey, PublicKey) {
+    let secret = SecretKey::from_bytes(&[seed; 32]).unwrap();
+    let public = PublicKey::from(&secret);
+    (ExpandedSecretKey::from(&secret), public)
+}
+
+fn sign(message: &[u8], seed: u8) -> (Vec<u8>, String) {
+    let (secret, public) = keys(seed);
+    let signature = secret.sign(message, &public);
+    (signature.to_bytes().to_vec(), hex::encode(public.as_bytes()))
+}
+
+/// Serves `body` with status 200 to one client, returns the URL
+fn serve_once(body: Vec<u8>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/mithril", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = [0; 1024];
+        let _ = stream.read(&mut request).unwrap();
+        write!(stream, "HTTP/1.0 200 OK\r\nContent-Length: {}\r\n\r\n", body.len()).unwrap();
+        stream.write_all(&body).unwrap();
+    });
+    url
+}
+
+#[test]
+fn test_is_newer_compares_version_parts_numerically() {
+    assert!(update::is_newer("0.10.0", "0.9.9"));
+    assert!(update::is_newer("v1.0.0", "0.9.9"));
+    assert!(!update::is_newer("0.9.9", "0.9.9"));
+    assert!(!update::is_newer("0.9.8", "0.9.9"));
+    assert!(!update::is_newer("1.0", "0.9.9"));
+    assert_eq!(update::parse_version("1.2.3-beta"), Some((1, 2, 3)));
+    assert_eq!(update::parse_version("1.0.0-/../../x"), None);
+    assert_eq!(update::parse_version("1.0.0-beta x"), None);
+}
+
+#[test]
+fn test_verify_manifest_accepts_raw_and_hex_signature() {
+    let (signature, public_key) = sign(MANIFEST.as_bytes(), 7);
+    let expected = Release{version: "9.1.0".to_string(), url: "https://example.com/mithril-9.1.0".to_string(), sha256: "00".to_string()};
+
+    assert_eq!(update::verify_manifest(MANIFEST.as_bytes(), &signature, &public_key).unwrap(), expected);
+    let hex_signature = format!("{}\n", hex::encode(&signature));
+    assert_eq!(update::verify_manifest(MANIFEST.as_bytes(), hex_signature.as_bytes(), &public_key).unwrap(), expected);
+}
+
+#[test]
+fn test_verify_manifest_rejects_other_key_and_modified_manifest() {
+    let (signature, _) = sign(MANIFEST.as_bytes(), 7);
+    let (_, other_key) = sign(MANIFEST.as_bytes(), 8);
+    match update::verify_manifest(MANIFEST.as_bytes(), &signature, &other_key) {
+        Err(UpdateError::Signature(_)) => {},
+        other => assert!(false, "Wrong result returned: {:?}", other)
+    }
+
+    let (signature, public_key) = sign(MANIFEST.as_bytes(), 7);
+    let modified = MANIFEST.replace("example.com", "evil.example.com");
+    match update::verify_manifest(modified.as_bytes(), &signature, &public_key) {
+        Err(UpdateError::Signature(_)) => {},
+        other => assert!(false, "Wrong result returned: {:?}", other)
+    }
+}
+
+#[test]
+fn test_verify_manifest_checks_content_only_after_signature() {
+    let manifest = br#"{"version": "latest", "url": "x", "sha256": "00"}"#;
+    let (signature, public_key) = sign(manifest, 7);
+    match update::verify_manifest(manifest, &signature, &public_key) {
+        Err(UpdateError::Manifest(_)) => {},
+        other => assert!(false, "Wrong result returned: {:?}", other)
+    }
+}
+
+#[test]
+fn test_verify_manifest_rejects_version_with_path() {
+    let manifest = br#"{"version": "1.0.0-/../../x", "url": "x", "sha256": "00"}"#;
+    let (signature, public_key) = sign(manifest, 7);
+    match update::verify_manifest(manifest, &signature, &public_key) {
+        Err(UpdateError::Manifest(_)) => {},
+        other => assert!(false, "Wrong result returned: {:?}", other)
+    }
+}
+
+#[test]
+fn test_parse_url() {
+    assert_eq!(update::parse_url("https://example.com/releases/latest.json").unwrap(),
+        (true, "example.com".to_string(), 443, "/releases/latest.json".to_string()));
+    assert_eq!(update::parse_url("http://127.0.0.1:8080").unwrap(),
+        (false, "127.0.0.1".to_string(), 8080, "/".to_string()));
+    assert!(update::parse_url("ftp://example.com/latest.json").is_err());
+}
+
+#[test]
+fn test_parse_response_returns_status_location_and_body() {
+    let (status, location, body) = update::parse_response(b"HTTP/1.1 302 Found\r\nlocation: https://cdn.example.com/a\r\n\r\nmoved").unwrap();
+    assert_eq!(status, 302);
+    assert_eq!(location, Some("https://cdn.example.com/a".to_string()));
+    assert_eq!(body, b"moved");
+}
+
+#[test]
+fn test_resolve_location() {
+    let url = "https://example.com:8443/releases/latest.json?v=1";
+    assert_eq!(update::resolve_location(url, "https://cdn.example.com/a"), "https://cdn.example.com/a");
+    assert_eq!(update::resolve_location(url, "//cdn.example.com/a"), "https://cdn.example.com/a");
+    assert_eq!(update::resolve_location(url, "/a/b.json"), "https://example.com:8443/a/b.json");
+    assert_eq!(update::resolve_location(url, "b.json"), "https://example.com:8443/releases/b.json");
+    assert_eq!(update::resolve_location("http://example.com", "b.json"), "http://example.com/b.json");
+}
+
+#[test]
+fn test_read_limited_fails_above_the_limit() {
+    assert_eq!(update::read_limited(&b"response"[..], 8).unwrap(), b"response".to_vec());
+    assert!(update::read_limited(&b"response"[..], 7).is_err());
+}
+
+#[test]
+fn test_download_writes_binary_with_matching_checksum() {
+    let binary = b"new mithril binary".to_vec();
+    let sha256 = hex::encode(&Sha256::digest(&binary));
+    let url = serve_once(binary.clone());
+    let release = Release{version: "9.1.0".to_string(), url, sha256};
+
+    let dir = env::temp_dir().join("mithril_update_download_test");
+    fs::create_dir_all(&dir).unwrap();
+    let path = update::download(&release, &dir).unwrap();
+
+    assert_eq!(path, dir.join("mithril-9.1.0"));
+    assert_eq!(fs::read(&path).unwrap(), binary);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_download_rejects_checksum_mismatch() {
+    let url = serve_once(b"tampered binary".to_vec());
+    let release = Release{version: "9.1.1".to_string(), url, sha256: hex::encode(&[0; 32])};
+
+    let dir = env::temp_dir().join("mithril_update_mismatch_test");
+    fs::create_dir_all(&dir).unwrap();
+    match update::download(&release, &dir) {
+        Err(UpdateError::Checksum{..}) => {},
+        other => assert!(false, "Wrong result returned: {:?}", other)
+    }
+    assert!(!dir.join("mithril-9.1.1").exists());
+}
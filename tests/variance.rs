@@ -0,0 +1,37 @@
+extern crate mithril;
+
+use std::time::Duration;
+
+use mithril::variance;
+use mithril::variance::{ShareEstimate};
+
+#[test]
+fn test_shares_per_hour() {
+    let estimate = ShareEstimate::new(500.0, 90_000);
+    assert_eq!(estimate.shares_per_hour(), 20.0);
+    assert_eq!(estimate.expected(Duration::from_secs(180)), 1.0);
+    assert_eq!(ShareEstimate::new(500.0, 0).shares_per_hour(), 0.0);
+}
+
+#[test]
+fn test_p_no_share() {
+    let estimate = ShareEstimate::new(500.0, 90_000);
+    assert!((estimate.p_no_share(Duration::from_secs(180)) - (-1.0f64).exp()).abs() < 1e-12);
+    assert_eq!(estimate.p_no_share(Duration::from_secs(0)), 1.0);
+}
+
+#[test]
+fn test_poisson_interval() {
+    assert_eq!(variance::poisson_interval(0.0), (0, 0));
+    assert_eq!(variance::poisson_interval(1.0), (0, 3));
+    assert_eq!(variance::poisson_interval(10.0), (4, 17));
+    let (low, high) = variance::poisson_interval(10_000.0);
+    assert_eq!((low, high), (9804, 10196));
+}
+
+#[test]
+fn test_render() {
+    let estimate = ShareEstimate::new(500.0, 90_000);
+    assert_eq!(estimate.render(0, Duration::from_secs(600)),
+        "500.0 H/s at difficulty 90k: 20.00 shares/hour expected, 0 found in the last 10 minutes (95% range 0-7, no share with probability 3.6%)");
+}
@@ -1,10 +1,25 @@
+#![cfg(feature = "stratum")]
 #![allow(unknown_lints)]
 #![allow(unreadable_literal)]
 
 extern crate mithril;
 
 use mithril::worker::worker_pool;
+use mithril::worker::worker_pool::{AlgorithmProfile, WorkerEvent, WorkerGroup, WorkerOptions};
+use mithril::worker::scratchpad::{Scratchpad};
+use mithril::worker::nonce::{NonceStrategyKind};
+use mithril::worker::throttle::{BackgroundLevel};
 use mithril::cryptonight::hash;
+use mithril::cryptonight::{HashVersion};
+use mithril::cryptonight::aes::{AESSupport};
+use mithril::metric::counters::{HashCounters};
+use mithril::metric::coverage::{NonceCoverage};
+use mithril::stratum::share_queue::{QueuePolicy, ShareQueue};
+
+use std::sync::Arc;
+use std::sync::mpsc::{channel};
+use std::thread;
+use std::time::{Duration, Instant};
 
 #[test]
 fn test_num_bits() {
@@ -18,6 +33,37 @@ fn test_num_bits() {
     assert_eq!(worker_pool::num_bits(16), 4);
 }
 
+#[test]
+fn test_start_delay() {
+    let warmup = Duration::from_secs(60);
+    assert_eq!(worker_pool::start_delay(warmup, 0, 4), Duration::from_secs(0));
+    assert_eq!(worker_pool::start_delay(warmup, 1, 4), Duration::from_secs(15));
+    assert_eq!(worker_pool::start_delay(warmup, 3, 4), Duration::from_secs(45));
+    assert_eq!(worker_pool::start_delay(warmup, 0, 0), Duration::from_secs(0));
+}
+
+#[test]
+fn test_worker_group_hashes_jobs_of_its_pool() {
+    let group = WorkerGroup{name: "e-cores".to_string(), num_threads: 4, pool: Some(1)};
+    assert!(group.hashes(Some(1)));
+    assert!(!group.hashes(Some(0)));
+    //jobs of single pool clients are for all groups
+    assert!(group.hashes(None));
+    assert!(WorkerGroup::all(8).hashes(Some(0)));
+}
+
+#[test]
+fn test_limit_groups_keeps_proportions() {
+    let groups = vec![WorkerGroup{name: "p-cores".to_string(), num_threads: 8, pool: Some(0)},
+        WorkerGroup{name: "e-cores".to_string(), num_threads: 4, pool: Some(1)}];
+    assert_eq!(worker_pool::limit_groups(&groups, 12), groups);
+
+    let limited : Vec<u64> = worker_pool::limit_groups(&groups, 6).iter().map(|g| g.num_threads).collect();
+    assert_eq!(limited, vec![4, 2]);
+    let limited : Vec<u64> = worker_pool::limit_groups(&groups, 1).iter().map(|g| g.num_threads).collect();
+    assert_eq!(limited, vec![1, 1]);
+}
+
 #[test]
 fn test_target_u64() {
     assert_eq!(worker_pool::target_u64(171798), 737869762948382);
@@ -30,3 +76,136 @@ fn test_with_nonce() {
     assert_eq!("0606cbe692d005ecfebc7d2249d2b43535c237c02359e888b8b05d2e980c1405779241ac3ab48512345678e62a06e71559c98a37e7b6743465f4f72e42784c5719411c935dc002e347826b05",
                worker_pool::with_nonce(blob, nonce));
 }
+
+#[test]
+fn test_is_retarget() {
+    let job = job_data("job1", "169f0200");
+    assert!(worker_pool::is_retarget(&job, &job_data("job1", "4b9f0200")));
+    //the same job sent again continues at the current nonce as well
+    assert!(worker_pool::is_retarget(&job, &job_data("job1", "169f0200")));
+    assert!(!worker_pool::is_retarget(&job, &job_data("job2", "4b9f0200")));
+    assert!(!worker_pool::is_retarget(&job, &worker_pool::JobData{hash_version: HashVersion::VersionR, ..job_data("job1", "4b9f0200")}));
+    assert!(!worker_pool::is_retarget(&job, &worker_pool::JobData{height: 1, ..job_data("job1", "4b9f0200")}));
+    assert!(!worker_pool::is_retarget(&job, &worker_pool::JobData{seed_hash: Some("00".to_string()), ..job_data("job1", "4b9f0200")}));
+}
+
+#[test]
+fn test_is_cancelled() {
+    let job = job_data("job1", "169f0200");
+    assert!(worker_pool::is_cancelled(&job, &Some("job1".to_string())));
+    assert!(!worker_pool::is_cancelled(&job, &Some("job2".to_string())));
+    assert!(worker_pool::is_cancelled(&job, &None));
+}
+
+fn job_data(job_id: &str, target: &str) -> worker_pool::JobData {
+    worker_pool::JobData{
+        miner_id: "miner".to_string(),
+        blob: "blob".to_string(),
+        job_id: job_id.to_string(),
+        target: target.to_string(),
+        nonce_partition: 0,
+        nonce_partition_num_bits: 1,
+        nonce_prefix: Vec::new(),
+        hash_version: HashVersion::V8,
+        height: 0,
+        seed_hash: None,
+        cpu: None,
+        received: Instant::now(),
+        pool_received: Instant::now()
+    }
+}
+
+#[test]
+fn test_allocate_scratchpads_reduces_thread_count() {
+    let mut available = 3;
+    let scratchpads = worker_pool::allocate_scratchpads(8, || {
+        if available == 0 {
+            return None;
+        }
+        available -= 1;
+        Some(vec![0u8; 16])
+    });
+    assert_eq!(scratchpads.len(), 3);
+
+    let all = worker_pool::allocate_scratchpads(4, || Some(0u8));
+    assert_eq!(all.len(), 4);
+
+    let none = worker_pool::allocate_scratchpads(4, || None::<u8>);
+    assert!(none.is_empty());
+}
+
+#[test]
+fn test_scratchpad_alloc_is_zeroed_and_writable() {
+    //huge pages if reserved on this machine, heap otherwise
+    let mut scratchpad = Scratchpad::alloc().expect("scratchpad");
+    assert!(scratchpad.iter().all(|x| x.0 == 0 && x.1 == 0));
+    scratchpad[hash::MEM_SIZE - 1].0 = 7;
+    assert_eq!(scratchpad[hash::MEM_SIZE - 1].0, 7);
+}
+
+const BLOB : &str = "06068cf792d0057f8b118fb8ee53bc32f72dcbae3e6ab44fd846995e8e145566eca098b19020f30000000104c599c5199374899d45470ffd1a381cb6d3aa186965298dbf37a37d03cea32a05";
+
+fn start_workers(num_threads: u64, max_job_age: Option<Duration>, counters: &Arc<HashCounters>) -> worker_pool::WorkerPool {
+    let (share_tx, _share_rx) = channel();
+    let share_queue = Arc::new(ShareQueue::new(16, QueuePolicy::Error));
+    let coverage = Arc::new(NonceCoverage::new());
+    let options = WorkerOptions{
+        max_job_age,
+        warmup: None,
+        nonce_strategy: NonceStrategyKind::Sequential,
+        background_level: BackgroundLevel::Off,
+        randomx_full_dataset: false
+    };
+    worker_pool::start(num_threads, AESSupport::SW, &share_tx, &share_queue, 1, counters, &coverage, options).unwrap()
+}
+
+fn start_one_worker(max_job_age: Option<Duration>, counters: &Arc<HashCounters>) -> worker_pool::WorkerPool {
+    let pool = start_workers(1, max_job_age, counters);
+    pool.job_change(None, "miner1", BLOB, "job1", "01000000", &[], HashVersion::V8, 0, None, Instant::now()).unwrap();
+    pool
+}
+
+#[test]
+fn test_expired_job_is_reported_as_event() {
+    let counters = Arc::new(HashCounters::new(1));
+    let pool = start_one_worker(Some(Duration::from_millis(1)), &counters);
+
+    match pool.events().recv_timeout(Duration::from_secs(60)).unwrap() {
+        WorkerEvent::JobExpired{job_id, thread_ix, ..} => {
+            assert_eq!(job_id, "job1");
+            assert_eq!(thread_ix, 0);
+        }
+    }
+
+    pool.stop();
+    pool.join();
+}
+
+#[test]
+fn test_dropped_pool_stops_hashing() {
+    let counters = Arc::new(HashCounters::new(1));
+    let pool = start_one_worker(None, &counters);
+    drop(pool);
+
+    //the thread finishes its current hash at most
+    thread::sleep(Duration::from_secs(2));
+    let hashes = counters.total();
+    thread::sleep(Duration::from_secs(2));
+    assert_eq!(counters.total(), hashes);
+}
+
+#[test]
+fn test_algorithm_profile_limits_the_hashing_threads() {
+    let counters = Arc::new(HashCounters::new(2));
+    let mut pool = start_workers(2, None, &counters);
+    pool.set_algorithm_profiles(vec![AlgorithmProfile{hash_version: HashVersion::V8, num_threads: Some(1), affinity: Vec::new()}]);
+    pool.job_change(None, "miner1", BLOB, "job1", "01000000", &[], HashVersion::V8, 0, None, Instant::now()).unwrap();
+
+    thread::sleep(Duration::from_secs(2));
+    let hashes = counters.snapshot().per_thread;
+    assert!(hashes[0] > 0, "the thread of the profile did not hash");
+    assert_eq!(hashes[1], 0, "a thread beyond the profile hashed");
+
+    pool.stop();
+    pool.join();
+}
@@ -0,0 +1,61 @@
+#![cfg(feature = "miner-bin")]
+
+extern crate mithril;
+
+use std::env;
+use std::fs;
+
+use mithril::mithril_config;
+use mithril::xmrig_import;
+
+const XMRIG_CONFIG : &str = r#"{
+    "api": {"id": null, "worker-id": null},
+    "http": {"enabled": true, "host": "127.0.0.1", "port": 8080},
+    "cpu": {"enabled": true, "huge-pages": true, "hw-aes": null, "rx": [0, 1, 2, 3]},
+    "donate-level": 1,
+    "pools": [
+        {"url": "stratum+ssl://pool.example.com:443", "user": "4wallet", "pass": "rig\"1", "rig-id": "rig1",
+         "keepalive": false, "enabled": true, "tls": false, "tls-fingerprint": "ab:cd"},
+        {"url": "disabled.example.com:3333", "user": "4wallet", "pass": "x", "enabled": false},
+        {"url": "stratum+tcp://backup.example.com:3333", "user": "4wallet", "pass": "rig\"1", "enabled": true}
+    ]
+}"#;
+
+#[test]
+fn test_convert_xmrig_config() {
+    let config = xmrig_import::convert(XMRIG_CONFIG).unwrap();
+    let lines : Vec<&str> = config.lines().collect();
+    assert!(lines.contains(&"# not imported: huge-pages is not a setting, mithril uses huge pages if reserved (see mithril setup-hugepages)"));
+    assert!(lines.contains(&"pool_address = \"pool.example.com:443\""));
+    assert!(lines.contains(&"pool_password = \"rig\\\"1\""));
+    assert!(lines.contains(&"tls = true"));
+    assert!(lines.contains(&"tls_fingerprints = [\"ab:cd\"]"));
+    assert!(lines.contains(&"keepalive_interval_secs = 0"));
+    assert!(lines.contains(&"failover_pool_addresses = [\"backup.example.com:3333\"]"));
+    assert!(lines.contains(&"num_threads = 4"));
+    assert!(lines.contains(&"auto_tune = false"));
+    assert!(lines.contains(&"percentage = 1.0"));
+}
+
+#[test]
+fn test_converted_config_is_readable() {
+    let path = env::temp_dir().join(format!("mithril-import-{}.toml", std::process::id()));
+    fs::write(&path, xmrig_import::convert(XMRIG_CONFIG).unwrap()).unwrap();
+    let result = mithril_config::read_config(&path, path.to_str().unwrap());
+    let _ = fs::remove_file(&path);
+
+    let config = result.unwrap();
+    assert_eq!(config.pool_conf.pool_address, "pool.example.com:443");
+    assert_eq!(config.pool_conf.wallet_address, "4wallet");
+    assert_eq!(config.pool_conf.rig_id, Some("rig1".to_string()));
+    assert_eq!(config.pool_conf.keepalive_interval_secs, None);
+    assert_eq!(config.worker_conf.num_threads, 4);
+    assert_eq!(config.donation_conf.percentage, 1.0);
+}
+
+#[test]
+fn test_convert_without_pool() {
+    assert!(xmrig_import::convert(r#"{"pools": []}"#).is_err());
+    assert!(xmrig_import::convert("not json").is_err());
+    assert!(xmrig_import::convert(r#"{"pools": [{"url": "http://pool:80"}]}"#).is_err());
+}